@@ -0,0 +1,213 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::{http::header::HeaderName, HttpRequest};
+use ipnet::IpNet;
+
+/// RFC 7239で定義された`Forwarded`ヘッダー
+const FORWARDED: &str = "forwarded";
+
+/// `X-Forwarded-For`ヘッダー
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// 信頼するプロキシのIPアドレスか確認する。
+fn is_trusted(ip: IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|net| net.contains(&ip))
+}
+
+/// 転送経路に記録されたホップからIPアドレスを取得する。
+///
+/// ポート番号が付与されている場合や、IPv6アドレスが`[]`で囲まれている場合も解析する。`unknown`や難読化した
+/// 識別子など、IPアドレスとして解析できない場合は`None`を返却する。
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim().trim_matches('"');
+    hop.parse::<IpAddr>()
+        .or_else(|_| hop.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| hop.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
+
+/// リクエストヘッダーの値を、ヘッダーが記録された順に連結して返却する。
+fn header_values<'a>(req: &'a HttpRequest, name: &'static str) -> Vec<&'a str> {
+    req.headers()
+        .get_all(HeaderName::from_static(name))
+        .filter_map(|value| value.to_str().ok())
+        .collect()
+}
+
+/// 転送経路のホップを、クライアントに近い順に返却する。
+///
+/// `Forwarded`ヘッダーがある場合は`for`パラメーターを、ない場合は`X-Forwarded-For`ヘッダーを使用する。
+fn forwarded_chain(req: &HttpRequest) -> Vec<String> {
+    let forwarded = header_values(req, FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .map(|(_, value)| value.to_owned())
+                    .unwrap_or_default()
+            })
+            .collect();
+    }
+
+    header_values(req, X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// リクエストの接続元が、信頼するプロキシか確認する。
+///
+/// 接続元のアドレスを取得できない場合は、信頼しない。
+///
+/// # Arguments
+///
+/// * `req` - HTTPリクエスト。
+/// * `trusted` - 信頼するプロキシのCIDRのリスト。
+///
+/// # Returns
+///
+/// 接続元が信頼するプロキシの場合は`true`。
+pub fn is_trusted_peer(req: &HttpRequest, trusted: &[IpNet]) -> bool {
+    req.peer_addr()
+        .map(|addr| is_trusted(addr.ip(), trusted))
+        .unwrap_or(false)
+}
+
+/// リクエストを送信したクライアントの実際のIPアドレスを返却する。
+///
+/// 接続元が信頼するプロキシの場合のみ、`Forwarded`又は`X-Forwarded-For`ヘッダーの転送経路を、接続元に近い
+/// ホップから順にたどり、最初に見つかった信頼しないホップをクライアントのIPアドレスとする。クライアントが
+/// 偽装したヘッダーの値は、信頼しないホップより前に記録されるため使用しない。
+///
+/// # Arguments
+///
+/// * `req` - HTTPリクエスト。
+/// * `trusted` - 信頼するプロキシのCIDRのリスト。
+///
+/// # Returns
+///
+/// クライアントのIPアドレス。接続元のアドレスを取得できない場合は`None`。
+pub fn real_client_ip(req: &HttpRequest, trusted: &[IpNet]) -> Option<IpAddr> {
+    let mut client = req.peer_addr()?.ip();
+    for hop in forwarded_chain(req).iter().rev() {
+        if !is_trusted(client, trusted) {
+            break;
+        }
+        // IPアドレスとして解析できないホップより前は、たどらない
+        match parse_hop(hop) {
+            Some(ip) => client = ip,
+            None => break,
+        }
+    }
+
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+    }
+
+    fn request(peer: &str, headers: &[(&str, &str)]) -> HttpRequest {
+        let mut req = TestRequest::default().peer_addr(peer.parse().unwrap());
+        for (name, value) in headers {
+            req = req.append_header((*name, *value));
+        }
+
+        req.to_http_request()
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    /// 接続元が信頼するプロキシか判定できることを確認するテスト
+    #[test]
+    fn judges_whether_peer_is_trusted() {
+        assert!(is_trusted_peer(&request("10.0.0.1:443", &[]), &trusted()));
+        assert!(is_trusted_peer(&request("[::1]:443", &[]), &trusted()));
+        assert!(!is_trusted_peer(
+            &request("203.0.113.10:443", &[]),
+            &trusted()
+        ));
+        assert!(!is_trusted_peer(&request("10.0.0.1:443", &[]), &[]));
+        // 接続元のアドレスを取得できない場合
+        let req = TestRequest::default().to_http_request();
+        assert!(!is_trusted_peer(&req, &trusted()));
+    }
+
+    /// 信頼しない接続元が送信した転送ヘッダーを無視することを確認するテスト
+    #[test]
+    fn ignores_forwarded_headers_from_untrusted_peer() {
+        let req = request(
+            "203.0.113.10:443",
+            &[
+                ("X-Forwarded-For", "198.51.100.1"),
+                ("Forwarded", "for=198.51.100.2"),
+            ],
+        );
+        assert_eq!(real_client_ip(&req, &trusted()), ip("203.0.113.10"));
+        // 信頼するプロキシが設定されていない場合
+        let req = request("10.0.0.1:443", &[("X-Forwarded-For", "198.51.100.1")]);
+        assert_eq!(real_client_ip(&req, &[]), ip("10.0.0.1"));
+    }
+
+    /// 信頼するプロキシを経由した場合に、クライアントが偽装したホップを使用せず、最初の信頼しないホップを
+    /// 返却することを確認するテスト
+    #[test]
+    fn walks_x_forwarded_for_through_trusted_hops() {
+        let req = request(
+            "10.0.0.1:443",
+            &[("X-Forwarded-For", "198.51.100.1, 203.0.113.10, 10.0.0.2")],
+        );
+        assert_eq!(real_client_ip(&req, &trusted()), ip("203.0.113.10"));
+        // 複数のヘッダーは記録された順に連結
+        let req = request(
+            "10.0.0.1:443",
+            &[
+                ("X-Forwarded-For", "198.51.100.1"),
+                ("X-Forwarded-For", "203.0.113.10:5000"),
+            ],
+        );
+        assert_eq!(real_client_ip(&req, &trusted()), ip("203.0.113.10"));
+        // すべてのホップが信頼するプロキシの場合は、最もクライアントに近いホップ
+        let req = request("10.0.0.1:443", &[("X-Forwarded-For", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(real_client_ip(&req, &trusted()), ip("10.0.0.3"));
+    }
+
+    /// `Forwarded`ヘッダーを`X-Forwarded-For`ヘッダーより優先して、`for`パラメーターをたどることを確認するテスト
+    #[test]
+    fn walks_forwarded_through_trusted_hops() {
+        let req = request(
+            "[::1]:443",
+            &[
+                (
+                    "Forwarded",
+                    r#"for=198.51.100.1, for="[2001:db8:cafe::17]:4711";proto=https, For=10.0.0.2;by=10.0.0.1"#,
+                ),
+                ("X-Forwarded-For", "198.51.100.2"),
+            ],
+        );
+        assert_eq!(real_client_ip(&req, &trusted()), ip("2001:db8:cafe::17"));
+    }
+
+    /// IPアドレスとして解析できないホップより前をたどらないことを確認するテスト
+    #[test]
+    fn stops_at_unparsable_hop() {
+        let req = request(
+            "10.0.0.1:443",
+            &[("Forwarded", "for=198.51.100.1, for=unknown, for=10.0.0.2")],
+        );
+        assert_eq!(real_client_ip(&req, &trusted()), ip("10.0.0.2"));
+    }
+}
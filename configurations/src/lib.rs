@@ -1,52 +1,106 @@
 mod settings;
 
 pub use settings::*;
+pub mod client_ip;
 pub mod password;
 pub mod session;
 pub mod telemetries;
 pub mod tokens;
+pub mod totp;
 
 use anyhow::anyhow;
 use miscellaneous::current_unix_epoch;
-use session::SessionData;
-use tokens::generate_jwt_pair;
+use session::{SessionData, SESSION_DATA_VERSION, SESSION_GENERATION};
+use tokens::{generate_jwt_pair, generate_opaque_token_pair, RedactedToken};
 use uuid::Uuid;
 
 /// セッションデータを生成する。
 ///
+/// 生成したセッションデータのリフレッシュ回数は`0`で、トークンをリフレッシュする呼び出し元が更新する。
+///
 /// # Arguments
 ///
+/// * `session_id` - セッションID。
 /// * `user_id` - ユーザーID。
+/// * `device_name` - デバイス名。
+/// * `last_authenticated_at` - 最終認証日時を示すUNIXエポック秒。
 /// * `token_settings` - トークン設定。
 ///
 /// # Returns
 ///
-/// セッションデータ。
+/// セッションデータ。トークンの有効期間が0以下か、有効期限を計算できないほど長い場合はエラー。
 pub fn generate_session_data(
+    session_id: Uuid,
     user_id: Uuid,
+    device_name: Option<String>,
+    last_authenticated_at: u64,
     token_settings: &TokensSettings,
 ) -> Result<SessionData, anyhow::Error> {
     let base_epoch = current_unix_epoch();
-    let access_expiration = base_epoch + token_settings.access_token_duration();
-    let refresh_expiration = base_epoch + token_settings.refresh_token_duration();
-    let (access_token, refresh_token) = generate_jwt_pair(
-        user_id,
-        &token_settings.secret_key,
-        access_expiration,
-        refresh_expiration,
-    )
-    .map_err(|e| {
-        anyhow!(format!(
-            "JWTトークンペアを生成するときにエラーが発生しました。{}",
-            e
-        ))
-    })?;
+    // 有効秒数は、`base_epoch`以降の現在日時に加算できることを確認済み
+    let access_expiration = base_epoch + token_settings.access_token_duration_secs()?;
+    let refresh_expiration = base_epoch + token_settings.refresh_token_duration_secs()?;
+    // 不透明トークンの場合は、ユーザーIDと有効期限をセッションデータにのみ記録
+    let (access_token, refresh_token) = match token_settings.token_mode {
+        TokenMode::Jwt => generate_jwt_pair(
+            user_id,
+            &token_settings.secret_key,
+            access_expiration,
+            refresh_expiration,
+            token_settings.audiences.first().map(String::as_str),
+        )
+        .map_err(|e| {
+            anyhow!(format!(
+                "JWTトークンペアを生成するときにエラーが発生しました。{}",
+                e
+            ))
+        })?,
+        TokenMode::Opaque => generate_opaque_token_pair(),
+    };
 
     Ok(SessionData {
+        session_id,
         user_id,
-        access_token,
+        access_token: RedactedToken::new(access_token),
         access_expiration,
-        refresh_token,
+        refresh_token: RedactedToken::new(refresh_token),
         refresh_expiration,
+        generation: SESSION_GENERATION,
+        last_accessed_at: base_epoch,
+        last_authenticated_at,
+        device_name,
+        refresh_count: 0,
+        version: SESSION_DATA_VERSION,
     })
 }
+
+/// セッションIDを変更せずに、トークンを再発行したセッションデータを生成する。
+///
+/// トークンのリフレッシュで使用して、セッションID、ユーザーID、デバイス名及び最終認証日時を引き継ぎ、
+/// リフレッシュ回数を`1`増やす。セッションIDを変更しないため、データベースに記録したリフレッシュトークンは
+/// 同じセッションのレコードとして更新される。
+///
+/// # Arguments
+///
+/// * `session_data` - 現在のセッションデータ。
+/// * `token_settings` - トークン設定。
+///
+/// # Returns
+///
+/// トークンを再発行したセッションデータ。
+pub fn reissue_session_data(
+    session_data: SessionData,
+    token_settings: &TokensSettings,
+) -> Result<SessionData, anyhow::Error> {
+    let refresh_count = session_data.refresh_count.saturating_add(1);
+    let mut reissued = generate_session_data(
+        session_data.session_id,
+        session_data.user_id,
+        session_data.device_name,
+        session_data.last_authenticated_at,
+        token_settings,
+    )?;
+    reissued.refresh_count = refresh_count;
+
+    Ok(reissued)
+}
@@ -1,11 +1,103 @@
+use std::sync::Arc;
+
+use actix_web::rt::task::JoinError;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
-use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use argon2::{Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use once_cell::sync::Lazy;
+use rand::{CryptoRng, RngCore};
 use secrecy::{ExposeSecret, Secret};
+use tokio::sync::Semaphore;
+
+use crate::telemetries::spawn_blocking_with_tracing;
+use crate::{argon2_settings, Argon2Settings};
+
+/// パスワードハッシュスレッドプール構造体
+///
+/// パスワードのハッシュ化と検証は、CPUを占有する時間が長いため、ブロッキング処理用のスレッドで実行する。
+/// ログインが集中したときに、ブロッキング処理用のスレッドをパスワードのハッシュ化と検証で使い切らない
+/// ように、同時に実行する数をプールのサイズに制限して、超えた分は実行中の処理が終わるまで待機させる。
+#[derive(Debug, Clone)]
+pub struct PasswordHashingPool {
+    /// 同時に実行できる処理の数を管理するセマフォ
+    permits: Arc<Semaphore>,
+}
+
+impl PasswordHashingPool {
+    /// パスワードハッシュスレッドプールを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - 同時に実行する処理の数。`0`の場合は`1`として扱う。
+    ///
+    /// # Returns
+    ///
+    /// パスワードハッシュスレッドプール。
+    pub fn new(size: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(size.max(1))),
+        }
+    }
+
+    /// パスワードのハッシュ化又は検証をブロッキング処理用のスレッドで実行する。
+    ///
+    /// 呼び出し元が完了を待たずに破棄した場合でも、実行を開始した処理が終わるまで枠を解放しない。
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - パスワードのハッシュ化又は検証をする関数。
+    ///
+    /// # Returns
+    ///
+    /// 関数の戻り値。スレッドがパニックした場合はエラー。
+    pub async fn spawn<F, R>(&self, f: F) -> Result<R, JoinError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("パスワードハッシュスレッドプールのセマフォは閉じません。");
+        spawn_blocking_with_tracing(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+    }
+}
+
+/// パスワードハッシュスレッドプール
+///
+/// Argon2設定と同様に、環境変数から読み込んだサイズで構築する。Argon2設定を読み込めない場合は、Webアプリの
+/// 構築時に検証して起動を中止するため、ここでは`1`として扱う。
+pub static PASSWORD_HASHING_POOL: Lazy<PasswordHashingPool> = Lazy::new(|| {
+    PasswordHashingPool::new(argon2_settings().map_or(1, |settings| settings.pool_size))
+});
+
+/// パスワードのハッシュ化又は検証を、パスワードハッシュスレッドプールで実行する。
+///
+/// # Arguments
+///
+/// * `f` - パスワードのハッシュ化又は検証をする関数。
+///
+/// # Returns
+///
+/// 関数の戻り値。スレッドがパニックした場合はエラー。
+pub async fn spawn_password_hashing<F, R>(f: F) -> Result<R, JoinError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    PASSWORD_HASHING_POOL.spawn(f).await
+}
 
 /// パスワードをハッシュ化した文字列をPHCフォーマットで返却する。
 ///
-/// パスワードに生成したソルトを付与して、ハッシュ化する。
+/// パスワードに生成したソルトを付与して、環境変数`ARGON2_VARIANT`で指定したアルゴリズムでハッシュ化する。
+/// ハッシュとソルトの長さは、環境変数`ARGON2_OUTPUT_LEN`と`ARGON2_SALT_LEN`で指定したバイト数とする。
 ///
 /// # Arguments
 ///
@@ -15,11 +107,58 @@ use secrecy::{ExposeSecret, Secret};
 ///
 /// ソルトを付与したハッシュ化したパスワードのPHC文字列。
 pub fn compute_hashed_password(password: &Secret<String>) -> anyhow::Result<Secret<String>> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
+    compute_hashed_password_with_rng(password, &mut rand::thread_rng())
+}
+
+/// 指定した乱数生成器で生成したソルトを使用して、パスワードをハッシュ化した文字列をPHCフォーマットで返却する。
+///
+/// 本番では`compute_hashed_password`を経由して`thread_rng`を使用して、テストでは固定したシードの
+/// 乱数生成器を渡すことで、ソルトの生成を再現できる。
+///
+/// # Arguments
+///
+/// * `password`: パスワードインスタンス。
+/// * `rng`: ソルトを生成する乱数生成器。
+///
+/// # Returns
+///
+/// ソルトを付与したハッシュ化したパスワードのPHC文字列。
+pub fn compute_hashed_password_with_rng<R>(
+    password: &Secret<String>,
+    rng: &mut R,
+) -> anyhow::Result<Secret<String>>
+where
+    R: RngCore + CryptoRng,
+{
+    compute_hashed_password_with_settings(password, argon2_settings()?, rng)
+}
+
+/// 指定したArgon2設定と乱数生成器で、パスワードをハッシュ化した文字列をPHCフォーマットで返却する。
+///
+/// # Arguments
+///
+/// * `password`: パスワードインスタンス。
+/// * `settings`: Argon2設定。
+/// * `rng`: ソルトを生成する乱数生成器。
+///
+/// # Returns
+///
+/// ソルトを付与したハッシュ化したパスワードのPHC文字列。
+pub fn compute_hashed_password_with_settings<R>(
+    password: &Secret<String>,
+    settings: &Argon2Settings,
+    rng: &mut R,
+) -> anyhow::Result<Secret<String>>
+where
+    R: RngCore + CryptoRng,
+{
+    let mut salt = vec![0u8; settings.salt_len];
+    rng.fill_bytes(&mut salt);
+    let salt = SaltString::b64_encode(&salt)?;
     let password_hash = Argon2::new(
-        Algorithm::Argon2id,
+        settings.algorithm,
         Version::V0x13,
-        Params::new(15_000, 2, 1, None).unwrap(),
+        Params::new(15_000, 2, 1, Some(settings.output_len))?,
     )
     .hash_password(password.expose_secret().as_bytes(), &salt)?
     .to_string();
@@ -37,6 +176,11 @@ pub enum AuthError {
 
 /// パスワードを検証する。
 ///
+/// パスワードは、ハッシュ化したパスワードのPHC文字列に記録されたアルゴリズムとパラメーターで検証する。
+///
+/// この関数は状態を持たず、呼び出しごとにハッシュを計算する。検証結果をキャッシュすると、パスワードを変更した
+/// 後も古いパスワードで認証できる脆弱性になるため、検証結果をキャッシュしてはならない。
+///
 /// # Arguments
 ///
 /// * `expected_hashed` - データベースに保存されているハッシュ化したユーザーのパスワード。
@@ -61,9 +205,71 @@ pub fn verify_password(
         .map_err(AuthError::InvalidCredentials)
 }
 
+/// ユーザーが見つからなかった場合に、パスワードを検証するダミーのハッシュ化したパスワード
+///
+/// ユーザーのパスワードと同じアルゴリズムとパラメーターでハッシュ化して、検証にかかる時間をユーザーの
+/// パスワードの検証と同程度にする。
+static DUMMY_HASHED_PASSWORD: Lazy<Secret<String>> = Lazy::new(|| {
+    compute_hashed_password(&Secret::new("dummy-password".to_owned()))
+        .expect("ダミーのパスワードをハッシュ化できませんでした。")
+});
+
+/// ダミーのハッシュ化したパスワードで、パスワードを検証する。
+///
+/// ユーザーが見つからなかった場合も、ユーザーが見つかった場合と同様にArgon2で検証することで、応答時間から
+/// ユーザーの存在が推測されることを防止する。検証結果は常に破棄する。
+///
+/// # Arguments
+///
+/// * `raw_password` - ユーザー認証する際に、ユーザーがパスワードとして入力した文字列。
+pub fn verify_dummy_password(raw_password: &Secret<String>) {
+    let _ = verify_password(&DUMMY_HASHED_PASSWORD, raw_password);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use argon2::Algorithm;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{DEFAULT_ARGON2_OUTPUT_LEN, DEFAULT_ARGON2_SALT_LEN};
+
+    /// 関数を複数回実行して、実行時間の中央値を返却する。
+    fn median_elapsed(f: impl Fn()) -> std::time::Duration {
+        let mut elapsed: Vec<_> = (0..5)
+            .map(|_| {
+                let started_at = std::time::Instant::now();
+                f();
+                started_at.elapsed()
+            })
+            .collect();
+        elapsed.sort();
+        elapsed[elapsed.len() / 2]
+    }
+
+    /// ダミーのハッシュ化したパスワードでの検証に、ユーザーのパスワードの検証と同程度の時間がかかることを
+    /// 確認するテスト
+    ///
+    /// 実行環境による揺らぎを許容するために、実行時間の比が0.5倍から2倍の範囲であることを確認する。
+    #[test]
+    fn test_verify_dummy_password_takes_comparable_time() {
+        let hashed = compute_hashed_password(&Secret::new("some-password".to_owned())).unwrap();
+        let wrong_password = Secret::new("wrong-password".to_owned());
+        // ダミーのハッシュ化したパスワードを事前に計算
+        verify_dummy_password(&wrong_password);
+
+        let found = median_elapsed(|| {
+            assert!(verify_password(&hashed, &wrong_password).is_err());
+        });
+        let not_found = median_elapsed(|| verify_dummy_password(&wrong_password));
+        let ratio = not_found.as_secs_f64() / found.as_secs_f64();
+        assert!(
+            (0.5..=2.0).contains(&ratio),
+            "found: {:?}, not found: {:?}",
+            found,
+            not_found
+        );
+    }
 
     /// パスワードを正常にハッシュ化できることを確認するテスト
     #[test]
@@ -72,4 +278,159 @@ mod tests {
         let hashed = compute_hashed_password(&password).unwrap();
         assert!(verify_password(&hashed, &password).is_ok())
     }
+
+    /// 同じパスワードでも、呼び出しごとに異なるソルトでハッシュ化されることを確認するテスト
+    #[test]
+    fn test_hashed_password_uses_unique_salt() {
+        let password = Secret::new("some-password".to_owned());
+        let first = compute_hashed_password(&password).unwrap();
+        let second = compute_hashed_password(&password).unwrap();
+        let first_hash = PasswordHash::new(first.expose_secret()).unwrap();
+        let second_hash = PasswordHash::new(second.expose_secret()).unwrap();
+        assert_ne!(first_hash.salt, second_hash.salt);
+        assert_ne!(first.expose_secret(), second.expose_secret());
+        assert!(verify_password(&first, &password).is_ok());
+        assert!(verify_password(&second, &password).is_ok());
+    }
+
+    /// 固定したシードの乱数生成器を使用した場合、ハッシュ化した結果が再現されることを確認するテスト
+    #[test]
+    fn test_hashed_password_with_fixed_seed() {
+        let password = Secret::new("some-password".to_owned());
+        let first =
+            compute_hashed_password_with_rng(&password, &mut StdRng::seed_from_u64(42)).unwrap();
+        let second =
+            compute_hashed_password_with_rng(&password, &mut StdRng::seed_from_u64(42)).unwrap();
+        let other =
+            compute_hashed_password_with_rng(&password, &mut StdRng::seed_from_u64(43)).unwrap();
+        assert_eq!(first.expose_secret(), second.expose_secret());
+        assert_ne!(first.expose_secret(), other.expose_secret());
+        assert!(verify_password(&first, &password).is_ok());
+    }
+
+    /// それぞれのアルゴリズムでパスワードをハッシュ化して、検証できることを確認するテスト
+    #[test]
+    fn test_hashed_password_with_each_algorithm() {
+        let password = Secret::new("some-password".to_owned());
+        let wrong_password = Secret::new("wrong-password".to_owned());
+        for algorithm in [Algorithm::Argon2id, Algorithm::Argon2i, Algorithm::Argon2d] {
+            let settings = Argon2Settings {
+                algorithm,
+                pool_size: 1,
+                output_len: DEFAULT_ARGON2_OUTPUT_LEN,
+                salt_len: DEFAULT_ARGON2_SALT_LEN,
+            };
+            let hashed = compute_hashed_password_with_settings(
+                &password,
+                &settings,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            // PHC文字列にアルゴリズムが記録されていることを確認
+            let hash = PasswordHash::new(hashed.expose_secret()).unwrap();
+            assert_eq!(hash.algorithm, algorithm.ident());
+            // PHC文字列に記録されたアルゴリズムで検証できることを確認
+            assert!(verify_password(&hashed, &password).is_ok());
+            assert!(verify_password(&hashed, &wrong_password).is_err());
+        }
+    }
+
+    /// 指定したハッシュとソルトの長さでパスワードをハッシュ化して、PHC文字列に記録された長さで検証できることを
+    /// 確認するテスト
+    #[test]
+    fn test_hashed_password_with_custom_output_and_salt_len() {
+        let password = Secret::new("some-password".to_owned());
+        let wrong_password = Secret::new("wrong-password".to_owned());
+        let settings = Argon2Settings {
+            algorithm: Algorithm::Argon2id,
+            pool_size: 1,
+            output_len: 64,
+            salt_len: 32,
+        };
+        let hashed =
+            compute_hashed_password_with_settings(&password, &settings, &mut rand::thread_rng())
+                .unwrap();
+        // PHC文字列に指定した長さのハッシュとソルトが記録されていることを確認
+        let hash = PasswordHash::new(hashed.expose_secret()).unwrap();
+        assert_eq!(hash.hash.unwrap().len(), 64);
+        let mut salt = [0u8; 64];
+        assert_eq!(hash.salt.unwrap().b64_decode(&mut salt).unwrap().len(), 32);
+        // 既定の長さとは異なっても、PHC文字列に記録された長さで検証できることを確認
+        assert!(verify_password(&hashed, &password).is_ok());
+        assert!(verify_password(&hashed, &wrong_password).is_err());
+    }
+
+    /// 同じパスワードを連続して検証しても、検証結果をキャッシュせずに、毎回ハッシュを計算することを確認するテスト
+    ///
+    /// 検証結果をキャッシュすると、2回目以降の検証はハッシュを計算するよりも極端に短い時間で完了するため、
+    /// それぞれの検証にかかった時間が、ハッシュを1回計算する時間の一定の割合以上であることを確認する。
+    #[test]
+    fn test_verify_password_does_not_cache_result() {
+        let password = Secret::new("some-password".to_owned());
+        let hashed = compute_hashed_password(&password).unwrap();
+        // ハッシュを1回計算する時間を計測
+        let started_at = std::time::Instant::now();
+        compute_hashed_password(&password).unwrap();
+        let hashing_time = started_at.elapsed();
+        // 同じパスワードを連続して検証して、それぞれの検証でハッシュを計算していることを確認
+        for _ in 0..5 {
+            let started_at = std::time::Instant::now();
+            assert!(verify_password(&hashed, &password).is_ok());
+            let verifying_time = started_at.elapsed();
+            assert!(
+                hashing_time / 4 <= verifying_time,
+                "hashing: {:?}, verifying: {:?}",
+                hashing_time,
+                verifying_time
+            );
+        }
+    }
+
+    /// パスワードを変更した後は、変更前のパスワードを検証できないことを確認するテスト
+    #[test]
+    fn test_verify_password_rejects_old_password_after_change() {
+        let old_password = Secret::new("old-password".to_owned());
+        let new_password = Secret::new("new-password".to_owned());
+        let old_hashed = compute_hashed_password(&old_password).unwrap();
+        assert!(verify_password(&old_hashed, &old_password).is_ok());
+        // パスワードを変更して、変更前のパスワードの検証に失敗することを確認
+        let new_hashed = compute_hashed_password(&new_password).unwrap();
+        assert!(verify_password(&new_hashed, &old_password).is_err());
+        assert!(verify_password(&new_hashed, &new_password).is_ok());
+    }
+
+    /// パスワードを同時に検証しても、パスワードハッシュスレッドプールのサイズを超えて実行しないことを
+    /// 確認するテスト
+    #[actix_web::test]
+    async fn test_password_hashing_pool_bounds_parallelism() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const POOL_SIZE: usize = 2;
+        let pool = PasswordHashingPool::new(POOL_SIZE);
+        let password = Secret::new("some-password".to_owned());
+        let hashed = compute_hashed_password(&password).unwrap();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..POOL_SIZE * 4)
+            .map(|_| {
+                let pool = pool.clone();
+                let (password, hashed) = (password.clone(), hashed.clone());
+                let (running, max_running) = (running.clone(), max_running.clone());
+                actix_web::rt::spawn(async move {
+                    pool.spawn(move || {
+                        let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(current, Ordering::SeqCst);
+                        let result = verify_password(&hashed, &password);
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        result
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.await.unwrap().unwrap().is_ok());
+        }
+        assert!(max_running.load(Ordering::SeqCst) <= POOL_SIZE);
+    }
 }
@@ -1,76 +1,668 @@
 use std::future::{ready, Ready};
 
 use actix_session::{Session, SessionExt};
-use actix_web::{cookie::Cookie, dev::Payload, FromRequest, HttpRequest, HttpResponse};
-use serde::{Deserialize, Serialize};
+use actix_web::{
+    cookie::{time::Duration, Cookie},
+    dev::Payload,
+    error::HttpError,
+    http::header::{HeaderName, HeaderValue},
+    web, FromRequest, HttpRequest, HttpResponse,
+};
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
 use uuid::Uuid;
 
-use crate::SessionCookieSettings;
+use crate::tokens::{token_fingerprint, RedactedToken};
+use crate::totp::TotpChallenge;
+use crate::{SessionCookieSettings, SessionStoreSettings, Settings};
 
 pub const ACCESS_TOKEN_COOKIE_NAME: &str = "access_token";
 pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
+pub const TOKEN_FINGERPRINT_HEADER_NAME: &str = "x-token-fingerprint";
+
+/// セッションの状態に、セッションIDを記録するキー
+///
+/// セッションデータを暗号化する場合でも、セッションストアがセッションIDからセッションを特定できるように、
+/// セッションIDは暗号化せずに記録する。
+pub const SESSION_ID_STATE_KEY: &str = "session_id";
+
+/// 現在のセッションデータの世代
+///
+/// セッションデータの形式やトークンの発行方針を変更したときに値を上げると、既存のセッションは
+/// 次のアクセスでトークンがリフレッシュされる。
+pub const SESSION_GENERATION: u32 = 1;
+
+/// 現在のセッションデータの形式のバージョン
+///
+/// セッションデータにフィールドを追加するなど、形式を変更したときに値を上げて、`SessionData::from_stored`に
+/// 以前の形式からの移行処理を追加する。
+pub const SESSION_DATA_VERSION: u32 = 1;
 
 /// セッションデータ構造体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionData {
+    /// セッションID
+    ///
+    /// ログインしたときに発行して、トークンをリフレッシュしても変わらない。
+    /// データベースに記録したリフレッシュトークンを特定するために使用する。
+    pub session_id: Uuid,
     /// ユーザーID
     pub user_id: Uuid,
     /// アクセストークン
-    pub access_token: String,
+    pub access_token: RedactedToken,
     /// アクセストークン有効期限（UNIXエポック秒）
     pub access_expiration: u64,
     /// リフレッシュトークン
-    pub refresh_token: String,
+    pub refresh_token: RedactedToken,
     /// リフレッシュトークン有効期限（UNIXエポック秒）
     pub refresh_expiration: u64,
+    /// セッションデータの世代
+    ///
+    /// `SESSION_GENERATION`より古い世代のセッションデータは、ミドルウェアでトークンをリフレッシュする。
+    /// 世代を記録していないセッションデータは、世代`0`とみなす。
+    #[serde(default)]
+    pub generation: u32,
+    /// 最終アクセス日時（UNIXエポック秒）
+    ///
+    /// ミドルウェアは、前回の更新から一定の間隔が経過したときにのみ更新して、Redisへの書き込みを抑制する。
+    #[serde(default)]
+    pub last_accessed_at: u64,
+    /// 最終認証日時（UNIXエポック秒）
+    ///
+    /// ログインしたとき、又は現在のパスワード検証APIで再認証したときに更新する。トークンをリフレッシュしても
+    /// 変わらない。最終認証日時を記録していないセッションデータは、再認証が必要とみなす。
+    #[serde(default)]
+    pub last_authenticated_at: u64,
+    /// デバイス名
+    ///
+    /// ユーザーがセッションを識別できるように、ログインしたときにクライアントが指定したデバイス名、または
+    /// User-Agentから推測したデバイス名を記録する。トークンをリフレッシュしても変わらない。
+    #[serde(default)]
+    pub device_name: Option<String>,
+    /// リフレッシュ回数
+    ///
+    /// ログインしたときに`0`にして、トークンをリフレッシュするたびに増加させる。1回のログインでトークンを
+    /// リフレッシュし続けられる回数を制限するために使用する。
+    #[serde(default)]
+    pub refresh_count: u32,
+    /// セッションデータの形式のバージョン
+    ///
+    /// バージョンを記録していないセッションデータは、バージョン`0`とみなす。
+    #[serde(default)]
+    pub version: u32,
+}
+
+/// セッションキーの検証コードを計算する。
+///
+/// セッションキーは、actix-sessionがクッキーに暗号化して記録する、セッションストアのキーである。
+///
+/// # Arguments
+///
+/// * `session_key` - セッションキー。
+/// * `secret_key` - トークンの秘密鍵。
+///
+/// # Returns
+///
+/// Base64でエンコードしたセッションキーのHMAC-SHA256。
+pub fn session_key_mac(session_key: &str, secret_key: &Secret<String>) -> String {
+    base64::encode(
+        new_session_key_mac(session_key, secret_key)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// セッションキーの検証コードが、セッションキーと一致するか確認する。
+///
+/// 検証コードは、処理時間から推測されないように定数時間で比較する。
+///
+/// # Arguments
+///
+/// * `session_key` - セッションキー。
+/// * `mac` - セッションの状態に記録された検証コード。
+/// * `secret_key` - トークンの秘密鍵。
+///
+/// # Returns
+///
+/// 検証コードが一致する場合は`true`。
+pub fn verify_session_key_mac(session_key: &str, mac: &str, secret_key: &Secret<String>) -> bool {
+    match base64::decode(mac) {
+        Ok(expected) => new_session_key_mac(session_key, secret_key)
+            .verify_slice(&expected)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// セッションキーを入力したHMACを構築する。
+fn new_session_key_mac(session_key: &str, secret_key: &Secret<String>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.expose_secret().as_bytes())
+        .expect("HMACは任意の長さの鍵を受け付けます。");
+    // 同じ秘密鍵で計算する他のHMACと区別するために、用途を示す接頭辞を付与
+    mac.update(b"session_key\0");
+    mac.update(session_key.as_bytes());
+
+    mac
+}
+
+impl SessionData {
+    /// セッションの最終認証日時から、再認証せずに重要な操作を許可する期間が経過していないか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - 現在日時を示すUNIXエポック秒。
+    /// * `window` - 再認証せずに重要な操作を許可する秒数。`0`の場合は常に許可する。
+    ///
+    /// # Returns
+    ///
+    /// 期間が経過していない場合は`true`。
+    pub fn is_recently_authenticated(&self, now: u64, window: u64) -> bool {
+        window == 0 || now <= self.last_authenticated_at.saturating_add(window)
+    }
+
+    /// トークンをリフレッシュできる回数の上限に達しているか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `max_refresh_chain` - 1回のログインでトークンをリフレッシュできる回数。`0`の場合は制限しない。
+    ///
+    /// # Returns
+    ///
+    /// 上限に達しているため、トークンをリフレッシュせずに再ログインを要求する場合は`true`。
+    pub fn has_exhausted_refresh_chain(&self, max_refresh_chain: u32) -> bool {
+        0 < max_refresh_chain && max_refresh_chain <= self.refresh_count
+    }
+
+    /// テスト用のセッションデータを構築する。
+    ///
+    /// セッションIDはnil UUID、アクセストークンは`foo`、リフレッシュトークンは`bar`で、最終アクセス日時と
+    /// 最終認証日時は現在日時にする。テスト及び`test-util`フィーチャーを有効にしたビルドでのみ使用できる。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `access_offset` - 現在日時からアクセストークンの有効期限までの秒数。負の場合は有効期限切れ。
+    /// * `refresh_offset` - 現在日時からリフレッシュトークンの有効期限までの秒数。負の場合は有効期限切れ。
+    ///
+    /// # Returns
+    ///
+    /// セッションデータ。
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn for_test(user_id: Uuid, access_offset: i64, refresh_offset: i64) -> Self {
+        let now = miscellaneous::current_unix_epoch();
+
+        Self {
+            session_id: Uuid::nil(),
+            user_id,
+            access_token: RedactedToken::new("foo"),
+            access_expiration: now.saturating_add_signed(access_offset),
+            refresh_token: RedactedToken::new("bar"),
+            refresh_expiration: now.saturating_add_signed(refresh_offset),
+            generation: SESSION_GENERATION,
+            last_accessed_at: now,
+            last_authenticated_at: now,
+            device_name: None,
+            refresh_count: 0,
+            version: SESSION_DATA_VERSION,
+        }
+    }
+
+    /// セッションストアに記録されたセッションデータを、現在の形式に移行して読み込む。
+    ///
+    /// 以前の形式で記録されたセッションデータは、追加されたフィールドを既定値で補って現在のバージョンに
+    /// 移行する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - セッションストアに記録されたセッションデータ。
+    ///
+    /// # Returns
+    ///
+    /// 現在の形式のセッションデータ。現在の形式に移行できない場合はエラー。
+    pub fn from_stored(value: serde_json::Value) -> Result<Self, SessionDataError> {
+        let mut data: Self = serde_json::from_value(value)?;
+        if data.version > SESSION_DATA_VERSION {
+            return Err(SessionDataError::UnsupportedVersion(data.version));
+        }
+        // バージョン0から1へは、`serde(default)`で補ったフィールドのみで移行できる
+        data.version = SESSION_DATA_VERSION;
+
+        Ok(data)
+    }
+}
+
+/// デバイス名の最大文字数
+pub const DEVICE_NAME_MAX_LEN: usize = 100;
+
+/// User-Agentからデバイス名を推測する。
+///
+/// ブラウザとOSを判定して、`Chrome (Windows)`のようなデバイス名を返却する。OSを判定できない場合は
+/// ブラウザ名のみを返却する。
+///
+/// # Arguments
+///
+/// * `user_agent` - User-Agent。
+///
+/// # Returns
+///
+/// デバイス名。ブラウザとOSのどちらも判定できない場合は`None`。
+pub fn guess_device_name(user_agent: &str) -> Option<String> {
+    // iPhoneやAndroidのUser-AgentはMac OS XやLinuxを含むため、より具体的なOSから判定
+    let os = [
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("CrOS", "Chromebook"),
+        ("Windows", "Windows"),
+        ("Macintosh", "Mac"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| *name);
+    // ChromeのUser-AgentはSafariを、EdgeやOperaのUser-AgentはChromeを含むため、より具体的なブラウザから判定
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => Some(format!("{} ({})", browser, os)),
+        (None, Some(os)) => Some(os.to_owned()),
+        (Some(browser), None) => Some(browser.to_owned()),
+        (None, None) => None,
+    }
+}
+
+/// セッションデータの暗号鍵のバイト数
+pub const SESSION_DATA_ENCRYPTION_KEY_BYTES: usize = 32;
+
+/// AES-GCMのナンスのバイト数
+const SESSION_DATA_NONCE_BYTES: usize = 12;
+
+/// Base64でエンコードしたセッションデータの暗号鍵をデコードする。
+///
+/// # Arguments
+///
+/// * `key` - Base64でエンコードした暗号鍵。
+///
+/// # Returns
+///
+/// 暗号鍵。
+pub fn decode_session_data_encryption_key(key: &str) -> anyhow::Result<Vec<u8>> {
+    let key = base64::decode(key.trim())
+        .map_err(|e| anyhow!("暗号鍵をBase64でデコードできません。{}", e))?;
+    if key.len() != SESSION_DATA_ENCRYPTION_KEY_BYTES {
+        return Err(anyhow!(
+            "暗号鍵は{}バイトで指定してください。",
+            SESSION_DATA_ENCRYPTION_KEY_BYTES
+        ));
+    }
+
+    Ok(key)
+}
+
+/// セッションデータエラー
+#[derive(Debug, thiserror::Error)]
+pub enum SessionDataError {
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+    #[error("セッションデータを暗号化できませんでした。")]
+    Encryption,
+    #[error("セッションデータを復号できませんでした。")]
+    Decryption,
+    #[error("セッションデータのバージョン({0})に対応していません。")]
+    UnsupportedVersion(u32),
+}
+
+/// セッションデータ暗号構造体
+///
+/// セッションストアが漏洩したときにトークンが流出しないように、セッションデータをAES-256-GCMで暗号化する。
+/// actix-sessionのクッキーの暗号化とは別に、アプリケーション層で暗号化する。
+#[derive(Clone)]
+pub struct SessionDataCipher {
+    cipher: Aes256Gcm,
+}
+
+impl std::fmt::Debug for SessionDataCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionDataCipher")
+    }
+}
+
+impl SessionDataCipher {
+    /// セッションデータ暗号インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Base64でエンコードした32バイトの暗号鍵。
+    ///
+    /// # Returns
+    ///
+    /// セッションデータ暗号インスタンス。
+    pub fn new(key: &str) -> anyhow::Result<Self> {
+        let key = decode_session_data_encryption_key(key)?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::from_slice(&key)),
+        })
+    }
+
+    /// トークンの秘密鍵から用途ごとに導出した暗号鍵で、暗号インスタンスを構築する。
+    ///
+    /// 冪等キーに記録する応答など、暗号鍵を設定せずに暗号化する必要があるデータに使用する。暗号鍵は、
+    /// トークンの秘密鍵で用途を示す文字列を入力したHMAC-SHA256である。
+    ///
+    /// # Arguments
+    ///
+    /// * `secret_key` - トークンの秘密鍵。
+    /// * `purpose` - 暗号鍵の用途。
+    ///
+    /// # Returns
+    ///
+    /// 暗号インスタンス。
+    pub fn derive(secret_key: &Secret<String>, purpose: &str) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.expose_secret().as_bytes())
+            .expect("HMACは任意の長さの鍵を受け付けます。");
+        mac.update(purpose.as_bytes());
+        let key = mac.finalize().into_bytes();
+
+        Self {
+            cipher: Aes256Gcm::new(Key::from_slice(&key)),
+        }
+    }
+
+    /// セッションストア設定から、セッションデータ暗号インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - セッションストア設定。
+    ///
+    /// # Returns
+    ///
+    /// セッションデータ暗号インスタンス。暗号鍵が設定されていない場合は`None`。
+    pub fn from_settings(settings: &SessionStoreSettings) -> anyhow::Result<Option<Self>> {
+        settings
+            .encryption_key
+            .as_ref()
+            .map(|key| Self::new(key.expose_secret()))
+            .transpose()
+    }
+
+    /// アプリケーションデータに登録されたセッションデータ暗号を取得する。
+    ///
+    /// セッションデータ暗号は、リクエストごとに構築しないように、Webアプリの構築時に構築して、暗号鍵が
+    /// 設定されている場合のみアプリケーションデータに登録する。暗号鍵が設定されているにもかかわらず、
+    /// セッションデータ暗号が登録されていない場合は、セッションデータを暗号化せずに記録しないように
+    /// エラーを返却する。
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - HTTPリクエスト。
+    ///
+    /// # Returns
+    ///
+    /// セッションデータ暗号。暗号鍵が設定されていない場合は`None`。
+    pub fn from_app_data(req: &HttpRequest) -> anyhow::Result<Option<Self>> {
+        if let Some(cipher) = req.app_data::<web::Data<Self>>() {
+            return Ok(Some(cipher.as_ref().clone()));
+        }
+        let encryption_required = req
+            .app_data::<web::Data<Settings>>()
+            .map(|settings| settings.session_store.encryption_key.is_some())
+            .unwrap_or(false);
+        if encryption_required {
+            return Err(anyhow!("セッションデータ暗号を取得できませんでした。"));
+        }
+
+        Ok(None)
+    }
+
+    /// 平文を暗号化する。
+    ///
+    /// 暗号文は、ランダムに生成したナンスと暗号文を連結して、Base64でエンコードした文字列である。
+    ///
+    /// # Arguments
+    ///
+    /// * `plaintext` - 平文。
+    ///
+    /// # Returns
+    ///
+    /// 暗号文。
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, SessionDataError> {
+        let nonce = rand::thread_rng().gen::<[u8; SESSION_DATA_NONCE_BYTES]>();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| SessionDataError::Encryption)?;
+
+        Ok(base64::encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    /// 暗号文を復号する。
+    ///
+    /// # Arguments
+    ///
+    /// * `ciphertext` - `encrypt`で暗号化した暗号文。
+    ///
+    /// # Returns
+    ///
+    /// 平文。
+    pub fn decrypt(&self, ciphertext: &str) -> Result<Vec<u8>, SessionDataError> {
+        let bytes = base64::decode(ciphertext).map_err(|_| SessionDataError::Decryption)?;
+        if bytes.len() < SESSION_DATA_NONCE_BYTES {
+            return Err(SessionDataError::Decryption);
+        }
+        let (nonce, ciphertext) = bytes.split_at(SESSION_DATA_NONCE_BYTES);
+
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| SessionDataError::Decryption)
+    }
 }
 
 /// 型付けセッション構造体
 ///
 /// RedisにセッションIDをキーにアクセストークンを記録する。
-pub struct TypedSession(pub Session);
+/// セッションデータ暗号を指定した場合は、セッションデータを暗号化して記録する。
+pub struct TypedSession {
+    session: Session,
+    cipher: Option<SessionDataCipher>,
+}
 
 impl TypedSession {
     const SESSION_DATA_KEY: &'static str = "session_data";
+    const ENCRYPTED_SESSION_DATA_KEY: &'static str = "encrypted_session_data";
+    const WEBAUTHN_STATE_KEY: &'static str = "webauthn_state";
+    const TOTP_CHALLENGE_KEY: &'static str = "totp_challenge";
+
+    /// 型付けセッションインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - セッション。
+    /// * `cipher` - セッションデータ暗号。暗号化しない場合は`None`。
+    ///
+    /// # Returns
+    ///
+    /// 型付けセッションインスタンス。
+    pub fn new(session: Session, cipher: Option<SessionDataCipher>) -> Self {
+        Self { session, cipher }
+    }
 
     /// セッションデータを取得する。
     ///
+    /// セッションデータ暗号を指定した場合は、暗号化されていないセッションデータを無視する。
+    /// 以前の形式で記録されたセッションデータは、現在の形式に移行する。復号できないセッションデータと、
+    /// 現在の形式に移行できないセッションデータは、セッションを破棄して、セッションデータが存在しないものとして
+    /// 扱う。
+    ///
     /// # Returns
     ///
     /// セッションデータ。
-    pub fn get(&self) -> Result<Option<SessionData>, serde_json::Error> {
-        self.0.get(Self::SESSION_DATA_KEY)
+    pub fn get(&self) -> Result<Option<SessionData>, SessionDataError> {
+        let value = match &self.cipher {
+            Some(cipher) => {
+                let ciphertext = self
+                    .session
+                    .get::<String>(Self::ENCRYPTED_SESSION_DATA_KEY)?;
+                match ciphertext {
+                    Some(ciphertext) => {
+                        // 暗号鍵を変更した場合など、復号できないセッションデータは、セッションを破棄して、
+                        // セッションデータが存在しないものとして扱う
+                        let plaintext = match cipher.decrypt(&ciphertext) {
+                            Ok(plaintext) => plaintext,
+                            Err(e) => {
+                                tracing::warn!(
+                                    "セッションデータを復号できないため、セッションを破棄します。{}",
+                                    e
+                                );
+                                self.purge();
+                                return Ok(None);
+                            }
+                        };
+                        Some(serde_json::from_slice::<serde_json::Value>(&plaintext)?)
+                    }
+                    None => None,
+                }
+            }
+            None => self
+                .session
+                .get::<serde_json::Value>(Self::SESSION_DATA_KEY)?,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        match SessionData::from_stored(value) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) => {
+                tracing::warn!(
+                    "セッションデータを読み込めないため、セッションを破棄します。{}",
+                    e
+                );
+                self.purge();
+                Ok(None)
+            }
+        }
     }
 
     /// セッションデータを登録する。
     ///
+    /// 管理者がセッションを失効させたときに、セッションストアがセッションIDからセッションを特定して削除できる
+    /// ように、セッションデータとは別にセッションIDを記録する。
+    ///
     /// # Arguments
     ///
     /// * `data` - セッションデータ。
-    pub fn insert(&self, data: &SessionData) -> Result<(), serde_json::Error> {
-        self.0.insert(Self::SESSION_DATA_KEY, data)
+    pub fn insert(&self, data: &SessionData) -> Result<(), SessionDataError> {
+        self.session.insert(SESSION_ID_STATE_KEY, data.session_id)?;
+        match &self.cipher {
+            Some(cipher) => {
+                let ciphertext = cipher.encrypt(&serde_json::to_vec(data)?)?;
+                self.session
+                    .insert(Self::ENCRYPTED_SESSION_DATA_KEY, ciphertext)?;
+                self.session.remove(Self::SESSION_DATA_KEY);
+            }
+            None => {
+                self.session.insert(Self::SESSION_DATA_KEY, data)?;
+                self.session.remove(Self::ENCRYPTED_SESSION_DATA_KEY);
+            }
+        }
+
+        Ok(())
     }
 
     /// セッションデータを削除する。
     pub fn remove(&self) -> Option<String> {
-        self.0.remove(Self::SESSION_DATA_KEY)
+        self.session.remove(SESSION_ID_STATE_KEY);
+        let encrypted = self.session.remove(Self::ENCRYPTED_SESSION_DATA_KEY);
+        self.session.remove(Self::SESSION_DATA_KEY).or(encrypted)
+    }
+
+    /// パスキーの登録又は認証を開始したときのWebAuthnの状態を登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - WebAuthnの状態。
+    pub fn insert_webauthn_state<T: Serialize>(&self, state: &T) -> Result<(), SessionDataError> {
+        Ok(self.session.insert(Self::WEBAUTHN_STATE_KEY, state)?)
+    }
+
+    /// WebAuthnの状態を取り出す。
+    ///
+    /// WebAuthnの状態に含まれるチャレンジは1回しか使用できないように、取得すると同時に削除する。
+    ///
+    /// # Returns
+    ///
+    /// WebAuthnの状態。登録されていない場合は`None`。
+    pub fn take_webauthn_state<T: DeserializeOwned>(&self) -> Result<Option<T>, SessionDataError> {
+        let state = self.session.get(Self::WEBAUTHN_STATE_KEY)?;
+        self.session.remove(Self::WEBAUTHN_STATE_KEY);
+
+        Ok(state)
+    }
+
+    /// TOTPチャレンジを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - TOTPチャレンジ。
+    pub fn insert_totp_challenge(&self, challenge: &TotpChallenge) -> Result<(), SessionDataError> {
+        Ok(self.session.insert(Self::TOTP_CHALLENGE_KEY, challenge)?)
+    }
+
+    /// TOTPチャレンジを取り出す。
+    ///
+    /// コードの総当たりを防ぐため、TOTPチャレンジは1回しか使用できないように、取得すると同時に削除する。
+    ///
+    /// # Returns
+    ///
+    /// TOTPチャレンジ。登録されていない場合は`None`。
+    pub fn take_totp_challenge(&self) -> Result<Option<TotpChallenge>, SessionDataError> {
+        let challenge = self.session.get(Self::TOTP_CHALLENGE_KEY)?;
+        self.session.remove(Self::TOTP_CHALLENGE_KEY);
+
+        Ok(challenge)
     }
 
     /// セッションをクリアする。
     pub fn clear(&self) {
-        self.0.clear()
+        self.session.clear()
     }
 
     /// セッションを更新する。
     ///
     /// 既存のセッションデータは、新しいセッションIDに割り当てられる。
+    ///
+    /// セッション固定化攻撃への対策として、ログインとパスワードの変更など、認証状態が変わる場合にのみ使用する。
+    /// トークンのリフレッシュでは、セッションストアのセッションを継続させるため、セッションを更新しない。
+    /// セッションを更新した後にセッションデータを登録すると更新が取り消されるため、セッションデータを登録した後に
+    /// 呼び出す。
     pub fn renew(&self) {
-        self.0.renew();
+        self.session.renew();
     }
 
     /// セッションストアからセッションデータを削除して、クライアントのセッションを削除する。
     pub fn purge(&self) {
-        self.0.purge()
+        self.session.purge()
     }
 }
 
@@ -96,13 +688,18 @@ impl FromRequest for TypedSession {
     ///
     /// 型付けセッション。
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
-        ready(Ok(TypedSession(req.get_session())))
+        // システム設定にセッションデータの暗号鍵が設定されている場合は、セッションデータを暗号化
+        ready(
+            SessionDataCipher::from_app_data(req)
+                .map(|cipher| TypedSession::new(req.get_session(), cipher))
+                .map_err(actix_web::error::ErrorInternalServerError),
+        )
     }
 }
 
 /// クッキーを構築する。
 ///
-/// 構築するクッキーのSecure及びSameSiteは、システム設定による。
+/// 構築するクッキーのSecure、SameSite及びMax-Ageは、システム設定による。
 /// また、クッキーのPathは`/`で、HttpOnlyである。
 ///
 /// # Arguments
@@ -119,13 +716,17 @@ pub fn build_session_data_cookie<'a>(
     value: &'a str,
     settings: &'a SessionCookieSettings,
 ) -> Cookie<'a> {
-    Cookie::build(name.to_owned(), value.to_owned())
+    let mut cookie = Cookie::build(name.to_owned(), value.to_owned())
         .path("/")
         .secure(settings.secure.to_owned())
         .http_only(true)
         .same_site(settings.same_site.to_owned())
-        .finish()
-        .into_owned()
+        .finish();
+    if let Some(max_age) = settings.max_age_seconds {
+        cookie.set_max_age(Duration::seconds(max_age as i64));
+    }
+
+    cookie.into_owned()
 }
 
 /// レスポンスにセッションデータ（トークン）をクッキーに保存するように指示する。
@@ -136,17 +737,361 @@ pub fn build_session_data_cookie<'a>(
 /// * `access_token` - アクセストークン。
 /// * `refresh_token` - リフレッシュトークン。
 /// * `settings` - セッションクッキー設定。
+///
+/// # Returns
+///
+/// クッキーをレスポンスヘッダーに追加できなかった場合はエラー。
 pub fn add_session_data_cookies(
     response: &mut HttpResponse,
     access_token: &str,
     refresh_token: &str,
     settings: &SessionCookieSettings,
-) {
+) -> Result<(), HttpError> {
     let access_token_cookie =
         build_session_data_cookie(ACCESS_TOKEN_COOKIE_NAME, access_token, settings);
     let refresh_token_cookie =
         build_session_data_cookie(REFRESH_TOKEN_COOKIE_NAME, refresh_token, settings);
 
-    response.add_cookie(&access_token_cookie).unwrap();
-    response.add_cookie(&refresh_token_cookie).unwrap();
+    response.add_cookie(&access_token_cookie)?;
+    response.add_cookie(&refresh_token_cookie)?;
+
+    Ok(())
+}
+
+/// レスポンスヘッダーにアクセストークンのフィンガープリントを追加する。
+///
+/// # Arguments
+///
+/// * `response` - HTTPレスポンス。
+/// * `access_token` - アクセストークン。
+///
+/// # Returns
+///
+/// ヘッダーをレスポンスに追加できなかった場合はエラー。
+pub fn add_token_fingerprint_header(
+    response: &mut HttpResponse,
+    access_token: &str,
+) -> Result<(), HttpError> {
+    response.headers_mut().insert(
+        HeaderName::from_static(TOKEN_FINGERPRINT_HEADER_NAME),
+        HeaderValue::from_str(&token_fingerprint(access_token))?,
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// User-Agentから妥当なデバイス名を推測できることを確認するテスト
+    #[test]
+    fn test_guess_device_name() {
+        let cases = [
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1",
+                Some("Safari (iPhone)"),
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36",
+                Some("Chrome (Windows)"),
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36 Edg/106.0.1370.34",
+                Some("Edge (Windows)"),
+            ),
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:105.0) Gecko/20100101 Firefox/105.0",
+                Some("Firefox (Mac)"),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Mobile Safari/537.36",
+                Some("Chrome (Android)"),
+            ),
+            ("curl/7.85.0", None),
+            ("", None),
+        ];
+        for (user_agent, expected) in cases {
+            assert_eq!(
+                guess_device_name(user_agent).as_deref(),
+                expected,
+                "{}",
+                user_agent
+            );
+        }
+    }
+
+    /// デバイス名を記録していないセッションデータを、デバイス名なしとして読み込めることを確認するテスト
+    #[test]
+    fn test_session_data_without_device_name() {
+        let json = serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": 1_000,
+            "refresh_token": "bar",
+            "refresh_expiration": 2_000,
+        });
+        let session_data: SessionData = serde_json::from_value(json).unwrap();
+        assert!(session_data.device_name.is_none());
+    }
+
+    /// テスト用のセッションクッキー設定を構築する。
+    fn session_cookie_settings(max_age_seconds: Option<u64>) -> SessionCookieSettings {
+        SessionCookieSettings {
+            session_id_cookie_name: "session_id".to_owned(),
+            secure: true,
+            same_site: actix_web::cookie::SameSite::Lax,
+            max_age_seconds,
+        }
+    }
+
+    /// `Max-Age`を設定した場合に、トークンを記録するクッキーに`Max-Age`が付与されることを確認するテスト
+    #[test]
+    fn test_session_data_cookie_with_max_age() {
+        let settings = session_cookie_settings(Some(3600));
+        let cookie = build_session_data_cookie(REFRESH_TOKEN_COOKIE_NAME, "foo", &settings);
+        assert_eq!(cookie.max_age(), Some(Duration::seconds(3600)));
+        assert!(cookie.to_string().contains("Max-Age=3600"), "{}", cookie);
+    }
+
+    /// `Max-Age`を設定しない場合に、トークンを記録するクッキーがセッションクッキーになることを確認するテスト
+    #[test]
+    fn test_session_data_cookie_without_max_age() {
+        let settings = session_cookie_settings(None);
+        let cookie = build_session_data_cookie(ACCESS_TOKEN_COOKIE_NAME, "foo", &settings);
+        assert!(cookie.max_age().is_none());
+        assert!(!cookie.to_string().contains("Max-Age"), "{}", cookie);
+    }
+
+    /// バージョンを記録する前の形式のセッションデータを、現在の形式に移行できることを確認するテスト
+    #[test]
+    fn test_session_data_from_stored_upgrades_old_shape() {
+        let session_id = Uuid::new_v4();
+        let json = serde_json::json!({
+            "session_id": session_id,
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": 1_000,
+            "refresh_token": "bar",
+            "refresh_expiration": 2_000,
+        });
+        let session_data = SessionData::from_stored(json).unwrap();
+        assert_eq!(session_data.session_id, session_id);
+        assert_eq!(session_data.version, SESSION_DATA_VERSION);
+        assert_eq!(session_data.generation, 0);
+        assert_eq!(session_data.last_accessed_at, 0);
+        assert!(session_data.device_name.is_none());
+    }
+
+    /// 現在より新しいバージョンや、形式が異なるセッションデータを読み込めないことを確認するテスト
+    #[test]
+    fn test_session_data_from_stored_rejects_unsupported_data() {
+        let json = serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": 1_000,
+            "refresh_token": "bar",
+            "refresh_expiration": 2_000,
+            "version": SESSION_DATA_VERSION + 1,
+        });
+        assert!(matches!(
+            SessionData::from_stored(json),
+            Err(SessionDataError::UnsupportedVersion(_))
+        ));
+        let json = serde_json::json!({ "access_token": "foo" });
+        assert!(matches!(
+            SessionData::from_stored(json),
+            Err(SessionDataError::Serialization(_))
+        ));
+    }
+
+    /// セッションキーの検証コードを検証できることを確認するテスト
+    #[test]
+    fn test_verify_session_key_mac() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let mac = session_key_mac("session-key", &secret_key);
+        assert!(verify_session_key_mac("session-key", &mac, &secret_key));
+        // 異なる秘密鍵では検証に失敗
+        assert!(!verify_session_key_mac(
+            "session-key",
+            &mac,
+            &Secret::new("other-secret".to_owned())
+        ));
+        // 他のセッションキーに記録された検証コードは検証に失敗
+        assert!(!verify_session_key_mac("other-key", &mac, &secret_key));
+        // 壊れた検証コードは検証に失敗
+        assert!(!verify_session_key_mac("session-key", "", &secret_key));
+        assert!(!verify_session_key_mac(
+            "session-key",
+            "not base64!",
+            &secret_key
+        ));
+    }
+
+    /// 最終認証日時から、再認証せずに許可する期間が経過したかどうかを判定できることを確認するテスト
+    #[test]
+    fn test_is_recently_authenticated() {
+        let mut session_data: SessionData = serde_json::from_value(serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": 1_000,
+            "refresh_token": "bar",
+            "refresh_expiration": 2_000,
+        }))
+        .unwrap();
+        // 最終認証日時を記録していないセッションデータは、再認証が必要
+        assert!(!session_data.is_recently_authenticated(1_000, 600));
+        session_data.last_authenticated_at = 1_000;
+        assert!(session_data.is_recently_authenticated(1_000, 600));
+        assert!(session_data.is_recently_authenticated(1_600, 600));
+        assert!(!session_data.is_recently_authenticated(1_601, 600));
+        // 期間が`0`の場合は、常に再認証を要求しない
+        assert!(session_data.is_recently_authenticated(u64::MAX, 0));
+    }
+
+    /// テスト用のセッションデータの有効期限が、現在日時からの秒数で計算されることを確認するテスト
+    #[test]
+    fn test_session_data_for_test() {
+        let user_id = Uuid::new_v4();
+        let before = miscellaneous::current_unix_epoch();
+        let session_data = SessionData::for_test(user_id, 300, -1);
+        let after = miscellaneous::current_unix_epoch();
+        assert_eq!(session_data.user_id, user_id);
+        assert!(before + 300 <= session_data.access_expiration);
+        assert!(session_data.access_expiration <= after + 300);
+        assert!(session_data.refresh_expiration < after);
+        assert_eq!(session_data.access_token.expose(), "foo");
+        assert_eq!(session_data.refresh_token.expose(), "bar");
+        assert_eq!(session_data.generation, SESSION_GENERATION);
+        assert_eq!(session_data.version, SESSION_DATA_VERSION);
+    }
+
+    /// リフレッシュ回数が上限に達したときに、再ログインを要求することを確認するテスト
+    #[test]
+    fn test_has_exhausted_refresh_chain() {
+        let mut session_data: SessionData = serde_json::from_value(serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": 1_000,
+            "refresh_token": "bar",
+            "refresh_expiration": 2_000,
+        }))
+        .unwrap();
+        // リフレッシュ回数を記録していないセッションデータは、ログインした直後とみなす
+        assert_eq!(session_data.refresh_count, 0);
+        assert!(!session_data.has_exhausted_refresh_chain(3));
+        session_data.refresh_count = 2;
+        assert!(!session_data.has_exhausted_refresh_chain(3));
+        session_data.refresh_count = 3;
+        assert!(session_data.has_exhausted_refresh_chain(3));
+        session_data.refresh_count = 4;
+        assert!(session_data.has_exhausted_refresh_chain(3));
+        // 上限が`0`の場合は、制限しない
+        session_data.refresh_count = u32::MAX;
+        assert!(!session_data.has_exhausted_refresh_chain(0));
+    }
+
+    /// セッションデータを暗号化して、復号できることを確認するテスト
+    #[test]
+    fn test_session_data_cipher() {
+        let key = base64::encode([7u8; SESSION_DATA_ENCRYPTION_KEY_BYTES]);
+        let cipher = SessionDataCipher::new(&key).unwrap();
+        let plaintext = br#"{"access_token":"secret-access-token"}"#;
+        let ciphertext = cipher.encrypt(plaintext).unwrap();
+        assert!(!ciphertext.contains("secret-access-token"));
+        // 同じ平文でも、ナンスが異なるため暗号文は異なる
+        assert_ne!(ciphertext, cipher.encrypt(plaintext).unwrap());
+        assert_eq!(cipher.decrypt(&ciphertext).unwrap(), plaintext);
+
+        // 異なる暗号鍵や改ざんした暗号文は復号できない
+        let other = SessionDataCipher::new(&base64::encode([8u8; 32])).unwrap();
+        assert!(other.decrypt(&ciphertext).is_err());
+        let mut tampered = base64::decode(&ciphertext).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(cipher.decrypt(&base64::encode(tampered)).is_err());
+        assert!(cipher.decrypt("not-base64!").is_err());
+        assert!(cipher.decrypt("").is_err());
+    }
+
+    /// 復号できないセッションデータを、セッションを破棄して存在しないものとして扱うことを確認するテスト
+    #[test]
+    fn test_typed_session_purges_undecryptable_session_data() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        let cipher = SessionDataCipher::new(&base64::encode([7u8; 32])).unwrap();
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 600);
+        TypedSession::new(req.get_session(), Some(cipher.clone()))
+            .insert(&session_data)
+            .unwrap();
+        assert!(TypedSession::new(req.get_session(), Some(cipher))
+            .get()
+            .unwrap()
+            .is_some());
+
+        // 暗号鍵を変更した場合
+        let other = SessionDataCipher::new(&base64::encode([8u8; 32])).unwrap();
+        assert!(TypedSession::new(req.get_session(), Some(other))
+            .get()
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            req.get_session().status(),
+            actix_session::SessionStatus::Purged
+        );
+    }
+
+    /// セッションデータを暗号化する場合でも、セッションIDを暗号化せずに記録することを確認するテスト
+    #[test]
+    fn test_typed_session_records_session_id() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        let cipher = SessionDataCipher::new(&base64::encode([7u8; 32])).unwrap();
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 600);
+        let session = TypedSession::new(req.get_session(), Some(cipher));
+        session.insert(&session_data).unwrap();
+        assert_eq!(
+            req.get_session().get::<Uuid>(SESSION_ID_STATE_KEY).unwrap(),
+            Some(session_data.session_id)
+        );
+        // セッションデータを削除すると、セッションIDも削除
+        session.remove();
+        assert!(req
+            .get_session()
+            .get::<Uuid>(SESSION_ID_STATE_KEY)
+            .unwrap()
+            .is_none());
+    }
+
+    /// トークンの秘密鍵から、用途ごとに異なる暗号鍵を導出することを確認するテスト
+    #[test]
+    fn test_derived_cipher() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let cipher = SessionDataCipher::derive(&secret_key, "foo");
+        let ciphertext = cipher.encrypt(b"plaintext").unwrap();
+        assert_eq!(
+            SessionDataCipher::derive(&secret_key, "foo")
+                .decrypt(&ciphertext)
+                .unwrap(),
+            b"plaintext"
+        );
+        assert!(SessionDataCipher::derive(&secret_key, "bar")
+            .decrypt(&ciphertext)
+            .is_err());
+        assert!(
+            SessionDataCipher::derive(&Secret::new("other-secret".to_owned()), "foo")
+                .decrypt(&ciphertext)
+                .is_err()
+        );
+    }
+
+    /// 不正な暗号鍵を拒否することを確認するテスト
+    #[test]
+    fn test_decode_session_data_encryption_key() {
+        assert!(decode_session_data_encryption_key(&base64::encode([0u8; 32])).is_ok());
+        assert!(decode_session_data_encryption_key(&base64::encode([0u8; 16])).is_err());
+        assert!(decode_session_data_encryption_key("not-base64!").is_err());
+    }
 }
@@ -1,11 +1,18 @@
-use std::env;
+use std::collections::HashSet;
+use std::{env, fs};
 
 use actix_web::cookie::{time::Duration, SameSite};
-use anyhow::bail;
+use actix_web::http::StatusCode;
+use anyhow::{anyhow, bail};
+use argon2::Algorithm;
+use ipnet::IpNet;
+use miscellaneous::current_unix_epoch;
 use once_cell::sync::Lazy;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{postgres::PgConnectOptions, ConnectOptions};
 
+use crate::session::decode_session_data_encryption_key;
+
 /// 設定構造体
 #[derive(Debug, Clone)]
 pub struct Settings {
@@ -21,15 +28,27 @@ pub struct Settings {
     pub session_store: SessionStoreSettings,
     /// データベース設定
     pub db: DatabaseSettings,
+    /// WebAuthn設定
+    pub webauthn: WebAuthnSettings,
+    /// TOTP設定
+    pub totp: TotpSettings,
+    /// 管理者設定
+    pub admin: AdminSettings,
+    /// サインアップ設定
+    pub signup: SignupSettings,
+    /// ユーザーキャッシュ設定
+    pub user_cache: UserCacheSettings,
+    /// Webhook設定
+    pub webhook: WebhookSettings,
 }
 
-impl Settings {
+impl Default for Settings {
     /// 環境変数から設定を取得する。
     ///
     /// # Returns
     ///
     /// 設定インスタンス。
-    pub fn default() -> Settings {
+    fn default() -> Settings {
         Settings {
             rust_log: ENV_VALUES.rust_log.clone(),
             web_app: WebAppSettings::default(),
@@ -37,6 +56,12 @@ impl Settings {
             tokens: TokensSettings::default(),
             session_store: SessionStoreSettings::default(),
             db: DatabaseSettings::default(),
+            webauthn: WebAuthnSettings::default(),
+            totp: TotpSettings::default(),
+            admin: AdminSettings::default(),
+            signup: SignupSettings::default(),
+            user_cache: UserCacheSettings::default(),
+            webhook: WebhookSettings::default(),
         }
     }
 }
@@ -56,29 +81,122 @@ pub struct EnvValues {
 
     pub web_app_host: String,
     pub web_app_port: u16,
+    pub web_app_json_payload_limit: usize,
+    pub web_app_json_content_type_required: bool,
+    pub web_app_tls_cert_path: Option<String>,
+    pub web_app_tls_key_path: Option<String>,
+    pub web_app_cors_allowed_origins: Vec<String>,
+    pub trusted_proxies: Vec<IpNet>,
+    pub app_environment: AppEnvironment,
 
     pub session_id_cookie_name: String,
     pub session_cookie_secure: bool,
     pub session_cookie_same_site: SameSite,
+    pub session_cookie_max_age_seconds: Option<u64>,
 
     pub token_secret_key: Secret<String>,
     pub access_token_duration: Duration,
     pub refresh_token_duration: Duration,
+    pub silent_refresh_enabled: bool,
+    pub sliding_renewal_duration: Duration,
+    pub inactive_user_status: StatusCode,
+    pub logout_on_password_change: bool,
+    pub token_mode: TokenMode,
+    pub token_audiences: Vec<String>,
+    pub refresh_token_cleanup_interval: Duration,
+    pub reauthentication_window: Duration,
+    pub max_refresh_chain: u32,
 
     pub session_store_uri: Secret<String>,
     pub session_store_key: Secret<String>,
+    pub session_store_legacy_keys: Vec<Secret<String>>,
+    pub session_touch_interval: Duration,
+    pub session_store_key_prefix: String,
+    pub session_data_encryption_key: Option<Secret<String>>,
+    pub session_store_connect_timeout: Duration,
+    pub session_store_command_timeout: Duration,
+    pub idempotency_key_ttl: Duration,
 
     pub postgres_user_name: String,
     pub postgres_user_password: Secret<String>,
     pub postgres_host: String,
     pub postgres_port: u16,
     pub postgres_database_name: String,
+    pub auto_migrate: bool,
+    // WebAuthn設定
+    pub webauthn_rp_id: Option<String>,
+    pub webauthn_rp_name: String,
+    pub webauthn_origin: Option<String>,
+    // TOTP設定
+    pub totp_issuer: String,
+    // 管理者設定
+    pub admin_api_key: Option<Secret<String>>,
+    // サインアップ設定
+    pub signup_enabled: bool,
+    pub signup_privacy_mode: bool,
+    pub signup_block_disposable_email: bool,
+    pub disposable_email_domains_path: Option<String>,
+    // ユーザーキャッシュ設定
+    pub user_cache_enabled: bool,
+    pub user_cache_capacity: usize,
+    pub user_cache_ttl: Duration,
+    // Webhook設定
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<Secret<String>>,
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_interval: Duration,
+    pub webhook_timeout: Duration,
 }
 
 fn string_from_env(key: &str) -> String {
     env::var(key).unwrap_or_else(|_| panic!("環境変数に{}が設定されていません。", key))
 }
 
+/// 環境変数から秘密の値を取得する。
+///
+/// `<key>_FILE`が設定されている場合は、`<key>`よりも優先して、`<key>_FILE`に設定されたパスのファイルの
+/// 内容を値とする。ファイルの末尾の改行は削除する。Dockerシークレットなど、ファイルとしてマウントされた
+/// 秘密の値を読み込むために使用する。
+fn secret_from_env(key: &str) -> Secret<String> {
+    let file_key = format!("{}_FILE", key);
+    match env::var(&file_key) {
+        Ok(path) => Secret::new(
+            fs::read_to_string(&path)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "環境変数{}に設定されたファイル({})を読み込めません。{}",
+                        file_key, path, e
+                    )
+                })
+                .trim_end_matches(['\r', '\n'])
+                .to_owned(),
+        ),
+        Err(_) => Secret::new(string_from_env(key)),
+    }
+}
+
+fn optional_string_from_env(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+fn optional_secret_from_env(key: &str) -> Option<Secret<String>> {
+    let file_key = format!("{}_FILE", key);
+    if env::var(&file_key).is_ok() {
+        return Some(secret_from_env(key));
+    }
+
+    optional_string_from_env(key).map(Secret::new)
+}
+
+fn session_data_encryption_key_from_env(key: &str) -> Option<Secret<String>> {
+    let value = optional_secret_from_env(key)?;
+    if let Err(e) = decode_session_data_encryption_key(value.expose_secret()) {
+        panic!("環境変数{}を暗号鍵として認識できません。{}", key, e);
+    }
+
+    Some(value)
+}
+
 fn u16_from_env(key: &str) -> u16 {
     env::var(key)
         .unwrap_or_else(|_| panic!("環境変数に{}が設定されていません。", key))
@@ -86,6 +204,32 @@ fn u16_from_env(key: &str) -> u16 {
         .unwrap_or_else(|_| panic!("環境変数{}を数値として認識できません。", key))
 }
 
+fn u32_from_env_or(key: &str, default: u32) -> u32 {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("環境変数{}を数値として認識できません。", key)),
+        Err(_) => default,
+    }
+}
+
+fn usize_from_env_or(key: &str, default: usize) -> usize {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("環境変数{}を数値として認識できません。", key)),
+        Err(_) => default,
+    }
+}
+
+fn optional_u64_from_env(key: &str) -> Option<u64> {
+    optional_string_from_env(key).map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("環境変数{}を数値として認識できません。", key))
+    })
+}
+
 fn bool_from_env(key: &str) -> bool {
     env::var(key)
         .unwrap_or_else(|_| panic!("環境変数に{}が設定されていません。", key))
@@ -93,6 +237,15 @@ fn bool_from_env(key: &str) -> bool {
         .unwrap_or_else(|_| panic!("環境変数{}を論理値として認識できません。", key))
 }
 
+fn bool_from_env_or(key: &str, default: bool) -> bool {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("環境変数{}を論理値として認識できません。", key)),
+        Err(_) => default,
+    }
+}
+
 fn same_site_from_env(key: &str) -> SameSite {
     str_to_same_site(
         &env::var(key).unwrap_or_else(|_| panic!("環境変数に{}が設定されていません。", key)),
@@ -100,6 +253,117 @@ fn same_site_from_env(key: &str) -> SameSite {
     .unwrap_or_else(|_| panic!("環境変数{}をSameSiteとして認識できません。", key))
 }
 
+fn argon2_algorithm_from_env_or(key: &str, default: Algorithm) -> anyhow::Result<Algorithm> {
+    match env::var(key) {
+        Ok(value) => Algorithm::new(value.trim().to_lowercase()).map_err(|_| {
+            anyhow!(
+                "環境変数{}をArgon2のアルゴリズムとして認識できません。argon2id、argon2i又はargon2dを設定してください。",
+                key
+            )
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+fn try_usize_from_env_or(key: &str, default: usize) -> anyhow::Result<usize> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| anyhow!("環境変数{}を数値として認識できません。", key)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn bounded_usize_from_env_or(
+    key: &str,
+    default: usize,
+    min: usize,
+    max: usize,
+) -> anyhow::Result<usize> {
+    let value = try_usize_from_env_or(key, default)?;
+    if !(min..=max).contains(&value) {
+        bail!(
+            "環境変数{}には、{}以上{}以下を設定してください。",
+            key,
+            min,
+            max
+        );
+    }
+
+    Ok(value)
+}
+
+fn list_from_env_or(key: &str, default: &[&str]) -> Vec<String> {
+    match env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        Err(_) => default.iter().map(|item| (*item).to_owned()).collect(),
+    }
+}
+
+/// 環境変数から、カンマ区切りで設定された信頼するプロキシのCIDRのリストを取得する。
+///
+/// プレフィックス長を省略したIPアドレスは、そのIPアドレスのみを示すCIDRとする。
+fn trusted_proxies_from_env(key: &str) -> Vec<IpNet> {
+    list_from_env_or(key, &[])
+        .iter()
+        .map(|value| {
+            value
+                .parse::<IpNet>()
+                .or_else(|_| value.parse::<std::net::IpAddr>().map(IpNet::from))
+                .unwrap_or_else(|_| {
+                    panic!("環境変数{}の{}をCIDRとして認識できません。", key, value)
+                })
+        })
+        .collect()
+}
+
+fn inactive_user_status_from_env_or(key: &str, default: StatusCode) -> StatusCode {
+    match env::var(key) {
+        Ok(value) => match value.trim() {
+            "401" => StatusCode::UNAUTHORIZED,
+            "403" => StatusCode::FORBIDDEN,
+            _ => panic!(
+                "環境変数{}をステータスコードとして認識できません。401又は403を設定してください。",
+                key
+            ),
+        },
+        Err(_) => default,
+    }
+}
+
+fn app_environment_from_env_or(key: &str, default: AppEnvironment) -> AppEnvironment {
+    match env::var(key) {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "production" => AppEnvironment::Production,
+            "development" => AppEnvironment::Development,
+            _ => panic!(
+                "環境変数{}を実行環境として認識できません。production又はdevelopmentを設定してください。",
+                key
+            ),
+        },
+        Err(_) => default,
+    }
+}
+
+fn token_mode_from_env_or(key: &str, default: TokenMode) -> TokenMode {
+    match env::var(key) {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "jwt" => TokenMode::Jwt,
+            "opaque" => TokenMode::Opaque,
+            _ => panic!(
+                "環境変数{}をトークンの形式として認識できません。jwt又はopaqueを設定してください。",
+                key
+            ),
+        },
+        Err(_) => default,
+    }
+}
+
 fn seconds_from_env(key: &str) -> Duration {
     Duration::seconds(
         env::var(key)
@@ -109,6 +373,50 @@ fn seconds_from_env(key: &str) -> Duration {
     )
 }
 
+fn seconds_from_env_or(key: &str, default: i64) -> Duration {
+    match env::var(key) {
+        Ok(value) => Duration::seconds(
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("環境変数{}を秒数として認識できません。", key)),
+        ),
+        Err(_) => Duration::seconds(default),
+    }
+}
+
+/// セッションの最終アクセス日時を更新する間隔（秒）の既定値
+const DEFAULT_SESSION_TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// Redisに接続するときのタイムアウト（秒）の既定値
+const DEFAULT_SESSION_STORE_CONNECT_TIMEOUT_SECONDS: i64 = 5;
+
+/// Redisのコマンドのタイムアウト（秒）の既定値
+const DEFAULT_SESSION_STORE_COMMAND_TIMEOUT_SECONDS: i64 = 3;
+
+/// 冪等キーとリクエストの処理結果をRedisに記録する期間（秒）の既定値
+const DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS: i64 = 600;
+
+/// 有効期限が切れたリフレッシュトークンを削除する間隔（秒）の既定値
+const DEFAULT_REFRESH_TOKEN_CLEANUP_INTERVAL_SECONDS: i64 = 3600;
+
+/// パスワードの変更などの重要な操作を、再認証せずに許可する期間（秒）の既定値
+const DEFAULT_REAUTHENTICATION_WINDOW_SECONDS: i64 = 600;
+
+/// WebAuthnのリライングパーティー名の既定値
+const DEFAULT_WEBAUTHN_RP_NAME: &str = "jwt-auth-example";
+
+/// TOTPの認証アプリに表示するサービス名の既定値
+const DEFAULT_TOTP_ISSUER: &str = "jwt-auth-example";
+
+/// ユーザーキャッシュに記録するユーザー数の上限の既定値
+const DEFAULT_USER_CACHE_CAPACITY: usize = 1024;
+
+/// ユーザーキャッシュの有効期間（秒）の既定値
+const DEFAULT_USER_CACHE_TTL_SECONDS: i64 = 30;
+
+/// JSONペイロードの最大バイト数の既定値
+const DEFAULT_JSON_PAYLOAD_LIMIT: usize = 16 * 1024;
+
 /// 環境変数
 pub static ENV_VALUES: Lazy<EnvValues> = Lazy::new(|| {
     EnvValues {
@@ -118,28 +426,82 @@ pub static ENV_VALUES: Lazy<EnvValues> = Lazy::new(|| {
         // Webアプリ設定
         web_app_host: string_from_env("WEB_APP_HOST"),
         web_app_port: u16_from_env("WEB_APP_PORT"),
+        web_app_json_payload_limit: usize_from_env_or(
+            "WEB_APP_JSON_PAYLOAD_LIMIT",
+            DEFAULT_JSON_PAYLOAD_LIMIT,
+        ),
+        web_app_json_content_type_required: bool_from_env_or(
+            "WEB_APP_JSON_CONTENT_TYPE_REQUIRED",
+            true,
+        ),
+        web_app_tls_cert_path: optional_string_from_env("WEB_APP_TLS_CERT_PATH"),
+        web_app_tls_key_path: optional_string_from_env("WEB_APP_TLS_KEY_PATH"),
+        web_app_cors_allowed_origins: list_from_env_or("WEB_APP_CORS_ALLOWED_ORIGINS", &[]),
+        trusted_proxies: trusted_proxies_from_env("TRUSTED_PROXIES"),
+        app_environment: app_environment_from_env_or("APP_ENV", AppEnvironment::Development),
 
         // セッション設定
         session_id_cookie_name: string_from_env("SESSION_ID_COOKIE_NAME"),
         session_cookie_secure: bool_from_env("SESSION_COOKIE_SECURE"),
         session_cookie_same_site: same_site_from_env("SESSION_COOKIE_SAME_SITE"),
+        session_cookie_max_age_seconds: optional_u64_from_env("SESSION_COOKIE_MAX_AGE_SECONDS"),
 
         // セッションストア設定
-        session_store_uri: Secret::new(string_from_env("SESSION_STORE_URI")),
-        session_store_key: Secret::new(string_from_env("SESSION_STORE_KEY")),
+        session_store_uri: secret_from_env("SESSION_STORE_URI"),
+        session_store_key: secret_from_env("SESSION_STORE_KEY"),
+        session_store_legacy_keys: list_from_env_or("SESSION_STORE_LEGACY_KEYS", &[])
+            .into_iter()
+            .map(Secret::new)
+            .collect(),
+        session_touch_interval: seconds_from_env_or(
+            "SESSION_TOUCH_INTERVAL_SECONDS",
+            DEFAULT_SESSION_TOUCH_INTERVAL_SECONDS,
+        ),
+        session_store_key_prefix: optional_string_from_env("SESSION_STORE_KEY_PREFIX")
+            .unwrap_or_default(),
+        session_data_encryption_key: session_data_encryption_key_from_env(
+            "SESSION_DATA_ENCRYPTION_KEY",
+        ),
+        session_store_connect_timeout: seconds_from_env_or(
+            "SESSION_STORE_CONNECT_TIMEOUT_SECONDS",
+            DEFAULT_SESSION_STORE_CONNECT_TIMEOUT_SECONDS,
+        ),
+        session_store_command_timeout: seconds_from_env_or(
+            "SESSION_STORE_COMMAND_TIMEOUT_SECONDS",
+            DEFAULT_SESSION_STORE_COMMAND_TIMEOUT_SECONDS,
+        ),
+        idempotency_key_ttl: seconds_from_env_or(
+            "IDEMPOTENCY_KEY_TTL_SECONDS",
+            DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS,
+        ),
 
         // トークン設定
-        token_secret_key: Secret::new(string_from_env("TOKEN_SECRET_KEY")),
+        token_secret_key: secret_from_env("TOKEN_SECRET_KEY"),
         access_token_duration: seconds_from_env("ACCESS_TOKEN_SECONDS"),
         refresh_token_duration: seconds_from_env("REFRESH_TOKEN_SECONDS"),
+        silent_refresh_enabled: bool_from_env_or("SILENT_REFRESH_ENABLED", true),
+        sliding_renewal_duration: seconds_from_env_or("SLIDING_RENEWAL_SECONDS", 0),
+        inactive_user_status: inactive_user_status_from_env_or(
+            "INACTIVE_USER_STATUS",
+            StatusCode::FORBIDDEN,
+        ),
+        logout_on_password_change: bool_from_env_or("LOGOUT_ON_PASSWORD_CHANGE", true),
+        token_mode: token_mode_from_env_or("TOKEN_MODE", TokenMode::Jwt),
+        token_audiences: list_from_env_or("TOKEN_AUDIENCE", &[]),
+        refresh_token_cleanup_interval: seconds_from_env_or(
+            "REFRESH_TOKEN_CLEANUP_INTERVAL_SECONDS",
+            DEFAULT_REFRESH_TOKEN_CLEANUP_INTERVAL_SECONDS,
+        ),
+        reauthentication_window: seconds_from_env_or(
+            "REAUTHENTICATION_WINDOW_SECONDS",
+            DEFAULT_REAUTHENTICATION_WINDOW_SECONDS,
+        ),
+        max_refresh_chain: u32_from_env_or("MAX_REFRESH_CHAIN", 0),
 
         // データベース設定
         postgres_user_name: env::var("POSTGRES_USER_NAME")
             .expect("環境変数にPOSTGRES_USER_NAMEが設定されていません。"),
-        postgres_user_password: Secret::new(
-            env::var("POSTGRES_USER_PASSWORD")
-                .expect("環境変数にPOSTGRES_USER_PASSWORDが設定されていません。"),
-        ),
+        postgres_user_password: secret_from_env("POSTGRES_USER_PASSWORD"),
         postgres_host: env::var("POSTGRES_HOST")
             .expect("環境変数にPOSTGRES_HOSTが設定されていません。"),
         postgres_port: env::var("POSTGRES_PORT")
@@ -148,6 +510,47 @@ pub static ENV_VALUES: Lazy<EnvValues> = Lazy::new(|| {
             .expect("環境変数POSTGRES_PORTを数値として認識できません。"),
         postgres_database_name: env::var("POSTGRES_DATABASE_NAME")
             .expect("環境変数にPOSTGRES_DATABASE_NAMEが設定されてません。"),
+        auto_migrate: bool_from_env_or("AUTO_MIGRATE", false),
+
+        // WebAuthn設定
+        webauthn_rp_id: optional_string_from_env("WEBAUTHN_RP_ID"),
+        webauthn_rp_name: optional_string_from_env("WEBAUTHN_RP_NAME")
+            .unwrap_or_else(|| DEFAULT_WEBAUTHN_RP_NAME.to_owned()),
+        webauthn_origin: optional_string_from_env("WEBAUTHN_ORIGIN"),
+
+        // TOTP設定
+        totp_issuer: optional_string_from_env("TOTP_ISSUER")
+            .unwrap_or_else(|| DEFAULT_TOTP_ISSUER.to_owned()),
+
+        // 管理者設定
+        admin_api_key: optional_secret_from_env("ADMIN_API_KEY"),
+
+        // サインアップ設定
+        signup_enabled: bool_from_env_or("SIGNUP_ENABLED", true),
+        signup_privacy_mode: bool_from_env_or("SIGNUP_PRIVACY_MODE", false),
+        signup_block_disposable_email: bool_from_env_or("SIGNUP_BLOCK_DISPOSABLE_EMAIL", false),
+        disposable_email_domains_path: optional_string_from_env("DISPOSABLE_EMAIL_DOMAINS_PATH"),
+
+        // ユーザーキャッシュ設定
+        user_cache_enabled: bool_from_env_or("USER_CACHE_ENABLED", false),
+        user_cache_capacity: usize_from_env_or("USER_CACHE_CAPACITY", DEFAULT_USER_CACHE_CAPACITY),
+        user_cache_ttl: seconds_from_env_or(
+            "USER_CACHE_TTL_SECONDS",
+            DEFAULT_USER_CACHE_TTL_SECONDS,
+        ),
+
+        // Webhook設定
+        webhook_url: optional_string_from_env("WEBHOOK_URL"),
+        webhook_secret: optional_secret_from_env("WEBHOOK_SECRET"),
+        webhook_max_attempts: u32_from_env_or("WEBHOOK_MAX_ATTEMPTS", DEFAULT_WEBHOOK_MAX_ATTEMPTS),
+        webhook_retry_interval: seconds_from_env_or(
+            "WEBHOOK_RETRY_INTERVAL_SECONDS",
+            DEFAULT_WEBHOOK_RETRY_INTERVAL_SECONDS,
+        ),
+        webhook_timeout: seconds_from_env_or(
+            "WEBHOOK_TIMEOUT_SECONDS",
+            DEFAULT_WEBHOOK_TIMEOUT_SECONDS,
+        ),
     }
 });
 
@@ -156,21 +559,83 @@ pub static ENV_VALUES: Lazy<EnvValues> = Lazy::new(|| {
 pub struct WebAppSettings {
     pub host: String,
     pub port: u16,
+    /// アカウントAPIが受け付けるJSONペイロードの最大バイト数
+    pub json_payload_limit: usize,
+    /// アカウントAPIがJSONペイロードに`application/json`のContent-Typeを要求するか
+    ///
+    /// `true`の場合は、Content-TypeがJSONではないリクエストを`415 Unsupported Media Type`で拒否する。
+    /// `false`の場合は、Content-TypeにかかわらずリクエストボディをJSONとして解釈する。
+    pub json_content_type_required: bool,
+    /// TLS設定
+    ///
+    /// `None`の場合は、TLSを使用せずにバインドする。
+    pub tls: Option<TlsSettings>,
+    /// クロスオリジンリクエストを許可するオリジン
+    ///
+    /// 空の場合は、CORSを有効にしない。
+    pub cors_allowed_origins: Vec<String>,
+    /// 信頼するプロキシのCIDR
+    ///
+    /// 接続元がこのCIDRに含まれる場合のみ、`Forwarded`又は`X-Forwarded-For`ヘッダーからクライアントの
+    /// IPアドレスを取得する。
+    pub trusted_proxies: Vec<IpNet>,
+    /// 実行環境
+    pub environment: AppEnvironment,
 }
 
-impl WebAppSettings {
+/// 実行環境
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppEnvironment {
+    /// 本番環境。
+    ///
+    /// 認証情報を記録するクッキーを平文で送信しないように、安全ではないクッキー設定でWebアプリを起動しない。
+    Production,
+    /// 開発環境。
+    Development,
+}
+
+/// TLS設定構造体
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    /// PEM形式の証明書（チェーン）ファイルのパス
+    pub cert_path: String,
+    /// PEM形式の秘密鍵ファイルのパス
+    pub key_path: String,
+}
+
+impl Default for WebAppSettings {
     /// 環境変数からWebアプリ設定を構築する。
     ///
     /// # Returns
     ///
     /// Webアプリ設定インスタンス。
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             host: ENV_VALUES.web_app_host.clone(),
             port: ENV_VALUES.web_app_port,
+            json_payload_limit: ENV_VALUES.web_app_json_payload_limit,
+            json_content_type_required: ENV_VALUES.web_app_json_content_type_required,
+            tls: match (
+                &ENV_VALUES.web_app_tls_cert_path,
+                &ENV_VALUES.web_app_tls_key_path,
+            ) {
+                (Some(cert_path), Some(key_path)) => Some(TlsSettings {
+                    cert_path: cert_path.clone(),
+                    key_path: key_path.clone(),
+                }),
+                (None, None) => None,
+                _ => panic!(
+                    "環境変数WEB_APP_TLS_CERT_PATHとWEB_APP_TLS_KEY_PATHは、両方を設定してください。"
+                ),
+            },
+            cors_allowed_origins: ENV_VALUES.web_app_cors_allowed_origins.clone(),
+            trusted_proxies: ENV_VALUES.trusted_proxies.clone(),
+            environment: ENV_VALUES.app_environment,
         }
     }
+}
 
+impl WebAppSettings {
     /// Webアプリがバインドするソケットアドレスを返却する。
     ///
     /// # Returns
@@ -186,34 +651,173 @@ pub struct SessionCookieSettings {
     pub session_id_cookie_name: String,
     pub secure: bool,
     pub same_site: SameSite,
+    /// トークンを記録するクッキーの`Max-Age`（秒）
+    ///
+    /// `None`の場合は`Max-Age`を付与せず、クッキーはブラウザを閉じると削除される。リフレッシュトークンの
+    /// 有効期間と同じ秒数を指定すると、ブラウザを閉じてもリフレッシュトークンの有効期限までログインを継続できる。
+    pub max_age_seconds: Option<u64>,
 }
 
-impl SessionCookieSettings {
-    pub fn default() -> Self {
+impl Default for SessionCookieSettings {
+    fn default() -> Self {
         Self {
             session_id_cookie_name: ENV_VALUES.session_id_cookie_name.clone(),
             secure: ENV_VALUES.session_cookie_secure,
             same_site: ENV_VALUES.session_cookie_same_site,
+            max_age_seconds: ENV_VALUES.session_cookie_max_age_seconds,
         }
     }
 }
 
+impl SessionCookieSettings {
+    /// 実行環境で、クッキー設定が安全か確認する。
+    ///
+    /// 本番環境では、クッキーに`Secure`属性を付与しない場合や、`SameSite`属性が`None`の場合に、
+    /// 認証情報を記録したクッキーが平文で送信されたり、クロスサイトリクエストで送信されたりするため、
+    /// 安全ではないと判断する。
+    ///
+    /// また、`SameSite`属性が`None`のクッキーは、`Secure`属性を付与しないとブラウザに破棄されるため、
+    /// 実行環境にかかわらず拒否する。
+    ///
+    /// # Arguments
+    ///
+    /// * `environment` - 実行環境。
+    ///
+    /// # Returns
+    ///
+    /// クッキー設定が安全な場合は`()`。安全ではない場合は、その理由を示すエラー。
+    pub fn verify_security(&self, environment: AppEnvironment) -> anyhow::Result<()> {
+        if self.same_site == SameSite::None && !self.secure {
+            bail!(
+                "SameSite属性がNoneのクッキーは、Secure属性を付与しないとブラウザに破棄されます。\
+                環境変数SESSION_COOKIE_SECUREにtrueを設定するか、SESSION_COOKIE_SAME_SITEにlax又はstrictを\
+                設定してください。"
+            );
+        }
+        if environment != AppEnvironment::Production {
+            return Ok(());
+        }
+        if !self.secure {
+            bail!("本番環境では、環境変数SESSION_COOKIE_SECUREにtrueを設定してください。");
+        }
+        if self.same_site == SameSite::None {
+            bail!(
+                "本番環境では、環境変数SESSION_COOKIE_SAME_SITEにlax又はstrictを設定してください。"
+            );
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TokensSettings {
     pub secret_key: Secret<String>,
     pub access_token_duration: Duration,
     pub refresh_token_duration: Duration,
+    /// アクセストークンの有効期限が切れたとき、ミドルウェアでトークンをサイレントリフレッシュするか
+    ///
+    /// `false`の場合、クライアントはリフレッシュAPIを明示的に呼び出す必要がある。
+    pub silent_refresh_enabled: bool,
+    /// リフレッシュトークンの残りの有効期間がこの期間以下になったとき、アクセストークンの有効期限内でも
+    /// ミドルウェアでトークンをリフレッシュ（スライディング延長）する期間
+    ///
+    /// `0`の場合、スライディング延長しない。
+    pub sliding_renewal_duration: Duration,
+    /// 有効なセッションを持つユーザーが無効になっていたときに、ミドルウェアが応答するステータスコード
+    ///
+    /// `401 Unauthorized`又は`403 Forbidden`。
+    pub inactive_user_status: StatusCode,
+    /// パスワードを変更したときに、現在のセッションからログアウトするか
+    ///
+    /// `false`の場合、現在のセッションはトークンを更新して継続する。他のセッションは、設定にかかわらず失効する。
+    pub logout_on_password_change: bool,
+    /// アクセストークンとリフレッシュトークンの形式
+    pub token_mode: TokenMode,
+    /// JWTの`aud`クレームに指定するオーディエンス
+    ///
+    /// 生成するJWTには最初のオーディエンスを指定して、検証するJWTの`aud`クレームがいずれかのオーディエンスと
+    /// 一致する場合に受け付ける。空の場合は、`aud`クレームを指定せず、検証もしない。
+    pub audiences: Vec<String>,
+    /// 有効期限が切れたリフレッシュトークンを、データベースから削除する間隔
+    ///
+    /// `0`の場合、有効期限が切れたリフレッシュトークンを削除しない。
+    pub refresh_token_cleanup_interval: Duration,
+    /// ログイン又は現在のパスワードの検証から、パスワードの変更を再認証せずに許可する期間
+    ///
+    /// この期間が経過したセッションでパスワードを変更する場合は、現在のパスワード検証APIで再認証する必要がある。
+    /// `0`の場合、再認証を要求しない。
+    pub reauthentication_window: Duration,
+    /// 1回のログインでトークンをリフレッシュできる回数
+    ///
+    /// リフレッシュ回数がこの回数に達したセッションは、トークンをリフレッシュせずに破棄して、再ログインを
+    /// 要求する。`0`の場合、制限しない。
+    pub max_refresh_chain: u32,
 }
 
-impl TokensSettings {
-    pub fn default() -> Self {
+/// トークンの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenMode {
+    /// ユーザーIDと有効期限をクレームに含めたJWT。
+    Jwt,
+    /// クレームを含まないランダムな文字列（不透明トークン）。
+    ///
+    /// トークンからユーザーIDや有効期限を読み取れないため、クライアントにクレームを公開しない。
+    Opaque,
+}
+
+impl Default for TokensSettings {
+    fn default() -> Self {
         Self {
             secret_key: ENV_VALUES.token_secret_key.clone(),
             access_token_duration: ENV_VALUES.access_token_duration,
             refresh_token_duration: ENV_VALUES.refresh_token_duration,
+            silent_refresh_enabled: ENV_VALUES.silent_refresh_enabled,
+            sliding_renewal_duration: ENV_VALUES.sliding_renewal_duration,
+            inactive_user_status: ENV_VALUES.inactive_user_status,
+            logout_on_password_change: ENV_VALUES.logout_on_password_change,
+            token_mode: ENV_VALUES.token_mode,
+            audiences: ENV_VALUES.token_audiences.clone(),
+            refresh_token_cleanup_interval: ENV_VALUES.refresh_token_cleanup_interval,
+            reauthentication_window: ENV_VALUES.reauthentication_window,
+            max_refresh_chain: ENV_VALUES.max_refresh_chain,
         }
     }
+}
+
+/// トークンの有効期間を秒数に変換する。
+///
+/// # Arguments
+///
+/// * `name` - エラーメッセージに含めるトークンの名前。
+/// * `duration` - トークンの有効期間。
+/// * `base_epoch` - 有効秒数を加算するUNIXエポック秒。
+///
+/// # Returns
+///
+/// トークンの有効秒数。有効期間が0以下の場合や、`base_epoch`に加算するとオーバーフローする場合はエラー。
+fn token_duration_secs(name: &str, duration: Duration, base_epoch: u64) -> anyhow::Result<u64> {
+    let seconds = duration.whole_seconds();
+    if seconds <= 0 {
+        return Err(anyhow!(
+            "{}の有効期間({}秒)は、正の秒数で指定してください。",
+            name,
+            seconds
+        ));
+    }
+    let seconds = seconds as u64;
+    base_epoch.checked_add(seconds).ok_or_else(|| {
+        anyhow!(
+            "{}の有効期間({}秒)が長すぎるため、有効期限を計算できません。",
+            name,
+            seconds
+        )
+    })?;
 
+    Ok(seconds)
+}
+
+impl TokensSettings {
     /// アクセストークンの有効秒数を返却する。
     ///
     /// # Returns
@@ -231,6 +835,61 @@ impl TokensSettings {
     pub fn refresh_token_duration(&self) -> u64 {
         self.refresh_token_duration.as_seconds_f64() as u64
     }
+
+    /// 現在日時に加算できることを確認したアクセストークンの有効秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// アクセストークンの有効秒数。有効期間が0以下の場合や、現在日時に加算するとオーバーフローする場合は
+    /// エラー。
+    pub fn access_token_duration_secs(&self) -> anyhow::Result<u64> {
+        token_duration_secs(
+            "アクセストークン",
+            self.access_token_duration,
+            current_unix_epoch(),
+        )
+    }
+
+    /// 現在日時に加算できることを確認したリフレッシュトークンの有効秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンの有効秒数。有効期間が0以下の場合や、現在日時に加算するとオーバーフローする場合は
+    /// エラー。
+    pub fn refresh_token_duration_secs(&self) -> anyhow::Result<u64> {
+        token_duration_secs(
+            "リフレッシュトークン",
+            self.refresh_token_duration,
+            current_unix_epoch(),
+        )
+    }
+
+    /// スライディング延長する秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンの残りの有効秒数がこの秒数以下になったときにスライディング延長する秒数。
+    pub fn sliding_renewal_duration(&self) -> u64 {
+        self.sliding_renewal_duration.as_seconds_f64() as u64
+    }
+
+    /// 有効期限が切れたリフレッシュトークンを削除する秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限が切れたリフレッシュトークンを削除する秒数。
+    pub fn refresh_token_cleanup_interval(&self) -> u64 {
+        self.refresh_token_cleanup_interval.as_seconds_f64() as u64
+    }
+
+    /// 再認証せずにパスワードの変更を許可する秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 再認証せずにパスワードの変更を許可する秒数。
+    pub fn reauthentication_window(&self) -> u64 {
+        self.reauthentication_window.as_seconds_f64() as u64
+    }
 }
 
 /// SessionStore設定構造体
@@ -238,17 +897,91 @@ impl TokensSettings {
 pub struct SessionStoreSettings {
     pub uri: Secret<String>,
     pub key: Secret<String>,
+    /// 以前にセッションIDのクッキーを暗号化していた鍵
+    ///
+    /// 鍵を更新した後も、以前の鍵で暗号化されたクッキーを受け付けて、現在の鍵で暗号化し直す。
+    pub legacy_keys: Vec<Secret<String>>,
+    /// セッションの最終アクセス日時を更新して、セッションの有効期限を延長する最小の間隔
+    ///
+    /// 前回の更新からこの間隔が経過していない場合は、Redisへの書き込みを省略する。
+    pub touch_interval: Duration,
+    /// Redisに記録するセッションデータのキーに付与する接頭辞
+    ///
+    /// 同じRedisを複数のWebアプリで共有する場合に、セッションデータのキーが衝突しないように指定する。
+    pub key_prefix: String,
+    /// セッションデータを暗号化する暗号鍵
+    ///
+    /// 32バイトの鍵をBase64でエンコードした文字列で、指定した場合はセッションデータをAES-256-GCMで暗号化して
+    /// Redisに記録する。
+    pub encryption_key: Option<Secret<String>>,
+    /// Redisに接続するときのタイムアウト
+    ///
+    /// Webアプリの起動時に、この時間内にRedisに接続できない場合は、Webアプリの構築を中止する。
+    pub connect_timeout: Duration,
+    /// Redisのコマンドのタイムアウト
+    ///
+    /// セッションデータの読み込みや書き込みが、この時間内に完了しない場合はエラーとする。
+    pub command_timeout: Duration,
+    /// 冪等キーとリクエストの処理結果をRedisに記録する期間
+    ///
+    /// この期間内に同じ冪等キーを指定したリクエストを受け取った場合は、記録した処理結果で応答する。
+    pub idempotency_key_ttl: Duration,
 }
 
-impl SessionStoreSettings {
-    pub fn default() -> Self {
+impl Default for SessionStoreSettings {
+    fn default() -> Self {
         Self {
             uri: ENV_VALUES.session_store_uri.clone(),
             key: ENV_VALUES.session_store_key.clone(),
+            legacy_keys: ENV_VALUES.session_store_legacy_keys.clone(),
+            touch_interval: ENV_VALUES.session_touch_interval,
+            key_prefix: ENV_VALUES.session_store_key_prefix.clone(),
+            encryption_key: ENV_VALUES.session_data_encryption_key.clone(),
+            connect_timeout: ENV_VALUES.session_store_connect_timeout,
+            command_timeout: ENV_VALUES.session_store_command_timeout,
+            idempotency_key_ttl: ENV_VALUES.idempotency_key_ttl,
         }
     }
 }
 
+impl SessionStoreSettings {
+    /// セッションの最終アクセス日時を更新する間隔の秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// セッションの最終アクセス日時を更新する間隔の秒数。
+    pub fn touch_interval(&self) -> u64 {
+        self.touch_interval.as_seconds_f64() as u64
+    }
+
+    /// Redisに接続するときのタイムアウトを返却する。
+    ///
+    /// # Returns
+    ///
+    /// Redisに接続するときのタイムアウト。
+    pub fn connect_timeout(&self) -> std::time::Duration {
+        std::time::Duration::try_from(self.connect_timeout).unwrap_or_default()
+    }
+
+    /// Redisのコマンドのタイムアウトを返却する。
+    ///
+    /// # Returns
+    ///
+    /// Redisのコマンドのタイムアウト。
+    pub fn command_timeout(&self) -> std::time::Duration {
+        std::time::Duration::try_from(self.command_timeout).unwrap_or_default()
+    }
+
+    /// 冪等キーとリクエストの処理結果を記録する期間を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 冪等キーとリクエストの処理結果を記録する期間。
+    pub fn idempotency_key_ttl(&self) -> std::time::Duration {
+        std::time::Duration::try_from(self.idempotency_key_ttl).unwrap_or_default()
+    }
+}
+
 /// データベース設定構造体
 #[derive(Debug, Clone)]
 pub struct DatabaseSettings {
@@ -257,24 +990,31 @@ pub struct DatabaseSettings {
     pub host: String,
     pub port: u16,
     pub database_name: String,
+    /// 起動時に、適用されていないマイグレーションを適用するかどうか
+    ///
+    /// `false`の場合は、適用されていないマイグレーションがあれば、Webアプリを起動しない。
+    pub auto_migrate: bool,
 }
 
-impl DatabaseSettings {
+impl Default for DatabaseSettings {
     /// 環境変数からデータベース設定を構築する。
     ///
     /// # Returns
     ///
     /// データベース設定インスタンス。
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             username: ENV_VALUES.postgres_user_name.clone(),
             password: ENV_VALUES.postgres_user_password.clone(),
             host: ENV_VALUES.postgres_host.clone(),
             port: ENV_VALUES.postgres_port,
             database_name: ENV_VALUES.postgres_database_name.clone(),
+            auto_migrate: ENV_VALUES.auto_migrate,
         }
     }
+}
 
+impl DatabaseSettings {
     /// template1データベースに接続するオプションを返却する。
     ///
     /// # Returns
@@ -300,3 +1040,869 @@ impl DatabaseSettings {
         options
     }
 }
+
+/// WebAuthn設定構造体
+#[derive(Debug, Clone)]
+pub struct WebAuthnSettings {
+    /// リライングパーティーID
+    ///
+    /// パスキーをバインドするドメインで、省略した場合はWebアプリのホストとする。
+    pub rp_id: String,
+    /// 認証器に表示するリライングパーティー名
+    pub rp_name: String,
+    /// パスキーの登録及び認証を許可するオリジン
+    ///
+    /// 省略した場合は、Webアプリのホストとポートから構築する。
+    pub origin: String,
+}
+
+impl Default for WebAuthnSettings {
+    /// 環境変数からWebAuthn設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// WebAuthn設定インスタンス。
+    fn default() -> Self {
+        let scheme = match ENV_VALUES.web_app_tls_cert_path {
+            Some(_) => "https",
+            None => "http",
+        };
+
+        Self {
+            rp_id: ENV_VALUES
+                .webauthn_rp_id
+                .clone()
+                .unwrap_or_else(|| ENV_VALUES.web_app_host.clone()),
+            rp_name: ENV_VALUES.webauthn_rp_name.clone(),
+            origin: ENV_VALUES.webauthn_origin.clone().unwrap_or_else(|| {
+                format!(
+                    "{}://{}:{}",
+                    scheme, ENV_VALUES.web_app_host, ENV_VALUES.web_app_port
+                )
+            }),
+        }
+    }
+}
+
+/// TOTP設定構造体
+#[derive(Debug, Clone)]
+pub struct TotpSettings {
+    /// 認証アプリに表示するサービス名
+    pub issuer: String,
+}
+
+impl Default for TotpSettings {
+    /// 環境変数からTOTP設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// TOTP設定インスタンス。
+    fn default() -> Self {
+        Self {
+            issuer: ENV_VALUES.totp_issuer.clone(),
+        }
+    }
+}
+
+/// 管理者設定構造体
+#[derive(Debug, Clone)]
+pub struct AdminSettings {
+    /// 管理者APIを呼び出すときに`X-Admin-Api-Key`ヘッダーに指定するAPIキー
+    ///
+    /// 設定されていない場合は、管理者APIを使用できない。
+    pub api_key: Option<Secret<String>>,
+}
+
+impl Default for AdminSettings {
+    /// 環境変数から管理者設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// 管理者設定インスタンス。
+    fn default() -> Self {
+        Self {
+            api_key: ENV_VALUES.admin_api_key.clone(),
+        }
+    }
+}
+
+/// サインアップ設定構造体
+#[derive(Debug, Clone)]
+pub struct SignupSettings {
+    /// サインアップを受け付けるか
+    ///
+    /// `false`の場合は、招待制での運用やメンテナンス中など、利用者によるユーザーの登録を停止する。
+    pub enabled: bool,
+    /// Eメールアドレスが登録されているかを秘匿するプライバシーモード
+    ///
+    /// `true`の場合は、Eメールアドレスが既に登録されていても、サインアップに成功したときと同じ
+    /// `202 Accepted`で応答して、Eメールアドレスの列挙を防ぐ。
+    pub privacy_mode: bool,
+    /// サインアップを拒否する使い捨てEメールアドレスのドメイン
+    ///
+    /// 小文字で記録する。使い捨てEメールアドレスを拒否しない場合は空。
+    pub disposable_email_domains: HashSet<String>,
+}
+
+impl Default for SignupSettings {
+    /// 環境変数からサインアップ設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// サインアップ設定インスタンス。
+    fn default() -> Self {
+        let disposable_email_domains = if ENV_VALUES.signup_block_disposable_email {
+            disposable_email_domains_from_path(ENV_VALUES.disposable_email_domains_path.as_deref())
+        } else {
+            HashSet::new()
+        };
+
+        Self {
+            enabled: ENV_VALUES.signup_enabled,
+            privacy_mode: ENV_VALUES.signup_privacy_mode,
+            disposable_email_domains,
+        }
+    }
+}
+
+impl SignupSettings {
+    /// 使い捨てEメールアドレスのドメインか確認する。
+    ///
+    /// 大文字と小文字を区別せずに比較して、サインアップを拒否するドメインのサブドメインも使い捨てEメール
+    /// アドレスのドメインとみなす。
+    ///
+    /// # Arguments
+    ///
+    /// * `email_address` - Eメールアドレス。
+    ///
+    /// # Returns
+    ///
+    /// 使い捨てEメールアドレスの場合は`true`。
+    pub fn is_disposable_email(&self, email_address: &str) -> bool {
+        if self.disposable_email_domains.is_empty() {
+            return false;
+        }
+        let domain = match email_address.rsplit_once('@') {
+            Some((_, domain)) => domain.to_lowercase(),
+            None => return false,
+        };
+        // ドメインと、ドメインの親ドメインを順に確認
+        let mut candidate = domain.as_str();
+        loop {
+            if self.disposable_email_domains.contains(candidate) {
+                return true;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// 組み込みの使い捨てEメールアドレスのドメインのリスト
+const DEFAULT_DISPOSABLE_EMAIL_DOMAINS: &str = include_str!("../disposable_email_domains.txt");
+
+/// 使い捨てEメールアドレスのドメインのリストを解析する。
+///
+/// 1行に1つのドメインを記述したリストから、`#`で始まる行と空行を無視して、ドメインを小文字で取得する。
+///
+/// # Arguments
+///
+/// * `list` - 使い捨てEメールアドレスのドメインのリスト。
+///
+/// # Returns
+///
+/// 使い捨てEメールアドレスのドメイン。
+fn parse_disposable_email_domains(list: &str) -> HashSet<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// 使い捨てEメールアドレスのドメインのリストを読み込む。
+///
+/// # Arguments
+///
+/// * `path` - 使い捨てEメールアドレスのドメインのリストを記述したファイルのパス。`None`の場合は、組み込みの
+///   リストを使用する。
+///
+/// # Returns
+///
+/// 使い捨てEメールアドレスのドメイン。
+fn disposable_email_domains_from_path(path: Option<&str>) -> HashSet<String> {
+    match path {
+        Some(path) => parse_disposable_email_domains(&fs::read_to_string(path).unwrap_or_else(
+            |e| {
+                panic!(
+                    "環境変数DISPOSABLE_EMAIL_DOMAINS_PATHに設定されたファイル({})を読み込めません。{}",
+                    path, e
+                )
+            },
+        )),
+        None => parse_disposable_email_domains(DEFAULT_DISPOSABLE_EMAIL_DOMAINS),
+    }
+}
+
+/// ユーザーキャッシュ設定構造体
+///
+/// 認証ミドルウェアは、保護されたリソースへのリクエストごとにデータベースからユーザーを取得するため、
+/// 有効にした場合は、取得したユーザーを短い期間メモリにキャッシュして、データベースへの問い合わせを減らす。
+#[derive(Debug, Clone)]
+pub struct UserCacheSettings {
+    /// ユーザーキャッシュを有効にするか
+    pub enabled: bool,
+    /// キャッシュに記録するユーザー数の上限
+    ///
+    /// 上限に達した場合は、最も長い間参照されていないユーザーをキャッシュから削除する。
+    pub capacity: usize,
+    /// キャッシュしたユーザーの有効期間
+    pub ttl: Duration,
+}
+
+impl UserCacheSettings {
+    /// キャッシュしたユーザーの有効期間の秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// キャッシュしたユーザーの有効期間の秒数。
+    pub fn ttl(&self) -> u64 {
+        self.ttl.as_seconds_f64() as u64
+    }
+}
+
+impl Default for UserCacheSettings {
+    /// 環境変数からユーザーキャッシュ設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーキャッシュ設定インスタンス。
+    fn default() -> Self {
+        Self {
+            enabled: ENV_VALUES.user_cache_enabled,
+            capacity: ENV_VALUES.user_cache_capacity,
+            ttl: ENV_VALUES.user_cache_ttl,
+        }
+    }
+}
+
+/// Webhookの送信を試行する回数の既定値
+const DEFAULT_WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+/// Webhookの送信に失敗してから再送するまでの秒数の既定値
+const DEFAULT_WEBHOOK_RETRY_INTERVAL_SECONDS: i64 = 1;
+/// Webhookの送信がタイムアウトするまでの秒数の既定値
+const DEFAULT_WEBHOOK_TIMEOUT_SECONDS: i64 = 5;
+
+/// Webhook設定構造体
+///
+/// サインアップやパスワードの変更など、アカウントのライフサイクルのイベントを外部のサービスに通知する。
+#[derive(Debug, Clone)]
+pub struct WebhookSettings {
+    /// イベントを送信するURL
+    ///
+    /// 設定されていない場合は、イベントを送信しない。
+    pub url: Option<String>,
+    /// イベントのペイロードに署名するHMAC-SHA256の秘密鍵
+    ///
+    /// URLを設定した場合は、必ず設定する必要がある。
+    pub secret: Option<Secret<String>>,
+    /// 送信に失敗したときに再送する回数を含めた、送信を試行する回数
+    pub max_attempts: u32,
+    /// 送信に失敗してから再送するまでの間隔
+    pub retry_interval: Duration,
+    /// 送信がタイムアウトするまでの期間
+    pub timeout: Duration,
+}
+
+impl WebhookSettings {
+    /// Webhookの設定を検証する。
+    ///
+    /// # Returns
+    ///
+    /// URLを設定して、署名する秘密鍵を設定していない場合はエラー。
+    pub fn verify(&self) -> anyhow::Result<()> {
+        if self.url.is_some() && self.secret.is_none() {
+            bail!("環境変数WEBHOOK_URLを設定した場合は、WEBHOOK_SECRETを設定してください。");
+        }
+
+        Ok(())
+    }
+
+    /// 送信に失敗してから再送するまでの秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 送信に失敗してから再送するまでの秒数。
+    pub fn retry_interval(&self) -> u64 {
+        self.retry_interval.as_seconds_f64() as u64
+    }
+
+    /// 送信がタイムアウトするまでの秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 送信がタイムアウトするまでの秒数。
+    pub fn timeout(&self) -> u64 {
+        self.timeout.as_seconds_f64() as u64
+    }
+}
+
+impl Default for WebhookSettings {
+    /// 環境変数からWebhook設定を構築する。
+    ///
+    /// # Returns
+    ///
+    /// Webhook設定インスタンス。
+    fn default() -> Self {
+        Self {
+            url: ENV_VALUES.webhook_url.clone(),
+            secret: ENV_VALUES.webhook_secret.clone(),
+            max_attempts: ENV_VALUES.webhook_max_attempts,
+            retry_interval: ENV_VALUES.webhook_retry_interval,
+            timeout: ENV_VALUES.webhook_timeout,
+        }
+    }
+}
+
+/// Argon2設定構造体
+#[derive(Debug, Clone)]
+pub struct Argon2Settings {
+    /// パスワードをハッシュ化するArgon2のアルゴリズム
+    ///
+    /// パスワードの検証は、ハッシュ化したパスワードのPHC文字列に記録されたアルゴリズムで実施する。
+    pub algorithm: Algorithm,
+    /// パスワードのハッシュ化と検証を同時に実行するスレッドの数
+    ///
+    /// ログインが集中しても、他のブロッキング処理に使用するスレッドが不足しないように、パスワードの
+    /// ハッシュ化と検証は、この数を超えて同時に実行しない。
+    pub pool_size: usize,
+    /// Argon2が出力するハッシュのバイト数
+    ///
+    /// パスワードの検証は、ハッシュ化したパスワードのPHC文字列に記録されたハッシュの長さで実施するため、
+    /// 変更しても既存のパスワードを検証できる。
+    pub output_len: usize,
+    /// パスワードに付与するソルトのバイト数
+    pub salt_len: usize,
+}
+
+/// Argon2が出力するハッシュのバイト数の既定値
+pub const DEFAULT_ARGON2_OUTPUT_LEN: usize = 32;
+/// Argon2が出力するハッシュのバイト数の最小値
+const MIN_ARGON2_OUTPUT_LEN: usize = 16;
+/// Argon2が出力するハッシュのバイト数の最大値
+const MAX_ARGON2_OUTPUT_LEN: usize = 64;
+/// パスワードに付与するソルトのバイト数の既定値
+pub const DEFAULT_ARGON2_SALT_LEN: usize = 16;
+/// パスワードに付与するソルトのバイト数の最小値
+const MIN_ARGON2_SALT_LEN: usize = 16;
+/// パスワードに付与するソルトのバイト数の最大値
+///
+/// PHC文字列に記録できるソルトは、Base64でエンコードして64文字以下であるため、48バイト以下とする。
+const MAX_ARGON2_SALT_LEN: usize = 48;
+
+/// パスワードのハッシュ化と検証を同時に実行するスレッドの数の既定値を返却する。
+///
+/// # Returns
+///
+/// 利用可能なCPUの数。取得できない場合は`1`。
+fn default_argon2_pool_size() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+impl Argon2Settings {
+    /// 環境変数からArgon2設定を読み込む。
+    ///
+    /// # Returns
+    ///
+    /// Argon2設定インスタンス。環境変数に不正な値が設定されている場合はエラー。
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            algorithm: argon2_algorithm_from_env_or("ARGON2_VARIANT", Algorithm::Argon2id)?,
+            pool_size: try_usize_from_env_or("ARGON2_POOL_SIZE", default_argon2_pool_size())?
+                .max(1),
+            output_len: bounded_usize_from_env_or(
+                "ARGON2_OUTPUT_LEN",
+                DEFAULT_ARGON2_OUTPUT_LEN,
+                MIN_ARGON2_OUTPUT_LEN,
+                MAX_ARGON2_OUTPUT_LEN,
+            )?,
+            salt_len: bounded_usize_from_env_or(
+                "ARGON2_SALT_LEN",
+                DEFAULT_ARGON2_SALT_LEN,
+                MIN_ARGON2_SALT_LEN,
+                MAX_ARGON2_SALT_LEN,
+            )?,
+        })
+    }
+}
+
+/// Argon2設定
+///
+/// パスワードのハッシュ化は、システム設定を受け取らないドメインモデルから呼び出されるため、他の設定とは
+/// 別に環境変数から読み込む。環境変数に不正な値が設定されている場合は、最初にパスワードをハッシュ化した
+/// ときにパニックしないように、読み込みのエラーを記録して、Webアプリの構築時に`argon2_settings`で検証する。
+pub static ARGON2_SETTINGS: Lazy<anyhow::Result<Argon2Settings>> =
+    Lazy::new(Argon2Settings::from_env);
+
+/// 環境変数から読み込んだArgon2設定を返却する。
+///
+/// # Returns
+///
+/// Argon2設定。環境変数に不正な値が設定されている場合はエラー。
+pub fn argon2_settings() -> anyhow::Result<&'static Argon2Settings> {
+    ARGON2_SETTINGS
+        .as_ref()
+        .map_err(|e| anyhow!("Argon2設定を読み込めません。{}", e))
+}
+
+/// 予約されたユーザー名の既定値
+const DEFAULT_RESERVED_USER_NAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "support",
+    "system",
+    "sysadmin",
+    "webmaster",
+    "postmaster",
+    "security",
+    "staff",
+];
+
+/// ユーザー名設定構造体
+#[derive(Debug, Clone)]
+pub struct UserNameSettings {
+    /// ユーザーが使用できない予約されたユーザー名
+    ///
+    /// 大文字と小文字を区別せずに比較する。
+    pub reserved_names: Vec<String>,
+}
+
+impl Default for UserNameSettings {
+    fn default() -> Self {
+        Self {
+            reserved_names: list_from_env_or("RESERVED_USER_NAMES", DEFAULT_RESERVED_USER_NAMES),
+        }
+    }
+}
+
+/// ユーザー名設定
+///
+/// ユーザー名の検証は、システム設定を受け取らないドメインモデルで実施するため、他の設定とは別に環境変数から
+/// 読み込む。
+pub static USER_NAME_SETTINGS: Lazy<UserNameSettings> = Lazy::new(UserNameSettings::default);
+
+/// パスワードの最小文字数の既定値
+///
+/// パスワードの最小文字数には、この文字数より短い文字数を設定できない。
+const DEFAULT_RAW_PASSWORD_MIN_LEN: usize = 8;
+
+/// パスワードの最大文字数の既定値
+const DEFAULT_RAW_PASSWORD_MAX_LEN: usize = 256;
+
+/// パスワード設定構造体
+#[derive(Debug, Clone)]
+pub struct RawPasswordSettings {
+    /// パスワードの最小文字数
+    ///
+    /// パスワードのポリシーを強化するために最小文字数を増やした場合、既存のユーザーのパスワードはログイン時に
+    /// ポリシーを満たしているか確認する。
+    pub min_len: usize,
+    /// パスワードの最大文字数
+    ///
+    /// 非常に長いパスワードをArgon2でハッシュ化すると処理に時間がかかるため、ハッシュ化する前にこの文字数を超える
+    /// パスワードを拒否する。
+    pub max_len: usize,
+}
+
+impl Default for RawPasswordSettings {
+    fn default() -> Self {
+        let min_len = usize_from_env_or("RAW_PASSWORD_MIN_LEN", DEFAULT_RAW_PASSWORD_MIN_LEN);
+        let max_len = usize_from_env_or("RAW_PASSWORD_MAX_LEN", DEFAULT_RAW_PASSWORD_MAX_LEN);
+        if min_len < DEFAULT_RAW_PASSWORD_MIN_LEN {
+            panic!(
+                "環境変数RAW_PASSWORD_MIN_LENには、{}以上を設定してください。",
+                DEFAULT_RAW_PASSWORD_MIN_LEN
+            );
+        }
+        if max_len < min_len {
+            panic!(
+                "環境変数RAW_PASSWORD_MAX_LENには、RAW_PASSWORD_MIN_LEN以上を設定してください。"
+            );
+        }
+
+        Self { min_len, max_len }
+    }
+}
+
+/// パスワード設定
+///
+/// パスワードの検証は、システム設定を受け取らないドメインモデルで実施するため、他の設定とは別に環境変数から
+/// 読み込む。
+pub static RAW_PASSWORD_SETTINGS: Lazy<RawPasswordSettings> =
+    Lazy::new(RawPasswordSettings::default);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の秘密の値を記録したファイルを作成して、そのパスを返却する。
+    fn write_secret_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn secret_from_env_reads_direct_value() {
+        env::set_var("TEST_DIRECT_SECRET", "direct-value");
+        let secret = secret_from_env("TEST_DIRECT_SECRET");
+        assert_eq!(secret.expose_secret(), "direct-value");
+    }
+
+    #[test]
+    fn secret_from_env_reads_file_value() {
+        let path = write_secret_file("test_file_secret", "file-value\n");
+        env::set_var("TEST_FILE_SECRET_FILE", &path);
+        let secret = secret_from_env("TEST_FILE_SECRET");
+        assert_eq!(secret.expose_secret(), "file-value");
+    }
+
+    #[test]
+    fn secret_from_env_prefers_file_value() {
+        let path = write_secret_file("test_both_secret", "file-value\r\n");
+        env::set_var("TEST_BOTH_SECRET", "direct-value");
+        env::set_var("TEST_BOTH_SECRET_FILE", &path);
+        let secret = secret_from_env("TEST_BOTH_SECRET");
+        assert_eq!(secret.expose_secret(), "file-value");
+    }
+
+    #[test]
+    #[should_panic]
+    fn secret_from_env_panics_when_file_does_not_exist() {
+        env::set_var("TEST_MISSING_SECRET", "direct-value");
+        env::set_var("TEST_MISSING_SECRET_FILE", "/path/to/missing/secret");
+        secret_from_env("TEST_MISSING_SECRET");
+    }
+
+    /// 使い捨てEメールアドレスのドメインを拒否して、それ以外のドメインを許可することを確認するテスト
+    #[test]
+    fn is_disposable_email_rejects_blocked_domains() {
+        let settings = SignupSettings {
+            enabled: true,
+            privacy_mode: false,
+            disposable_email_domains: parse_disposable_email_domains(
+                DEFAULT_DISPOSABLE_EMAIL_DOMAINS,
+            ),
+        };
+        for email_address in [
+            "foo@mailinator.com",
+            "foo@MailInator.COM",
+            "foo@sub.mailinator.com",
+            "foo@yopmail.com",
+        ] {
+            assert!(
+                settings.is_disposable_email(email_address),
+                "{}",
+                email_address
+            );
+        }
+        for email_address in [
+            "foo@example.com",
+            "foo@gmail.com",
+            "foo@mailinator.com.example.com",
+            "foo@notmailinator.com",
+        ] {
+            assert!(
+                !settings.is_disposable_email(email_address),
+                "{}",
+                email_address
+            );
+        }
+
+        // 拒否しない設定の場合は、すべてのドメインを許可
+        let settings = SignupSettings {
+            disposable_email_domains: HashSet::new(),
+            ..settings
+        };
+        assert!(!settings.is_disposable_email("foo@mailinator.com"));
+    }
+
+    /// 使い捨てEメールアドレスのドメインのリストから、コメントと空行を無視してドメインを取得することを
+    /// 確認するテスト
+    #[test]
+    fn parse_disposable_email_domains_ignores_comments() {
+        let domains = parse_disposable_email_domains("# comment\n\n  Example.ORG \nexample.net\n");
+        assert_eq!(
+            domains,
+            HashSet::from(["example.org".to_owned(), "example.net".to_owned()])
+        );
+    }
+
+    #[test]
+    fn token_duration_secs_accepts_positive_duration() {
+        assert_eq!(
+            token_duration_secs("アクセストークン", Duration::seconds(300), 1_000).unwrap(),
+            300
+        );
+    }
+
+    #[test]
+    fn token_duration_secs_rejects_non_positive_duration() {
+        for seconds in [-300, 0] {
+            assert!(
+                token_duration_secs("アクセストークン", Duration::seconds(seconds), 1_000).is_err(),
+                "{}",
+                seconds
+            );
+        }
+    }
+
+    #[test]
+    fn token_duration_secs_rejects_overflowing_duration() {
+        assert!(token_duration_secs(
+            "リフレッシュトークン",
+            Duration::seconds(i64::MAX),
+            u64::MAX - i64::MAX as u64 + 1,
+        )
+        .is_err());
+        assert!(
+            token_duration_secs("リフレッシュトークン", Duration::seconds(60), u64::MAX - 60)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn list_from_env_or_splits_comma_separated_values() {
+        env::set_var("TEST_LIST", " admin, root ,,support ");
+        assert_eq!(
+            list_from_env_or("TEST_LIST", &[]),
+            vec!["admin", "root", "support"]
+        );
+        assert_eq!(
+            list_from_env_or("TEST_LIST_MISSING", &["admin"]),
+            vec!["admin"]
+        );
+    }
+
+    #[test]
+    fn inactive_user_status_from_env_parses_status_codes() {
+        env::set_var("TEST_INACTIVE_USER_STATUS_401", "401");
+        assert_eq!(
+            inactive_user_status_from_env_or(
+                "TEST_INACTIVE_USER_STATUS_401",
+                StatusCode::FORBIDDEN
+            ),
+            StatusCode::UNAUTHORIZED
+        );
+        env::set_var("TEST_INACTIVE_USER_STATUS_403", " 403 ");
+        assert_eq!(
+            inactive_user_status_from_env_or(
+                "TEST_INACTIVE_USER_STATUS_403",
+                StatusCode::UNAUTHORIZED
+            ),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            inactive_user_status_from_env_or(
+                "TEST_INACTIVE_USER_STATUS_MISSING",
+                StatusCode::FORBIDDEN
+            ),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn inactive_user_status_from_env_rejects_other_status_codes() {
+        env::set_var("TEST_INACTIVE_USER_STATUS_500", "500");
+        inactive_user_status_from_env_or("TEST_INACTIVE_USER_STATUS_500", StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn trusted_proxies_from_env_parses_cidrs_and_addresses() {
+        env::set_var("TEST_TRUSTED_PROXIES", "10.0.0.0/8, 192.0.2.1,::1");
+        assert_eq!(
+            trusted_proxies_from_env("TEST_TRUSTED_PROXIES"),
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "192.0.2.1/32".parse().unwrap(),
+                "::1/128".parse().unwrap(),
+            ]
+        );
+        assert!(trusted_proxies_from_env("TEST_TRUSTED_PROXIES_MISSING").is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn trusted_proxies_from_env_rejects_invalid_cidr() {
+        env::set_var("TEST_TRUSTED_PROXIES_INVALID", "10.0.0.0/33");
+        trusted_proxies_from_env("TEST_TRUSTED_PROXIES_INVALID");
+    }
+
+    #[test]
+    fn app_environment_from_env_parses_environments() {
+        for (value, expected) in [
+            ("production", AppEnvironment::Production),
+            ("Development", AppEnvironment::Development),
+        ] {
+            env::set_var("TEST_APP_ENV", value);
+            assert_eq!(
+                app_environment_from_env_or("TEST_APP_ENV", AppEnvironment::Development),
+                expected
+            );
+        }
+        assert_eq!(
+            app_environment_from_env_or("TEST_APP_ENV_MISSING", AppEnvironment::Development),
+            AppEnvironment::Development
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn app_environment_from_env_rejects_unknown_environment() {
+        env::set_var("TEST_APP_ENV_UNKNOWN", "staging");
+        app_environment_from_env_or("TEST_APP_ENV_UNKNOWN", AppEnvironment::Development);
+    }
+
+    /// 本番環境では、安全ではないクッキー設定を拒否して、開発環境では`Secure`属性を付与しない`SameSite=None`
+    /// 以外を許可することを確認するテスト
+    #[test]
+    fn verify_session_cookie_security() {
+        let cookie = |secure, same_site| SessionCookieSettings {
+            session_id_cookie_name: "session_id".to_owned(),
+            secure,
+            same_site,
+            max_age_seconds: None,
+        };
+        for (secure, same_site, production_ok, development_ok) in [
+            (true, SameSite::Lax, true, true),
+            (true, SameSite::Strict, true, true),
+            (true, SameSite::None, false, true),
+            (false, SameSite::Lax, false, true),
+            (false, SameSite::Strict, false, true),
+            (false, SameSite::None, false, false),
+        ] {
+            let settings = cookie(secure, same_site);
+            assert_eq!(
+                settings.verify_security(AppEnvironment::Production).is_ok(),
+                production_ok,
+                "{:?}",
+                settings
+            );
+            assert_eq!(
+                settings
+                    .verify_security(AppEnvironment::Development)
+                    .is_ok(),
+                development_ok,
+                "{:?}",
+                settings
+            );
+        }
+        // Secure属性を付与しないSameSite=Noneは、理由を示すエラー
+        let e = cookie(false, SameSite::None)
+            .verify_security(AppEnvironment::Development)
+            .unwrap_err();
+        assert!(e.to_string().contains("SESSION_COOKIE_SECURE"), "{}", e);
+    }
+
+    /// WebhookのURLを設定して、署名する秘密鍵を設定していない場合は、設定を拒否することを確認するテスト
+    #[test]
+    fn verify_webhook_settings() {
+        let webhook = |url: Option<&str>, secret: Option<&str>| WebhookSettings {
+            url: url.map(str::to_owned),
+            secret: secret.map(|secret| Secret::new(secret.to_owned())),
+            max_attempts: 3,
+            retry_interval: Duration::seconds(1),
+            timeout: Duration::seconds(5),
+        };
+        assert!(webhook(None, None).verify().is_ok());
+        assert!(webhook(Some("https://example.com/hooks"), Some("secret"))
+            .verify()
+            .is_ok());
+        assert!(webhook(Some("https://example.com/hooks"), None)
+            .verify()
+            .is_err());
+    }
+
+    #[test]
+    fn token_mode_from_env_parses_modes() {
+        for (value, expected) in [
+            ("jwt", TokenMode::Jwt),
+            ("opaque", TokenMode::Opaque),
+            ("Opaque", TokenMode::Opaque),
+        ] {
+            env::set_var("TEST_TOKEN_MODE", value);
+            assert_eq!(
+                token_mode_from_env_or("TEST_TOKEN_MODE", TokenMode::Jwt),
+                expected
+            );
+        }
+        assert_eq!(
+            token_mode_from_env_or("TEST_TOKEN_MODE_MISSING", TokenMode::Jwt),
+            TokenMode::Jwt
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn token_mode_from_env_rejects_unknown_mode() {
+        env::set_var("TEST_TOKEN_MODE_UNKNOWN", "paseto");
+        token_mode_from_env_or("TEST_TOKEN_MODE_UNKNOWN", TokenMode::Jwt);
+    }
+
+    #[test]
+    fn argon2_algorithm_from_env_parses_variants() {
+        for (value, expected) in [
+            ("argon2id", Algorithm::Argon2id),
+            ("argon2i", Algorithm::Argon2i),
+            ("Argon2d", Algorithm::Argon2d),
+        ] {
+            env::set_var("TEST_ARGON2_VARIANT", value);
+            assert_eq!(
+                argon2_algorithm_from_env_or("TEST_ARGON2_VARIANT", Algorithm::Argon2id).unwrap(),
+                expected
+            );
+        }
+        assert_eq!(
+            argon2_algorithm_from_env_or("TEST_ARGON2_VARIANT_MISSING", Algorithm::Argon2i)
+                .unwrap(),
+            Algorithm::Argon2i
+        );
+    }
+
+    #[test]
+    fn argon2_algorithm_from_env_rejects_unknown_variant() {
+        env::set_var("TEST_ARGON2_VARIANT_UNKNOWN", "bcrypt");
+        assert!(
+            argon2_algorithm_from_env_or("TEST_ARGON2_VARIANT_UNKNOWN", Algorithm::Argon2id)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn bounded_usize_from_env_accepts_value_within_bounds() {
+        env::set_var("TEST_ARGON2_OUTPUT_LEN", "64");
+        assert_eq!(
+            bounded_usize_from_env_or("TEST_ARGON2_OUTPUT_LEN", 32, 16, 64).unwrap(),
+            64
+        );
+        assert_eq!(
+            bounded_usize_from_env_or("TEST_ARGON2_OUTPUT_LEN_MISSING", 32, 16, 64).unwrap(),
+            32
+        );
+    }
+
+    #[test]
+    fn bounded_usize_from_env_rejects_value_out_of_bounds() {
+        env::set_var("TEST_ARGON2_SALT_LEN_TOO_LONG", "49");
+        assert!(bounded_usize_from_env_or("TEST_ARGON2_SALT_LEN_TOO_LONG", 16, 16, 48).is_err());
+        env::set_var("TEST_ARGON2_SALT_LEN_NOT_NUMBER", "long");
+        assert!(bounded_usize_from_env_or("TEST_ARGON2_SALT_LEN_NOT_NUMBER", 16, 16, 48).is_err());
+    }
+}
@@ -4,17 +4,67 @@ use std::str::FromStr;
 use anyhow::anyhow;
 use hmac::{Hmac, Mac};
 use jwt::{SignWithKey, VerifyWithKey};
+use miscellaneous::current_unix_epoch;
+use rand::Rng;
 use secrecy::{ExposeSecret, Secret};
-use sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+/// ログに出力しないトークン
+///
+/// `Debug`と`Display`は、トークンの代わりに`***`を出力する。トークンの値が必要な場合は、`expose`で明示的に
+/// 取得する。
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RedactedToken(String);
+
+impl RedactedToken {
+    /// トークンインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - トークン。
+    ///
+    /// # Returns
+    ///
+    /// トークンインスタンス。
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// トークンの値を返却する。
+    ///
+    /// # Returns
+    ///
+    /// トークン。
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for RedactedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl std::fmt::Display for RedactedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
 /// 有効期限の開始を指定したJWTを生成する。
 ///
+/// 同じユーザーに同じ秒に発行したJWTが同じ値にならないように、`jti`クレームにランダムなIDを指定する。
+///
 /// # Arguments
 ///
 /// * `user_id` - ユーザーID。
 /// * `secret` - JWT生成鍵。
 /// * `expiration` - トークンの有効期限を示すUNIXエポック秒。
+/// * `audience` - `aud`クレームに指定するオーディエンス。`None`の場合は`aud`クレームを指定しない。
 ///
 /// # Returns
 ///
@@ -23,11 +73,16 @@ fn generate_jwt(
     user_id: Uuid,
     secret_key: &Secret<String>,
     expiration: u64,
+    audience: Option<&str>,
 ) -> anyhow::Result<String> {
     let key: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
     let mut claims = BTreeMap::new();
     claims.insert("sub", user_id.to_string());
     claims.insert("exp", expiration.to_string());
+    claims.insert("jti", Uuid::new_v4().to_string());
+    if let Some(audience) = audience {
+        claims.insert("aud", audience.to_owned());
+    }
 
     Ok(claims.sign_with_key(&key)?)
 }
@@ -40,6 +95,7 @@ fn generate_jwt(
 /// * `secret` - JWT生成鍵。
 /// * `access_expiration` - アクセストークンの有効期限を示すUNIXエポック秒。
 /// * `refresh_expiration` - リフレッシュトークンの有効期限を示すUNIXエポック秒。
+/// * `audience` - `aud`クレームに指定するオーディエンス。`None`の場合は`aud`クレームを指定しない。
 ///
 /// # Returns
 ///
@@ -49,30 +105,275 @@ pub fn generate_jwt_pair(
     secret_key: &Secret<String>,
     access_expiration: u64,
     refresh_expiration: u64,
+    audience: Option<&str>,
 ) -> anyhow::Result<(String, String)> {
     Ok((
-        generate_jwt(user_id, secret_key, access_expiration)?,
-        generate_jwt(user_id, secret_key, refresh_expiration)?,
+        generate_jwt(user_id, secret_key, access_expiration, audience)?,
+        generate_jwt(user_id, secret_key, refresh_expiration, audience)?,
     ))
 }
 
+/// リフレッシュトークンのハッシュを計算する。
+///
+/// データベースが漏洩してもリフレッシュトークンを使用できないように、リフレッシュトークンのHMAC-SHA256を
+/// 記録する。
+///
+/// # Arguments
+///
+/// * `refresh_token` - リフレッシュトークン。
+/// * `secret_key` - ハッシュを計算する鍵。
+///
+/// # Returns
+///
+/// リフレッシュトークンのハッシュを16進数で表現した文字列。
+pub fn refresh_token_hash(
+    refresh_token: &str,
+    secret_key: &Secret<String>,
+) -> anyhow::Result<String> {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
+    mac.update(refresh_token.as_bytes());
+
+    Ok(mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// 不透明トークンのバイト数
+const OPAQUE_TOKEN_BYTES: usize = 32;
+
+/// 不透明トークンを生成する。
+///
+/// # Returns
+///
+/// ランダムなバイト列を16進数で表現した不透明トークン。
+fn generate_opaque_token() -> String {
+    rand::thread_rng()
+        .gen::<[u8; OPAQUE_TOKEN_BYTES]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 不透明なアクセストークンとリフレッシュトークンを生成する。
+///
+/// 不透明トークンはユーザーIDや有効期限を含まないため、トークンを受け取ったときは、セッションストアに
+/// 記録したセッションデータと照合して検証する。
+///
+/// # Returns
+///
+/// アクセストークンとリフレッシュトークンを格納したタプル
+pub fn generate_opaque_token_pair() -> (String, String) {
+    (generate_opaque_token(), generate_opaque_token())
+}
+
+/// トークンのフィンガープリントの文字数
+const TOKEN_FINGERPRINT_LEN: usize = 8;
+
+/// トークンのフィンガープリントを返却する。
+///
+/// フィンガープリントは、トークンのSHA-256ハッシュを16進数で表現した文字列の先頭8文字である。
+/// トークン本体を露出せずに、クライアントが受け取ったトークンとサーバーのセッションを照合するために使用する。
+///
+/// # Arguments
+///
+/// * `token` - トークン。
+///
+/// # Returns
+///
+/// トークンのフィンガープリント。
+pub fn token_fingerprint(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()[..TOKEN_FINGERPRINT_LEN]
+        .to_owned()
+}
+
+/// パスワードリセットトークンのバイト数
+const PASSWORD_RESET_TOKEN_BYTES: usize = 32;
+
+/// パスワードリセットトークンを生成する。
+///
+/// # Returns
+///
+/// ランダムなバイト列を16進数で表現したパスワードリセットトークン。
+pub fn generate_password_reset_token() -> String {
+    rand::thread_rng()
+        .gen::<[u8; PASSWORD_RESET_TOKEN_BYTES]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// パスワードリセットトークンのハッシュを計算する。
+///
+/// トークンとパスワードをリセットするユーザーのメールアドレスを連結したメッセージのHMAC-SHA256を計算する。
+/// トークンをメールアドレスにバインドすることで、トークンが漏洩しても、別のメールアドレスでは
+/// 使用できないようにする。
+///
+/// # Arguments
+///
+/// * `token` - パスワードリセットトークン。
+/// * `email_address` - パスワードをリセットするユーザーのメールアドレス。
+/// * `secret_key` - ハッシュを計算する鍵。
+///
+/// # Returns
+///
+/// パスワードリセットトークンのハッシュを16進数で表現した文字列。
+pub fn password_reset_token_hash(
+    token: &str,
+    email_address: &str,
+    secret_key: &Secret<String>,
+) -> anyhow::Result<String> {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
+    mac.update(token.as_bytes());
+    mac.update(b"\0");
+    mac.update(email_address.as_bytes());
+
+    Ok(mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// 招待コードのバイト数
+const INVITE_CODE_BYTES: usize = 16;
+
+/// 招待コードを生成する。
+///
+/// # Returns
+///
+/// ランダムなバイト列を16進数で表現した招待コード。
+pub fn generate_invite_code() -> String {
+    rand::thread_rng()
+        .gen::<[u8; INVITE_CODE_BYTES]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 招待コードのハッシュを計算する。
+///
+/// データベースが漏洩しても招待コードを使用できないように、招待コードのHMAC-SHA256を記録する。
+///
+/// # Arguments
+///
+/// * `code` - 招待コード。
+/// * `secret_key` - ハッシュを計算する鍵。
+///
+/// # Returns
+///
+/// 招待コードのハッシュを16進数で表現した文字列。
+pub fn invite_code_hash(code: &str, secret_key: &Secret<String>) -> anyhow::Result<String> {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
+    mac.update(code.trim().to_ascii_lowercase().as_bytes());
+
+    Ok(mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// APIキーのバイト数
+const API_KEY_BYTES: usize = 32;
+
+/// APIキーを生成する。
+///
+/// # Returns
+///
+/// ランダムなバイト列を16進数で表現したAPIキー。
+pub fn generate_api_key() -> String {
+    rand::thread_rng()
+        .gen::<[u8; API_KEY_BYTES]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// APIキーのハッシュを計算する。
+///
+/// データベースが漏洩してもAPIキーを使用できないように、APIキーのHMAC-SHA256を記録する。
+///
+/// # Arguments
+///
+/// * `api_key` - APIキー。
+/// * `secret_key` - ハッシュを計算する鍵。
+///
+/// # Returns
+///
+/// APIキーのハッシュを16進数で表現した文字列。
+pub fn api_key_hash(api_key: &str, secret_key: &Secret<String>) -> anyhow::Result<String> {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
+    mac.update(api_key.trim().as_bytes());
+
+    Ok(mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
 /// クレーム構造体
 pub struct Claim {
     /// ユーザーID。
     pub user_id: Uuid,
     /// 有効期限を示すUNIXエポック秒。
     pub expiration: u64,
+    /// オーディエンス。
+    pub audience: Option<String>,
+}
+
+impl Claim {
+    /// 有効期限が切れているか確認する。
+    ///
+    /// サーバー間の時刻のずれを許容するため、有効期限に猶予秒数を加えた日時まで有効期限内として扱う。
+    ///
+    /// # Arguments
+    ///
+    /// * `leeway_secs` - 有効期限の猶予秒数。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限が切れている場合は`true`。
+    pub fn is_expired(&self, leeway_secs: u64) -> bool {
+        self.expiration.saturating_add(leeway_secs) < current_unix_epoch()
+    }
+
+    /// 有効期限までの残り秒数を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限までの残り秒数。有効期限が切れている場合は`0`。
+    pub fn remaining_secs(&self) -> u64 {
+        self.expiration.saturating_sub(current_unix_epoch())
+    }
 }
 
 /// JWTからクレームを取得する。
 ///
+/// オーディエンスを指定した場合は、JWTの`aud`クレームがいずれかのオーディエンスと一致するか確認して、
+/// `aud`クレームを含まないJWTや、異なるオーディエンスのJWTを拒否する。
+///
 /// * `token` - JWT。
 /// * `secret` - JWT生成鍵。
+/// * `audiences` - 受け付けるオーディエンス。空の場合は`aud`クレームを検証しない。
 ///
 /// # Returns
 ///
 /// クレーム。
-pub fn get_claim_from_jwt(token: &str, secret_key: &Secret<String>) -> anyhow::Result<Claim> {
+pub fn get_claim_from_jwt(
+    token: &str,
+    secret_key: &Secret<String>,
+    audiences: &[String],
+) -> anyhow::Result<Claim> {
     let key: Hmac<Sha256> = Hmac::new_from_slice(secret_key.expose_secret().as_bytes())?;
     let claims: BTreeMap<String, String> = token.verify_with_key(&key)?;
     // ユーザーIDを取得
@@ -88,17 +389,31 @@ pub fn get_claim_from_jwt(token: &str, secret_key: &Secret<String>) -> anyhow::R
         .ok_or_else(|| anyhow!("JWTにexpが含まれていません。"))?
         .parse()
         .map_err(|_| anyhow!("JWTに含まれている有効期限が不正です。"))?;
+    // オーディエンスを確認
+    let audience = claims.get("aud").cloned();
+    if !audiences.is_empty() {
+        match &audience {
+            Some(audience) if audiences.contains(audience) => {}
+            Some(audience) => {
+                return Err(anyhow!(
+                    "JWTのオーディエンス({})を受け付けません。",
+                    audience
+                ))
+            }
+            None => return Err(anyhow!("JWTにaudが含まれていません。")),
+        }
+    }
 
     Ok(Claim {
         user_id,
         expiration,
+        audience,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use miscellaneous::current_unix_epoch;
     use uuid::Uuid;
 
     /// JWTを正常に生成できることを確認するテスト
@@ -109,11 +424,83 @@ mod tests {
         let secret_key = Secret::new("some-secret".to_owned());
         let now = current_unix_epoch();
         let duration: u64 = 300;
-        let token = generate_jwt(user_id, &secret_key, now + duration).unwrap();
+        let token = generate_jwt(user_id, &secret_key, now + duration, None).unwrap();
         // JWTを検証
-        let claim = get_claim_from_jwt(&token, &secret_key).unwrap();
+        let claim = get_claim_from_jwt(&token, &secret_key, &[]).unwrap();
         assert_eq!(claim.user_id, user_id);
         assert_eq!(claim.expiration, now + duration);
+        assert!(claim.audience.is_none());
+    }
+
+    /// 同じユーザーに同じ有効期限で生成したJWTが、異なる値になることを確認するテスト
+    #[test]
+    fn test_generate_jwt_is_unique() {
+        let user_id = Uuid::new_v4();
+        let secret_key = Secret::new("some-secret".to_owned());
+        let expiration = current_unix_epoch() + 300;
+        let first = generate_jwt(user_id, &secret_key, expiration, None).unwrap();
+        let second = generate_jwt(user_id, &secret_key, expiration, None).unwrap();
+        assert_ne!(first, second);
+    }
+
+    /// 有効期限を指定したクレームを構築する。
+    fn claim_expiring_at(expiration: u64) -> Claim {
+        Claim {
+            user_id: Uuid::new_v4(),
+            expiration,
+            audience: None,
+        }
+    }
+
+    /// 有効期限が切れたクレームは、猶予秒数を超えて切れている場合に期限切れと判定することを確認するテスト
+    #[test]
+    fn test_claim_is_expired() {
+        let now = current_unix_epoch();
+        // 有効期限が切れている
+        let claim = claim_expiring_at(now - 60);
+        assert!(claim.is_expired(0));
+        assert!(claim.is_expired(30));
+        assert_eq!(claim.remaining_secs(), 0);
+        // 有効期限は切れているが、猶予秒数内
+        assert!(!claim.is_expired(120));
+    }
+
+    /// 有効期限内のクレームは、期限切れと判定せずに残り秒数を返却することを確認するテスト
+    #[test]
+    fn test_claim_is_not_expired() {
+        let now = current_unix_epoch();
+        let claim = claim_expiring_at(now + 300);
+        assert!(!claim.is_expired(0));
+        let remaining = claim.remaining_secs();
+        // テストの実行中に秒が進む場合を考慮
+        assert!((299..=300).contains(&remaining), "{}", remaining);
+    }
+
+    /// JWTのオーディエンスが、受け付けるオーディエンスのいずれかと一致する場合のみ、クレームを取得できることを
+    /// 確認するテスト
+    #[test]
+    fn test_get_claim_from_jwt_verifies_audience() {
+        let user_id = Uuid::new_v4();
+        let secret_key = Secret::new("some-secret".to_owned());
+        let expiration = current_unix_epoch() + 300;
+        let audiences = |values: &[&str]| -> Vec<String> {
+            values.iter().map(|value| (*value).to_owned()).collect()
+        };
+        let token = generate_jwt(user_id, &secret_key, expiration, Some("api")).unwrap();
+
+        // 一致する場合
+        let claim = get_claim_from_jwt(&token, &secret_key, &audiences(&["api"])).unwrap();
+        assert_eq!(claim.audience.as_deref(), Some("api"));
+        // 一致しない場合
+        assert!(get_claim_from_jwt(&token, &secret_key, &audiences(&["admin"])).is_err());
+        // 複数のオーディエンスのいずれかと一致する場合
+        let claim = get_claim_from_jwt(&token, &secret_key, &audiences(&["admin", "api"])).unwrap();
+        assert_eq!(claim.user_id, user_id);
+        // オーディエンスを指定しない場合は検証しない
+        assert!(get_claim_from_jwt(&token, &secret_key, &[]).is_ok());
+        // audクレームを含まないJWTは、オーディエンスを指定した場合に拒否
+        let token = generate_jwt(user_id, &secret_key, expiration, None).unwrap();
+        assert!(get_claim_from_jwt(&token, &secret_key, &audiences(&["api"])).is_err());
     }
 
     /// 異なるアクセストークンとリフレッシュトークンを作成することを確認するテスト
@@ -124,11 +511,167 @@ mod tests {
         let now = current_unix_epoch();
         let access_expiration: u64 = now + 300;
         let refresh_expiration: u64 = now + 3600;
-        let (access, refresh) =
-            generate_jwt_pair(user_id, &secret_key, access_expiration, refresh_expiration).unwrap();
+        let (access, refresh) = generate_jwt_pair(
+            user_id,
+            &secret_key,
+            access_expiration,
+            refresh_expiration,
+            None,
+        )
+        .unwrap();
         assert_ne!(
             access, refresh,
             "アクセストークンとリフレッシュトークンが同じです。"
         )
     }
+
+    /// リフレッシュトークンのハッシュが、トークンと鍵によって決まることを確認するテスト
+    #[test]
+    fn test_refresh_token_hash() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let hash = refresh_token_hash("refresh-token", &secret_key).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_ne!(hash, "refresh-token");
+        assert_eq!(
+            hash,
+            refresh_token_hash("refresh-token", &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            refresh_token_hash("other-token", &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            refresh_token_hash("refresh-token", &Secret::new("other".to_owned())).unwrap()
+        );
+    }
+
+    /// クレームを含まない、異なるアクセストークンとリフレッシュトークンを作成することを確認するテスト
+    #[test]
+    fn test_generate_opaque_token_pair() {
+        let (access, refresh) = generate_opaque_token_pair();
+        for token in [&access, &refresh] {
+            assert_eq!(token.len(), OPAQUE_TOKEN_BYTES * 2);
+            assert!(token.chars().all(|ch| ch.is_ascii_hexdigit()));
+            // JWTとして解析できないことを確認
+            let secret_key = Secret::new("some-secret".to_owned());
+            assert!(get_claim_from_jwt(token, &secret_key, &[]).is_err());
+        }
+        assert_ne!(
+            access, refresh,
+            "アクセストークンとリフレッシュトークンが同じです。"
+        );
+        assert_ne!(generate_opaque_token_pair().0, access);
+    }
+
+    /// 同じトークンから同じフィンガープリントを、異なるトークンから異なるフィンガープリントを生成することを確認するテスト
+    #[test]
+    fn test_token_fingerprint() {
+        let user_id = Uuid::new_v4();
+        let secret_key = Secret::new("some-secret".to_owned());
+        let now = current_unix_epoch();
+        let (access, refresh) =
+            generate_jwt_pair(user_id, &secret_key, now + 300, now + 3600, None).unwrap();
+        let fingerprint = token_fingerprint(&access);
+        assert_eq!(fingerprint.len(), TOKEN_FINGERPRINT_LEN);
+        assert!(fingerprint.chars().all(|ch| ch.is_ascii_hexdigit()));
+        assert_eq!(fingerprint, token_fingerprint(&access));
+        assert_ne!(fingerprint, token_fingerprint(&refresh));
+        // "abc"のSHA-256ハッシュはba7816bfから始まる
+        assert_eq!(token_fingerprint("abc"), "ba7816bf");
+    }
+
+    /// トークンのデバッグ出力にトークンが含まれないことを確認するテスト
+    #[test]
+    fn test_redacted_token_debug() {
+        let session_data = crate::session::SessionData {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            access_token: RedactedToken::new("access-token-value"),
+            access_expiration: 1_000,
+            refresh_token: RedactedToken::new("refresh-token-value"),
+            refresh_expiration: 2_000,
+            generation: 1,
+            last_accessed_at: 1_000,
+            last_authenticated_at: 1_000,
+            device_name: None,
+            refresh_count: 0,
+            version: 1,
+        };
+        let debug = format!("{:?}", session_data);
+        assert!(!debug.contains("access-token-value"));
+        assert!(!debug.contains("refresh-token-value"));
+        assert_eq!(format!("{}", session_data.access_token), "***");
+        assert_eq!(session_data.access_token.expose(), "access-token-value");
+        // シリアライズしたときはトークンがそのまま出力されることを確認
+        let json = serde_json::to_string(&session_data.refresh_token).unwrap();
+        assert_eq!(json, r#""refresh-token-value""#);
+    }
+
+    /// パスワードリセットトークンのハッシュが、トークンとメールアドレスの両方に依存することを確認するテスト
+    #[test]
+    fn test_password_reset_token_hash() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let token = generate_password_reset_token();
+        assert_eq!(token.len(), PASSWORD_RESET_TOKEN_BYTES * 2);
+        assert_ne!(token, generate_password_reset_token());
+        let hash = password_reset_token_hash(&token, "foo@example.com", &secret_key).unwrap();
+        assert_eq!(
+            hash,
+            password_reset_token_hash(&token, "foo@example.com", &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            password_reset_token_hash(&token, "bar@example.com", &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            password_reset_token_hash(
+                &generate_password_reset_token(),
+                "foo@example.com",
+                &secret_key
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invite_code_hash() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let code = generate_invite_code();
+        assert_eq!(code.len(), INVITE_CODE_BYTES * 2);
+        assert_ne!(code, generate_invite_code());
+        let hash = invite_code_hash(&code, &secret_key).unwrap();
+        // 前後の空白と大文字小文字の違いは無視
+        assert_eq!(
+            hash,
+            invite_code_hash(&format!(" {} ", code.to_uppercase()), &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            invite_code_hash(&generate_invite_code(), &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            invite_code_hash(&code, &Secret::new("other-secret".to_owned())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_api_key_hash() {
+        let secret_key = Secret::new("some-secret".to_owned());
+        let api_key = generate_api_key();
+        assert_eq!(api_key.len(), API_KEY_BYTES * 2);
+        assert_ne!(api_key, generate_api_key());
+        let hash = api_key_hash(&api_key, &secret_key).unwrap();
+        assert_eq!(hash, api_key_hash(&api_key, &secret_key).unwrap());
+        assert_ne!(
+            hash,
+            api_key_hash(&generate_api_key(), &secret_key).unwrap()
+        );
+        assert_ne!(
+            hash,
+            api_key_hash(&api_key, &Secret::new("other-secret".to_owned())).unwrap()
+        );
+    }
 }
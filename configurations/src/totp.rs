@@ -0,0 +1,399 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// TOTPの共有シークレットのバイト数
+const TOTP_SECRET_BYTES: usize = 20;
+
+/// TOTPのコードの桁数
+pub const TOTP_DIGITS: u32 = 6;
+
+/// TOTPの時間ステップ（秒）
+pub const TOTP_PERIOD: u64 = 30;
+
+/// 端末の時刻のずれを許容する、前後の時間ステップの数
+const TOTP_ALLOWED_SKEW: u64 = 1;
+
+/// TOTPチャレンジトークンのバイト数
+const CHALLENGE_TOKEN_BYTES: usize = 32;
+
+/// 2要素認証を有効にしたときに生成するリカバリーコードの数
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// リカバリーコードのバイト数
+const RECOVERY_CODE_BYTES: usize = 5;
+
+/// RFC 4648で定義されたBase32のアルファベット
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// TOTPチャレンジ構造体
+///
+/// 2要素認証を有効にしたユーザーがパスワードで認証したときに生成して、セッションに保存する。TOTPのコードで
+/// ログインを完了するときにセッションから取り出して、クライアントが送信したチャレンジトークンと照合する。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpChallenge {
+    /// チャレンジトークン。
+    pub token: String,
+    /// パスワードで認証したユーザーのユーザーID。
+    pub user_id: Uuid,
+    /// 有効期限（UNIXエポック秒）。
+    pub expiration: u64,
+}
+
+impl TotpChallenge {
+    /// TOTPチャレンジを生成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - パスワードで認証したユーザーのユーザーID。
+    /// * `expiration` - 有効期限（UNIXエポック秒）。
+    ///
+    /// # Returns
+    ///
+    /// TOTPチャレンジインスタンス。
+    pub fn generate(user_id: Uuid, expiration: u64) -> Self {
+        Self {
+            token: base64::encode_config(
+                rand::thread_rng().gen::<[u8; CHALLENGE_TOKEN_BYTES]>(),
+                base64::URL_SAFE_NO_PAD,
+            ),
+            user_id,
+            expiration,
+        }
+    }
+}
+
+/// バイト列をパディングなしのBase32でエンコードする。
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while 5 <= bits {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if 0 < bits {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Base32でエンコードされた文字列をデコードする。
+///
+/// 認証アプリに手入力されることを考慮して、大文字と小文字を区別せず、空白とパディングを無視する。
+fn decode_base32(value: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(value.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for ch in value.chars().filter(|ch| !ch.is_whitespace() && *ch != '=') {
+        let index = BASE32_ALPHABET
+            .iter()
+            .position(|c| *c as char == ch.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | index as u32;
+        bits += 5;
+        if 8 <= bits {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// TOTPの共有シークレットを生成する。
+///
+/// # Returns
+///
+/// Base32でエンコードした共有シークレット。
+pub fn generate_totp_secret() -> String {
+    encode_base32(&rand::thread_rng().gen::<[u8; TOTP_SECRET_BYTES]>())
+}
+
+/// URIの構成要素をパーセントエンコードする。
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// 認証アプリに共有シークレットを登録する`otpauth`URIを構築する。
+///
+/// # Arguments
+///
+/// * `issuer` - 認証アプリに表示するサービス名。
+/// * `account_name` - 認証アプリに表示するアカウント名。
+/// * `secret` - Base32でエンコードした共有シークレット。
+///
+/// # Returns
+///
+/// `otpauth`URI。
+pub fn otpauth_uri(issuer: &str, account_name: &str, secret: &str) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        percent_encode(account_name),
+        secret,
+        issuer,
+        TOTP_DIGITS,
+        TOTP_PERIOD
+    )
+}
+
+/// リカバリーコードを生成する。
+///
+/// リカバリーコードは、認証アプリを使用できなくなったときに、TOTPのコードの代わりに1回だけ使用できる。
+/// 読み取りやすいように、小文字のBase32でエンコードした8文字を、4文字ずつハイフンで区切る。
+///
+/// # Returns
+///
+/// リカバリーコードのベクタ。
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = encode_base32(&rand::thread_rng().gen::<[u8; RECOVERY_CODE_BYTES]>())
+                .to_lowercase();
+            format!("{}-{}", &code[..4], &code[4..])
+        })
+        .collect()
+}
+
+/// リカバリーコードのハッシュを計算する。
+///
+/// 入力の揺れを吸収するため、ハイフンと空白を取り除いて小文字にしたリカバリーコードと、ユーザーIDを連結した
+/// メッセージのSHA-256を計算する。
+///
+/// # Arguments
+///
+/// * `user_id` - リカバリーコードを発行したユーザーのユーザーID。
+/// * `code` - リカバリーコード。
+///
+/// # Returns
+///
+/// リカバリーコードのハッシュを16進数で表現した文字列。
+pub fn recovery_code_hash(user_id: Uuid, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|ch| !ch.is_whitespace() && *ch != '-')
+        .map(|ch| ch.to_ascii_lowercase())
+        .collect();
+    let mut hasher = Sha256::new();
+    hasher.update(user_id.as_bytes());
+    hasher.update(code.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// RFC 4226で定義されたHOTPのコードを計算する。
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret).expect("HMACは任意の長さの鍵を受け付けます。");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    // 動的切り捨て
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// TOTPのコードを検証する。
+///
+/// 端末の時刻のずれを考慮して、前後1つの時間ステップのコードも受け付ける。
+/// 盗み見たコードを再使用されないように、最後に受け付けたコード以前の時間ステップのコードは受け付けない。
+///
+/// # Arguments
+///
+/// * `secret` - Base32でエンコードした共有シークレット。
+/// * `code` - 検証するコード。
+/// * `now` - 現在日時（UNIXエポック秒）。
+/// * `last_step` - 最後に受け付けたコードの時間ステップ。コードを受け付けたことがない場合は`None`。
+///
+/// # Returns
+///
+/// コードが一致した時間ステップ。コードが一致しなかった場合は`None`。
+pub fn verify_totp(secret: &str, code: &str, now: u64, last_step: Option<u64>) -> Option<u64> {
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let secret = match decode_base32(secret) {
+        Some(secret) if !secret.is_empty() => secret,
+        _ => return None,
+    };
+    let code: u32 = code.parse().unwrap();
+    let counter = now / TOTP_PERIOD;
+    // 最後に受け付けたコード以前の時間ステップは検証しない
+    let earliest = match last_step {
+        Some(last_step) => counter
+            .saturating_sub(TOTP_ALLOWED_SKEW)
+            .max(last_step.saturating_add(1)),
+        None => counter.saturating_sub(TOTP_ALLOWED_SKEW),
+    };
+
+    (earliest..=counter + TOTP_ALLOWED_SKEW).find(|counter| hotp(&secret, *counter) == code)
+}
+
+/// TOTPのコードを生成する。
+///
+/// 認証アプリの代わりにコードを生成するテストで使用する。
+///
+/// # Arguments
+///
+/// * `secret` - Base32でエンコードした共有シークレット。
+/// * `now` - 現在日時（UNIXエポック秒）。
+///
+/// # Returns
+///
+/// コード。共有シークレットをデコードできない場合は`None`。
+pub fn generate_totp_code(secret: &str, now: u64) -> Option<String> {
+    let secret = decode_base32(secret)?;
+
+    Some(format!(
+        "{:0width$}",
+        hotp(&secret, now / TOTP_PERIOD),
+        width = TOTP_DIGITS as usize
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238の試験ベクトル（SHA-1）の共有シークレット
+    const RFC6238_SECRET: &[u8] = b"12345678901234567890";
+
+    /// RFC 4648の試験ベクトルで、Base32でエンコード及びデコードできることを確認するテスト
+    #[test]
+    fn base32_round_trip() {
+        let cases = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+        for (plain, encoded) in cases {
+            assert_eq!(encode_base32(plain.as_bytes()), encoded);
+            assert_eq!(decode_base32(encoded).unwrap(), plain.as_bytes());
+        }
+        // 大文字と小文字を区別せず、空白とパディングを無視
+        assert_eq!(decode_base32("mzxw 6ytb oi======").unwrap(), b"foobar");
+        assert!(decode_base32("MZXW1").is_none());
+        assert_eq!(generate_totp_secret().len(), 32);
+    }
+
+    /// RFC 6238の試験ベクトルの下位6桁と、コードが一致することを確認するテスト
+    #[test]
+    fn totp_matches_rfc6238_vectors() {
+        let secret = encode_base32(RFC6238_SECRET);
+        let cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (now, code) in cases {
+            assert_eq!(generate_totp_code(&secret, now).unwrap(), code, "{}", now);
+            assert_eq!(
+                verify_totp(&secret, code, now, None),
+                Some(now / TOTP_PERIOD)
+            );
+        }
+    }
+
+    /// 前後1つの時間ステップのコードを受け付けて、それ以外のコードや不正な形式のコードを拒否することを
+    /// 確認するテスト
+    #[test]
+    fn verify_totp_allows_one_step_of_skew() {
+        let secret = encode_base32(RFC6238_SECRET);
+        let now = 1111111111;
+        // 前後1つの時間ステップのコード
+        let step = now / TOTP_PERIOD;
+        for (code, expected) in [("081804", step - 1), ("050471", step), ("266759", step + 1)] {
+            assert_eq!(
+                verify_totp(&secret, code, now, None),
+                Some(expected),
+                "{}",
+                code
+            );
+        }
+        // 2つ前及び2つ後の時間ステップのコード
+        for at in [now - 2 * TOTP_PERIOD, now + 2 * TOTP_PERIOD] {
+            let code = generate_totp_code(&secret, at).unwrap();
+            assert!(verify_totp(&secret, &code, now, None).is_none(), "{}", at);
+        }
+        assert!(verify_totp(&secret, "12345", now, None).is_none());
+        assert!(verify_totp(&secret, "12a456", now, None).is_none());
+        assert!(verify_totp("not base32!", "123456", now, None).is_none());
+    }
+
+    /// 最後に受け付けたコード以前の時間ステップのコードを、時刻のずれの範囲内でも拒否することを確認するテスト
+    #[test]
+    fn verify_totp_rejects_replayed_steps() {
+        let secret = encode_base32(RFC6238_SECRET);
+        let now = 1111111111;
+        let step = now / TOTP_PERIOD;
+        // 受け付けたコードは、次の時間ステップになっても再使用できない
+        let code = generate_totp_code(&secret, now).unwrap();
+        assert_eq!(verify_totp(&secret, &code, now, None), Some(step));
+        assert!(verify_totp(&secret, &code, now, Some(step)).is_none());
+        assert!(verify_totp(&secret, &code, now + TOTP_PERIOD, Some(step)).is_none());
+        // 前の時間ステップのコードも受け付けない
+        let previous = generate_totp_code(&secret, now - TOTP_PERIOD).unwrap();
+        assert!(verify_totp(&secret, &previous, now, Some(step)).is_none());
+        // 後の時間ステップのコードは受け付ける
+        let next = generate_totp_code(&secret, now + TOTP_PERIOD).unwrap();
+        assert_eq!(verify_totp(&secret, &next, now, Some(step)), Some(step + 1));
+    }
+
+    /// リカバリーコードの形式と、ハッシュが入力の揺れを吸収してユーザーごとに異なることを確認するテスト
+    #[test]
+    fn recovery_code_hash_is_normalized() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), 9, "{}", code);
+            assert_eq!(&code[4..5], "-", "{}", code);
+        }
+        let user_id = Uuid::new_v4();
+        let hash = recovery_code_hash(user_id, "abcd-efgh");
+        assert_eq!(recovery_code_hash(user_id, " ABCDEFGH "), hash);
+        assert_ne!(recovery_code_hash(user_id, "abcd-efgi"), hash);
+        assert_ne!(recovery_code_hash(Uuid::new_v4(), "abcd-efgh"), hash);
+    }
+
+    /// `otpauth`URIのラベルとサービス名をパーセントエンコードすることを確認するテスト
+    #[test]
+    fn otpauth_uri_encodes_label() {
+        assert_eq!(
+            otpauth_uri("jwt auth", "taro@example.com", "MZXW6YTBOI"),
+            "otpauth://totp/jwt%20auth:taro%40example.com?secret=MZXW6YTBOI&issuer=jwt%20auth&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
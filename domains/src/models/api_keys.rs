@@ -0,0 +1,187 @@
+use anyhow::anyhow;
+use time::OffsetDateTime;
+
+use crate::models::base::EntityId;
+use crate::models::users::UserId;
+
+/// APIキーID
+pub type ApiKeyId = EntityId<ApiKey>;
+
+/// スコープの最大文字数
+const API_KEY_SCOPE_MAX_LEN: usize = 64;
+
+/// APIキーに付与できるスコープの最大数
+pub const API_KEY_MAX_SCOPES: usize = 16;
+
+/// APIキーのスコープ構造体
+///
+/// スコープは、英数字と`:`、`.`、`_`、`-`で構成した1文字以上64文字以内の文字列。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyScope {
+    value: String,
+}
+
+impl ApiKeyScope {
+    /// APIキーのスコープインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - スコープ。
+    ///
+    /// # Returns
+    ///
+    /// APIキーのスコープインスタンス。
+    pub fn new(value: &str) -> anyhow::Result<Self> {
+        let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, ':' | '.' | '_' | '-');
+        if value.is_empty() || API_KEY_SCOPE_MAX_LEN < value.len() || !value.chars().all(valid_char)
+        {
+            return Err(anyhow!(
+                "スコープは英数字と:._-で構成した1文字以上{}文字以内で指定してください。",
+                API_KEY_SCOPE_MAX_LEN
+            ));
+        }
+
+        Ok(Self {
+            value: value.to_owned(),
+        })
+    }
+
+    /// スコープを返却する。
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// APIキー構造体
+///
+/// サービスアカウントなど、セッションを使用しないクライアントが使用するAPIキーを表現する。APIキーそのものは
+/// 保持せず、APIキーのハッシュを保持する。
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    /// APIキーID。
+    id: ApiKeyId,
+    /// APIキーのハッシュ。
+    key_hash: String,
+    /// APIキーを所有するユーザーのユーザーID。
+    user_id: UserId,
+    /// スコープ。
+    scopes: Vec<ApiKeyScope>,
+    /// 有効期限。有効期限がない場合は`None`。
+    expired_at: Option<OffsetDateTime>,
+}
+
+impl ApiKey {
+    /// APIキーインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - APIキーID。
+    /// * `key_hash` - APIキーのハッシュ。
+    /// * `user_id` - APIキーを所有するユーザーのユーザーID。
+    /// * `scopes` - スコープ。
+    /// * `expired_at` - 有効期限。
+    ///
+    /// # Returns
+    ///
+    /// APIキーインスタンス。
+    pub fn new(
+        id: ApiKeyId,
+        key_hash: &str,
+        user_id: UserId,
+        scopes: Vec<ApiKeyScope>,
+        expired_at: Option<OffsetDateTime>,
+    ) -> Self {
+        Self {
+            id,
+            key_hash: key_hash.to_owned(),
+            user_id,
+            scopes,
+            expired_at,
+        }
+    }
+
+    /// APIキーIDを返却する。
+    pub fn id(&self) -> ApiKeyId {
+        self.id.clone()
+    }
+
+    /// APIキーのハッシュを返却する。
+    pub fn key_hash(&self) -> &str {
+        &self.key_hash
+    }
+
+    /// APIキーを所有するユーザーのユーザーIDを返却する。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// スコープを返却する。
+    pub fn scopes(&self) -> &[ApiKeyScope] {
+        &self.scopes
+    }
+
+    /// 有効期限を返却する。
+    pub fn expired_at(&self) -> Option<OffsetDateTime> {
+        self.expired_at
+    }
+
+    /// 有効期限が切れているか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - 現在日時。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限が切れている場合は`true`。有効期限がない場合は`false`。
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        matches!(self.expired_at, Some(expired_at) if expired_at <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::Duration;
+
+    #[test]
+    fn api_key_scope_accepts_valid_scopes() {
+        for value in ["read", "users:read", "reports.export_v2", "a-b"] {
+            assert_eq!(ApiKeyScope::new(value).unwrap().value(), value);
+        }
+        assert!(ApiKeyScope::new(&"a".repeat(API_KEY_SCOPE_MAX_LEN)).is_ok());
+    }
+
+    #[test]
+    fn api_key_scope_rejects_invalid_scopes() {
+        let too_long = "a".repeat(API_KEY_SCOPE_MAX_LEN + 1);
+        for value in [
+            "",
+            "users read",
+            "users/read",
+            "スコープ",
+            too_long.as_str(),
+        ] {
+            assert!(ApiKeyScope::new(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn api_key_expiration() {
+        let now = OffsetDateTime::now_utc();
+        let api_key = |expired_at| {
+            ApiKey::new(
+                ApiKeyId::default(),
+                "hash",
+                UserId::default(),
+                vec![],
+                expired_at,
+            )
+        };
+        assert!(!api_key(None).is_expired(now));
+        assert!(!api_key(Some(now + Duration::seconds(1))).is_expired(now));
+        assert!(api_key(Some(now)).is_expired(now));
+        assert!(api_key(Some(now - Duration::seconds(1))).is_expired(now));
+    }
+}
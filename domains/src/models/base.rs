@@ -4,26 +4,30 @@ use anyhow::anyhow;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::models::errors::{DomainError, ValidationError};
+
 /// エンティティID構造体
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct EntityId<T> {
     value: Uuid,
     _marker: PhantomData<T>,
 }
 
-impl<T> EntityId<T> {
+impl<T> Default for EntityId<T> {
     /// エンティティIDインスタンスを構築する。
     ///
     /// # Returns
     ///
     /// エンティティIDインスタンス。
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             value: Uuid::new_v4(),
             _marker: PhantomData,
         }
     }
+}
 
+impl<T> EntityId<T> {
     /// エンティティIDインスタンスを構築する。
     ///
     /// # Arguments
@@ -79,6 +83,9 @@ pub struct EmailAddress {
 impl EmailAddress {
     /// Eメールアドレスインスタンスを生成する。
     ///
+    /// サインアップとログインで同じEメールアドレスとして扱えるように、前後の空白を削除して、小文字に
+    /// 正規化する。
+    ///
     /// # Arguments
     ///
     /// * `value` - Eメールアドレス。
@@ -86,12 +93,15 @@ impl EmailAddress {
     /// # Returns
     ///
     /// Eメールアドレスインスタンス。
-    pub fn new(value: &str) -> anyhow::Result<Self> {
+    pub fn new(value: &str) -> Result<Self, DomainError> {
         let email = Self {
-            value: value.to_owned(),
+            value: value.trim().to_lowercase(),
         };
-        if email.validate().is_err() {
-            return Err(anyhow!(format!("Eメールアドレス({})が不正です。", value)));
+        if let Err(e) = email.validate() {
+            return Err(DomainError::InvalidEmail {
+                value: value.to_owned(),
+                errors: ValidationError::from_validator(&e),
+            });
         }
 
         Ok(email)
@@ -111,6 +121,16 @@ impl EmailAddress {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_entity_id_new() {
+        let uuid = uuid::Uuid::new_v4();
+        let id = EntityId::<i32>::new(uuid);
+        assert_eq!(id.value(), uuid);
+        // 文字列から構築したエンティティIDと一致することを確認
+        let other = EntityId::<i32>::try_from(uuid.to_string().as_str()).unwrap();
+        assert_eq!(id, other);
+    }
+
     #[test]
     fn test_entity_id_try_from() {
         let uuid = uuid::Uuid::new_v4();
@@ -157,6 +177,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_email_address_new_normalizes() {
+        let values = vec![
+            "Email@Example.com",
+            "EMAIL@EXAMPLE.COM",
+            "  email@example.com",
+            "email@example.com \t",
+            " Email@EXAMPLE.com ",
+        ];
+        for value in values {
+            let email = EmailAddress::new(value);
+            assert!(email.is_ok(), "{}", value);
+            assert_eq!(email.unwrap().value(), "email@example.com", "{}", value);
+        }
+    }
+
+    /// Eメールアドレスの検証に失敗した場合は、検証に失敗したフィールドと制約を返却することを確認する。
+    #[test]
+    fn test_email_address_validation_errors() {
+        let e = EmailAddress::new("email.example.com").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Eメールアドレス(email.example.com)が不正です。"
+        );
+        let expected = vec![ValidationError {
+            field: "value".to_owned(),
+            code: "email".to_owned(),
+        }];
+        assert_eq!(e.validation_errors(), expected);
+        assert!(matches!(
+            e,
+            DomainError::InvalidEmail { value, errors }
+                if value == "email.example.com" && errors == expected
+        ));
+    }
+
     #[test]
     fn test_email_address_gen_by_invalid_strings() {
         /* cSpell: disable */
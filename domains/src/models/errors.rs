@@ -0,0 +1,110 @@
+/// 検証エラー構造体
+///
+/// 値の検証に失敗したフィールドと、満たさなかった制約を表現する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// 検証に失敗したフィールド名
+    pub field: String,
+    /// 満たさなかった制約のコード（`length`、`email`など）
+    pub code: String,
+}
+
+impl ValidationError {
+    /// 値を格納するフィールドの検証エラーを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - 満たさなかった制約のコード。
+    ///
+    /// # Returns
+    ///
+    /// 検証エラー。
+    fn value(code: &str) -> Self {
+        Self {
+            field: "value".to_owned(),
+            code: code.to_owned(),
+        }
+    }
+
+    /// `validator`クレートの検証エラーから、検証エラーを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `errors` - `validator`クレートの検証エラー。
+    ///
+    /// # Returns
+    ///
+    /// 検証エラー。フィールド名の順に並べる。
+    pub fn from_validator(errors: &validator::ValidationErrors) -> Vec<Self> {
+        let mut errors: Vec<Self> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| Self {
+                    field: field.to_owned(),
+                    code: error.code.to_string(),
+                })
+            })
+            .collect();
+        errors.sort_by(|a, b| a.field.cmp(&b.field));
+
+        errors
+    }
+}
+
+/// ドメインエラー列挙型
+///
+/// ドメインの型の構築に失敗した理由を表現する。
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DomainError {
+    /// ユーザー名の文字数が範囲外
+    #[error("ユーザー名は{min}文字から{max}文字です。")]
+    UserNameLength {
+        min: usize,
+        max: usize,
+        /// `validator`クレートが報告した検証エラー
+        errors: Vec<ValidationError>,
+    },
+    /// 予約されたユーザー名
+    #[error("ユーザー名({0})は予約されているため使用できません。")]
+    ReservedUserName(String),
+    /// Eメールアドレスの形式が不正
+    #[error("Eメールアドレス({value})が不正です。")]
+    InvalidEmail {
+        value: String,
+        /// `validator`クレートが報告した検証エラー
+        errors: Vec<ValidationError>,
+    },
+    /// パスワードの文字数が最小文字数未満
+    #[error("パスワードは{0}文字以上の文字列で指定してください。")]
+    PasswordTooShort(usize),
+    /// パスワードの文字数が最大文字数超過
+    #[error("パスワードは{0}文字以下の文字列で指定してください。")]
+    PasswordTooLong(usize),
+    /// パスワードに必要な文字種が含まれていない
+    #[error("パスワードに{0}が含まれていません。")]
+    PasswordMissingClass(&'static str),
+    /// クライアントでハッシュ化したパスワードの文字数が範囲外
+    #[error("クライアントでハッシュ化したパスワードは{min}文字から{max}文字です。")]
+    ClientHashedPasswordLength { min: usize, max: usize },
+}
+
+impl DomainError {
+    /// 検証に失敗したフィールドと、満たさなかった制約を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 検証エラー。`validator`クレートで検証した場合は、`validator`クレートが報告した検証エラー。
+    pub fn validation_errors(&self) -> Vec<ValidationError> {
+        match self {
+            Self::UserNameLength { errors, .. } | Self::InvalidEmail { errors, .. } => {
+                errors.clone()
+            }
+            Self::ReservedUserName(_) => vec![ValidationError::value("reserved")],
+            Self::PasswordTooShort(_)
+            | Self::PasswordTooLong(_)
+            | Self::ClientHashedPasswordLength { .. } => vec![ValidationError::value("length")],
+            Self::PasswordMissingClass(_) => vec![ValidationError::value("character_class")],
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::anyhow;
+use time::OffsetDateTime;
+
+use crate::models::base::EntityId;
+use crate::models::users::UserId;
+
+/// ログイン試行ID
+pub type LoginAttemptId = EntityId<LoginAttempt>;
+
+/// 地球の半径（km）
+const EARTH_RADIUS_KM: f64 = 6_371.0;
+
+/// 位置構造体
+///
+/// 緯度と経度で、ログインを試行したクライアントのおおよその位置を表現する。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoLocation {
+    /// 緯度。
+    latitude: f64,
+    /// 経度。
+    longitude: f64,
+}
+
+impl GeoLocation {
+    /// 位置インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - 緯度。
+    /// * `longitude` - 経度。
+    ///
+    /// # Returns
+    ///
+    /// 位置インスタンス。
+    pub fn new(latitude: f64, longitude: f64) -> anyhow::Result<Self> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(anyhow!("緯度は-90度から90度で指定してください。"));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(anyhow!("経度は-180度から180度で指定してください。"));
+        }
+
+        Ok(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    /// 緯度を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 緯度。
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    /// 経度を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 経度。
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    /// 他の位置までの大円距離を、ハーバサイン公式で計算する。
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - 他の位置。
+    ///
+    /// # Returns
+    ///
+    /// 距離（km）。
+    pub fn distance_km(&self, other: &GeoLocation) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+/// ログイン試行構造体
+///
+/// ユーザーがログインを試行した結果と、試行したクライアントの情報を表現する。
+#[derive(Debug, Clone)]
+pub struct LoginAttempt {
+    /// ログイン試行ID。
+    id: LoginAttemptId,
+    /// ユーザーID。
+    user_id: UserId,
+    /// ログインに成功したか。
+    succeeded: bool,
+    /// IPアドレス。
+    ip_address: Option<String>,
+    /// デバイス名。
+    device_name: Option<String>,
+    /// 位置。
+    location: Option<GeoLocation>,
+    /// 試行日時。
+    attempted_at: OffsetDateTime,
+}
+
+impl LoginAttempt {
+    /// ログイン試行インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ログイン試行ID。
+    /// * `user_id` - ユーザーID。
+    /// * `succeeded` - ログインに成功したか。
+    /// * `ip_address` - IPアドレス。
+    /// * `device_name` - デバイス名。
+    /// * `location` - 位置。
+    /// * `attempted_at` - 試行日時。
+    ///
+    /// # Returns
+    ///
+    /// ログイン試行インスタンス。
+    pub fn new(
+        id: LoginAttemptId,
+        user_id: UserId,
+        succeeded: bool,
+        ip_address: Option<String>,
+        device_name: Option<String>,
+        location: Option<GeoLocation>,
+        attempted_at: OffsetDateTime,
+    ) -> Self {
+        Self {
+            id,
+            user_id,
+            succeeded,
+            ip_address,
+            device_name,
+            location,
+            attempted_at,
+        }
+    }
+
+    /// ログイン試行IDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ログイン試行ID。
+    pub fn id(&self) -> LoginAttemptId {
+        self.id.clone()
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// ログインに成功したかを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ログインに成功した場合は`true`。
+    pub fn succeeded(&self) -> bool {
+        self.succeeded
+    }
+
+    /// IPアドレスを返却する。
+    ///
+    /// # Returns
+    ///
+    /// IPアドレス。
+    pub fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_deref()
+    }
+
+    /// デバイス名を返却する。
+    ///
+    /// # Returns
+    ///
+    /// デバイス名。
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// 位置を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 位置。
+    pub fn location(&self) -> Option<GeoLocation> {
+        self.location
+    }
+
+    /// 試行日時を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 試行日時。
+    pub fn attempted_at(&self) -> OffsetDateTime {
+        self.attempted_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geo_location_new() {
+        assert!(GeoLocation::new(35.68, 139.77).is_ok());
+        assert!(GeoLocation::new(90.1, 0.0).is_err());
+        assert!(GeoLocation::new(-90.1, 0.0).is_err());
+        assert!(GeoLocation::new(0.0, 180.1).is_err());
+        assert!(GeoLocation::new(0.0, -180.1).is_err());
+    }
+
+    #[test]
+    fn test_geo_location_distance_km() {
+        let tokyo = GeoLocation::new(35.6812, 139.7671).unwrap();
+        let osaka = GeoLocation::new(34.7025, 135.4959).unwrap();
+        let london = GeoLocation::new(51.5074, -0.1278).unwrap();
+        assert_eq!(tokyo.distance_km(&tokyo), 0.0);
+        // 東京と大阪は約400km、東京とロンドンは約9,560km離れている
+        assert!((390.0..410.0).contains(&tokyo.distance_km(&osaka)));
+        assert!((9_500.0..9_620.0).contains(&tokyo.distance_km(&london)));
+        assert_eq!(tokyo.distance_km(&london), london.distance_km(&tokyo));
+    }
+}
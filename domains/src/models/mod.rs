@@ -1,4 +1,13 @@
 mod base;
+mod errors;
 
 pub use base::*;
+pub use errors::*;
+pub mod api_keys;
+pub mod login_attempts;
+pub mod password_reset_tokens;
+pub mod refresh_tokens;
+pub mod security_questions;
+pub mod user_credentials;
+pub mod user_email_addresses;
 pub mod users;
@@ -0,0 +1,77 @@
+use time::OffsetDateTime;
+
+use crate::models::users::UserId;
+
+/// パスワードリセットトークン構造体
+///
+/// パスワードリセットトークンそのものは保持せず、トークンとメールアドレスから計算したハッシュを保持する。
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken {
+    /// パスワードリセットトークンのハッシュ。
+    token_hash: String,
+    /// ユーザーID。
+    user_id: UserId,
+    /// 有効期限。
+    expired_at: OffsetDateTime,
+}
+
+impl PasswordResetToken {
+    /// パスワードリセットトークンインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - パスワードリセットトークンのハッシュ。
+    /// * `user_id` - ユーザーID。
+    /// * `expired_at` - 有効期限。
+    ///
+    /// # Returns
+    ///
+    /// パスワードリセットトークンインスタンス。
+    pub fn new(token_hash: &str, user_id: UserId, expired_at: OffsetDateTime) -> Self {
+        Self {
+            token_hash: token_hash.to_owned(),
+            user_id,
+            expired_at,
+        }
+    }
+
+    /// パスワードリセットトークンのハッシュを返却する。
+    ///
+    /// # Returns
+    ///
+    /// パスワードリセットトークンのハッシュ。
+    pub fn token_hash(&self) -> &str {
+        &self.token_hash
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// 有効期限を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限。
+    pub fn expired_at(&self) -> OffsetDateTime {
+        self.expired_at
+    }
+
+    /// 有効期限が切れているか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - 現在日時。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限が切れている場合は`true`。
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expired_at <= now
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::anyhow;
+use secrecy::Secret;
+use time::OffsetDateTime;
+
+use configurations::{session::SessionData, tokens::refresh_token_hash};
+
+use crate::models::base::EntityId;
+use crate::models::users::UserId;
+
+/// セッションID
+///
+/// ログインしたときに発行され、トークンをリフレッシュしても変わらない。
+pub type SessionId = EntityId<RefreshToken>;
+
+/// リフレッシュトークン構造体
+///
+/// セッションごとに発行したリフレッシュトークンを表現する。リフレッシュトークンそのものは保持せず、
+/// ハッシュのみを保持する。
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    /// セッションID。
+    session_id: SessionId,
+    /// ユーザーID。
+    user_id: UserId,
+    /// リフレッシュトークンのハッシュ。
+    token_hash: String,
+    /// 有効期限。
+    expired_at: OffsetDateTime,
+    /// デバイス名。
+    device_name: Option<String>,
+    /// 作成日時。
+    created_at: Option<OffsetDateTime>,
+    /// 更新日時。
+    updated_at: Option<OffsetDateTime>,
+}
+
+impl RefreshToken {
+    /// リフレッシュトークンインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - セッションID。
+    /// * `user_id` - ユーザーID。
+    /// * `token_hash` - リフレッシュトークンのハッシュ。
+    /// * `expired_at` - 有効期限。
+    /// * `device_name` - デバイス名。
+    /// * `created_at` - 作成日時。
+    /// * `updated_at` - 更新日時。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンインスタンス。
+    pub fn new(
+        session_id: SessionId,
+        user_id: UserId,
+        token_hash: &str,
+        expired_at: OffsetDateTime,
+        device_name: Option<String>,
+        created_at: Option<OffsetDateTime>,
+        updated_at: Option<OffsetDateTime>,
+    ) -> Self {
+        Self {
+            session_id,
+            user_id,
+            token_hash: token_hash.to_owned(),
+            expired_at,
+            device_name,
+            created_at,
+            updated_at,
+        }
+    }
+
+    /// セッションIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// セッションID。
+    pub fn session_id(&self) -> SessionId {
+        self.session_id.clone()
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// リフレッシュトークンのハッシュを返却する。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンのハッシュ。
+    pub fn token_hash(&self) -> &str {
+        &self.token_hash
+    }
+
+    /// 有効期限を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 有効期限。
+    pub fn expired_at(&self) -> OffsetDateTime {
+        self.expired_at
+    }
+
+    /// デバイス名を返却する。
+    ///
+    /// # Returns
+    ///
+    /// デバイス名。
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// 作成日時を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 作成日時。
+    pub fn created_at(&self) -> &Option<OffsetDateTime> {
+        &self.created_at
+    }
+
+    /// 更新日時を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 更新日時。
+    pub fn updated_at(&self) -> &Option<OffsetDateTime> {
+        &self.updated_at
+    }
+}
+
+impl RefreshToken {
+    /// セッションデータからリフレッシュトークンを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_data` - セッションデータ。
+    /// * `secret_key` - リフレッシュトークンのハッシュを計算する鍵。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンインスタンス。
+    pub fn from_session_data(
+        session_data: &SessionData,
+        secret_key: &Secret<String>,
+    ) -> anyhow::Result<Self> {
+        let expired_at =
+            OffsetDateTime::from_unix_timestamp(session_data.refresh_expiration as i64)
+                .map_err(|e| anyhow!("リフレッシュトークンの有効期限が不正です。{}", e))?;
+        let token_hash = refresh_token_hash(session_data.refresh_token.expose(), secret_key)?;
+
+        Ok(Self::new(
+            SessionId::new(session_data.session_id),
+            UserId::new(session_data.user_id),
+            &token_hash,
+            expired_at,
+            session_data.device_name.clone(),
+            None,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configurations::tokens::RedactedToken;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_refresh_token_from_session_data() {
+        let session_data = SessionData {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            access_token: RedactedToken::new("foo"),
+            access_expiration: 1_000,
+            refresh_token: RedactedToken::new("bar"),
+            refresh_expiration: 2_000,
+            generation: 1,
+            last_accessed_at: 1_000,
+            last_authenticated_at: 1_000,
+            device_name: Some("Chrome (Windows)".to_owned()),
+            refresh_count: 0,
+            version: 1,
+        };
+        let secret_key = Secret::new("some-secret".to_owned());
+        let refresh_token = RefreshToken::from_session_data(&session_data, &secret_key).unwrap();
+        assert_eq!(refresh_token.session_id().value(), session_data.session_id);
+        assert_eq!(refresh_token.user_id().value(), session_data.user_id);
+        assert_eq!(
+            refresh_token.token_hash(),
+            refresh_token_hash("bar", &secret_key).unwrap()
+        );
+        assert_eq!(refresh_token.expired_at().unix_timestamp(), 2_000);
+        assert_eq!(refresh_token.device_name(), Some("Chrome (Windows)"));
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::anyhow;
+use secrecy::{ExposeSecret, Secret};
+
+use configurations::password::{compute_hashed_password, verify_password};
+
+use crate::models::users::UserId;
+
+/// 秘密の質問の長さ
+const QUESTION_MIN_LEN: usize = 1;
+const QUESTION_MAX_LEN: usize = 200;
+
+/// 秘密の質問の回答の最小文字数
+const ANSWER_MIN_LEN: usize = 1;
+
+/// ユーザーが設定できる秘密の質問の最大数
+pub const SECURITY_QUESTIONS_MAX_COUNT: usize = 5;
+
+/// 秘密の質問の回答を正規化する。
+///
+/// 回答の入力ゆれを吸収するため、前後の空白を削除して、小文字に変換する。
+///
+/// # Arguments
+///
+/// * `answer` - 回答。
+///
+/// # Returns
+///
+/// 正規化した回答。
+fn normalize_answer(answer: &Secret<String>) -> Secret<String> {
+    Secret::new(answer.expose_secret().trim().to_lowercase())
+}
+
+/// 秘密の質問構造体
+///
+/// 回答は、パスワードと同様にハッシュ化して保持する。
+#[derive(Debug, Clone)]
+pub struct SecurityQuestion {
+    /// ユーザーID。
+    user_id: UserId,
+    /// 質問の順番。
+    position: i16,
+    /// 質問。
+    question: String,
+    /// ハッシュ化した回答。
+    hashed_answer: Secret<String>,
+}
+
+impl SecurityQuestion {
+    /// 秘密の質問インスタンスを構築する。
+    ///
+    /// 回答を正規化した後にハッシュ化する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `position` - 質問の順番。
+    /// * `question` - 質問。
+    /// * `answer` - 回答。
+    ///
+    /// # Returns
+    ///
+    /// 秘密の質問インスタンス。
+    pub fn new(
+        user_id: UserId,
+        position: i16,
+        question: &str,
+        answer: &Secret<String>,
+    ) -> anyhow::Result<Self> {
+        let question = question.trim();
+        let len = question.chars().count();
+        if !(QUESTION_MIN_LEN..=QUESTION_MAX_LEN).contains(&len) {
+            return Err(anyhow!(format!(
+                "秘密の質問は{}文字から{}文字です。",
+                QUESTION_MIN_LEN, QUESTION_MAX_LEN
+            )));
+        }
+        let answer = normalize_answer(answer);
+        if answer.expose_secret().chars().count() < ANSWER_MIN_LEN {
+            return Err(anyhow!(format!(
+                "秘密の質問の回答は{}文字以上で指定してください。",
+                ANSWER_MIN_LEN
+            )));
+        }
+        let hashed_answer = compute_hashed_password(&answer)?;
+
+        Ok(Self {
+            user_id,
+            position,
+            question: question.to_owned(),
+            hashed_answer,
+        })
+    }
+
+    /// ハッシュ化した回答から秘密の質問インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `position` - 質問の順番。
+    /// * `question` - 質問。
+    /// * `hashed_answer` - ハッシュ化した回答。
+    ///
+    /// # Returns
+    ///
+    /// 秘密の質問インスタンス。
+    pub fn new_unchecked(
+        user_id: UserId,
+        position: i16,
+        question: &str,
+        hashed_answer: &str,
+    ) -> Self {
+        Self {
+            user_id,
+            position,
+            question: question.to_owned(),
+            hashed_answer: Secret::new(hashed_answer.to_owned()),
+        }
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// 質問の順番を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 質問の順番。
+    pub fn position(&self) -> i16 {
+        self.position
+    }
+
+    /// 質問を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 質問。
+    pub fn question(&self) -> &str {
+        &self.question
+    }
+
+    /// ハッシュ化した回答を返却する。
+    ///
+    /// # Returns
+    ///
+    /// ハッシュ化した回答のPHC文字列。
+    pub fn hashed_answer(&self) -> &Secret<String> {
+        &self.hashed_answer
+    }
+
+    /// 回答が正しいか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `answer` - 回答。
+    ///
+    /// # Returns
+    ///
+    /// 回答が正しい場合は`true`。
+    pub fn verify(&self, answer: &Secret<String>) -> bool {
+        verify_password(&self.hashed_answer, &normalize_answer(answer)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_question_new() {
+        let answer = Secret::new("Tama".to_owned());
+        let question = SecurityQuestion::new(
+            UserId::default(),
+            0,
+            " 初めて飼ったペットの名前は？ ",
+            &answer,
+        )
+        .unwrap();
+        assert_eq!(question.question(), "初めて飼ったペットの名前は？");
+        // 回答が平文で保持されていないことを確認
+        assert!(!question.hashed_answer().expose_secret().contains("Tama"));
+        assert!(!question.hashed_answer().expose_secret().contains("tama"));
+        assert!(question
+            .hashed_answer()
+            .expose_secret()
+            .starts_with("$argon2"));
+        // 正規化した回答で検証できることを確認
+        assert!(question.verify(&answer));
+        assert!(question.verify(&Secret::new("  TAMA ".to_owned())));
+        assert!(!question.verify(&Secret::new("Pochi".to_owned())));
+    }
+
+    #[test]
+    fn test_security_question_new_invalid() {
+        let answer = Secret::new("Tama".to_owned());
+        assert!(SecurityQuestion::new(UserId::default(), 0, "  ", &answer).is_err());
+        assert!(SecurityQuestion::new(
+            UserId::default(),
+            0,
+            &"x".repeat(QUESTION_MAX_LEN + 1),
+            &answer
+        )
+        .is_err());
+        assert!(
+            SecurityQuestion::new(UserId::default(), 0, "質問", &Secret::new(" ".to_owned()))
+                .is_err()
+        );
+    }
+}
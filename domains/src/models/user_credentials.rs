@@ -0,0 +1,64 @@
+use crate::models::users::UserId;
+
+/// ユーザークレデンシャル構造体
+///
+/// ユーザーがWebAuthnで登録したパスキー（公開鍵クレデンシャル）を表現する。
+#[derive(Debug, Clone)]
+pub struct UserCredential {
+    /// クレデンシャルID。
+    credential_id: Vec<u8>,
+    /// ユーザーID。
+    user_id: UserId,
+    /// JSONにシリアライズしたパスキー。
+    ///
+    /// 公開鍵、署名カウンター及び登録時の検証ポリシーなどを含み、WebAuthnの実装が解釈する。
+    passkey: String,
+}
+
+impl UserCredential {
+    /// ユーザークレデンシャルインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `credential_id` - クレデンシャルID。
+    /// * `user_id` - ユーザーID。
+    /// * `passkey` - JSONにシリアライズしたパスキー。
+    ///
+    /// # Returns
+    ///
+    /// ユーザークレデンシャルインスタンス。
+    pub fn new(credential_id: Vec<u8>, user_id: UserId, passkey: String) -> Self {
+        Self {
+            credential_id,
+            user_id,
+            passkey,
+        }
+    }
+
+    /// クレデンシャルIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// クレデンシャルID。
+    pub fn credential_id(&self) -> &[u8] {
+        &self.credential_id
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// JSONにシリアライズしたパスキーを返却する。
+    ///
+    /// # Returns
+    ///
+    /// JSONにシリアライズしたパスキー。
+    pub fn passkey(&self) -> &str {
+        &self.passkey
+    }
+}
@@ -0,0 +1,115 @@
+use time::OffsetDateTime;
+
+use crate::models::base::{EmailAddress, EntityId};
+use crate::models::users::UserId;
+
+/// ユーザーEメールアドレスID
+pub type UserEmailAddressId = EntityId<UserEmailAddress>;
+
+/// ユーザーEメールアドレス構造体
+///
+/// ユーザーに登録されたEメールアドレスを表現する。ユーザーは複数のEメールアドレスを登録でき、どの
+/// Eメールアドレスでもログインできる。プライマリEメールアドレスは、ユーザーへの通知の送信先として使用する。
+#[derive(Debug, Clone)]
+pub struct UserEmailAddress {
+    /// ユーザーEメールアドレスID。
+    id: UserEmailAddressId,
+    /// ユーザーID。
+    user_id: UserId,
+    /// Eメールアドレス。
+    email_address: EmailAddress,
+    /// プライマリEメールアドレスか。
+    is_primary: bool,
+    /// 確認済みか。
+    is_verified: bool,
+    /// 作成日時。
+    created_at: Option<OffsetDateTime>,
+}
+
+impl UserEmailAddress {
+    /// ユーザーEメールアドレスインスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ユーザーEメールアドレスID。
+    /// * `user_id` - ユーザーID。
+    /// * `email_address` - Eメールアドレス。
+    /// * `is_primary` - プライマリEメールアドレスか。
+    /// * `is_verified` - 確認済みか。
+    /// * `created_at` - 作成日時。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーEメールアドレスインスタンス。
+    pub fn new(
+        id: UserEmailAddressId,
+        user_id: UserId,
+        email_address: EmailAddress,
+        is_primary: bool,
+        is_verified: bool,
+        created_at: Option<OffsetDateTime>,
+    ) -> Self {
+        Self {
+            id,
+            user_id,
+            email_address,
+            is_primary,
+            is_verified,
+            created_at,
+        }
+    }
+
+    /// ユーザーEメールアドレスIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーEメールアドレスID。
+    pub fn id(&self) -> UserEmailAddressId {
+        self.id.clone()
+    }
+
+    /// ユーザーIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーID。
+    pub fn user_id(&self) -> UserId {
+        self.user_id.clone()
+    }
+
+    /// Eメールアドレスを返却する。
+    ///
+    /// # Returns
+    ///
+    /// Eメールアドレス。
+    pub fn email_address(&self) -> &EmailAddress {
+        &self.email_address
+    }
+
+    /// プライマリEメールアドレスかを返却する。
+    ///
+    /// # Returns
+    ///
+    /// プライマリEメールアドレスの場合は`true`。
+    pub fn is_primary(&self) -> bool {
+        self.is_primary
+    }
+
+    /// 確認済みかを返却する。
+    ///
+    /// # Returns
+    ///
+    /// 確認済みの場合は`true`。
+    pub fn is_verified(&self) -> bool {
+        self.is_verified
+    }
+
+    /// 作成日時を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 作成日時。
+    pub fn created_at(&self) -> Option<OffsetDateTime> {
+        self.created_at
+    }
+}
@@ -1,26 +1,49 @@
 use anyhow::anyhow;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use time::OffsetDateTime;
+use uuid::Uuid;
 use validator::Validate;
 
-use configurations::password::compute_hashed_password;
+use configurations::{
+    password::compute_hashed_password, RawPasswordSettings, RAW_PASSWORD_SETTINGS,
+    USER_NAME_SETTINGS,
+};
 
 use crate::models::base::{EmailAddress, EntityId};
+use crate::models::errors::{DomainError, ValidationError};
 
 /// ユーザー名の長さ
 const USER_NAME_MIN_LEN: usize = 2;
 const USER_NAME_MAX_LEN: usize = 40;
 
 /// ユーザー名構造体
+///
+/// ユーザー名は入力された大文字と小文字を保持して表示するが、一意性や予約されたユーザー名との比較は、
+/// 正規化したユーザー名で大文字と小文字を区別せずに実施する。
 #[derive(Debug, Clone, Validate)]
 pub struct UserName {
     #[validate(length(min = "USER_NAME_MIN_LEN", max = "USER_NAME_MAX_LEN"))]
     value: String,
 }
 
+/// ユーザー名を比較するための正規化した形式に変換する。
+///
+/// # Arguments
+///
+/// * `value` - ユーザー名。
+///
+/// # Returns
+///
+/// 正規化したユーザー名。
+fn canonicalize_user_name(value: &str) -> String {
+    value.to_lowercase()
+}
+
 impl UserName {
     /// ユーザー名インスタンスを構築する。
     ///
+    /// 予約されたユーザー名は、大文字と小文字を区別せずに拒否する。
+    ///
     /// # Arguments
     ///
     /// * `value` - ユーザー名。
@@ -28,20 +51,60 @@ impl UserName {
     /// # Returns
     ///
     /// ユーザー名インスタンス。
-    pub fn new(value: &str) -> anyhow::Result<Self> {
-        let user_name = Self {
-            value: value.to_owned(),
-        };
-        if user_name.validate().is_err() {
-            return Err(anyhow!(format!(
-                "ユーザー名は{}文字から{}文字です。",
-                USER_NAME_MIN_LEN, USER_NAME_MAX_LEN
-            )));
+    pub fn new(value: &str) -> Result<Self, DomainError> {
+        Self::new_with_reserved_names(value, &USER_NAME_SETTINGS.reserved_names)
+    }
+
+    /// 予約されたユーザー名を指定して、ユーザー名インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - ユーザー名。
+    /// * `reserved_names` - 予約されたユーザー名のスライス。
+    ///
+    /// # Returns
+    ///
+    /// ユーザー名インスタンス。
+    pub fn new_with_reserved_names(
+        value: &str,
+        reserved_names: &[String],
+    ) -> Result<Self, DomainError> {
+        let user_name = Self::new_unchecked(value);
+        if let Err(e) = user_name.validate() {
+            return Err(DomainError::UserNameLength {
+                min: USER_NAME_MIN_LEN,
+                max: USER_NAME_MAX_LEN,
+                errors: ValidationError::from_validator(&e),
+            });
+        }
+        let canonical = user_name.canonical();
+        if reserved_names
+            .iter()
+            .any(|name| canonicalize_user_name(name) == canonical)
+        {
+            return Err(DomainError::ReservedUserName(value.to_owned()));
         }
 
         Ok(user_name)
     }
 
+    /// 検証せずにユーザー名インスタンスを構築する。
+    ///
+    /// データベースに登録されているユーザー名など、検証済みのユーザー名から構築するときに使用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - ユーザー名。
+    ///
+    /// # Returns
+    ///
+    /// ユーザー名インスタンス。
+    pub fn new_unchecked(value: &str) -> Self {
+        Self {
+            value: value.to_owned(),
+        }
+    }
+
     /// ユーザー名を文字列で返却する。
     ///
     /// # Returns
@@ -50,17 +113,30 @@ impl UserName {
     pub fn value(&self) -> &str {
         &self.value
     }
+
+    /// 比較するために正規化したユーザー名を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 正規化したユーザー名。
+    pub fn canonical(&self) -> String {
+        canonicalize_user_name(&self.value)
+    }
 }
 
-/// パスワードの最小文字数
-const RAW_PASSWORD_MIN_LEN: usize = 8;
 // パスワードに使用できる記号文字
 const RAW_PASSWORD_SIGNS: &str = r##" !"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##;
 
+/// クライアントでハッシュ化したパスワードの長さ
+const CLIENT_HASHED_PASSWORD_MIN_LEN: usize = 32;
+const CLIENT_HASHED_PASSWORD_MAX_LEN: usize = 1024;
+
 /// パスワード構造体
 ///
-/// パスワードは、アルファベットの大文字と小文字、数字及び記号で構成された、8文字以上の文字列
-/// でなければならない。
+/// パスワードは、アルファベットの大文字と小文字、数字及び記号で構成された、環境変数`RAW_PASSWORD_MIN_LEN`
+/// で設定した文字数以上で、環境変数`RAW_PASSWORD_MAX_LEN`で設定した文字数以下の文字列でなければならない。
+///
+/// ただし、クライアントでハッシュ化したパスワードは、文字種を検証せずに長さのみを検証する。
 #[derive(Debug, Clone)]
 pub struct RawPassword {
     value: Secret<String>,
@@ -76,31 +152,103 @@ impl RawPassword {
     /// # Returns
     ///
     /// パスワード。
-    pub fn new(value: &str) -> anyhow::Result<Self> {
-        if value.len() < RAW_PASSWORD_MIN_LEN {
-            return Err(anyhow!(format!(
-                "パスワードは{}文字以上の文字列で指定してください。",
-                RAW_PASSWORD_MIN_LEN
-            )));
+    pub fn new(value: &str) -> Result<Self, DomainError> {
+        Self::new_with_settings(value, &RAW_PASSWORD_SETTINGS)
+    }
+
+    /// パスワード設定を指定して、パスワードを構築する。
+    ///
+    /// 非常に長いパスワードのハッシュ化でサーバーの処理時間を浪費しないように、文字種を検証する前に文字数を
+    /// 検証する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - パスワード。
+    /// * `settings` - パスワードの最小文字数と最大文字数を記録したパスワード設定。
+    ///
+    /// # Returns
+    ///
+    /// パスワード。
+    pub fn new_with_settings(
+        value: &str,
+        settings: &RawPasswordSettings,
+    ) -> Result<Self, DomainError> {
+        if value.len() < settings.min_len {
+            return Err(DomainError::PasswordTooShort(settings.min_len));
+        }
+        if settings.max_len < value.len() {
+            return Err(DomainError::PasswordTooLong(settings.max_len));
         }
         if !value.chars().any(|ch| ch.is_ascii_alphabetic()) {
-            return Err(anyhow!("パスワードにアルファベットが含まれていません。"));
+            return Err(DomainError::PasswordMissingClass("アルファベット"));
         }
         if !value.chars().any(|ch| ch.is_ascii_lowercase()) {
-            return Err(anyhow!(
-                "パスワードに小文字のアルファベットが含まれていません。"
-            ));
+            return Err(DomainError::PasswordMissingClass("小文字のアルファベット"));
         }
         if !value.chars().any(|ch| ch.is_ascii_uppercase()) {
-            return Err(anyhow!(
-                "パスワードに大文字のアルファベットが含まれていません。"
-            ));
+            return Err(DomainError::PasswordMissingClass("大文字のアルファベット"));
         }
         if !value.chars().any(|ch| ch.is_ascii_digit()) {
-            return Err(anyhow!("パスワードに数字が含まれていません。"));
+            return Err(DomainError::PasswordMissingClass("数字"));
         }
         if !value.chars().any(|ch| RAW_PASSWORD_SIGNS.contains(ch)) {
-            return Err(anyhow!("パスワードに記号が含まれていません。"));
+            return Err(DomainError::PasswordMissingClass("記号"));
+        }
+
+        Ok(Self {
+            value: Secret::new(value.to_owned()),
+        })
+    }
+
+    /// パスワードが、現在のパスワードのポリシーを満たしているか確認する。
+    ///
+    /// 平文のパスワードは記録しないため、パスワードのポリシーを強化した後に既存のユーザーのパスワードが
+    /// ポリシーを満たしているかは、平文のパスワードを受け取るログイン時にのみ確認できる。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - パスワード。
+    ///
+    /// # Returns
+    ///
+    /// ポリシーを満たしている場合は`true`。
+    pub fn meets_current_policy(value: &Secret<String>) -> bool {
+        Self::meets_policy(value, &RAW_PASSWORD_SETTINGS)
+    }
+
+    /// パスワードが、パスワード設定で指定したポリシーを満たしているか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - パスワード。
+    /// * `settings` - パスワード設定。
+    ///
+    /// # Returns
+    ///
+    /// ポリシーを満たしている場合は`true`。
+    pub fn meets_policy(value: &Secret<String>, settings: &RawPasswordSettings) -> bool {
+        Self::new_with_settings(value.expose_secret(), settings).is_ok()
+    }
+
+    /// クライアントでハッシュ化したパスワードからパスワードインスタンスを構築する。
+    ///
+    /// クライアントでハッシュ化したパスワードは、文字種を検証せずに、そのままArgon2でハッシュ化する
+    /// 値とする。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - クライアントでハッシュ化したパスワード。
+    ///
+    /// # Returns
+    ///
+    /// パスワード。
+    pub fn new_client_hashed(value: &str) -> Result<Self, DomainError> {
+        if !(CLIENT_HASHED_PASSWORD_MIN_LEN..=CLIENT_HASHED_PASSWORD_MAX_LEN).contains(&value.len())
+        {
+            return Err(DomainError::ClientHashedPasswordLength {
+                min: CLIENT_HASHED_PASSWORD_MIN_LEN,
+                max: CLIENT_HASHED_PASSWORD_MAX_LEN,
+            });
         }
 
         Ok(Self {
@@ -142,16 +290,19 @@ impl HashedPassword {
 
     /// ハッシュ化した文字列からハッシュ化パスワードインスタンスを構築する。
     ///
+    /// ハッシュ化した文字列の複製をメモリに残さないように、文字列の所有権を受け取り、そのまま`Secret`で
+    /// 保持する。これにより、インスタンスを破棄したときに文字列がゼロクリアされる。
+    ///
     /// # Arguments
     ///
-    /// * `hashed_password`: パスワードインスタンス。
+    /// * `hashed_password`: ハッシュ化した文字列。
     ///
     /// # Returns
     ///
     /// ハッシュ化パスワードインスタンス。
-    pub fn new_unchecked(hashed_password: &str) -> Self {
+    pub fn new_unchecked(hashed_password: String) -> Self {
         Self {
-            value: Secret::new(hashed_password.to_owned()),
+            value: Secret::new(hashed_password),
         }
     }
 
@@ -165,14 +316,146 @@ impl HashedPassword {
     }
 }
 
+/// 公開範囲
+///
+/// ユーザーのプロフィール情報を、どの閲覧者に公開するかを表現する。
+/// ユーザー自身は、公開範囲にかかわらず、すべてのプロフィール情報を閲覧できる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// すべての閲覧者に公開する。
+    Public,
+    /// 認証された閲覧者にのみ公開する。
+    AuthenticatedOnly,
+    /// 公開しない。
+    Private,
+}
+
+impl Visibility {
+    /// 公開範囲を表現する文字列を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 公開範囲を表現する文字列。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::AuthenticatedOnly => "authenticated_only",
+            Self::Private => "private",
+        }
+    }
+
+    /// 閲覧者に公開するか判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `is_authenticated` - 閲覧者が認証されているか。
+    /// * `is_owner` - 閲覧者がユーザー自身か。
+    ///
+    /// # Returns
+    ///
+    /// 公開する場合は`true`。
+    pub fn is_visible_to(&self, is_authenticated: bool, is_owner: bool) -> bool {
+        match self {
+            Self::Public => true,
+            Self::AuthenticatedOnly => is_authenticated,
+            Self::Private => is_owner,
+        }
+    }
+}
+
+impl TryFrom<&str> for Visibility {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "public" => Ok(Self::Public),
+            "authenticated_only" => Ok(Self::AuthenticatedOnly),
+            "private" => Ok(Self::Private),
+            _ => Err(anyhow!(format!(
+                "公開範囲({})は、public、authenticated_only又はprivateで指定してください。",
+                value
+            ))),
+        }
+    }
+}
+
+/// プロフィールの公開設定
+///
+/// ユーザーのプロフィール情報ごとの公開範囲を表現する。
+/// ユーザーIDとユーザー名は、常にすべての閲覧者に公開する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileVisibility {
+    /// Eメールアドレスの公開範囲。
+    pub email_address: Visibility,
+    /// 最終ログイン日時の公開範囲。
+    pub last_logged_in: Visibility,
+}
+
+impl Default for ProfileVisibility {
+    fn default() -> Self {
+        Self {
+            email_address: Visibility::Private,
+            last_logged_in: Visibility::AuthenticatedOnly,
+        }
+    }
+}
+
+/// ユーザーの役割
+///
+/// 管理者APIなど、特定の役割のユーザーにのみ許可する操作を制限するために使用する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UserRole {
+    /// 一般ユーザー。
+    #[default]
+    User,
+    /// 所属するテナントのユーザーとセッションを管理する管理者。
+    Admin,
+}
+
+impl UserRole {
+    /// 役割を表現する文字列を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 役割を表現する文字列。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl TryFrom<&str> for UserRole {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "user" => Ok(Self::User),
+            "admin" => Ok(Self::Admin),
+            _ => Err(anyhow!(format!(
+                "役割({})は、user又はadminで指定してください。",
+                value
+            ))),
+        }
+    }
+}
+
 /// ユーザーID
 pub type UserId = EntityId<User>;
 
+/// 既定のテナントID
+///
+/// テナントを指定せずに登録したユーザーや、テナントを導入する前に登録されていたユーザーが所属するテナント。
+pub const DEFAULT_TENANT_ID: Uuid = Uuid::nil();
+
 /// ユーザー
 #[derive(Debug, Clone, Validate)]
 pub struct User {
     /// ユーザーID。
     id: UserId,
+    /// 所属するテナントのテナントID。
+    tenant_id: Uuid,
     /// ユーザー名。
     user_name: UserName,
     /// Eメールアドレス。
@@ -183,6 +466,14 @@ pub struct User {
     is_active: bool,
     /// 最終ログイン日時。
     last_logged_in: Option<OffsetDateTime>,
+    /// プロフィールの公開設定。
+    profile_visibility: ProfileVisibility,
+    /// Base32でエンコードしたTOTPの共有シークレット。2要素認証を有効にしていない場合は`None`。
+    totp_secret: Option<Secret<String>>,
+    /// 次回のログインでパスワードの変更を要求するか。
+    must_change_password: bool,
+    /// 役割。
+    role: UserRole,
     /// 作成日時。
     created_at: Option<OffsetDateTime>,
     /// 更新日時。
@@ -195,31 +486,46 @@ impl User {
     /// # Arguments
     ///
     /// * `id` - ユーザーID。
+    /// * `tenant_id` - 所属するテナントのテナントID。
     /// * `user_name` - ユーザー名。
     /// * `email_address` - Eメイルアドレス。
     /// * `hashed_password` - ハッシュ化パスワード。
     /// * `is_active` - アクティブフラグ。
     /// * `last_logged_in` - 最終ログイン日時。
+    /// * `profile_visibility` - プロフィールの公開設定。
+    /// * `totp_secret` - TOTPの共有シークレット。
+    /// * `must_change_password` - 次回のログインでパスワードの変更を要求するか。
+    /// * `role` - 役割。
     /// * `created_at` - 作成日時。
     /// * `updated_at` - 更新日時。
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: UserId,
+        tenant_id: Uuid,
         user_name: UserName,
         email_address: EmailAddress,
         hashed_password: HashedPassword,
         is_active: bool,
         last_logged_in: Option<OffsetDateTime>,
+        profile_visibility: ProfileVisibility,
+        totp_secret: Option<Secret<String>>,
+        must_change_password: bool,
+        role: UserRole,
         created_at: Option<OffsetDateTime>,
         updated_at: Option<OffsetDateTime>,
     ) -> Self {
         Self {
             id,
+            tenant_id,
             user_name,
             email_address,
             hashed_password,
             is_active,
             last_logged_in,
+            profile_visibility,
+            totp_secret,
+            must_change_password,
+            role,
             created_at,
             updated_at,
         }
@@ -234,6 +540,15 @@ impl User {
         self.id.clone()
     }
 
+    /// 所属するテナントのテナントIDを返却する。
+    ///
+    /// # Returns
+    ///
+    /// テナントID。
+    pub fn tenant_id(&self) -> Uuid {
+        self.tenant_id
+    }
+
     /// ユーザー名を返却する。
     ///
     /// # Returns
@@ -279,6 +594,51 @@ impl User {
         &self.last_logged_in
     }
 
+    /// プロフィールの公開設定を返却する。
+    ///
+    /// # Returns
+    ///
+    /// プロフィールの公開設定。
+    pub fn profile_visibility(&self) -> ProfileVisibility {
+        self.profile_visibility
+    }
+
+    /// プロフィールの公開設定を変更する。
+    ///
+    /// # Arguments
+    ///
+    /// * `profile_visibility` - プロフィールの公開設定。
+    pub fn set_profile_visibility(&mut self, profile_visibility: ProfileVisibility) {
+        self.profile_visibility = profile_visibility;
+    }
+
+    /// TOTPの共有シークレットを返却する。
+    ///
+    /// # Returns
+    ///
+    /// Base32でエンコードしたTOTPの共有シークレット。2要素認証を有効にしていない場合は`None`。
+    pub fn totp_secret(&self) -> Option<&Secret<String>> {
+        self.totp_secret.as_ref()
+    }
+
+    /// 次回のログインでパスワードの変更を要求するかを返却する。
+    ///
+    /// # Returns
+    ///
+    /// パスワードの変更を要求する場合は`true`。
+    pub fn must_change_password(&self) -> bool {
+        self.must_change_password
+    }
+
+    /// 役割を返却する。
+    ///
+    /// # Returns
+    ///
+    /// 役割。
+    pub fn role(&self) -> UserRole {
+        self.role
+    }
+
     /// 作成日時を返却する。
     ///
     /// # Returns
@@ -313,6 +673,37 @@ mod tests {
         }
     }
 
+    /// 予約されたユーザー名を、大文字と小文字を区別せずに拒否することを確認する。
+    #[test]
+    fn test_user_name_reserved() {
+        let reserved_names = vec!["admin".to_owned(), "Root".to_owned()];
+        for value in ["admin", "Admin", "ADMIN", "root", "rOOt"] {
+            assert!(
+                UserName::new_with_reserved_names(value, &reserved_names).is_err(),
+                "{}",
+                value
+            );
+        }
+        let user_name = UserName::new_with_reserved_names("Admin2", &reserved_names).unwrap();
+        assert_eq!(user_name.value(), "Admin2");
+        // 既定の予約されたユーザー名を拒否
+        assert!(UserName::new("Admin").is_err());
+    }
+
+    /// 大文字と小文字が異なるユーザー名が、同じ正規化した形式になることを確認する。
+    #[test]
+    fn test_user_name_canonical() {
+        let upper = UserName::new_unchecked("Admin");
+        let lower = UserName::new_unchecked("admin");
+        // 表示用の大文字と小文字は保持
+        assert_eq!(upper.value(), "Admin");
+        assert_eq!(upper.canonical(), lower.canonical());
+        assert_ne!(
+            UserName::new_unchecked("Alice").canonical(),
+            UserName::new_unchecked("Alicia").canonical()
+        );
+    }
+
     #[test]
     fn test_user_name_gen_by_invalid_strings() {
         let values = vec![
@@ -325,6 +716,46 @@ mod tests {
         }
     }
 
+    /// ユーザー名の検証に失敗した場合は、検証に失敗したフィールドと制約を返却することを確認する。
+    #[test]
+    fn test_user_name_validation_errors() {
+        let reserved_names = vec!["admin".to_owned()];
+        for (value, code) in [
+            ("x".repeat(USER_NAME_MIN_LEN - 1), "length"),
+            ("x".repeat(USER_NAME_MAX_LEN + 1), "length"),
+            ("Admin".to_owned(), "reserved"),
+        ] {
+            let e = UserName::new_with_reserved_names(&value, &reserved_names).unwrap_err();
+            assert_eq!(
+                e.validation_errors(),
+                vec![ValidationError {
+                    field: "value".to_owned(),
+                    code: code.to_owned(),
+                }],
+                "{}",
+                value
+            );
+        }
+    }
+
+    /// ユーザー名を構築できない理由を、ドメインエラーのバリアントで判別できることを確認する。
+    #[test]
+    fn test_user_name_new_error_variants() {
+        let reserved_names = vec!["admin".to_owned()];
+        let e = UserName::new_with_reserved_names("Admin", &reserved_names).unwrap_err();
+        assert_eq!(e, DomainError::ReservedUserName("Admin".to_owned()));
+        assert_eq!(
+            e.to_string(),
+            "ユーザー名(Admin)は予約されているため使用できません。"
+        );
+        let e = UserName::new_with_reserved_names("", &reserved_names).unwrap_err();
+        assert!(matches!(
+            e,
+            DomainError::UserNameLength { min, max, .. }
+                if min == USER_NAME_MIN_LEN && max == USER_NAME_MAX_LEN
+        ));
+    }
+
     /// パスワードを構築できることを確認する。
     #[test]
     fn test_raw_password_gen() {
@@ -356,4 +787,135 @@ mod tests {
         // 記号を含んでいない
         assert!(RawPassword::new("01abCDef").is_err(), "記号");
     }
+
+    /// パスワードを構築できない理由を、ドメインエラーのバリアントで判別できることを確認する。
+    #[test]
+    fn test_raw_password_new_error_variants() {
+        assert_eq!(
+            RawPassword::new("01abCD#").unwrap_err(),
+            DomainError::PasswordTooShort(RAW_PASSWORD_SETTINGS.min_len)
+        );
+        for (value, class) in [
+            ("012345#$", "アルファベット"),
+            ("01ABCD#$", "小文字のアルファベット"),
+            ("01abcd#$", "大文字のアルファベット"),
+            ("abcDEF#$", "数字"),
+            ("01abCDef", "記号"),
+        ] {
+            let e = RawPassword::new(value).unwrap_err();
+            assert_eq!(e, DomainError::PasswordMissingClass(class), "{}", value);
+            // 既存のメッセージを維持
+            assert_eq!(
+                e.to_string(),
+                format!("パスワードに{}が含まれていません。", class)
+            );
+        }
+        assert!(matches!(
+            RawPassword::new_client_hashed("a").unwrap_err(),
+            DomainError::ClientHashedPasswordLength { min, max }
+                if min == CLIENT_HASHED_PASSWORD_MIN_LEN && max == CLIENT_HASHED_PASSWORD_MAX_LEN
+        ));
+    }
+
+    /// 最大文字数のパスワードを構築でき、最大文字数を超えるパスワードを構築できないことを確認する。
+    #[test]
+    fn test_raw_password_max_len() {
+        let max_len = 256;
+        let settings = RawPasswordSettings {
+            min_len: 8,
+            max_len,
+        };
+        let password = format!("01abCD#${}", "x".repeat(max_len - 8));
+        assert_eq!(password.len(), max_len);
+        assert!(RawPassword::new_with_settings(&password, &settings).is_ok());
+        let password = format!("{}x", password);
+        let e = RawPassword::new_with_settings(&password, &settings).unwrap_err();
+        assert_eq!(e, DomainError::PasswordTooLong(max_len));
+        assert!(e.to_string().contains("256文字以下"), "{}", e);
+    }
+
+    /// パスワードのポリシーを強化すると、以前はポリシーを満たしていたパスワードが満たさなくなることを確認する。
+    #[test]
+    fn test_raw_password_meets_policy() {
+        let password = Secret::new("01abCD#$xy".to_owned());
+        let current = RawPasswordSettings {
+            min_len: 8,
+            max_len: 256,
+        };
+        assert!(RawPassword::meets_policy(&password, &current));
+        let tightened = RawPasswordSettings {
+            min_len: 12,
+            max_len: 256,
+        };
+        assert!(!RawPassword::meets_policy(&password, &tightened));
+        // 文字種を満たしていないパスワード
+        assert!(!RawPassword::meets_policy(
+            &Secret::new("password".to_owned()),
+            &current
+        ));
+    }
+
+    /// クライアントでハッシュ化したパスワードを、文字種を検証せずに構築できることを確認する。
+    #[test]
+    fn test_raw_password_new_client_hashed() {
+        let hashed = "a".repeat(64);
+        let result = RawPassword::new_client_hashed(&hashed);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().value().expose_secret(), &hashed);
+        assert!(
+            RawPassword::new_client_hashed(&"a".repeat(CLIENT_HASHED_PASSWORD_MIN_LEN)).is_ok()
+        );
+        assert!(
+            RawPassword::new_client_hashed(&"a".repeat(CLIENT_HASHED_PASSWORD_MAX_LEN)).is_ok()
+        );
+        // 長さが範囲外
+        assert!(
+            RawPassword::new_client_hashed(&"a".repeat(CLIENT_HASHED_PASSWORD_MIN_LEN - 1))
+                .is_err()
+        );
+        assert!(
+            RawPassword::new_client_hashed(&"a".repeat(CLIENT_HASHED_PASSWORD_MAX_LEN + 1))
+                .is_err()
+        );
+    }
+
+    /// 公開範囲と閲覧者の認証状態から、公開するか判定できることを確認する。
+    #[test]
+    fn test_visibility_is_visible_to() {
+        // (公開範囲, 未認証, 認証済み, ユーザー自身)
+        let cases = [
+            (Visibility::Public, true, true, true),
+            (Visibility::AuthenticatedOnly, false, true, true),
+            (Visibility::Private, false, false, true),
+        ];
+        for (visibility, anonymous, authenticated, owner) in cases {
+            assert_eq!(visibility.is_visible_to(false, false), anonymous);
+            assert_eq!(visibility.is_visible_to(true, false), authenticated);
+            assert_eq!(visibility.is_visible_to(true, true), owner);
+        }
+    }
+
+    #[test]
+    fn test_visibility_try_from() {
+        for visibility in [
+            Visibility::Public,
+            Visibility::AuthenticatedOnly,
+            Visibility::Private,
+        ] {
+            assert_eq!(
+                Visibility::try_from(visibility.as_str()).unwrap(),
+                visibility
+            );
+        }
+        assert!(Visibility::try_from("friends").is_err());
+    }
+
+    #[test]
+    fn test_user_role_try_from() {
+        for role in [UserRole::User, UserRole::Admin] {
+            assert_eq!(UserRole::try_from(role.as_str()).unwrap(), role);
+        }
+        assert_eq!(UserRole::default(), UserRole::User);
+        assert!(UserRole::try_from("owner").is_err());
+    }
 }
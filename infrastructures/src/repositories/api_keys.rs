@@ -0,0 +1,147 @@
+use sqlx::{Postgres, Transaction};
+
+use domains::models::api_keys::{ApiKey, ApiKeyId, ApiKeyScope};
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// APIキー登録エラー
+    #[error("APIキーを登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgApiKeyRepository;
+
+impl PgApiKeyRepository {
+    /// APIキーを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - 登録するAPIキーインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        api_key: &ApiKey,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), ApiKeyRepositoryError> {
+        let scopes: Vec<String> = api_key
+            .scopes()
+            .iter()
+            .map(|scope| scope.value().to_owned())
+            .collect();
+        // APIキーを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO api_keys (
+                id, key_hash, user_id, scopes, expired_at, created_at
+            ) VALUES (
+                $1, $2, $3, $4, $5, current_timestamp
+            )
+            "#,
+            api_key.id().value(),
+            api_key.key_hash(),
+            api_key.user_id().value(),
+            &scopes,
+            api_key.expired_at(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| ApiKeyRepositoryError::UnexpectedError(e.into()))?;
+        // APIキーが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(ApiKeyRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// APIキーのハッシュでAPIキーを取得する。
+    ///
+    /// 有効期限が切れたAPIキーも返却するため、呼び出し側で有効期限を確認すること。
+    ///
+    /// # Arguments
+    ///
+    /// * `key_hash` - APIキーのハッシュ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// APIキーインスタンス。APIキーが見つからなかった場合は`None`。
+    pub async fn get_by_key_hash(
+        &self,
+        key_hash: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<ApiKey>, ApiKeyRepositoryError> {
+        // データベースを操作
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                id, user_id, scopes, expired_at
+            FROM api_keys
+            WHERE
+                key_hash = $1
+            "#,
+            key_hash,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| ApiKeyRepositoryError::UnexpectedError(e.into()))?;
+        let record = match record {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let scopes = record
+            .scopes
+            .iter()
+            .map(|scope| ApiKeyScope::new(scope))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(ApiKeyRepositoryError::UnexpectedError)?;
+
+        Ok(Some(ApiKey::new(
+            ApiKeyId::new(record.id),
+            key_hash,
+            UserId::new(record.user_id),
+            scopes,
+            record.expired_at,
+        )))
+    }
+
+    /// ユーザーのAPIキーを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 削除するAPIキーのAPIキーID。
+    /// * `user_id` - APIキーを所有するユーザーのユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// APIキーを削除した場合は`true`。ユーザーのAPIキーが見つからなかった場合は`false`。
+    pub async fn delete(
+        &self,
+        id: ApiKeyId,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, ApiKeyRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM api_keys
+            WHERE
+                id = $1
+                AND user_id = $2
+            "#,
+            id.value(),
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| ApiKeyRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
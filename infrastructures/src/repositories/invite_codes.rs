@@ -0,0 +1,128 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InviteCodeRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// 招待コード登録エラー
+    #[error("招待コードを登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgInviteCodeRepository;
+
+impl PgInviteCodeRepository {
+    /// 未使用の招待コードを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `code_hash` - 招待コードのハッシュ。
+    /// * `tenant_id` - 招待コードを使用して登録したユーザーが所属するテナントのテナントID。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        code_hash: &str,
+        tenant_id: Uuid,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), InviteCodeRepositoryError> {
+        // 招待コードを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO invite_codes (
+                code_hash, tenant_id, used_by, used_at, created_at
+            ) VALUES (
+                $1, $2, NULL, NULL, current_timestamp
+            )
+            "#,
+            code_hash,
+            tenant_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| InviteCodeRepositoryError::UnexpectedError(e.into()))?;
+        // 招待コードが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(InviteCodeRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// 未使用の招待コードが招待するテナントのテナントIDを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `code_hash` - 招待コードのハッシュ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// テナントID。招待コードが見つからないか、使用済みの場合は`None`。
+    pub async fn get_unused_tenant_id(
+        &self,
+        code_hash: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Uuid>, InviteCodeRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                tenant_id
+            FROM
+                invite_codes
+            WHERE
+                code_hash = $1
+                AND used_at IS NULL
+            "#,
+            code_hash,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| InviteCodeRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.map(|record| record.tenant_id))
+    }
+
+    /// 未使用の招待コードを使用済みにする。
+    ///
+    /// # Arguments
+    ///
+    /// * `code_hash` - 招待コードのハッシュ。
+    /// * `user_id` - 招待コードを使用して登録したユーザーのユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 未使用の招待コードを使用済みにした場合は`true`。招待コードが見つからないか、使用済みの場合は`false`。
+    pub async fn consume(
+        &self,
+        code_hash: &str,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, InviteCodeRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE invite_codes
+            SET
+                used_by = $2,
+                used_at = current_timestamp
+            WHERE
+                code_hash = $1
+                AND used_at IS NULL
+            "#,
+            code_hash,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| InviteCodeRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,167 @@
+use sqlx::{types::time::OffsetDateTime, Postgres, Transaction};
+
+use domains::models::login_attempts::{GeoLocation, LoginAttempt, LoginAttemptId};
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoginAttemptRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// ログイン試行登録エラー
+    #[error("ログイン試行を登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgLoginAttemptRepository;
+
+impl PgLoginAttemptRepository {
+    /// ログイン試行を登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - 登録するログイン試行インスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        attempt: &LoginAttempt,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), LoginAttemptRepositoryError> {
+        // ログイン試行を登録
+        let location = attempt.location();
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO login_attempts (
+                id, user_id, succeeded, ip_address, device_name,
+                latitude, longitude, attempted_at
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            )
+            "#,
+            attempt.id().value(),
+            attempt.user_id().value(),
+            attempt.succeeded(),
+            attempt.ip_address(),
+            attempt.device_name(),
+            location.map(|l| l.latitude()),
+            location.map(|l| l.longitude()),
+            attempt.attempted_at(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| LoginAttemptRepositoryError::UnexpectedError(e.into()))?;
+        // ログイン試行が登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(LoginAttemptRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// ユーザーが最近ログインに成功したログイン試行を取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `limit` - 取得するログイン試行の最大数。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 試行日時の降順で並べたログイン試行インスタンスのベクタ。
+    pub async fn list_recent_succeeded_by_user_id(
+        &self,
+        user_id: UserId,
+        limit: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<LoginAttempt>, LoginAttemptRepositoryError> {
+        // データーベースに問い合わせ
+        let records = sqlx::query!(
+            r#"
+            SELECT
+                id, succeeded, ip_address, device_name,
+                latitude, longitude, attempted_at
+            FROM
+                login_attempts
+            WHERE
+                user_id = $1 AND succeeded
+            ORDER BY
+                attempted_at DESC
+            LIMIT $2
+            "#,
+            user_id.value(),
+            limit,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| LoginAttemptRepositoryError::UnexpectedError(e.into()))?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                let location = match (record.latitude, record.longitude) {
+                    (Some(latitude), Some(longitude)) => Some(
+                        GeoLocation::new(latitude, longitude)
+                            .map_err(LoginAttemptRepositoryError::UnexpectedError)?,
+                    ),
+                    _ => None,
+                };
+                Ok(LoginAttempt::new(
+                    LoginAttemptId::new(record.id),
+                    user_id.clone(),
+                    record.succeeded,
+                    record.ip_address,
+                    record.device_name,
+                    location,
+                    record.attempted_at,
+                ))
+            })
+            .collect()
+    }
+
+    /// ユーザーが最後にログインに成功した後に、指定した日時以降に失敗したログイン試行の数を返却する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `since` - 数える失敗したログイン試行の試行日時の下限。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 失敗したログイン試行の数。
+    pub async fn count_recent_failed_by_user_id(
+        &self,
+        user_id: UserId,
+        since: OffsetDateTime,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<i64, LoginAttemptRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "count!"
+            FROM
+                login_attempts
+            WHERE
+                user_id = $1 AND NOT succeeded
+                AND attempted_at >= GREATEST(
+                    $2,
+                    (
+                        SELECT MAX(attempted_at)
+                        FROM login_attempts
+                        WHERE user_id = $1 AND succeeded
+                    )
+                )
+            "#,
+            user_id.value(),
+            since,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| LoginAttemptRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.count)
+    }
+}
@@ -1 +1,10 @@
+pub mod api_keys;
+pub mod invite_codes;
+pub mod login_attempts;
+pub mod password_reset_tokens;
+pub mod refresh_tokens;
+pub mod security_questions;
+pub mod totp_recovery_codes;
+pub mod user_credentials;
+pub mod user_email_addresses;
 pub mod users;
@@ -0,0 +1,122 @@
+use sqlx::{Postgres, Transaction};
+
+use domains::models::password_reset_tokens::PasswordResetToken;
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordResetTokenRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// パスワードリセットトークン登録エラー
+    #[error("パスワードリセットトークンを登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgPasswordResetTokenRepository;
+
+impl PgPasswordResetTokenRepository {
+    /// パスワードリセットトークンを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - 登録するパスワードリセットトークンインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        token: &PasswordResetToken,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), PasswordResetTokenRepositoryError> {
+        // パスワードリセットトークンを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (
+                token_hash, user_id, expired_at, created_at
+            ) VALUES (
+                $1, $2, $3, current_timestamp
+            )
+            "#,
+            token.token_hash(),
+            token.user_id().value(),
+            token.expired_at(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PasswordResetTokenRepositoryError::UnexpectedError(e.into()))?;
+        // パスワードリセットトークンが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(PasswordResetTokenRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// パスワードリセットトークンを取り出す。
+    ///
+    /// パスワードリセットトークンは1回しか使用できないように、取得すると同時に削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - パスワードリセットトークンのハッシュ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// パスワードリセットトークンインスタンス。パスワードリセットトークンが見つからなかった場合は`None`。
+    pub async fn take(
+        &self,
+        token_hash: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<PasswordResetToken>, PasswordResetTokenRepositoryError> {
+        // データベースを操作
+        let record = sqlx::query!(
+            r#"
+            DELETE FROM password_reset_tokens
+            WHERE
+                token_hash = $1
+            RETURNING
+                user_id, expired_at
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| PasswordResetTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.map(|record| {
+            PasswordResetToken::new(token_hash, UserId::new(record.user_id), record.expired_at)
+        }))
+    }
+
+    /// ユーザーのパスワードリセットトークンをすべて削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 削除したパスワードリセットトークンの数。
+    pub async fn delete_by_user_id(
+        &self,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<u64, PasswordResetTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM password_reset_tokens
+            WHERE
+                user_id = $1
+            "#,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PasswordResetTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,376 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use domains::models::refresh_tokens::{RefreshToken, SessionId};
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefreshTokenRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// リフレッシュトークン登録エラー
+    #[error("リフレッシュトークンを登録できませんでした。")]
+    CreateError,
+    /// リフレッシュトークン存在エラー
+    #[error("セッション({0})のリフレッシュトークンが存在しません。")]
+    NotFoundError(Uuid),
+}
+
+#[derive(Default)]
+pub struct PgRefreshTokenRepository;
+
+impl PgRefreshTokenRepository {
+    /// セッションIDからリフレッシュトークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - セッションID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンインスタンス。リフレッシュトークンが見つからなかった場合は`None`。
+    pub async fn get_by_session_id(
+        &self,
+        session_id: SessionId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<RefreshToken>, RefreshTokenRepositoryError> {
+        // データーベースに問い合わせ
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                user_id, token_hash, expired_at, device_name, created_at, updated_at
+            FROM
+                refresh_tokens
+            WHERE
+                session_id = $1
+            "#,
+            session_id.value()
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        // リフレッシュトークンを取得できなかった場合、Noneを返却
+        if result.is_none() {
+            return Ok(None);
+        }
+        let record = result.unwrap();
+        let refresh_token = RefreshToken::new(
+            session_id,
+            UserId::new(record.user_id),
+            &record.token_hash,
+            record.expired_at,
+            record.device_name,
+            Some(record.created_at),
+            Some(record.updated_at),
+        );
+
+        Ok(Some(refresh_token))
+    }
+
+    /// リフレッシュトークンのハッシュからリフレッシュトークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - リフレッシュトークンのハッシュ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンインスタンス。リフレッシュトークンが見つからなかった場合は`None`。
+    pub async fn get_by_token(
+        &self,
+        token_hash: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<RefreshToken>, RefreshTokenRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                session_id, user_id, token_hash, expired_at, device_name, created_at, updated_at
+            FROM
+                refresh_tokens
+            WHERE
+                token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.map(|record| {
+            RefreshToken::new(
+                SessionId::new(record.session_id),
+                UserId::new(record.user_id),
+                &record.token_hash,
+                record.expired_at,
+                record.device_name,
+                Some(record.created_at),
+                Some(record.updated_at),
+            )
+        }))
+    }
+
+    /// セッションのリフレッシュトークンが存在するか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - セッションID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// リフレッシュトークンが存在する場合は`true`。
+    pub async fn exists(
+        &self,
+        session_id: SessionId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, RefreshTokenRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM
+                    refresh_tokens
+                WHERE
+                    session_id = $1
+            ) AS "exists!"
+            "#,
+            session_id.value()
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.exists)
+    }
+
+    /// リフレッシュトークンを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - 登録するリフレッシュトークンインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        refresh_token: &RefreshToken,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), RefreshTokenRepositoryError> {
+        // リフレッシュトークンを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (
+                session_id, user_id, token_hash, expired_at, device_name,
+                created_at, updated_at
+            ) VALUES (
+                $1, $2, $3, $4, $5, current_timestamp, current_timestamp
+            )
+            "#,
+            refresh_token.session_id().value(),
+            refresh_token.user_id().value(),
+            refresh_token.token_hash(),
+            refresh_token.expired_at(),
+            refresh_token.device_name(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        // リフレッシュトークンが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(RefreshTokenRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// リフレッシュトークンを更新する。
+    ///
+    /// リフレッシュトークンのハッシュ、有効期限及び更新日時を更新する。
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - 更新するリフレッシュトークンインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn update(
+        &self,
+        refresh_token: &RefreshToken,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), RefreshTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET
+                token_hash = $1,
+                expired_at = $2,
+                updated_at = current_timestamp
+            WHERE
+                session_id = $3
+            "#,
+            refresh_token.token_hash(),
+            refresh_token.expired_at(),
+            refresh_token.session_id().value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        // リフレッシュトークンが更新されたか確認
+        if result.rows_affected() != 1 {
+            return Err(RefreshTokenRepositoryError::NotFoundError(
+                refresh_token.session_id().value(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// リフレッシュトークンを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - 削除するリフレッシュトークンのセッションID。
+    /// * `tx` - トランザクション。
+    pub async fn delete(
+        &self,
+        session_id: SessionId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), RefreshTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE
+                session_id = $1
+            "#,
+            session_id.value()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        // リフレッシュトークンが削除されたか確認
+        if result.rows_affected() != 1 {
+            return Err(RefreshTokenRepositoryError::NotFoundError(
+                session_id.value(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// テナントに所属するユーザーのリフレッシュトークンを削除する。
+    ///
+    /// 他のテナントのセッションを失効させられないように、セッションのユーザーが所属するテナントで絞り込んで
+    /// 削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - 削除するリフレッシュトークンのセッションID。
+    /// * `tenant_id` - セッションのユーザーが所属するテナントのテナントID。
+    /// * `tx` - トランザクション。
+    pub async fn delete_in_tenant(
+        &self,
+        session_id: SessionId,
+        tenant_id: Uuid,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), RefreshTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens r
+            USING users u
+            WHERE
+                r.session_id = $1
+                AND r.user_id = u.id
+                AND u.tenant_id = $2
+            "#,
+            session_id.value(),
+            tenant_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        // リフレッシュトークンが削除されたか確認
+        if result.rows_affected() != 1 {
+            return Err(RefreshTokenRepositoryError::NotFoundError(
+                session_id.value(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// ユーザーのリフレッシュトークンをすべて削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - リフレッシュトークンを削除するユーザーのユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 削除したリフレッシュトークンの数。
+    pub async fn delete_by_user_id(
+        &self,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<u64, RefreshTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE
+                user_id = $1
+            "#,
+            user_id.value()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 有効期限が切れたリフレッシュトークンを削除する。
+    ///
+    /// 大量のリフレッシュトークンを一度に削除してテーブルを長時間ロックしないように、1回の呼び出しで削除する
+    /// リフレッシュトークンの数を制限する。
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` - 1回の呼び出しで削除するリフレッシュトークンの最大数。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 削除したリフレッシュトークンの数。
+    pub async fn delete_expired(
+        &self,
+        batch_size: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<u64, RefreshTokenRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE
+                session_id IN (
+                    SELECT session_id
+                    FROM
+                        refresh_tokens
+                    WHERE
+                        expired_at < current_timestamp
+                    LIMIT $1
+                )
+            "#,
+            batch_size
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,121 @@
+use secrecy::ExposeSecret;
+use sqlx::{Postgres, Transaction};
+
+use domains::models::security_questions::SecurityQuestion;
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecurityQuestionRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// 秘密の質問登録エラー
+    #[error("秘密の質問を登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgSecurityQuestionRepository;
+
+impl PgSecurityQuestionRepository {
+    /// ユーザーの秘密の質問を取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 質問の順番で並べた秘密の質問インスタンスのベクタ。
+    pub async fn list_by_user_id(
+        &self,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<SecurityQuestion>, SecurityQuestionRepositoryError> {
+        // データーベースに問い合わせ
+        let records = sqlx::query!(
+            r#"
+            SELECT
+                position, question, hashed_answer
+            FROM
+                security_questions
+            WHERE
+                user_id = $1
+            ORDER BY
+                position
+            "#,
+            user_id.value()
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| SecurityQuestionRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(records
+            .iter()
+            .map(|record| {
+                SecurityQuestion::new_unchecked(
+                    user_id.clone(),
+                    record.position,
+                    &record.question,
+                    &record.hashed_answer,
+                )
+            })
+            .collect())
+    }
+
+    /// ユーザーの秘密の質問を置き換える。
+    ///
+    /// ユーザーに登録されている秘密の質問をすべて削除した後、秘密の質問を登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `questions` - 登録する秘密の質問インスタンスのスライス。
+    /// * `tx` - トランザクション。
+    pub async fn replace(
+        &self,
+        user_id: UserId,
+        questions: &[SecurityQuestion],
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), SecurityQuestionRepositoryError> {
+        // 登録されている秘密の質問を削除
+        sqlx::query!(
+            r#"
+            DELETE FROM security_questions
+            WHERE
+                user_id = $1
+            "#,
+            user_id.value()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| SecurityQuestionRepositoryError::UnexpectedError(e.into()))?;
+        // 秘密の質問を登録
+        for question in questions {
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO security_questions (
+                    user_id, position, question, hashed_answer,
+                    created_at, updated_at
+                ) VALUES (
+                    $1, $2, $3, $4, current_timestamp, current_timestamp
+                )
+                "#,
+                user_id.value(),
+                question.position(),
+                question.question(),
+                question.hashed_answer().expose_secret(),
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| SecurityQuestionRepositoryError::UnexpectedError(e.into()))?;
+            // 秘密の質問が登録されたか確認
+            if result.rows_affected() != 1 {
+                return Err(SecurityQuestionRepositoryError::CreateError);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use sqlx::{Postgres, Transaction};
+
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TotpRecoveryCodeRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+}
+
+#[derive(Default)]
+pub struct PgTotpRecoveryCodeRepository;
+
+impl PgTotpRecoveryCodeRepository {
+    /// ユーザーのリカバリーコードを置き換える。
+    ///
+    /// 既存のリカバリーコードは、使用済みかどうかにかかわらずすべて削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `code_hashes` - 登録するリカバリーコードのハッシュ。
+    /// * `tx` - トランザクション。
+    pub async fn replace(
+        &self,
+        user_id: UserId,
+        code_hashes: &[String],
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), TotpRecoveryCodeRepositoryError> {
+        // 既存のリカバリーコードを削除
+        sqlx::query!(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE
+                user_id = $1
+            "#,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| TotpRecoveryCodeRepositoryError::UnexpectedError(e.into()))?;
+        // リカバリーコードを登録
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_recovery_codes (
+                code_hash, user_id, used_at, created_at
+            )
+            SELECT
+                code_hash, $2, NULL, current_timestamp
+            FROM UNNEST($1::TEXT[]) AS code_hash
+            "#,
+            code_hashes,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| TotpRecoveryCodeRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    /// 未使用のリカバリーコードを使用済みにする。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `code_hash` - リカバリーコードのハッシュ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 未使用のリカバリーコードを使用済みにした場合は`true`。リカバリーコードが見つからないか、使用済みの
+    /// 場合は`false`。
+    pub async fn consume(
+        &self,
+        user_id: UserId,
+        code_hash: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, TotpRecoveryCodeRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_recovery_codes
+            SET
+                used_at = current_timestamp
+            WHERE
+                code_hash = $1
+                AND user_id = $2
+                AND used_at IS NULL
+            "#,
+            code_hash,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| TotpRecoveryCodeRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,171 @@
+use sqlx::{Postgres, Transaction};
+
+use domains::models::user_credentials::UserCredential;
+use domains::models::users::UserId;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UserCredentialRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// ユーザークレデンシャル登録エラー
+    #[error("ユーザークレデンシャルを登録できませんでした。")]
+    CreateError,
+}
+
+#[derive(Default)]
+pub struct PgUserCredentialRepository;
+
+impl PgUserCredentialRepository {
+    /// ユーザークレデンシャルを登録する。
+    ///
+    /// 同じクレデンシャルIDのユーザークレデンシャルが既に登録されている場合は登録しない。
+    ///
+    /// # Arguments
+    ///
+    /// * `credential` - 登録するユーザークレデンシャルインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        credential: &UserCredential,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserCredentialRepositoryError> {
+        // ユーザークレデンシャルを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_credentials (
+                credential_id, user_id, passkey, created_at
+            ) VALUES (
+                $1, $2, $3, current_timestamp
+            )
+            ON CONFLICT (credential_id) DO NOTHING
+            "#,
+            credential.credential_id(),
+            credential.user_id().value(),
+            credential.passkey(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserCredentialRepositoryError::UnexpectedError(e.into()))?;
+        // ユーザークレデンシャルが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserCredentialRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// クレデンシャルIDを指定して、ユーザークレデンシャルを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `credential_id` - クレデンシャルID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザークレデンシャルインスタンス。見つからなかった場合は`None`。
+    pub async fn get_by_credential_id(
+        &self,
+        credential_id: &[u8],
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<UserCredential>, UserCredentialRepositoryError> {
+        // データベースを操作
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                credential_id, user_id, passkey
+            FROM user_credentials
+            WHERE
+                credential_id = $1
+            "#,
+            credential_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UserCredentialRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.map(|record| {
+            UserCredential::new(
+                record.credential_id,
+                UserId::new(record.user_id),
+                record.passkey,
+            )
+        }))
+    }
+
+    /// ユーザーのユーザークレデンシャルを、登録した順に取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザークレデンシャルインスタンスを格納したベクタ。
+    pub async fn list_by_user_id(
+        &self,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<UserCredential>, UserCredentialRepositoryError> {
+        // データベースを操作
+        let records = sqlx::query!(
+            r#"
+            SELECT
+                credential_id, user_id, passkey
+            FROM user_credentials
+            WHERE
+                user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id.value(),
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| UserCredentialRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| {
+                UserCredential::new(
+                    record.credential_id,
+                    UserId::new(record.user_id),
+                    record.passkey,
+                )
+            })
+            .collect())
+    }
+
+    /// ユーザークレデンシャルのパスキーと最終使用日時を更新する。
+    ///
+    /// # Arguments
+    ///
+    /// * `credential_id` - クレデンシャルID。
+    /// * `passkey` - 署名カウンターなどを更新した、JSONにシリアライズしたパスキー。
+    /// * `tx` - トランザクション。
+    pub async fn update_passkey(
+        &self,
+        credential_id: &[u8],
+        passkey: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserCredentialRepositoryError> {
+        // データベースを操作
+        sqlx::query!(
+            r#"
+            UPDATE user_credentials
+            SET
+                passkey = $2, last_used_at = current_timestamp
+            WHERE
+                credential_id = $1
+            "#,
+            credential_id,
+            passkey,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserCredentialRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use sqlx::{Postgres, Transaction};
+
+use domains::models::user_email_addresses::{UserEmailAddress, UserEmailAddressId};
+use domains::models::users::UserId;
+use domains::models::EmailAddress;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UserEmailAddressRepositoryError {
+    /// 予期していないエラー
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    /// ドメイン制約エラー
+    #[error("{0}")]
+    DomainError(anyhow::Error),
+    /// ユーザーEメールアドレス登録エラー
+    #[error("Eメールアドレスを登録できませんでした。")]
+    CreateError,
+    /// ユーザーEメールアドレス存在エラー
+    #[error("Eメールアドレス({0})が登録されていません。")]
+    NotFoundError(String),
+}
+
+#[derive(Default)]
+pub struct PgUserEmailAddressRepository;
+
+impl PgUserEmailAddressRepository {
+    /// ユーザーに登録されたEメールアドレスを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// プライマリEメールアドレスを先頭に、登録した順番で並べたユーザーEメールアドレスインスタンスのベクタ。
+    pub async fn list_by_user_id(
+        &self,
+        user_id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<UserEmailAddress>, UserEmailAddressRepositoryError> {
+        // データーベースに問い合わせ
+        let records = sqlx::query!(
+            r#"
+            SELECT
+                id, email_address, is_primary, is_verified, created_at
+            FROM
+                user_email_addresses
+            WHERE
+                user_id = $1
+            ORDER BY
+                is_primary DESC, created_at, email_address
+            "#,
+            user_id.value()
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(UserEmailAddress::new(
+                    UserEmailAddressId::new(record.id),
+                    user_id.clone(),
+                    EmailAddress::new(&record.email_address)
+                        .map_err(|e| UserEmailAddressRepositoryError::DomainError(e.into()))?,
+                    record.is_primary,
+                    record.is_verified,
+                    Some(record.created_at),
+                ))
+            })
+            .collect()
+    }
+
+    /// Eメールアドレスが登録されているか確認する。
+    ///
+    /// 論理削除したユーザーのEメールアドレスを除いて、すべてのユーザーに登録されたEメールアドレスと比較する。
+    /// 未確認のエイリアスも登録されたEメールアドレスとして扱う。
+    ///
+    /// # Arguments
+    ///
+    /// * `email_address` - 確認するEメールアドレス。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// Eメールアドレスが登録されている場合は`true`。
+    pub async fn exists_by_email_address(
+        &self,
+        email_address: &EmailAddress,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, UserEmailAddressRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM
+                    user_email_addresses
+                WHERE
+                    email_address = $1
+                    AND deleted_at IS NULL
+            ) AS "exists!"
+            "#,
+            email_address.value()
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.exists)
+    }
+
+    /// ユーザーEメールアドレスを登録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `email_address` - 登録するユーザーEメールアドレスインスタンス。
+    /// * `tx` - トランザクション。
+    pub async fn insert(
+        &self,
+        email_address: &UserEmailAddress,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserEmailAddressRepositoryError> {
+        // ユーザーEメールアドレスを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_email_addresses (
+                id, user_id, email_address, is_primary, is_verified, created_at
+            ) VALUES (
+                $1, $2, $3, $4, $5, current_timestamp
+            )
+            "#,
+            email_address.id().value(),
+            email_address.user_id().value(),
+            email_address.email_address().value(),
+            email_address.is_primary(),
+            email_address.is_verified(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+        // ユーザーEメールアドレスが登録されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserEmailAddressRepositoryError::CreateError);
+        }
+
+        Ok(())
+    }
+
+    /// ユーザーのプライマリEメールアドレスを変更する。
+    ///
+    /// プライマリEメールアドレスはユーザーごとに1つであるため、現在のプライマリEメールアドレスを解除した後に、
+    /// 指定されたEメールアドレスをプライマリEメールアドレスに設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `email_address` - プライマリEメールアドレスに設定するEメールアドレス。
+    /// * `tx` - トランザクション。
+    pub async fn set_primary(
+        &self,
+        user_id: UserId,
+        email_address: &EmailAddress,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserEmailAddressRepositoryError> {
+        // 現在のプライマリEメールアドレスを解除
+        sqlx::query!(
+            r#"
+            UPDATE user_email_addresses
+            SET
+                is_primary = false
+            WHERE
+                user_id = $1
+                AND is_primary
+            "#,
+            user_id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+        // プライマリEメールアドレスを設定
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_email_addresses
+            SET
+                is_primary = true
+            WHERE
+                user_id = $1
+                AND email_address = $2
+            "#,
+            user_id.value(),
+            email_address.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+        // プライマリEメールアドレスが設定されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserEmailAddressRepositoryError::NotFoundError(
+                email_address.value().to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// ユーザーEメールアドレスを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `email_address` - 削除するEメールアドレス。
+    /// * `tx` - トランザクション。
+    pub async fn delete(
+        &self,
+        user_id: UserId,
+        email_address: &EmailAddress,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserEmailAddressRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_email_addresses
+            WHERE
+                user_id = $1
+                AND email_address = $2
+            "#,
+            user_id.value(),
+            email_address.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserEmailAddressRepositoryError::UnexpectedError(e.into()))?;
+        // ユーザーEメールアドレスが削除されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserEmailAddressRepositoryError::NotFoundError(
+                email_address.value().to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+}
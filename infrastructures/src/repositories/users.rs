@@ -1,8 +1,10 @@
-use secrecy::ExposeSecret;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
-use domains::models::users::{HashedPassword, User, UserId, UserName};
+use domains::models::users::{
+    HashedPassword, ProfileVisibility, User, UserId, UserName, UserRole, Visibility,
+};
 use domains::models::EmailAddress;
 
 #[derive(Debug, thiserror::Error)]
@@ -19,14 +21,25 @@ pub enum UserRepositoryError {
     /// ユーザー存在エラー
     #[error("ユーザー({0})が存在しません。")]
     NotFoundError(Uuid),
+    /// テナント不一致エラー
+    #[error("ユーザー({0})は他のテナントに所属しています。")]
+    TenantMismatch(Uuid),
 }
 
+/// パスワードの変更を直列化するアドバイザリロックの種類
+///
+/// ユーザーIDのハッシュと組み合わせて、アドバイザリロックのキーにする。
+const PASSWORD_CHANGE_LOCK_CLASS: i32 = 1;
+
 #[derive(Default)]
 pub struct PgUserRepository;
 
 impl PgUserRepository {
     /// Eメールアドレスからユーザーを取得する。
     ///
+    /// ユーザーに登録された確認済みのEメールアドレスと比較する。所有を確認していないエイリアスでは、
+    /// ユーザーを取得しない。取得したユーザーのEメールアドレスは、プライマリEメールアドレスである。
+    ///
     /// # Argument:
     ///
     /// * `email_address` - Eメールアドレス。
@@ -44,12 +57,16 @@ impl PgUserRepository {
         let result = sqlx::query!(
             r#"
             SELECT
-                id, user_name, email_address, hashed_password, is_active,
-                last_logged_in, created_at, updated_at
+                u.id, u.tenant_id, u.user_name, u.email_address, u.hashed_password, u.is_active,
+                u.last_logged_in, u.email_address_visibility, u.last_logged_in_visibility,
+                u.totp_secret, u.must_change_password, u.role, u.created_at, u.updated_at
             FROM
-                users
+                users u
+                INNER JOIN user_email_addresses e ON e.user_id = u.id
             WHERE
-                email_address = $1
+                e.email_address = $1
+                AND e.is_verified
+                AND u.deleted_at IS NULL
             "#,
             email_address.value()
         )
@@ -62,16 +79,93 @@ impl PgUserRepository {
         }
         let record = result.unwrap();
         let id = UserId::new(record.id);
-        let user_name =
-            UserName::new(&record.user_name).map_err(UserRepositoryError::DomainError)?;
-        let hashed_password = HashedPassword::new_unchecked(&record.hashed_password);
+        let user_name = UserName::new_unchecked(&record.user_name);
+        let email_address = EmailAddress::new(&record.email_address)
+            .map_err(|e| UserRepositoryError::DomainError(e.into()))?;
+        let hashed_password = HashedPassword::new_unchecked(record.hashed_password);
+        let profile_visibility = profile_visibility_from_record(
+            &record.email_address_visibility,
+            &record.last_logged_in_visibility,
+        )?;
         let user = User::new(
             id,
+            record.tenant_id,
+            user_name,
+            email_address,
+            hashed_password,
+            record.is_active,
+            record.last_logged_in,
+            profile_visibility,
+            record.totp_secret.map(Secret::new),
+            record.must_change_password,
+            UserRole::try_from(record.role.as_str()).map_err(UserRepositoryError::DomainError)?,
+            Some(record.created_at),
+            Some(record.updated_at),
+        );
+
+        Ok(Some(user))
+    }
+
+    /// ユーザー名からユーザーを取得する。
+    ///
+    /// ユーザー名は、大文字と小文字を区別せずに比較する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_name` - ユーザー名。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーインスタンス。ユーザーが見つからなかった場合は`None`。
+    pub async fn get_by_user_name(
+        &self,
+        user_name: &UserName,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<User>, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                id, tenant_id, user_name, email_address, hashed_password, is_active,
+                last_logged_in, email_address_visibility, last_logged_in_visibility,
+                totp_secret, must_change_password, role, created_at, updated_at
+            FROM
+                users
+            WHERE
+                LOWER(user_name) = $1
+                AND deleted_at IS NULL
+            "#,
+            user_name.canonical()
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // ユーザーを取得できなかった場合、Noneを返却
+        if result.is_none() {
+            return Ok(None);
+        }
+        let record = result.unwrap();
+        let user_name = UserName::new_unchecked(&record.user_name);
+        let email_address = EmailAddress::new(&record.email_address)
+            .map_err(|e| UserRepositoryError::DomainError(e.into()))?;
+        let hashed_password = HashedPassword::new_unchecked(record.hashed_password);
+        let profile_visibility = profile_visibility_from_record(
+            &record.email_address_visibility,
+            &record.last_logged_in_visibility,
+        )?;
+        let user = User::new(
+            UserId::new(record.id),
+            record.tenant_id,
             user_name,
-            (*email_address).clone(),
+            email_address,
             hashed_password,
             record.is_active,
             record.last_logged_in,
+            profile_visibility,
+            record.totp_secret.map(Secret::new),
+            record.must_change_password,
+            UserRole::try_from(record.role.as_str()).map_err(UserRepositoryError::DomainError)?,
             Some(record.created_at),
             Some(record.updated_at),
         );
@@ -98,12 +192,14 @@ impl PgUserRepository {
         let result = sqlx::query!(
             r#"
             SELECT
-                user_name, email_address, hashed_password, is_active,
-                last_logged_in, created_at, updated_at
+                tenant_id, user_name, email_address, hashed_password, is_active,
+                last_logged_in, email_address_visibility, last_logged_in_visibility,
+                totp_secret, must_change_password, role, created_at, updated_at
             FROM
                 users
             WHERE
                 id = $1
+                AND deleted_at IS NULL
             "#,
             id.value()
         )
@@ -116,18 +212,102 @@ impl PgUserRepository {
         }
         // ユーザーを取得
         let record = result.unwrap();
-        let user_name =
-            UserName::new(&record.user_name).map_err(UserRepositoryError::DomainError)?;
-        let email_address =
-            EmailAddress::new(&record.email_address).map_err(UserRepositoryError::DomainError)?;
-        let hashed_password = HashedPassword::new_unchecked(&record.hashed_password);
+        let user_name = UserName::new_unchecked(&record.user_name);
+        let email_address = EmailAddress::new(&record.email_address)
+            .map_err(|e| UserRepositoryError::DomainError(e.into()))?;
+        let hashed_password = HashedPassword::new_unchecked(record.hashed_password);
+        let profile_visibility = profile_visibility_from_record(
+            &record.email_address_visibility,
+            &record.last_logged_in_visibility,
+        )?;
         let user = User::new(
             id.clone(),
+            record.tenant_id,
+            user_name,
+            email_address,
+            hashed_password,
+            record.is_active,
+            record.last_logged_in,
+            profile_visibility,
+            record.totp_secret.map(Secret::new),
+            record.must_change_password,
+            UserRole::try_from(record.role.as_str()).map_err(UserRepositoryError::DomainError)?,
+            Some(record.created_at),
+            Some(record.updated_at),
+        );
+
+        Ok(Some(user))
+    }
+
+    /// テナントに所属するユーザーを取得する。
+    ///
+    /// 他のテナントのユーザーを取得できないように、テナントIDで絞り込んで問い合わせる。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 取得するユーザーのユーザーID。
+    /// * `tenant_id` - ユーザーが所属するテナントのテナントID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーインスタンス。ユーザーが見つからなかった場合は`None`。ユーザーが他のテナントに所属している
+    /// 場合は`UserRepositoryError::TenantMismatch`。
+    pub async fn get_by_id_in_tenant(
+        &self,
+        id: UserId,
+        tenant_id: Uuid,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<User>, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                user_name, email_address, hashed_password, is_active,
+                last_logged_in, email_address_visibility, last_logged_in_visibility,
+                totp_secret, must_change_password, role, created_at, updated_at
+            FROM
+                users
+            WHERE
+                id = $1
+                AND tenant_id = $2
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+            tenant_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // テナントに所属するユーザーを取得できなかった場合は、他のテナントに所属しているか確認
+        if result.is_none() {
+            if self.exists(id.clone(), &mut *tx).await? {
+                return Err(UserRepositoryError::TenantMismatch(id.value()));
+            }
+            return Ok(None);
+        }
+        // ユーザーを取得
+        let record = result.unwrap();
+        let user_name = UserName::new_unchecked(&record.user_name);
+        let email_address = EmailAddress::new(&record.email_address)
+            .map_err(|e| UserRepositoryError::DomainError(e.into()))?;
+        let hashed_password = HashedPassword::new_unchecked(record.hashed_password);
+        let profile_visibility = profile_visibility_from_record(
+            &record.email_address_visibility,
+            &record.last_logged_in_visibility,
+        )?;
+        let user = User::new(
+            id,
+            tenant_id,
             user_name,
             email_address,
             hashed_password,
             record.is_active,
             record.last_logged_in,
+            profile_visibility,
+            record.totp_secret.map(Secret::new),
+            record.must_change_password,
+            UserRole::try_from(record.role.as_str()).map_err(UserRepositoryError::DomainError)?,
             Some(record.created_at),
             Some(record.updated_at),
         );
@@ -135,8 +315,87 @@ impl PgUserRepository {
         Ok(Some(user))
     }
 
+    /// ユーザーが存在するか確認する。
+    ///
+    /// ユーザーを取得せずに、存在するかどうかのみを問い合わせる。論理削除したユーザーは存在しないと判断する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 確認するユーザーのユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーが存在する場合は`true`。
+    pub async fn exists(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM
+                    users
+                WHERE
+                    id = $1
+                    AND deleted_at IS NULL
+            ) AS "exists!"
+            "#,
+            id.value()
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.exists)
+    }
+
+    /// ユーザー名が登録されているか確認する。
+    ///
+    /// ユーザー名は、大文字と小文字を区別せずに比較する。論理削除したユーザーのユーザー名は登録されていないと
+    /// 判断する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_name` - 確認するユーザー名。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザー名が登録されている場合は`true`。
+    pub async fn exists_by_user_name(
+        &self,
+        user_name: &UserName,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let record = sqlx::query!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM
+                    users
+                WHERE
+                    LOWER(user_name) = $1
+                    AND deleted_at IS NULL
+            ) AS "exists!"
+            "#,
+            user_name.canonical()
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(record.exists)
+    }
+
     /// ユーザーを登録する。
     ///
+    /// ユーザーのEメールアドレスは、確認済みのプライマリEメールアドレスとして登録する。
+    ///
     /// # Arguments
     ///
     /// * `user` - 登録するユーザーのユーザーインスタンス。
@@ -154,17 +413,22 @@ impl PgUserRepository {
         let result = sqlx::query!(
             r#"
             INSERT INTO users (
-                id, user_name, email_address, hashed_password,
-                is_active, created_at, updated_at
+                id, tenant_id, user_name, email_address, hashed_password,
+                is_active, email_address_visibility, last_logged_in_visibility, role,
+                created_at, updated_at
             ) VALUES (
-                $1, $2, $3, $4, $5, current_timestamp, current_timestamp
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, current_timestamp, current_timestamp
             )
             "#,
             user.id().value(),
+            user.tenant_id(),
             user.user_name().value(),
             user.email_address().value(),
             user.hashed_password().value().expose_secret(),
             user.is_active(),
+            user.profile_visibility().email_address.as_str(),
+            user.profile_visibility().last_logged_in.as_str(),
+            user.role().as_str(),
         )
         .execute(&mut *tx)
         .await
@@ -173,6 +437,25 @@ impl PgUserRepository {
         if result.rows_affected() != 1 {
             return Err(UserRepositoryError::CreateError);
         }
+        // プライマリEメールアドレスを登録
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_email_addresses (
+                id, user_id, email_address, is_primary, is_verified, created_at
+            ) VALUES (
+                $1, $2, $3, true, true, current_timestamp
+            )
+            "#,
+            Uuid::new_v4(),
+            user.id().value(),
+            user.email_address().value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::CreateError);
+        }
         // 作成日時と更新日時を取得するため、登録したユーザーを取得
         let inserted_user = self.get_by_id(user.id(), &mut *tx).await?;
         if inserted_user.is_none() {
@@ -184,7 +467,7 @@ impl PgUserRepository {
 
     /// ユーザーを更新する。
     ///
-    /// ユーザー名、アクティブフラグ及び更新日時を更新する。
+    /// ユーザー名、アクティブフラグ、プロフィールの公開設定及び更新日時を更新する。
     ///
     /// # Arguments
     ///
@@ -206,12 +489,16 @@ impl PgUserRepository {
             SET
                 user_name = $1,
                 is_active = $2,
+                email_address_visibility = $3,
+                last_logged_in_visibility = $4,
                 updated_at = current_timestamp
             WHERE
-                id = $3
+                id = $5
             "#,
             user.user_name().value(),
             user.is_active(),
+            user.profile_visibility().email_address.as_str(),
+            user.profile_visibility().last_logged_in.as_str(),
             user.id().value(),
         )
         .execute(&mut *tx)
@@ -258,8 +545,76 @@ impl PgUserRepository {
         Ok(())
     }
 
+    /// ユーザーを論理削除する。
+    ///
+    /// ユーザーを無効にして、削除日時を記録する。論理削除したユーザーは、ユーザーを取得するメソッドで
+    /// 取得できなくなる。ユーザーのエイリアスは削除して、プライマリEメールアドレスは論理削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 論理削除するユーザーのID。
+    /// * `tx` - トランザクション。
+    pub async fn soft_delete(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                is_active = false,
+                deleted_at = current_timestamp,
+                updated_at = current_timestamp
+            WHERE
+                id = $1
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // ユーザーが論理削除されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::NotFoundError(id.value()));
+        }
+        // 削除したユーザーのEメールアドレスを他のユーザーが登録できるように、エイリアスを削除して、
+        // プライマリEメールアドレスを論理削除
+        sqlx::query!(
+            r#"
+            DELETE FROM user_email_addresses
+            WHERE
+                user_id = $1
+                AND NOT is_primary
+            "#,
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        sqlx::query!(
+            r#"
+            UPDATE user_email_addresses
+            SET
+                deleted_at = current_timestamp
+            WHERE
+                user_id = $1
+            "#,
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
     /// パスワードを変更する。
     ///
+    /// パスワードを変更すると、次回のログインでパスワードの変更を要求しないようにする。
+    ///
     /// # Arguments
     ///
     /// * `id` - パスワードを変更するユーザーのID。
@@ -276,6 +631,7 @@ impl PgUserRepository {
             UPDATE users
             SET
                 hashed_password = $1,
+                must_change_password = FALSE,
                 updated_at = current_timestamp
             WHERE
                 id = $2
@@ -294,6 +650,304 @@ impl PgUserRepository {
         Ok(())
     }
 
+    /// ユーザーのパスワードの変更を直列化するロックを取得する。
+    ///
+    /// ユーザーIDのハッシュをキーにしたアドバイザリロックを、トランザクションが終了するまで保持する。同じ
+    /// ユーザーのパスワードを同時に変更しようとした場合は、先にロックを取得したトランザクションが終了するまで
+    /// 待機する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - パスワードを変更するユーザーのID。
+    /// * `tx` - トランザクション。
+    pub async fn lock_password_change(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock($1, hashtext($2::uuid::text))
+            "#,
+            PASSWORD_CHANGE_LOCK_CLASS,
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    /// ユーザーのEメールアドレスを変更する。
+    ///
+    /// ユーザーのEメールアドレスには、プライマリEメールアドレスを記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Eメールアドレスを変更するユーザーのID。
+    /// * `email_address` - 新たに設定するEメールアドレス。
+    /// * `tx` - トランザクション。
+    pub async fn change_email_address(
+        &self,
+        id: UserId,
+        email_address: &EmailAddress,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                email_address = $1,
+                updated_at = current_timestamp
+            WHERE
+                id = $2
+            "#,
+            email_address.value(),
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // Eメールアドレスが更新されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::NotFoundError(id.value()));
+        }
+
+        Ok(())
+    }
+
+    /// 確認待ちのTOTPの共有シークレットを設定する。
+    ///
+    /// 確認待ちの共有シークレットは、認証アプリが生成したコードで確認するまで、2要素認証に使用しない。
+    /// `None`を指定した場合は、確認待ちの共有シークレットを破棄する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 確認待ちの共有シークレットを設定するユーザーのID。
+    /// * `pending_totp_secret` - Base32でエンコードしたTOTPの共有シークレット。
+    /// * `tx` - トランザクション。
+    pub async fn set_pending_totp_secret(
+        &self,
+        id: UserId,
+        pending_totp_secret: Option<&Secret<String>>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                pending_totp_secret = $1,
+                updated_at = current_timestamp
+            WHERE
+                id = $2
+                AND deleted_at IS NULL
+            "#,
+            pending_totp_secret.map(|secret| secret.expose_secret().as_str()),
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // 確認待ちの共有シークレットが設定されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::NotFoundError(id.value()));
+        }
+
+        Ok(())
+    }
+
+    /// 確認待ちのTOTPの共有シークレットを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// Base32でエンコードした確認待ちの共有シークレット。確認待ちの共有シークレットが存在しない場合は`None`。
+    pub async fn get_pending_totp_secret(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Secret<String>>, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let result = sqlx::query!(
+            r#"
+            SELECT pending_totp_secret
+            FROM users
+            WHERE
+                id = $1
+                AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+            id.value(),
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result
+            .and_then(|record| record.pending_totp_secret)
+            .map(Secret::new))
+    }
+
+    /// 確認待ちのTOTPの共有シークレットを、2要素認証に使用する共有シークレットにする。
+    ///
+    /// 既存の共有シークレットは置き換えて、確認待ちの共有シークレットは破棄する。確認に使用したコードを
+    /// 2要素認証に再使用できないように、確認に使用したコードの時間ステップを記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 共有シークレットを確定するユーザーのID。
+    /// * `step` - 確認に使用したコードの時間ステップ。
+    /// * `tx` - トランザクション。
+    pub async fn activate_pending_totp_secret(
+        &self,
+        id: UserId,
+        step: u64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                totp_secret = pending_totp_secret,
+                pending_totp_secret = NULL,
+                totp_last_step = $2,
+                updated_at = current_timestamp
+            WHERE
+                id = $1
+                AND pending_totp_secret IS NOT NULL
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+            step as i64,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // 共有シークレットが確定されたか確認
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::NotFoundError(id.value()));
+        }
+
+        Ok(())
+    }
+
+    /// 最後に受け付けたTOTPのコードの時間ステップを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ユーザーID。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 時間ステップ。TOTPのコードを受け付けたことがない場合は`None`。
+    pub async fn get_totp_last_step(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<u64>, UserRepositoryError> {
+        // データーベースに問い合わせ
+        let result = sqlx::query!(
+            r#"
+            SELECT totp_last_step
+            FROM users
+            WHERE
+                id = $1
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result
+            .and_then(|record| record.totp_last_step)
+            .map(|step| step as u64))
+    }
+
+    /// 受け付けたTOTPのコードの時間ステップを記録する。
+    ///
+    /// 同じコードを同時に受け付けないように、記録されている時間ステップより後の時間ステップの場合にのみ記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ユーザーID。
+    /// * `step` - 受け付けたコードの時間ステップ。
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// 時間ステップを記録した場合は`true`。既に同じか後の時間ステップを記録していた場合は`false`。
+    pub async fn record_totp_step(
+        &self,
+        id: UserId,
+        step: u64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_step = $2
+            WHERE
+                id = $1
+                AND (totp_last_step IS NULL OR totp_last_step < $2)
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+            step as i64,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// 次回のログインでパスワードの変更を要求する。
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - パスワードの変更を要求するユーザーのID。
+    /// * `tx` - トランザクション。
+    pub async fn require_password_change(
+        &self,
+        id: UserId,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), UserRepositoryError> {
+        // データベースを操作
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                must_change_password = TRUE,
+                updated_at = current_timestamp
+            WHERE
+                id = $1
+                AND deleted_at IS NULL
+            "#,
+            id.value(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserRepositoryError::UnexpectedError(e.into()))?;
+        // パスワードの変更を要求したか確認
+        if result.rows_affected() != 1 {
+            return Err(UserRepositoryError::NotFoundError(id.value()));
+        }
+
+        Ok(())
+    }
+
     /// 最終ログイン日時に現在日時を設定する。
     ///
     /// # Arguments
@@ -328,3 +982,25 @@ impl PgUserRepository {
         Ok(())
     }
 }
+
+/// データベースに記録されている公開範囲から、プロフィールの公開設定を構築する。
+///
+/// # Arguments
+///
+/// * `email_address` - Eメールアドレスの公開範囲。
+/// * `last_logged_in` - 最終ログイン日時の公開範囲。
+///
+/// # Returns
+///
+/// プロフィールの公開設定。
+fn profile_visibility_from_record(
+    email_address: &str,
+    last_logged_in: &str,
+) -> Result<ProfileVisibility, UserRepositoryError> {
+    Ok(ProfileVisibility {
+        email_address: Visibility::try_from(email_address)
+            .map_err(UserRepositoryError::DomainError)?,
+        last_logged_in: Visibility::try_from(last_logged_in)
+            .map_err(UserRepositoryError::DomainError)?,
+    })
+}
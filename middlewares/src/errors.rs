@@ -0,0 +1,274 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// ミドルウェアエラー
+///
+/// 認証ミドルウェアや冪等ミドルウェアが返却するエラーで、どの分岐で失敗しても、同じ形式のJSONで応答する。
+#[derive(Debug, thiserror::Error)]
+pub enum MiddlewareError {
+    /// 認証されていない。
+    #[error("認証されていません。")]
+    Unauthorized,
+    /// パスワードの変更などで、セッションが失効している。
+    #[error("セッションは失効しています。")]
+    SessionExpired,
+    /// トークンをリフレッシュできる回数の上限に達したため、再ログインが必要。
+    #[error("ログインし直してください。")]
+    RefreshChainExhausted,
+    /// サイレントリフレッシュが無効で、アクセストークンの有効期限が切れている。
+    #[error("アクセストークンの有効期限が切れています。トークンをリフレッシュしてください。")]
+    AccessTokenExpired,
+    /// ユーザーが無効になっている。
+    #[error("ユーザーが無効になっています。")]
+    Forbidden,
+    /// パスワードの変更を要求されている。
+    #[error("パスワードを変更してください。")]
+    PasswordChangeRequired,
+    /// APIキーに必要なスコープが付与されていない。
+    #[error("APIキーにスコープ({0})が付与されていません。")]
+    InsufficientScope(&'static str),
+    /// APIキーで認証できないリソースに、APIキーでアクセスした。
+    #[error("このリソースには、APIキーでアクセスできません。")]
+    ApiKeyNotAllowed,
+    /// ユーザーがリソースに必要な役割を持っていない。
+    #[error("このリソースにアクセスする権限がありません。")]
+    InsufficientRole,
+    /// 冪等キーの形式が不正。
+    #[error("冪等キーは1文字以上255文字以内の英数字と記号で指定してください。")]
+    InvalidIdempotencyKey,
+    /// 同じ冪等キーを指定したリクエストを処理している。
+    #[error("同じ冪等キーを指定したリクエストを処理しています。")]
+    IdempotencyKeyInUse,
+    /// 冪等キーが異なるリクエストで使用されている。
+    #[error("冪等キーが異なるリクエストで使用されています。")]
+    IdempotencyKeyReused,
+    /// データベースの障害などで、セッションが失効しているか確認できない。
+    #[error("現在、認証できません。しばらくしてから再度お試しください。")]
+    ServiceUnavailable,
+    /// 予期していないエラー。
+    #[error("{0}")]
+    UnexpectedError(String),
+}
+
+impl MiddlewareError {
+    /// ユーザーが無効になっている場合のエラーを返却する。
+    ///
+    /// `401 Unauthorized`で応答するように設定されている場合は、ユーザーが無効になっていることを明かさない
+    /// ように、認証されていない場合と同じエラーを返却する。
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - ユーザーが無効になっている場合に応答するステータスコード。
+    ///
+    /// # Returns
+    ///
+    /// ミドルウェアエラー。
+    pub fn inactive_user(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            _ => Self::Forbidden,
+        }
+    }
+
+    /// 予期していないエラーを返却する。
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - エラーの原因。
+    ///
+    /// # Returns
+    ///
+    /// ミドルウェアエラー。
+    pub fn unexpected<E: std::fmt::Display>(e: E) -> Self {
+        Self::UnexpectedError(format!("{}", e))
+    }
+
+    /// エラーコードを返却する。
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::SessionExpired => "SESSION_EXPIRED",
+            Self::RefreshChainExhausted => "LOGIN_REQUIRED",
+            Self::AccessTokenExpired => "ACCESS_TOKEN_EXPIRED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::PasswordChangeRequired => "PASSWORD_CHANGE_REQUIRED",
+            Self::InsufficientScope(_) => "INSUFFICIENT_SCOPE",
+            Self::ApiKeyNotAllowed => "API_KEY_NOT_ALLOWED",
+            Self::InsufficientRole => "INSUFFICIENT_ROLE",
+            Self::InvalidIdempotencyKey => "INVALID_IDEMPOTENCY_KEY",
+            Self::IdempotencyKeyInUse => "IDEMPOTENCY_KEY_IN_USE",
+            Self::IdempotencyKeyReused => "IDEMPOTENCY_KEY_REUSED",
+            Self::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            Self::UnexpectedError(_) => "INTERNAL_SERVER_ERROR",
+        }
+    }
+}
+
+/// エラーレスポンスボディ
+#[derive(Debug, Serialize)]
+struct ErrorResponseBody {
+    /// エラーコード
+    code: &'static str,
+    /// エラーメッセージ
+    message: String,
+}
+
+impl ResponseError for MiddlewareError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized
+            | Self::SessionExpired
+            | Self::RefreshChainExhausted
+            | Self::AccessTokenExpired => StatusCode::UNAUTHORIZED,
+            Self::Forbidden
+            | Self::PasswordChangeRequired
+            | Self::InsufficientScope(_)
+            | Self::ApiKeyNotAllowed
+            | Self::InsufficientRole => StatusCode::FORBIDDEN,
+            Self::InvalidIdempotencyKey => StatusCode::BAD_REQUEST,
+            Self::IdempotencyKeyInUse => StatusCode::CONFLICT,
+            Self::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // 予期していないエラーの原因はログにのみ記録して、応答には含めない
+        let message = match self {
+            Self::UnexpectedError(e) => {
+                tracing::error!("{}", e);
+                "サーバー内部でエラーが発生しました。".to_owned()
+            }
+            _ => self.to_string(),
+        };
+
+        HttpResponse::build(self.status_code()).json(ErrorResponseBody {
+            code: self.code(),
+            message,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    /// それぞれのエラーが、ステータスコードと同じ形式のJSONに変換されることを確認するテスト
+    #[actix_web::test]
+    async fn middleware_error_response() {
+        let cases = vec![
+            (
+                MiddlewareError::Unauthorized,
+                StatusCode::UNAUTHORIZED,
+                "UNAUTHORIZED",
+                "認証されていません。",
+            ),
+            (
+                MiddlewareError::SessionExpired,
+                StatusCode::UNAUTHORIZED,
+                "SESSION_EXPIRED",
+                "セッションは失効しています。",
+            ),
+            (
+                MiddlewareError::RefreshChainExhausted,
+                StatusCode::UNAUTHORIZED,
+                "LOGIN_REQUIRED",
+                "ログインし直してください。",
+            ),
+            (
+                MiddlewareError::AccessTokenExpired,
+                StatusCode::UNAUTHORIZED,
+                "ACCESS_TOKEN_EXPIRED",
+                "アクセストークンの有効期限が切れています。トークンをリフレッシュしてください。",
+            ),
+            (
+                MiddlewareError::Forbidden,
+                StatusCode::FORBIDDEN,
+                "FORBIDDEN",
+                "ユーザーが無効になっています。",
+            ),
+            (
+                MiddlewareError::PasswordChangeRequired,
+                StatusCode::FORBIDDEN,
+                "PASSWORD_CHANGE_REQUIRED",
+                "パスワードを変更してください。",
+            ),
+            (
+                MiddlewareError::InsufficientScope("users:read"),
+                StatusCode::FORBIDDEN,
+                "INSUFFICIENT_SCOPE",
+                "APIキーにスコープ(users:read)が付与されていません。",
+            ),
+            (
+                MiddlewareError::ApiKeyNotAllowed,
+                StatusCode::FORBIDDEN,
+                "API_KEY_NOT_ALLOWED",
+                "このリソースには、APIキーでアクセスできません。",
+            ),
+            (
+                MiddlewareError::InsufficientRole,
+                StatusCode::FORBIDDEN,
+                "INSUFFICIENT_ROLE",
+                "このリソースにアクセスする権限がありません。",
+            ),
+            (
+                MiddlewareError::InvalidIdempotencyKey,
+                StatusCode::BAD_REQUEST,
+                "INVALID_IDEMPOTENCY_KEY",
+                "冪等キーは1文字以上255文字以内の英数字と記号で指定してください。",
+            ),
+            (
+                MiddlewareError::IdempotencyKeyInUse,
+                StatusCode::CONFLICT,
+                "IDEMPOTENCY_KEY_IN_USE",
+                "同じ冪等キーを指定したリクエストを処理しています。",
+            ),
+            (
+                MiddlewareError::IdempotencyKeyReused,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "IDEMPOTENCY_KEY_REUSED",
+                "冪等キーが異なるリクエストで使用されています。",
+            ),
+            (
+                MiddlewareError::ServiceUnavailable,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "SERVICE_UNAVAILABLE",
+                "現在、認証できません。しばらくしてから再度お試しください。",
+            ),
+            (
+                MiddlewareError::unexpected("connection refused"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INTERNAL_SERVER_ERROR",
+                "サーバー内部でエラーが発生しました。",
+            ),
+        ];
+        for (error, status, code, message) in cases {
+            assert_eq!(error.status_code(), status, "{:?}", error);
+            let resp = error.error_response();
+            assert_eq!(resp.status(), status, "{:?}", error);
+            let body = to_bytes(resp.into_body()).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                body,
+                serde_json::json!({ "code": code, "message": message }),
+                "{:?}",
+                error
+            );
+        }
+    }
+
+    /// ユーザーが無効になっている場合のエラーが、設定したステータスコードで応答することを確認するテスト
+    #[test]
+    fn inactive_user_error_follows_status() {
+        assert!(matches!(
+            MiddlewareError::inactive_user(StatusCode::FORBIDDEN),
+            MiddlewareError::Forbidden
+        ));
+        assert!(matches!(
+            MiddlewareError::inactive_user(StatusCode::UNAUTHORIZED),
+            MiddlewareError::Unauthorized
+        ));
+    }
+}
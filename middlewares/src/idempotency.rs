@@ -0,0 +1,533 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use configurations::session::SessionDataCipher;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::errors::MiddlewareError;
+
+/// 冪等キーを指定するリクエストヘッダー
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// 記録した処理結果で応答したことを示すレスポンスヘッダー
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// 冪等キーの最大文字数
+const IDEMPOTENCY_KEY_MAX_LEN: usize = 255;
+
+/// 冪等ストアに記録するリクエストの状態を暗号化する暗号鍵の用途
+const IDEMPOTENCY_RECORD_KEY_PURPOSE: &str = "idempotency_record";
+
+/// 冪等ストア
+///
+/// 冪等キーと、そのキーを指定したリクエストの処理結果を記録する。
+#[async_trait::async_trait(?Send)]
+pub trait IdempotencyStore {
+    /// キーが記録されていない場合のみ、値を記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - キー。
+    /// * `value` - 値。
+    /// * `ttl` - 値を記録する期間。
+    ///
+    /// # Returns
+    ///
+    /// 値を記録した場合は`true`、既にキーが記録されていた場合は`false`。
+    async fn insert_if_absent(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+    ) -> anyhow::Result<bool>;
+
+    /// キーに記録されている値を取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - キー。
+    ///
+    /// # Returns
+    ///
+    /// 値。キーが記録されていない場合は`None`。
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+
+    /// キーに値を記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - キー。
+    /// * `value` - 値。
+    /// * `ttl` - 値を記録する期間。
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> anyhow::Result<()>;
+
+    /// キーを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - キー。
+    async fn remove(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// 冪等ストアに記録する、冪等キーを指定したリクエストの状態
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum IdempotencyRecord {
+    /// リクエストを処理している。
+    Processing { request_hash: String },
+    /// リクエストの処理が完了した。
+    Completed {
+        request_hash: String,
+        response: RecordedResponse,
+    },
+}
+
+impl IdempotencyRecord {
+    /// リクエストの状態を暗号化する。
+    ///
+    /// 記録する応答には、トークンを含むクッキーやリクエストの処理結果が含まれるため、冪等ストアが漏洩しても
+    /// 流出しないように、暗号化して記録する。
+    fn seal(&self, cipher: &SessionDataCipher) -> Result<String, MiddlewareError> {
+        let plaintext = serde_json::to_vec(self).map_err(MiddlewareError::unexpected)?;
+
+        cipher
+            .encrypt(&plaintext)
+            .map_err(MiddlewareError::unexpected)
+    }
+
+    /// 暗号化したリクエストの状態を復号する。
+    fn open(cipher: &SessionDataCipher, value: &str) -> Result<Self, MiddlewareError> {
+        let plaintext = cipher.decrypt(value).map_err(MiddlewareError::unexpected)?;
+
+        serde_json::from_slice(&plaintext).map_err(MiddlewareError::unexpected)
+    }
+
+    /// リクエストのハッシュ値を返却する。
+    fn request_hash(&self) -> &str {
+        match self {
+            Self::Processing { request_hash } | Self::Completed { request_hash, .. } => {
+                request_hash
+            }
+        }
+    }
+}
+
+/// 冪等ストアに記録する応答
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    /// ステータスコード。
+    status: u16,
+    /// レスポンスヘッダー。
+    headers: Vec<(String, Vec<u8>)>,
+    /// レスポンスボディ。
+    body: Vec<u8>,
+}
+
+impl RecordedResponse {
+    /// 記録した応答を復元する。
+    ///
+    /// 復元した応答には、記録した処理結果で応答したことを示すヘッダーを追加する。
+    fn to_response(&self) -> Result<HttpResponse, MiddlewareError> {
+        let status = StatusCode::from_u16(self.status).map_err(MiddlewareError::unexpected)?;
+        let mut builder = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            let name =
+                HeaderName::from_bytes(name.as_bytes()).map_err(MiddlewareError::unexpected)?;
+            let value = HeaderValue::from_bytes(value).map_err(MiddlewareError::unexpected)?;
+            builder.append_header((name, value));
+        }
+        builder.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+
+        Ok(builder.body(self.body.clone()))
+    }
+}
+
+/// 冪等ミドルウェア
+///
+/// 指定したパスへの`POST`リクエストに`Idempotency-Key`ヘッダーが含まれている場合に、最初のリクエストの
+/// 処理結果を冪等ストアに記録して、同じ冪等キーを指定したリクエストには、リクエストを処理せずに記録した
+/// 処理結果で応答する。
+///
+/// * 同じ冪等キーを指定したリクエストを処理している場合は、`409 Conflict`で応答する。
+/// * 同じ冪等キーが、メソッド、パス又はボディが異なるリクエストで使用されていた場合は、
+///   `422 Unprocessable Entity`で応答する。
+/// * サーバーエラーで応答した場合は、再試行できるように処理結果を記録しない。
+///
+/// セッションIDを含むクッキーも応答に記録するため、セッションミドルウェアより外側で処理する。
+/// 記録する処理結果は、トークンの秘密鍵から導出した暗号鍵で暗号化して、リクエストのハッシュ値は、パスワードを
+/// 含むボディを推測されないように、トークンの秘密鍵で計算したHMAC-SHA256とする。
+pub struct Idempotency<T> {
+    /// 冪等ストア。
+    store: T,
+    /// 処理結果を記録する期間。
+    ttl: Duration,
+    /// 冪等キーを受け付けるパス。
+    paths: Rc<Vec<String>>,
+    /// リクエストのハッシュ値を計算するトークンの秘密鍵。
+    secret_key: Secret<String>,
+    /// リクエストの状態を暗号化する暗号。
+    cipher: SessionDataCipher,
+}
+
+impl<T> Idempotency<T> {
+    /// 冪等ミドルウェアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - 冪等ストア。
+    /// * `ttl` - 処理結果を記録する期間。
+    /// * `paths` - 冪等キーを受け付けるパス。
+    /// * `secret_key` - トークンの秘密鍵。
+    ///
+    /// # Returns
+    ///
+    /// 冪等ミドルウェアインスタンス。
+    pub fn new(store: T, ttl: Duration, paths: &[&str], secret_key: &Secret<String>) -> Self {
+        Self {
+            store,
+            ttl,
+            paths: Rc::new(paths.iter().map(|path| path.to_string()).collect()),
+            secret_key: secret_key.clone(),
+            cipher: SessionDataCipher::derive(secret_key, IDEMPOTENCY_RECORD_KEY_PURPOSE),
+        }
+    }
+}
+
+impl<S, B, T> Transform<S, ServiceRequest> for Idempotency<T>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    T: IdempotencyStore + Clone + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = IdempotencyMiddleware<S, T>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+            store: Rc::new(self.store.clone()),
+            ttl: self.ttl,
+            paths: Rc::clone(&self.paths),
+            secret_key: Rc::new(self.secret_key.clone()),
+            cipher: Rc::new(self.cipher.clone()),
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S, T> {
+    service: Rc<S>,
+    store: Rc<T>,
+    ttl: Duration,
+    paths: Rc<Vec<String>>,
+    secret_key: Rc<Secret<String>>,
+    cipher: Rc<SessionDataCipher>,
+}
+
+/// リクエストヘッダーから冪等キーを取得する。
+///
+/// # Returns
+///
+/// 冪等キー。リクエストヘッダーに冪等キーが含まれていない場合は`None`。
+fn get_idempotency_key(req: &ServiceRequest) -> Result<Option<String>, MiddlewareError> {
+    let value = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let key = value
+        .to_str()
+        .map_err(|_| MiddlewareError::InvalidIdempotencyKey)?;
+    if key.is_empty()
+        || IDEMPOTENCY_KEY_MAX_LEN < key.len()
+        || !key.chars().all(|c| c.is_ascii_graphic())
+    {
+        return Err(MiddlewareError::InvalidIdempotencyKey);
+    }
+
+    Ok(Some(key.to_owned()))
+}
+
+/// リクエストのメソッド、パス及びボディからハッシュ値を計算する。
+///
+/// 同じ冪等キーが異なるリクエストで使用されていないか確認するために使用する。ボディにはパスワードが
+/// 含まれるため、冪等ストアが漏洩しても総当たりで推測されないように、トークンの秘密鍵で計算した
+/// HMAC-SHA256とする。
+fn compute_request_hash(req: &ServiceRequest, body: &[u8], secret_key: &Secret<String>) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.expose_secret().as_bytes())
+        .expect("HMACは任意の長さの鍵を受け付けます。");
+    // 同じ秘密鍵で計算する他のHMACと区別するために、用途を示す接頭辞を付与
+    mac.update(b"idempotency_request\0");
+    mac.update(req.method().as_str().as_bytes());
+    mac.update(b"\n");
+    mac.update(req.path().as_bytes());
+    mac.update(b"\n");
+    mac.update(body);
+
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// 冪等ストアに記録されたリクエストの状態から、応答を返却する。
+async fn replay<T: IdempotencyStore>(
+    store: &T,
+    cipher: &SessionDataCipher,
+    store_key: &str,
+    request_hash: &str,
+) -> Result<HttpResponse, MiddlewareError> {
+    let record = store
+        .get(store_key)
+        .await
+        .map_err(MiddlewareError::unexpected)?
+        .ok_or(MiddlewareError::IdempotencyKeyInUse)?;
+    let record = IdempotencyRecord::open(cipher, &record)?;
+    if record.request_hash() != request_hash {
+        return Err(MiddlewareError::IdempotencyKeyReused);
+    }
+    match record {
+        IdempotencyRecord::Processing { .. } => Err(MiddlewareError::IdempotencyKeyInUse),
+        IdempotencyRecord::Completed { response, .. } => response.to_response(),
+    }
+}
+
+/// リクエストを再試行できるように、冪等ストアから冪等キーを削除する。
+async fn release<T: IdempotencyStore>(store: &T, store_key: &str) {
+    if let Err(e) = store.remove(store_key).await {
+        tracing::warn!("冪等キーを削除できませんでした。{}", e);
+    }
+}
+
+impl<S, B, T> Service<ServiceRequest> for IdempotencyMiddleware<S, T>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+    T: IdempotencyStore + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let store = Rc::clone(&self.store);
+        let ttl = self.ttl;
+        let secret_key = Rc::clone(&self.secret_key);
+        let cipher = Rc::clone(&self.cipher);
+        let is_target =
+            req.method() == Method::POST && self.paths.iter().any(|path| path == req.path());
+
+        Box::pin(async move {
+            // 冪等キーを受け付けないリクエストと、冪等キーを含まないリクエストは、そのまま処理を移譲
+            if !is_target {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            }
+            let key = match get_idempotency_key(&req) {
+                Ok(Some(key)) => key,
+                Ok(None) => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+                Err(e) => return Ok(req.error_response(e)),
+            };
+            // リクエストボディを読み込んでハッシュ値を計算した後、後続の処理で読み込めるように戻す
+            let body = match req.extract::<Bytes>().await {
+                Ok(body) => body,
+                Err(e) => return Ok(req.error_response(e)),
+            };
+            let request_hash = compute_request_hash(&req, &body, &secret_key);
+            let (_, mut payload) = actix_http::h1::Payload::create(true);
+            payload.unread_data(body);
+            req.set_payload(payload.into());
+
+            // リクエストを処理していることを記録して、既に記録されていた場合は、記録された状態で応答
+            let store_key = format!("idempotency:{}:{}", req.path(), key);
+            let processing = match (IdempotencyRecord::Processing {
+                request_hash: request_hash.clone(),
+            })
+            .seal(&cipher)
+            {
+                Ok(processing) => processing,
+                Err(e) => return Ok(req.error_response(e)),
+            };
+            match store.insert_if_absent(&store_key, processing, ttl).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Ok(
+                        match replay(store.as_ref(), &cipher, &store_key, &request_hash).await {
+                            Ok(response) => req.into_response(response),
+                            Err(e) => req.error_response(e),
+                        },
+                    );
+                }
+                // 冪等ストアを使用できない場合は、リクエストの処理を止めないように、冪等キーを無視して処理
+                Err(e) => {
+                    tracing::warn!("冪等キーを記録できませんでした。{}", e);
+                    return service.call(req).await.map(|res| res.map_into_boxed_body());
+                }
+            }
+
+            // リクエストを処理して、サーバーエラーの場合は、再試行できるように冪等キーを削除
+            let res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    release(store.as_ref(), &store_key).await;
+                    return Err(e);
+                }
+            };
+            if res.status().is_server_error() {
+                release(store.as_ref(), &store_key).await;
+                return Ok(res.map_into_boxed_body());
+            }
+
+            // 応答を記録した後、記録したボディで応答
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+            let body = match to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    release(store.as_ref(), &store_key).await;
+                    return Err(MiddlewareError::unexpected(e.into()).into());
+                }
+            };
+            let completed = IdempotencyRecord::Completed {
+                request_hash,
+                response: RecordedResponse {
+                    status: res.status().as_u16(),
+                    headers: res
+                        .headers()
+                        .iter()
+                        .map(|(name, value)| (name.as_str().to_owned(), value.as_bytes().to_vec()))
+                        .collect(),
+                    body: body.to_vec(),
+                },
+            };
+            let recorded = match completed.seal(&cipher) {
+                Ok(completed) => store.set(&store_key, completed, ttl).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = recorded {
+                tracing::warn!("リクエストの処理結果を記録できませんでした。{}", e);
+                release(store.as_ref(), &store_key).await;
+            }
+
+            Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    /// 冪等キーの形式を検証することを確認するテスト
+    #[test]
+    fn idempotency_key_is_validated() {
+        let req = TestRequest::post().to_srv_request();
+        assert!(get_idempotency_key(&req).unwrap().is_none());
+        let req = TestRequest::post()
+            .insert_header((IDEMPOTENCY_KEY_HEADER, "b3f1c2d4-key"))
+            .to_srv_request();
+        assert_eq!(
+            get_idempotency_key(&req).unwrap().as_deref(),
+            Some("b3f1c2d4-key")
+        );
+        for key in ["", "has space", &"x".repeat(IDEMPOTENCY_KEY_MAX_LEN + 1)] {
+            let req = TestRequest::post()
+                .insert_header((IDEMPOTENCY_KEY_HEADER, key))
+                .to_srv_request();
+            assert!(
+                matches!(
+                    get_idempotency_key(&req),
+                    Err(MiddlewareError::InvalidIdempotencyKey)
+                ),
+                "{}",
+                key
+            );
+        }
+    }
+
+    /// 記録した応答を、ステータスコード、ヘッダー及びボディを含めて復元できることを確認するテスト
+    #[test]
+    fn recorded_response_is_restored() {
+        let recorded = RecordedResponse {
+            status: 200,
+            headers: vec![
+                ("set-cookie".to_owned(), b"a=1".to_vec()),
+                ("set-cookie".to_owned(), b"b=2".to_vec()),
+            ],
+            body: b"{}".to_vec(),
+        };
+        let response = recorded.to_response().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get_all("set-cookie").count(), 2);
+        assert_eq!(
+            response.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+            "true"
+        );
+    }
+
+    /// 記録するリクエストの状態を、平文を含まないように暗号化して復号できることを確認するテスト
+    #[test]
+    fn record_is_sealed() {
+        let cipher = SessionDataCipher::derive(
+            &Secret::new("some-secret".to_owned()),
+            IDEMPOTENCY_RECORD_KEY_PURPOSE,
+        );
+        let record = IdempotencyRecord::Completed {
+            request_hash: "hash".to_owned(),
+            response: RecordedResponse {
+                status: 200,
+                headers: vec![(
+                    "set-cookie".to_owned(),
+                    b"access_token=secret-token".to_vec(),
+                )],
+                body: b"{}".to_vec(),
+            },
+        };
+        let sealed = record.seal(&cipher).unwrap();
+        assert!(!sealed.contains("secret-token"));
+        assert!(!sealed.contains("set-cookie"));
+        match IdempotencyRecord::open(&cipher, &sealed).unwrap() {
+            IdempotencyRecord::Completed { response, .. } => {
+                assert_eq!(response.headers[0].1, b"access_token=secret-token".to_vec());
+            }
+            IdempotencyRecord::Processing { .. } => panic!("処理結果を復元できませんでした。"),
+        }
+        let other = SessionDataCipher::derive(
+            &Secret::new("other-secret".to_owned()),
+            IDEMPOTENCY_RECORD_KEY_PURPOSE,
+        );
+        assert!(IdempotencyRecord::open(&other, &sealed).is_err());
+    }
+
+    /// リクエストのハッシュ値を、トークンの秘密鍵で計算することを確認するテスト
+    #[test]
+    fn request_hash_is_keyed() {
+        let body = br#"{"password":"Passw0rd!"}"#;
+        let req = TestRequest::post().uri("/accounts/login").to_srv_request();
+        let secret_key = Secret::new("some-secret".to_owned());
+        let hash = compute_request_hash(&req, body, &secret_key);
+        assert_eq!(hash, compute_request_hash(&req, body, &secret_key));
+        assert_ne!(
+            hash,
+            compute_request_hash(&req, body, &Secret::new("other-secret".to_owned()))
+        );
+        assert_ne!(
+            hash,
+            compute_request_hash(&req, br#"{"password":"Passw0rd?"}"#, &secret_key)
+        );
+    }
+}
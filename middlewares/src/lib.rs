@@ -26,6 +26,10 @@
 //! 即座に`401 Unauthorized`で応答するとともに、Redisに格納された当該`セッションデータ`を削除して、クッキーの
 //! 削除を応答で指示する。
 //!
+//! クッキーのリフレッシュトークンが正しく署名されたJWTで、その主体(`sub`)が`セッションデータ`のユーザーIDと
+//! 一致しない場合は、他のユーザーのリフレッシュトークンが使用されたと判断して、警告をログに記録して、
+//! 当該`セッションデータ`を削除して`401 Unauthorized`で応答する。
+//!
 //! 次に、`セッションデータ`のアクセストークンの有効期限を確認して、その有効期限が切れていない場合は、保護された
 //! リソースへのアクセスを許可する。
 //!
@@ -41,25 +45,107 @@
 //! をキーに`セッションデータ`として保存する。
 //! また、ブラウザにセッションIDと、新しく生成したアクセストークンとリフレッシュトークンをクッキーに保存するように
 //! 指示する。
+//!
+//! ただし、システム設定でサイレントリフレッシュを無効にしている場合は、(A)の代わりに`401 Unauthorized`で
+//! 応答して、クライアントにリフレッシュAPI(`/accounts/refresh`)を呼び出すように促す。
+//!
+//! アクセストークンの有効期限内でも、以下の場合は(A)と同様にトークンをリフレッシュする。
+//!
+//! * `セッションデータ`の世代が現在の世代より古い場合(世代更新)
+//! * リフレッシュトークンの残りの有効期間が、システム設定のスライディング延長する期間以下の場合(スライディング延長)
+//!
+//! トークンをリフレッシュするときは、その理由をログに記録する。
+//!
+//! リクエストは`JwtAuth`スパン内で処理して、ユーザーを取得できた場合は、スパンにユーザーIDを記録する。
+//! 後続のハンドラが出力するログにもユーザーIDが含まれるため、ユーザーごとにログを関連付けられる。
+//!
+//! `セッションデータ`に含まれているユーザーが無効になっている場合は、トークンをリフレッシュせずに、Redisに
+//! 格納された当該`セッションデータ`を削除して、システム設定のステータスコード(`403 Forbidden`又は
+//! `401 Unauthorized`)で応答する。
+//!
+//! トークンをリフレッシュしない場合は、`セッションデータ`の最終アクセス日時を更新して、Redisに記録された
+//! `セッションデータ`の有効期限を延長する。ただし、Redisへの書き込みを抑制するため、前回の更新からシステム設定の
+//! 間隔が経過していない場合は更新しない。
+//!
+//! データベースの一時的な障害でユーザーを取得できなかった場合でも、トークンのリフレッシュはデータベースに依存しない
+//! ため、トークンをリフレッシュして後続の処理に移譲する。このとき、リクエストにユーザーの代わりに`UserLookupError`を
+//! 追加して、ユーザーを必要とするハンドラにエラーを伝える。
+//! ただし、セッションが失効しているか確認できない場合は、失効したセッションを受け付けないように、
+//! `503 Service Unavailable`で応答する。
+//!
+//! ユーザーを取得できた場合は、リクエストにユーザーと、ユーザーが所属するテナントを表現する`TenantContext`を
+//! 追加する。ハンドラは`TenantContext`のテナントIDで、他のテナントのリソースにアクセスできないように制限する。
+//!
+//! 管理者がパスワードをリセットしたユーザーなど、パスワードの変更を要求されているユーザーは、パスワードの変更と
+//! ログアウト以外のリソースへのアクセスを`403 Forbidden`で拒否する。
+//!
+//! `OptionalJwtAuth`は、`セッションデータ`を取得できなかった場合に`401 Unauthorized`で応答せずに、
+//! リクエストにユーザーを追加しないで後続の処理に移譲する。
+//!
+//! CORSのプリフライトリクエスト(`OPTIONS`)は認証情報を含まないため、認証せずに後続の処理に移譲する。
+//! また、外側のミドルウェアが応答にCORSヘッダーを付与できるように、認証に失敗した場合はエラーではなく
+//! 応答を返却する。
+//!
+//! 認証に失敗した場合は、`MiddlewareError`で失敗した理由を表現して、どの分岐でも同じ形式のJSONで応答する。
+//!
+//! `Authorization: ApiKey <APIキー>`ヘッダーを含むリクエストは、サービスアカウントなどのセッションを使用しない
+//! クライアントからのリクエストと判断して、セッションの代わりにAPIキーで認証する。APIキーのハッシュで
+//! データベースからAPIキーを取得して、APIキーが見つからないか、有効期限が切れている場合は`401 Unauthorized`で
+//! 応答する。APIキーで認証できるリソースは、スコープ制限ミドルウェア(`RequireScope`)でスコープを宣言した
+//! リソースに限定して、スコープを宣言していないリソースや、APIキーにスコープが付与されていない場合は
+//! `403 Forbidden`で応答する。認証できた場合は、リクエストにAPIキーを所有するユーザーと`TenantContext`に加えて、
+//! APIキーのスコープを表現する`ApiKeyContext`を追加する。APIキーで認証したリクエストでは、トークンを
+//! リフレッシュしない。
+//!
+//! WebSocketへのアップグレードなど、このミドルウェアでラップできないハンドラは、`authenticate_request`で
+//! このミドルウェアと同じ方法でリクエストを認証できる。
+pub mod errors;
+pub mod idempotency;
+pub mod require_role;
+pub mod require_scope;
+pub mod session_key_rotation;
+pub mod user_cache;
+
 use std::future::{ready, Future, Ready};
 use std::pin::Pin;
 use std::rc::Rc;
 
 use actix_session::SessionExt;
+use actix_web::cookie::time::OffsetDateTime;
 use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
-use actix_web::{web, HttpMessage};
+use actix_web::{
+    http::{header::AUTHORIZATION, Method},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
 use configurations::session::{ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::Instrument;
 use uuid::Uuid;
 
 use configurations::{
-    generate_session_data,
-    session::{add_session_data_cookies, SessionData, TypedSession},
-    Settings,
+    reissue_session_data,
+    session::{
+        add_session_data_cookies, add_token_fingerprint_header, SessionData, SessionDataCipher,
+        TypedSession, SESSION_GENERATION,
+    },
+    tokens::{api_key_hash, get_claim_from_jwt},
+    SessionCookieSettings, Settings,
+};
+use domains::models::{
+    refresh_tokens::{RefreshToken, SessionId},
+    users::{User, UserId},
+};
+use infrastructures::repositories::{
+    api_keys::PgApiKeyRepository,
+    refresh_tokens::{PgRefreshTokenRepository, RefreshTokenRepositoryError},
+    users::PgUserRepository,
 };
-use domains::models::users::{User, UserId};
-use infrastructures::repositories::users::PgUserRepository;
 use miscellaneous::current_unix_epoch;
+use secrecy::Secret;
+
+use crate::errors::MiddlewareError;
+use crate::require_scope::authorize_api_key;
+use crate::user_cache::UserCache;
 
 pub struct JwtAuth;
 
@@ -77,51 +163,67 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(JwtAuthMiddleware {
             service: Rc::new(service),
+            optional: false,
         }))
     }
 }
 
-pub struct JwtAuthMiddleware<S> {
-    service: Rc<S>,
-}
+/// 認証されていないリクエストも受け付けるJwtAuth
+///
+/// `セッションデータ`がない場合は、リクエストにユーザーを追加せずに、後続のミドルウェアなどにリクエストの
+/// 処理を移譲する。`セッションデータ`がある場合は、`JwtAuth`と同様に処理する。
+/// 閲覧者の認証状態によって応答を変えるリソースで使用する。
+pub struct OptionalJwtAuth;
 
-fn get_settings(service_req: &ServiceRequest) -> Result<&Settings, actix_web::Error> {
-    let settings = service_req.app_data::<web::Data<Settings>>();
-    if settings.is_none() {
-        return Err(actix_web::error::ErrorInternalServerError(
-            "システム設定を取得できませんでした。",
-        ));
-    }
+impl<S> Transform<S, ServiceRequest> for OptionalJwtAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = actix_web::Error;
+    type Transform = JwtAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
-    Ok(settings.unwrap().as_ref())
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(JwtAuthMiddleware {
+            service: Rc::new(service),
+            optional: true,
+        }))
+    }
 }
 
-fn get_database_connection_pool(service_req: &ServiceRequest) -> Result<&PgPool, actix_web::Error> {
-    let pool = service_req.app_data::<web::Data<PgPool>>();
-    if pool.is_none() {
-        return Err(actix_web::error::ErrorInternalServerError(
-            "データベースコネクションプールを取得できませんでした。",
-        ));
-    }
+pub struct JwtAuthMiddleware<S> {
+    service: Rc<S>,
+    /// 認証されていないリクエストを受け付けるか。
+    optional: bool,
+}
 
-    Ok(pool.unwrap().as_ref())
+fn get_settings(req: &HttpRequest) -> Result<&Settings, MiddlewareError> {
+    req.app_data::<web::Data<Settings>>()
+        .map(|settings| settings.as_ref())
+        .ok_or_else(|| MiddlewareError::unexpected("システム設定を取得できませんでした。"))
 }
 
-fn get_session_data(session: &TypedSession) -> Result<Option<SessionData>, actix_web::Error> {
-    let session_data = session.get();
-    if let Err(e) = session_data {
-        return Err(actix_web::error::ErrorInternalServerError(e));
-    }
+fn get_database_connection_pool(req: &HttpRequest) -> Result<&PgPool, MiddlewareError> {
+    req.app_data::<web::Data<PgPool>>()
+        .map(|pool| pool.as_ref())
+        .ok_or_else(|| {
+            MiddlewareError::unexpected("データベースコネクションプールを取得できませんでした。")
+        })
+}
 
-    Ok(session_data.unwrap())
+fn get_session_data(session: &TypedSession) -> Result<Option<SessionData>, MiddlewareError> {
+    session.get().map_err(MiddlewareError::unexpected)
 }
 
-fn get_tokens(service_req: &ServiceRequest) -> (String, String) {
-    let access_token = match service_req.cookie(ACCESS_TOKEN_COOKIE_NAME) {
+fn get_tokens(req: &HttpRequest) -> (String, String) {
+    let access_token = match req.cookie(ACCESS_TOKEN_COOKIE_NAME) {
         Some(cookie) => cookie.value().to_owned(),
         None => "".to_owned(),
     };
-    let refresh_token = match service_req.cookie(REFRESH_TOKEN_COOKIE_NAME) {
+    let refresh_token = match req.cookie(REFRESH_TOKEN_COOKIE_NAME) {
         Some(cookie) => cookie.value().to_owned(),
         None => "".to_owned(),
     };
@@ -129,24 +231,69 @@ fn get_tokens(service_req: &ServiceRequest) -> (String, String) {
     (access_token, refresh_token)
 }
 
+/// パスワードの変更を要求されているユーザーがアクセスできるパス
+const PASSWORD_CHANGE_ALLOWED_PATHS: [&str; 2] = ["/accounts/change_password", "/accounts/logout"];
+
+/// トークンをリフレッシュする理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RefreshReason {
+    /// アクセストークンの有効期限切れ
+    AccessExpired,
+    /// アクセストークンのクッキーの欠落
+    AccessMissing,
+    /// リフレッシュトークンの有効期限が近づいたことによるスライディング延長
+    SlidingRenewal,
+    /// セッションデータの世代更新
+    GenerationBump,
+}
+
+impl RefreshReason {
+    /// ログやメトリクスに記録する理由の名前を返却する。
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccessExpired => "access_expired",
+            Self::AccessMissing => "access_missing",
+            Self::SlidingRenewal => "sliding_renewal",
+            Self::GenerationBump => "generation_bump",
+        }
+    }
+}
+
+impl std::fmt::Display for RefreshReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum TokenValidation {
     /// 成功
     Succeed,
     /// リフレッシュを要求
-    RequiredRefresh,
+    RequiredRefresh(RefreshReason),
     /// 失敗
     Failure,
+    /// リフレッシュトークンの主体がセッションデータのユーザーと一致しない
+    ///
+    /// 他のユーザーのリフレッシュトークンが使用されたと判断して、セキュリティイベントとして扱う。
+    SubjectMismatch,
 }
 
 /// Redisに記録されているセッションデータと、クッキーに記録されたアクセストークンとリフレッシュトークンを評価する。
 ///
-/// 1. リフレッシュトークンの有効期限が切れていた場合は、認証を許可できないため`失敗`を返却。
-/// 2. アクセストークンの有効期限を確認して、有効期限内であればアクセストークンが一致するか確認
-///   * 一致すれば`成功`を返却
+/// 1. リフレッシュトークンが正しく署名されたJWTで、その`sub`がセッションデータのユーザーIDと一致しない場合は
+///    `主体不一致`を返却。
+/// 2. リフレッシュトークンの有効期限が切れていた場合は、認証を許可できないため`失敗`を返却。
+/// 3. アクセストークンの有効期限を確認して、有効期限内であればアクセストークンが一致するか確認
+///   * クッキーにアクセストークンが記録されていない場合は、リフレッシュトークンが一致すれば理由を
+///     `アクセストークン欠落`とした`リフレッシュ要求`を返却して、一致しなければ`失敗`を返却
 ///   * 一致しなければ`失敗`を返却
-/// 3. アクセストークンの有効期限が切れている場合は、リフレッシュトークンが一致するか確認
-///   * 一致すれば`リフレッシュ要求`を返却
+///   * セッションデータの世代が古ければ、理由を`世代更新`とした`リフレッシュ要求`を返却
+///   * リフレッシュトークンの残りの有効期間がスライディング延長する期間以下であれば、理由を
+///     `スライディング延長`とした`リフレッシュ要求`を返却
+///   * それ以外は`成功`を返却
+/// 4. アクセストークンの有効期限が切れている場合は、リフレッシュトークンが一致するか確認
+///   * 一致すれば理由を`アクセストークン期限切れ`とした`リフレッシュ要求`を返却
 ///   * 一致しなければ`失敗`を返却
 ///
 /// # Arguments
@@ -154,21 +301,36 @@ enum TokenValidation {
 /// * `session_data` - Redisに記録されているセッションデータ。
 /// * `access_token` - クッキーに記録されていたアクセストークン。
 /// * `refresh_token` - クッキーに記録されていたリフレッシュトークン。
+/// * `secret_key` - JWT生成鍵。
+/// * `sliding_renewal_duration` - スライディング延長する秒数。`0`の場合はスライディング延長しない。
 ///
 /// # Returns
 ///
 /// * `TokenValidation::Succeed` - アクセストークンの検証に成功したため、保護されたリソースにアクセス可能。
-/// * `TokenValidation::RequiredRefresh` - リフレッシュトークンの検証に成功したため、保護されたリソースにアクセス可能。
-///     ただし、トークンをリフレッシュする必要がある。
+/// * `TokenValidation::RequiredRefresh` - トークンの検証に成功したため、保護されたリソースにアクセス可能。
+///   ただし、理由に示す原因で、トークンをリフレッシュする必要がある。
 /// * `TokenValidation::Failure` - トークンの検証に失敗したため、保護されたリソースにアクセス不可。
+/// * `TokenValidation::SubjectMismatch` - 他のユーザーのリフレッシュトークンが使用されたため、保護された
+///   リソースにアクセス不可。
 fn inspect_token_by_session_data(
     session_data: &SessionData,
     access_token: &str,
     refresh_token: &str,
+    secret_key: &Secret<String>,
+    sliding_renewal_duration: u64,
 ) -> TokenValidation {
     // 現在日時をUnixエポック秒で取得
     let now = current_unix_epoch();
 
+    // リフレッシュトークンが正しく署名されたJWTで、主体がセッションデータのユーザーと一致しない場合は
+    // `主体不一致`を返却
+    // 不透明トークンなど、JWTとして検証できないリフレッシュトークンは、セッションデータとの比較で評価する
+    if let Ok(claim) = get_claim_from_jwt(refresh_token, secret_key, &[]) {
+        if claim.user_id != session_data.user_id {
+            return TokenValidation::SubjectMismatch;
+        }
+    }
+
     // リフレッシュトークンの有効期限が切れている場合は`失敗`を返却
     if session_data.refresh_expiration < now {
         return TokenValidation::Failure;
@@ -176,39 +338,692 @@ fn inspect_token_by_session_data(
 
     // アクセストークンが有効期限ないか確認
     if now <= session_data.access_expiration {
+        // アクセストークンのクッキーが欠落している場合は、リフレッシュトークンが一致すればリフレッシュを要求
+        if access_token.is_empty() {
+            return if session_data.refresh_token.expose() == refresh_token {
+                TokenValidation::RequiredRefresh(RefreshReason::AccessMissing)
+            } else {
+                TokenValidation::Failure
+            };
+        }
         // アクセストークンが一致するか確認
-        if session_data.access_token == access_token {
-            return TokenValidation::Succeed;
-        } else {
+        if session_data.access_token.expose() != access_token {
             return TokenValidation::Failure;
         }
+        // セッションデータの世代が古い場合は`リフレッシュ要求`を返却
+        if session_data.generation < SESSION_GENERATION {
+            return TokenValidation::RequiredRefresh(RefreshReason::GenerationBump);
+        }
+        // リフレッシュトークンの有効期限が近い場合は`リフレッシュ要求`を返却
+        if 0 < sliding_renewal_duration
+            && session_data.refresh_expiration - now <= sliding_renewal_duration
+        {
+            return TokenValidation::RequiredRefresh(RefreshReason::SlidingRenewal);
+        }
+        return TokenValidation::Succeed;
     }
 
     // リフレッシュトークンが一致するか確認
-    if session_data.refresh_token == refresh_token {
-        TokenValidation::RequiredRefresh
+    if session_data.refresh_token.expose() == refresh_token {
+        TokenValidation::RequiredRefresh(RefreshReason::AccessExpired)
     } else {
         TokenValidation::Failure
     }
 }
 
-async fn get_user(pool: &PgPool, user_id: Uuid) -> Result<User, actix_web::Error> {
-    let user_id = UserId::new(user_id);
-    let mut tx = pool
-        .begin()
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("{}", e)))?;
-    let user = PgUserRepository::default()
-        .get_by_id(user_id, &mut tx)
+/// トークンをリフレッシュする理由を記録する。
+///
+/// # Arguments
+///
+/// * `session_data` - リフレッシュするセッションデータ。
+/// * `reason` - トークンをリフレッシュする理由。
+fn record_refresh_reason(session_data: &SessionData, reason: RefreshReason) {
+    tracing::info!(
+        session_id = %session_data.session_id,
+        reason = %reason,
+        "トークンをリフレッシュします。"
+    );
+}
+
+/// セッションの最終アクセス日時を更新する必要があるか確認する。
+///
+/// # Arguments
+///
+/// * `session_data` - セッションデータ。
+/// * `now` - 現在日時（UNIXエポック秒）。
+/// * `touch_interval` - セッションの最終アクセス日時を更新する間隔の秒数。
+///
+/// # Returns
+///
+/// 前回の更新から間隔が経過している場合は`true`。
+fn should_touch_session(session_data: &SessionData, now: u64, touch_interval: u64) -> bool {
+    session_data.last_accessed_at.saturating_add(touch_interval) <= now
+}
+
+/// ユーザーを取得できなかったことを示すエラー
+///
+/// データベースの一時的な障害などで、認証ミドルウェアがユーザーを取得できなかった場合に、ユーザーの代わりに
+/// リクエストデータとして追加する。ユーザーを必要とするハンドラは、`web::ReqData<User>`の代わりにこのエラーを
+/// 取得して応答できる。
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("ユーザーを取得できませんでした。{0}")]
+pub struct UserLookupError(String);
+
+impl actix_web::ResponseError for UserLookupError {}
+
+/// 認証されたユーザーが所属するテナント
+///
+/// 認証ミドルウェアがユーザーを取得できた場合に、リクエストデータとして追加する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TenantContext {
+    /// テナントID。
+    pub tenant_id: Uuid,
+}
+
+/// セッションで認証したリクエストの情報
+///
+/// 認証ミドルウェアがセッションでリクエストを認証した場合に、リクエストデータとして追加する。トークンを
+/// 更新した場合は、更新した後のトークンの有効期限を保持する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionContext {
+    /// セッションID。
+    pub session_id: Uuid,
+    /// アクセストークン有効期限（UNIXエポック秒）。
+    pub access_expiration: u64,
+    /// リフレッシュトークン有効期限（UNIXエポック秒）。
+    pub refresh_expiration: u64,
+}
+
+/// リクエストでトークンを更新したか
+///
+/// 認証ミドルウェアが、後続のミドルウェアやハンドラに処理を移譲する前に、すべてのリクエストにリクエスト
+/// データとして追加する。セッションで認証して、トークンを更新した場合にのみ`true`になる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshOccurred(pub bool);
+
+/// APIキーで認証したリクエストの情報
+///
+/// 認証ミドルウェアがAPIキーでリクエストを認証した場合に、リクエストデータとして追加する。ハンドラは、
+/// APIキーのスコープでアクセスを制限できる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyContext {
+    /// APIキーID。
+    pub api_key_id: Uuid,
+    /// APIキーのスコープ。
+    pub scopes: Vec<String>,
+}
+
+impl ApiKeyContext {
+    /// APIキーにスコープが付与されているか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - スコープ。
+    ///
+    /// # Returns
+    ///
+    /// スコープが付与されている場合は`true`。
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// APIキーを指定する`Authorization`ヘッダーの認証スキーム
+pub const API_KEY_AUTH_SCHEME: &str = "ApiKey";
+
+/// `Authorization`ヘッダーからAPIキーを取得する。
+///
+/// # Returns
+///
+/// APIキー。`Authorization`ヘッダーがないか、認証スキームが`ApiKey`でない場合は`None`。
+fn get_api_key(req: &HttpRequest) -> Option<&str> {
+    let (scheme, api_key) = req
+        .headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .split_once(' ')?;
+
+    scheme
+        .eq_ignore_ascii_case(API_KEY_AUTH_SCHEME)
+        .then(|| api_key.trim())
+}
+
+/// APIキーでリクエストを認証する。
+///
+/// セッションで認証する場合と異なり、データベースに問い合わせできない場合は、APIキーを確認できないため、
+/// 予期していないエラーを返却する。
+///
+/// # Arguments
+///
+/// * `req` - リクエスト。
+/// * `api_key` - APIキー。
+///
+/// # Returns
+///
+/// APIキーを所有するユーザーと、APIキーで認証したリクエストの情報。認証に失敗した場合は、失敗した理由を示す
+/// エラー。
+async fn authenticate_api_key(
+    req: &HttpRequest,
+    api_key: &str,
+) -> Result<(User, ApiKeyContext), MiddlewareError> {
+    let settings = get_settings(req)?;
+    let pool = get_database_connection_pool(req)?;
+    let key_hash =
+        api_key_hash(api_key, &settings.tokens.secret_key).map_err(MiddlewareError::unexpected)?;
+    // APIキーを取得して、APIキーが見つからないか、有効期限が切れている場合は、`401 Unauthorized`で応答
+    let mut tx = LazyTransaction::new(pool);
+    let record = PgApiKeyRepository
+        .get_by_key_hash(
+            &key_hash,
+            tx.get().await.map_err(MiddlewareError::unexpected)?,
+        )
         .await
-        .map_err(|e| actix_web::error::ErrorUnauthorized(format!("{}", e)))?;
-    if user.is_none() {
-        return Err(actix_web::error::ErrorUnauthorized(
-            "セッションデータに含まれているユーザーは存在しません。",
+        .map_err(MiddlewareError::unexpected)?
+        .ok_or(MiddlewareError::Unauthorized)?;
+    if record.is_expired(OffsetDateTime::now_utc()) {
+        tracing::info!(
+            api_key_id = %record.id().value(),
+            "有効期限が切れたAPIキーが使用されました。"
+        );
+        return Err(MiddlewareError::Unauthorized);
+    }
+    // APIキーを所有するユーザーを取得
+    let cache = req
+        .app_data::<web::Data<UserCache>>()
+        .map(|cache| cache.as_ref());
+    let user = get_user(&mut tx, cache, record.user_id().value())
+        .await?
+        .map_err(MiddlewareError::unexpected)?;
+    // ユーザーが無効になっている場合は、システム設定のステータスコードで応答
+    if !user.is_active() {
+        return Err(MiddlewareError::inactive_user(
+            settings.tokens.inactive_user_status,
         ));
     }
+    // パスワードの変更を要求されている場合は、パスワードの変更とログアウト以外へのアクセスを拒否
+    if user.must_change_password() && !PASSWORD_CHANGE_ALLOWED_PATHS.contains(&req.path()) {
+        return Err(MiddlewareError::PasswordChangeRequired);
+    }
 
-    Ok(user.unwrap())
+    let context = ApiKeyContext {
+        api_key_id: record.id().value(),
+        scopes: record
+            .scopes()
+            .iter()
+            .map(|scope| scope.value().to_owned())
+            .collect(),
+    };
+
+    Ok((user, context))
+}
+
+/// 認証ミドルウェアがデータベースに問い合わせるトランザクション
+///
+/// 1つのリクエストを認証するときに、ユーザーの取得、セッションの確認及びリフレッシュトークンの更新で同じ
+/// トランザクションを使用するため、最初に問い合わせるときにトランザクションを開始する。リフレッシュトークンを
+/// 更新した場合はコミットして、読み取りのみの場合はコミットせずに破棄する。
+struct LazyTransaction<'a> {
+    /// データベースコネクションプール。
+    pool: &'a PgPool,
+    /// 開始したトランザクション。開始していない場合は`None`。
+    tx: Option<Transaction<'static, Postgres>>,
+}
+
+impl<'a> LazyTransaction<'a> {
+    fn new(pool: &'a PgPool) -> Self {
+        Self { pool, tx: None }
+    }
+
+    /// トランザクションを返却して、開始していない場合は開始する。
+    async fn get(&mut self) -> Result<&mut Transaction<'static, Postgres>, sqlx::Error> {
+        let tx = match self.tx.take() {
+            Some(tx) => tx,
+            None => self.pool.begin().await?,
+        };
+
+        Ok(self.tx.insert(tx))
+    }
+
+    /// 開始したトランザクションを破棄する。
+    ///
+    /// 問い合わせに失敗したトランザクションは、以降の問い合わせに使用できないため、次に問い合わせるときに
+    /// トランザクションを開始し直す。
+    fn discard(&mut self) {
+        self.tx = None;
+    }
+
+    /// 開始したトランザクションをコミットする。開始していない場合は何もしない。
+    async fn commit(&mut self) -> Result<(), sqlx::Error> {
+        match self.tx.take() {
+            Some(tx) => tx.commit().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// セッションのリフレッシュトークンがデータベースに記録されているか確認する。
+///
+/// パスワードの変更などで、ユーザーのリフレッシュトークンがデータベースから削除された場合は、セッションが
+/// 失効したと判断して、`401 Unauthorized`を返却する。
+/// データベースに問い合わせできなかった場合は、セッションが失効しているか判断できないため、失効したセッションを
+/// 受け付けないように、`503 Service Unavailable`を返却する。
+async fn ensure_session_registered(
+    tx: &mut LazyTransaction<'_>,
+    session_id: Uuid,
+) -> Result<(), MiddlewareError> {
+    let session_id = SessionId::new(session_id);
+    let exists = async {
+        let tx = tx.get().await.map_err(|e| format!("{}", e))?;
+        PgRefreshTokenRepository
+            .exists(session_id.clone(), tx)
+            .await
+            .map_err(|e| format!("{}", e))
+    }
+    .await;
+    match exists {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(MiddlewareError::SessionExpired),
+        Err(e) => {
+            tracing::error!(
+                session_id = %session_id.value(),
+                "セッションが失効しているか確認できませんでした。{}",
+                e
+            );
+            Err(MiddlewareError::ServiceUnavailable)
+        }
+    }
+}
+
+/// セッションデータに含まれているユーザーを取得する。
+///
+/// ユーザーが存在しない場合は、`401 Unauthorized`を返却する。
+/// ユーザーキャッシュが有効な場合は、キャッシュしたユーザーを返却して、データベースから取得したユーザーを
+/// キャッシュする。キャッシュしたユーザーを返却する場合も、削除されたユーザーがキャッシュの有効期間が経過するまで
+/// 認証されないように、ユーザーが存在するかをデータベースで確認する。ユーザーの削除は他のプロセスでも実行
+/// されるため、削除したプロセスのキャッシュを破棄するだけでは防げないためである。
+///
+/// # Returns
+///
+/// ユーザー。データベースに問い合わせできなかった場合は、トークンのリフレッシュを継続できるように、
+/// `Ok(Err(UserLookupError))`を返却する。
+async fn get_user(
+    tx: &mut LazyTransaction<'_>,
+    cache: Option<&UserCache>,
+    user_id: Uuid,
+) -> Result<Result<User, UserLookupError>, MiddlewareError> {
+    // ユーザーキャッシュが有効で、ユーザーがキャッシュされている場合は、ユーザーが存在するかのみを確認
+    let now = current_unix_epoch();
+    if let Some(user) = cache.and_then(|cache| cache.get(user_id, now)) {
+        let exists = async {
+            let tx = tx.get().await.map_err(|e| format!("{}", e))?;
+            PgUserRepository
+                .exists(UserId::new(user_id), tx)
+                .await
+                .map_err(|e| format!("{}", e))
+        }
+        .await;
+        return match exists {
+            Ok(true) => Ok(Ok(user)),
+            Ok(false) => {
+                if let Some(cache) = cache {
+                    cache.invalidate(user_id);
+                }
+                Err(MiddlewareError::Unauthorized)
+            }
+            Err(e) => {
+                tracing::warn!(user_id = %user_id, "ユーザーが存在するか確認できませんでした。{}", e);
+                // セッションの確認は、新しいトランザクションで問い合わせる
+                tx.discard();
+                Ok(Err(UserLookupError(e)))
+            }
+        };
+    }
+    let user = async {
+        let tx = tx.get().await.map_err(|e| format!("{}", e))?;
+        PgUserRepository
+            .get_by_id(UserId::new(user_id), tx)
+            .await
+            .map_err(|e| format!("{}", e))
+    }
+    .await;
+    match user {
+        Ok(Some(user)) => {
+            if let Some(cache) = cache {
+                cache.insert(&user, now);
+            }
+            Ok(Ok(user))
+        }
+        Ok(None) => Err(MiddlewareError::Unauthorized),
+        Err(e) => {
+            tracing::warn!(user_id = %user_id, "ユーザーを取得できませんでした。{}", e);
+            // セッションの確認は、新しいトランザクションで問い合わせる
+            tx.discard();
+            Ok(Err(UserLookupError(e)))
+        }
+    }
+}
+
+/// データベースに記録されているリフレッシュトークンを、リフレッシュしたトークンで更新する。
+///
+/// セッションのリフレッシュトークンがデータベースに記録されていない場合は、セッションが失効したと
+/// 判断して、`401 Unauthorized`を返却する。
+/// データベースに問い合わせできなかった場合は、失効していないリフレッシュトークンをデータベースに記録できない
+/// ため、`503 Service Unavailable`を返却する。
+async fn update_refresh_token(
+    tx: &mut LazyTransaction<'_>,
+    session_data: &SessionData,
+    secret_key: &Secret<String>,
+) -> Result<(), MiddlewareError> {
+    let refresh_token = RefreshToken::from_session_data(session_data, secret_key)
+        .map_err(MiddlewareError::unexpected)?;
+    let result = async {
+        let started = tx
+            .get()
+            .await
+            .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        PgRefreshTokenRepository
+            .update(&refresh_token, started)
+            .await?;
+        tx.commit()
+            .await
+            .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))
+    }
+    .await;
+    match result {
+        Ok(_) => Ok(()),
+        Err(RefreshTokenRepositoryError::NotFoundError(_)) => Err(MiddlewareError::SessionExpired),
+        Err(e) => {
+            tracing::error!(
+                session_id = %session_data.session_id,
+                "データベースのリフレッシュトークンを更新できませんでした。{}",
+                e
+            );
+            Err(MiddlewareError::ServiceUnavailable)
+        }
+    }
+}
+
+/// 認証の結果
+enum Authentication {
+    /// 認証されていないリクエストを受け付ける場合に、セッションデータがなかったことを示す。
+    Anonymous,
+    /// 認証されたことを示す。
+    Authenticated(Box<Authenticated>),
+}
+
+/// 認証されたリクエストの情報
+struct Authenticated {
+    /// ユーザー。データベースに問い合わせできなかった場合は、そのエラー。
+    user: Result<User, UserLookupError>,
+    /// セッションデータ。トークンを更新する必要がある場合は、トークンを更新したセッションデータ。
+    session_data: SessionData,
+    /// トークンをリフレッシュする理由。トークンをリフレッシュしない場合は`None`。
+    refresh_reason: Option<RefreshReason>,
+    /// セッションクッキー設定。
+    session_cookie: SessionCookieSettings,
+}
+
+/// セッションデータとクッキーのトークンで、リクエストを認証する。
+///
+/// # Arguments
+///
+/// * `req` - リクエスト。
+/// * `optional` - 認証されていないリクエストを受け付けるか。
+///
+/// # Returns
+///
+/// 認証の結果。認証に失敗した場合は、失敗した理由を示すエラー。
+async fn authenticate(
+    req: &HttpRequest,
+    optional: bool,
+) -> Result<Authentication, MiddlewareError> {
+    // システム設定を取得
+    let settings = get_settings(req)?;
+    let Settings {
+        tokens,
+        session_cookie,
+        session_store,
+        ..
+    } = settings;
+    let session_cookie = session_cookie.to_owned();
+    tracing::info!("システム設定: {:?}", settings);
+    // データベースコネクションプールを取得
+    let pool = get_database_connection_pool(req)?;
+    tracing::info!("データベースコネクションプール: {:?}", pool);
+    // セッションデータを取得
+    let cipher = SessionDataCipher::from_app_data(req).map_err(MiddlewareError::unexpected)?;
+    let session = TypedSession::new(req.get_session(), cipher);
+    // セッションデータがない場合は、`401 Unauthorized`で応答
+    // ただし、認証されていないリクエストを受け付ける場合は、ユーザーを追加せずに処理を移譲
+    let mut session_data = match get_session_data(&session)? {
+        Some(session_data) => session_data,
+        None if optional => return Ok(Authentication::Anonymous),
+        None => return Err(MiddlewareError::Unauthorized),
+    };
+    tracing::info!("セッションデータ: {:?}", session_data);
+    // トークンを取得
+    let (access_token, refresh_token) = get_tokens(req);
+    // Redisに格納されているセッションデータと、クッキーに記録されていたトークンを評価
+    let result = inspect_token_by_session_data(
+        &session_data,
+        &access_token,
+        &refresh_token,
+        &tokens.secret_key,
+        tokens.sliding_renewal_duration(),
+    );
+    let refresh_reason = match result {
+        TokenValidation::Failure => return Err(MiddlewareError::Unauthorized),
+        // 他のユーザーのリフレッシュトークンが使用された場合は、セッションが攻撃されていると判断して、
+        // セッションを破棄して`401 Unauthorized`で応答
+        TokenValidation::SubjectMismatch => {
+            tracing::warn!(
+                session_id = %session_data.session_id,
+                "リフレッシュトークンの主体がセッションのユーザーと一致しないため、セッションを破棄します。"
+            );
+            session.purge();
+            return Err(MiddlewareError::Unauthorized);
+        }
+        // サイレントリフレッシュが無効な場合、アクセストークンの有効期限が切れているか欠落していれば、
+        // クライアントにリフレッシュAPIを呼び出すように`401 Unauthorized`で応答して、
+        // アクセストークンが有効期限内であれば、トークンをリフレッシュしない
+        TokenValidation::RequiredRefresh(
+            RefreshReason::AccessExpired | RefreshReason::AccessMissing,
+        ) if !tokens.silent_refresh_enabled => {
+            return Err(MiddlewareError::AccessTokenExpired);
+        }
+        TokenValidation::RequiredRefresh(_) if !tokens.silent_refresh_enabled => None,
+        TokenValidation::RequiredRefresh(reason) => Some(reason),
+        TokenValidation::Succeed => None,
+    };
+    // セッションデータに含まれているユーザーを取得して、ユーザーが存在しない場合は、トークンを
+    // リフレッシュせずに、`401 Unauthorized`で応答
+    // データベースの障害でユーザーを取得できなかった場合は、トークンのリフレッシュを継続して、
+    // ユーザーの代わりにエラーをハンドラに伝える
+    let cache = req
+        .app_data::<web::Data<UserCache>>()
+        .map(|cache| cache.as_ref());
+    // ユーザーの取得、セッションの確認及びリフレッシュトークンの更新は、同じトランザクションで問い合わせる
+    let mut tx = LazyTransaction::new(pool);
+    let user = get_user(&mut tx, cache, session_data.user_id).await?;
+    // ユーザーが無効になっている場合は、セッションを破棄して、システム設定のステータスコードで応答
+    if matches!(&user, Ok(user) if !user.is_active()) {
+        session.purge();
+        return Err(MiddlewareError::inactive_user(tokens.inactive_user_status));
+    }
+    // パスワードの変更や管理者による失効などで、セッションが失効している場合は、セッションを破棄して
+    // `401 Unauthorized`で応答
+    // セッションが失効しているか確認できない場合は、セッションを破棄せずに`503 Service Unavailable`で応答
+    if let Err(e) = ensure_session_registered(&mut tx, session_data.session_id).await {
+        if matches!(e, MiddlewareError::SessionExpired) {
+            session.purge();
+        }
+        return Err(e);
+    }
+    // パスワードの変更を要求されている場合は、パスワードの変更とログアウト以外へのアクセスを拒否
+    if matches!(&user, Ok(user) if user.must_change_password())
+        && !PASSWORD_CHANGE_ALLOWED_PATHS.contains(&req.path())
+    {
+        return Err(MiddlewareError::PasswordChangeRequired);
+    }
+    // トークンを更新する必要がある場合は、トークンを更新したセッションデータを作成
+    if let Some(reason) = refresh_reason {
+        // 1回のログインでリフレッシュできる回数の上限に達している場合は、セッションを破棄して再ログインを要求
+        if session_data.has_exhausted_refresh_chain(tokens.max_refresh_chain) {
+            tracing::info!(
+                session_id = %session_data.session_id,
+                refresh_count = session_data.refresh_count,
+                "リフレッシュ回数が上限に達したため、セッションを破棄します。"
+            );
+            session.purge();
+            return Err(MiddlewareError::RefreshChainExhausted);
+        }
+        record_refresh_reason(&session_data, reason);
+        session_data =
+            reissue_session_data(session_data, tokens).map_err(MiddlewareError::unexpected)?;
+        // データベースに記録されているリフレッシュトークンを更新
+        update_refresh_token(&mut tx, &session_data, &tokens.secret_key).await?;
+        // ハンドラが更新したセッションデータを記録できるように、ハンドラを呼び出す前にRedisにセッションデータを登録
+        session
+            .insert(&session_data)
+            .map_err(MiddlewareError::unexpected)?;
+    }
+
+    // トークンをリフレッシュしない場合は、前回の更新から間隔が経過しているときにのみ、セッションの
+    // 最終アクセス日時を更新して、Redisに記録されたセッションの有効期限を延長
+    let now = current_unix_epoch();
+    if refresh_reason.is_none()
+        && should_touch_session(&session_data, now, session_store.touch_interval())
+    {
+        session_data.last_accessed_at = now;
+        session
+            .insert(&session_data)
+            .map_err(MiddlewareError::unexpected)?;
+    }
+
+    Ok(Authentication::Authenticated(Box::new(Authenticated {
+        user,
+        session_data,
+        refresh_reason,
+        session_cookie,
+    })))
+}
+
+/// 更新したトークンをクッキーに記録するように、応答でブラウザに指示する。
+///
+/// # Arguments
+///
+/// * `response` - 応答。
+/// * `session_data` - トークンを更新したセッションデータ。
+/// * `session_cookie` - セッションクッキー設定。
+fn add_refreshed_tokens(
+    response: &mut HttpResponse,
+    session_data: &SessionData,
+    session_cookie: &SessionCookieSettings,
+) -> Result<(), MiddlewareError> {
+    add_session_data_cookies(
+        response,
+        session_data.access_token.expose(),
+        session_data.refresh_token.expose(),
+        session_cookie,
+    )
+    .map_err(MiddlewareError::unexpected)?;
+    // アクセストークンのフィンガープリントをヘッダーに追加
+    add_token_fingerprint_header(response, session_data.access_token.expose())
+        .map_err(MiddlewareError::unexpected)
+}
+
+/// 認証ミドルウェアの外で認証したリクエストの情報
+pub struct AuthenticatedRequest {
+    /// ユーザー。
+    user: User,
+    /// セッションデータ。
+    session_data: SessionData,
+    /// トークンを更新したか。
+    refreshed: bool,
+    /// セッションクッキー設定。
+    session_cookie: SessionCookieSettings,
+}
+
+impl AuthenticatedRequest {
+    /// 認証したユーザーを返却する。
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// 認証時にトークンを更新した場合は、更新したトークンをクッキーに記録するように、応答でブラウザに指示する。
+    ///
+    /// トークンを更新した場合に応答で指示しないと、ブラウザのクッキーのトークンがセッションデータと一致しなく
+    /// なるため、以降のリクエストが認証されない。
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - 応答。
+    pub fn add_refreshed_tokens(&self, response: &mut HttpResponse) -> Result<(), MiddlewareError> {
+        if self.refreshed {
+            add_refreshed_tokens(response, &self.session_data, &self.session_cookie)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 認証ミドルウェアを経由しないリクエストを、認証ミドルウェアと同じ方法で認証する。
+///
+/// WebSocketへのアップグレードなど、認証ミドルウェアでラップできないハンドラが、ハンドラ内でリクエストを
+/// 認証するために使用する。認証時にトークンを更新した場合は、ハンドラが`AuthenticatedRequest::add_refreshed_tokens`
+/// で応答にトークンを記録すること。
+///
+/// データベースの障害でユーザーを取得できなかった場合は、認証ミドルウェアと異なり、予期していないエラーを
+/// 返却する。
+///
+/// # Arguments
+///
+/// * `req` - リクエスト。
+///
+/// # Returns
+///
+/// 認証したリクエストの情報。認証に失敗した場合は、失敗した理由を示すエラー。
+pub async fn authenticate_request(
+    req: &HttpRequest,
+) -> Result<AuthenticatedRequest, MiddlewareError> {
+    let authenticated = match authenticate(req, false).await? {
+        Authentication::Anonymous => return Err(MiddlewareError::Unauthorized),
+        Authentication::Authenticated(authenticated) => authenticated,
+    };
+    let Authenticated {
+        user,
+        session_data,
+        refresh_reason,
+        session_cookie,
+    } = *authenticated;
+
+    Ok(AuthenticatedRequest {
+        user: user.map_err(MiddlewareError::unexpected)?,
+        session_data,
+        refreshed: refresh_reason.is_some(),
+        session_cookie,
+    })
+}
+
+/// 認証ミドルウェアがリクエストを処理するスパンを作成する。
+///
+/// ユーザーIDは、ユーザーを取得できたときに`record_user_id`で記録するため、認証されていないリクエストや、
+/// 認証に失敗したリクエストのスパンには記録されない。
+fn authentication_span() -> tracing::Span {
+    tracing::info_span!("JwtAuth", user_id = tracing::field::Empty)
+}
+
+/// 認証したユーザーのユーザーIDをスパンに記録する。
+///
+/// 後続のハンドラが出力するログにも、ユーザーIDが含まれるようになる。
+///
+/// # Arguments
+///
+/// * `span` - 認証ミドルウェアがリクエストを処理するスパン。
+/// * `user_id` - 認証したユーザーのユーザーID。
+fn record_user_id(span: &tracing::Span, user_id: Uuid) {
+    span.record("user_id", tracing::field::display(user_id));
 }
 
 // FIXME: 認証に失敗した場合、ブラウザにトークンを記録したクッキーを削除するように指示するように修正すること。
@@ -227,75 +1042,97 @@ where
         tracing::info!("JwtAuthMiddlewareが要求を受け取りました。");
 
         let service = Rc::clone(&self.service);
+        let optional = self.optional;
+        let span = authentication_span();
+        let request_span = span.clone();
 
-        #[allow(clippy::redundant_closure)]
-        Box::pin(async move {
-            // システム設定を取得
-            let settings = get_settings(&service_req)?;
-            let Settings {
-                tokens,
-                session_cookie,
-                ..
-            } = settings;
-            let session_cookie = session_cookie.to_owned();
-            tracing::info!("システム設定: {:?}", settings);
-            // データベースコネクションプールを取得
-            let pool = get_database_connection_pool(&service_req)?;
-            tracing::info!("データベースコネクションプール: {:?}", pool);
-            // セッションデータを取得
-            let session = TypedSession(service_req.get_session());
-            let session_data = get_session_data(&session)?;
-            // セッションデータがない場合は、`401 Unauthorized`で応答
-            if session_data.is_none() {
-                return Err(actix_web::error::ErrorUnauthorized("認証されていません。"));
-            }
-            let mut session_data = session_data.unwrap();
-            tracing::info!("セッションデータ: {:?}", session_data);
-            // トークンを取得
-            let (access_token, refresh_token) = get_tokens(&service_req);
-            // Redisに格納されているセッションデータと、クッキーに記録されていたトークンを評価
-            let result =
-                inspect_token_by_session_data(&session_data, &access_token, &refresh_token);
-            if result == TokenValidation::Failure {
-                return Err(actix_web::error::ErrorUnauthorized("認証されていません。"));
-            }
-            // トークンを更新する必要がある場合は、トークンを更新したセッションデータを作成
-            if result == TokenValidation::RequiredRefresh {
-                session_data = generate_session_data(session_data.user_id, tokens)
-                    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-            }
+        Box::pin(
+            async move {
+                // 後続のミドルウェアやハンドラが、トークンを更新したかを常に参照できるように、更新していない
+                // ことを記録して、トークンを更新した場合は上書き
+                service_req.extensions_mut().insert(RefreshOccurred(false));
+                // CORSのプリフライトリクエストは認証情報を含まないため、認証せずに処理を移譲
+                if service_req.method() == Method::OPTIONS {
+                    return service.call(service_req).await;
+                }
+                // APIキーを指定したリクエストは、セッションの代わりにAPIキーで認証して、ユーザー、所属する
+                // テナント及びAPIキーのスコープをリクエストに追加
+                // APIキーが漏洩した場合にアカウントを乗っ取られないように、スコープを宣言していないリソースや、
+                // APIキーにスコープが付与されていない場合は、APIキーで認証したリクエストを拒否
+                if let Some(api_key) = get_api_key(service_req.request()) {
+                    let (user, context) =
+                        match authenticate_api_key(service_req.request(), api_key).await {
+                            Ok(authenticated) => authenticated,
+                            Err(e) => return Ok(service_req.error_response(e)),
+                        };
+                    if let Err(e) = authorize_api_key(service_req.request(), &context) {
+                        return Ok(service_req.error_response(e));
+                    }
+                    record_user_id(&request_span, user.id().value());
+                    let tenant = TenantContext {
+                        tenant_id: user.tenant_id(),
+                    };
+                    service_req.extensions_mut().insert(tenant);
+                    service_req.extensions_mut().insert(user);
+                    service_req.extensions_mut().insert(context);
+                    return service.call(service_req).await;
+                }
+                // リクエストを認証して、認証に失敗した場合は、外側のミドルウェア（CORSなど）が応答を加工できる
+                // ように、エラーを応答に変換して返却
+                let authenticated = match authenticate(service_req.request(), optional).await {
+                    Ok(Authentication::Anonymous) => return service.call(service_req).await,
+                    Ok(Authentication::Authenticated(authenticated)) => authenticated,
+                    Err(e) => return Ok(service_req.error_response(e)),
+                };
+                let Authenticated {
+                    user,
+                    session_data,
+                    refresh_reason,
+                    session_cookie,
+                } = *authenticated;
 
-            // リクエストにユーザーをデータとして追加
-            let user = get_user(pool, session_data.user_id).await?;
-            service_req.extensions_mut().insert(user);
-
-            // 後続のミドルウェアなどにリクエストの処理を移譲
-            let future = service.call(service_req);
-
-            // リクエストの処理が完了した後、リクエストの処理を移譲した先から返却されたフューチャーを、
-            // レスポンスとして返却
-            let mut resp = future.await?;
-
-            // トークンを更新する必要がある場合は、トークンを更新してRedisに記録するとともに、
-            // ブラウザにトークンをクッキーに記録するように指示
-            if result == TokenValidation::RequiredRefresh {
-                // Redisにセッションデータを登録
-                session
-                    .insert(&session_data)
-                    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-                // ブラウザにトークンをクッキーに記録するように指示
-                let response = resp.response_mut();
-                add_session_data_cookies(
-                    response,
-                    &session_data.access_token,
-                    &session_data.refresh_token,
-                    &session_cookie,
-                );
-            }
+                // リクエストにユーザーと所属するテナントをデータとして追加して、ユーザーを取得できなかった場合は、
+                // 代わりにエラーを追加
+                match user {
+                    Ok(user) => {
+                        record_user_id(&request_span, user.id().value());
+                        let tenant = TenantContext {
+                            tenant_id: user.tenant_id(),
+                        };
+                        service_req.extensions_mut().insert(tenant);
+                        service_req.extensions_mut().insert(user);
+                    }
+                    Err(e) => {
+                        service_req.extensions_mut().insert(e);
+                    }
+                }
+                // セッションの情報と、トークンを更新したかをリクエストに追加
+                service_req.extensions_mut().insert(SessionContext {
+                    session_id: session_data.session_id,
+                    access_expiration: session_data.access_expiration,
+                    refresh_expiration: session_data.refresh_expiration,
+                });
+                service_req
+                    .extensions_mut()
+                    .insert(RefreshOccurred(refresh_reason.is_some()));
 
-            tracing::info!("JwtAuthMiddlewareが応答を返しました。");
-            Ok(resp)
-        })
+                // 後続のミドルウェアなどにリクエストの処理を移譲
+                let future = service.call(service_req);
+
+                // リクエストの処理が完了した後、リクエストの処理を移譲した先から返却されたフューチャーを、
+                // レスポンスとして返却
+                let mut resp = future.await?;
+
+                // トークンを更新した場合は、ブラウザにトークンをクッキーに記録するように指示
+                if refresh_reason.is_some() {
+                    add_refreshed_tokens(resp.response_mut(), &session_data, &session_cookie)?;
+                }
+
+                tracing::info!("JwtAuthMiddlewareが応答を返しました。");
+                Ok(resp)
+            }
+            .instrument(span),
+        )
     }
 }
 
@@ -303,84 +1140,760 @@ where
 mod tests {
     use super::*;
 
+    use actix_session::{storage::CookieSessionStore, Session, SessionMiddleware};
+    use actix_web::cookie::{time::Duration, Cookie, Key, SameSite};
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{http::StatusCode, App, HttpResponse};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+
+    use configurations::{
+        session::SESSION_DATA_VERSION,
+        tokens::{generate_jwt_pair, RedactedToken},
+        AdminSettings, AppEnvironment, DatabaseSettings, SessionCookieSettings,
+        SessionStoreSettings, SignupSettings, TokenMode, TokensSettings, TotpSettings,
+        UserCacheSettings, WebAppSettings, WebAuthnSettings, WebhookSettings,
+    };
+
+    /// テスト用のシステム設定を構築する。
+    fn test_settings() -> Settings {
+        Settings {
+            rust_log: "info".to_owned(),
+            web_app: WebAppSettings {
+                host: "localhost".to_owned(),
+                port: 0,
+                json_payload_limit: 16 * 1024,
+                json_content_type_required: true,
+                tls: None,
+                cors_allowed_origins: vec![],
+                trusted_proxies: vec![],
+                environment: AppEnvironment::Development,
+            },
+            session_cookie: SessionCookieSettings {
+                session_id_cookie_name: "session_id".to_owned(),
+                secure: false,
+                same_site: SameSite::Lax,
+                max_age_seconds: None,
+            },
+            tokens: TokensSettings {
+                secret_key: Secret::new("some-secret".to_owned()),
+                access_token_duration: Duration::seconds(300),
+                refresh_token_duration: Duration::seconds(3600),
+                silent_refresh_enabled: true,
+                sliding_renewal_duration: Duration::seconds(0),
+                inactive_user_status: StatusCode::FORBIDDEN,
+                logout_on_password_change: true,
+                token_mode: TokenMode::Jwt,
+                audiences: vec![],
+                refresh_token_cleanup_interval: Duration::seconds(0),
+                reauthentication_window: Duration::seconds(600),
+                max_refresh_chain: 0,
+            },
+            session_store: SessionStoreSettings {
+                uri: Secret::new("redis://127.0.0.1:6379".to_owned()),
+                key: Secret::new("x".repeat(64)),
+                legacy_keys: vec![],
+                touch_interval: Duration::seconds(60),
+                key_prefix: String::new(),
+                encryption_key: None,
+                connect_timeout: Duration::seconds(5),
+                command_timeout: Duration::seconds(3),
+                idempotency_key_ttl: Duration::seconds(600),
+            },
+            db: DatabaseSettings {
+                username: "postgres".to_owned(),
+                password: Secret::new("password".to_owned()),
+                host: "localhost".to_owned(),
+                port: 5432,
+                database_name: "postgres".to_owned(),
+                auto_migrate: false,
+            },
+            webauthn: WebAuthnSettings {
+                rp_id: "localhost".to_owned(),
+                rp_name: "jwt-auth-example".to_owned(),
+                origin: "http://localhost:8000".to_owned(),
+            },
+            totp: TotpSettings {
+                issuer: "jwt-auth-example".to_owned(),
+            },
+            admin: AdminSettings { api_key: None },
+            signup: SignupSettings {
+                enabled: true,
+                privacy_mode: false,
+                disposable_email_domains: Default::default(),
+            },
+            user_cache: UserCacheSettings {
+                enabled: false,
+                capacity: 1024,
+                ttl: Duration::seconds(30),
+            },
+            webhook: WebhookSettings {
+                url: None,
+                secret: None,
+                max_attempts: 3,
+                retry_interval: Duration::seconds(1),
+                timeout: Duration::seconds(5),
+            },
+        }
+    }
+
+    /// 任意のJSONをセッションデータとしてセッションに登録するハンドラ。
+    async fn set_session_data(
+        session: Session,
+        body: web::Json<serde_json::Value>,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        session.insert("session_data", body.into_inner())?;
+
+        Ok(HttpResponse::Ok().finish())
+    }
+
+    /// ミドルウェアが返却した結果から、クライアントが受け取るステータスコードを取得する。
+    fn status_of(result: Result<ServiceResponse, actix_web::Error>) -> StatusCode {
+        match result {
+            Ok(resp) => resp.status(),
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    /// セッションに任意のJSONを登録した後、任意のトークンを記録したクッキーで保護されたリソースに
+    /// アクセスしたときのステータスコードを返却する。
+    async fn call_protected_with(
+        session_data: Option<serde_json::Value>,
+        access_token: &str,
+        refresh_token: &str,
+    ) -> StatusCode {
+        // データベースに接続しない遅延接続のコネクションプール
+        let pool = PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new());
+        let app = init_service(
+            App::new()
+                .wrap(
+                    SessionMiddleware::builder(CookieSessionStore::default(), Key::generate())
+                        .cookie_name("session_id".to_owned())
+                        .cookie_secure(false)
+                        .build(),
+                )
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool))
+                .route("/session_data", web::post().to(set_session_data))
+                .service(
+                    web::scope("")
+                        .wrap(JwtAuth)
+                        .route("/protected_resource", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        // セッションを登録
+        let mut session_cookie = None;
+        if let Some(session_data) = session_data {
+            let req = TestRequest::post()
+                .uri("/session_data")
+                .set_json(session_data)
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            session_cookie = resp
+                .response()
+                .cookies()
+                .find(|cookie| cookie.name() == "session_id")
+                .map(|cookie| cookie.into_owned());
+        }
+
+        // 保護されたリソースにアクセス
+        let mut req = TestRequest::get()
+            .uri("/protected_resource")
+            .cookie(Cookie::new(
+                ACCESS_TOKEN_COOKIE_NAME,
+                access_token.to_owned(),
+            ))
+            .cookie(Cookie::new(
+                REFRESH_TOKEN_COOKIE_NAME,
+                refresh_token.to_owned(),
+            ));
+        if let Some(cookie) = session_cookie {
+            req = req.cookie(cookie);
+        }
+
+        status_of(app.call(req.to_request()).await)
+    }
+
+    /// クッキーに使用できる文字で構成された、ランダムな長さの文字列を生成する。
+    fn random_cookie_value(rng: &mut StdRng, max_len: usize) -> String {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_.";
+        let len = rng.gen_range(0..=max_len);
+        (0..len)
+            .map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char)
+            .collect()
+    }
+
+    /// セッションデータがない場合に、パニックせずに`401 Unauthorized`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_rejects_request_without_session() {
+        let status = call_protected_with(None, "foo", "bar").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// 壊れたセッションデータの場合に、パニックせずに`401 Unauthorized`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_does_not_panic_with_broken_session_data() {
+        let broken_values = vec![
+            serde_json::json!("garbage"),
+            serde_json::json!(0),
+            serde_json::json!([1, 2, 3]),
+            serde_json::json!({}),
+            serde_json::json!({ "user_id": 1, "access_token": null }),
+            serde_json::json!({
+                "session_id": "not-uuid",
+                "user_id": "not-uuid",
+                "access_token": "foo",
+                "access_expiration": -1,
+                "refresh_token": "bar",
+                "refresh_expiration": "tomorrow",
+            }),
+        ];
+        for value in broken_values {
+            let status = call_protected_with(Some(value.clone()), "foo", "bar").await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", value);
+        }
+    }
+
+    /// 対応していないバージョンのセッションデータの場合に、`401 Unauthorized`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_rejects_unsupported_session_data_version() {
+        let now = current_unix_epoch();
+        let session_data = serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": now + 300,
+            "refresh_token": "bar",
+            "refresh_expiration": now + 1800,
+            "version": SESSION_DATA_VERSION + 1,
+        });
+        let status = call_protected_with(Some(session_data), "foo", "bar").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// リフレッシュトークンが他のユーザーのJWTの場合に、`401 Unauthorized`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_rejects_refresh_token_of_another_user() {
+        let now = current_unix_epoch();
+        let secret_key = &test_settings().tokens.secret_key;
+        let (_, refresh_token) =
+            generate_jwt_pair(Uuid::new_v4(), secret_key, now + 300, now + 1800, None).unwrap();
+        let session_id = Uuid::new_v4();
+        let session_data = serde_json::json!({
+            "session_id": session_id,
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": now + 300,
+            "refresh_token": refresh_token,
+            "refresh_expiration": now + 1800,
+        });
+        let status = call_protected_with(Some(session_data), "foo", &refresh_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// 巨大なクッキーを受け取った場合に、パニックせずに`401 Unauthorized`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_does_not_panic_with_huge_cookies() {
+        let now = current_unix_epoch();
+        let session_data = serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": now + 300,
+            "refresh_token": "bar",
+            "refresh_expiration": now + 1800,
+        });
+        let huge = "x".repeat(32 * 1024);
+        let status = call_protected_with(Some(session_data), &huge, &huge).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// データベースに接続できない場合は、セッションが失効しているか確認できないため、トークンをリフレッシュ
+    /// せずに`503 Service Unavailable`で応答することを確認するテスト
+    #[actix_web::test]
+    async fn middleware_rejects_session_while_database_is_unavailable() {
+        // 接続できないポートを指定したコネクションプール
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_secs(1))
+            .connect_lazy_with(PgConnectOptions::new().port(1));
+        let app = init_service(
+            App::new()
+                .wrap(
+                    SessionMiddleware::builder(CookieSessionStore::default(), Key::generate())
+                        .cookie_name("session_id".to_owned())
+                        .cookie_secure(false)
+                        .build(),
+                )
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool))
+                .route("/session_data", web::post().to(set_session_data))
+                .service(
+                    web::scope("")
+                        .wrap(JwtAuth)
+                        .route("/protected_resource", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        // アクセストークンの有効期限が切れている場合も、有効期限内の場合も、セッションを受け付けない
+        let now = current_unix_epoch();
+        for access_expiration in [now - 1, now + 300] {
+            let session_data = serde_json::json!({
+                "session_id": Uuid::new_v4(),
+                "user_id": Uuid::new_v4(),
+                "access_token": "foo",
+                "access_expiration": access_expiration,
+                "refresh_token": "bar",
+                "refresh_expiration": now + 1800,
+                "generation": SESSION_GENERATION,
+                "last_accessed_at": now,
+            });
+            let req = TestRequest::post()
+                .uri("/session_data")
+                .set_json(session_data)
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let session_cookie = resp
+                .response()
+                .cookies()
+                .find(|cookie| cookie.name() == "session_id")
+                .map(|cookie| cookie.into_owned())
+                .unwrap();
+            let req = TestRequest::get()
+                .uri("/protected_resource")
+                .cookie(session_cookie)
+                .cookie(Cookie::new(ACCESS_TOKEN_COOKIE_NAME, "foo"))
+                .cookie(Cookie::new(REFRESH_TOKEN_COOKIE_NAME, "bar"))
+                .to_request();
+            let resp = app.call(req).await;
+            let (status, cookies) = match resp {
+                Ok(resp) => (
+                    resp.status(),
+                    resp.response()
+                        .cookies()
+                        .map(|cookie| cookie.name().to_owned())
+                        .collect::<Vec<_>>(),
+                ),
+                Err(e) => (e.as_response_error().status_code(), vec![]),
+            };
+            assert_eq!(
+                status,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "{}",
+                access_expiration
+            );
+            // リフレッシュしたトークンをクッキーに記録しない
+            assert!(!cookies.iter().any(|name| name == ACCESS_TOKEN_COOKIE_NAME));
+            assert!(!cookies.iter().any(|name| name == REFRESH_TOKEN_COOKIE_NAME));
+        }
+    }
+
+    /// 認証ミドルウェアがリクエストに追加した、トークンを更新したかを応答するハンドラ。
+    async fn refresh_occurred(refresh_occurred: web::ReqData<RefreshOccurred>) -> HttpResponse {
+        HttpResponse::Ok().body(refresh_occurred.0.to_string())
+    }
+
+    /// 認証されていないリクエストに、`RefreshOccurred(false)`が追加されることを確認するテスト
+    ///
+    /// 認証されたリクエストでトークンを更新したかは、セッションの失効を確認するためにデータベースが必要なため、
+    /// 統合テストで確認する。
+    #[actix_web::test]
+    async fn middleware_inserts_refresh_occurred_for_anonymous_request() {
+        // セッションデータがないリクエストはデータベースに問い合わせないため、接続できないポートを指定した
+        // コネクションプール
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_secs(1))
+            .connect_lazy_with(PgConnectOptions::new().port(1));
+        let app = init_service(
+            App::new()
+                .wrap(
+                    SessionMiddleware::builder(CookieSessionStore::default(), Key::generate())
+                        .cookie_name("session_id".to_owned())
+                        .cookie_secure(false)
+                        .build(),
+                )
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool))
+                .service(
+                    web::scope("/optional")
+                        .wrap(OptionalJwtAuth)
+                        .route("/refresh_occurred", web::get().to(refresh_occurred)),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/optional/refresh_occurred")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = actix_web::test::read_body(resp).await;
+        assert_eq!(body, "false");
+    }
+
+    /// ランダムなトークンとセッションデータの組み合わせで、ミドルウェアがパニックしないことを確認するテスト
+    #[actix_web::test]
+    async fn middleware_does_not_panic_with_random_inputs() {
+        let mut rng = StdRng::seed_from_u64(2079);
+        let now = current_unix_epoch();
+        for _ in 0..50 {
+            let access_token = random_cookie_value(&mut rng, 4096);
+            let refresh_token = random_cookie_value(&mut rng, 4096);
+            let session_data = match rng.gen_range(0..3) {
+                0 => None,
+                1 => Some(serde_json::json!(random_cookie_value(&mut rng, 256))),
+                _ => Some(serde_json::json!({
+                    "session_id": Uuid::new_v4(),
+                    "user_id": Uuid::new_v4(),
+                    "access_token": random_cookie_value(&mut rng, 64),
+                    "access_expiration": now + rng.gen_range(0..600),
+                    "refresh_token": random_cookie_value(&mut rng, 64),
+                    "refresh_expiration": now + rng.gen_range(600..3600),
+                })),
+            };
+            let status = call_protected_with(session_data, &access_token, &refresh_token).await;
+            assert!(
+                status.is_client_error() || status.is_server_error(),
+                "{}",
+                status
+            );
+        }
+    }
+
     #[test]
     fn inspect_token_by_session_data_succeed() {
-        let now = current_unix_epoch();
         let access_token = "foo";
         let refresh_token = "bar";
-        let session_data = SessionData {
-            user_id: Uuid::new_v4(),
-            access_token: access_token.to_owned(),
-            access_expiration: now + 300,
-            refresh_token: refresh_token.to_owned(),
-            refresh_expiration: now + 1800,
-        };
-        let result = inspect_token_by_session_data(&session_data, access_token, refresh_token);
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
         assert_eq!(result, TokenValidation::Succeed);
     }
 
     #[test]
     fn inspect_token_by_session_data_required_refresh() {
-        let now = current_unix_epoch();
         let access_token = "foo";
         let refresh_token = "bar";
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), -1, 1800);
+        session_data.access_token = RedactedToken::new("baz");
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
+        assert_eq!(
+            result,
+            TokenValidation::RequiredRefresh(RefreshReason::AccessExpired)
+        );
+    }
 
-        let session_data = SessionData {
-            user_id: Uuid::new_v4(),
-            access_token: "baz".to_owned(),
-            access_expiration: now - 1,
-            refresh_token: refresh_token.to_owned(),
-            refresh_expiration: now + 1800,
-        };
-        let result = inspect_token_by_session_data(&session_data, access_token, refresh_token);
-        assert_eq!(result, TokenValidation::RequiredRefresh);
+    /// アクセストークンの有効期限内でも、クッキーにアクセストークンが記録されておらず、リフレッシュトークンが
+    /// 一致する場合は`リフレッシュ要求`を返却することを確認するテスト
+    #[test]
+    fn inspect_token_by_session_data_required_refresh_for_missing_access_token() {
+        let refresh_token = "bar";
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        let result = inspect_token_by_session_data(
+            &session_data,
+            "",
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
+        assert_eq!(
+            result,
+            TokenValidation::RequiredRefresh(RefreshReason::AccessMissing)
+        );
+        // リフレッシュトークンが一致しない場合は`失敗`
+        let result = inspect_token_by_session_data(
+            &session_data,
+            "",
+            "baz",
+            &test_settings().tokens.secret_key,
+            0,
+        );
+        assert_eq!(result, TokenValidation::Failure);
     }
 
     #[test]
     fn inspect_token_by_session_data_failure_for_refresh_token_expiration() {
-        let now = current_unix_epoch();
         let access_token = "foo";
         let refresh_token = "bar";
-        let session_data = SessionData {
-            user_id: Uuid::new_v4(),
-            access_token: access_token.to_owned(),
-            access_expiration: now + 300,
-            refresh_token: refresh_token.to_owned(),
-            refresh_expiration: now - 1,
-        };
-        let result = inspect_token_by_session_data(&session_data, access_token, refresh_token);
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, -1);
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
         assert_eq!(result, TokenValidation::Failure);
     }
 
     #[test]
     fn inspect_token_by_session_data_failure_for_access_token() {
-        let now = current_unix_epoch();
         let access_token = "foo";
         let refresh_token = "bar";
-        let session_data = SessionData {
-            user_id: Uuid::new_v4(),
-            access_token: "baz".to_owned(),
-            access_expiration: now + 300,
-            refresh_token: refresh_token.to_owned(),
-            refresh_expiration: now + 1800,
-        };
-        let result = inspect_token_by_session_data(&session_data, access_token, refresh_token);
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        session_data.access_token = RedactedToken::new("baz");
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
         assert_eq!(result, TokenValidation::Failure);
     }
 
     #[test]
     fn inspect_token_by_session_data_failure_for_refresh_token() {
-        let now = current_unix_epoch();
         let access_token = "foo";
         let refresh_token = "bar";
-        let session_data = SessionData {
-            user_id: Uuid::new_v4(),
-            access_token: access_token.to_owned(),
-            access_expiration: now - 1,
-            refresh_token: "baz".to_owned(),
-            refresh_expiration: now + 1800,
-        };
-        let result = inspect_token_by_session_data(&session_data, access_token, refresh_token);
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), -1, 1800);
+        session_data.refresh_token = RedactedToken::new("baz");
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
         assert_eq!(result, TokenValidation::Failure);
     }
+
+    /// リフレッシュトークンが他のユーザーのJWTの場合は、トークンが一致していても`主体不一致`を返却することを
+    /// 確認するテスト
+    #[test]
+    fn inspect_token_by_session_data_subject_mismatch() {
+        let now = current_unix_epoch();
+        let secret_key = test_settings().tokens.secret_key;
+        let (access_token, refresh_token) =
+            generate_jwt_pair(Uuid::new_v4(), &secret_key, now + 300, now + 1800, None).unwrap();
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        session_data.access_token = RedactedToken::new(access_token.clone());
+        session_data.refresh_token = RedactedToken::new(refresh_token.clone());
+        let result = inspect_token_by_session_data(
+            &session_data,
+            &access_token,
+            &refresh_token,
+            &secret_key,
+            0,
+        );
+        assert_eq!(result, TokenValidation::SubjectMismatch);
+
+        // 主体が一致する場合は`成功`を返却
+        let claim = get_claim_from_jwt(&refresh_token, &secret_key, &[]).unwrap();
+        session_data.user_id = claim.user_id;
+        let result = inspect_token_by_session_data(
+            &session_data,
+            &access_token,
+            &refresh_token,
+            &secret_key,
+            0,
+        );
+        assert_eq!(result, TokenValidation::Succeed);
+    }
+
+    #[test]
+    fn inspect_token_by_session_data_required_refresh_for_generation_bump() {
+        let access_token = "foo";
+        let refresh_token = "bar";
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        session_data.generation = SESSION_GENERATION - 1;
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
+        assert_eq!(
+            result,
+            TokenValidation::RequiredRefresh(RefreshReason::GenerationBump)
+        );
+    }
+
+    #[test]
+    fn inspect_token_by_session_data_required_refresh_for_sliding_renewal() {
+        let access_token = "foo";
+        let refresh_token = "bar";
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 600);
+        // リフレッシュトークンの残りの有効期間がスライディング延長する期間以下の場合
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            600,
+        );
+        assert_eq!(
+            result,
+            TokenValidation::RequiredRefresh(RefreshReason::SlidingRenewal)
+        );
+        // リフレッシュトークンの残りの有効期間がスライディング延長する期間より長い場合
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            300,
+        );
+        assert_eq!(result, TokenValidation::Succeed);
+        // スライディング延長しない場合
+        let result = inspect_token_by_session_data(
+            &session_data,
+            access_token,
+            refresh_token,
+            &test_settings().tokens.secret_key,
+            0,
+        );
+        assert_eq!(result, TokenValidation::Succeed);
+    }
+
+    /// ログを記録するバッファ
+    #[derive(Clone, Default)]
+    struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogBuffer {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn record_refresh_reason_logs_reason() {
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        let reasons = [
+            RefreshReason::AccessExpired,
+            RefreshReason::AccessMissing,
+            RefreshReason::SlidingRenewal,
+            RefreshReason::GenerationBump,
+        ];
+        for reason in reasons {
+            let buffer = LogBuffer::default();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(buffer.clone())
+                .with_ansi(false)
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                record_refresh_reason(&session_data, reason)
+            });
+            let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+            assert!(
+                log.contains(&format!("reason={}", reason.as_str())),
+                "{}",
+                log
+            );
+            assert!(
+                log.contains(&format!("session_id={}", session_data.session_id)),
+                "{}",
+                log
+            );
+        }
+    }
+
+    /// ユーザーIDを記録した認証ミドルウェアのスパン内で出力したログに、ユーザーIDが含まれることを確認するテスト
+    #[test]
+    fn authentication_span_records_user_id() {
+        let user_id = Uuid::new_v4();
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let span = authentication_span();
+            // ユーザーを取得する前のログには、ユーザーIDが含まれない
+            span.in_scope(|| tracing::info!("before"));
+            record_user_id(&span, user_id);
+            // ハンドラが出力したログ
+            span.in_scope(|| tracing::info!("handler"));
+        });
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", log);
+        assert!(!lines[0].contains("user_id="), "{}", log);
+        assert!(
+            lines[1].contains(&format!("JwtAuth{{user_id={}}}", user_id)),
+            "{}",
+            log
+        );
+    }
+
+    /// 認証に失敗したリクエストのログに、ユーザーIDが記録されないことを確認するテスト
+    #[actix_web::test]
+    async fn authentication_span_omits_user_id_for_failed_requests() {
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let now = current_unix_epoch();
+        let session_data = serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "access_token": "foo",
+            "access_expiration": now + 300,
+            "refresh_token": "bar",
+            "refresh_expiration": now + 1800,
+        });
+        let status = call_protected_with(Some(session_data), "baz", "bar").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("JwtAuth"), "{}", log);
+        assert!(!log.contains("user_id="), "{}", log);
+    }
+
+    /// 頻繁にアクセスしても、セッションの最終アクセス日時の更新が一定間隔に制限されることを確認するテスト
+    #[test]
+    fn should_touch_session_throttles_updates() {
+        let mut session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        let start = session_data.last_accessed_at;
+        // 5分間、10秒ごとにアクセス
+        let mut touched = vec![];
+        for elapsed in (10..=300).step_by(10) {
+            let now = start + elapsed;
+            if should_touch_session(&session_data, now, 60) {
+                session_data.last_accessed_at = now;
+                touched.push(elapsed);
+            }
+        }
+        assert_eq!(touched, vec![60, 120, 180, 240, 300]);
+    }
+
+    /// 間隔が`0`の場合は、アクセスのたびにセッションの最終アクセス日時を更新することを確認するテスト
+    #[test]
+    fn should_touch_session_without_interval() {
+        let session_data = SessionData::for_test(Uuid::new_v4(), 300, 1800);
+        let now = session_data.last_accessed_at;
+        assert!(should_touch_session(&session_data, now, 0));
+        assert!(!should_touch_session(&session_data, now, 1));
+    }
 }
@@ -0,0 +1,190 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{HttpMessage, HttpRequest};
+
+use domains::models::users::{User, UserRole};
+
+use crate::errors::MiddlewareError;
+use crate::UserLookupError;
+
+/// 役割制限ミドルウェア
+///
+/// 認証ミドルウェアが認証したユーザーが、リソースに必要な役割を持っているか確認して、持っていない場合は
+/// `403 Forbidden`で応答する。
+///
+/// 認証ミドルウェアがリクエストに追加したユーザーを参照するため、認証ミドルウェアより内側で処理する。
+/// ユーザーが追加されていない場合は`401 Unauthorized`で、データベースの障害などでユーザーを取得できなかった
+/// 場合は、役割を確認できないため`500 Internal Server Error`で応答する。
+pub struct RequireRole {
+    /// 必要な役割。
+    role: UserRole,
+}
+
+/// 認証されたユーザーが、必要な役割を持っているか確認する。
+///
+/// # Arguments
+///
+/// * `req` - HTTPリクエスト。
+/// * `role` - 必要な役割。
+///
+/// # Returns
+///
+/// ユーザーが必要な役割を持っている場合は`()`。
+fn authorize_role(req: &HttpRequest, role: UserRole) -> Result<(), MiddlewareError> {
+    let extensions = req.extensions();
+    let user = match extensions.get::<User>() {
+        Some(user) => user,
+        None => {
+            return Err(match extensions.get::<UserLookupError>() {
+                Some(e) => MiddlewareError::unexpected(e),
+                None => MiddlewareError::Unauthorized,
+            })
+        }
+    };
+    if user.role() != role {
+        tracing::warn!(
+            user_id = %user.id().value(),
+            required_role = role.as_str(),
+            "必要な役割を持っていないユーザーがリソースにアクセスしました。"
+        );
+        return Err(MiddlewareError::InsufficientRole);
+    }
+
+    Ok(())
+}
+
+impl RequireRole {
+    /// 役割制限ミドルウェアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - ユーザーが持っている必要がある役割。
+    ///
+    /// # Returns
+    ///
+    /// 役割制限ミドルウェアインスタンス。
+    pub fn new(role: UserRole) -> Self {
+        Self { role }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = actix_web::Error;
+    type Transform = RequireRoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service: Rc::new(service),
+            role: self.role,
+        }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: Rc<S>,
+    role: UserRole,
+}
+
+impl<S> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // 外側のミドルウェアが応答を加工できるように、エラーを応答に変換して返却
+        if let Err(e) = authorize_role(req.request(), self.role) {
+            return Box::pin(ready(Ok(req.error_response(e))));
+        }
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move { service.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use domains::models::{
+        users::{HashedPassword, ProfileVisibility, UserId, UserName, DEFAULT_TENANT_ID},
+        EmailAddress,
+    };
+
+    /// 指定した役割のユーザーで認証したリクエストを模倣するアプリで、管理者の役割が必要なリソースを呼び出す。
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - 認証したユーザーの役割。ユーザーが認証されていない場合は`None`。
+    async fn call_admin_resource(role: Option<UserRole>) -> StatusCode {
+        let user = role.map(|role| {
+            User::new(
+                UserId::default(),
+                DEFAULT_TENANT_ID,
+                UserName::new("taro").unwrap(),
+                EmailAddress::new("taro@example.com").unwrap(),
+                HashedPassword::new_unchecked("hashed".to_owned()),
+                true,
+                None,
+                ProfileVisibility::default(),
+                None,
+                false,
+                role,
+                None,
+                None,
+            )
+        });
+        let resource = web::resource("/")
+            .wrap(RequireRole::new(UserRole::Admin))
+            .wrap_fn(move |req, srv| {
+                if let Some(user) = user.clone() {
+                    req.extensions_mut().insert(user);
+                }
+                srv.call(req)
+            })
+            .route(web::get().to(HttpResponse::Ok));
+        let app = init_service(App::new().service(resource)).await;
+
+        call_service(&app, TestRequest::get().uri("/").to_request())
+            .await
+            .status()
+    }
+
+    /// 必要な役割を持つユーザーのリクエストを許可することを確認するテスト
+    #[actix_web::test]
+    async fn user_with_role_is_allowed() {
+        assert_eq!(
+            call_admin_resource(Some(UserRole::Admin)).await,
+            StatusCode::OK
+        );
+    }
+
+    /// 必要な役割を持たないユーザーのリクエストを`403 Forbidden`で、ユーザーが認証されていないリクエストを
+    /// `401 Unauthorized`で拒否することを確認するテスト
+    #[actix_web::test]
+    async fn user_without_role_is_rejected() {
+        assert_eq!(
+            call_admin_resource(Some(UserRole::User)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(call_admin_resource(None).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,193 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{HttpMessage, HttpRequest};
+
+use crate::errors::MiddlewareError;
+use crate::ApiKeyContext;
+
+/// スコープ制限ミドルウェア
+///
+/// APIキーで認証したリクエストを受け付けるリソースで、APIキーに必要なスコープを宣言する。認証ミドルウェアは、
+/// スコープを宣言していないリソースへのAPIキーで認証したリクエストを`403 Forbidden`で拒否して、スコープを宣言した
+/// リソースでは、APIキーにスコープが付与されていない場合に`403 Forbidden`で応答する。
+///
+/// セッションで認証したリクエストや、認証されていないリクエストはAPIキーのスコープを持たないため、スコープを
+/// 確認しない。セッションで認証したユーザーは、自身のすべてのリソースにアクセスできるため、APIキーのスコープは、
+/// APIキーで許可する操作をユーザーの権限の範囲内で制限するために使用する。
+///
+/// 認証ミドルウェアがAPIキーを認証する前にスコープを宣言するため、認証ミドルウェアより外側で処理する。
+/// 認証ミドルウェアより内側で処理した場合は、スコープを宣言していないリソースとして、APIキーで認証した
+/// リクエストを拒否する。
+pub struct RequireScope {
+    /// 必要なスコープ。
+    scope: &'static str,
+}
+
+/// リソースが宣言した、APIキーに必要なスコープ
+///
+/// スコープ制限ミドルウェアが、リクエストデータとして追加する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RequiredScope(&'static str);
+
+/// APIキーで認証したリクエストが、リソースにアクセスできるか確認する。
+///
+/// # Arguments
+///
+/// * `req` - HTTPリクエスト。
+/// * `context` - APIキーで認証したリクエストの情報。
+///
+/// # Returns
+///
+/// リソースがスコープを宣言していない場合は、APIキーでアクセスできないことを示すエラー。APIキーにスコープが
+/// 付与されていない場合は、スコープが不足していることを示すエラー。
+pub(crate) fn authorize_api_key(
+    req: &HttpRequest,
+    context: &ApiKeyContext,
+) -> Result<(), MiddlewareError> {
+    let scope = req
+        .extensions()
+        .get::<RequiredScope>()
+        .map(|required| required.0)
+        .ok_or(MiddlewareError::ApiKeyNotAllowed)?;
+    if !context.has_scope(scope) {
+        return Err(MiddlewareError::InsufficientScope(scope));
+    }
+
+    Ok(())
+}
+
+impl RequireScope {
+    /// スコープ制限ミドルウェアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - APIキーに付与されている必要があるスコープ。
+    ///
+    /// # Returns
+    ///
+    /// スコープ制限ミドルウェアインスタンス。
+    pub fn new(scope: &'static str) -> Self {
+        Self { scope }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for RequireScope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = actix_web::Error;
+    type Transform = RequireScopeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireScopeMiddleware {
+            service: Rc::new(service),
+            scope: self.scope,
+        }))
+    }
+}
+
+pub struct RequireScopeMiddleware<S> {
+    service: Rc<S>,
+    scope: &'static str,
+}
+
+impl<S> Service<ServiceRequest> for RequireScopeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let scope = self.scope;
+        // 認証ミドルウェアが参照できるように、APIキーに必要なスコープをリクエストに追加
+        req.extensions_mut().insert(RequiredScope(scope));
+
+        Box::pin(async move { service.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use uuid::Uuid;
+
+    /// 指定したスコープを付与したAPIキーで認証したリクエストを模倣するアプリで、リソースを呼び出す。
+    ///
+    /// 認証ミドルウェアと同様に、APIキーで認証したリクエストがリソースにアクセスできるか確認する。
+    ///
+    /// # Arguments
+    ///
+    /// * `declared` - リソースがスコープを宣言するか。
+    /// * `scopes` - APIキーのスコープ。
+    async fn call_resource_with_api_key(declared: bool, scopes: Vec<&str>) -> StatusCode {
+        let context = ApiKeyContext {
+            api_key_id: Uuid::new_v4(),
+            scopes: scopes.into_iter().map(|scope| scope.to_owned()).collect(),
+        };
+        let resource = web::resource("/")
+            .wrap_fn(move |req, srv| -> Pin<Box<dyn Future<Output = _>>> {
+                match authorize_api_key(req.request(), &context) {
+                    Ok(()) => Box::pin(srv.call(req)),
+                    Err(e) => Box::pin(ready(Ok(req.error_response(e)))),
+                }
+            })
+            .route(web::get().to(HttpResponse::Ok));
+        let resource = if declared {
+            App::new().service(resource.wrap(RequireScope::new("users:read")))
+        } else {
+            App::new().service(resource)
+        };
+        let app = init_service(resource).await;
+
+        call_service(&app, TestRequest::get().uri("/").to_request())
+            .await
+            .status()
+    }
+
+    /// スコープを付与したAPIキーで認証したリクエストを許可することを確認するテスト
+    #[actix_web::test]
+    async fn api_key_with_scope_is_allowed() {
+        let status = call_resource_with_api_key(true, vec!["users:write", "users:read"]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    /// スコープを付与していないAPIキーで認証したリクエストを`403 Forbidden`で拒否することを確認するテスト
+    #[actix_web::test]
+    async fn api_key_without_scope_is_forbidden() {
+        assert_eq!(
+            call_resource_with_api_key(true, vec!["users:write"]).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_resource_with_api_key(true, vec![]).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    /// スコープを宣言していないリソースでは、APIキーで認証したリクエストを`403 Forbidden`で拒否することを
+    /// 確認するテスト
+    #[actix_web::test]
+    async fn api_key_is_forbidden_without_declared_scope() {
+        assert_eq!(
+            call_resource_with_api_key(false, vec!["users:read"]).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::cookie::{time, Cookie, CookieJar, Key, SameSite};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderValue, COOKIE};
+
+use configurations::SessionCookieSettings;
+
+/// セッションIDのクッキーの鍵更新ミドルウェア
+///
+/// actix-sessionのセッションミドルウェアは1つの鍵でしかクッキーを復号できないため、鍵を更新すると、すべての
+/// セッションが一斉に無効になる。このミドルウェアは、現在の鍵で復号できないセッションIDのクッキーを以前の鍵で
+/// 復号して、現在の鍵で暗号化し直したクッキーをセッションミドルウェアに渡す。また、ブラウザが以降のリクエストで
+/// 現在の鍵で暗号化したクッキーを送信するように、応答で暗号化し直したクッキーを保存するように指示する。
+///
+/// セッションミドルウェアがクッキーを読み込む前に処理するため、セッションミドルウェアより外側で処理する。
+pub struct SessionKeyRotation {
+    inner: Rc<Inner>,
+}
+
+/// 鍵更新ミドルウェアの設定
+struct Inner {
+    /// セッションIDのクッキーを暗号化する現在の鍵。
+    primary_key: Key,
+    /// 以前にセッションIDのクッキーを暗号化していた鍵。
+    legacy_keys: Vec<Key>,
+    /// セッションIDのクッキーの名前。
+    cookie_name: String,
+    /// クッキーに`Secure`属性を付与するか。
+    secure: bool,
+    /// クッキーの`SameSite`属性。
+    same_site: SameSite,
+    /// クッキーの`Max-Age`属性の秒数。
+    max_age_seconds: Option<u64>,
+}
+
+impl SessionKeyRotation {
+    /// 鍵更新ミドルウェアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `primary_key` - セッションIDのクッキーを暗号化する現在の鍵。
+    /// * `legacy_keys` - 以前にセッションIDのクッキーを暗号化していた鍵。
+    /// * `settings` - セッションクッキー設定。
+    ///
+    /// # Returns
+    ///
+    /// 鍵更新ミドルウェアインスタンス。
+    pub fn new(primary_key: Key, legacy_keys: Vec<Key>, settings: &SessionCookieSettings) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                primary_key,
+                legacy_keys,
+                cookie_name: settings.session_id_cookie_name.clone(),
+                secure: settings.secure,
+                same_site: settings.same_site,
+                max_age_seconds: settings.max_age_seconds,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionKeyRotation
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = SessionKeyRotationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionKeyRotationMiddleware {
+            service: Rc::new(service),
+            inner: Rc::clone(&self.inner),
+        }))
+    }
+}
+
+pub struct SessionKeyRotationMiddleware<S> {
+    service: Rc<S>,
+    inner: Rc<Inner>,
+}
+
+impl Inner {
+    /// リクエストヘッダーから、セッションIDのクッキーの値を取得する。
+    ///
+    /// actix-webはリクエストから解析したクッキーを記憶するため、`ServiceRequest::cookie`を使用せずに、
+    /// リクエストヘッダーを解析する。
+    fn session_cookie_value(&self, req: &ServiceRequest) -> Option<String> {
+        req.headers()
+            .get_all(COOKIE)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| Cookie::parse_encoded(pair.trim().to_owned()).ok())
+            .find(|cookie| cookie.name() == self.cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+
+    /// 以前の鍵で暗号化されたクッキーの値を、現在の鍵で暗号化し直す。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - クッキーの値。
+    ///
+    /// # Returns
+    ///
+    /// 現在の鍵で暗号化し直したクッキーの値。現在の鍵で復号できる場合と、どの鍵でも復号できない場合は`None`。
+    fn reencrypt(&self, value: &str) -> Option<String> {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(self.cookie_name.clone(), value.to_owned()));
+        if jar
+            .private(&self.primary_key)
+            .get(&self.cookie_name)
+            .is_some()
+        {
+            return None;
+        }
+        let decrypted = self
+            .legacy_keys
+            .iter()
+            .find_map(|key| jar.private(key).get(&self.cookie_name))?;
+
+        let mut jar = CookieJar::new();
+        jar.private_mut(&self.primary_key).add(Cookie::new(
+            self.cookie_name.clone(),
+            decrypted.value().to_owned(),
+        ));
+
+        jar.get(&self.cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+
+    /// リクエストヘッダーのセッションIDのクッキーの値を置き換える。
+    ///
+    /// 暗号化したクッキーの値はBase64でエンコードされているため、パーセントエンコードせずに設定する。
+    fn replace_request_cookie(&self, req: &mut ServiceRequest, value: &str) {
+        let prefix = format!("{}=", self.cookie_name);
+        let cookies: Vec<String> = req
+            .headers()
+            .get_all(COOKIE)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                if pair.starts_with(&prefix) {
+                    format!("{}{}", prefix, value)
+                } else {
+                    pair.to_owned()
+                }
+            })
+            .collect();
+        if let Ok(header) = HeaderValue::from_str(&cookies.join("; ")) {
+            req.headers_mut().insert(COOKIE, header);
+        }
+    }
+
+    /// 現在の鍵で暗号化し直したセッションIDのクッキーを構築する。
+    ///
+    /// セッションミドルウェアと同じ属性を付与する。
+    fn build_cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::build(self.cookie_name.clone(), value)
+            .path("/")
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .finish();
+        if let Some(max_age) = self.max_age_seconds {
+            cookie.set_max_age(time::Duration::seconds(max_age as i64));
+        }
+
+        cookie
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for SessionKeyRotationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let inner = Rc::clone(&self.inner);
+
+        Box::pin(async move {
+            // 以前の鍵で暗号化されたクッキーでない場合は、そのまま処理を移譲
+            let value = match inner
+                .session_cookie_value(&req)
+                .and_then(|value| inner.reencrypt(&value))
+            {
+                Some(value) => value,
+                None => return service.call(req).await,
+            };
+            tracing::info!("以前の鍵で暗号化されたセッションIDのクッキーを暗号化し直しました。");
+            inner.replace_request_cookie(&mut req, &value);
+
+            // セッションミドルウェアがクッキーを設定しなかった場合は、暗号化し直したクッキーを保存するように指示
+            let mut res = service.call(req).await?;
+            let cookie_is_set = res
+                .response()
+                .cookies()
+                .any(|cookie| cookie.name() == inner.cookie_name);
+            if !cookie_is_set {
+                if let Err(e) = res.response_mut().add_cookie(&inner.build_cookie(value)) {
+                    tracing::warn!("暗号化し直したクッキーを設定できませんでした。{}", e);
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_session::{storage::CookieSessionStore, Session, SessionMiddleware};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    /// セッションクッキー設定を返却する。
+    fn cookie_settings() -> SessionCookieSettings {
+        SessionCookieSettings {
+            session_id_cookie_name: "session_id".to_owned(),
+            secure: false,
+            same_site: SameSite::Lax,
+            max_age_seconds: None,
+        }
+    }
+
+    /// 鍵を生成する。
+    fn key(byte: u8) -> Key {
+        Key::from(&[byte; 64])
+    }
+
+    /// セッションを開始するハンドラ
+    async fn login(session: Session) -> HttpResponse {
+        session.insert("user", "alice").unwrap();
+
+        HttpResponse::Ok().finish()
+    }
+
+    /// セッションに記録したユーザーを返却するハンドラ
+    async fn whoami(session: Session) -> HttpResponse {
+        let user = session.get::<String>("user").unwrap().unwrap_or_default();
+
+        HttpResponse::Ok().body(user)
+    }
+
+    /// 指定した鍵でセッションIDのクッキーを暗号化して、鍵更新ミドルウェアを経由するアプリを構築するマクロ
+    macro_rules! rotated_app {
+        ($primary_key:expr, $legacy_keys:expr) => {
+            init_service(
+                App::new()
+                    .wrap(
+                        SessionMiddleware::builder(CookieSessionStore::default(), $primary_key)
+                            .cookie_name("session_id".to_owned())
+                            .build(),
+                    )
+                    .wrap(SessionKeyRotation::new(
+                        $primary_key,
+                        $legacy_keys,
+                        &cookie_settings(),
+                    ))
+                    .route("/login", web::post().to(login))
+                    .route("/whoami", web::get().to(whoami)),
+            )
+            .await
+        };
+    }
+
+    /// 以前の鍵でセッションを開始して、セッションIDのクッキーを返却する。
+    async fn login_with_old_key() -> Cookie<'static> {
+        let app = rotated_app!(key(1), vec![]);
+        let resp = call_service(&app, TestRequest::post().uri("/login").to_request()).await;
+
+        resp.response()
+            .cookies()
+            .find(|cookie| cookie.name() == "session_id")
+            .unwrap()
+            .into_owned()
+    }
+
+    /// 鍵を更新した後も、以前の鍵で暗号化したセッションIDのクッキーでセッションを継続でき、応答で現在の鍵で
+    /// 暗号化し直したクッキーを保存するように指示することを確認するテスト
+    #[actix_web::test]
+    async fn session_encrypted_with_legacy_key_is_accepted_after_rotation() {
+        let old_cookie = login_with_old_key().await;
+
+        let app = rotated_app!(key(2), vec![key(1)]);
+        let req = TestRequest::get()
+            .uri("/whoami")
+            .cookie(old_cookie.clone())
+            .to_request();
+        let resp = call_service(&app, req).await;
+        let new_cookie = resp
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "session_id")
+            .unwrap()
+            .into_owned();
+        assert_ne!(new_cookie.value(), old_cookie.value());
+        assert_eq!(read_body(resp).await, "alice");
+
+        // 暗号化し直したクッキーは、以前の鍵がなくても復号できることを確認
+        let app = rotated_app!(key(2), vec![]);
+        let req = TestRequest::get()
+            .uri("/whoami")
+            .cookie(new_cookie)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(read_body(resp).await, "alice");
+    }
+
+    /// 以前の鍵を指定しない場合は、以前の鍵で暗号化したセッションIDのクッキーを受け付けないことを確認するテスト
+    #[actix_web::test]
+    async fn session_encrypted_with_unknown_key_is_rejected() {
+        let old_cookie = login_with_old_key().await;
+
+        let app = rotated_app!(key(2), vec![key(3)]);
+        let req = TestRequest::get()
+            .uri("/whoami")
+            .cookie(old_cookie)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(read_body(resp).await, "");
+    }
+}
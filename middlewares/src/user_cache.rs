@@ -0,0 +1,281 @@
+//! ユーザーキャッシュ
+//!
+//! 認証ミドルウェアが取得したユーザーを、ユーザーIDをキーにメモリに短い期間キャッシュして、同じユーザーから
+//! 繰り返し送信されたリクエストで、データベースへの問い合わせを省略する。
+//!
+//! キャッシュしたユーザーは、有効期間が経過すると破棄する。また、記録したユーザー数が上限に達した場合は、
+//! 最も長い間参照されていないユーザーを破棄する。
+//!
+//! パスワードの変更や、ユーザーの無効化など、認証ミドルウェアの判断に影響するユーザーの変更をしたハンドラは、
+//! `invalidate`でキャッシュしたユーザーを破棄すること。破棄しない場合でも、キャッシュの有効期間が経過
+//! すれば、変更が反映される。
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+use configurations::UserCacheSettings;
+use domains::models::users::User;
+
+/// キャッシュしたユーザー
+struct CachedUser {
+    /// ユーザー。
+    user: User,
+    /// キャッシュした日時(Unixエポック秒)。
+    cached_at: u64,
+    /// 最後に参照された順序。
+    last_used: u64,
+}
+
+/// キャッシュの内容
+#[derive(Default)]
+struct Entries {
+    /// ユーザーIDをキーにしたキャッシュしたユーザー。
+    users: HashMap<Uuid, CachedUser>,
+    /// 参照するたびに増加させる順序。
+    clock: u64,
+}
+
+/// ユーザーキャッシュ
+pub struct UserCache {
+    /// キャッシュの内容。キャッシュが無効な場合は`None`。
+    entries: Option<Mutex<Entries>>,
+    /// キャッシュに記録するユーザー数の上限。
+    capacity: usize,
+    /// キャッシュしたユーザーの有効期間(秒)。
+    ttl: u64,
+}
+
+impl UserCache {
+    /// ユーザーキャッシュを構築する。
+    ///
+    /// ユーザーキャッシュ設定でキャッシュが無効になっている場合や、上限又は有効期間が0の場合は、ユーザーを
+    /// キャッシュしない。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - ユーザーキャッシュ設定。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーキャッシュ。
+    pub fn new(settings: &UserCacheSettings) -> Self {
+        let enabled = settings.enabled && 0 < settings.capacity && 0 < settings.ttl();
+
+        Self {
+            entries: enabled.then(|| Mutex::new(Entries::default())),
+            capacity: settings.capacity,
+            ttl: settings.ttl(),
+        }
+    }
+
+    /// ユーザーをキャッシュするか確認する。
+    pub fn is_enabled(&self) -> bool {
+        self.entries.is_some()
+    }
+
+    /// キャッシュからユーザーを取得する。
+    ///
+    /// 有効期間が経過したユーザーは、キャッシュから破棄する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `now` - 現在日時(Unixエポック秒)。
+    ///
+    /// # Returns
+    ///
+    /// ユーザー。キャッシュされていないか、有効期間が経過している場合は`None`。
+    pub fn get(&self, user_id: Uuid, now: u64) -> Option<User> {
+        let mut entries = self.entries.as_ref()?.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        match entries.users.get_mut(&user_id) {
+            Some(cached) if now < cached.cached_at + self.ttl => {
+                cached.last_used = clock;
+                Some(cached.user.clone())
+            }
+            Some(_) => {
+                entries.users.remove(&user_id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// ユーザーをキャッシュする。
+    ///
+    /// 記録したユーザー数が上限に達している場合は、最も長い間参照されていないユーザーを破棄する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - ユーザー。
+    /// * `now` - 現在日時(Unixエポック秒)。
+    pub fn insert(&self, user: &User, now: u64) {
+        let mut entries = match self.entries.as_ref() {
+            Some(entries) => entries.lock().unwrap(),
+            None => return,
+        };
+        let user_id = user.id().value();
+        if !entries.users.contains_key(&user_id) && self.capacity <= entries.users.len() {
+            let least_recently_used = entries
+                .users
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| *id);
+            if let Some(id) = least_recently_used {
+                entries.users.remove(&id);
+            }
+        }
+        entries.clock += 1;
+        let last_used = entries.clock;
+        entries.users.insert(
+            user_id,
+            CachedUser {
+                user: user.clone(),
+                cached_at: now,
+                last_used,
+            },
+        );
+    }
+
+    /// キャッシュしたユーザーを破棄する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - 破棄するユーザーのユーザーID。
+    pub fn invalidate(&self, user_id: Uuid) {
+        if let Some(entries) = self.entries.as_ref() {
+            entries.lock().unwrap().users.remove(&user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::cookie::time::Duration;
+    use domains::models::{
+        users::{HashedPassword, ProfileVisibility, UserId, UserName, UserRole, DEFAULT_TENANT_ID},
+        EmailAddress,
+    };
+
+    /// 指定した容量と有効期間（秒）で、有効なユーザーキャッシュを構築する。
+    fn cache(capacity: usize, ttl: i64) -> UserCache {
+        UserCache::new(&UserCacheSettings {
+            enabled: true,
+            capacity,
+            ttl: Duration::seconds(ttl),
+        })
+    }
+
+    /// 指定したユーザー名から、テスト用のユーザーを構築する。
+    fn user(name: &str) -> User {
+        User::new(
+            UserId::default(),
+            DEFAULT_TENANT_ID,
+            UserName::new(name).unwrap(),
+            EmailAddress::new(&format!("{}@example.com", name)).unwrap(),
+            HashedPassword::new_unchecked("hashed".to_owned()),
+            true,
+            None,
+            ProfileVisibility::default(),
+            None,
+            false,
+            UserRole::User,
+            None,
+            None,
+        )
+    }
+
+    /// キャッシュしたユーザーを、有効期間内であればキャッシュから取得できることを確認するテスト
+    #[test]
+    fn cached_user_is_returned_within_ttl() {
+        let cache = cache(10, 30);
+        let taro = user("taro");
+        assert!(cache.get(taro.id().value(), 1000).is_none());
+
+        cache.insert(&taro, 1000);
+        let cached = cache.get(taro.id().value(), 1029).unwrap();
+        assert_eq!(cached.id().value(), taro.id().value());
+    }
+
+    /// 有効期間が経過したユーザーは、キャッシュから取得できないことを確認するテスト
+    #[test]
+    fn cached_user_expires_after_ttl() {
+        let cache = cache(10, 30);
+        let taro = user("taro");
+        cache.insert(&taro, 1000);
+
+        assert!(cache.get(taro.id().value(), 1030).is_none());
+        // 破棄されたため、有効期間内の日時を指定しても取得できない
+        assert!(cache.get(taro.id().value(), 1001).is_none());
+    }
+
+    /// ユーザーの変更後にキャッシュを破棄すると、変更前のユーザーが返却されないことを確認するテスト
+    #[test]
+    fn invalidated_user_is_not_returned() {
+        let cache = cache(10, 30);
+        let taro = user("taro");
+        cache.insert(&taro, 1000);
+
+        // パスワードの変更を要求するようにユーザーを変更して、キャッシュを破棄
+        let changed = User::new(
+            taro.id(),
+            DEFAULT_TENANT_ID,
+            UserName::new("taro").unwrap(),
+            EmailAddress::new("taro@example.com").unwrap(),
+            HashedPassword::new_unchecked("hashed".to_owned()),
+            true,
+            None,
+            ProfileVisibility::default(),
+            None,
+            true,
+            UserRole::User,
+            None,
+            None,
+        );
+        cache.invalidate(taro.id().value());
+        assert!(cache.get(taro.id().value(), 1001).is_none());
+
+        // データベースから取得し直したユーザーをキャッシュ
+        cache.insert(&changed, 1001);
+        assert!(cache
+            .get(taro.id().value(), 1002)
+            .unwrap()
+            .must_change_password());
+    }
+
+    /// 上限に達した場合は、最も長い間参照されていないユーザーを破棄することを確認するテスト
+    #[test]
+    fn least_recently_used_user_is_evicted() {
+        let cache = cache(2, 30);
+        let taro = user("taro");
+        let jiro = user("jiro");
+        let saburo = user("saburo");
+        cache.insert(&taro, 1000);
+        cache.insert(&jiro, 1000);
+        // 太郎を参照して、次郎を最も長い間参照されていないユーザーにする
+        assert!(cache.get(taro.id().value(), 1001).is_some());
+
+        cache.insert(&saburo, 1002);
+        assert!(cache.get(taro.id().value(), 1003).is_some());
+        assert!(cache.get(jiro.id().value(), 1003).is_none());
+        assert!(cache.get(saburo.id().value(), 1003).is_some());
+    }
+
+    /// 無効にしたキャッシュは、ユーザーをキャッシュしないことを確認するテスト
+    #[test]
+    fn disabled_cache_does_not_cache_users() {
+        let cache = UserCache::new(&UserCacheSettings {
+            enabled: false,
+            capacity: 10,
+            ttl: Duration::seconds(30),
+        });
+        assert!(!cache.is_enabled());
+        let taro = user("taro");
+        cache.insert(&taro, 1000);
+        assert!(cache.get(taro.id().value(), 1001).is_none());
+    }
+}
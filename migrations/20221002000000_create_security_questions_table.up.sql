@@ -0,0 +1,9 @@
+CREATE TABLE security_questions(
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    question VARCHAR(200) NOT NULL,
+    hashed_answer TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id, position)
+);
@@ -0,0 +1,4 @@
+DROP INDEX users_email_address_key;
+DELETE FROM users WHERE deleted_at IS NOT NULL;
+ALTER TABLE users ADD CONSTRAINT users_email_address_key UNIQUE (email_address);
+ALTER TABLE users DROP COLUMN deleted_at;
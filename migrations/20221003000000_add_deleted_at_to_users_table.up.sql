@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
+-- 削除したユーザーのEメールアドレスで再登録できるように、一意制約を削除されていないユーザーに限定
+ALTER TABLE users DROP CONSTRAINT users_email_address_key;
+CREATE UNIQUE INDEX users_email_address_key ON users(email_address) WHERE deleted_at IS NULL;
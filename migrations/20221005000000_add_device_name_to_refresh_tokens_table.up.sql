@@ -0,0 +1 @@
+ALTER TABLE refresh_tokens ADD COLUMN device_name VARCHAR(100);
@@ -0,0 +1,11 @@
+CREATE TABLE login_attempts(
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    succeeded BOOLEAN NOT NULL,
+    ip_address VARCHAR(45),
+    device_name VARCHAR(100),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    attempted_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX login_attempts_user_id_attempted_at_idx ON login_attempts(user_id, attempted_at DESC);
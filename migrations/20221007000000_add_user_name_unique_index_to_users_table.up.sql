@@ -0,0 +1 @@
+CREATE UNIQUE INDEX users_user_name_key ON users(LOWER(user_name)) WHERE deleted_at IS NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE users
+    DROP COLUMN email_address_visibility,
+    DROP COLUMN last_logged_in_visibility;
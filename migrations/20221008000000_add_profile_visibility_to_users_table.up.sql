@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD COLUMN email_address_visibility VARCHAR(20) NOT NULL DEFAULT 'private'
+        CHECK (email_address_visibility IN ('public', 'authenticated_only', 'private')),
+    ADD COLUMN last_logged_in_visibility VARCHAR(20) NOT NULL DEFAULT 'authenticated_only'
+        CHECK (last_logged_in_visibility IN ('public', 'authenticated_only', 'private'));
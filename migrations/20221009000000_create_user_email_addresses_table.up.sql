@@ -0,0 +1,13 @@
+CREATE TABLE user_email_addresses(
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email_address VARCHAR(120) NOT NULL UNIQUE,
+    is_primary BOOLEAN NOT NULL,
+    is_verified BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+-- ユーザーごとにプライマリEメールアドレスは1つ
+CREATE UNIQUE INDEX user_email_addresses_primary_idx ON user_email_addresses (user_id) WHERE is_primary;
+-- 既存のユーザーのEメールアドレスを、確認済みのプライマリEメールアドレスとして登録
+INSERT INTO user_email_addresses (id, user_id, email_address, is_primary, is_verified, created_at)
+    SELECT gen_random_uuid(), id, email_address, true, true, created_at FROM users;
@@ -0,0 +1,2 @@
+DROP INDEX users_tenant_id_idx;
+ALTER TABLE users DROP COLUMN tenant_id;
@@ -0,0 +1,3 @@
+-- 既存のユーザーは、既定のテナントに所属
+ALTER TABLE users ADD COLUMN tenant_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000';
+CREATE INDEX users_tenant_id_idx ON users(tenant_id);
@@ -0,0 +1,7 @@
+CREATE TABLE totp_recovery_codes(
+    code_hash TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX totp_recovery_codes_user_id_idx ON totp_recovery_codes(user_id);
@@ -0,0 +1,6 @@
+CREATE TABLE invite_codes(
+    code_hash TEXT PRIMARY KEY,
+    used_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE api_keys(
+    id UUID PRIMARY KEY,
+    key_hash TEXT NOT NULL UNIQUE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    expired_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys(user_id);
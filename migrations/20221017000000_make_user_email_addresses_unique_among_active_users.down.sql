@@ -0,0 +1,5 @@
+DROP INDEX user_email_addresses_email_address_key;
+DELETE FROM user_email_addresses WHERE deleted_at IS NOT NULL;
+ALTER TABLE user_email_addresses
+    ADD CONSTRAINT user_email_addresses_email_address_key UNIQUE (email_address);
+ALTER TABLE user_email_addresses DROP COLUMN deleted_at;
@@ -0,0 +1,13 @@
+ALTER TABLE user_email_addresses ADD COLUMN deleted_at TIMESTAMPTZ;
+-- 論理削除したユーザーのエイリアスを削除して、プライマリEメールアドレスを論理削除
+DELETE FROM user_email_addresses e
+    USING users u
+    WHERE e.user_id = u.id AND u.deleted_at IS NOT NULL AND NOT e.is_primary;
+UPDATE user_email_addresses e
+    SET deleted_at = u.deleted_at
+    FROM users u
+    WHERE e.user_id = u.id AND u.deleted_at IS NOT NULL;
+-- 削除したユーザーのEメールアドレスで再登録できるように、一意制約を論理削除されていないEメールアドレスに限定
+ALTER TABLE user_email_addresses DROP CONSTRAINT user_email_addresses_email_address_key;
+CREATE UNIQUE INDEX user_email_addresses_email_address_key
+    ON user_email_addresses(email_address) WHERE deleted_at IS NULL;
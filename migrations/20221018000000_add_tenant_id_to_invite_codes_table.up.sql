@@ -0,0 +1,3 @@
+-- 既存の招待コードは、既定のテナントに招待する招待コードとして扱う
+ALTER TABLE invite_codes ADD COLUMN tenant_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000';
+ALTER TABLE invite_codes ALTER COLUMN tenant_id DROP DEFAULT;
@@ -0,0 +1,5 @@
+DELETE FROM user_credentials;
+ALTER TABLE user_credentials
+    DROP COLUMN passkey,
+    ADD COLUMN public_key BYTEA NOT NULL,
+    ADD COLUMN sign_count BIGINT NOT NULL;
@@ -0,0 +1,6 @@
+-- COSE形式の公開鍵と署名カウンターからwebauthn-rsのパスキーを復元できないため、登録済みのパスキーは削除
+DELETE FROM user_credentials;
+ALTER TABLE user_credentials
+    DROP COLUMN public_key,
+    DROP COLUMN sign_count,
+    ADD COLUMN passkey TEXT NOT NULL;
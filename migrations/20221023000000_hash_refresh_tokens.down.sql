@@ -0,0 +1,4 @@
+DELETE FROM refresh_tokens;
+ALTER TABLE refresh_tokens
+    DROP COLUMN token_hash,
+    ADD COLUMN refresh_token TEXT NOT NULL;
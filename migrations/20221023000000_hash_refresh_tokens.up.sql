@@ -0,0 +1,5 @@
+-- マイグレーションではハッシュを計算する鍵を参照できないため、記録済みのリフレッシュトークンは削除
+DELETE FROM refresh_tokens;
+ALTER TABLE refresh_tokens
+    DROP COLUMN refresh_token,
+    ADD COLUMN token_hash TEXT NOT NULL UNIQUE;
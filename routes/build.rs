@@ -0,0 +1,42 @@
+use std::process::Command;
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// バージョンAPIが応答するビルド情報を、コンパイル時の環境変数に設定する。
+///
+/// * `GIT_COMMIT`: ビルドしたコミット。環境変数`GIT_COMMIT`が設定されている場合はその値を使用して、
+///   設定されていない場合は`git rev-parse`で取得する（取得できない場合は`unknown`）。
+/// * `BUILD_TIMESTAMP`: ビルドした日時（RFC3339形式）。
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+
+    let git_commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(git_rev_parse)
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GIT_COMMIT={}", git_commit);
+
+    let build_timestamp = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .expect("ビルド日時をフォーマットできませんでした。");
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp);
+}
+
+/// `git rev-parse`で、HEADのコミットハッシュを取得する。
+fn git_rev_parse() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|commit| commit.trim().to_owned())
+        .filter(|commit| !commit.is_empty())
+}
@@ -1,22 +1,48 @@
-use actix_web::{cookie::Cookie, http::header::ContentType, web, HttpResponse};
+use actix_web::{
+    cookie::Cookie,
+    http::{
+        header::{ContentType, LOCATION, USER_AGENT},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use ipnet::IpNet;
 use secrecy::{ExposeSecret, Secret};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use uuid::Uuid;
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential, Webauthn};
 
 use configurations::{
+    client_ip::{is_trusted_peer, real_client_ip},
     session::{
-        add_session_data_cookies, TypedSession, ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME,
+        add_session_data_cookies, add_token_fingerprint_header, guess_device_name, SessionData,
+        TypedSession, ACCESS_TOKEN_COOKIE_NAME, DEVICE_NAME_MAX_LEN, REFRESH_TOKEN_COOKIE_NAME,
     },
     Settings,
 };
 use domains::models::{
-    users::{RawPassword, User, UserName},
+    login_attempts::GeoLocation,
+    security_questions::SECURITY_QUESTIONS_MAX_COUNT,
+    users::{ProfileVisibility, RawPassword, User, UserName, Visibility},
     EmailAddress,
 };
-use middlewares::JwtAuth;
-use usecases::accounts::{self, ChangePasswordError, LoginError, SignupError};
+use middlewares::{user_cache::UserCache, JwtAuth};
+use miscellaneous::current_unix_epoch;
+use usecases::accounts::{self, LoginIdentifier, LoginOutcome, SignupError};
+use usecases::email_addresses;
+use usecases::errors::AuthError;
+use usecases::login_attempts::LoginClient;
+use usecases::passkeys::{self, PasskeyLoginOutcome};
+use usecases::password_resets;
+use usecases::security_questions::{self, NewSecurityQuestion};
+use usecases::sessions::{self, RevokeTarget, SessionPurger};
+use usecases::totp::{self, SecondFactor};
+use usecases::users;
+use usecases::webhooks::WebhookDispatcher;
 
-use crate::responses::e400;
+use crate::extractors::{FieldError, Validate, ValidatedJson};
+use crate::responses::{e400, e500, json_error};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,25 +50,110 @@ pub struct SignupData {
     pub user_name: String,
     pub email_address: String,
     pub password: Secret<String>,
+    /// パスワードをクライアントでハッシュ化しているか
+    #[serde(default)]
+    pub client_hashed: bool,
+    /// 招待コード
+    ///
+    /// サインアップを停止している場合に、招待された利用者が指定する。
+    #[serde(default)]
+    pub invite_code: Option<Secret<String>>,
+}
+
+/// 検証済みサインアップデータ構造体
+#[derive(Debug)]
+pub struct SignupInput {
+    pub user_name: UserName,
+    pub email_address: EmailAddress,
+    pub password: RawPassword,
+    pub invite_code: Option<Secret<String>>,
 }
 
-#[tracing::instrument(skip(pool), name = "Signup")]
+impl Validate for SignupData {
+    type Validated = SignupInput;
+
+    fn validate(self) -> Result<Self::Validated, Vec<FieldError>> {
+        let mut errors = vec![];
+        let user_name = UserName::new(&self.user_name)
+            .map_err(|e| errors.extend(FieldError::from_domain("userName", e)));
+        let email_address = EmailAddress::new(&self.email_address)
+            .map_err(|e| errors.extend(FieldError::from_domain("emailAddress", e)));
+        let password = if self.client_hashed {
+            RawPassword::new_client_hashed(self.password.expose_secret())
+        } else {
+            RawPassword::new(self.password.expose_secret())
+        }
+        .map_err(|e| errors.extend(FieldError::from_domain("password", e)));
+
+        match (user_name, email_address, password) {
+            (Ok(user_name), Ok(email_address), Ok(password)) => Ok(SignupInput {
+                user_name,
+                email_address,
+                password,
+                invite_code: self.invite_code,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// サインアップハンドラ
+///
+/// サインアップ設定のプライバシーモードが有効な場合は、Eメールアドレスが登録されているかどうかを秘匿する
+/// ため、Eメールアドレスが既に登録されていても、ユーザーを登録したときと同じ`202 Accepted`で応答する。
+/// この場合、2つ目のアカウントは登録しない。
+///
+/// サインアップ設定でサインアップを停止している場合は、招待コードを指定したリクエストのみ受け付けて、
+/// 未使用の招待コードを使用済みにしてユーザーを登録する。招待コードを指定していない場合は、リクエスト
+/// ボディを検証せず、データベースにも問い合わせずに`403 Forbidden`で応答する。サインアップを受け付けて
+/// いる場合は、招待コードを使用しない。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(data, settings, webhooks, pool), name = "Signup")]
 pub async fn signup(
-    data: web::Json<SignupData>,
+    data: Result<web::Json<SignupData>, actix_web::Error>,
+    settings: web::Data<Settings>,
+    webhooks: web::Data<WebhookDispatcher>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let user_name = UserName::new(&data.user_name).map_err(e400)?;
-    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
-    let password = RawPassword::new(data.password.expose_secret()).map_err(e400)?;
-    let user = accounts::signup(user_name, email_address, password, &pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("{:?}", e);
-            match e {
-                SignupError::EmailAddressAlreadyExists => actix_web::error::ErrorBadRequest(e),
-                SignupError::UnexpectedError(_) => actix_web::error::ErrorInternalServerError(e),
+    let invited = matches!(&data, Ok(data) if data.invite_code.is_some());
+    if !settings.signup.enabled && !invited {
+        return Err(json_error(
+            StatusCode::FORBIDDEN,
+            "SIGNUP_DISABLED",
+            "現在、招待コードを指定しないサインアップを受け付けていません。".to_owned(),
+        ));
+    }
+    let SignupInput {
+        user_name,
+        email_address,
+        password,
+        invite_code,
+    } = ValidatedJson::from_data(data?.into_inner())?.into_inner();
+    let invite_code = invite_code.filter(|_| !settings.signup.enabled);
+    let result = accounts::signup(
+        user_name,
+        email_address,
+        password,
+        invite_code
+            .as_ref()
+            .map(|code| code.expose_secret().as_str()),
+        &settings,
+        &webhooks,
+        &pool,
+    )
+    .await;
+    if settings.signup.privacy_mode {
+        return match result {
+            Ok(_) | Err(AuthError::Signup(SignupError::EmailAddressAlreadyExists)) => {
+                Ok(HttpResponse::Accepted().finish())
             }
-        })?;
+            Err(e) => Err(e.into()),
+        };
+    }
+    let user = result?;
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::json())
@@ -52,45 +163,425 @@ pub async fn signup(
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginData {
-    pub email_address: String,
+    /// Eメールアドレス
+    ///
+    /// Eメールアドレスとユーザー名のどちらか一方を指定する。
+    #[serde(default)]
+    pub email_address: Option<String>,
+    /// ユーザー名
+    #[serde(default)]
+    pub user_name: Option<String>,
     pub password: Secret<String>,
+    /// パスワードをクライアントでハッシュ化しているか
+    #[serde(default)]
+    pub client_hashed: bool,
+    /// デバイス名
+    ///
+    /// 指定しなかった場合は、User-Agentから推測する。
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+impl LoginData {
+    /// ログインするユーザーの識別子を取得する。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーの識別子。Eメールアドレスとユーザー名の両方を指定したか、どちらも指定しなかった場合はエラー。
+    fn identifier(&self) -> Result<LoginIdentifier, actix_web::Error> {
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+        };
+        match (non_empty(&self.email_address), non_empty(&self.user_name)) {
+            (Some(email_address), None) => Ok(LoginIdentifier::EmailAddress(
+                EmailAddress::new(&email_address).map_err(e400)?,
+            )),
+            // 予約されたユーザー名を導入する前に登録されたユーザーもログインできるように、ユーザー名は検証しない
+            (None, Some(user_name)) => Ok(LoginIdentifier::UserName(UserName::new_unchecked(
+                &user_name,
+            ))),
+            _ => Err(e400(
+                "Eメールアドレスとユーザー名のどちらか一方を指定してください。",
+            )),
+        }
+    }
+}
+
+/// ログインしたセッションのデバイス名を決定する。
+///
+/// クライアントがデバイス名を指定した場合はそのデバイス名を、指定しなかった場合はUser-Agentから推測した
+/// デバイス名を返却する。
+fn decide_device_name(
+    req: &HttpRequest,
+    device_name: Option<&str>,
+) -> Result<Option<String>, actix_web::Error> {
+    if let Some(device_name) = device_name.map(str::trim).filter(|name| !name.is_empty()) {
+        if DEVICE_NAME_MAX_LEN < device_name.chars().count() {
+            return Err(e400(format!(
+                "デバイス名は{}文字以内で指定してください。",
+                DEVICE_NAME_MAX_LEN
+            )));
+        }
+        return Ok(Some(device_name.to_owned()));
+    }
+
+    Ok(req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(guess_device_name))
+}
+
+/// リバースプロキシがIPアドレスから推定したクライアントの緯度を設定するヘッダー
+pub const CLIENT_LATITUDE_HEADER: &str = "X-Client-Latitude";
+
+/// リバースプロキシがIPアドレスから推定したクライアントの経度を設定するヘッダー
+pub const CLIENT_LONGITUDE_HEADER: &str = "X-Client-Longitude";
+
+/// リバースプロキシが設定したヘッダーから、クライアントの位置を取得する。
+///
+/// クライアントが偽装したヘッダーを記録しないように、接続元が信頼するプロキシの場合のみヘッダーを使用する。
+/// 接続元が信頼するプロキシではない場合や、緯度と経度のどちらかが設定されていない場合、不正な値の場合は
+/// `None`を返却する。
+fn client_location(req: &HttpRequest, trusted: &[IpNet]) -> Option<GeoLocation> {
+    if !is_trusted_peer(req, trusted) {
+        return None;
+    }
+    let header_value = |name: &str| -> Option<f64> {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    };
+
+    GeoLocation::new(
+        header_value(CLIENT_LATITUDE_HEADER)?,
+        header_value(CLIENT_LONGITUDE_HEADER)?,
+    )
+    .ok()
 }
 
-#[tracing::instrument(skip(session, pool), name = "Login user")]
+/// ログインクエリパラメーター構造体
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    /// ログインに成功した後にリダイレクトするパス
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// ログインに成功した後にリダイレクトするパスの最大文字数
+const NEXT_PATH_MAX_LEN: usize = 2048;
+
+/// ログインに成功した後にリダイレクトするパスを検証する。
+///
+/// オープンリダイレクトを防止するために、同じオリジンの相対パスのみを許可する。絶対URLや、
+/// `//evil.com`のようなプロトコル相対URLは拒否する。ブラウザが`/`と同様に扱う`\`や、
+/// 制御文字を含むパスも拒否する。
+///
+/// # Arguments
+///
+/// * `next` - リダイレクトするパス。
+///
+/// # Returns
+///
+/// リダイレクトするパス。
+fn validate_next_path(next: &str) -> Result<&str, actix_web::Error> {
+    let is_relative_path = next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.contains('\\')
+        && !next.chars().any(char::is_control)
+        && next.len() <= NEXT_PATH_MAX_LEN;
+    if !is_relative_path {
+        return Err(e400(
+            "リダイレクト先には、同じオリジンの相対パスを指定してください。",
+        ));
+    }
+
+    Ok(next)
+}
+
+/// セッションデータをクッキーに追加するように指示するレスポンスを構築する。
+///
+/// アクセストークンのフィンガープリントをヘッダーに追加する。
+fn session_data_response(
+    session_data: &SessionData,
+    settings: &Settings,
+) -> Result<HttpResponse, actix_web::Error> {
+    add_session_data(HttpResponse::Ok().finish(), session_data, settings)
+}
+
+/// レスポンスに、セッションデータをクッキーに追加する指示と、アクセストークンのフィンガープリントを追加する。
+fn add_session_data(
+    mut response: HttpResponse,
+    session_data: &SessionData,
+    settings: &Settings,
+) -> Result<HttpResponse, actix_web::Error> {
+    add_session_data_cookies(
+        &mut response,
+        session_data.access_token.expose(),
+        session_data.refresh_token.expose(),
+        &settings.session_cookie,
+    )
+    .map_err(e500)?;
+    add_token_fingerprint_header(&mut response, session_data.access_token.expose())
+        .map_err(e500)?;
+
+    Ok(response)
+}
+
+#[tracing::instrument(skip(req, session, pool), name = "Login user")]
 pub async fn login(
+    req: HttpRequest,
+    query: web::Query<LoginQuery>,
     data: web::Json<LoginData>,
     settings: web::Data<Settings>,
     session: TypedSession,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
-    let session_data = accounts::login(
-        email_address,
+    let identifier = data.identifier()?;
+    // 認証する前に、リダイレクト先を検証
+    let next = query.next.as_deref().map(validate_next_path).transpose()?;
+    // クライアントでハッシュ化したパスワードの場合は、Argon2で検証する前に長さを検証
+    if data.client_hashed {
+        RawPassword::new_client_hashed(data.password.expose_secret()).map_err(e400)?;
+    }
+    let client = LoginClient {
+        ip_address: real_client_ip(&req, &settings.web_app.trusted_proxies)
+            .map(|ip| ip.to_string()),
+        device_name: decide_device_name(&req, data.device_name.as_deref())?,
+        location: client_location(&req, &settings.web_app.trusted_proxies),
+    };
+    let outcome = accounts::login(
+        identifier,
         data.password.clone(),
+        data.client_hashed,
+        client,
         settings.as_ref(),
         &session,
         &pool,
     )
-    .await
-    .map_err(|e| {
-        tracing::error!("{:?}", e);
-        match e {
-            LoginError::UnexpectedError(_) => actix_web::error::ErrorInternalServerError(e),
-            LoginError::InvalidCredentials => actix_web::error::ErrorUnauthorized(e),
-            LoginError::NotActive(_) => actix_web::error::ErrorUnauthorized(e),
+    .await?;
+
+    match outcome {
+        LoginOutcome::Authenticated {
+            session_data,
+            must_change_password,
+            password_meets_current_policy,
+        } => {
+            // パスワードの変更を要求されている場合や、パスワードが現在のパスワードのポリシーを満たしていない場合は、
+            // クライアントがパスワード変更画面に遷移できるように通知
+            let notifies = must_change_password || !password_meets_current_policy;
+            let response = match (notifies, next) {
+                (true, _) => HttpResponse::Ok().json(LoginResponseBody {
+                    must_change_password,
+                    password_meets_current_policy,
+                }),
+                // リダイレクト先を指定された場合は、リダイレクト先に遷移するように指示
+                (false, Some(next)) => HttpResponse::SeeOther()
+                    .insert_header((LOCATION, next))
+                    .finish(),
+                (false, None) => HttpResponse::Ok().finish(),
+            };
+            add_session_data(response, &session_data, &settings)
         }
-    })?;
+        // 2要素認証を有効にしている場合は、TOTPのコードの検証を要求
+        LoginOutcome::TotpRequired(challenge_token) => {
+            Ok(HttpResponse::Accepted().json(TotpChallengeResponseBody { challenge_token }))
+        }
+    }
+}
 
-    // セッションデータをクッキーに追加するように指示してレスポンスを返却
-    let mut response = HttpResponse::Ok().finish();
-    add_session_data_cookies(
-        &mut response,
-        &session_data.access_token,
-        &session_data.refresh_token,
-        &settings.session_cookie,
-    );
+/// ログインレスポンスボディ構造体
+///
+/// パスワードの変更を要求されている場合か、パスワードが現在のパスワードのポリシーを満たしていない場合にのみ
+/// 応答する。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginResponseBody {
+    /// パスワードを変更するまで、パスワードの変更以外の保護されたリソースにアクセスできないか。
+    pub must_change_password: bool,
+    /// パスワードが現在のパスワードのポリシーを満たしているか。
+    ///
+    /// `false`の場合でも保護されたリソースにアクセスできるが、クライアントはパスワードの変更を促す。
+    pub password_meets_current_policy: bool,
+}
 
-    Ok(response)
+/// TOTPチャレンジレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpChallengeResponseBody {
+    /// `/accounts/login/totp`にコードと一緒に送信するチャレンジトークン。
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpLoginData {
+    pub challenge_token: String,
+    pub code: String,
+    /// デバイス名
+    ///
+    /// 指定しなかった場合は、User-Agentから推測する。
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+/// TOTPのコード又はリカバリーコードで、2要素認証を有効にしたユーザーのログインを完了する。
+async fn finish_totp_login(
+    req: &HttpRequest,
+    data: &TotpLoginData,
+    factor: SecondFactor<'_>,
+    settings: &Settings,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> Result<HttpResponse, actix_web::Error> {
+    let client = LoginClient {
+        ip_address: real_client_ip(req, &settings.web_app.trusted_proxies).map(|ip| ip.to_string()),
+        device_name: decide_device_name(req, data.device_name.as_deref())?,
+        location: client_location(req, &settings.web_app.trusted_proxies),
+    };
+    let session_data = totp::finish_totp_login(
+        &data.challenge_token,
+        factor,
+        client,
+        settings,
+        session,
+        pool,
+    )
+    .await?;
+
+    session_data_response(&session_data, settings)
+}
+
+#[tracing::instrument(skip(req, data, settings, session, pool), name = "Finish TOTP login")]
+pub async fn login_totp(
+    req: HttpRequest,
+    data: web::Json<TotpLoginData>,
+    settings: web::Data<Settings>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    finish_totp_login(
+        &req,
+        &data,
+        SecondFactor::Totp(data.code.trim()),
+        settings.as_ref(),
+        &session,
+        pool.as_ref(),
+    )
+    .await
+}
+
+#[tracing::instrument(
+    skip(req, data, settings, session, pool),
+    name = "Finish recovery code login"
+)]
+pub async fn login_recovery(
+    req: HttpRequest,
+    data: web::Json<TotpLoginData>,
+    settings: web::Data<Settings>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    finish_totp_login(
+        &req,
+        &data,
+        SecondFactor::RecoveryCode(&data.code),
+        settings.as_ref(),
+        &session,
+        pool.as_ref(),
+    )
+    .await
+}
+
+/// TOTP登録データ構造体
+///
+/// 既に2要素認証を有効にしている場合は、現在のコード又はリカバリーコードのどちらかを指定する。
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnrollTotpData {
+    /// 現在の共有シークレットで認証アプリが生成したコード。
+    #[serde(default)]
+    pub current_code: Option<String>,
+    /// リカバリーコード。
+    #[serde(default)]
+    pub recovery_code: Option<String>,
+}
+
+#[tracing::instrument(skip(data, settings, pool), name = "Enroll TOTP")]
+pub async fn enroll_totp(
+    user: web::ReqData<User>,
+    data: web::Json<EnrollTotpData>,
+    settings: web::Data<Settings>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current = match (&data.current_code, &data.recovery_code) {
+        (Some(code), _) => Some(SecondFactor::Totp(code.trim())),
+        (None, Some(code)) => Some(SecondFactor::RecoveryCode(code.trim())),
+        (None, None) => None,
+    };
+    let enrollment = totp::enroll_totp(&user, current, settings.as_ref(), pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+/// TOTP登録確認データ構造体
+#[derive(Debug, Deserialize)]
+pub struct ConfirmTotpData {
+    /// 確認待ちの共有シークレットで認証アプリが生成したコード。
+    pub code: String,
+}
+
+#[tracing::instrument(skip(data, pool, user_cache), name = "Confirm TOTP")]
+pub async fn confirm_totp(
+    user: web::ReqData<User>,
+    data: web::Json<ConfirmTotpData>,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recovery_codes = totp::confirm_totp(&user, data.code.trim(), pool.as_ref()).await?;
+    user_cache.invalidate(user.id().value());
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponseBody { recovery_codes }))
+}
+
+/// リカバリーコードレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponseBody {
+    /// 発行したリカバリーコード。
+    pub recovery_codes: Vec<String>,
+}
+
+#[tracing::instrument(skip(pool), name = "Regenerate recovery codes")]
+pub async fn regenerate_recovery_codes(
+    user: web::ReqData<User>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recovery_codes = totp::regenerate_recovery_codes(&user, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponseBody { recovery_codes }))
+}
+
+#[tracing::instrument(skip(req, session, pool), name = "Refresh tokens")]
+pub async fn refresh(
+    req: HttpRequest,
+    settings: web::Data<Settings>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let refresh_token = req
+        .cookie(REFRESH_TOKEN_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .unwrap_or_default();
+    let session_data =
+        accounts::refresh(&refresh_token, settings.as_ref(), &session, &pool).await?;
+
+    session_data_response(&session_data, &settings)
 }
 
 /// 有効期限の切れたトークンを記録するクッキーを作成する。
@@ -103,18 +594,157 @@ fn create_expired_token_cookies<'a>() -> (Cookie<'a>, Cookie<'a>) {
     (access, refresh)
 }
 
-#[tracing::instrument(skip(session), name = "Logout user")]
-pub async fn logout(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    // クッキーに記録しているセッションIDを削除するようにブラウザに指示して、Redisからセッションデータを削除
-    session.purge();
+#[tracing::instrument(skip(session, pool), name = "Logout user")]
+pub async fn logout(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // データベースから現在のセッションのリフレッシュトークンを削除して、クッキーに記録しているセッションIDを
+    // 削除するようにブラウザに指示して、Redisからセッションデータを削除
+    let session_id = accounts::logout(&session, pool.as_ref()).await?;
     // 有効期限のないトークン用のクッキーを生成
     let (access_token_cookie, refresh_token_cookie) = create_expired_token_cookies();
+    let body = LogoutResponseBody {
+        logged_out: true,
+        session_id: session_id.map(mask_session_id),
+        cleared_cookies: vec![ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME],
+    };
 
-    // パスワード変更に成功したら、ブラウザにクッキーを削除するように指示
+    // ログアウトに成功したら、ブラウザにクッキーを削除するように指示
     Ok(HttpResponse::Ok()
         .cookie(access_token_cookie)
         .cookie(refresh_token_cookie)
-        .finish())
+        .json(body))
+}
+
+/// ログアウトレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogoutResponseBody {
+    /// ログアウトしたか。
+    pub logged_out: bool,
+    /// 終了したセッションのマスクしたセッションID。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// ブラウザに削除を指示したクッキーの名前。
+    pub cleared_cookies: Vec<&'static str>,
+}
+
+/// セッションIDのマスクしない先頭の文字数
+const SESSION_ID_VISIBLE_LEN: usize = 8;
+
+/// セッションIDの先頭の数文字以外をマスクする。
+///
+/// # Arguments
+///
+/// * `session_id` - セッションID。
+///
+/// # Returns
+///
+/// マスクしたセッションID。
+fn mask_session_id(session_id: Uuid) -> String {
+    let session_id = session_id.to_string();
+
+    format!(
+        "{}{}",
+        &session_id[..SESSION_ID_VISIBLE_LEN],
+        "*".repeat(session_id.len() - SESSION_ID_VISIBLE_LEN)
+    )
+}
+
+/// リフレッシュトークン失効データ構造体
+///
+/// 失効させるセッションを、リフレッシュトークンの値とセッションIDのどちらか一方で指定する。
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeRefreshTokenData {
+    #[serde(default)]
+    pub refresh_token: Option<Secret<String>>,
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+}
+
+impl RevokeRefreshTokenData {
+    /// 失効させるセッションの指定を取得する。
+    ///
+    /// # Returns
+    ///
+    /// 失効させるセッションの指定。リフレッシュトークンとセッションIDの両方を指定したか、どちらも指定しなかった
+    /// 場合はエラー。
+    fn target(&self) -> Result<RevokeTarget, actix_web::Error> {
+        let refresh_token = self
+            .refresh_token
+            .as_ref()
+            .filter(|token| !token.expose_secret().trim().is_empty());
+        match (refresh_token, self.session_id) {
+            (Some(token), None) => Ok(RevokeTarget::RefreshToken(token.clone())),
+            (None, Some(session_id)) => Ok(RevokeTarget::SessionId(session_id)),
+            _ => Err(e400(
+                "リフレッシュトークンとセッションIDのどちらか一方を指定してください。",
+            )),
+        }
+    }
+}
+
+/// リフレッシュトークン失効レスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeRefreshTokenResponseBody {
+    /// リフレッシュトークンを失効させたか。
+    pub revoked: bool,
+    /// 失効させたセッションのマスクしたセッションID。
+    pub session_id: String,
+}
+
+/// リフレッシュトークン失効ハンドラ
+///
+/// ログインしているユーザーが所有するリフレッシュトークンを失効させて、そのセッションを終了させる。他の
+/// ユーザーのリフレッシュトークンを指定した場合は、存在しない場合と同様に`404 Not Found`で応答する。現在の
+/// セッションを失効させた場合は、ログアウトと同様にブラウザにトークンを記録したクッキーを削除するように指示する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(
+    skip(data, session, settings, purger, pool),
+    name = "Revoke refresh token"
+)]
+pub async fn revoke_refresh_token(
+    user: web::ReqData<User>,
+    data: web::Json<RevokeRefreshTokenData>,
+    session: TypedSession,
+    settings: web::Data<Settings>,
+    purger: web::Data<dyn SessionPurger>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = data.target()?;
+    let current_session_id = session
+        .get()
+        .map_err(e500)?
+        .map(|session_data| session_data.session_id);
+    let session_id = sessions::revoke_own_session(
+        &user,
+        target,
+        &session,
+        &settings.tokens,
+        purger.as_ref(),
+        pool.as_ref(),
+    )
+    .await?;
+    let body = RevokeRefreshTokenResponseBody {
+        revoked: true,
+        session_id: mask_session_id(session_id),
+    };
+    if current_session_id != Some(session_id) {
+        return Ok(HttpResponse::Ok().json(body));
+    }
+
+    // 現在のセッションを失効させた場合は、ブラウザにクッキーを削除するように指示
+    let (access_token_cookie, refresh_token_cookie) = create_expired_token_cookies();
+    Ok(HttpResponse::Ok()
+        .cookie(access_token_cookie)
+        .cookie(refresh_token_cookie)
+        .json(body))
 }
 
 #[derive(Debug, Deserialize)]
@@ -124,34 +754,68 @@ pub struct ChangePasswordData {
     pub new_password: Secret<String>,
 }
 
-#[tracing::instrument(skip(session, pool), name = "Change password")]
+/// 検証済みパスワード変更データ構造体
+#[derive(Debug)]
+pub struct ChangePasswordInput {
+    pub current_password: RawPassword,
+    pub new_password: RawPassword,
+}
+
+impl Validate for ChangePasswordData {
+    type Validated = ChangePasswordInput;
+
+    fn validate(self) -> Result<Self::Validated, Vec<FieldError>> {
+        let mut errors = vec![];
+        let current_password = RawPassword::new(self.current_password.expose_secret())
+            .map_err(|e| errors.extend(FieldError::from_domain("currentPassword", e)));
+        let new_password = RawPassword::new(self.new_password.expose_secret())
+            .map_err(|e| errors.extend(FieldError::from_domain("newPassword", e)));
+
+        match (current_password, new_password) {
+            (Ok(current_password), Ok(new_password)) => Ok(ChangePasswordInput {
+                current_password,
+                new_password,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip(data, webhooks, session, pool, user_cache),
+    name = "Change password"
+)]
 pub async fn change_password(
     user: web::ReqData<User>,
-    data: web::Json<ChangePasswordData>,
+    data: ValidatedJson<ChangePasswordData>,
+    settings: web::Data<Settings>,
+    webhooks: web::Data<WebhookDispatcher>,
     session: TypedSession,
     pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let current_password = RawPassword::new(data.current_password.expose_secret()).map_err(e400)?;
-    let new_password = RawPassword::new(data.new_password.expose_secret()).map_err(e400)?;
+    let ChangePasswordInput {
+        current_password,
+        new_password,
+    } = data.into_inner();
 
-    accounts::change_password(
+    let session_data = accounts::change_password(
         &user,
         current_password,
         new_password,
+        settings.as_ref(),
+        &webhooks,
         &session,
         pool.as_ref(),
     )
-    .await
-    .map_err(|e| {
-        tracing::error!("{:?}", e);
-        match e {
-            ChangePasswordError::UnexpectedError(_) => {
-                actix_web::error::ErrorInternalServerError(e)
-            }
-            ChangePasswordError::IncorrectCurrentPassword => actix_web::error::ErrorBadRequest(e),
-            ChangePasswordError::NotFound(_) => actix_web::error::ErrorBadRequest(e),
-        }
-    })?;
+    .await?;
+    // 変更前のパスワードでユーザーを認証しないように、キャッシュしたユーザーを破棄
+    user_cache.invalidate(user.id().value());
+
+    // 現在のセッションを継続する場合は、更新したトークンをクッキーに記録するように指示
+    if let Some(session_data) = session_data {
+        return session_data_response(&session_data, &settings);
+    }
 
     // 有効期限のないトークン用のクッキーを生成
     let (access_token_cookie, refresh_token_cookie) = create_expired_token_cookies();
@@ -163,15 +827,640 @@ pub async fn change_password(
         .finish())
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccountData {
+    pub password: Secret<String>,
+}
+
+#[tracing::instrument(skip(webhooks, session, pool, user_cache), name = "Delete account")]
+pub async fn delete_account(
+    user: web::ReqData<User>,
+    data: web::Json<DeleteAccountData>,
+    webhooks: web::Data<WebhookDispatcher>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    accounts::delete_account(
+        &user,
+        data.password.clone(),
+        &webhooks,
+        &session,
+        pool.as_ref(),
+    )
+    .await?;
+    user_cache.invalidate(user.id().value());
+
+    // 有効期限のないトークン用のクッキーを生成
+    let (access_token_cookie, refresh_token_cookie) = create_expired_token_cookies();
+
+    // アカウントの削除に成功したら、ブラウザにクッキーを削除するように指示
+    Ok(HttpResponse::Ok()
+        .cookie(access_token_cookie)
+        .cookie(refresh_token_cookie)
+        .finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyPasswordData {
+    pub password: Secret<String>,
+}
+
+/// トークン状態レスポンスボディ構造体
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenStatusResponseBody {
+    /// アクセストークンの有効期限までの秒数。
+    pub access_expires_in: u64,
+    /// リフレッシュトークンの有効期限までの秒数。
+    pub refresh_expires_in: u64,
+}
+
+impl TokenStatusResponseBody {
+    /// セッションデータから、トークンの有効期限までの秒数を計算する。
+    ///
+    /// 有効期限が切れている場合は`0`とする。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_data` - セッションデータ。
+    /// * `now` - 現在日時を示すUNIXエポック秒。
+    ///
+    /// # Returns
+    ///
+    /// トークン状態レスポンスボディ。
+    pub fn new(session_data: &SessionData, now: u64) -> Self {
+        Self {
+            access_expires_in: session_data.access_expiration.saturating_sub(now),
+            refresh_expires_in: session_data.refresh_expiration.saturating_sub(now),
+        }
+    }
+}
+
+/// トークン状態ハンドラ
+///
+/// クライアントがトークンの有効期限を確認できるように、アクセストークンとリフレッシュトークンの有効期限までの
+/// 秒数を返却する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(session), name = "Token status")]
+pub async fn token_status(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    let session_data = session.get().map_err(e500)?.ok_or_else(|| {
+        json_error(
+            StatusCode::UNAUTHORIZED,
+            "UNAUTHORIZED",
+            "セッションが存在しません。".to_owned(),
+        )
+    })?;
+
+    Ok(HttpResponse::Ok().json(TokenStatusResponseBody::new(
+        &session_data,
+        current_unix_epoch(),
+    )))
+}
+
+/// 現在のパスワード検証ハンドラ
+///
+/// 重要な操作の前に、ログインしているユーザーにパスワードを再度入力させて検証する。パスワードが一致した
+/// 場合は、セッションの最終認証日時を更新して、一致しない場合は、`401 Unauthorized`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(session), name = "Verify password")]
+pub async fn verify_password(
+    user: web::ReqData<User>,
+    data: web::Json<VerifyPasswordData>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    accounts::verify_current_password(&user, data.password.clone(), &session).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityQuestionData {
+    pub question: String,
+    pub answer: Secret<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSecurityQuestionsData {
+    pub questions: Vec<SecurityQuestionData>,
+}
+
+#[tracing::instrument(skip(pool), name = "Set security questions")]
+pub async fn set_security_questions(
+    user: web::ReqData<User>,
+    data: web::Json<SetSecurityQuestionsData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let questions = data
+        .into_inner()
+        .questions
+        .into_iter()
+        .map(|q| NewSecurityQuestion {
+            question: q.question,
+            answer: q.answer,
+        })
+        .collect();
+    security_questions::set_security_questions(&user, questions, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverAccountData {
+    pub email_address: String,
+    pub answers: Vec<Secret<String>>,
+}
+
+/// アカウント回復ハンドラ
+///
+/// 秘密の質問の回答を検証して、すべての回答が正しい場合は、パスワードリセットトークンを発行して、Webhookで
+/// メールなどを送信するサービスに通知する。Eメールアドレスが登録されているか推測されないように、回答が
+/// 間違っている場合も`202 Accepted`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(req, data, settings, webhooks, pool), name = "Recover account")]
+pub async fn recover_account(
+    req: HttpRequest,
+    data: web::Json<RecoverAccountData>,
+    settings: web::Data<Settings>,
+    webhooks: web::Data<WebhookDispatcher>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let data = data.into_inner();
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    if data.answers.is_empty() || SECURITY_QUESTIONS_MAX_COUNT < data.answers.len() {
+        return Err(e400(format!(
+            "秘密の質問の回答は1個から{}個指定してください。",
+            SECURITY_QUESTIONS_MAX_COUNT
+        )));
+    }
+    let client = LoginClient {
+        ip_address: real_client_ip(&req, &settings.web_app.trusted_proxies)
+            .map(|ip| ip.to_string()),
+        device_name: decide_device_name(&req, None)?,
+        location: client_location(&req, &settings.web_app.trusted_proxies),
+    };
+    security_questions::recover_account(
+        email_address,
+        data.answers,
+        client,
+        &settings.tokens,
+        webhooks.as_ref(),
+        pool.as_ref(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileVisibilityData {
+    pub email_address: String,
+    pub last_logged_in: String,
+}
+
+#[tracing::instrument(skip(pool, user_cache), name = "Set profile visibility")]
+pub async fn set_profile_visibility(
+    user: web::ReqData<User>,
+    data: web::Json<ProfileVisibilityData>,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let profile_visibility = ProfileVisibility {
+        email_address: Visibility::try_from(data.email_address.as_str()).map_err(e400)?,
+        last_logged_in: Visibility::try_from(data.last_logged_in.as_str()).map_err(e400)?,
+    };
+    users::set_profile_visibility(&user, profile_visibility, pool.as_ref()).await?;
+    user_cache.invalidate(user.id().value());
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailAddressData {
+    pub email_address: String,
+}
+
+#[tracing::instrument(skip(pool), name = "Add email alias")]
+pub async fn add_email_alias(
+    user: web::ReqData<User>,
+    data: web::Json<EmailAddressData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    email_addresses::add_email_alias(&user, email_address, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(skip(pool), name = "Remove email alias")]
+pub async fn remove_email_alias(
+    user: web::ReqData<User>,
+    data: web::Json<EmailAddressData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    email_addresses::remove_email_alias(&user, email_address, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(skip(pool, user_cache), name = "Set primary email")]
+pub async fn set_primary_email(
+    user: web::ReqData<User>,
+    data: web::Json<EmailAddressData>,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    email_addresses::set_primary_email(&user, email_address, pool.as_ref()).await?;
+    user_cache.invalidate(user.id().value());
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPasswordResetData {
+    pub email_address: String,
+}
+
+/// パスワードリセット要求ハンドラ
+///
+/// パスワードリセットトークンを発行して、Webhookでメールなどを送信するサービスに通知する。Eメールアドレスが
+/// 登録されているか推測されないように、ユーザーが存在しない場合も`202 Accepted`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(settings, webhooks, pool), name = "Request password reset")]
+pub async fn request_password_reset(
+    data: web::Json<RequestPasswordResetData>,
+    settings: web::Data<Settings>,
+    webhooks: web::Data<WebhookDispatcher>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    password_resets::request_password_reset(
+        email_address,
+        &settings.tokens,
+        webhooks.as_ref(),
+        pool.as_ref(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordData {
+    pub token: Secret<String>,
+    pub email_address: String,
+    pub new_password: Secret<String>,
+}
+
+#[tracing::instrument(skip(settings, pool, user_cache), name = "Reset password")]
+pub async fn reset_password(
+    data: web::Json<ResetPasswordData>,
+    settings: web::Data<Settings>,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let data = data.into_inner();
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    let new_password = RawPassword::new(data.new_password.expose_secret()).map_err(e400)?;
+    let user_id = password_resets::reset_password(
+        data.token,
+        email_address,
+        new_password,
+        &settings.tokens,
+        pool.as_ref(),
+    )
+    .await?;
+    user_cache.invalidate(user_id.value());
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(skip(webauthn, session, pool), name = "Start passkey registration")]
+pub async fn start_passkey_registration(
+    user: web::ReqData<User>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let options = passkeys::start_registration(&user, &webauthn, &session, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+#[tracing::instrument(
+    skip(data, webauthn, session, pool),
+    name = "Finish passkey registration"
+)]
+pub async fn finish_passkey_registration(
+    user: web::ReqData<User>,
+    data: web::Json<RegisterPublicKeyCredential>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    passkeys::finish_registration(&user, &data, &webauthn, &session, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyLoginStartData {
+    pub email_address: String,
+}
+
+#[tracing::instrument(skip(settings, webauthn, session, pool), name = "Start passkey login")]
+pub async fn start_passkey_login(
+    data: web::Json<PasskeyLoginStartData>,
+    settings: web::Data<Settings>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email_address = EmailAddress::new(&data.email_address).map_err(e400)?;
+    let options = passkeys::start_authentication(
+        email_address,
+        &settings,
+        &webauthn,
+        &session,
+        pool.as_ref(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyLoginData {
+    /// `navigator.credentials.get()`が返却したアサーション
+    #[serde(flatten)]
+    pub credential: PublicKeyCredential,
+    /// デバイス名
+    ///
+    /// 指定しなかった場合は、User-Agentから推測する。
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+#[tracing::instrument(
+    skip(req, data, settings, webauthn, session, pool),
+    name = "Finish passkey login"
+)]
+pub async fn finish_passkey_login(
+    req: HttpRequest,
+    data: web::Json<PasskeyLoginData>,
+    settings: web::Data<Settings>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let client = LoginClient {
+        ip_address: real_client_ip(&req, &settings.web_app.trusted_proxies)
+            .map(|ip| ip.to_string()),
+        device_name: decide_device_name(&req, data.device_name.as_deref())?,
+        location: client_location(&req, &settings.web_app.trusted_proxies),
+    };
+    let outcome = passkeys::finish_authentication(
+        &data.credential,
+        client,
+        settings.as_ref(),
+        &webauthn,
+        &session,
+        pool.as_ref(),
+    )
+    .await?;
+
+    match outcome {
+        PasskeyLoginOutcome::Authenticated(session_data) => {
+            session_data_response(&session_data, &settings)
+        }
+        PasskeyLoginOutcome::TotpRequired(challenge_token) => {
+            Ok(HttpResponse::Accepted().json(TotpChallengeResponseBody { challenge_token }))
+        }
+    }
+}
+
 /// アカウントスコープを返却する。
 pub fn accounts_scope() -> actix_web::Scope {
     web::scope("/accounts")
         .service(web::resource("/signup").route(web::post().to(signup)))
         .service(web::resource("/login").route(web::post().to(login)))
+        .service(web::resource("/login/totp").route(web::post().to(login_totp)))
+        .service(web::resource("/login/recovery").route(web::post().to(login_recovery)))
+        .service(web::resource("/refresh").route(web::post().to(refresh)))
+        .service(web::resource("/reset_password").route(web::post().to(reset_password)))
+        .service(
+            web::resource("/reset_password/request").route(web::post().to(request_password_reset)),
+        )
+        .service(web::resource("/recover").route(web::post().to(recover_account)))
+        .service(web::resource("/webauthn/login/start").route(web::post().to(start_passkey_login)))
+        .service(
+            web::resource("/webauthn/login/finish").route(web::post().to(finish_passkey_login)),
+        )
         .service(
             web::scope("")
                 .wrap(JwtAuth)
                 .service(web::resource("/logout").route(web::post().to(logout)))
-                .service(web::resource("/change_password").route(web::post().to(change_password))),
+                .service(
+                    web::resource("/refresh/revoke").route(web::post().to(revoke_refresh_token)),
+                )
+                .service(web::resource("/change_password").route(web::post().to(change_password)))
+                .service(web::resource("/me").route(web::delete().to(delete_account)))
+                .service(web::resource("/verify_password").route(web::post().to(verify_password)))
+                .service(web::resource("/token_status").route(web::get().to(token_status)))
+                .service(web::resource("/totp").route(web::post().to(enroll_totp)))
+                .service(web::resource("/totp/confirm").route(web::post().to(confirm_totp)))
+                .service(
+                    web::resource("/totp/recovery_codes")
+                        .route(web::post().to(regenerate_recovery_codes)),
+                )
+                .service(
+                    web::resource("/security_questions")
+                        .route(web::put().to(set_security_questions)),
+                )
+                .service(
+                    web::resource("/profile_visibility")
+                        .route(web::put().to(set_profile_visibility)),
+                )
+                .service(
+                    web::resource("/email_addresses")
+                        .route(web::post().to(add_email_alias))
+                        .route(web::delete().to(remove_email_alias)),
+                )
+                .service(
+                    web::resource("/email_addresses/primary")
+                        .route(web::put().to(set_primary_email)),
+                )
+                .service(
+                    web::resource("/webauthn/register/start")
+                        .route(web::post().to(start_passkey_registration)),
+                )
+                .service(
+                    web::resource("/webauthn/register/finish")
+                        .route(web::post().to(finish_passkey_registration)),
+                ),
         )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configurations::tokens::RedactedToken;
+
+    /// ログインとログアウトのレスポンスボディが、リクエストボディと同じキャメルケースのキーを使用することを
+    /// 確認するテスト
+    #[test]
+    fn response_bodies_use_camel_case_keys() {
+        let login = serde_json::to_value(LoginResponseBody {
+            must_change_password: true,
+            password_meets_current_policy: false,
+        })
+        .unwrap();
+        assert_eq!(
+            login,
+            serde_json::json!({ "mustChangePassword": true, "passwordMeetsCurrentPolicy": false })
+        );
+        let logout = serde_json::to_value(LogoutResponseBody {
+            logged_out: true,
+            session_id: Some("67e55044".to_owned()),
+            cleared_cookies: vec![ACCESS_TOKEN_COOKIE_NAME],
+        })
+        .unwrap();
+        assert_eq!(
+            logout,
+            serde_json::json!({
+                "loggedOut": true,
+                "sessionId": "67e55044",
+                "clearedCookies": [ACCESS_TOKEN_COOKIE_NAME],
+            })
+        );
+    }
+
+    /// 同じオリジンの相対パスを、ログインに成功した後のリダイレクト先として許可することを確認するテスト
+    #[test]
+    fn next_path_accepts_relative_paths() {
+        for next in [
+            "/",
+            "/dashboard",
+            "/users/me?tab=profile#top",
+            "/search?q=//example.com",
+        ] {
+            assert_eq!(validate_next_path(next).unwrap(), next, "{}", next);
+        }
+    }
+
+    /// 外部のURLを、ログインに成功した後のリダイレクト先として拒否することを確認するテスト
+    #[test]
+    fn next_path_rejects_external_urls() {
+        let too_long = format!("/{}", "x".repeat(NEXT_PATH_MAX_LEN));
+        for next in [
+            "",
+            "dashboard",
+            "https://evil.com",
+            "http://example.com/dashboard",
+            "javascript:alert(1)",
+            "//evil.com",
+            "///evil.com",
+            "/\\evil.com",
+            "\\\\evil.com",
+            "/\t/evil.com",
+            "/\r\nLocation: https://evil.com",
+            too_long.as_str(),
+        ] {
+            assert!(validate_next_path(next).is_err(), "{:?}", next);
+        }
+    }
+
+    #[test]
+    fn session_id_is_masked() {
+        let session_id = Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        assert_eq!(
+            mask_session_id(session_id),
+            "67e55044****************************"
+        );
+    }
+
+    /// サインアップとログインで受け取ったEメールアドレスの前後の空白を削除して、小文字に正規化することを
+    /// 確認するテスト
+    #[test]
+    fn email_address_is_normalized_at_signup_and_login() {
+        let data: SignupData = serde_json::from_value(serde_json::json!({
+            "userName": "foo",
+            "emailAddress": "  Foo@Example.com ",
+            "password": "tOC8pHh:K/-G",
+        }))
+        .unwrap();
+        let input = data.validate().unwrap();
+        assert_eq!(input.email_address.value(), "foo@example.com");
+
+        let data: LoginData = serde_json::from_value(serde_json::json!({
+            "emailAddress": "  Foo@Example.com ",
+            "password": "tOC8pHh:K/-G",
+        }))
+        .unwrap();
+        match data.identifier().unwrap() {
+            LoginIdentifier::EmailAddress(email_address) => {
+                assert_eq!(email_address.value(), "foo@example.com")
+            }
+            _ => panic!("Eメールアドレスでログインするユーザーを識別していません。"),
+        }
+    }
+
+    /// トークンの有効期限までの秒数が、時間の経過とともに減少して、有効期限が切れた後は`0`になることを
+    /// 確認するテスト
+    #[test]
+    fn token_status_counts_down_to_zero() {
+        let session_data = SessionData {
+            session_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            access_token: RedactedToken::new("access"),
+            access_expiration: 1_000 + 300,
+            refresh_token: RedactedToken::new("refresh"),
+            refresh_expiration: 1_000 + 3_600,
+            generation: 0,
+            last_accessed_at: 0,
+            last_authenticated_at: 0,
+            device_name: None,
+            refresh_count: 0,
+            version: 0,
+        };
+        let statuses: Vec<TokenStatusResponseBody> = [1_000, 1_060, 1_300, 5_000]
+            .into_iter()
+            .map(|now| TokenStatusResponseBody::new(&session_data, now))
+            .collect();
+        let expected = [(300, 3_600), (240, 3_540), (0, 3_300), (0, 0)];
+        for (status, (access, refresh)) in statuses.iter().zip(expected) {
+            assert_eq!(
+                status,
+                &TokenStatusResponseBody {
+                    access_expires_in: access,
+                    refresh_expires_in: refresh,
+                }
+            );
+        }
+    }
+}
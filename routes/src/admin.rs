@@ -0,0 +1,173 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use configurations::{AdminSettings, Settings};
+use domains::models::users::{RawPassword, User, UserRole};
+use middlewares::{require_role::RequireRole, user_cache::UserCache, JwtAuth};
+use usecases::{
+    invite_codes, password_resets,
+    sessions::{self, SessionPurger},
+};
+
+use crate::responses::{e400, e404, json_error};
+
+/// 管理者APIキーを指定するヘッダー
+pub const ADMIN_API_KEY_HEADER: &str = "X-Admin-Api-Key";
+
+/// 管理者が操作するテナントのテナントIDを指定するヘッダー
+pub const ADMIN_TENANT_ID_HEADER: &str = "X-Tenant-Id";
+
+/// リクエストが管理者APIキーを指定しているか確認して、管理者が操作するテナントのテナントIDを返却する。
+///
+/// 管理者APIキーが設定されていない場合は、管理者APIが存在しないものとして`404 Not Found`を、ヘッダーの
+/// APIキーが一致しない場合は`401 Unauthorized`を返却する。APIキーは、処理時間から推測されないように
+/// 定数時間で比較する。
+///
+/// 管理者APIは、指定されたテナントのユーザーとセッションのみを操作する。テナントIDを指定していないか、
+/// テナントIDとして解釈できない場合は、すべてのテナントを操作できないように`400 Bad Request`を返却する。
+fn authorize_admin(req: &HttpRequest, settings: &AdminSettings) -> Result<Uuid, actix_web::Error> {
+    let expected = settings
+        .api_key
+        .as_ref()
+        .ok_or_else(|| e404("リソースが見つかりません。"))?;
+    let actual = req
+        .headers()
+        .get(ADMIN_API_KEY_HEADER)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    if !bool::from(expected.expose_secret().as_bytes().ct_eq(actual)) {
+        return Err(json_error(
+            StatusCode::UNAUTHORIZED,
+            "UNAUTHORIZED",
+            "管理者APIキーが一致しません。".to_owned(),
+        ));
+    }
+
+    req.headers()
+        .get(ADMIN_TENANT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value).ok())
+        .ok_or_else(|| {
+            json_error(
+                StatusCode::BAD_REQUEST,
+                "TENANT_REQUIRED",
+                format!(
+                    "{}ヘッダーに操作するテナントのテナントIDを指定してください。",
+                    ADMIN_TENANT_ID_HEADER
+                ),
+            )
+        })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminResetPasswordData {
+    pub new_password: Secret<String>,
+}
+
+/// 管理者パスワードリセットハンドラ
+///
+/// ユーザーのパスワードをリセットして、ユーザーのすべてのセッションを失効させる。ユーザーは、次回のログインで
+/// パスワードを変更するまで、パスワードの変更以外の保護されたリソースにアクセスできない。他のテナントの
+/// ユーザーは、存在しないユーザーと同様に`404 Not Found`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(
+    skip(req, data, settings, pool, user_cache),
+    name = "Admin reset password"
+)]
+pub async fn reset_password(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    data: web::Json<AdminResetPasswordData>,
+    settings: web::Data<Settings>,
+    pool: web::Data<PgPool>,
+    user_cache: web::Data<UserCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = authorize_admin(&req, &settings.admin)?;
+    let new_password = RawPassword::new(data.new_password.expose_secret()).map_err(e400)?;
+    let user_id = path.into_inner();
+    password_resets::admin_reset_password(user_id, tenant_id, new_password, pool.as_ref()).await?;
+    // パスワードの変更を要求したユーザーを認証ミドルウェアが取得し直すように、キャッシュしたユーザーを破棄
+    user_cache.invalidate(user_id);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// 招待コードレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteCodeResponseBody {
+    /// 発行した招待コード。
+    pub invite_code: String,
+}
+
+/// 招待コード発行ハンドラ
+///
+/// サインアップを停止しているときに、招待した利用者がサインアップできる招待コードを発行する。招待コードで
+/// サインアップしたユーザーは、指定されたテナントに所属する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(req, settings, pool), name = "Admin issue invite code")]
+pub async fn issue_invite_code(
+    req: HttpRequest,
+    settings: web::Data<Settings>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = authorize_admin(&req, &settings.admin)?;
+    let invite_code =
+        invite_codes::issue_invite_code(tenant_id, &settings.tokens, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(InviteCodeResponseBody {
+        invite_code: invite_code.expose_secret().to_owned(),
+    }))
+}
+
+/// 管理者セッション失効ハンドラ
+///
+/// 不正アクセスなどに対応するため、指定されたセッションを失効させて、セッションストアからも削除する。
+/// 管理者APIキーではなく、管理者の役割を持つユーザーのセッションで認証して、管理者が所属するテナントの
+/// ユーザーのセッションのみを失効させる。セッションが存在しないか、他のテナントのユーザーのセッションの
+/// 場合は、`404 Not Found`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(admin, purger, pool), name = "Admin revoke session")]
+pub async fn revoke_session(
+    path: web::Path<Uuid>,
+    admin: web::ReqData<User>,
+    purger: web::Data<dyn SessionPurger>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    sessions::revoke_session(
+        path.into_inner(),
+        admin.tenant_id(),
+        purger.as_ref(),
+        pool.as_ref(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// 管理者スコープを返却する。
+pub fn admin_scope() -> actix_web::Scope {
+    web::scope("/admin")
+        .service(web::resource("/users/{id}/reset_password").route(web::post().to(reset_password)))
+        .service(web::resource("/invite_codes").route(web::post().to(issue_invite_code)))
+        .service(
+            web::resource("/sessions/{id}")
+                .wrap(RequireRole::new(UserRole::Admin))
+                .wrap(JwtAuth)
+                .route(web::delete().to(revoke_session)),
+        )
+}
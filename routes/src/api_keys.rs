@@ -0,0 +1,155 @@
+use actix_web::{cookie::time::Duration, http::StatusCode, web, HttpResponse};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use configurations::Settings;
+use domains::models::{
+    api_keys::{ApiKeyScope, API_KEY_MAX_SCOPES},
+    users::User,
+};
+use middlewares::{ApiKeyContext, JwtAuth};
+use usecases::api_keys;
+
+use crate::extractors::{FieldError, Validate, ValidatedJson};
+use crate::responses::json_error;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueApiKeyData {
+    /// APIキーのスコープ
+    pub scopes: Vec<String>,
+    /// APIキーの有効期間（秒）。省略した場合は、有効期限を設定しない。
+    pub expires_in_seconds: Option<i64>,
+}
+
+/// 検証済みのAPIキー発行リクエストボディ
+#[derive(Debug)]
+pub struct IssueApiKeyInput {
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_in: Option<Duration>,
+}
+
+impl Validate for IssueApiKeyData {
+    type Validated = IssueApiKeyInput;
+
+    fn validate(self) -> Result<Self::Validated, Vec<FieldError>> {
+        let mut errors = vec![];
+        if API_KEY_MAX_SCOPES < self.scopes.len() {
+            errors.push(FieldError::new(
+                "scopes",
+                format!("スコープは{}個以内で指定してください。", API_KEY_MAX_SCOPES),
+            ));
+        }
+        let scopes = self
+            .scopes
+            .iter()
+            .map(|scope| ApiKeyScope::new(scope))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| errors.push(FieldError::new("scopes", e)));
+        let expires_in = match self.expires_in_seconds {
+            Some(seconds) if seconds <= 0 => {
+                errors.push(FieldError::new(
+                    "expiresInSeconds",
+                    "有効期間は1秒以上で指定してください。",
+                ));
+                None
+            }
+            seconds => seconds.map(Duration::seconds),
+        };
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(IssueApiKeyInput {
+            scopes: scopes.unwrap_or_default(),
+            expires_in,
+        })
+    }
+}
+
+/// APIキーレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyResponseBody {
+    /// APIキーID。
+    pub id: Uuid,
+    /// 発行したAPIキー。
+    pub api_key: String,
+}
+
+/// APIキーで認証したリクエストで、APIキーを発行又は失効できないように拒否する。
+///
+/// APIキーが漏洩した場合に、漏洩したAPIキーで新しいAPIキーを発行したり、他のAPIキーを失効させたりできない
+/// ように、APIキーの管理はセッションで認証したリクエストに限定する。
+fn reject_api_key_request(
+    context: Option<web::ReqData<ApiKeyContext>>,
+) -> Result<(), actix_web::Error> {
+    if context.is_some() {
+        return Err(json_error(
+            StatusCode::FORBIDDEN,
+            "FORBIDDEN",
+            "APIキーで認証したリクエストでは、APIキーを管理できません。".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// APIキー発行ハンドラ
+///
+/// サービスアカウントなど、セッションを使用しないクライアントが使用するAPIキーを発行する。発行したAPIキーは
+/// 再度取得できないため、クライアントはこの応答でAPIキーを記録する必要がある。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(data, context, settings, pool), name = "Issue API key")]
+pub async fn issue_api_key(
+    user: web::ReqData<User>,
+    data: ValidatedJson<IssueApiKeyData>,
+    context: Option<web::ReqData<ApiKeyContext>>,
+    settings: web::Data<Settings>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    reject_api_key_request(context)?;
+    let IssueApiKeyInput { scopes, expires_in } = data.into_inner();
+    let issued =
+        api_keys::issue_api_key(&user, scopes, expires_in, &settings.tokens, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiKeyResponseBody {
+        id: issued.id,
+        api_key: issued.api_key.expose_secret().to_owned(),
+    }))
+}
+
+/// APIキー失効ハンドラ
+///
+/// ユーザーのAPIキーを失効させる。ユーザーのAPIキーが見つからない場合は、`404 Not Found`で応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(context, pool), name = "Revoke API key")]
+pub async fn revoke_api_key(
+    user: web::ReqData<User>,
+    path: web::Path<Uuid>,
+    context: Option<web::ReqData<ApiKeyContext>>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    reject_api_key_request(context)?;
+    api_keys::revoke_api_key(&user, path.into_inner(), pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// APIキースコープを返却する。
+pub fn api_keys_scope() -> actix_web::Scope {
+    web::scope("/api_keys").service(
+        web::scope("")
+            .wrap(JwtAuth)
+            .service(web::resource("").route(web::post().to(issue_api_key)))
+            .service(web::resource("/{id}").route(web::delete().to(revoke_api_key))),
+    )
+}
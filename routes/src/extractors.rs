@@ -0,0 +1,296 @@
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+use actix_web::{
+    dev::Payload, error::InternalError, http::StatusCode, web, FromRequest, HttpRequest,
+    HttpResponse,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use domains::models::DomainError;
+
+/// 検証に失敗したフィールドのエラー
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    /// 検証に失敗したフィールド名
+    pub field: &'static str,
+    /// 満たさなかった制約のコード（`length`、`email`など）
+    ///
+    /// ドメインの型の構築に失敗して、満たさなかった制約が判明している場合に設定する。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// エラーメッセージ
+    pub message: String,
+}
+
+impl FieldError {
+    /// フィールドのエラーを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - 検証に失敗したフィールド名。
+    /// * `e` - 検証エラー。
+    ///
+    /// # Returns
+    ///
+    /// フィールドのエラー。
+    pub fn new<E: std::fmt::Display>(field: &'static str, e: E) -> Self {
+        Self {
+            field,
+            code: None,
+            message: e.to_string(),
+        }
+    }
+
+    /// ドメインの型の構築に失敗したエラーから、フィールドのエラーを構築する。
+    ///
+    /// 満たさなかった制約ごとに、制約のコードとエラーメッセージを設定したフィールドのエラーを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - 検証に失敗したフィールド名。
+    /// * `e` - ドメインの型の構築に失敗したエラー。
+    ///
+    /// # Returns
+    ///
+    /// フィールドのエラー。
+    pub fn from_domain(field: &'static str, e: DomainError) -> Vec<Self> {
+        let message = e.to_string();
+        let errors = e.validation_errors();
+        if errors.is_empty() {
+            return vec![Self::new(field, message)];
+        }
+
+        errors
+            .into_iter()
+            .map(|error| Self {
+                field,
+                code: Some(error.code),
+                message: message.clone(),
+            })
+            .collect()
+    }
+}
+
+/// 検証エラーレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+pub struct ValidationErrorResponseBody {
+    /// エラーコード
+    pub code: &'static str,
+    /// エラーメッセージ
+    pub message: String,
+    /// 検証に失敗したフィールドのエラー
+    pub errors: Vec<FieldError>,
+}
+
+/// リクエストボディの検証トレイト
+///
+/// デシリアライズしたリクエストボディを検証して、ドメインの型で構成した検証済みの値に変換する。
+pub trait Validate: DeserializeOwned {
+    /// 検証済みの値の型
+    type Validated;
+
+    /// リクエストボディを検証する。
+    ///
+    /// 最初に検証に失敗したフィールドで中断せずに、すべてのフィールドを検証する。
+    ///
+    /// # Returns
+    ///
+    /// 検証済みの値。検証に失敗した場合は、検証に失敗したフィールドのエラー。
+    fn validate(self) -> Result<Self::Validated, Vec<FieldError>>;
+}
+
+/// 検証済みJSON抽出器
+///
+/// JSONのリクエストボディをデシリアライズした後に検証して、検証済みの値をハンドラに渡す。
+/// デシリアライズに失敗した場合は`web::Json`と同様に、`JsonConfig`のエラーハンドラでエラーを応答する。
+/// 検証に失敗した場合は、ハンドラを呼び出さずに、検証に失敗したフィールドのエラーを
+/// `422 Unprocessable Entity`で応答する。
+pub struct ValidatedJson<T: Validate>(pub T::Validated);
+
+impl<T> std::fmt::Debug for ValidatedJson<T>
+where
+    T: Validate,
+    T::Validated: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ValidatedJson").field(&self.0).finish()
+    }
+}
+
+impl<T: Validate> ValidatedJson<T> {
+    /// デシリアライズしたリクエストボディを検証して、検証済みJSON抽出器を構築する。
+    ///
+    /// 検証する前にリクエストボディの内容を確認する必要があるハンドラは、`web::Json`で受け取ったリクエスト
+    /// ボディをこの関数で検証する。
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - デシリアライズしたリクエストボディ。
+    ///
+    /// # Returns
+    ///
+    /// 検証済みJSON抽出器。検証に失敗した場合は、`422 Unprocessable Entity`のエラー。
+    pub fn from_data(data: T) -> Result<Self, actix_web::Error> {
+        data.validate().map(ValidatedJson).map_err(validation_error)
+    }
+
+    /// 検証済みの値を返却する。
+    pub fn into_inner(self) -> T::Validated {
+        self.0
+    }
+}
+
+impl<T: Validate> Deref for ValidatedJson<T> {
+    type Target = T::Validated;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// 検証に失敗したフィールドのエラーを、`422 Unprocessable Entity`のエラーレスポンスに変換する。
+fn validation_error(errors: Vec<FieldError>) -> actix_web::Error {
+    let message = "リクエストボディの検証に失敗しました。".to_owned();
+    let body = ValidationErrorResponseBody {
+        code: "VALIDATION_FAILED",
+        message: message.clone(),
+        errors,
+    };
+    InternalError::from_response(
+        message,
+        HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(body),
+    )
+    .into()
+}
+
+impl<T> FromRequest for ValidatedJson<T>
+where
+    T: Validate + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            let data = json.await?.into_inner();
+            Self::from_data(data)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct NumbersData {
+        small: i32,
+        large: i32,
+    }
+
+    impl Validate for NumbersData {
+        type Validated = (i32, i32);
+
+        fn validate(self) -> Result<Self::Validated, Vec<FieldError>> {
+            let mut errors = vec![];
+            if 10 <= self.small {
+                errors.push(FieldError::new("small", "10未満で指定してください。"));
+            }
+            if self.large < 10 {
+                errors.push(FieldError::new("large", "10以上で指定してください。"));
+            }
+            if !errors.is_empty() {
+                return Err(errors);
+            }
+
+            Ok((self.small, self.large))
+        }
+    }
+
+    async fn numbers(data: ValidatedJson<NumbersData>) -> HttpResponse {
+        let (small, large) = data.into_inner();
+        assert!(
+            small < 10 && 10 <= large,
+            "検証されていない値を受け取りました。"
+        );
+
+        HttpResponse::Ok().finish()
+    }
+
+    /// 検証に失敗した場合は、ハンドラを呼び出さずに、すべてのフィールドのエラーを構造化して返却する
+    /// ことを確認するテスト
+    #[actix_web::test]
+    async fn validated_json_returns_structured_errors() {
+        let app = init_service(App::new().route("/", web::post().to(numbers))).await;
+
+        let req = TestRequest::post()
+            .uri("/")
+            .set_json(serde_json::json!({ "small": 10, "large": 9 }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["code"], "VALIDATION_FAILED");
+        let fields: Vec<&str> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["small", "large"]);
+
+        // 検証に成功した場合は、ハンドラに検証済みの値を渡す
+        let req = TestRequest::post()
+            .uri("/")
+            .set_json(serde_json::json!({ "small": 9, "large": 10 }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    /// ドメインの型の検証エラーから、満たさなかった制約のコードを含むフィールドのエラーを構築することを
+    /// 確認するテスト
+    #[test]
+    fn field_error_from_domain_includes_code() {
+        let e = domains::models::EmailAddress::new("email.example.com").unwrap_err();
+        let errors = FieldError::from_domain("emailAddress", e);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "emailAddress");
+        assert_eq!(errors[0].code.as_deref(), Some("email"));
+        let value = serde_json::to_value(&errors[0]).unwrap();
+        assert_eq!(value["code"], "email");
+
+        let e = domains::models::users::RawPassword::new("Abcdefg1").unwrap_err();
+        let errors = FieldError::from_domain("password", e);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code.as_deref(), Some("character_class"));
+        assert_eq!(errors[0].message, "パスワードに記号が含まれていません。");
+
+        // 制約が判明していないエラーは、コードを含めない
+        let error = FieldError::new("password", "error");
+        assert!(error.code.is_none());
+        let value = serde_json::to_value(&error).unwrap();
+        assert!(value.get("code").is_none());
+    }
+
+    /// デシリアライズに失敗した場合は、検証せずに`400 Bad Request`を返却することを確認するテスト
+    #[actix_web::test]
+    async fn validated_json_rejects_invalid_json() {
+        let app = init_service(App::new().route("/", web::post().to(numbers))).await;
+
+        let req = TestRequest::post()
+            .uri("/")
+            .set_json(serde_json::json!({ "small": 1 }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,4 +1,5 @@
 use actix_web::HttpResponse;
+use serde::Serialize;
 
 /// ヘルスチェックハンドラ
 ///
@@ -9,3 +10,31 @@ use actix_web::HttpResponse;
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().body("Are you ready?")
 }
+
+/// バージョンレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResponseBody {
+    /// クレートのバージョン
+    pub version: &'static str,
+    /// ビルドしたコミット
+    pub git_commit: &'static str,
+    /// ビルドした日時（RFC3339形式）
+    pub build_timestamp: &'static str,
+}
+
+/// バージョンハンドラ
+///
+/// デプロイしたビルドを確認できるように、クレートのバージョンと、ビルドしたコミット及び日時を応答する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(name = "version")]
+pub async fn version() -> HttpResponse {
+    HttpResponse::Ok().json(VersionResponseBody {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT"),
+        build_timestamp: env!("BUILD_TIMESTAMP"),
+    })
+}
@@ -1,4 +1,9 @@
 pub mod accounts;
+pub mod admin;
+pub mod api_keys;
+pub mod extractors;
 pub mod health_check;
 pub mod protected_resource;
 pub mod responses;
+pub mod users;
+pub mod websocket;
@@ -1,13 +1,70 @@
 use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use domains::models::users::User;
+use middlewares::{RefreshOccurred, SessionContext};
+
+/// サンプル保護リソースの取得に必要なAPIキーのスコープ
+pub const PROTECTED_RESOURCE_READ_SCOPE: &str = "protected_resource:read";
+
+/// サンプル保護リソースクエリパラメーター構造体
+#[derive(Debug, Deserialize)]
+pub struct ProtectedResourceQuery {
+    /// 認証したユーザーとトークンの情報を応答するか
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+/// サンプル保護リソースの詳細レスポンスボディ構造体
+///
+/// クライアントが認証をデバッグできるように、認証したユーザーとトークンの情報を応答する。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtectedResourceBody {
+    /// ユーザーID
+    pub user_id: Uuid,
+    /// テナントID
+    pub tenant_id: Uuid,
+    /// 役割
+    pub role: &'static str,
+    /// アクセストークン有効期限（UNIXエポック秒）
+    ///
+    /// APIキーで認証した場合は`None`。
+    pub access_expiration: Option<u64>,
+    /// リフレッシュトークン有効期限（UNIXエポック秒）
+    ///
+    /// APIキーで認証した場合は`None`。
+    pub refresh_expiration: Option<u64>,
+    /// このリクエストでトークンをリフレッシュしたか
+    pub refreshed: bool,
+}
 
 /// サンプル保護リソースハンドラ
 ///
+/// クエリパラメーター`verbose`に`true`を指定した場合は、ユーザーIDの代わりに、認証したユーザーとトークンの
+/// 情報をJSONで応答する。
+///
 /// # Returns
 ///
 /// Httpレスポンス。
 #[tracing::instrument(name = "Sample protected resource")]
-pub async fn protected_resource(user: web::ReqData<User>) -> HttpResponse {
-    HttpResponse::Ok().body(user.id().value().to_string())
+pub async fn protected_resource(
+    user: web::ReqData<User>,
+    query: web::Query<ProtectedResourceQuery>,
+    session: Option<web::ReqData<SessionContext>>,
+    refresh_occurred: web::ReqData<RefreshOccurred>,
+) -> HttpResponse {
+    if !query.verbose {
+        return HttpResponse::Ok().body(user.id().value().to_string());
+    }
+
+    HttpResponse::Ok().json(ProtectedResourceBody {
+        user_id: user.id().value(),
+        tenant_id: user.tenant_id(),
+        role: user.role().as_str(),
+        access_expiration: session.as_ref().map(|session| session.access_expiration),
+        refresh_expiration: session.as_ref().map(|session| session.refresh_expiration),
+        refreshed: refresh_occurred.0,
+    })
 }
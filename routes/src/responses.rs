@@ -1,3 +1,10 @@
+use actix_web::{
+    error::{InternalError, JsonPayloadError},
+    http::StatusCode,
+    web, HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+
 // エラールートのログの原因を保持しながら、不透明な500を返します。
 pub fn e500<T>(e: T) -> actix_web::Error
 where
@@ -6,9 +13,151 @@ where
     actix_web::error::ErrorInternalServerError(e)
 }
 
-pub fn e400<T: std::fmt::Debug + std::fmt::Display>(e: T) -> actix_web::Error
+pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
     actix_web::error::ErrorBadRequest(e)
 }
+
+pub fn e404<T>(e: T) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    actix_web::error::ErrorNotFound(e)
+}
+
+/// エラーレスポンスボディ構造体
+#[derive(Debug, Serialize)]
+pub struct ErrorResponseBody {
+    /// エラーコード
+    pub code: &'static str,
+    /// エラーメッセージ
+    pub message: String,
+    /// エラーの原因となったフィールド
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+/// JSONでエラーを応答するエラーを生成する。
+///
+/// # Arguments
+///
+/// * `status` - HTTPステータスコード。
+/// * `code` - エラーコード。
+/// * `message` - エラーメッセージ。
+///
+/// # Returns
+///
+/// エラー。
+pub fn json_error(status: StatusCode, code: &'static str, message: String) -> actix_web::Error {
+    let body = ErrorResponseBody {
+        code,
+        message: message.clone(),
+        field: None,
+    };
+    InternalError::from_response(message, HttpResponse::build(status).json(body)).into()
+}
+
+/// 一致するルートがない場合に、`404 Not Found`をJSONで返却するハンドラ。
+pub async fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponseBody {
+        code: "NOT_FOUND",
+        message: "リソースが見つかりません。".to_owned(),
+        field: None,
+    })
+}
+
+/// JSONのデシリアライズエラーのメッセージから、エラーの原因となったフィールドを取得する。
+///
+/// `missing field `email_address``のように、メッセージにフィールド名が含まれている場合のみ取得できる。
+///
+/// # Arguments
+///
+/// * `message` - デシリアライズエラーのメッセージ。
+///
+/// # Returns
+///
+/// エラーの原因となったフィールド名。取得できなかった場合は`None`。
+fn offending_field(message: &str) -> Option<String> {
+    ["missing field `", "unknown field `", "duplicate field `"]
+        .iter()
+        .find_map(|prefix| {
+            let start = message.find(prefix)? + prefix.len();
+            let len = message[start..].find('`')?;
+            Some(message[start..start + len].to_owned())
+        })
+}
+
+/// JSONペイロードを抽出するときのエラーを、JSONのエラーレスポンスに変換する。
+fn json_payload_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "PAYLOAD_TOO_LARGE",
+                format!("{}", err),
+            )
+        }
+        JsonPayloadError::ContentType => json_error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UNSUPPORTED_MEDIA_TYPE",
+            "Content-Typeにapplication/jsonを指定してください。".to_owned(),
+        ),
+        JsonPayloadError::Deserialize(e) => {
+            let message = format!("JSONを解釈できません。{}", e);
+            let body = ErrorResponseBody {
+                code: "INVALID_JSON",
+                message: message.clone(),
+                field: offending_field(&e.to_string()),
+            };
+            InternalError::from_response(message, HttpResponse::BadRequest().json(body)).into()
+        }
+        _ => err.into(),
+    }
+}
+
+/// アカウントAPIで使用するJSON抽出設定を返却する。
+///
+/// # Arguments
+///
+/// * `limit` - JSONペイロードの最大バイト数。
+/// * `content_type_required` - JSONのContent-Typeを要求するか。
+///
+/// # Returns
+///
+/// JSON抽出設定。
+pub fn json_config(limit: usize, content_type_required: bool) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .content_type_required(content_type_required)
+        .error_handler(json_payload_error_handler)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offending_field_is_extracted_from_message() {
+        assert_eq!(
+            offending_field("missing field `emailAddress` at line 1 column 24").as_deref(),
+            Some("emailAddress")
+        );
+        assert_eq!(
+            offending_field("unknown field `foo`, expected `bar`").as_deref(),
+            Some("foo")
+        );
+        assert!(offending_field("expected value at line 1 column 1").is_none());
+    }
+
+    #[test]
+    fn content_type_error_is_unsupported_media_type() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        let err = json_payload_error_handler(JsonPayloadError::ContentType, &req);
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use sqlx::PgPool;
+
+use domains::models::users::{User, UserId, DEFAULT_TENANT_ID};
+use middlewares::{require_scope::RequireScope, OptionalJwtAuth, TenantContext};
+use usecases::users;
+
+use crate::responses::{e404, e500};
+
+/// ユーザープロフィールの取得に必要なAPIキーのスコープ
+pub const USERS_READ_SCOPE: &str = "users:read";
+
+/// ユーザープロフィール取得ハンドラ
+///
+/// 閲覧者の認証状態と、ユーザーのプロフィールの公開設定に応じて、公開するプロフィール情報のみを返却する。
+/// 認証された閲覧者が、他のテナントのユーザーのプロフィールを取得しようとした場合は、`403 Forbidden`で応答する。
+/// 認証されていない閲覧者は、既定のテナントのユーザーのプロフィールのみを取得できる。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(pool), name = "Get user profile")]
+pub async fn get_user_profile(
+    path: web::Path<String>,
+    viewer: Option<web::ReqData<User>>,
+    tenant: Option<web::ReqData<TenantContext>>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // ユーザーIDとして解釈できない場合は、存在しないユーザーと同様に`404 Not Found`で応答
+    let user_id = UserId::try_from(path.as_str()).map_err(e404)?;
+    let viewer = viewer.map(|viewer| viewer.into_inner());
+    // 認証された閲覧者のテナントを特定できない場合は、すべてのテナントのユーザーを取得できないように拒否
+    let tenant_id = match (&viewer, tenant) {
+        (_, Some(tenant)) => tenant.tenant_id,
+        (None, None) => DEFAULT_TENANT_ID,
+        (Some(_), None) => return Err(e500("閲覧者が所属するテナントを特定できません。")),
+    };
+    let profile =
+        users::get_user_profile(user_id, viewer.as_ref(), tenant_id, pool.as_ref()).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .json(profile))
+}
+
+/// ユーザースコープを返却する。
+pub fn users_scope() -> actix_web::Scope {
+    web::scope("/users").service(
+        web::resource("/{id}")
+            .wrap(OptionalJwtAuth)
+            // APIキーで認証したリクエストは、`users:read`スコープを付与したAPIキーに限定
+            .wrap(RequireScope::new(USERS_READ_SCOPE))
+            .route(web::get().to(get_user_profile)),
+    )
+}
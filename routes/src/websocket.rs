@@ -0,0 +1,93 @@
+use actix_web::{
+    http::{header::ORIGIN, StatusCode},
+    rt, web, HttpRequest, HttpResponse,
+};
+use actix_ws::Message;
+
+use configurations::Settings;
+use middlewares::authenticate_request;
+
+use crate::responses::json_error;
+
+/// WebSocketへのアップグレードを許可するオリジンか確認する。
+///
+/// ブラウザは、他のサイトのページが開いたWebSocketにもクッキーを送信するため、`Origin`ヘッダーが
+/// Webアプリと同じオリジン又はクロスオリジンリクエストを許可するオリジンの場合のみ許可する。`Origin`ヘッダーを
+/// 送信しないクライアントはブラウザではなく、他のサイトのページからクッキーを送信されることがないため許可する。
+///
+/// # Arguments
+///
+/// * `req` - アップグレードのリクエスト。
+/// * `allowed_origins` - クロスオリジンリクエストを許可するオリジン。
+///
+/// # Returns
+///
+/// 許可するオリジンの場合は`true`。
+fn is_allowed_origin(req: &HttpRequest, allowed_origins: &[String]) -> bool {
+    let origin = match req.headers().get(ORIGIN) {
+        Some(origin) => origin,
+        None => return true,
+    };
+    let origin = match origin.to_str() {
+        Ok(origin) => origin,
+        Err(_) => return false,
+    };
+    let connection_info = req.connection_info();
+    let own_origin = format!("{}://{}", connection_info.scheme(), connection_info.host());
+
+    origin == own_origin || allowed_origins.iter().any(|allowed| allowed == origin)
+}
+
+/// サンプル認証WebSocketハンドラ
+///
+/// WebSocketへのアップグレード時に、認証ミドルウェアと同じ方法でセッションを認証して、認証されていない場合は
+/// アップグレードせずに`401 Unauthorized`で応答する。接続した後は、接続時とテキストメッセージを受信したときに、
+/// 認証したユーザーのユーザーIDを送信する。
+///
+/// クロスサイトWebSocketハイジャックを防ぐため、許可していないオリジンからのアップグレードは、認証する前に
+/// `403 Forbidden`で拒否する。
+///
+/// # Returns
+///
+/// Httpレスポンス。
+#[tracing::instrument(skip(req, body, settings), name = "Sample authenticated websocket")]
+pub async fn websocket(
+    req: HttpRequest,
+    body: web::Payload,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !is_allowed_origin(&req, &settings.web_app.cors_allowed_origins) {
+        return Err(json_error(
+            StatusCode::FORBIDDEN,
+            "ORIGIN_NOT_ALLOWED",
+            "許可されていないオリジンからWebSocketに接続できません。".to_owned(),
+        ));
+    }
+    let authenticated = authenticate_request(&req).await?;
+    let user_id = authenticated.user().id().value().to_string();
+    let (mut response, mut session, mut stream) = actix_ws::handle(&req, body)?;
+    authenticated.add_refreshed_tokens(&mut response)?;
+
+    rt::spawn(async move {
+        if session.text(user_id.clone()).await.is_err() {
+            return;
+        }
+        while let Some(Ok(message)) = stream.recv().await {
+            let result = match message {
+                Message::Text(_) => session.text(user_id.clone()).await,
+                Message::Ping(bytes) => session.pong(&bytes).await,
+                Message::Close(reason) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                _ => Ok(()),
+            };
+            if result.is_err() {
+                return;
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -1,11 +1,5 @@
 #!/usr/bin/env bash
 
-cargo test --package tests -- --ignored \
-    --skip can_access_protected_resource_at_within_expiration_of_refresh_token \
-    --skip cannot_access_protected_resource_at_expired_expiration_of_refresh_token
-
-cargo test --package tests -- --ignored \
-    can_access_protected_resource_at_within_expiration_of_refresh_token
-
-cargo test --package tests -- --ignored \
-    cannot_access_protected_resource_at_expired_expiration_of_refresh_token
+# テスト用Webアプリごとに、データベースとRedisに記録するセッションデータのキーの接頭辞を分けているため、
+# 統合テストを並行して実行できる。
+cargo test --package tests -- --ignored
@@ -1,4 +1,6 @@
-use crate::helpers::spawn_web_app;
+use time::Duration;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with};
 
 /// ログインしていないユーザーがパスワード変更APIにアクセスできないことを確認するテスト
 #[tokio::test]
@@ -34,7 +36,8 @@ async fn cannot_change_password_if_incorrect_current_password() {
     assert_eq!(response.status(), reqwest::StatusCode::OK)
 }
 
-/// ログインしているユーザーがパスワードを変更できることを確認するテスト
+/// ログインしているユーザーがパスワードを変更できて、既定の設定では現在のセッションからログアウトすることを
+/// 確認するテスト
 #[tokio::test]
 #[ignore]
 async fn can_change_password() {
@@ -44,7 +47,7 @@ async fn can_change_password() {
     let response = app.call_login_api(&login_data).await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
     // セッションIDとトークンを記憶
-    let session_id = app.get_session_id().unwrap();
+    let session_id = app.get_session_key().unwrap();
     let (access_token, refresh_token) = app.get_token_values();
 
     // パスワードを変更
@@ -65,9 +68,200 @@ async fn can_change_password() {
     let response = app.call_login_api(&login_data).await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
     // セッションIDとトークンが変わっていることを確認
-    let session_id_2nd = app.get_session_id().unwrap();
+    let session_id_2nd = app.get_session_key().unwrap();
     let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
     assert!(session_id != session_id_2nd);
     assert!(access_token != access_token_2nd);
     assert!(refresh_token != refresh_token_2nd);
 }
+
+/// パスワードを変更すると、他のセッションでも保護されたリソースにアクセスできなくなることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn change_password_invalidates_other_sessions() {
+    // 2つのクライアントでログイン
+    let app = spawn_web_app(true).await;
+    let login_data = app.active_user_login_data();
+    let response = app.call_login_api(&login_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .json(&login_data)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let protected_resource_url = format!("{}/protected_resource", app.web_app_address);
+    let response = other_client
+        .get(&protected_resource_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 一方のクライアントでパスワードを変更
+    let change_password_data = app.change_password_data();
+    let response = app.call_change_password_api(&change_password_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 他方のクライアントで保護されたリソースにアクセスできないことを確認
+    let response = other_client
+        .get(&protected_resource_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// パスワード変更時にログアウトしない設定の場合に、現在のセッションはトークンを更新して継続し、
+/// 他のセッションは失効することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn change_password_keeps_current_session_alive_when_configured() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.logout_on_password_change = false;
+    })
+    .await;
+    // 2つのクライアントでログイン
+    let mut login_data = app.active_user_login_data();
+    let response = app.call_login_api(&login_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .json(&login_data)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // セッションIDとトークンを記憶
+    let session_id = app.get_session_key().unwrap();
+    let (access_token, refresh_token) = app.get_token_values();
+
+    // パスワードを変更
+    let change_password_data = app.change_password_data();
+    let response = app.call_change_password_api(&change_password_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // セッションIDとトークンが更新されていることを確認
+    let session_id_2nd = app.get_session_key().unwrap();
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert!(session_id != session_id_2nd);
+    assert!(access_token_2nd.is_some() && access_token != access_token_2nd);
+    assert!(refresh_token_2nd.is_some() && refresh_token != refresh_token_2nd);
+
+    // 現在のセッションで、保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 現在のセッションで、トークンをリフレッシュできることを確認
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 他のセッションでは、保護されたリソースにアクセスできないことを確認
+    let protected_resource_url = format!("{}/protected_resource", app.web_app_address);
+    let response = other_client
+        .get(&protected_resource_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 古いパスワードではログインできず、新しいパスワードでログインできることを確認
+    let login_url = format!("{}/accounts/login", app.web_app_address);
+    let response = other_client
+        .post(&login_url)
+        .json(&login_data)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    login_data.password = change_password_data.new_password.clone();
+    let response = other_client
+        .post(&login_url)
+        .json(&login_data)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしてから再認証せずに許可する期間内であれば、パスワードを変更できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_change_password_within_reauthentication_window() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.reauthentication_window = Duration::seconds(60);
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let response = app
+        .call_change_password_api(&app.change_password_data())
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしてから再認証せずに許可する期間が経過した場合は、現在のパスワードを検証して再認証するまで、
+/// パスワードを変更できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_change_password_after_reauthentication_window() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.reauthentication_window = Duration::seconds(1);
+    })
+    .await;
+    let login_data = app.active_user_login_data();
+    let response = app.call_login_api(&login_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 期間が経過するまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    let change_password_data = app.change_password_data();
+    let response = app.call_change_password_api(&change_password_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // パスワードが変更されず、セッションが継続していることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 現在のパスワードを検証して再認証した後は、パスワードを変更できることを確認
+    let response = app.call_verify_password_api(&login_data.password).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_change_password_api(&change_password_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 同じユーザーのパスワードを同時に変更した場合は、1つのリクエストのみ成功することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn only_one_of_concurrent_password_changes_succeeds() {
+    // 先に完了したパスワードの変更でセッションが終了しないように、現在のセッションを継続
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.logout_on_password_change = false;
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 同じ現在のパスワードで、異なるパスワードへの変更を同時に要求
+    let first = app.change_password_data();
+    let mut second = app.change_password_data();
+    second.new_password = "wB6$gT9!qZ2#".to_owned();
+    let (first, second) = tokio::join!(
+        app.call_change_password_api(&first),
+        app.call_change_password_api(&second),
+    );
+    let succeeded = [first.status(), second.status()]
+        .iter()
+        .filter(|status| **status == reqwest::StatusCode::OK)
+        .count();
+    assert_eq!(succeeded, 1);
+}
@@ -0,0 +1,91 @@
+use crate::helpers::spawn_web_app;
+
+/// アカウントを削除した後、ログイン及び保護されたリソースへのアクセスができないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn delete_account() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 別のクライアントでもログイン
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .json(&data)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // アカウントを削除
+    let response = app.call_delete_account_api(&data.password).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ユーザーが論理削除され、リフレッシュトークンがすべて削除されていることを確認
+    let record = sqlx::query!(
+        r#"
+            SELECT
+                is_active, deleted_at,
+                (SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1) AS "refresh_tokens!"
+            FROM users
+            WHERE id = $1
+        "#,
+        user.id().value(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("データベースからユーザーを取得できませんでした。");
+    assert!(!record.is_active);
+    assert!(record.deleted_at.is_some());
+    assert_eq!(record.refresh_tokens, 0);
+
+    // 保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = other_client
+        .get(format!("{}/protected_resource", app.web_app_address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // ログインできないことを確認
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// パスワードが間違っている場合に、アカウントを削除できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_delete_account_with_incorrect_password() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // アカウントの削除を試行
+    let response = app.call_delete_account_api("incorrect-password").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしていないユーザーがアカウントを削除できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_delete_account_without_login() {
+    let app = spawn_web_app(true).await;
+    let password = app.test_users.active_user_password.clone();
+    let response = app.call_delete_account_api(&password).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
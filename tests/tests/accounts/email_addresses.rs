@@ -0,0 +1,216 @@
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app_with_store, LoginData, SignupData, TestWebApp};
+
+/// アクティブユーザーに登録するエイリアス
+const ALIAS_EMAIL_ADDRESS: &str = "active-user-alias@example.com";
+
+/// アクティブユーザーでログインして、エイリアスを登録する。
+async fn add_alias_to_active_user(app: &TestWebApp) {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_add_email_alias_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// アクティブユーザーに登録したエイリアスを、確認済みにする。
+async fn verify_alias(app: &TestWebApp) {
+    sqlx::query!(
+        "UPDATE user_email_addresses SET is_verified = true WHERE email_address = $1",
+        ALIAS_EMAIL_ADDRESS
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+}
+
+/// 指定したEメールアドレスとアクティブユーザーのパスワードで、ログインAPIを呼び出す。
+async fn login_with(app: &TestWebApp, email_address: &str) -> reqwest::Response {
+    let data = LoginData {
+        email_address: email_address.to_owned(),
+        password: app.test_users.active_user_password.clone(),
+    };
+
+    app.call_login_api(&data).await
+}
+
+/// アクティブユーザーのプライマリEメールアドレスを取得する。
+async fn primary_email_address(app: &TestWebApp) -> (String, String) {
+    let user_id = app.test_users.active_user.id().value();
+    let user = sqlx::query!("SELECT email_address FROM users WHERE id = $1", user_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    let primary = sqlx::query!(
+        r#"
+        SELECT email_address
+        FROM user_email_addresses
+        WHERE user_id = $1 AND is_primary
+        "#,
+        user_id
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+
+    (user.email_address, primary.email_address)
+}
+
+/// 確認済みのエイリアスとプライマリEメールアドレスのどちらでもログインでき、未確認のエイリアスでは
+/// ログインできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_login_with_any_email_address() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 未確認のエイリアスではログインできない
+    let response = login_with(&app, ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // 確認済みのエイリアスでログイン
+    verify_alias(&app).await;
+    let response = login_with(&app, ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // プライマリEメールアドレスでログイン
+    let response = login_with(&app, app.test_users.active_user.email_address().value()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 登録したエイリアスが、未確認のEメールアドレスとして記録されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn added_alias_is_not_verified() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    let record = sqlx::query!(
+        r#"
+        SELECT is_primary, is_verified
+        FROM user_email_addresses
+        WHERE email_address = $1
+        "#,
+        ALIAS_EMAIL_ADDRESS
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert!(!record.is_primary);
+    assert!(!record.is_verified);
+}
+
+/// 他のユーザーに登録されたEメールアドレスを、エイリアスとして登録できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_add_registered_email_address_as_alias() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    let other = app.test_users.non_active_user.email_address().value();
+    let response = app.call_add_email_alias_api(other).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let response = app.call_add_email_alias_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// 確認済みのエイリアスに、プライマリEメールアドレスを変更できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_change_primary_email_address() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    // 未確認のエイリアスは、プライマリEメールアドレスに設定できない
+    let response = app.call_set_primary_email_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let (_, primary) = primary_email_address(&app).await;
+    assert_eq!(primary, app.test_users.active_user.email_address().value());
+    verify_alias(&app).await;
+    let response = app.call_set_primary_email_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // ユーザーのEメールアドレスとプライマリEメールアドレスが変更されたことを確認
+    let (user_email_address, primary) = primary_email_address(&app).await;
+    assert_eq!(user_email_address, ALIAS_EMAIL_ADDRESS);
+    assert_eq!(primary, ALIAS_EMAIL_ADDRESS);
+    // 以前のプライマリEメールアドレスはエイリアスとして残り、ログインできる
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = login_with(&app, app.test_users.active_user.email_address().value()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 登録されていないEメールアドレスは、プライマリEメールアドレスに設定できない
+    let response = app
+        .call_set_primary_email_api("not-registered@example.com")
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// エイリアスを削除すると、そのエイリアスでログインできなくなることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_remove_email_alias() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    verify_alias(&app).await;
+    // プライマリEメールアドレスは削除できない
+    let primary = app.test_users.active_user.email_address().value();
+    let response = app.call_remove_email_alias_api(primary).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    // エイリアスを削除
+    let response = app.call_remove_email_alias_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_remove_email_alias_api(ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    // 削除したエイリアスではログインできない
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = login_with(&app, ALIAS_EMAIL_ADDRESS).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = login_with(&app, primary).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// アカウントを削除すると、エイリアスとプライマリEメールアドレスを他のユーザーが登録できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn email_addresses_of_deleted_user_can_be_registered() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    add_alias_to_active_user(&app).await;
+    verify_alias(&app).await;
+    let response = app
+        .call_delete_account_api(&app.test_users.active_user_password)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 削除したユーザーのエイリアスは削除されている
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM user_email_addresses
+        WHERE email_address = $1
+        "#,
+        ALIAS_EMAIL_ADDRESS
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(record.count, 0);
+    // 削除したユーザーのエイリアスとプライマリEメールアドレスで、サインアップできる
+    let primary = app.test_users.active_user.email_address().value();
+    for (user_name, email_address) in [
+        ("alias-owner", ALIAS_EMAIL_ADDRESS),
+        ("primary-owner", primary),
+    ] {
+        let data = SignupData {
+            user_name: user_name.to_owned(),
+            email_address: email_address.to_owned(),
+            password: "Passw0rd!Passw0rd!".to_owned(),
+        };
+        let response = app.call_signup_api(&data).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::OK,
+            "{}",
+            email_address
+        );
+    }
+}
@@ -1,12 +1,19 @@
 extern crate web_server;
 
+use std::collections::HashMap;
+
+use configurations::password::compute_hashed_password;
 use configurations::session::{ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
 use configurations::{SessionCookieSettings, Settings};
 use cookie_store::{Cookie, CookieExpiration};
-// use redis::Commands;
-// use secrecy::ExposeSecret;
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use web_server::session_stores::InMemorySessionStore;
 
-use crate::helpers::{spawn_web_app, LoginData};
+use crate::helpers::{
+    spawn_web_app, spawn_web_app_with, spawn_web_app_with_store, LoginData, TestWebApp,
+};
 
 /// 登録されていないユーザーが認証されないことを確認するテスト
 #[tokio::test]
@@ -47,13 +54,45 @@ async fn non_active_user_unauthorized() {
 
 fn assert_cookie(cookie: &Cookie, settings: &SessionCookieSettings) {
     assert!(cookie.http_only().unwrap());
-    if !cookie.secure().is_none() {
+    if cookie.secure().is_some() {
         assert_eq!(cookie.secure().unwrap(), settings.secure);
     } else {
         assert!(!settings.secure);
     }
     assert_eq!(cookie.same_site().unwrap(), settings.same_site);
-    assert_eq!(cookie.expires, CookieExpiration::SessionEnd);
+    match settings.max_age_seconds {
+        Some(_) => assert!(matches!(cookie.expires, CookieExpiration::AtUtc(_))),
+        None => assert_eq!(cookie.expires, CookieExpiration::SessionEnd),
+    }
+}
+
+/// `Max-Age`を設定した場合に、セッションIDとトークンを記録したクッキーが、ブラウザを閉じても削除されない
+/// クッキーになることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_cookies_have_max_age_when_configured() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.session_cookie.max_age_seconds = Some(settings.tokens.refresh_token_duration())
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let session_cookie = &app.settings.session_cookie;
+    let store = app.cookie_store.lock().unwrap();
+    for cookie_name in [
+        session_cookie.session_id_cookie_name.as_str(),
+        ACCESS_TOKEN_COOKIE_NAME,
+        REFRESH_TOKEN_COOKIE_NAME,
+    ] {
+        let cookie = store.get("localhost", "/", cookie_name);
+        assert!(
+            cookie.is_some(),
+            "クッキーに{}が記録されていません。",
+            cookie_name
+        );
+        assert_cookie(cookie.unwrap(), session_cookie);
+    }
 }
 
 // Eメールアドレスとパスワードが正しくて、アクティブなユーザーが認証されることを確認するテスト
@@ -120,3 +159,587 @@ async fn active_user_authorized() {
     // 下の行で、セッションデータの取得を試みるが、Redisはnilを返却する。
     // let _session_data: String = conn.get(session_id).unwrap();
 }
+
+/// 大文字や前後の空白を含むEメールアドレスでも、ログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn active_user_authorized_with_unnormalized_email_address() {
+    let app = spawn_web_app(true).await;
+    let email_address = app
+        .test_users
+        .active_user
+        .email_address()
+        .value()
+        .to_owned();
+    let values = vec![
+        email_address.to_uppercase(),
+        format!("  {} ", email_address),
+        format!("\t{}\n", email_address.to_uppercase()),
+    ];
+    for value in values {
+        let mut data = app.active_user_login_data();
+        data.email_address = value.clone();
+        let response = app.call_login_api(&data).await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK, "{:?}", value);
+    }
+}
+
+/// 大文字を含むEメールアドレスでサインアップしたユーザーが、小文字のEメールアドレスでログインできることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signed_up_user_authorized_with_different_case_email_address() {
+    let app = spawn_web_app(true).await;
+    // cspell:disable-next-line
+    let password = "tOC8pHh:K/-G";
+    let data = serde_json::json!({
+        "userName": "foo",
+        "emailAddress": " Foo@Example.COM ",
+        "password": password,
+    });
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["emailAddress"], "foo@example.com");
+
+    let data = LoginData {
+        email_address: "foo@example.com".to_owned(),
+        password: password.to_owned(),
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ユーザー名で、大文字と小文字を区別せずにログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_user_name() {
+    let app = spawn_web_app(true).await;
+    let user_name = app
+        .test_users
+        .active_user
+        .user_name()
+        .value()
+        .to_uppercase();
+    let response = app
+        .call_login_api(&serde_json::json!({
+            "userName": user_name,
+            "password": app.test_users.active_user_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ユーザー名でログインするときに、パスワードが間違っているか、ユーザー名が登録されていない場合に
+/// ログインできず、失敗したログイン試行が記録されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_login_with_user_name_and_wrong_password() {
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    for (user_name, password) in [
+        (user.user_name().value(), "wrong-password"),
+        ("unknown-user", app.test_users.active_user_password.as_str()),
+    ] {
+        let response = app
+            .call_login_api(&serde_json::json!({
+                "userName": user_name,
+                "password": password,
+            }))
+            .await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNAUTHORIZED,
+            "{}",
+            user_name
+        );
+    }
+
+    let failed = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM login_attempts
+        WHERE user_id = $1 AND NOT succeeded
+        "#,
+        user.id().value()
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(failed.count, 1);
+}
+
+/// 登録されているユーザーのパスワードが間違っている場合と、Eメールアドレスが登録されていない場合で、
+/// 応答時間が同程度であることを確認するテスト
+///
+/// 実行環境による揺らぎを許容するために、応答時間の中央値の比が0.5倍から2倍の範囲であることを確認する。
+#[tokio::test]
+#[ignore]
+async fn login_takes_comparable_time_whether_user_exists_or_not() {
+    let app = spawn_web_app(true).await;
+    let found = serde_json::json!({
+        "emailAddress": app.test_users.active_user.email_address().value(),
+        "password": "wrong-password",
+    });
+    let not_found = serde_json::json!({
+        "emailAddress": "unknown@example.com",
+        "password": "wrong-password",
+    });
+    // ダミーのハッシュ化したパスワードを事前に計算させる
+    app.call_login_api(&not_found).await;
+
+    let mut found_elapsed = vec![];
+    let mut not_found_elapsed = vec![];
+    for _ in 0..5 {
+        for (data, elapsed) in [
+            (&found, &mut found_elapsed),
+            (&not_found, &mut not_found_elapsed),
+        ] {
+            let started_at = std::time::Instant::now();
+            let response = app.call_login_api(data).await;
+            elapsed.push(started_at.elapsed());
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+    }
+    found_elapsed.sort();
+    not_found_elapsed.sort();
+    let found = found_elapsed[found_elapsed.len() / 2];
+    let not_found = not_found_elapsed[not_found_elapsed.len() / 2];
+    let ratio = not_found.as_secs_f64() / found.as_secs_f64();
+    assert!(
+        (0.5..=2.0).contains(&ratio),
+        "found: {:?}, not found: {:?}",
+        found,
+        not_found
+    );
+}
+
+/// Eメールアドレスとユーザー名の両方を指定したか、どちらも指定しなかった場合に`400 Bad Request`が
+/// 返却されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_requires_either_email_address_or_user_name() {
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let password = &app.test_users.active_user_password;
+    for data in [
+        serde_json::json!({
+            "emailAddress": user.email_address().value(),
+            "userName": user.user_name().value(),
+            "password": password,
+        }),
+        serde_json::json!({ "password": password }),
+        serde_json::json!({ "emailAddress": " ", "userName": "", "password": password }),
+    ] {
+        let response = app.call_login_api(&data).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{}",
+            data
+        );
+    }
+}
+
+/// ログインしたセッションのリフレッシュトークンに記録されたデバイス名を取得する。
+async fn get_device_name(app: &crate::helpers::TestWebApp) -> Option<String> {
+    sqlx::query!(
+        "SELECT device_name FROM refresh_tokens WHERE user_id = $1",
+        app.test_users.active_user.id().value()
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .device_name
+}
+
+/// ログインしたときに、User-Agentから推測したデバイス名がセッションに記録されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_records_device_name_guessed_from_user_agent() {
+    let app = spawn_web_app(true).await;
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header(
+            reqwest::header::USER_AGENT,
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1",
+        )
+        .json(&app.active_user_login_data())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        get_device_name(&app).await.as_deref(),
+        Some("Safari (iPhone)")
+    );
+}
+
+/// ログインしたときに、クライアントが指定したデバイス名がセッションに記録されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_records_device_name_specified_by_client() {
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+
+    // デバイス名が長すぎる場合はログインできないことを確認
+    let response = app
+        .call_login_api(&serde_json::json!({
+            "emailAddress": data.email_address,
+            "password": data.password,
+            "deviceName": "x".repeat(101),
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 指定したデバイス名が記録されることを確認
+    let response = app
+        .call_login_api(&serde_json::json!({
+            "emailAddress": data.email_address,
+            "password": data.password,
+            "deviceName": " 会社のPC ",
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(get_device_name(&app).await.as_deref(), Some("会社のPC"));
+}
+
+/// 不正なJSONでログインした場合に、エラーの原因となったフィールドを含む`400 Bad Request`が返却されることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_malformed_json_returns_structured_bad_request() {
+    let app = spawn_web_app(true).await;
+
+    // ログインに必要なフィールドがない
+    let response = app
+        .call_login_api(&serde_json::json!({ "emailAddress": "foo@example.com" }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_JSON");
+    assert_eq!(body["field"], "password");
+
+    // フィールドの型が間違っている
+    let response = app
+        .call_login_api(&serde_json::json!({ "emailAddress": 123, "password": "password" }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_JSON");
+    assert!(body["message"].as_str().unwrap().contains("invalid type"));
+}
+
+/// パスワードのポリシーを強化する前に登録したパスワードでログインした場合に、ログインに成功して、パスワードが
+/// 現在のパスワードのポリシーを満たしていないことが通知されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_password_not_meeting_current_policy_is_flagged() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    // 現在のポリシーでは登録できない、記号を含まないパスワードを登録済みのパスワードとして記録
+    let password = "Password1234".to_owned();
+    let hashed_password = compute_hashed_password(&Secret::new(password.clone())).unwrap();
+    sqlx::query!(
+        "UPDATE users SET hashed_password = $1 WHERE id = $2",
+        hashed_password.expose_secret(),
+        app.test_users.active_user.id().value(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let mut data = app.active_user_login_data();
+    data.password = password;
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["mustChangePassword"], false);
+    assert_eq!(body["passwordMeetsCurrentPolicy"], false);
+    // パスワードの変更を強制しないため、保護されたリソースにアクセスできる
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 現在のポリシーを満たすパスワードでログインした場合は、レスポンスボディを返却しない
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.text().await.unwrap().is_empty());
+}
+
+/// JSONではないContent-Typeでリクエストボディを送信した場合に、`415 Unsupported Media Type`が返却されることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_wrong_content_type_returns_unsupported_media_type() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let body = serde_json::to_string(&app.active_user_login_data()).unwrap();
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header(reqwest::header::CONTENT_TYPE, "text/plain")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNSUPPORTED_MEDIA_TYPE");
+}
+
+/// JSONのContent-Typeを要求しない設定の場合に、JSONではないContent-Typeでもログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_wrong_content_type_succeeds_when_not_required() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |settings| {
+        settings.web_app.json_content_type_required = false;
+    })
+    .await;
+    let body = serde_json::to_string(&app.active_user_login_data()).unwrap();
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header(reqwest::header::CONTENT_TYPE, "text/plain")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// Redisの代わりにメモリ内セッションストアを使用して、ログインからログアウトまでできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_flow_with_in_memory_session_store() {
+    // Redisに接続できないURIを設定して、Redisを使用していないことを確認
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |settings| {
+        settings.session_store.uri = "redis://127.0.0.1:1".to_owned().into();
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(app.get_session_id().is_some());
+
+    // セッションデータで保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // トークンをリフレッシュできることを確認
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ログアウトした後は、保護されたリソースにアクセスできないことを確認
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// トークンをリフレッシュしてもセッションIDは変わらず、改めてログインするとセッションIDが変わることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn refresh_keeps_session_id_but_login_rotates_it() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let session_key = app.get_session_key().unwrap();
+    let (access_token, refresh_token) = app.get_token_values();
+    let session_record_id = stored_session_id(&app).await;
+
+    // 有効期限が異なるトークンが発行されるように待機
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    // トークンをリフレッシュすると、トークンは更新されるが、セッションIDは変わらないことを確認
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert!(access_token_2nd.is_some() && access_token != access_token_2nd);
+    assert!(refresh_token_2nd.is_some() && refresh_token != refresh_token_2nd);
+    assert_eq!(app.get_session_key().unwrap(), session_key);
+    assert_eq!(stored_session_id(&app).await, session_record_id);
+
+    // 改めてログインすると、セッションIDが変わることを確認
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_ne!(app.get_session_key().unwrap(), session_key);
+    assert_ne!(stored_session_id(&app).await, session_record_id);
+}
+
+/// ログインした後に無効になったユーザーは、トークンをリフレッシュできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn non_active_user_cannot_refresh_tokens() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // ログインした後にユーザーを無効化
+    sqlx::query!(
+        "UPDATE users SET is_active = FALSE WHERE id = $1",
+        app.test_users.active_user.id().value(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // セッションが破棄されているため、再度リフレッシュしても認証されない
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// データベースに記録されている、最後に登録されたリフレッシュトークンのセッションIDを返却する。
+async fn stored_session_id(app: &TestWebApp) -> Uuid {
+    sqlx::query!(
+        r#"
+        SELECT session_id
+        FROM refresh_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        app.test_users.active_user.id().value(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .session_id
+}
+
+/// ログインを試行したときに、成否とクライアントの情報がログイン試行として記録されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_attempts_are_recorded() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |settings| {
+        settings.web_app.trusted_proxies = vec!["127.0.0.0/8".parse().unwrap()];
+    })
+    .await;
+    // パスワードを誤ってログイン
+    let mut data = app.active_user_login_data();
+    data.password = "5B_@T5aV#[)?".to_owned();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // リバースプロキシが設定した位置でログイン
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header("X-Client-Latitude", "35.6812")
+        .header("X-Client-Longitude", "139.7671")
+        .json(&app.active_user_login_data())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let records = sqlx::query!(
+        r#"
+        SELECT succeeded, ip_address, latitude, longitude
+        FROM login_attempts
+        WHERE user_id = $1
+        ORDER BY attempted_at
+        "#,
+        app.test_users.active_user.id().value()
+    )
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(!records[0].succeeded);
+    assert_eq!(records[0].latitude, None);
+    assert!(records[1].succeeded);
+    let ip_address: std::net::IpAddr = records[1].ip_address.as_deref().unwrap().parse().unwrap();
+    assert!(ip_address.is_loopback());
+    assert_eq!(records[1].latitude, Some(35.6812));
+    assert_eq!(records[1].longitude, Some(139.7671));
+}
+
+/// 接続元が信頼するプロキシではない場合に、クライアントが送信した位置のヘッダーを記録しないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn location_headers_from_untrusted_peer_are_ignored() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header("X-Client-Latitude", "35.6812")
+        .header("X-Client-Longitude", "139.7671")
+        .json(&app.active_user_login_data())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let record = sqlx::query!(
+        r#"
+        SELECT latitude, longitude
+        FROM login_attempts
+        WHERE user_id = $1
+        "#,
+        app.test_users.active_user.id().value()
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(record.latitude, None);
+    assert_eq!(record.longitude, None);
+}
+
+/// セッションデータの暗号鍵を設定した場合に、Redisに暗号化したセッションデータが記録されて、復号して
+/// 保護されたリソースにアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn session_data_is_encrypted_in_session_store() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.session_store.encryption_key = Some(base64::encode([42u8; 32]).into());
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+    let (access_token, refresh_token) = (access_token.unwrap(), refresh_token.unwrap());
+
+    // Redisに記録されたセッションの状態を取得
+    let client =
+        redis::Client::open(app.settings.session_store.uri.expose_secret().as_str()).unwrap();
+    let mut connection = client.get_async_connection().await.unwrap();
+    let key_prefix = &app.settings.session_store.key_prefix;
+    let mut keys: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{}*", key_prefix))
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    // セッションIDからセッションキーを引くインデックスを除外
+    let index_prefix = format!("{}session_index:", key_prefix);
+    let (index_keys, session_keys): (Vec<String>, Vec<String>) = keys
+        .drain(..)
+        .partition(|key| key.starts_with(&index_prefix));
+    assert_eq!(session_keys.len(), 1);
+    assert_eq!(index_keys.len(), 1);
+    let state: String = redis::cmd("GET")
+        .arg(&session_keys[0])
+        .query_async(&mut connection)
+        .await
+        .unwrap();
+    // セッションの状態にトークンが平文で含まれていないことを確認
+    assert!(!state.contains(&access_token));
+    assert!(!state.contains(&refresh_token));
+    let state: HashMap<String, String> = serde_json::from_str(&state).unwrap();
+    assert!(!state.contains_key("session_data"));
+    // インデックスは、セッションの状態に記録したセッションIDからセッションキーを引く
+    let session_id: String = serde_json::from_str(&state["session_id"]).unwrap();
+    assert_eq!(index_keys[0], format!("{}{}", index_prefix, session_id));
+    let ciphertext: String = serde_json::from_str(&state["encrypted_session_data"]).unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&ciphertext).is_err());
+
+    // 暗号化したセッションデータを復号して、保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
@@ -1,4 +1,8 @@
-use crate::helpers::spawn_web_app;
+use uuid::Uuid;
+
+use configurations::session::{ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+
+use crate::helpers::{spawn_web_app, TestWebApp};
 
 // ログインしているユーザーがログアウトできることを確認するテスト
 #[tokio::test]
@@ -36,6 +40,91 @@ async fn logout() {
     // }
 }
 
+/// ログアウトしたとき、ログアウトを確認するJSONとともに、トークンを記録したクッキーを削除する指示が
+/// 応答されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn logout_returns_body_and_removal_cookies() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ログアウト
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // トークンを記録したクッキーを削除するように指示されていることを確認
+    let removal_cookies: Vec<String> = response
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_owned())
+        .filter(|value| value.contains("Max-Age=0"))
+        .collect();
+    for name in [ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME] {
+        assert!(
+            removal_cookies
+                .iter()
+                .any(|cookie| cookie.starts_with(&format!("{}=;", name))),
+            "{}: {:?}",
+            name,
+            removal_cookies
+        );
+    }
+
+    // ログアウトを確認するJSONが応答されていることを確認
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["loggedOut"], true);
+    assert_eq!(
+        body["clearedCookies"],
+        serde_json::json!([ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME])
+    );
+    // セッションIDはマスクされていることを確認
+    let session_id = body["sessionId"].as_str().unwrap();
+    assert_eq!(session_id.len(), 36);
+    assert!(session_id.ends_with("****"));
+    assert!(Uuid::parse_str(session_id).is_err());
+}
+
+/// ログアウトしたとき、現在のセッションのリフレッシュトークンがデータベースから削除されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn logout_deletes_refresh_token() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // リフレッシュトークンがデータベースに記録されていることを確認
+    assert_eq!(count_refresh_tokens(&app, user.id().value()).await, 1);
+
+    // ログアウト
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // リフレッシュトークンがデータベースから削除されていることを確認
+    assert_eq!(count_refresh_tokens(&app, user.id().value()).await, 0);
+}
+
+/// ユーザーのリフレッシュトークンの数を返却する。
+async fn count_refresh_tokens(app: &TestWebApp, user_id: Uuid) -> i64 {
+    sqlx::query!(
+        r#"
+            SELECT COUNT(*) AS "count!"
+            FROM refresh_tokens
+            WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("データベースからリフレッシュトークンを取得できませんでした。")
+    .count
+}
+
 // ログインしていないユーザーがログアウトできないことを確認するテスト
 #[tokio::test]
 #[ignore]
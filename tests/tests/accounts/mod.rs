@@ -1,4 +1,13 @@
 mod change_password;
+mod delete_account;
+mod email_addresses;
 mod login;
 mod logout;
+mod passkeys;
+mod reset_password;
+mod revoke_refresh_token;
+mod security_questions;
 mod signup;
+mod token_status;
+mod totp;
+mod verify_password;
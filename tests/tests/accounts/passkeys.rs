@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::Rng;
+use reqwest::StatusCode;
+use serde_cbor::Value;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use configurations::totp::generate_totp_secret;
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app_with_store, TestWebApp};
+
+/// ES256（P-256曲線とSHA-256を使用したECDSA）を示すCOSEアルゴリズム識別子
+const COSE_ALGORITHM_ES256: i64 = -7;
+
+fn encode_base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_base64url(value: &str) -> Vec<u8> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD).unwrap()
+}
+
+/// テスト用のソフトウェア認証器
+///
+/// `none`形式のアテステーションで、ES256の公開鍵クレデンシャルを登録して、アサーションに署名する。
+struct SoftwareAuthenticator {
+    credential_id: Vec<u8>,
+    signing_key: SigningKey,
+    sign_count: u32,
+    /// アサーションでユーザーを検証（UV）したことを示すか。
+    user_verified: bool,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        Self {
+            credential_id: rand::thread_rng().gen::<[u8; 16]>().to_vec(),
+            signing_key: SigningKey::random(&mut rand::thread_rng()),
+            sign_count: 0,
+            user_verified: true,
+        }
+    }
+
+    fn cose_public_key(&self) -> Vec<u8> {
+        let point = self.signing_key.verifying_key().to_encoded_point(false);
+        let map: BTreeMap<Value, Value> = [
+            (1, Value::Integer(2)),
+            (3, Value::Integer(COSE_ALGORITHM_ES256 as i128)),
+            (-1, Value::Integer(1)),
+            (-2, Value::Bytes(point.x().unwrap().to_vec())),
+            (-3, Value::Bytes(point.y().unwrap().to_vec())),
+        ]
+        .into_iter()
+        .map(|(label, value)| (Value::Integer(label), value))
+        .collect();
+
+        serde_cbor::to_vec(&map).unwrap()
+    }
+
+    fn client_data(ceremony_type: &str, challenge: &str, app: &TestWebApp) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": app.settings.webauthn.origin,
+        }))
+        .unwrap()
+    }
+
+    /// 登録オプションのチャレンジから、パスキーの登録APIに送信するアテステーションを生成する。
+    fn create(&self, challenge: &str, app: &TestWebApp) -> serde_json::Value {
+        // RP IDハッシュ、フラグ（UP、UV及びAT）、署名カウンター及びクレデンシャルデータ
+        let mut auth_data = Sha256::digest(app.settings.webauthn.rp_id.as_bytes()).to_vec();
+        auth_data.push(0x45);
+        auth_data.extend_from_slice(&self.sign_count.to_be_bytes());
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend_from_slice(&self.cose_public_key());
+        let attestation_object: BTreeMap<String, Value> = [
+            ("fmt".to_owned(), Value::Text("none".to_owned())),
+            ("attStmt".to_owned(), Value::Map(BTreeMap::new())),
+            ("authData".to_owned(), Value::Bytes(auth_data)),
+        ]
+        .into_iter()
+        .collect();
+
+        json!({
+            "id": encode_base64url(&self.credential_id),
+            "rawId": encode_base64url(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": encode_base64url(&Self::client_data("webauthn.create", challenge, app)),
+                "attestationObject": encode_base64url(&serde_cbor::to_vec(&attestation_object).unwrap()),
+            },
+        })
+    }
+
+    /// 認証オプションのチャレンジから、パスキーの認証APIに送信するアサーションを生成する。
+    fn get(&mut self, challenge: &str, app: &TestWebApp) -> serde_json::Value {
+        self.sign_count += 1;
+        // RP IDハッシュ、フラグ（UP及びUV）及び署名カウンター
+        let mut auth_data = Sha256::digest(app.settings.webauthn.rp_id.as_bytes()).to_vec();
+        auth_data.push(if self.user_verified { 0x05 } else { 0x01 });
+        auth_data.extend_from_slice(&self.sign_count.to_be_bytes());
+        let client_data = Self::client_data("webauthn.get", challenge, app);
+        let message = [auth_data.as_slice(), &Sha256::digest(&client_data)].concat();
+        let signature: Signature = self.signing_key.sign(&message);
+
+        json!({
+            "id": encode_base64url(&self.credential_id),
+            "rawId": encode_base64url(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": encode_base64url(&client_data),
+                "authenticatorData": encode_base64url(&auth_data),
+                "signature": encode_base64url(signature.to_der().as_bytes()),
+            },
+        })
+    }
+}
+
+/// パスワードでログインしたアクティブユーザーに、パスキーを登録する。
+async fn register_passkey(app: &TestWebApp, authenticator: &SoftwareAuthenticator) {
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.call_webauthn_api("register/start", &json!({})).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let options: serde_json::Value = response.json().await.unwrap();
+    let options = &options["publicKey"];
+    assert_eq!(options["rp"]["id"], app.settings.webauthn.rp_id.as_str());
+    assert!(options["pubKeyCredParams"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|param| param["alg"] == COSE_ALGORITHM_ES256));
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api("register/finish", &authenticator.create(challenge, app))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // ログアウト
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Eメールアドレスを指定して、パスキーによる認証を開始して、認証オプションを返却する。
+async fn start_passkey_login_with(app: &TestWebApp, email_address: &str) -> serde_json::Value {
+    let response = app
+        .call_webauthn_api("login/start", &json!({ "emailAddress": email_address }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let options: serde_json::Value = response.json().await.unwrap();
+
+    options["publicKey"].clone()
+}
+
+/// アクティブユーザーのEメールアドレスで、パスキーによる認証を開始して、認証オプションを返却する。
+async fn start_passkey_login(app: &TestWebApp) -> serde_json::Value {
+    start_passkey_login_with(app, app.test_users.active_user.email_address().value()).await
+}
+
+/// パスキーを登録したユーザーが、パスキーでログインして保護されたリソースにアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_login_with_registered_passkey() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+    // パスキーが登録されていることを確認
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM user_credentials WHERE user_id = $1 AND credential_id = $2"#,
+        app.test_users.active_user.id().value(),
+        &authenticator.credential_id,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 1);
+
+    // パスキーでログイン
+    let options = start_passkey_login(&app).await;
+    let allowed = options["allowCredentials"][0]["id"].as_str().unwrap();
+    assert_eq!(decode_base64url(allowed), authenticator.credential_id);
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+    assert!(access_token.is_some());
+    assert!(refresh_token.is_some());
+    // 保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let text = response.text().await.unwrap();
+    assert_eq!(text, app.test_users.active_user.id().value().to_string());
+
+    // パスキーを登録した後も、パスワードでログインできることを確認
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// 異なるチャレンジに署名したアサーションや、使用済みのチャレンジを拒否することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn passkey_login_rejects_invalid_challenge_response() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    // 異なるチャレンジに署名したアサーション
+    start_passkey_login(&app).await;
+    let other_challenge = encode_base64url(&rand::thread_rng().gen::<[u8; 32]>());
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(&other_challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    // 認証を完了しようとしたチャレンジは使用できない
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(&other_challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // 保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // 登録されていない認証器のアサーション
+    let options = start_passkey_login(&app).await;
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api(
+            "login/finish",
+            &SoftwareAuthenticator::new().get(challenge, &app),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// 異なるチャレンジに署名したアテステーションでは、パスキーを登録できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn passkey_registration_rejects_invalid_challenge_response() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.call_webauthn_api("register/start", &json!({})).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let other_challenge = encode_base64url(&rand::thread_rng().gen::<[u8; 32]>());
+    let authenticator = SoftwareAuthenticator::new();
+    let response = app
+        .call_webauthn_api(
+            "register/finish",
+            &authenticator.create(&other_challenge, &app),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM user_credentials WHERE user_id = $1"#,
+        app.test_users.active_user.id().value(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 0);
+
+    // ログインしていない場合は、パスキーの登録を開始できない
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.call_webauthn_api("register/start", &json!({})).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// パスキーによる認証では認証器にユーザーの検証を要求して、ユーザーを検証していないアサーションを拒否し、
+/// 2要素認証を有効にしているユーザーでも、ユーザーを検証したアサーションではTOTPのコードの検証を省略できる
+/// ことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn passkey_login_requires_user_verification() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+    let secret = generate_totp_secret();
+    sqlx::query!(
+        "UPDATE users SET totp_secret = $1 WHERE id = $2",
+        secret,
+        app.test_users.active_user.id().value(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    // ユーザーを検証していないアサーション
+    authenticator.user_verified = false;
+    let options = start_passkey_login(&app).await;
+    assert_eq!(options["userVerification"], "required");
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // ユーザーを検証したアサーションは、TOTPのコードを検証せずにログインできる
+    authenticator.user_verified = true;
+    let options = start_passkey_login(&app).await;
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// パスキーによる認証の開始APIは、Eメールアドレスのユーザーが存在しないか、パスキーを登録していない場合も、
+/// パスキーを登録したユーザーと区別できない認証オプションで応答することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn passkey_login_options_do_not_reveal_registered_users() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+    let registered = start_passkey_login(&app).await;
+
+    let email_addresses = [
+        // 存在しないユーザー
+        "unknown@example.com",
+        // パスキーを登録していないユーザー
+        app.test_users.non_active_user.email_address().value(),
+    ];
+    for email_address in email_addresses {
+        let options = start_passkey_login_with(&app, email_address).await;
+        // パスキーを登録したユーザーと同じ項目で、1つのクレデンシャルを含む
+        let keys = |options: &serde_json::Value| {
+            let mut keys: Vec<String> = options.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&options), keys(&registered));
+        let allowed = options["allowCredentials"].as_array().unwrap();
+        assert_eq!(allowed.len(), 1);
+        assert_eq!(allowed[0]["type"], "public-key");
+        let credential_id = decode_base64url(allowed[0]["id"].as_str().unwrap());
+        assert_eq!(credential_id.len(), authenticator.credential_id.len());
+        // 同じEメールアドレスでは、常に同じクレデンシャルIDを含む
+        let again = start_passkey_login_with(&app, email_address).await;
+        assert_eq!(again["allowCredentials"], options["allowCredentials"]);
+        assert_ne!(again["challenge"], options["challenge"]);
+    }
+    let unknown = start_passkey_login_with(&app, "unknown@example.com").await;
+    let other = start_passkey_login_with(&app, "other@example.com").await;
+    assert_ne!(unknown["allowCredentials"], other["allowCredentials"]);
+
+    // 登録したパスキーでも、存在しないユーザーの認証オプションのチャレンジでは認証できない
+    let options = start_passkey_login_with(&app, "unknown@example.com").await;
+    let challenge = options["challenge"].as_str().unwrap();
+    let response = app
+        .call_webauthn_api("login/finish", &authenticator.get(challenge, &app))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
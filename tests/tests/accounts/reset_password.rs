@@ -0,0 +1,167 @@
+use secrecy::{ExposeSecret, Secret};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use usecases::password_resets::issue_password_reset_token;
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with_store, LoginData, TestWebApp};
+
+/// アクティブなユーザーのパスワードリセットトークンを発行する。
+async fn issue_token(app: &TestWebApp) -> Secret<String> {
+    issue_password_reset_token(
+        app.test_users.active_user.email_address().clone(),
+        &app.settings.tokens,
+        &app.pool,
+    )
+    .await
+    .unwrap()
+    .expect("パスワードリセットトークンを発行できませんでした。")
+    .token
+}
+
+/// パスワードリセットトークンと発行したときのメールアドレスで、パスワードをリセットできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn reset_password_with_token_and_email_address() {
+    let app = spawn_web_app(true).await;
+    let token = issue_token(&app).await;
+    let email_address = app
+        .test_users
+        .active_user
+        .email_address()
+        .value()
+        .to_owned();
+    let new_password = "Fk3$wLq9@zTp".to_owned();
+
+    // パスワードをリセット
+    let response = app
+        .call_reset_password_api(&serde_json::json!({
+            "token": token.expose_secret(),
+            "emailAddress": email_address,
+            "newPassword": new_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 新しいパスワードでログインでき、古いパスワードでログインできないことを確認
+    let response = app
+        .call_login_api(&LoginData {
+            email_address: email_address.clone(),
+            password: new_password.clone(),
+        })
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 同じパスワードリセットトークンを再度使用できないことを確認
+    let response = app
+        .call_reset_password_api(&serde_json::json!({
+            "token": token.expose_secret(),
+            "emailAddress": email_address,
+            "newPassword": "Zr8!mVb2#Hq5",
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// パスワードリセットトークンを発行したときと異なるメールアドレスでは、パスワードをリセットできないことを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_reset_password_with_other_email_address() {
+    let app = spawn_web_app(true).await;
+    let token = issue_token(&app).await;
+    let new_password = "Fk3$wLq9@zTp";
+
+    // 他のユーザーのメールアドレスでパスワードをリセットできないことを確認
+    let response = app
+        .call_reset_password_api(&serde_json::json!({
+            "token": token.expose_secret(),
+            "emailAddress": app.test_users.non_active_user.email_address().value(),
+            "newPassword": new_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 存在しないメールアドレスでパスワードをリセットできないことを確認
+    let response = app
+        .call_reset_password_api(&serde_json::json!({
+            "token": token.expose_secret(),
+            "emailAddress": "attacker@example.com",
+            "newPassword": new_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // パスワードが変更されていないことを確認
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// パスワードのリセットを要求すると、Webhookで通知されたパスワードリセットトークンでパスワードをリセット
+/// できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn reset_password_with_token_notified_by_webhook() {
+    let receiver = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+    let url = format!("{}/hooks", receiver.uri());
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), move |settings| {
+        settings.webhook.url = Some(url);
+        settings.webhook.secret = Some(Secret::new("webhook-secret".to_owned()));
+    })
+    .await;
+    let email_address = app
+        .test_users
+        .active_user
+        .email_address()
+        .value()
+        .to_owned();
+
+    // 登録されていないEメールアドレスでも同じ応答を返却して、Webhookで通知しない
+    let response = app
+        .call_request_password_reset_api("unknown@example.com")
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    // パスワードのリセットを要求して、Webhookで通知されたパスワードリセットトークンを取得
+    let response = app.call_request_password_reset_api(&email_address).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let mut event = None;
+    for _ in 0..50 {
+        let requests = receiver.received_requests().await.unwrap();
+        if let Some(request) = requests.first() {
+            event = Some(serde_json::from_slice::<serde_json::Value>(&request.body).unwrap());
+            assert_eq!(requests.len(), 1);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let event = event.expect("Webhookを受信しませんでした。");
+    assert_eq!(event["type"], "user.password_reset_requested");
+    assert_eq!(event["emailAddress"], email_address);
+    let token = event["passwordResetToken"].as_str().unwrap();
+
+    // 通知されたパスワードリセットトークンでパスワードをリセット
+    let new_password = "Fk3$wLq9@zTp".to_owned();
+    let response = app
+        .call_reset_password_api(&serde_json::json!({
+            "token": token,
+            "emailAddress": email_address,
+            "newPassword": new_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app
+        .call_login_api(&LoginData {
+            email_address,
+            password: new_password,
+        })
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
@@ -0,0 +1,122 @@
+extern crate web_server;
+
+use uuid::Uuid;
+
+use configurations::tokens::refresh_token_hash;
+
+use web_server::session_stores::{InMemorySessionStore, KeyedSessionStore};
+
+use crate::helpers::{spawn_web_app_with_store, LoginData, SignupData, TestWebApp};
+
+/// データベースに記録されているリフレッシュトークンのセッションIDを取得する。
+async fn stored_session_id(app: &TestWebApp, refresh_token: &str) -> Option<Uuid> {
+    sqlx::query!(
+        "SELECT session_id FROM refresh_tokens WHERE token_hash = $1",
+        refresh_token_hash(refresh_token, &app.settings.tokens.secret_key).unwrap(),
+    )
+    .fetch_optional(&app.pool)
+    .await
+    .unwrap()
+    .map(|record| record.session_id)
+}
+
+/// アクティブユーザーでログインして、リフレッシュトークンを返却する。
+async fn login_active_user(app: &TestWebApp) -> String {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    app.get_token_values().1.unwrap()
+}
+
+/// ユーザーが他のデバイスのリフレッシュトークンを失効させることができ、失効させたセッションがセッション
+/// ストアからも削除されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn owner_can_revoke_own_refresh_token() {
+    let store = InMemorySessionStore::default();
+    let app = spawn_web_app_with_store(true, store.clone(), |_| {}).await;
+    // 他のデバイスでログインしたセッションを用意
+    let other_refresh_token = login_active_user(&app).await;
+    let other_session_id = stored_session_id(&app, &other_refresh_token).await.unwrap();
+    app.cookie_store.lock().unwrap().clear();
+    // 現在のデバイスでログインして、他のデバイスのリフレッシュトークンを失効
+    let current_refresh_token = login_active_user(&app).await;
+    let response = app
+        .call_revoke_refresh_token_api(&serde_json::json!({ "refreshToken": other_refresh_token }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["revoked"], true);
+    assert!(body["sessionId"]
+        .as_str()
+        .unwrap()
+        .starts_with(&other_session_id.to_string()[..8]));
+    assert!(stored_session_id(&app, &other_refresh_token)
+        .await
+        .is_none());
+    // 他のデバイスのセッションは、セッションストアから削除済み
+    assert!(!store.delete_by_session_id(other_session_id).await.unwrap());
+    // 現在のセッションは失効していない
+    assert!(stored_session_id(&app, &current_refresh_token)
+        .await
+        .is_some());
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // セッションIDで現在のセッションを失効させた場合は、以後は認証されない
+    let current_session_id = stored_session_id(&app, &current_refresh_token)
+        .await
+        .unwrap();
+    let response = app
+        .call_revoke_refresh_token_api(&serde_json::json!({ "sessionId": current_session_id }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(stored_session_id(&app, &current_refresh_token)
+        .await
+        .is_none());
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// 他のユーザーのリフレッシュトークンを失効させようとした場合に、存在しない場合と同様に`404 Not Found`が
+/// 返却されて、リフレッシュトークンが失効しないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_revoke_other_users_refresh_token() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    // アクティブユーザーでログインしたセッションを用意
+    let other_refresh_token = login_active_user(&app).await;
+    let other_session_id = stored_session_id(&app, &other_refresh_token).await.unwrap();
+    app.cookie_store.lock().unwrap().clear();
+    // 別のユーザーを登録してログイン
+    // cspell:disable-next-line
+    let password = "tOC8pHh:K/-G";
+    let data = SignupData {
+        user_name: "foo".to_owned(),
+        email_address: "foo@example.com".to_owned(),
+        password: password.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let data = LoginData {
+        email_address: "foo@example.com".to_owned(),
+        password: password.to_owned(),
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // アクティブユーザーのリフレッシュトークンとセッションIDのどちらを指定しても、存在しない場合と同様に
+    // 失効できない
+    for data in [
+        serde_json::json!({ "refreshToken": other_refresh_token }),
+        serde_json::json!({ "sessionId": other_session_id }),
+        serde_json::json!({ "refreshToken": "unknown-refresh-token" }),
+        serde_json::json!({ "sessionId": Uuid::new_v4() }),
+    ] {
+        let response = app.call_revoke_refresh_token_api(&data).await;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+    assert!(stored_session_id(&app, &other_refresh_token)
+        .await
+        .is_some());
+}
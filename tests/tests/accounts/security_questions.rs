@@ -0,0 +1,339 @@
+use secrecy::Secret;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use usecases::errors::AuthError;
+use usecases::security_questions::{
+    verify_security_answers, SecurityQuestionError, ACCOUNT_RECOVERY_MAX_FAILED_ATTEMPTS,
+};
+
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with_store, LoginData, TestWebApp};
+
+/// 秘密の質問を設定して、回答を検証できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_set_and_verify_security_questions() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 秘密の質問を設定
+    let data = json!({
+        "questions": [
+            { "question": "初めて飼ったペットの名前は？", "answer": "Tama" },
+            { "question": "生まれた町の名前は？", "answer": "Kamakura" },
+        ]
+    });
+    let response = app.call_set_security_questions_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 回答が平文で保存されていないことを確認
+    let records = sqlx::query!(
+        r#"
+            SELECT hashed_answer
+            FROM security_questions
+            WHERE user_id = $1
+            ORDER BY position
+        "#,
+        user.id().value(),
+    )
+    .fetch_all(&app.pool)
+    .await
+    .expect("データベースから秘密の質問を取得できませんでした。");
+    assert_eq!(records.len(), 2);
+    for (record, answer) in records.iter().zip(["tama", "kamakura"]) {
+        assert!(record.hashed_answer.starts_with("$argon2"));
+        assert!(!record.hashed_answer.to_lowercase().contains(answer));
+    }
+
+    // 正しい回答を検証できることを確認
+    let answers = vec![
+        Secret::new("tama".to_owned()),
+        Secret::new(" KAMAKURA ".to_owned()),
+    ];
+    assert!(verify_security_answers(user, answers, &app.pool)
+        .await
+        .is_ok());
+
+    // 間違った回答を検証できないことを確認
+    let answers = vec![
+        Secret::new("Tama".to_owned()),
+        Secret::new("Kyoto".to_owned()),
+    ];
+    let result = verify_security_answers(user, answers, &app.pool).await;
+    assert!(matches!(
+        result,
+        Err(AuthError::SecurityQuestion(
+            SecurityQuestionError::IncorrectAnswers
+        ))
+    ));
+
+    // 回答の数が質問の数と異なる場合に検証できないことを確認
+    let answers = vec![Secret::new("Tama".to_owned())];
+    let result = verify_security_answers(user, answers, &app.pool).await;
+    assert!(matches!(
+        result,
+        Err(AuthError::SecurityQuestion(
+            SecurityQuestionError::IncorrectAnswers
+        ))
+    ));
+}
+
+/// 秘密の質問を設定していないユーザーの回答を検証できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_verify_without_security_questions() {
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let answers = vec![Secret::new("Tama".to_owned())];
+    let result = verify_security_answers(user, answers, &app.pool).await;
+    assert!(matches!(
+        result,
+        Err(AuthError::SecurityQuestion(
+            SecurityQuestionError::NotConfigured
+        ))
+    ));
+}
+
+/// 不正な秘密の質問を設定できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_set_invalid_security_questions() {
+    // ログイン
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 質問が空の場合
+    let data = json!({ "questions": [] });
+    let response = app.call_set_security_questions_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 回答が空の場合
+    let data = json!({ "questions": [{ "question": "生まれた町の名前は？", "answer": " " }] });
+    let response = app.call_set_security_questions_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// ログインしていないユーザーが秘密の質問を設定できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_set_security_questions_without_login() {
+    let app = spawn_web_app(true).await;
+    let data = json!({
+        "questions": [{ "question": "生まれた町の名前は？", "answer": "Kamakura" }]
+    });
+    let response = app.call_set_security_questions_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// Webhookの受信サーバーを起動して、Webhookを設定したアプリを起動する。
+async fn spawn_web_app_with_webhook_receiver() -> (TestWebApp, MockServer) {
+    let receiver = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+    let url = format!("{}/hooks", receiver.uri());
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), move |settings| {
+        settings.webhook.url = Some(url);
+        settings.webhook.secret = Some(Secret::new("webhook-secret".to_owned()));
+    })
+    .await;
+
+    (app, receiver)
+}
+
+/// アクティブなユーザーでログインして、秘密の質問を設定した後にログアウトする。
+async fn set_active_user_security_questions(app: &TestWebApp) {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let data = json!({
+        "questions": [
+            { "question": "初めて飼ったペットの名前は？", "answer": "Tama" },
+            { "question": "生まれた町の名前は？", "answer": "Kamakura" },
+        ]
+    });
+    let response = app.call_set_security_questions_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 秘密の質問の回答でアカウントを回復して、Webhookで通知されたパスワードリセットトークンでパスワードを
+/// リセットできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn recover_account_with_security_answers() {
+    let (app, receiver) = spawn_web_app_with_webhook_receiver().await;
+    let email_address = app
+        .test_users
+        .active_user
+        .email_address()
+        .value()
+        .to_owned();
+    set_active_user_security_questions(&app).await;
+
+    // 回答が間違っている場合や、登録されていないEメールアドレスの場合も同じ応答を返却して、Webhookで通知しない
+    for data in [
+        json!({ "emailAddress": email_address, "answers": ["tama", "yokohama"] }),
+        json!({ "emailAddress": email_address, "answers": ["tama"] }),
+        json!({ "emailAddress": "unknown@example.com", "answers": ["tama", "kamakura"] }),
+    ] {
+        let response = app.call_recover_account_api(&data).await;
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+        assert!(response.text().await.unwrap().is_empty());
+    }
+    // 間違っている回答は、失敗したログイン試行として記録
+    let failed_attempts = sqlx::query!(
+        r#"
+            SELECT COUNT(*) AS "count!"
+            FROM login_attempts
+            WHERE user_id = $1 AND NOT succeeded
+        "#,
+        app.test_users.active_user.id().value(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(failed_attempts, 2);
+
+    // 正しい回答でアカウントを回復して、Webhookで通知されたパスワードリセットトークンを取得
+    let response = app
+        .call_recover_account_api(&json!({
+            "emailAddress": email_address,
+            "answers": [" TAMA ", "kamakura"],
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let mut event = None;
+    for _ in 0..50 {
+        let requests = receiver.received_requests().await.unwrap();
+        if let Some(request) = requests.first() {
+            event = Some(serde_json::from_slice::<serde_json::Value>(&request.body).unwrap());
+            assert_eq!(requests.len(), 1);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let event = event.expect("Webhookを受信しませんでした。");
+    assert_eq!(event["type"], "user.password_reset_requested");
+    assert_eq!(event["emailAddress"], email_address);
+    let token = event["passwordResetToken"].as_str().unwrap().to_owned();
+
+    // パスワードリセットトークンでパスワードをリセットして、新しいパスワードでログインできることを確認
+    let new_password = "Fk3$wLq9@zTp".to_owned();
+    let response = app
+        .call_reset_password_api(&json!({
+            "token": token,
+            "emailAddress": email_address,
+            "newPassword": new_password,
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app
+        .call_login_api(&LoginData {
+            email_address,
+            password: new_password,
+        })
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 秘密の質問の回答を続けて間違えたユーザーは、正しい回答でもアカウントを回復できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_recover_account_after_too_many_failed_attempts() {
+    let (app, receiver) = spawn_web_app_with_webhook_receiver().await;
+    let email_address = app
+        .test_users
+        .active_user
+        .email_address()
+        .value()
+        .to_owned();
+    set_active_user_security_questions(&app).await;
+
+    // 上限の回数まで間違った回答でアカウントの回復を試行
+    for _ in 0..ACCOUNT_RECOVERY_MAX_FAILED_ATTEMPTS {
+        let response = app
+            .call_recover_account_api(&json!({
+                "emailAddress": email_address,
+                "answers": ["tama", "yokohama"],
+            }))
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    }
+
+    // 正しい回答でも同じ応答を返却して、Webhookで通知しない
+    let response = app
+        .call_recover_account_api(&json!({
+            "emailAddress": email_address,
+            "answers": ["tama", "kamakura"],
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(receiver.received_requests().await.unwrap().is_empty());
+
+    // ログインに成功すると、再びアカウントを回復できる
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app
+        .call_recover_account_api(&json!({
+            "emailAddress": email_address,
+            "answers": ["tama", "kamakura"],
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let mut received = false;
+    for _ in 0..50 {
+        if !receiver.received_requests().await.unwrap().is_empty() {
+            received = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(received, "Webhookを受信しませんでした。");
+}
+
+/// 秘密の質問を設定していないユーザーは、アカウントを回復できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_recover_account_without_security_questions() {
+    let (app, receiver) = spawn_web_app_with_webhook_receiver().await;
+    let response = app
+        .call_recover_account_api(&json!({
+            "emailAddress": app.test_users.active_user.email_address().value(),
+            "answers": ["tama"],
+        }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(receiver.received_requests().await.unwrap().is_empty());
+}
+
+/// 回答の数が不正な場合は、アカウントの回復を受け付けないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_recover_account_with_invalid_number_of_answers() {
+    let app = spawn_web_app(true).await;
+    let email_address = app.test_users.active_user.email_address().value();
+    for answers in [json!([]), json!(["a", "b", "c", "d", "e", "f"])] {
+        let response = app
+            .call_recover_account_api(&json!({
+                "emailAddress": email_address,
+                "answers": answers,
+            }))
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,11 +1,20 @@
 extern crate web_server;
 
+use std::collections::HashSet;
+
+use secrecy::Secret;
 use serde::Deserialize;
 use time::OffsetDateTime;
+use uuid::Uuid;
+
+use web_server::session_stores::InMemorySessionStore;
 
-use crate::helpers::{spawn_web_app, SignupData, TestWebApp};
+use crate::helpers::{
+    spawn_web_app, spawn_web_app_with, spawn_web_app_with_store, SignupData, TestWebApp,
+};
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct PartialUser {
     user_name: String,
     email_address: String,
@@ -57,3 +66,421 @@ async fn cannot_signup_same_email_address() {
     let response = signup_fixed_user(&app).await;
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
 }
+
+/// プライバシーモードでは、同じEメールアドレスを持つユーザーが登録されていても、登録したときと同じ
+/// レスポンスを返却して、2つ目のユーザーを登録しないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_same_email_address_in_privacy_mode() {
+    let app = spawn_web_app_with(true, |settings| settings.signup.privacy_mode = true).await;
+    let response = signup_fixed_user(&app).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let first_body = response.bytes().await.unwrap();
+    // 同じEメールアドレスで、異なるユーザー名とパスワードを登録
+    let data = SignupData {
+        user_name: "bar".to_owned(),
+        email_address: EMAIL_ADDRESS.to_owned(),
+        // cspell:disable-next-line
+        password: "pV9#wq2LmZ!x".to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    assert_eq!(response.bytes().await.unwrap(), first_body);
+
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM users
+        WHERE email_address = $1
+        "#,
+        EMAIL_ADDRESS
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(count.count, 1);
+    // 最初に登録したユーザーのパスワードでログインできる
+    let data = serde_json::json!({
+        "emailAddress": EMAIL_ADDRESS,
+        "password": PASSWORD,
+    });
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// JSONペイロードが上限を超えている場合に、`413 Payload Too Large`が返却されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_oversized_body() {
+    let app = spawn_web_app(true).await;
+    let data = SignupData {
+        user_name: "x".repeat(app.settings.web_app.json_payload_limit + 1),
+        email_address: EMAIL_ADDRESS.to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+}
+
+/// クライアントでハッシュ化したパスワード（"client-side-password"のSHA-256）
+// cspell:disable-next-line
+const CLIENT_HASHED_PASSWORD: &str =
+    "c5fad3bb5acbe98019011e34f23b8abdb62783bf68fd2c5e755e0108e589042e";
+
+/// クライアントでハッシュ化したパスワードでサインアップして、ログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_and_login_with_client_hashed_password() {
+    let app = spawn_web_app(true).await;
+    // パスワードの文字種の検証をせずにサインアップできることを確認
+    let data = serde_json::json!({
+        "userName": USER_NAME,
+        "emailAddress": EMAIL_ADDRESS,
+        "password": CLIENT_HASHED_PASSWORD,
+        "clientHashed": true,
+    });
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // クライアントでハッシュ化したパスワードでログインできることを確認
+    let data = serde_json::json!({
+        "emailAddress": EMAIL_ADDRESS,
+        "password": CLIENT_HASHED_PASSWORD,
+        "clientHashed": true,
+    });
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 平文のパスワードでサインアップしたユーザーが、平文のパスワードでのみログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_and_login_with_plain_password() {
+    let app = spawn_web_app(true).await;
+    let response = signup_fixed_user(&app).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 平文のパスワードをクライアントでハッシュ化したパスワードとして送信した場合はログインできない
+    let data = serde_json::json!({
+        "emailAddress": EMAIL_ADDRESS,
+        "password": CLIENT_HASHED_PASSWORD,
+        "clientHashed": true,
+    });
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 平文のパスワードでログインできる
+    let data = serde_json::json!({
+        "emailAddress": EMAIL_ADDRESS,
+        "password": PASSWORD,
+    });
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// クライアントでハッシュ化したパスワードが短すぎる場合に、サインアップできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_short_client_hashed_password() {
+    let app = spawn_web_app(true).await;
+    let data = serde_json::json!({
+        "userName": USER_NAME,
+        "emailAddress": EMAIL_ADDRESS,
+        "password": "short",
+        "clientHashed": true,
+    });
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+/// 検証に失敗したすべてのフィールドのエラーが、構造化されて返却されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_with_invalid_fields_returns_structured_errors() {
+    let app = spawn_web_app(true).await;
+    let data = SignupData {
+        user_name: "x".to_owned(),
+        email_address: "not-an-email-address".to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    let fields: Vec<&str> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["userName", "emailAddress"]);
+}
+
+/// 予約されたユーザー名で、大文字と小文字にかかわらず登録できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_reserved_user_name() {
+    let app = spawn_web_app(true).await;
+    for user_name in ["admin", "Admin"] {
+        let data = SignupData {
+            user_name: user_name.to_owned(),
+            email_address: EMAIL_ADDRESS.to_owned(),
+            password: PASSWORD.to_owned(),
+        };
+        let response = app.call_signup_api(&data).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            "{}",
+            user_name
+        );
+    }
+}
+
+/// 大文字と小文字のみが異なるユーザー名を登録できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_user_name_differing_only_in_case() {
+    let app = spawn_web_app(true).await;
+    let data = SignupData {
+        user_name: "Alice".to_owned(),
+        email_address: "alice@example.com".to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 登録したユーザー名の大文字と小文字が保持されていることを確認
+    let user = response.json::<PartialUser>().await.unwrap();
+    assert_eq!(user.user_name, "Alice");
+
+    let data = SignupData {
+        user_name: "alice".to_owned(),
+        email_address: "another-alice@example.com".to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// サインアップを停止している場合は、ユーザーを登録せずに`403 Forbidden`で応答することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_when_signup_is_disabled() {
+    let app = spawn_web_app_with(true, |settings| settings.signup.enabled = false).await;
+    let response = signup_fixed_user(&app).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "SIGNUP_DISABLED");
+
+    // リクエストボディの検証に失敗する場合も、同じエラーで応答
+    let data = SignupData {
+        user_name: USER_NAME.to_owned(),
+        email_address: "invalid".to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM users
+        WHERE email_address = $1
+        "#,
+        EMAIL_ADDRESS
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(count.count, 0);
+}
+
+/// サインアップのレスポンスボディが、リクエストボディと同じキャメルケースのキーを使用することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_response_uses_camel_case_keys() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = signup_fixed_user(&app).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let mut keys: Vec<&str> = body
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec![
+            "createdAt",
+            "emailAddress",
+            "id",
+            "isActive",
+            "updatedAt",
+            "userName"
+        ]
+    );
+}
+
+/// 使い捨てEメールアドレスを拒否するように設定した場合は、使い捨てEメールアドレスのドメインでは登録できず、
+/// それ以外のドメインでは登録できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_disposable_email_address() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |settings| {
+        settings.signup.disposable_email_domains = HashSet::from(["mailinator.com".to_owned()]);
+    })
+    .await;
+    let data = SignupData {
+        user_name: USER_NAME.to_owned(),
+        email_address: "foo@mailinator.com".to_owned(),
+        password: PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM users
+        WHERE email_address = $1
+        "#,
+        data.email_address
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(count.count, 0);
+
+    // 使い捨てEメールアドレスではないドメインでは登録できる
+    let response = signup_fixed_user(&app).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// テストで使用する管理者APIキー
+const ADMIN_API_KEY: &str = "admin-api-key-for-test";
+
+/// サインアップを停止して、管理者APIを有効にしたWebアプリを起動する。
+async fn spawn_invite_only_web_app() -> TestWebApp {
+    spawn_web_app_with(true, |settings| {
+        settings.signup.enabled = false;
+        settings.admin.api_key = Some(Secret::new(ADMIN_API_KEY.to_owned()));
+    })
+    .await
+}
+
+/// 管理者APIで招待コードを発行する。
+async fn issue_invite_code(app: &TestWebApp) -> String {
+    let response = app.call_admin_issue_invite_code_api(ADMIN_API_KEY).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    body["inviteCode"].as_str().unwrap().to_owned()
+}
+
+/// 招待コードを指定してサインアップする。
+async fn signup_with_invite_code(
+    app: &TestWebApp,
+    user_name: &str,
+    email_address: &str,
+    invite_code: &str,
+) -> reqwest::Response {
+    let data = serde_json::json!({
+        "userName": user_name,
+        "emailAddress": email_address,
+        "password": PASSWORD,
+        "inviteCode": invite_code,
+    });
+
+    app.call_signup_api(&data).await
+}
+
+/// 招待コードを使用したユーザーを取得する。
+async fn invite_code_used_by(app: &TestWebApp) -> Vec<Option<Uuid>> {
+    sqlx::query!("SELECT used_by FROM invite_codes")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.used_by)
+        .collect()
+}
+
+/// サインアップを停止していても、未使用の招待コードを指定するとサインアップでき、招待コードが使用済みに
+/// なることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_with_valid_invite_code() {
+    let app = spawn_invite_only_web_app().await;
+    let invite_code = issue_invite_code(&app).await;
+    // 平文の招待コードはデータベースに記録しない
+    let stored = sqlx::query!("SELECT code_hash FROM invite_codes")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.code_hash, invite_code);
+
+    let response = signup_with_invite_code(&app, USER_NAME, EMAIL_ADDRESS, &invite_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let user_id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(invite_code_used_by(&app).await, vec![Some(user_id)]);
+}
+
+/// 使用済みの招待コードでは、サインアップできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_used_invite_code() {
+    let app = spawn_invite_only_web_app().await;
+    let invite_code = issue_invite_code(&app).await;
+    let response = signup_with_invite_code(&app, USER_NAME, EMAIL_ADDRESS, &invite_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let response = signup_with_invite_code(&app, "bar", "bar@example.com", &invite_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM users
+        WHERE email_address = $1
+        "#,
+        "bar@example.com"
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(count.count, 0);
+}
+
+/// 発行されていない招待コードでは、サインアップできず、ユーザーが登録されないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_signup_with_invalid_invite_code() {
+    let app = spawn_invite_only_web_app().await;
+    let invite_code = issue_invite_code(&app).await;
+
+    let response = signup_with_invite_code(&app, USER_NAME, EMAIL_ADDRESS, "not-issued-code").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM users
+        WHERE email_address = $1
+        "#,
+        EMAIL_ADDRESS
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(count.count, 0);
+    // 発行した招待コードは未使用のまま
+    assert_eq!(invite_code_used_by(&app).await, vec![None]);
+
+    // 発行した招待コードではサインアップできる
+    let response = signup_with_invite_code(&app, USER_NAME, EMAIL_ADDRESS, &invite_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
@@ -0,0 +1,31 @@
+use crate::helpers::spawn_web_app;
+
+/// ログインした直後は、トークンの有効期限までの秒数が正の値で、システム設定の有効期間を超えないことを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn token_status_after_login() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let response = app.call_token_status_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let access_expires_in = body["accessExpiresIn"].as_u64().unwrap();
+    let refresh_expires_in = body["refreshExpiresIn"].as_u64().unwrap();
+    let tokens = &app.settings.tokens;
+    assert!(0 < access_expires_in);
+    assert!(access_expires_in <= tokens.access_token_duration.whole_seconds() as u64);
+    assert!(access_expires_in < refresh_expires_in);
+    assert!(refresh_expires_in <= tokens.refresh_token_duration.whole_seconds() as u64);
+}
+
+/// ログインしていない場合は、トークンの状態を取得できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_get_token_status_without_login() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_token_status_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,341 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use configurations::totp::{generate_totp_code, RECOVERY_CODE_COUNT, TOTP_PERIOD};
+
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app_with_store, TestWebApp};
+
+/// 現在日時をUNIXエポック秒で返却する。
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// ログインしているユーザーの2要素認証の登録を開始して、確認待ちの共有シークレットを取得する。
+async fn start_enrollment(app: &TestWebApp, body: &serde_json::Value) -> String {
+    let response = app.call_enroll_totp_api(body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let secret = body["secret"].as_str().unwrap().to_owned();
+    let uri = body["otpauthUri"].as_str().unwrap();
+    assert!(
+        uri.starts_with("otpauth://totp/jwt-auth-example:"),
+        "{}",
+        uri
+    );
+    assert!(uri.contains(&format!("secret={}", secret)), "{}", uri);
+    // 確認するまでは、リカバリーコードを発行しないことを確認
+    assert!(body.get("recoveryCodes").is_none());
+
+    secret
+}
+
+/// 確認待ちの共有シークレットで生成したコードで、2要素認証の登録を確認する。
+///
+/// 受け付けたコード以前の時間ステップのコードは再使用できないため、確認した後に現在と次の時間ステップのコードで
+/// ログインできるように、前の時間ステップのコードで確認する。
+///
+/// # Returns
+///
+/// リカバリーコード。
+async fn confirm_enrollment(app: &TestWebApp, secret: &str) -> Vec<String> {
+    let code = generate_totp_code(secret, now() - TOTP_PERIOD).unwrap();
+    let response = app.call_confirm_totp_api(&code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let recovery_codes = recovery_codes(&body);
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    recovery_codes
+}
+
+/// アクティブユーザーでログインして2要素認証を有効にした後、ログアウトする。
+///
+/// # Returns
+///
+/// Base32でエンコードした共有シークレットとリカバリーコード。
+async fn enroll_totp(app: &TestWebApp) -> (String, Vec<String>) {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let secret = start_enrollment(app, &serde_json::json!({})).await;
+    let recovery_codes = confirm_enrollment(app, &secret).await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    (secret, recovery_codes)
+}
+
+/// 指定した日時のTOTPのコードでログインする。
+async fn login_with_totp_code(app: &TestWebApp, secret: &str, at: u64) {
+    let challenge_token = login_with_password(app).await;
+    let code = generate_totp_code(secret, at).unwrap();
+    let response = app.call_login_totp_api(&challenge_token, &code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// レスポンスボディからリカバリーコードを取得する。
+fn recovery_codes(body: &serde_json::Value) -> Vec<String> {
+    body["recoveryCodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_owned())
+        .collect()
+}
+
+/// パスワードでログインして、TOTPチャレンジトークンを取得する。
+async fn login_with_password(app: &TestWebApp) -> String {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    body["challengeToken"].as_str().unwrap().to_owned()
+}
+
+/// 2要素認証を有効にしたユーザーは、パスワードで認証しただけではセッションが開始されず、正しいコードを
+/// 送信するとログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_correct_totp_code() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (secret, _) = enroll_totp(&app).await;
+
+    let challenge_token = login_with_password(&app).await;
+    // コードを検証するまでは、保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let code = generate_totp_code(&secret, now()).unwrap();
+    let response = app.call_login_totp_api(&challenge_token, &code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 間違ったコードではログインできず、失敗したログイン試行が記録されて、TOTPチャレンジを再利用できない
+/// ことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_login_with_wrong_totp_code() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (secret, _) = enroll_totp(&app).await;
+
+    let challenge_token = login_with_password(&app).await;
+    let wrong_code = generate_totp_code(&secret, now() + 10 * TOTP_PERIOD).unwrap();
+    let response = app.call_login_totp_api(&challenge_token, &wrong_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 使用したTOTPチャレンジでは、正しいコードでもログインできないことを確認
+    let code = generate_totp_code(&secret, now()).unwrap();
+    let response = app.call_login_totp_api(&challenge_token, &code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let failed = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM login_attempts
+        WHERE user_id = $1 AND NOT succeeded
+        "#,
+        app.test_users.active_user.id().value()
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(failed.count, 1);
+}
+
+/// ログインに使用したコードと、それ以前の時間ステップのコードを再使用できず、後の時間ステップのコードで
+/// ログインできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn totp_code_cannot_be_replayed() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (secret, _) = enroll_totp(&app).await;
+    let at = now();
+    login_with_totp_code(&app, &secret, at).await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ログインに使用したコードと、登録の確認に使用した前の時間ステップのコード
+    for replayed_at in [at, at - TOTP_PERIOD] {
+        let challenge_token = login_with_password(&app).await;
+        let code = generate_totp_code(&secret, replayed_at).unwrap();
+        let response = app.call_login_totp_api(&challenge_token, &code).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::UNAUTHORIZED,
+            "{}",
+            replayed_at
+        );
+        let response = app.call_protected_api().await;
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+
+    // 次の時間ステップのコードでログインできることを確認
+    login_with_totp_code(&app, &secret, at + TOTP_PERIOD).await;
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしていない場合は、2要素認証を有効にできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_enroll_totp_without_login() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_enroll_totp_api(&serde_json::json!({})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// リカバリーコードでログインでき、使用したリカバリーコードは再度使用できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn recovery_code_can_be_used_only_once() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (_, recovery_codes) = enroll_totp(&app).await;
+
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &recovery_codes[0])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 使用したリカバリーコードを再度使用
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &recovery_codes[0])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 未使用のリカバリーコードは、大文字で入力しても使用できることを確認
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &recovery_codes[1].to_uppercase())
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// リカバリーコードを再発行すると、以前のリカバリーコードを使用できなくなることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn regenerating_recovery_codes_invalidates_old_codes() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (_, old_codes) = enroll_totp(&app).await;
+
+    // リカバリーコードでログインして、リカバリーコードを再発行
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &old_codes[0])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_regenerate_recovery_codes_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let new_codes = recovery_codes(&body);
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 以前のリカバリーコード
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &old_codes[1])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // 再発行したリカバリーコード
+    let challenge_token = login_with_password(&app).await;
+    let response = app
+        .call_login_recovery_api(&challenge_token, &new_codes[0])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 2要素認証を有効にしていない場合は、リカバリーコードを再発行できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_regenerate_recovery_codes_without_totp() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_regenerate_recovery_codes_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// 確認待ちの共有シークレットは、確認するまで2要素認証に使用されないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn pending_totp_secret_is_not_used_until_confirmed() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    start_enrollment(&app, &serde_json::json!({})).await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 確認していないため、パスワードだけでログインできることを確認
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 間違ったコードで確認すると、確認待ちの共有シークレットが破棄されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn wrong_confirmation_code_discards_pending_secret() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let secret = start_enrollment(&app, &serde_json::json!({})).await;
+
+    let wrong_code = generate_totp_code(&secret, now() + 10 * TOTP_PERIOD).unwrap();
+    let response = app.call_confirm_totp_api(&wrong_code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // 確認待ちの共有シークレットが破棄されたため、正しいコードでも確認できないことを確認
+    let code = generate_totp_code(&secret, now()).unwrap();
+    let response = app.call_confirm_totp_api(&code).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// 2要素認証を有効にしている場合は、再認証しなければ共有シークレットを置き換えられないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_replace_totp_secret_without_reverification() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let (secret, recovery_codes) = enroll_totp(&app).await;
+    login_with_totp_code(&app, &secret, now()).await;
+
+    // 現在のコードを指定しない場合
+    let response = app.call_enroll_totp_api(&serde_json::json!({})).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    // 間違ったコードを指定した場合
+    let wrong_code = generate_totp_code(&secret, now() + 10 * TOTP_PERIOD).unwrap();
+    let response = app
+        .call_enroll_totp_api(&serde_json::json!({ "currentCode": wrong_code }))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 以前の共有シークレットでログインできることを確認
+    login_with_totp_code(&app, &secret, now() + TOTP_PERIOD).await;
+
+    // リカバリーコードで再認証して、共有シークレットを置き換え
+    let new_secret = start_enrollment(
+        &app,
+        &serde_json::json!({ "recoveryCode": recovery_codes[0] }),
+    )
+    .await;
+    confirm_enrollment(&app, &new_secret).await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 置き換えた共有シークレットでログインできることを確認
+    login_with_totp_code(&app, &new_secret, now()).await;
+}
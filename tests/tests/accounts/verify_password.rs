@@ -0,0 +1,35 @@
+use crate::helpers::spawn_web_app;
+
+/// 現在のパスワードが一致する場合は`200 OK`、一致しない場合は`401 Unauthorized`で応答され、どちらの場合も
+/// セッションが継続することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn verify_current_password() {
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let tokens = app.get_token_values();
+
+    // 正しいパスワード
+    let response = app.call_verify_password_api(&data.password).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 間違ったパスワード
+    let response = app.call_verify_password_api("Wrong-Passw0rd!").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // トークンが変更されず、保護されたリソースに引き続きアクセスできることを確認
+    assert_eq!(app.get_token_values(), tokens);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしていない場合は、現在のパスワードを検証できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_verify_password_without_login() {
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_verify_password_api(&data.password).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
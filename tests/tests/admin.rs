@@ -0,0 +1,324 @@
+use secrecy::Secret;
+use uuid::Uuid;
+
+use domains::models::users::DEFAULT_TENANT_ID;
+use routes::admin::{ADMIN_API_KEY_HEADER, ADMIN_TENANT_ID_HEADER};
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{
+    spawn_web_app, spawn_web_app_with, spawn_web_app_with_store, ChangePasswordData, LoginData,
+    TestWebApp,
+};
+
+/// テストで使用する管理者APIキー
+const ADMIN_API_KEY: &str = "admin-api-key-for-test";
+
+/// 管理者がリセットしたパスワード
+const RESET_PASSWORD: &str = "Reset-Passw0rd!";
+
+/// 管理者がパスワードをリセットしたユーザーは、ログインできるがパスワードの変更以外にアクセスできず、
+/// パスワードを変更した後は制限が解除されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_reset_password_requires_password_change() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.admin.api_key = Some(Secret::new(ADMIN_API_KEY.to_owned()));
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 管理者がパスワードをリセットすると、既存のセッションが失効することを確認
+    let user_id = app.test_users.active_user.id().value();
+    let response = app
+        .call_admin_reset_password_api(user_id, RESET_PASSWORD, Some(ADMIN_API_KEY))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // リセットしたパスワードでログインすると、パスワードの変更を要求されることを確認
+    let data = LoginData {
+        email_address: app.active_user_login_data().email_address,
+        password: RESET_PASSWORD.to_owned(),
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["mustChangePassword"], true);
+    // パスワードを変更するまでは、保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PASSWORD_CHANGE_REQUIRED");
+
+    // パスワードを変更
+    let change_password_data = ChangePasswordData {
+        current_password: RESET_PASSWORD.to_owned(),
+        new_password: "6i8TR:6Al@.d".to_owned(),
+    };
+    let response = app.call_change_password_api(&change_password_data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 変更したパスワードでログインすると、保護されたリソースにアクセスできることを確認
+    let data = LoginData {
+        email_address: data.email_address,
+        password: change_password_data.new_password,
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.text().await.unwrap().is_empty());
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 管理者APIキーが一致しない場合や、ユーザーが存在しない場合に、パスワードをリセットできないことを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_reset_password_requires_api_key() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.admin.api_key = Some(Secret::new(ADMIN_API_KEY.to_owned()));
+    })
+    .await;
+    let user_id = app.test_users.active_user.id().value();
+    for api_key in [None, Some("wrong-api-key")] {
+        let response = app
+            .call_admin_reset_password_api(user_id, RESET_PASSWORD, api_key)
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+    let response = app
+        .call_admin_reset_password_api(Uuid::new_v4(), RESET_PASSWORD, Some(ADMIN_API_KEY))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // パスワードが変更されていないことを確認
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 管理者APIキーが設定されていない場合は、管理者APIを使用できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_api_is_disabled_without_api_key() {
+    let app = spawn_web_app(true).await;
+    let user_id = app.test_users.active_user.id().value();
+    let response = app
+        .call_admin_reset_password_api(user_id, RESET_PASSWORD, Some(ADMIN_API_KEY))
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// ログインしているテストユーザーのセッションIDを取得する。
+async fn active_user_session_id(app: &TestWebApp) -> Uuid {
+    sqlx::query!(
+        r#"
+        SELECT session_id
+        FROM refresh_tokens
+        WHERE user_id = $1
+        "#,
+        app.test_users.active_user.id().value()
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .session_id
+}
+
+/// 管理者がユーザーのセッションを失効させると、セッションストアからもセッションが削除されて、そのセッションで
+/// 保護されたリソースにアクセスできなくなり、存在しないセッションを指定した場合は`404 Not Found`で応答する
+/// ことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_revoke_session_denies_protected_access() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let session_id = active_user_session_id(&app).await;
+
+    // 管理者がユーザーのセッションを失効させる
+    let admin_client = app.admin_client().await;
+    let response = app
+        .call_admin_revoke_session_api(&admin_client, session_id)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // セッションストアからセッションが削除されたため、セッションが存在しないものとして拒否
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNAUTHORIZED");
+
+    // 失効したセッションや存在しないセッションを指定した場合は、`404 Not Found`で応答
+    for session_id in [session_id, Uuid::new_v4()] {
+        let response = app
+            .call_admin_revoke_session_api(&admin_client, session_id)
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
+
+/// 管理者が失効させたセッションは、データベースでセッションが失効しているか確認できない場合でも、保護された
+/// リソースにアクセスできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_revoked_session_is_rejected_while_session_check_is_unavailable() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let session_id = active_user_session_id(&app).await;
+    let admin_client = app.admin_client().await;
+    let response = app
+        .call_admin_revoke_session_api(&admin_client, session_id)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // リフレッシュトークンを問い合わせできないように、テーブルの名前を変更
+    sqlx::query("ALTER TABLE refresh_tokens RENAME TO refresh_tokens_unavailable")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// 管理者の役割を持たないユーザーや、認証されていないクライアントは、セッションを失効させられないことを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_revoke_session_requires_admin_role() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.admin.api_key = Some(Secret::new(ADMIN_API_KEY.to_owned()));
+    })
+    .await;
+    let session_url =
+        |session_id: Uuid| format!("{}/admin/sessions/{}", app.web_app_address, session_id);
+
+    // 認証されていないクライアントは、管理者APIキーを指定しても拒否
+    let response = app
+        .api_client
+        .delete(session_url(Uuid::new_v4()))
+        .header(ADMIN_API_KEY_HEADER, ADMIN_API_KEY)
+        .header(ADMIN_TENANT_ID_HEADER, DEFAULT_TENANT_ID.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 管理者の役割を持たないユーザーは、自分のセッションも失効させられない
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let session_id = active_user_session_id(&app).await;
+    let response = app
+        .call_admin_revoke_session_api(&app.api_client, session_id)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INSUFFICIENT_ROLE");
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 管理者APIは、指定されたテナントのユーザーとセッションのみを操作して、テナントを指定していない場合は
+/// 拒否することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn admin_api_is_scoped_to_tenant() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |settings| {
+        settings.admin.api_key = Some(Secret::new(ADMIN_API_KEY.to_owned()));
+        settings.signup.enabled = false;
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let user_id = app.test_users.active_user.id().value();
+    let session = sqlx::query!(
+        "SELECT session_id FROM refresh_tokens WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    let reset_password_url = format!(
+        "{}/admin/users/{}/reset_password",
+        app.web_app_address, user_id
+    );
+    let invite_codes_url = format!("{}/admin/invite_codes", app.web_app_address);
+    let body = serde_json::json!({ "newPassword": RESET_PASSWORD });
+
+    // テナントを指定していないか、テナントIDとして解釈できない場合は拒否
+    for tenant_id in [None, Some("not-a-tenant-id")] {
+        for request in [
+            app.api_client.post(&reset_password_url).json(&body),
+            app.api_client.post(&invite_codes_url),
+        ] {
+            let mut request = request.header(ADMIN_API_KEY_HEADER, ADMIN_API_KEY);
+            if let Some(tenant_id) = tenant_id {
+                request = request.header(ADMIN_TENANT_ID_HEADER, tenant_id);
+            }
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["code"], "TENANT_REQUIRED");
+        }
+    }
+
+    // 他のテナントのユーザーとセッションは、存在しないものとして扱う
+    let other_tenant_id = Uuid::new_v4().to_string();
+    let response = app
+        .api_client
+        .post(&reset_password_url)
+        .json(&body)
+        .header(ADMIN_API_KEY_HEADER, ADMIN_API_KEY)
+        .header(ADMIN_TENANT_ID_HEADER, &other_tenant_id)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    // 他のテナントに所属する管理者は、ユーザーのセッションを失効させられない
+    sqlx::query!(
+        "UPDATE users SET tenant_id = $1 WHERE id = $2",
+        Uuid::parse_str(&other_tenant_id).unwrap(),
+        app.test_users.admin_user.id().value()
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    let admin_client = app.admin_client().await;
+    let response = app
+        .call_admin_revoke_session_api(&admin_client, session.session_id)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // 他のテナントの招待コードでサインアップしたユーザーは、そのテナントに所属する
+    let response = app
+        .api_client
+        .post(&invite_codes_url)
+        .header(ADMIN_API_KEY_HEADER, ADMIN_API_KEY)
+        .header(ADMIN_TENANT_ID_HEADER, &other_tenant_id)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let data = serde_json::json!({
+        "userName": "invited-user",
+        "emailAddress": "invited-user@example.com",
+        "password": RESET_PASSWORD,
+        "inviteCode": body["inviteCode"],
+    });
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let user = sqlx::query!(
+        "SELECT tenant_id FROM users WHERE email_address = $1",
+        "invited-user@example.com"
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(user.tenant_id.to_string(), other_tenant_id);
+}
@@ -0,0 +1,111 @@
+use reqwest::{header, Method, StatusCode};
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with, TestWebApp};
+
+/// クロスオリジンリクエストを許可するオリジン
+const ALLOWED_ORIGIN: &str = "https://spa.example.com";
+
+/// CORSを有効にしたテスト用Webアプリを生成する。
+async fn spawn_cors_enabled_web_app() -> TestWebApp {
+    spawn_web_app_with(true, |settings| {
+        settings.web_app.cors_allowed_origins = vec![ALLOWED_ORIGIN.to_owned()];
+    })
+    .await
+}
+
+/// 保護されたリソースに、プリフライトリクエストを送信する。
+async fn send_preflight(app: &TestWebApp, method: &str) -> reqwest::Response {
+    app.api_client
+        .request(
+            Method::OPTIONS,
+            format!("{}/protected_resource", app.web_app_address),
+        )
+        .header(header::ORIGIN, ALLOWED_ORIGIN)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+        .send()
+        .await
+        .expect("保護リソースにプリフライトリクエストを送信できませんでした。")
+}
+
+/// 保護されたリソースに、オリジンを指定してGETリクエストを送信する。
+async fn get_protected_resource_from_origin(app: &TestWebApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/protected_resource", app.web_app_address))
+        .header(header::ORIGIN, ALLOWED_ORIGIN)
+        .send()
+        .await
+        .expect("保護リソース取得APIにアクセスできませんでした。")
+}
+
+/// 応答に許可したオリジンのCORSヘッダーが含まれていることを確認する。
+fn assert_cors_headers(response: &reqwest::Response) {
+    let headers = response.headers();
+    assert_eq!(
+        headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ALLOWED_ORIGIN
+    );
+    assert_eq!(
+        headers
+            .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .unwrap(),
+        "true"
+    );
+}
+
+/// 保護されたリソースへのプリフライトリクエストが、認証されずにCORSヘッダーを返却することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn preflight_request_to_protected_resource_is_not_authenticated() {
+    let app = spawn_cors_enabled_web_app().await;
+    let response = send_preflight(&app, "GET").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_cors_headers(&response);
+    let allowed_methods = response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allowed_methods.contains("GET"), "{}", allowed_methods);
+}
+
+/// プリフライトリクエスト以外のクロスオリジンリクエストは、従来通り認証が必要であることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cross_origin_request_to_protected_resource_requires_authentication() {
+    let app = spawn_cors_enabled_web_app().await;
+    // 認証されていない場合は、ブラウザが応答を読めるようにCORSヘッダーを付与して`401 Unauthorized`で応答
+    let response = get_protected_resource_from_origin(&app).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_cors_headers(&response);
+    // ログインした後は、保護されたリソースにアクセスできる
+    let response = app
+        .api_client
+        .post(format!("{}/accounts/login", app.web_app_address))
+        .header(header::ORIGIN, ALLOWED_ORIGIN)
+        .json(&app.active_user_login_data())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_cors_headers(&response);
+    let response = get_protected_resource_from_origin(&app).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_cors_headers(&response);
+}
+
+/// CORSを有効にしていない場合も、OPTIONSリクエストが認証ミドルウェアで拒否されないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn options_request_is_not_rejected_by_authentication_middleware() {
+    let app = spawn_web_app(true).await;
+    let response = send_preflight(&app, "GET").await;
+    assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+    // 実際のメソッドでは、従来通り認証が必要
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -1,6 +1,8 @@
 extern crate web_server;
 
-use crate::helpers::spawn_web_app;
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with_store};
 
 /// ヘルスチェックが正常に動作するか確認するテスト
 #[tokio::test]
@@ -18,3 +20,16 @@ async fn health_check() {
         "ヘルスチェックAPIが返却したボディが想定と一致しません。"
     )
 }
+
+/// バージョンAPIが、クレートのバージョンとビルド情報を返却することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn version_returns_build_info() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let response = app.call_version_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(!body["version"].as_str().unwrap().is_empty());
+    assert!(!body["gitCommit"].as_str().unwrap().is_empty());
+    assert!(!body["buildTimestamp"].as_str().unwrap().is_empty());
+}
@@ -1,16 +1,24 @@
 use std::sync::{Arc, MutexGuard};
 
+use actix_web::cookie::{Cookie as ActixCookie, CookieJar, Key};
 use cookie_store::{Cookie, CookieStore};
 use dotenvy::dotenv;
 use once_cell::sync::Lazy;
 use reqwest_cookie_store::CookieStoreMutex;
+use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPool, Connection, Executor, PgConnection};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::client::IntoClientRequest, MaybeTlsStream, WebSocketStream};
 use uuid::Uuid;
 
 use configurations::session::{ACCESS_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
 use configurations::telemetries::{get_subscriber, init_subscriber};
 use configurations::{DatabaseSettings, Settings};
+use domains::models::users::DEFAULT_TENANT_ID;
+use middlewares::API_KEY_AUTH_SCHEME;
+use routes::admin::{ADMIN_API_KEY_HEADER, ADMIN_TENANT_ID_HEADER};
+use web_server::session_stores::KeyedSessionStore;
 use web_server::startup::{get_connection_pool, WebApp};
 
 use crate::users::TestUsers;
@@ -52,9 +60,13 @@ pub struct ChangePasswordData {
 }
 
 /// テスト用Webアプリ構造体
+/// テスト用Webアプリに接続したWebSocket
+pub type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
 pub struct TestWebApp {
     pub settings: Settings,
     pub web_app_address: String,
+    #[allow(dead_code)]
     pub port: u16,
     pub pool: PgPool,
     pub api_client: reqwest::Client,
@@ -66,16 +78,25 @@ impl TestWebApp {
     /// ヘルスチェックAPIを呼び出す。
     pub async fn call_health_check_api(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/health_check", self.web_app_address))
+            .get(format!("{}/health_check", self.web_app_address))
             .send()
             .await
             .expect("ヘルスチェックAPIにアクセスできませんでした。")
     }
 
+    /// バージョンAPIを呼び出す。
+    pub async fn call_version_api(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/version", self.web_app_address))
+            .send()
+            .await
+            .expect("バージョンAPIにアクセスできませんでした。")
+    }
+
     /// サインアップAPIを呼び出す。
-    pub async fn call_signup_api(&self, data: &SignupData) -> reqwest::Response {
+    pub async fn call_signup_api<T: Serialize>(&self, data: &T) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/accounts/signup", self.web_app_address))
+            .post(format!("{}/accounts/signup", self.web_app_address))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .json(&data)
             .send()
@@ -107,10 +128,42 @@ impl TestWebApp {
         }
     }
 
+    pub fn admin_user_login_data(&self) -> LoginData {
+        LoginData {
+            email_address: self
+                .test_users
+                .admin_user
+                .email_address()
+                .value()
+                .to_owned(),
+            password: self.test_users.admin_user_password.clone(),
+        }
+    }
+
+    /// 管理者ユーザーでログインしたAPIクライアントを返却する。
+    ///
+    /// テストユーザーのセッションと区別できるように、テスト用WebアプリのAPIクライアントとは異なるクッキーストアを
+    /// 使用する。
+    pub async fn admin_client(&self) -> reqwest::Client {
+        let client = reqwest::Client::builder()
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let response = client
+            .post(format!("{}/accounts/login", self.web_app_address))
+            .json(&self.admin_user_login_data())
+            .send()
+            .await
+            .expect("ログインAPIにアクセスできませんでした。");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        client
+    }
+
     /// ログインAPIを呼び出す。
-    pub async fn call_login_api(&self, data: &LoginData) -> reqwest::Response {
+    pub async fn call_login_api<T: Serialize>(&self, data: &T) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/accounts/login", self.web_app_address))
+            .post(format!("{}/accounts/login", self.web_app_address))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .json(&data)
             .send()
@@ -121,21 +174,148 @@ impl TestWebApp {
     /// ログアウトAPIを呼び出す。
     pub async fn call_logout_api(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/accounts/logout", self.web_app_address))
+            .post(format!("{}/accounts/logout", self.web_app_address))
             .send()
             .await
             .expect("ログアウトAPIにアクセスできませんでした。")
     }
 
+    /// リフレッシュAPIを呼び出す。
+    pub async fn call_refresh_api(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/refresh", self.web_app_address))
+            .send()
+            .await
+            .expect("リフレッシュAPIにアクセスできませんでした。")
+    }
+
+    /// リフレッシュトークン失効APIを呼び出す。
+    pub async fn call_revoke_refresh_token_api(
+        &self,
+        data: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/refresh/revoke", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("リフレッシュトークン失効APIにアクセスできませんでした。")
+    }
+
     /// 保護リソース取得APIを呼び出す。
     pub async fn call_protected_api(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/protected_resource", self.web_app_address))
+            .get(format!("{}/protected_resource", self.web_app_address))
+            .send()
+            .await
+            .expect("保護リソース取得APIにアクセスできませんでした。")
+    }
+
+    /// 認証したユーザーとトークンの情報を応答するように、保護リソース取得APIを呼び出す。
+    pub async fn call_verbose_protected_api(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/protected_resource?verbose=true",
+                self.web_app_address
+            ))
+            .send()
+            .await
+            .expect("保護リソース取得APIにアクセスできませんでした。")
+    }
+
+    /// APIキーを指定して、保護リソース取得APIを呼び出す。
+    pub async fn call_protected_api_with_api_key(&self, api_key: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/protected_resource", self.web_app_address))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("{} {}", API_KEY_AUTH_SCHEME, api_key),
+            )
             .send()
             .await
             .expect("保護リソース取得APIにアクセスできませんでした。")
     }
 
+    /// APIキー発行APIを呼び出す。
+    ///
+    /// APIキーを指定した場合は、APIキーで認証する。
+    pub async fn call_issue_api_key_api(
+        &self,
+        data: &serde_json::Value,
+        api_key: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .post(format!("{}/api_keys", self.web_app_address))
+            .json(data);
+        if let Some(api_key) = api_key {
+            request = request.header(
+                reqwest::header::AUTHORIZATION,
+                format!("{} {}", API_KEY_AUTH_SCHEME, api_key),
+            );
+        }
+        request
+            .send()
+            .await
+            .expect("APIキー発行APIにアクセスできませんでした。")
+    }
+
+    /// APIキー失効APIを呼び出す。
+    pub async fn call_revoke_api_key_api(&self, id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/api_keys/{}", self.web_app_address, id))
+            .send()
+            .await
+            .expect("APIキー失効APIにアクセスできませんでした。")
+    }
+
+    /// 認証WebSocketに接続する。
+    ///
+    /// クッキーストアに記録されたクッキーを、アップグレードのリクエストに含める。
+    pub async fn connect_websocket(
+        &self,
+    ) -> Result<WebSocket, tokio_tungstenite::tungstenite::Error> {
+        self.connect_websocket_with_origin(None).await
+    }
+
+    /// `Origin`ヘッダーを指定して、認証WebSocketに接続する。
+    ///
+    /// クッキーストアに記録されたクッキーを、アップグレードのリクエストに含める。
+    pub async fn connect_websocket_with_origin(
+        &self,
+        origin: Option<&str>,
+    ) -> Result<WebSocket, tokio_tungstenite::tungstenite::Error> {
+        let url = reqwest::Url::parse(&format!("{}/ws", self.web_app_address)).unwrap();
+        let cookies = {
+            let store = self.cookie_store.lock().unwrap();
+            store
+                .get_request_values(&url)
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ")
+        };
+        let mut request = url
+            .as_str()
+            .replacen("http", "ws", 1)
+            .into_client_request()
+            .unwrap();
+        if !cookies.is_empty() {
+            request
+                .headers_mut()
+                .insert(reqwest::header::COOKIE, cookies.parse().unwrap());
+        }
+        if let Some(origin) = origin {
+            request
+                .headers_mut()
+                .insert(reqwest::header::ORIGIN, origin.parse().unwrap());
+        }
+
+        tokio_tungstenite::connect_async(request)
+            .await
+            .map(|(stream, _)| stream)
+    }
+
     pub fn change_password_data(&self) -> ChangePasswordData {
         ChangePasswordData {
             current_password: self.test_users.active_user_password.clone(),
@@ -146,10 +326,7 @@ impl TestWebApp {
     /// パスワード変更APIを呼び出す。
     pub async fn call_change_password_api(&self, data: &ChangePasswordData) -> reqwest::Response {
         self.api_client
-            .post(&format!(
-                "{}/accounts/change_password",
-                self.web_app_address
-            ))
+            .post(format!("{}/accounts/change_password", self.web_app_address))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .json(&data)
             .send()
@@ -157,6 +334,309 @@ impl TestWebApp {
             .expect("パスワード変更APIにアクセスできませんでした。")
     }
 
+    /// アカウント削除APIを呼び出す。
+    pub async fn call_delete_account_api(&self, password: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/accounts/me", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "password": password }))
+            .send()
+            .await
+            .expect("アカウント削除APIにアクセスできませんでした。")
+    }
+
+    /// 現在のパスワード検証APIを呼び出す。
+    pub async fn call_verify_password_api(&self, password: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/verify_password", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "password": password }))
+            .send()
+            .await
+            .expect("現在のパスワード検証APIにアクセスできませんでした。")
+    }
+
+    /// トークン状態APIを呼び出す。
+    pub async fn call_token_status_api(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/accounts/token_status", self.web_app_address))
+            .send()
+            .await
+            .expect("トークン状態APIにアクセスできませんでした。")
+    }
+
+    /// TOTP登録APIを呼び出す。
+    pub async fn call_enroll_totp_api(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/totp", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(body)
+            .send()
+            .await
+            .expect("TOTP登録APIにアクセスできませんでした。")
+    }
+
+    /// TOTP登録確認APIを呼び出す。
+    pub async fn call_confirm_totp_api(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/totp/confirm", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("TOTP登録確認APIにアクセスできませんでした。")
+    }
+
+    /// TOTPログインAPIを呼び出す。
+    pub async fn call_login_totp_api(
+        &self,
+        challenge_token: &str,
+        code: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/login/totp", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "challengeToken": challenge_token, "code": code }))
+            .send()
+            .await
+            .expect("TOTPログインAPIにアクセスできませんでした。")
+    }
+
+    /// リカバリーコードログインAPIを呼び出す。
+    pub async fn call_login_recovery_api(
+        &self,
+        challenge_token: &str,
+        code: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/login/recovery", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "challengeToken": challenge_token, "code": code }))
+            .send()
+            .await
+            .expect("リカバリーコードログインAPIにアクセスできませんでした。")
+    }
+
+    /// リカバリーコード再発行APIを呼び出す。
+    pub async fn call_regenerate_recovery_codes_api(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/accounts/totp/recovery_codes",
+                self.web_app_address
+            ))
+            .send()
+            .await
+            .expect("リカバリーコード再発行APIにアクセスできませんでした。")
+    }
+
+    /// 既定のテナントを操作する管理者パスワードリセットAPIを呼び出す。
+    pub async fn call_admin_reset_password_api(
+        &self,
+        user_id: Uuid,
+        new_password: &str,
+        api_key: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .post(format!(
+                "{}/admin/users/{}/reset_password",
+                self.web_app_address, user_id
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ADMIN_TENANT_ID_HEADER, DEFAULT_TENANT_ID.to_string())
+            .json(&serde_json::json!({ "newPassword": new_password }));
+        if let Some(api_key) = api_key {
+            request = request.header(ADMIN_API_KEY_HEADER, api_key);
+        }
+
+        request
+            .send()
+            .await
+            .expect("管理者パスワードリセットAPIにアクセスできませんでした。")
+    }
+
+    /// 既定のテナントに招待する招待コード発行APIを呼び出す。
+    pub async fn call_admin_issue_invite_code_api(&self, api_key: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/invite_codes", self.web_app_address))
+            .header(ADMIN_API_KEY_HEADER, api_key)
+            .header(ADMIN_TENANT_ID_HEADER, DEFAULT_TENANT_ID.to_string())
+            .send()
+            .await
+            .expect("招待コード発行APIにアクセスできませんでした。")
+    }
+
+    /// 管理者セッション失効APIを呼び出す。
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - 管理者ユーザーでログインしたAPIクライアント。
+    /// * `session_id` - 失効させるセッションのセッションID。
+    pub async fn call_admin_revoke_session_api(
+        &self,
+        client: &reqwest::Client,
+        session_id: Uuid,
+    ) -> reqwest::Response {
+        client
+            .delete(format!(
+                "{}/admin/sessions/{}",
+                self.web_app_address, session_id
+            ))
+            .send()
+            .await
+            .expect("管理者セッション失効APIにアクセスできませんでした。")
+    }
+
+    /// 秘密の質問設定APIを呼び出す。
+    pub async fn call_set_security_questions_api(
+        &self,
+        data: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .put(format!(
+                "{}/accounts/security_questions",
+                self.web_app_address
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("秘密の質問設定APIにアクセスできませんでした。")
+    }
+
+    /// プロフィール公開設定APIを呼び出す。
+    pub async fn call_set_profile_visibility_api(
+        &self,
+        data: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .put(format!(
+                "{}/accounts/profile_visibility",
+                self.web_app_address
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("プロフィール公開設定APIにアクセスできませんでした。")
+    }
+
+    /// ユーザープロフィール取得APIを呼び出す。
+    pub async fn call_get_user_profile_api(&self, user_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/users/{}", self.web_app_address, user_id))
+            .send()
+            .await
+            .expect("ユーザープロフィール取得APIにアクセスできませんでした。")
+    }
+
+    /// APIキーを指定して、ユーザープロフィール取得APIを呼び出す。
+    pub async fn call_get_user_profile_api_with_api_key(
+        &self,
+        user_id: &str,
+        api_key: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/users/{}", self.web_app_address, user_id))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("{} {}", API_KEY_AUTH_SCHEME, api_key),
+            )
+            .send()
+            .await
+            .expect("ユーザープロフィール取得APIにアクセスできませんでした。")
+    }
+
+    /// Eメールアドレスエイリアス登録APIを呼び出す。
+    pub async fn call_add_email_alias_api(&self, email_address: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/email_addresses", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "emailAddress": email_address }))
+            .send()
+            .await
+            .expect("Eメールアドレスエイリアス登録APIにアクセスできませんでした。")
+    }
+
+    /// Eメールアドレスエイリアス削除APIを呼び出す。
+    pub async fn call_remove_email_alias_api(&self, email_address: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/accounts/email_addresses", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "emailAddress": email_address }))
+            .send()
+            .await
+            .expect("Eメールアドレスエイリアス削除APIにアクセスできませんでした。")
+    }
+
+    /// プライマリEメールアドレス変更APIを呼び出す。
+    pub async fn call_set_primary_email_api(&self, email_address: &str) -> reqwest::Response {
+        self.api_client
+            .put(format!(
+                "{}/accounts/email_addresses/primary",
+                self.web_app_address
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "emailAddress": email_address }))
+            .send()
+            .await
+            .expect("プライマリEメールアドレス変更APIにアクセスできませんでした。")
+    }
+
+    /// アカウント回復APIを呼び出す。
+    pub async fn call_recover_account_api(&self, data: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/recover", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("アカウント回復APIにアクセスできませんでした。")
+    }
+
+    /// パスワードリセット要求APIを呼び出す。
+    pub async fn call_request_password_reset_api(&self, email_address: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/accounts/reset_password/request",
+                self.web_app_address
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&serde_json::json!({ "emailAddress": email_address }))
+            .send()
+            .await
+            .expect("パスワードリセット要求APIにアクセスできませんでした。")
+    }
+
+    /// パスワードリセットAPIを呼び出す。
+    pub async fn call_reset_password_api(&self, data: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/reset_password", self.web_app_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("パスワードリセットAPIにアクセスできませんでした。")
+    }
+
+    /// WebAuthn API（`/accounts/webauthn/{path}`）を呼び出す。
+    pub async fn call_webauthn_api(
+        &self,
+        path: &str,
+        data: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/accounts/webauthn/{}",
+                self.web_app_address, path
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(data)
+            .send()
+            .await
+            .expect("WebAuthn APIにアクセスできませんでした。")
+    }
+
     /// セッションIDを取得する。
     pub fn get_session_id(&self) -> Option<String> {
         let store = self.cookie_store.lock().unwrap();
@@ -167,6 +647,24 @@ impl TestWebApp {
         ))
     }
 
+    /// セッションクッキーを復号して、セッションストアのセッションキーを取得する。
+    ///
+    /// セッションクッキーは暗号化されているため、セッションキーが同じでも応答ごとに値が変わる。
+    pub fn get_session_key(&self) -> Option<String> {
+        let session_id = self.get_session_id()?;
+        let cookie_name = &self.settings.session_cookie.session_id_cookie_name;
+        let cookie = ActixCookie::parse_encoded(format!("{}={}", cookie_name, session_id))
+            .ok()?
+            .into_owned();
+        let key = Key::from(self.settings.session_store.key.expose_secret().as_bytes());
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+
+        jar.private(&key)
+            .get(cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+
     /// アクセストークンとリフレッシュトークンを取得する。
     pub fn get_token_values(&self) -> (Option<String>, Option<String>) {
         let store = self.cookie_store.lock().unwrap();
@@ -187,21 +685,72 @@ fn get_cookie<'a>(store: &'a MutexGuard<CookieStore>, key: &str) -> Option<&'a C
 }
 
 fn get_cookie_value(cookie: Option<&Cookie>) -> Option<String> {
-    match cookie {
-        Some(cookie) => Some(cookie.value().to_owned()),
-        None => None,
-    }
+    cookie.map(|cookie| cookie.value().to_owned())
 }
 
 /// テスト用Webアプリを生成する。
 ///
-/// `is_dotenv`を`false`にすることで、テストコードで`dotenv().ok()`を実行した後、環境変数を設定することで、
-/// システム設定をカスタマイズできる。
+/// システム設定をカスタマイズする場合は、`spawn_web_app_with`を使用する。
 ///
 /// # Arguments
 ///
 /// * `is_dotenv` - `true`の場合`dotenv().ok()`を実行して、`false`の場合は実行しない。
 pub async fn spawn_web_app(is_dotenv: bool) -> TestWebApp {
+    spawn_web_app_with(is_dotenv, |_| {}).await
+}
+
+/// システム設定をカスタマイズしたテスト用Webアプリを生成する。
+///
+/// 環境変数は最初に参照したときに読み込まれるため、他のテストと一緒に実行しても、環境変数を変更した
+/// テストと同様にシステム設定をカスタマイズできるように、構築したシステム設定を`customize`で変更する。
+///
+/// テスト用Webアプリごとに、新しいデータベースを作成するとともに、Redisに記録するセッションデータのキーに
+/// 異なる接頭辞を付与する。これにより、他のテストが記録したデータやセッションデータの影響を受けないため、
+/// 統合テストを並行して実行できる。
+///
+/// # Arguments
+///
+/// * `is_dotenv` - `true`の場合`dotenv().ok()`を実行して、`false`の場合は実行しない。
+/// * `customize` - システム設定を変更するクロージャー。
+pub async fn spawn_web_app_with<F>(is_dotenv: bool, customize: F) -> TestWebApp
+where
+    F: FnOnce(&mut Settings),
+{
+    let settings = prepare_settings(is_dotenv, customize).await;
+    let web_app = WebApp::build(settings.clone())
+        .await
+        .expect("テスト用Webあアプリの構築に失敗しました。");
+
+    start_web_app(settings, web_app).await
+}
+
+/// セッションストアを指定したテスト用Webアプリを生成する。
+///
+/// Redisを使用せずに、メモリ内セッションストアなどでセッションを扱うテストで使用する。
+///
+/// # Arguments
+///
+/// * `is_dotenv` - `true`の場合`dotenv().ok()`を実行して、`false`の場合は実行しない。
+/// * `store` - セッションストア。
+/// * `customize` - システム設定を変更するクロージャー。
+pub async fn spawn_web_app_with_store<S, F>(is_dotenv: bool, store: S, customize: F) -> TestWebApp
+where
+    S: KeyedSessionStore + Clone + Send + 'static,
+    F: FnOnce(&mut Settings),
+{
+    let settings = prepare_settings(is_dotenv, customize).await;
+    let web_app = WebApp::build_with_store(settings.clone(), store)
+        .await
+        .expect("テスト用Webあアプリの構築に失敗しました。");
+
+    start_web_app(settings, web_app).await
+}
+
+/// テスト用のシステム設定を構築して、テスト用のデータベースを作成する。
+async fn prepare_settings<F>(is_dotenv: bool, customize: F) -> Settings
+where
+    F: FnOnce(&mut Settings),
+{
     if is_dotenv {
         dotenv().ok();
     }
@@ -210,8 +759,14 @@ pub async fn spawn_web_app(is_dotenv: bool) -> TestWebApp {
 
     let settings = {
         let mut s = Settings::default();
-        s.web_app.port = 0; // OSにポート番号を指定してもらうようにポート0を設定
-        s.db.database_name = Uuid::new_v4().to_string(); // 新しいテスト用のデータベース
+        // OSにポート番号を指定してもらうようにポート0を設定
+        s.web_app.port = 0;
+        // テストごとに新しいデータベースを作成して、テストが記録したデータを他のテストから分離
+        s.db.database_name = Uuid::new_v4().to_string();
+        // テストごとにセッションデータのキーに異なる接頭辞を付与して、テストがRedisに記録したセッション
+        // データを他のテストから分離
+        s.session_store.key_prefix = format!("test:{}:", s.db.database_name);
+        customize(&mut s);
 
         s
     };
@@ -219,11 +774,13 @@ pub async fn spawn_web_app(is_dotenv: bool) -> TestWebApp {
     // テスト用のデータベースを作成してマイグレート
     configure_database(&settings.db).await;
 
-    let web_app = WebApp::build(settings.clone())
-        .await
-        .expect("テスト用Webあアプリの構築に失敗しました。");
+    settings
+}
+
+/// テスト用Webアプリを起動して、APIクライアントとテストユーザーを準備する。
+async fn start_web_app(settings: Settings, web_app: WebApp) -> TestWebApp {
     let port = web_app.port();
-    let _ = tokio::spawn(web_app.run_until_stopped());
+    tokio::spawn(web_app.run_until_stopped());
 
     // APIクライアントを構築
     let cookie_store = get_cookie_store();
@@ -0,0 +1,139 @@
+use reqwest::{header, StatusCode};
+use serde::Serialize;
+
+use middlewares::idempotency::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER};
+
+use crate::helpers::{spawn_web_app, SignupData, TestWebApp};
+
+/// 冪等キーを指定して、APIにPOSTリクエストを送信する。
+async fn post_with_idempotency_key<T: Serialize>(
+    app: &TestWebApp,
+    path: &str,
+    key: &str,
+    data: &T,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}{}", app.web_app_address, path))
+        .header(IDEMPOTENCY_KEY_HEADER, key)
+        .json(data)
+        .send()
+        .await
+        .expect("APIにアクセスできませんでした。")
+}
+
+fn signup_data() -> SignupData {
+    SignupData {
+        user_name: "foo".to_owned(),
+        email_address: "foo@example.com".to_owned(),
+        // cspell:disable-next-line
+        password: "tOC8pHh:K/-G".to_owned(),
+    }
+}
+
+/// 応答に含まれる`Set-Cookie`ヘッダーの値を返却する。
+fn set_cookies(response: &reqwest::Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_owned())
+        .collect()
+}
+
+/// 同じ冪等キーを指定したサインアップが1回だけ実行され、2回目は同じ応答を返却することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_with_same_idempotency_key_is_executed_once() {
+    let app = spawn_web_app(true).await;
+    let data = signup_data();
+    let first = post_with_idempotency_key(&app, "/accounts/signup", "signup-key", &data).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+    let first_body = first.text().await.unwrap();
+    // 同じ冪等キーでサインアップを再試行
+    let second = post_with_idempotency_key(&app, "/accounts/signup", "signup-key", &data).await;
+    assert_eq!(second.status(), StatusCode::OK);
+    assert_eq!(
+        second.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+        "true"
+    );
+    assert_eq!(second.text().await.unwrap(), first_body);
+    // ユーザーが1回だけ登録されていることを確認
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM users WHERE email_address = $1"#,
+        data.email_address,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 1);
+    // 冪等キーを指定しない場合は、従来通り登録済みのユーザーとして扱う
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 同じ冪等キーを指定したログインで、セッションが1つだけ生成され、2回目は同じクッキーで応答することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn login_with_same_idempotency_key_creates_one_session() {
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let first = post_with_idempotency_key(&app, "/accounts/login", "login-key", &data).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let second = post_with_idempotency_key(&app, "/accounts/login", "login-key", &data).await;
+    assert_eq!(second.status(), StatusCode::OK);
+    assert_eq!(
+        second.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+        "true"
+    );
+    assert!(!set_cookies(&first).is_empty());
+    assert_eq!(set_cookies(&second), set_cookies(&first));
+    // リフレッシュトークンが1つだけ登録されていることを確認
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM refresh_tokens WHERE user_id = $1"#,
+        app.test_users.active_user.id().value(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 1);
+}
+
+/// 冪等キーを異なるリクエストで使用した場合と、冪等キーの形式が不正な場合に、リクエストを処理しないことを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn reused_or_invalid_idempotency_key_is_rejected() {
+    let app = spawn_web_app(true).await;
+    let data = signup_data();
+    let response = post_with_idempotency_key(&app, "/accounts/signup", "signup-key", &data).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // 同じ冪等キーで、異なる内容のサインアップ
+    let other = SignupData {
+        user_name: "bar".to_owned(),
+        email_address: "bar@example.com".to_owned(),
+        ..signup_data()
+    };
+    let response = post_with_idempotency_key(&app, "/accounts/signup", "signup-key", &other).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "IDEMPOTENCY_KEY_REUSED");
+    // 形式が不正な冪等キー
+    let key = "x".repeat(256);
+    let response = post_with_idempotency_key(&app, "/accounts/signup", &key, &other).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_IDEMPOTENCY_KEY");
+    // 異なる内容のユーザーは登録されていないことを確認
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM users WHERE email_address = $1"#,
+        other.email_address,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 0);
+}
@@ -1,5 +1,17 @@
 mod accounts;
+mod admin;
+mod api_keys;
+mod cors;
 mod health_check;
 mod helpers;
+mod idempotency;
+mod not_found;
 mod protected_resource;
+mod refresh_tokens;
+mod startup;
+mod tls;
+mod transactions;
+mod user_profiles;
 mod users;
+mod webhooks;
+mod websocket;
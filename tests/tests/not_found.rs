@@ -0,0 +1,24 @@
+use crate::helpers::spawn_web_app;
+
+/// 存在しないパスにアクセスしたときに、`404 Not Found`をJSONで返却することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn unknown_path_returns_json_not_found() {
+    let app = spawn_web_app(true).await;
+    let response = app
+        .api_client
+        .get(format!("{}/bogus/path", app.web_app_address))
+        .send()
+        .await
+        .expect("Webアプリにアクセスできませんでした。");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .unwrap(),
+        "application/json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "NOT_FOUND");
+}
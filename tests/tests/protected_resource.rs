@@ -1,4 +1,13 @@
-use crate::helpers::{spawn_web_app, LoginData};
+use configurations::session::TOKEN_FINGERPRINT_HEADER_NAME;
+use configurations::tokens::{refresh_token_hash, token_fingerprint};
+use configurations::TokenMode;
+
+use actix_web::cookie::time::Duration;
+use domains::models::users::{User, UserId};
+use infrastructures::repositories::users::PgUserRepository;
+use uuid::Uuid;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with, LoginData, TestWebApp};
 
 // ログインしたユーザが、保護されたリソースにアクセスできることを確認するテスト。
 #[tokio::test]
@@ -27,22 +36,222 @@ async fn cannot_access_protected_resource() {
     assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
 }
 
+/// ユーザーが存在するか正しく判定できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn user_repository_exists() {
+    let app = spawn_web_app(true).await;
+    let mut tx = app.pool.begin().await.unwrap();
+    // 登録されているユーザー
+    let user = &app.test_users.active_user;
+    assert!(PgUserRepository.exists(user.id(), &mut tx).await.unwrap());
+    // 登録されていないユーザー
+    assert!(!PgUserRepository
+        .exists(UserId::new(Uuid::new_v4()), &mut tx)
+        .await
+        .unwrap());
+    // 論理削除したユーザー
+    PgUserRepository
+        .soft_delete(user.id(), &mut tx)
+        .await
+        .unwrap();
+    assert!(!PgUserRepository.exists(user.id(), &mut tx).await.unwrap());
+    tx.rollback().await.unwrap();
+}
+
+/// ログインした後にユーザーが存在しなくなった場合、保護されたリソースにアクセスできず、
+/// トークンがリフレッシュされないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_access_protected_resource_after_user_removed() {
+    // アクセストークンの有効期限が切れた後に保護されたリソースにアクセスするように設定
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = true;
+    })
+    .await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (_, refresh_token) = app.get_token_values();
+    // ユーザーを論理削除
+    sqlx::query!(
+        "UPDATE users SET deleted_at = current_timestamp WHERE id = $1",
+        user.id().value()
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    // アクセストークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    // 保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    // トークンがリフレッシュされていないことを確認
+    assert_eq!(app.get_token_values().1, refresh_token);
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM refresh_tokens WHERE user_id = $1 AND token_hash = $2"#,
+        user.id().value(),
+        refresh_token_hash(&refresh_token.unwrap(), &app.settings.tokens.secret_key).unwrap(),
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 1);
+}
+
+/// セッションの失効を確認できない場合に、失効したセッションで保護されたリソースにアクセスすると
+/// `503 Service Unavailable`で応答され、確認できるようになった後は`401 Unauthorized`で応答されることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn revoked_session_is_rejected_while_session_check_is_unavailable() {
+    let app = spawn_web_app(true).await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // セッションを失効
+    sqlx::query!(
+        "DELETE FROM refresh_tokens WHERE user_id = $1",
+        user.id().value()
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    // リフレッシュトークンを問い合わせできないように、テーブルの名前を変更
+    sqlx::query("ALTER TABLE refresh_tokens RENAME TO refresh_tokens_unavailable")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    // リフレッシュトークンを問い合わせできるようになった後は、セッションが失効していることを確認
+    sqlx::query("ALTER TABLE refresh_tokens_unavailable RENAME TO refresh_tokens")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// ユーザーを取得できない場合でも、トークンがリフレッシュされて、ユーザーを取得できるようになった後に
+/// 保護されたリソースにアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn tokens_are_refreshed_while_user_lookup_is_unavailable() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = true;
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+    // ユーザーを取得できないように、列の名前を変更
+    sqlx::query("ALTER TABLE users RENAME COLUMN user_name TO user_name_unavailable")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    // アクセストークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    // ユーザーを必要とする保護されたリソースにはアクセスできないが、トークンはリフレッシュされることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(
+        response.status(),
+        reqwest::StatusCode::INTERNAL_SERVER_ERROR
+    );
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert_ne!(access_token, access_token_2nd);
+    assert_ne!(refresh_token, refresh_token_2nd);
+    // ユーザーを取得できるようになった後は、保護されたリソースにアクセスできることを確認
+    sqlx::query("ALTER TABLE users RENAME COLUMN user_name_unavailable TO user_name")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ログインしたユーザーを無効にする。
+async fn deactivate_user(app: &TestWebApp, user: &User) {
+    let user = User::new(
+        user.id(),
+        user.tenant_id(),
+        user.user_name().clone(),
+        user.email_address().clone(),
+        user.hashed_password().clone(),
+        false,
+        *user.last_logged_in(),
+        user.profile_visibility(),
+        user.totp_secret().cloned(),
+        user.must_change_password(),
+        user.role(),
+        *user.created_at(),
+        *user.updated_at(),
+    );
+    let mut tx = app.pool.begin().await.unwrap();
+    let updated_user = PgUserRepository.update(&user, &mut tx).await.unwrap();
+    tx.commit().await.unwrap();
+    assert!(!updated_user.is_active());
+}
+
+/// ログインした後にユーザーが無効になった場合、保護されたリソースにアクセスすると`403 Forbidden`で
+/// 応答され、セッションが破棄されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_access_protected_resource_after_user_deactivated() {
+    let app = spawn_web_app(true).await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // ログインしたユーザーが保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // ユーザーを無効化
+    deactivate_user(&app, &app.test_users.active_user).await;
+    // 保護されたリソースにアクセスできないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    // セッションが破棄されているため、`401 Unauthorized`で応答されることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// ユーザーが無効になった場合に応答するステータスコードを、システム設定で変更できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn inactive_user_status_can_be_configured() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.inactive_user_status = actix_web::http::StatusCode::UNAUTHORIZED;
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    deactivate_user(&app, &app.test_users.active_user).await;
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
 /// アクセストークンが失効していて、リフレッシュトークンが期限内の場合に、保護されたリソースにアクセスできることを確認するテスト
 ///
 /// ログイン済みのユーザーのアクセストークンの有効期限が切れていて、リフレッシュトークンが有効期限内の場合に、
 /// 保護されたリソースにアクセスできることを確認する。また、ブラウザにクッキーとして保存されたアクセストークン
 /// とリフレッシュトークンが、ログインしたときと2回目に保護されたリソースにアクセスしたときで、異なることを
 /// 確認する。
-// FIXME: 単体で本テストを実行するとパスするが、他の統合テストと一緒に実行するとパスしない。
-// 原因を特定して修正すること。
-// 修正するまで、統合テストは`./scripts/integration_tests.sh`スクリプトで実行すること。
 #[tokio::test]
 #[ignore]
 async fn can_access_protected_resource_at_within_expiration_of_refresh_token() {
-    // 環境変数を設定して、テスト用Webアプリを起動
-    dotenvy::dotenv().ok();
-    std::env::set_var("ACCESS_TOKEN_SECONDS", "1");
-    let app = spawn_web_app(false).await;
+    // アクセストークンの有効期限を1秒に設定して、テスト用Webアプリを起動
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = true;
+    })
+    .await;
     let user = &app.test_users.active_user;
     // ログイン
     let data = LoginData {
@@ -51,8 +260,13 @@ async fn can_access_protected_resource_at_within_expiration_of_refresh_token() {
     };
     let response = app.call_login_api(&data).await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let fingerprint = get_token_fingerprint(&response);
     // アクセストークンとリフレッシュトークンを取得
     let (access_token, refresh_token) = app.get_token_values();
+    assert_eq!(
+        fingerprint,
+        token_fingerprint(access_token.as_ref().unwrap())
+    );
     // 保護されたリソースにアクセス
     let response = app.call_protected_api().await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
@@ -65,6 +279,7 @@ async fn can_access_protected_resource_at_within_expiration_of_refresh_token() {
     // 再度、保護されたリソースにアクセス
     let response = app.call_protected_api().await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let fingerprint_2nd = get_token_fingerprint(&response);
     let text = response.text().await.unwrap();
     assert_eq!(text, user.id().value().to_string());
     // 再度、アクセストークンとリフレッシュトークンを取得
@@ -72,20 +287,35 @@ async fn can_access_protected_resource_at_within_expiration_of_refresh_token() {
     // アクセストークンとリフレッシュトークンが変更されていることを確認
     assert!(access_token != access_token_2nd);
     assert!(refresh_token != refresh_token_2nd);
+    // フィンガープリントが、リフレッシュしたアクセストークンのフィンガープリントに変更されていることを確認
+    assert_ne!(fingerprint, fingerprint_2nd);
+    assert_eq!(
+        fingerprint_2nd,
+        token_fingerprint(access_token_2nd.as_ref().unwrap())
+    );
+}
+
+/// レスポンスヘッダーからトークンのフィンガープリントを取得する。
+fn get_token_fingerprint(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(TOKEN_FINGERPRINT_HEADER_NAME)
+        .expect("レスポンスにトークンのフィンガープリントが含まれていません。")
+        .to_str()
+        .unwrap()
+        .to_owned()
 }
 
 // ログイン済みのユーザーが、リフレッシュトークンが失効したとき、保護されたリソースにアクセスできないことを確認するテスト
-// FIXME: 単体で本テストを実行するとパスするが、他の統合テストと一緒に実行するとパスしない。
-// 原因を特定して修正すること。
-// 修正するまで、統合テストは`./scripts/integration_tests.sh`スクリプトで実行すること。
 #[tokio::test]
 #[ignore]
 async fn cannot_access_protected_resource_at_expired_expiration_of_refresh_token() {
-    // 環境変数を設定して、テスト用Webアプリを起動
-    dotenvy::dotenv().ok();
-    std::env::set_var("ACCESS_TOKEN_SECONDS", "1");
-    std::env::set_var("REFRESH_TOKEN_SECONDS", "1");
-    let app = spawn_web_app(false).await;
+    // アクセストークンとリフレッシュトークンの有効期限を1秒に設定して、テスト用Webアプリを起動
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.refresh_token_duration = Duration::seconds(1);
+    })
+    .await;
     let user = &app.test_users.active_user;
     // ログイン
     let data = LoginData {
@@ -113,3 +343,292 @@ async fn cannot_access_protected_resource_at_expired_expiration_of_refresh_token
     // assert!(access_token_2nd.is_none());
     // assert!(refresh_token_2nd.is_none());
 }
+
+/// サイレントリフレッシュが有効な場合に、アクセストークンの有効期限が切れていても、保護されたリソースに
+/// アクセスできて、トークンがリフレッシュされることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn silent_refresh_enabled_rotates_tokens() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = true;
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+
+    // アクセストークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    // 保護されたリソースにアクセスできて、トークンがリフレッシュされていることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert_ne!(access_token, access_token_2nd);
+    assert_ne!(refresh_token, refresh_token_2nd);
+}
+
+/// 詳細な情報を要求した場合に、認証したユーザーとトークンの有効期限を応答して、アクセストークンの有効期限が
+/// 切れたリクエストでは、サイレントリフレッシュしたことを応答することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn verbose_protected_resource_reports_silent_refresh() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = true;
+    })
+    .await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // アクセストークンの有効期限内は、リフレッシュしていないことを応答
+    let response = app.call_verbose_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["userId"], user.id().value().to_string());
+    assert_eq!(body["tenantId"], user.tenant_id().to_string());
+    assert_eq!(body["role"], "user");
+    let access_expiration = body["accessExpiration"].as_u64().unwrap();
+    assert!(access_expiration < body["refreshExpiration"].as_u64().unwrap());
+    assert_eq!(body["refreshed"], false);
+
+    // アクセストークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    // サイレントリフレッシュしたことと、リフレッシュしたアクセストークンの有効期限を応答
+    let response = app.call_verbose_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["refreshed"], true);
+    assert!(access_expiration < body["accessExpiration"].as_u64().unwrap());
+}
+
+/// サイレントリフレッシュが無効な場合に、アクセストークンの有効期限が切れたら保護されたリソースに
+/// アクセスできず、リフレッシュAPIを呼び出した後にアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn silent_refresh_disabled_requires_explicit_refresh() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.silent_refresh_enabled = false;
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+
+    // アクセストークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    // 保護されたリソースにアクセスできず、トークンがリフレッシュされていないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        app.get_token_values(),
+        (access_token.clone(), refresh_token.clone())
+    );
+
+    // リフレッシュAPIを呼び出して、トークンがリフレッシュされることを確認
+    let response = app.call_refresh_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert_ne!(access_token, access_token_2nd);
+    assert_ne!(refresh_token, refresh_token_2nd);
+
+    // 保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// スライディング延長する期間を設定した場合に、アクセストークンの有効期限内でも、リフレッシュトークンの
+/// 有効期限が近づいたらトークンがリフレッシュされることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn sliding_renewal_rotates_tokens_within_access_token_expiration() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.sliding_renewal_duration = settings.tokens.refresh_token_duration;
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let (access_token, refresh_token) = app.get_token_values();
+
+    // 異なるトークンが生成されるように、有効期限の秒数が変わるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    // 保護されたリソースにアクセスできて、トークンがリフレッシュされていることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(get_token_fingerprint(&response).len(), 8);
+    let (access_token_2nd, refresh_token_2nd) = app.get_token_values();
+    assert_ne!(access_token, access_token_2nd);
+    assert_ne!(refresh_token, refresh_token_2nd);
+}
+
+/// レスポンスがセッションIDのクッキーを設定しているか確認する。
+///
+/// セッションデータがRedisに書き込まれた場合のみ、セッションIDのクッキーが設定される。
+fn sets_session_cookie(app: &TestWebApp, response: &reqwest::Response) -> bool {
+    let prefix = format!("{}=", app.settings.session_cookie.session_id_cookie_name);
+    response
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .any(|value| value.to_str().unwrap().starts_with(&prefix))
+}
+
+/// 頻繁にアクセスしても、セッションの更新が一定間隔でのみ行われることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn session_touch_is_throttled() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.session_store.touch_interval = Duration::seconds(2);
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ログイン直後に頻繁にアクセスしても、セッションが更新されないことを確認
+    for _ in 0..3 {
+        let response = app.call_protected_api().await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(!sets_session_cookie(&app, &response));
+    }
+
+    // 間隔が経過するまで待機
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    // 間隔が経過した後の最初のアクセスでのみ、セッションが更新されることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(sets_session_cookie(&app, &response));
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(!sets_session_cookie(&app, &response));
+}
+
+/// 不透明トークンを発行するように設定した場合に、ログインでJWTではないトークンが発行され、
+/// 保護されたリソースにアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_access_protected_resource_with_opaque_tokens() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.token_mode = TokenMode::Opaque;
+    })
+    .await;
+    let user = &app.test_users.active_user;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // JWTではないトークンが発行されていることを確認
+    let (access_token, refresh_token) = app.get_token_values();
+    for token in [access_token.unwrap(), refresh_token.unwrap()] {
+        assert!(!token.contains('.'), "{}", token);
+        assert!(token.chars().all(|ch| ch.is_ascii_hexdigit()));
+    }
+    // セッションデータと照合して、保護されたリソースにアクセスできることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let text = response.text().await.unwrap();
+    assert_eq!(text, user.id().value().to_string());
+}
+
+/// 不透明トークンを発行するように設定した場合に、トークンの有効期限が切れた後は、保護されたリソースに
+/// アクセスできないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_access_protected_resource_with_expired_opaque_tokens() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.token_mode = TokenMode::Opaque;
+        settings.tokens.access_token_duration = Duration::seconds(1);
+        settings.tokens.refresh_token_duration = Duration::seconds(1);
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // トークンの有効期限が切れるまで待機
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// トークンをリフレッシュできる回数の上限に達した場合は、セッションが破棄されて`401 Unauthorized`で応答され、
+/// 再ログインするとリフレッシュ回数がリセットされることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn refresh_chain_is_bounded_until_next_login() {
+    // リフレッシュトークンの有効期間よりスライディング延長する秒数を長くして、アクセスするたびにリフレッシュ
+    // 同じ秒にリフレッシュしてもトークンが変わるように、不透明トークンを発行
+    let app = spawn_web_app_with(true, |settings| {
+        settings.tokens.max_refresh_chain = 2;
+        settings.tokens.token_mode = TokenMode::Opaque;
+        settings.tokens.sliding_renewal_duration =
+            settings.tokens.refresh_token_duration + Duration::seconds(60);
+    })
+    .await;
+    let data = app.active_user_login_data();
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 上限の回数まではトークンがリフレッシュされることを確認
+    for _ in 0..2 {
+        let (_, refresh_token) = app.get_token_values();
+        let response = app.call_protected_api().await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_ne!(app.get_token_values().1, refresh_token);
+    }
+    // 上限を超えてリフレッシュしようとすると、再ログインを要求されることを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "LOGIN_REQUIRED");
+    // セッションが破棄されたため、続けてアクセスしても認証されないことを確認
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 再ログインすると、リフレッシュ回数がリセットされることを確認
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// ユーザーキャッシュが有効な場合に、キャッシュしたユーザーが他のプロセスなどで削除されたときは、キャッシュの
+/// 有効期間内でも認証されないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cached_user_is_rejected_after_deletion() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.user_cache.enabled = true;
+        settings.user_cache.ttl = Duration::seconds(3600);
+    })
+    .await;
+    let user_id = app.test_users.active_user.id().value();
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 保護されたリソースにアクセスして、ユーザーをキャッシュ
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // ユーザーキャッシュを破棄せずに、データベースからユーザーを削除
+    sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    // セッションの確認より前に、ユーザーが存在しないと判断して拒否
+    let response = app.call_protected_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNAUTHORIZED");
+}
@@ -0,0 +1,104 @@
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use infrastructures::repositories::refresh_tokens::PgRefreshTokenRepository;
+use web_server::refresh_token_cleanup::delete_expired_refresh_tokens;
+
+use crate::helpers::{spawn_web_app, TestWebApp};
+
+/// アクティブユーザーのリフレッシュトークンを、指定した有効期限でデータベースに登録する。
+async fn seed_refresh_token(app: &TestWebApp, expired_at: OffsetDateTime) -> Uuid {
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (
+            session_id, user_id, token_hash, expired_at, created_at, updated_at
+        ) VALUES (
+            $1, $2, $3, $4, current_timestamp, current_timestamp
+        )
+        "#,
+        session_id,
+        app.test_users.active_user.id().value(),
+        session_id.to_string(),
+        expired_at,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    session_id
+}
+
+/// データベースに記録されているリフレッシュトークンのセッションIDを返却する。
+async fn stored_session_ids(app: &TestWebApp) -> Vec<Uuid> {
+    let mut session_ids: Vec<Uuid> = sqlx::query!("SELECT session_id FROM refresh_tokens")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.session_id)
+        .collect();
+    session_ids.sort();
+
+    session_ids
+}
+
+/// 有効期限が切れたリフレッシュトークンのみを、バッチの最大数まで削除することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn delete_expired_removes_only_expired_refresh_tokens() {
+    let app = spawn_web_app(true).await;
+    let now = OffsetDateTime::now_utc();
+    for _ in 0..3 {
+        seed_refresh_token(&app, now - Duration::hours(1)).await;
+    }
+    let mut valid_session_ids = vec![
+        seed_refresh_token(&app, now + Duration::hours(1)).await,
+        seed_refresh_token(&app, now + Duration::days(1)).await,
+    ];
+    valid_session_ids.sort();
+
+    // バッチの最大数まで削除
+    let mut tx = app.pool.begin().await.unwrap();
+    let deleted = PgRefreshTokenRepository
+        .delete_expired(2, &mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(deleted, 2);
+    assert_eq!(stored_session_ids(&app).await.len(), 3);
+    // 残りの有効期限が切れたリフレッシュトークンを削除
+    let mut tx = app.pool.begin().await.unwrap();
+    let deleted = PgRefreshTokenRepository
+        .delete_expired(2, &mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(stored_session_ids(&app).await, valid_session_ids);
+    // 有効期限が切れたリフレッシュトークンがない場合は、何も削除しない
+    let mut tx = app.pool.begin().await.unwrap();
+    let deleted = PgRefreshTokenRepository
+        .delete_expired(2, &mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(deleted, 0);
+    assert_eq!(stored_session_ids(&app).await, valid_session_ids);
+}
+
+/// バックグラウンドタスクが、有効期限が切れたリフレッシュトークンをすべて削除することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn delete_expired_refresh_tokens_removes_all_expired_refresh_tokens() {
+    let app = spawn_web_app(true).await;
+    let now = OffsetDateTime::now_utc();
+    for _ in 0..5 {
+        seed_refresh_token(&app, now - Duration::minutes(1)).await;
+    }
+    let valid_session_id = seed_refresh_token(&app, now + Duration::hours(1)).await;
+
+    let deleted = delete_expired_refresh_tokens(&app.pool).await.unwrap();
+    assert_eq!(deleted, 5);
+    assert_eq!(stored_session_ids(&app).await, vec![valid_session_id]);
+}
@@ -0,0 +1,188 @@
+use std::net::TcpListener;
+
+use actix_web::cookie::{time::Duration, SameSite};
+
+use sqlx::{Connection, Executor, PgConnection};
+use uuid::Uuid;
+
+use configurations::{AppEnvironment, DatabaseSettings};
+use web_server::startup::{pending_migrations, WebApp};
+
+use crate::helpers::spawn_web_app;
+
+/// 接続を受け付けていないポート番号を返却する。
+fn unused_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// データベースに接続できない場合に、Webアプリの構築に失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_when_database_is_down() {
+    let app = spawn_web_app(true).await;
+    let mut settings = app.settings.clone();
+    settings.db.host = "127.0.0.1".to_owned();
+    settings.db.port = unused_port();
+    let error = WebApp::build(settings).await.err().unwrap();
+    assert!(format!("{}", error).contains("データベース"), "{}", error);
+}
+
+/// マイグレーションを適用していない空のデータベースを作成して、接続する。
+async fn connect_to_empty_database() -> (DatabaseSettings, PgConnection) {
+    dotenvy::dotenv().ok();
+    let settings = DatabaseSettings {
+        database_name: Uuid::new_v4().to_string(),
+        ..DatabaseSettings::default()
+    };
+    let mut connection = PgConnection::connect_with(&settings.without_db())
+        .await
+        .unwrap();
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, settings.database_name).as_str())
+        .await
+        .unwrap();
+    let connection = PgConnection::connect_with(&settings.with_db())
+        .await
+        .unwrap();
+
+    (settings, connection)
+}
+
+/// マイグレーションを確認するのみの場合に、マイグレーションを適用していないデータベースでは適用されていない
+/// マイグレーションを報告して、マイグレーションを適用したデータベースでは報告しないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn pending_migrations_are_reported_until_migrated() {
+    let (_, mut connection) = connect_to_empty_database().await;
+    let pending = pending_migrations(&mut connection).await.unwrap();
+    assert!(!pending.is_empty());
+    // マイグレーションを記録するテーブルを作成していないことを確認
+    assert_eq!(pending_migrations(&mut connection).await.unwrap(), pending);
+
+    sqlx::migrate!("../migrations")
+        .run(&mut connection)
+        .await
+        .unwrap();
+    assert!(pending_migrations(&mut connection)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// マイグレーションを適用したデータベースでは、マイグレーションを確認するのみの設定で、Webアプリを構築できる
+/// ことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_build_with_migrated_database_without_auto_migrate() {
+    let app = spawn_web_app(true).await;
+    let mut settings = app.settings.clone();
+    settings.db.auto_migrate = false;
+    assert!(WebApp::build(settings).await.is_ok());
+}
+
+/// マイグレーションを適用していないデータベースでは、マイグレーションを確認するのみの設定の場合は
+/// Webアプリの構築に失敗して、自動的に適用する設定の場合はWebアプリを構築できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_with_pending_migrations_without_auto_migrate() {
+    let app = spawn_web_app(true).await;
+    let (db, _) = connect_to_empty_database().await;
+    let mut settings = app.settings.clone();
+    settings.db = db;
+    let error = WebApp::build(settings.clone()).await.err().unwrap();
+    assert!(format!("{}", error).contains("AUTO_MIGRATE"), "{}", error);
+
+    settings.db.auto_migrate = true;
+    assert!(WebApp::build(settings).await.is_ok());
+}
+
+/// Redisに接続できない場合に、Webアプリの構築に失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_when_session_store_is_down() {
+    let app = spawn_web_app(true).await;
+    let mut settings = app.settings.clone();
+    settings.session_store.uri = format!("redis://127.0.0.1:{}", unused_port()).into();
+    let error = WebApp::build(settings).await.err().unwrap();
+    assert!(format!("{}", error).contains("Redis"), "{}", error);
+}
+
+/// Redisが応答しない場合に、タイムアウトしてWebアプリの構築に失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_when_session_store_does_not_respond() {
+    // 接続を受け付けるが、応答しないRedisを用意
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let _accept = tokio::spawn(async move {
+        let mut connections = vec![];
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+    let app = spawn_web_app(true).await;
+    let mut settings = app.settings.clone();
+    settings.session_store.uri = format!("redis://127.0.0.1:{}", port).into();
+    settings.session_store.connect_timeout = Duration::seconds(1);
+    // Webアプリの構築が停止せずに、エラーを返却することを確認
+    let result = tokio::time::timeout(std::time::Duration::from_secs(10), WebApp::build(settings))
+        .await
+        .expect("Webアプリの構築が停止しました。");
+    let error = result.err().unwrap();
+    assert!(format!("{}", error).contains("タイムアウト"), "{}", error);
+}
+
+/// 本番環境で、クッキー設定が安全ではない場合に、Webアプリの構築に失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_in_production_with_insecure_cookies() {
+    let app = spawn_web_app(true).await;
+    for (secure, same_site) in [(false, SameSite::Lax), (true, SameSite::None)] {
+        let mut settings = app.settings.clone();
+        settings.web_app.environment = AppEnvironment::Production;
+        settings.session_cookie.secure = secure;
+        settings.session_cookie.same_site = same_site;
+        let error = WebApp::build(settings).await.err().unwrap();
+        assert!(format!("{}", error).contains("本番環境"), "{}", error);
+    }
+}
+
+/// 開発環境では安全ではないクッキー設定で、本番環境では安全なクッキー設定で、Webアプリを構築できることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_build_with_cookie_settings_allowed_in_environment() {
+    let app = spawn_web_app(true).await;
+    for (environment, secure, same_site) in [
+        (AppEnvironment::Development, false, SameSite::Lax),
+        (AppEnvironment::Development, true, SameSite::None),
+        (AppEnvironment::Production, true, SameSite::Strict),
+    ] {
+        let mut settings = app.settings.clone();
+        settings.web_app.environment = environment;
+        settings.session_cookie.secure = secure;
+        settings.session_cookie.same_site = same_site;
+        let result = WebApp::build(settings).await;
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+}
+
+/// 実行環境にかかわらず、`Secure`属性を付与しない`SameSite=None`のクッキー設定では、Webアプリの構築に
+/// 失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_with_insecure_same_site_none_cookies() {
+    let app = spawn_web_app(true).await;
+    for environment in [AppEnvironment::Development, AppEnvironment::Production] {
+        let mut settings = app.settings.clone();
+        settings.web_app.environment = environment;
+        settings.session_cookie.secure = false;
+        settings.session_cookie.same_site = SameSite::None;
+        let error = WebApp::build(settings).await.err().unwrap();
+        assert!(format!("{}", error).contains("SameSite"), "{}", error);
+    }
+}
@@ -0,0 +1,78 @@
+use configurations::TlsSettings;
+use uuid::Uuid;
+use web_server::startup::WebApp;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with};
+
+/// 自己署名証明書と秘密鍵をファイルに出力して、TLS設定を返却する。
+fn write_self_signed_certificate() -> TlsSettings {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+    let dir = std::env::temp_dir();
+    let id = Uuid::new_v4();
+    let cert_path = dir.join(format!("{}-cert.pem", id));
+    let key_path = dir.join(format!("{}-key.pem", id));
+    std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+    TlsSettings {
+        cert_path: cert_path.to_str().unwrap().to_owned(),
+        key_path: key_path.to_str().unwrap().to_owned(),
+    }
+}
+
+/// TLSを有効にしたWebアプリに、HTTPSでアクセスできることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_access_over_https() {
+    let tls = write_self_signed_certificate();
+    let app = spawn_web_app_with(true, |settings| {
+        settings.web_app.tls = Some(tls);
+    })
+    .await;
+    // 自己署名証明書を受け入れるクライアントでアクセス
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("https://localhost:{}/health_check", app.port))
+        .send()
+        .await
+        .expect("HTTPSでヘルスチェックAPIにアクセスできませんでした。");
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await.unwrap(), "Are you ready?");
+}
+
+/// TLS証明書または秘密鍵を読み込めない場合に、Webアプリの構築に失敗することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_build_with_invalid_tls_files() {
+    let app = spawn_web_app(true).await;
+    let valid = write_self_signed_certificate();
+
+    // 証明書ファイルが存在しない
+    let mut settings = app.settings.clone();
+    settings.web_app.tls = Some(TlsSettings {
+        cert_path: "/path/to/missing/cert.pem".to_owned(),
+        key_path: valid.key_path.clone(),
+    });
+    let error = WebApp::build(settings).await.err().unwrap();
+    assert!(
+        format!("{}", error).contains("TLS証明書ファイル"),
+        "{}",
+        error
+    );
+
+    // 秘密鍵ファイルに秘密鍵が含まれていない
+    let mut settings = app.settings.clone();
+    settings.web_app.tls = Some(TlsSettings {
+        cert_path: valid.cert_path.clone(),
+        key_path: valid.cert_path.clone(),
+    });
+    let error = WebApp::build(settings).await.err().unwrap();
+    assert!(
+        format!("{}", error).contains("TLS秘密鍵ファイル"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,66 @@
+use anyhow::anyhow;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use usecases::transactions::with_transaction;
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::spawn_web_app_with_store;
+
+/// ユーザーがアクティブか取得する。
+async fn is_active(user_id: Uuid, pool: &PgPool) -> bool {
+    sqlx::query!("SELECT is_active FROM users WHERE id = $1", user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .is_active
+}
+
+/// 処理がエラーを返却した場合は、トランザクションをロールバックすることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn with_transaction_rolls_back_when_closure_returns_error() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let user_id = app.test_users.active_user.id().value();
+
+    let result: anyhow::Result<()> = with_transaction(
+        &app.pool,
+        |e| e,
+        |tx| {
+            Box::pin(async move {
+                sqlx::query!("UPDATE users SET is_active = FALSE WHERE id = $1", user_id)
+                    .execute(&mut **tx)
+                    .await?;
+                Err(anyhow!("error"))
+            })
+        },
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(is_active(user_id, &app.pool).await);
+}
+
+/// 処理が成功した場合は、トランザクションをコミットすることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn with_transaction_commits_when_closure_succeeds() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    let user_id = app.test_users.active_user.id().value();
+
+    let result: anyhow::Result<u64> = with_transaction(
+        &app.pool,
+        |e| e,
+        |tx| {
+            Box::pin(async move {
+                let result =
+                    sqlx::query!("UPDATE users SET is_active = FALSE WHERE id = $1", user_id)
+                        .execute(&mut **tx)
+                        .await?;
+                Ok(result.rows_affected())
+            })
+        },
+    )
+    .await;
+    assert_eq!(result.unwrap(), 1);
+    assert!(!is_active(user_id, &app.pool).await);
+}
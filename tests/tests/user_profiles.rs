@@ -0,0 +1,197 @@
+use web_server::session_stores::InMemorySessionStore;
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with_store, LoginData, SignupData, TestWebApp};
+
+/// 閲覧者として登録するユーザー
+const VIEWER_USER_NAME: &str = "viewer";
+const VIEWER_EMAIL_ADDRESS: &str = "viewer@example.com";
+/* cSpell: disable */
+const VIEWER_PASSWORD: &str = "Vq7#pL2@xW9!";
+/* cSpell: enable */
+
+/// ユーザープロフィール取得APIを呼び出して、応答したJSONを返却する。
+async fn get_user_profile(app: &TestWebApp) -> serde_json::Value {
+    let user_id = app.test_users.active_user.id().value().to_string();
+    let response = app.call_get_user_profile_api(&user_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let profile: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(profile["id"], user_id);
+    assert_eq!(
+        profile["userName"],
+        app.test_users.active_user.user_name().value()
+    );
+
+    profile
+}
+
+/// アクティブユーザーでログインして、プロフィールの公開設定を変更する。
+async fn set_active_user_profile_visibility(
+    app: &TestWebApp,
+    email_address: &str,
+    last_logged_in: &str,
+) {
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let data = serde_json::json!({
+        "emailAddress": email_address,
+        "lastLoggedIn": last_logged_in,
+    });
+    let response = app.call_set_profile_visibility_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// 閲覧者の認証状態と公開設定に応じて、プロフィール情報が返却されるか、隠されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn profile_fields_are_filtered_by_visibility() {
+    let app = spawn_web_app(true).await;
+    set_active_user_profile_visibility(&app, "authenticated_only", "private").await;
+    // ユーザー自身は、公開設定にかかわらずすべてのプロフィール情報を閲覧できる
+    let profile = get_user_profile(&app).await;
+    assert_eq!(
+        profile["emailAddress"],
+        app.test_users.active_user.email_address().value()
+    );
+    assert!(profile.get("lastLoggedIn").is_some());
+    // 認証されていない閲覧者は、認証された閲覧者にのみ公開する情報を閲覧できない
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let profile = get_user_profile(&app).await;
+    assert!(profile.get("emailAddress").is_none());
+    assert!(profile.get("lastLoggedIn").is_none());
+    // 認証された他のユーザーは、認証された閲覧者にのみ公開する情報を閲覧できる
+    let data = SignupData {
+        user_name: VIEWER_USER_NAME.to_owned(),
+        email_address: VIEWER_EMAIL_ADDRESS.to_owned(),
+        password: VIEWER_PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let data = LoginData {
+        email_address: VIEWER_EMAIL_ADDRESS.to_owned(),
+        password: VIEWER_PASSWORD.to_owned(),
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let profile = get_user_profile(&app).await;
+    assert_eq!(
+        profile["emailAddress"],
+        app.test_users.active_user.email_address().value()
+    );
+    assert!(profile.get("lastLoggedIn").is_none());
+}
+
+/// すべての閲覧者に公開する設定にした場合、認証されていない閲覧者がプロフィール情報を閲覧できることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn public_profile_fields_are_visible_to_anonymous_viewer() {
+    let app = spawn_web_app(true).await;
+    // 既定の公開設定では、認証されていない閲覧者はプロフィール情報を閲覧できない
+    let profile = get_user_profile(&app).await;
+    assert!(profile.get("emailAddress").is_none());
+    assert!(profile.get("lastLoggedIn").is_none());
+    // すべての閲覧者に公開
+    set_active_user_profile_visibility(&app, "public", "public").await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let profile = get_user_profile(&app).await;
+    assert_eq!(
+        profile["emailAddress"],
+        app.test_users.active_user.email_address().value()
+    );
+    assert!(profile.get("lastLoggedIn").is_some());
+}
+
+/// 不正な公開範囲を指定した場合、`400 Bad Request`で応答されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_set_invalid_profile_visibility() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let data = serde_json::json!({
+        "emailAddress": "friends",
+        "lastLoggedIn": "public",
+    });
+    let response = app.call_set_profile_visibility_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// 存在しないユーザーや無効なユーザーのプロフィールを取得できないことを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_get_profile_of_unknown_or_non_active_user() {
+    let app = spawn_web_app(true).await;
+    let user_ids = [
+        uuid::Uuid::new_v4().to_string(),
+        app.test_users.non_active_user.id().value().to_string(),
+        "not-a-uuid".to_owned(),
+    ];
+    for user_id in user_ids {
+        let response = app.call_get_user_profile_api(&user_id).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND,
+            "{}",
+            user_id
+        );
+    }
+}
+
+/// 他のテナントに所属するユーザーのプロフィールを取得しようとした場合、`403 Forbidden`で応答されることを
+/// 確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_get_profile_of_user_in_other_tenant() {
+    let app = spawn_web_app_with_store(true, InMemorySessionStore::default(), |_| {}).await;
+    set_active_user_profile_visibility(&app, "public", "public").await;
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 閲覧者を登録して、他のテナントに所属させる
+    let data = SignupData {
+        user_name: VIEWER_USER_NAME.to_owned(),
+        email_address: VIEWER_EMAIL_ADDRESS.to_owned(),
+        password: VIEWER_PASSWORD.to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let other_tenant_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        "UPDATE users SET tenant_id = $1 WHERE email_address = $2",
+        other_tenant_id,
+        VIEWER_EMAIL_ADDRESS,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    let data = LoginData {
+        email_address: VIEWER_EMAIL_ADDRESS.to_owned(),
+        password: VIEWER_PASSWORD.to_owned(),
+    };
+    let response = app.call_login_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    // 公開設定にかかわらず、他のテナントのユーザーのプロフィールは取得できない
+    let user_id = app.test_users.active_user.id().value().to_string();
+    let response = app.call_get_user_profile_api(&user_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    // 閲覧者と同じテナントに所属させると、プロフィールを取得できる
+    sqlx::query!(
+        "UPDATE users SET tenant_id = $1 WHERE id = $2",
+        other_tenant_id,
+        app.test_users.active_user.id().value(),
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    let profile = get_user_profile(&app).await;
+    assert_eq!(
+        profile["emailAddress"],
+        app.test_users.active_user.email_address().value()
+    );
+    // 認証されていない閲覧者は、既定のテナント以外のユーザーのプロフィールを取得できない
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_get_user_profile_api(&user_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
@@ -1,27 +1,37 @@
 use actix_web::cookie::time::OffsetDateTime;
 use domains::models::{
-    users::{HashedPassword, RawPassword, User, UserId, UserName},
+    users::{
+        HashedPassword, ProfileVisibility, RawPassword, User, UserId, UserName, UserRole,
+        DEFAULT_TENANT_ID,
+    },
     EmailAddress,
 };
 use secrecy::ExposeSecret;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 fn generate_user(
     user_name: &str,
     email_address: &str,
     password: &str,
     is_active: bool,
+    role: UserRole,
     timestamp: OffsetDateTime,
 ) -> User {
     let raw_password = RawPassword::new(password).unwrap();
     let hashed_password = HashedPassword::new(&raw_password).unwrap();
     User::new(
         UserId::default(),
+        DEFAULT_TENANT_ID,
         UserName::new(user_name).unwrap(),
         EmailAddress::new(email_address).unwrap(),
         hashed_password,
         is_active,
         None,
+        ProfileVisibility::default(),
+        None,
+        false,
+        role,
         Some(timestamp),
         Some(timestamp),
     )
@@ -31,7 +41,10 @@ pub struct TestUsers {
     pub active_user: User,
     pub active_user_password: String,
     pub non_active_user: User,
+    #[allow(dead_code)]
     pub non_active_user_password: String,
+    pub admin_user: User,
+    pub admin_user_password: String,
 }
 
 impl TestUsers {
@@ -39,6 +52,7 @@ impl TestUsers {
         /* cSpell: disable */
         let active_user_password = "&MpHFQZKVr7i".to_owned();
         let non_active_user_password = "3nHUW@[bCs?b".to_owned();
+        let admin_user_password = "k7#Vq2!mZp9x".to_owned();
         /* cSpell: enable */
 
         let timestamp = OffsetDateTime::now_utc();
@@ -48,6 +62,7 @@ impl TestUsers {
                 "active-user@example.com",
                 &active_user_password,
                 true,
+                UserRole::User,
                 timestamp,
             ),
             active_user_password,
@@ -56,37 +71,65 @@ impl TestUsers {
                 "non-active-user@example.com",
                 &non_active_user_password,
                 false,
+                UserRole::User,
                 timestamp,
             ),
             non_active_user_password,
+            admin_user: generate_user(
+                "admin-user",
+                "admin-user@example.com",
+                &admin_user_password,
+                true,
+                UserRole::Admin,
+                timestamp,
+            ),
+            admin_user_password,
         }
     }
 
     /// テストユーザーをデータベースに登録する。
     pub async fn store(&self, pool: &PgPool) {
-        let users: Vec<&User> = vec![&self.active_user, &self.non_active_user];
+        let users: Vec<&User> = vec![&self.active_user, &self.non_active_user, &self.admin_user];
         for user in users.iter() {
             sqlx::query!(
                 r#"
                 INSERT INTO users (
-                    id, user_name, email_address, hashed_password,
-                    is_active, created_at, updated_at
+                    id, tenant_id, user_name, email_address, hashed_password,
+                    is_active, role, created_at, updated_at
                 ) VALUES (
-                    $1, $2, $3, $4,
-                    $5, $6, $7
+                    $1, $2, $3, $4, $5,
+                    $6, $7, $8, $9
                 )
                 "#,
                 user.id().value(),
+                user.tenant_id(),
                 user.user_name().value(),
                 user.email_address().value(),
                 user.hashed_password().value().expose_secret(),
                 user.is_active(),
+                user.role().as_str(),
                 user.created_at().unwrap(),
                 user.updated_at().unwrap(),
             )
             .execute(pool)
             .await
             .expect("テスト用のユーザーをデータベースに登録できませんでした。");
+            sqlx::query!(
+                r#"
+                INSERT INTO user_email_addresses (
+                    id, user_id, email_address, is_primary, is_verified, created_at
+                ) VALUES (
+                    $1, $2, $3, true, true, $4
+                )
+                "#,
+                Uuid::new_v4(),
+                user.id().value(),
+                user.email_address().value(),
+                user.created_at().unwrap(),
+            )
+            .execute(pool)
+            .await
+            .expect("テスト用のユーザーのEメールアドレスをデータベースに登録できませんでした。");
         }
     }
 }
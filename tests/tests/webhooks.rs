@@ -0,0 +1,117 @@
+use actix_web::cookie::time::Duration;
+use hmac::{Hmac, Mac};
+use secrecy::Secret;
+use sha2::Sha256;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+use usecases::webhooks::WEBHOOK_SIGNATURE_HEADER;
+
+use crate::helpers::{spawn_web_app_with, SignupData, TestWebApp};
+
+/// Webhookのペイロードに署名する秘密鍵
+const WEBHOOK_SECRET: &str = "webhook-secret";
+
+/// Webhookを受信するモックサーバーを送信先に設定して、Webアプリを起動する。
+async fn spawn_web_app_with_webhook(receiver: &MockServer) -> TestWebApp {
+    let url = format!("{}/hooks", receiver.uri());
+    spawn_web_app_with(true, move |settings| {
+        settings.webhook.url = Some(url);
+        settings.webhook.secret = Some(Secret::new(WEBHOOK_SECRET.to_owned()));
+        settings.webhook.max_attempts = 3;
+        settings.webhook.retry_interval = Duration::seconds(0);
+    })
+    .await
+}
+
+/// モックサーバーが指定した数のリクエストを受信するまで待機する。
+async fn wait_for_requests(receiver: &MockServer, count: usize) -> Vec<Request> {
+    for _ in 0..50 {
+        let requests = receiver.received_requests().await.unwrap();
+        if count <= requests.len() {
+            return requests;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Webhookを{}回受信しませんでした。", count);
+}
+
+/// サインアップする。
+async fn signup(app: &TestWebApp) -> serde_json::Value {
+    let data = SignupData {
+        user_name: "webhook".to_owned(),
+        email_address: "webhook@example.com".to_owned(),
+        // cspell:disable-next-line
+        password: "tOC8pHh:K/-G".to_owned(),
+    };
+    let response = app.call_signup_api(&data).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    response.json().await.unwrap()
+}
+
+/// サインアップすると、署名したイベントがWebhookで送信されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn signup_fires_signed_webhook() {
+    let receiver = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+    let app = spawn_web_app_with_webhook(&receiver).await;
+    let user = signup(&app).await;
+
+    let requests = wait_for_requests(&receiver, 1).await;
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    // 受信したリクエストボディの署名が、ヘッダーの署名と一致することを確認
+    let mut mac = Hmac::<Sha256>::new_from_slice(WEBHOOK_SECRET.as_bytes()).unwrap();
+    mac.update(&request.body);
+    let expected: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let signature = request
+        .headers
+        .iter()
+        .find(|(name, _)| name.as_str().eq_ignore_ascii_case(WEBHOOK_SIGNATURE_HEADER))
+        .map(|(_, values)| values.as_str())
+        .unwrap();
+    assert_eq!(signature, format!("sha256={}", expected));
+    let event: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(event["type"], "user.signed_up");
+    assert_eq!(event["userId"], user["id"]);
+}
+
+/// Webhookの送信に失敗した場合は、同じイベントを再送することを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn failed_webhook_is_retried() {
+    let receiver = MockServer::start().await;
+    // 最初の送信は失敗させる
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&receiver)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+    let app = spawn_web_app_with_webhook(&receiver).await;
+    let _ = signup(&app).await;
+
+    let requests = wait_for_requests(&receiver, 2).await;
+    let first: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let second: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(first["id"], second["id"]);
+    // 送信に成功した後は再送しないことを確認
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(receiver.received_requests().await.unwrap().len(), 2);
+}
@@ -0,0 +1,92 @@
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::{Error, Message};
+
+use crate::helpers::{spawn_web_app, spawn_web_app_with};
+
+/// クロスオリジンリクエストを許可するオリジン
+const ALLOWED_ORIGIN: &str = "https://spa.example.com";
+
+/// ログインしたユーザーが、認証WebSocketに接続でき、ユーザーIDを受信できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_connect_authenticated_websocket() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let user_id = app.test_users.active_user.id().value().to_string();
+
+    let mut socket = app.connect_websocket().await.unwrap();
+    // 接続時にユーザーIDを受信
+    let message = socket.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text(user_id.clone()));
+    // テキストメッセージを送信すると、ユーザーIDを受信
+    socket
+        .send(Message::Text("hello".to_owned()))
+        .await
+        .unwrap();
+    let message = socket.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text(user_id));
+    socket.close(None).await.unwrap();
+}
+
+/// ログインしていない場合は、認証WebSocketへのアップグレードが`401 Unauthorized`で拒否されることを確認する
+/// テスト
+#[tokio::test]
+#[ignore]
+async fn cannot_connect_websocket_without_login() {
+    let app = spawn_web_app(true).await;
+    assert!(matches!(
+        app.connect_websocket().await,
+        Err(Error::Http(response)) if response.status() == 401
+    ));
+
+    // ログアウトしたセッションでも接続できないことを確認
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = app.call_logout_api().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(matches!(
+        app.connect_websocket().await,
+        Err(Error::Http(response)) if response.status() == 401
+    ));
+}
+
+/// 同じオリジンからは接続でき、他のオリジンからのアップグレードは、ログインしていても`403 Forbidden`で拒否
+/// されることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn cannot_connect_websocket_from_disallowed_origin() {
+    let app = spawn_web_app(true).await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let mut socket = app
+        .connect_websocket_with_origin(Some(&app.web_app_address))
+        .await
+        .unwrap();
+    socket.close(None).await.unwrap();
+    for origin in ["https://attacker.example.com", "null"] {
+        assert!(matches!(
+            app.connect_websocket_with_origin(Some(origin)).await,
+            Err(Error::Http(response)) if response.status() == 403
+        ));
+    }
+}
+
+/// クロスオリジンリクエストを許可するオリジンからは、認証WebSocketに接続できることを確認するテスト
+#[tokio::test]
+#[ignore]
+async fn can_connect_websocket_from_cors_allowed_origin() {
+    let app = spawn_web_app_with(true, |settings| {
+        settings.web_app.cors_allowed_origins = vec![ALLOWED_ORIGIN.to_owned()];
+    })
+    .await;
+    let response = app.call_login_api(&app.active_user_login_data()).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let mut socket = app
+        .connect_websocket_with_origin(Some(ALLOWED_ORIGIN))
+        .await
+        .unwrap();
+    socket.close(None).await.unwrap();
+}
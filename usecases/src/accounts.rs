@@ -7,16 +7,33 @@ use uuid::Uuid;
 
 use configurations::{
     generate_session_data,
-    password::{verify_password, AuthError},
+    password::{self, spawn_password_hashing, verify_dummy_password, verify_password},
+    reissue_session_data,
     session::{SessionData, TypedSession},
-    telemetries::spawn_blocking_with_tracing,
-    Settings,
+    tokens::invite_code_hash,
+    RawPasswordSettings, Settings, TokensSettings, RAW_PASSWORD_SETTINGS,
 };
 use domains::models::{
-    users::{HashedPassword, RawPassword, User, UserId, UserName},
+    refresh_tokens::{RefreshToken, SessionId},
+    users::{
+        HashedPassword, ProfileVisibility, RawPassword, User, UserId, UserName, UserRole,
+        DEFAULT_TENANT_ID,
+    },
     EmailAddress,
 };
-use infrastructures::repositories::users::{PgUserRepository, UserRepositoryError};
+use infrastructures::repositories::{
+    invite_codes::PgInviteCodeRepository,
+    refresh_tokens::{PgRefreshTokenRepository, RefreshTokenRepositoryError},
+    user_email_addresses::PgUserEmailAddressRepository,
+    users::{PgUserRepository, UserRepositoryError},
+};
+use miscellaneous::current_unix_epoch;
+
+use crate::errors::AuthError;
+use crate::login_attempts::{detect_anomaly, record_login_attempt, LoginClient};
+use crate::totp::start_totp_challenge;
+use crate::transactions::with_transaction;
+use crate::webhooks::{WebhookDispatcher, WebhookEventType};
 
 #[derive(Debug, thiserror::Error)]
 pub enum SignupError {
@@ -24,9 +41,16 @@ pub enum SignupError {
     UnexpectedError(anyhow::Error),
     #[error("Eメールアドレスが既に登録されています。")]
     EmailAddressAlreadyExists,
+    #[error("ユーザー名が既に登録されています。")]
+    UserNameAlreadyExists,
+    #[error("招待コードが無効か、既に使用されています。")]
+    InvalidInviteCode,
+    #[error("使い捨てEメールアドレスでは登録できません。")]
+    DisposableEmail,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SignupResult {
     pub id: Uuid,
     pub user_name: String,
@@ -36,50 +60,127 @@ pub struct SignupResult {
     pub updated_at: OffsetDateTime,
 }
 
+/// ユーザーを登録する。
+///
+/// 招待コードを指定した場合は、未使用の招待コードであることを確認して、ユーザーの登録と同じトランザクションで
+/// 招待コードを使用済みにする。招待コードが無効か、既に使用されている場合は、ユーザーを登録しない。
+/// 招待コードを使用して登録したユーザーは、招待コードを発行したテナントに所属して、招待コードを指定しない
+/// 場合は、既定のテナントに所属する。
+///
+/// 使い捨てEメールアドレスを拒否するように設定されている場合は、使い捨てEメールアドレスのドメインの
+/// Eメールアドレスでは、ユーザーを登録しない。
+///
+/// # Arguments
+///
+/// * `user_name` - ユーザー名。
+/// * `email_address` - Eメールアドレス。
+/// * `password` - パスワード。
+/// * `invite_code` - 招待コード。招待コードを必要としない場合は`None`。
+/// * `settings` - システム設定。
+/// * `webhooks` - Webhookディスパッチャー。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 登録したユーザー。
 pub async fn signup(
     user_name: UserName,
     email_address: EmailAddress,
     password: RawPassword,
+    invite_code: Option<&str>,
+    settings: &Settings,
+    webhooks: &WebhookDispatcher,
     pool: &PgPool,
-) -> anyhow::Result<SignupResult, SignupError> {
-    // トランザクションを開始
-    let mut tx = pool
-        .begin()
-        .await
-        .map_err(|e| SignupError::UnexpectedError(e.into()))?;
-    // リポジトリを構築
-    let repository = PgUserRepository::default();
-
-    // メールアドレスが一致するユーザーが存在しないか確認
-    let found = repository
-        .get_by_email_address(&email_address, &mut tx)
-        .await
-        .map_err(|e| SignupError::UnexpectedError(e.into()))?;
-    if found.is_some() {
-        return Err(SignupError::EmailAddressAlreadyExists);
+) -> anyhow::Result<SignupResult, AuthError> {
+    // 使い捨てEメールアドレスを拒否
+    if settings.signup.is_disposable_email(email_address.value()) {
+        return Err(SignupError::DisposableEmail.into());
     }
 
-    // ユーザーを登録
-    let hashed_password = HashedPassword::new(&password).map_err(SignupError::UnexpectedError)?;
-    let user = User::new(
-        UserId::default(),
-        user_name,
-        email_address,
-        hashed_password,
-        true,
-        None,
-        None,
-        None,
-    );
-    let user = repository
-        .insert(&user, &mut tx)
+    // Eメールアドレスが登録されているかどうかで処理時間が変わらないように、重複を確認する前にパスワードを
+    // ハッシュ化
+    let hashed_password = spawn_password_hashing(move || HashedPassword::new(&password))
         .await
-        .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+        .map_err(|e| SignupError::UnexpectedError(e.into()))?
+        .map_err(SignupError::UnexpectedError)?;
 
-    // トランザクションをコミット
-    tx.commit()
-        .await
-        .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+    // トランザクション内でユーザーを登録
+    let user = with_transaction(pool, SignupError::UnexpectedError, |tx| {
+        Box::pin(async move {
+            // リポジトリを構築
+            let repository = PgUserRepository;
+
+            // 未確認のエイリアスを含めて、メールアドレスが他のユーザーに登録されていないか確認
+            let exists = PgUserEmailAddressRepository
+                .exists_by_email_address(&email_address, tx)
+                .await
+                .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+            if exists {
+                return Err(SignupError::EmailAddressAlreadyExists);
+            }
+
+            // 大文字と小文字を区別せずに、ユーザー名が一致するユーザーが存在しないか確認
+            let exists = repository
+                .exists_by_user_name(&user_name, tx)
+                .await
+                .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+            if exists {
+                return Err(SignupError::UserNameAlreadyExists);
+            }
+
+            // 招待コードを指定した場合は、招待コードを発行したテナントにユーザーを登録
+            let code_hash = invite_code
+                .map(|invite_code| invite_code_hash(invite_code, &settings.tokens.secret_key))
+                .transpose()
+                .map_err(SignupError::UnexpectedError)?;
+            let tenant_id = match &code_hash {
+                Some(code_hash) => PgInviteCodeRepository
+                    .get_unused_tenant_id(code_hash, tx)
+                    .await
+                    .map_err(|e| SignupError::UnexpectedError(e.into()))?
+                    .ok_or(SignupError::InvalidInviteCode)?,
+                None => DEFAULT_TENANT_ID,
+            };
+
+            // ユーザーを登録
+            let user = User::new(
+                UserId::default(),
+                tenant_id,
+                user_name,
+                email_address,
+                hashed_password,
+                true,
+                None,
+                ProfileVisibility::default(),
+                None,
+                false,
+                UserRole::User,
+                None,
+                None,
+            );
+            let user = repository
+                .insert(&user, tx)
+                .await
+                .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+
+            // 招待コードを使用済みにして、使用できなかった場合は、トランザクションをロールバックしてユーザーを
+            // 登録しない
+            if let Some(code_hash) = code_hash {
+                let consumed = PgInviteCodeRepository
+                    .consume(&code_hash, user.id(), tx)
+                    .await
+                    .map_err(|e| SignupError::UnexpectedError(e.into()))?;
+                if !consumed {
+                    return Err(SignupError::InvalidInviteCode);
+                }
+            }
+
+            Ok(user)
+        })
+    })
+    .await?;
+    // ユーザーを登録したことを外部のサービスに通知
+    webhooks.dispatch(WebhookEventType::UserSignedUp, user.id().value());
 
     Ok(SignupResult {
         id: user.id().value().to_owned(),
@@ -101,47 +202,120 @@ pub enum LoginError {
     NotActive(Uuid),
 }
 
+impl From<password::AuthError> for LoginError {
+    fn from(e: password::AuthError) -> Self {
+        match e {
+            password::AuthError::InvalidCredentials(_) => Self::InvalidCredentials,
+            password::AuthError::UnexpectedError(e) => Self::UnexpectedError(e),
+        }
+    }
+}
+
+/// ログインするユーザーの識別子
+#[derive(Debug, Clone)]
+pub enum LoginIdentifier {
+    /// Eメールアドレス。
+    EmailAddress(EmailAddress),
+    /// ユーザー名。大文字と小文字を区別せずに比較する。
+    UserName(UserName),
+}
+
+impl LoginIdentifier {
+    /// 識別子からユーザーを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - トランザクション。
+    ///
+    /// # Returns
+    ///
+    /// ユーザーインスタンス。ユーザーが見つからなかった場合は`None`。
+    async fn find_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<User>, UserRepositoryError> {
+        match self {
+            Self::EmailAddress(email_address) => {
+                PgUserRepository
+                    .get_by_email_address(email_address, tx)
+                    .await
+            }
+            Self::UserName(user_name) => PgUserRepository.get_by_user_name(user_name, tx).await,
+        }
+    }
+}
+
 /// データベースからユーザーを取得して、パスワードを検証する。
 ///
+/// パスワードの検証に成功した場合は、平文のパスワードを受け取っている間に、パスワードが現在のパスワードの
+/// ポリシーを満たしているかも確認する。クライアントでハッシュ化したパスワードは、文字種を確認できないため
+/// ポリシーを満たしているとみなす。
+///
 /// # Arguments
 ///
-/// * `email_address` - Eメールアドレス。
+/// * `identifier` - ユーザーの識別子。
 /// * `raw_password` - パスワード。
+/// * `client_hashed` - クライアントでハッシュ化したパスワードか。
 /// * `tx` - トランザクション。
 ///
 /// # Returns
 ///
-/// * ユーザーインスタンス。
+/// * ユーザーインスタンスと、パスワードが現在のパスワードのポリシーを満たしているか。
 #[tracing::instrument(name = "Validate credentials", skip(raw_password, tx))]
 async fn validate_credentials(
-    email_address: EmailAddress,
+    identifier: &LoginIdentifier,
     raw_password: Secret<String>,
+    client_hashed: bool,
     tx: &mut Transaction<'_, Postgres>,
-) -> Result<User, LoginError> {
-    // Eメールアドレスからユーザーを取得
-    let result = PgUserRepository::default()
-        .get_by_email_address(&email_address, tx)
+) -> Result<(User, bool), LoginError> {
+    // Eメールアドレス又はユーザー名からユーザーを取得
+    let result = identifier
+        .find_user(tx)
         .await
         .map_err(|e| LoginError::UnexpectedError(e.into()))?;
-    if result.is_none() {
-        return Err(LoginError::InvalidCredentials);
-    }
+    let user = match result {
+        Some(user) => user,
+        None => {
+            // 応答時間からユーザーの存在が推測されないように、ユーザーが見つからなかった場合も
+            // ダミーのハッシュ化したパスワードで検証
+            spawn_password_hashing(move || verify_dummy_password(&raw_password))
+                .await
+                .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+            return Err(LoginError::InvalidCredentials);
+        }
+    };
 
     // 引数で受け取ったパスワードをハッシュ化した結果が、ユーザーに記録されているハッシュ化パスワードと一致するか確認
-    let user = result.unwrap();
-    let expected_hashed = user.hashed_password().value().to_owned();
-    let result =
-        spawn_blocking_with_tracing(move || verify_password(&expected_hashed, &raw_password))
-            .await
-            .map_err(|e| LoginError::UnexpectedError(e.into()))?;
-    if let Err(e) = result {
-        return Err(match e {
-            AuthError::InvalidCredentials(_) => LoginError::InvalidCredentials,
-            AuthError::UnexpectedError(e) => LoginError::UnexpectedError(e),
-        });
-    }
+    let policy = (!client_hashed).then_some(&*RAW_PASSWORD_SETTINGS);
+    spawn_password_hashing(move || verify_user_password(user, raw_password, policy))
+        .await
+        .map_err(|e| LoginError::UnexpectedError(e.into()))?
+}
+
+/// ユーザーに記録されているハッシュ化パスワードで、パスワードを検証する。
+///
+/// ハッシュ化パスワードや平文のパスワードの複製をメモリに残さないように、ユーザーとパスワードの所有権を受け取り、
+/// ハッシュ化パスワードを複製せずに参照して検証する。パスワードはこの関数を抜けるときに破棄され、
+/// `Secret`によってゼロクリアされる。
+///
+/// # Arguments
+///
+/// * `user` - ユーザー。
+/// * `raw_password` - パスワード。
+/// * `policy` - パスワードが満たしているか確認するパスワードのポリシー。`None`の場合は確認しない。
+///
+/// # Returns
+///
+/// パスワードの検証に成功した場合は、受け取ったユーザーと、パスワードがポリシーを満たしているか。
+fn verify_user_password(
+    user: User,
+    raw_password: Secret<String>,
+    policy: Option<&RawPasswordSettings>,
+) -> Result<(User, bool), LoginError> {
+    verify_password(user.hashed_password().value(), &raw_password)?;
+    let meets_policy = policy.is_none_or(|policy| RawPassword::meets_policy(&raw_password, policy));
 
-    Ok(user)
+    Ok((user, meets_policy))
 }
 
 /// ユーザーの最終更新日時を更新する。
@@ -157,7 +331,7 @@ async fn update_last_logged_in(
     user_id: UserId,
     tx: &mut Transaction<'_, Postgres>,
 ) -> Result<(), LoginError> {
-    PgUserRepository::default()
+    PgUserRepository
         .update_last_logged_in(user_id, tx)
         .await
         .map_err(|e| LoginError::UnexpectedError(e.into()))?;
@@ -165,56 +339,345 @@ async fn update_last_logged_in(
     Ok(())
 }
 
+/// ログイン結果列挙型
+#[derive(Debug)]
+pub enum LoginOutcome {
+    /// 認証に成功して、セッションを開始した。
+    Authenticated {
+        /// セッションデータ。
+        session_data: SessionData,
+        /// パスワードを変更するまで、パスワードの変更以外の保護されたリソースにアクセスできないか。
+        must_change_password: bool,
+        /// パスワードが現在のパスワードのポリシーを満たしているか。
+        password_meets_current_policy: bool,
+    },
+    /// 2要素認証を有効にしているため、TOTPのコードの検証が必要。チャレンジトークンを保持する。
+    TotpRequired(String),
+}
+
 /// ログインする。
 ///
 /// ログインを試行して、ログインに成功したら、ユーザーの最終ログイン日時を更新して、Redisにセッションデータ
 /// を登録する。
+/// 登録されているユーザーのログイン試行は、成否にかかわらず記録する。ログインに成功した場合は、過去の
+/// ログイン試行と比較して異常を検知したときに警告をログに出力する。
+/// ユーザーが2要素認証を有効にしている場合は、セッションを開始せずにTOTPチャレンジを生成して、
+/// チャレンジトークンを返却する。
+/// パスワードのポリシーを強化した後に、既存のユーザーのパスワードがポリシーを満たしていない場合は、
+/// ログインを許可して、パスワードがポリシーを満たしていないことを返却する。
 pub async fn login(
-    email_address: EmailAddress,
+    identifier: LoginIdentifier,
     raw_password: Secret<String>,
+    client_hashed: bool,
+    client: LoginClient,
     settings: &Settings,
     session: &TypedSession,
     pool: &PgPool,
-) -> anyhow::Result<SessionData, LoginError> {
-    // トランザクションを開始
-    let mut tx = pool
-        .begin()
-        .await
-        .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+) -> anyhow::Result<LoginOutcome, AuthError> {
+    let attempted_at = OffsetDateTime::now_utc();
 
-    // データベースからユーザーを取得して、パスワードを検証
-    let user = validate_credentials(email_address, raw_password, &mut tx).await?;
+    // 失敗したログイン試行を記録するため、識別子とクライアントを処理に移動せずに参照を渡す
+    let (identifier_ref, client_ref) = (&identifier, &client);
+    let result = with_transaction(
+        pool,
+        |e| LoginError::UnexpectedError(e).into(),
+        |tx| {
+            Box::pin(async move {
+                // データベースからユーザーを取得して、パスワードを検証
+                let (user, password_meets_current_policy) =
+                    validate_credentials(identifier_ref, raw_password, client_hashed, tx).await?;
 
-    // ユーザーがアクティブでない場合は、エラーを返却が確認
-    if !user.is_active() {
-        return Err(LoginError::NotActive(user.id().value()));
+                // ユーザーがアクティブでない場合は、エラーを返却が確認
+                if !user.is_active() {
+                    return Err(LoginError::NotActive(user.id().value()).into());
+                }
+
+                // 2要素認証を有効にしている場合は、TOTPのコードを検証するまでセッションを開始しない
+                if user.totp_secret().is_some() {
+                    let challenge_token = start_totp_challenge(&user, session)?;
+                    return Ok(LoginOutcome::TotpRequired(challenge_token));
+                }
+
+                // セッションを開始
+                let session_data = start_session(
+                    &user,
+                    client_ref.clone(),
+                    attempted_at,
+                    settings,
+                    session,
+                    tx,
+                )
+                .await?;
+
+                // セッションデータを返却
+                Ok(LoginOutcome::Authenticated {
+                    session_data,
+                    must_change_password: user.must_change_password(),
+                    password_meets_current_policy,
+                })
+            })
+        },
+    )
+    .await;
+
+    // 認証に失敗した場合は、トランザクションをロールバックした後に、失敗したログイン試行を記録
+    if let Err(AuthError::Login(LoginError::InvalidCredentials | LoginError::NotActive(_))) = result
+    {
+        record_failed_login_attempt(&identifier, &client, attempted_at, pool).await;
+    }
+
+    result
+}
+
+/// 認証したユーザーのセッションを開始する。
+///
+/// ログイン試行を記録して、セッションデータを生成してリフレッシュトークンをデータベースに、セッションデータを
+/// Redisに登録した後、ユーザーの最終ログイン日時を更新する。パスワードとパスキーのどちらで認証した場合も、
+/// 同じ手順でセッションを開始する。
+///
+/// # Arguments
+///
+/// * `user` - 認証したユーザー。
+/// * `client` - ログインしたクライアントの情報。
+/// * `attempted_at` - 試行日時。
+/// * `settings` - システム設定。
+/// * `session` - セッション。
+/// * `tx` - トランザクション。
+///
+/// # Returns
+///
+/// セッションデータ。
+pub(crate) async fn start_session(
+    user: &User,
+    client: LoginClient,
+    attempted_at: OffsetDateTime,
+    settings: &Settings,
+    session: &TypedSession,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<SessionData, AuthError> {
+    // 過去のログイン試行と比較して異常を検知した後、ログイン試行を記録
+    let attempt = client.to_login_attempt(user.id(), true, attempted_at);
+    if let Some(anomaly) = detect_anomaly(user.id(), &attempt, tx).await? {
+        tracing::warn!(
+            "ユーザー({})のログイン試行に異常を検知しました。{:?}",
+            user.id().value(),
+            anomaly
+        );
     }
+    record_login_attempt(&attempt, tx).await?;
 
     // セッションデータを生成
     let Settings { tokens, .. } = settings;
     #[allow(clippy::redundant_closure)]
-    let session_data = generate_session_data(user.id().value(), tokens)
-        .map_err(|e| LoginError::UnexpectedError(e))?;
+    let session_data = generate_session_data(
+        Uuid::new_v4(),
+        user.id().value(),
+        client.device_name,
+        current_unix_epoch(),
+        tokens,
+    )
+    .map_err(|e| LoginError::UnexpectedError(e))?;
+
+    // リフレッシュトークンをデータベースに登録
+    let refresh_token = RefreshToken::from_session_data(&session_data, &tokens.secret_key)
+        .map_err(LoginError::UnexpectedError)?;
+    PgRefreshTokenRepository
+        .insert(&refresh_token, tx)
+        .await
+        .map_err(|e| LoginError::UnexpectedError(e.into()))?;
 
-    // セッション固定化攻撃に対する対策として、セッションを更新
-    session.renew();
     // セッションデータをセッションストアに登録
     session
         .insert(&session_data)
         .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+    // セッション固定化攻撃に対する対策として、セッションを更新
+    session.renew();
 
     // ユーザーの最終ログイン日時を更新
-    update_last_logged_in(user.id(), &mut tx).await?;
+    update_last_logged_in(user.id(), tx).await?;
+
+    Ok(session_data)
+}
+
+/// ログインに失敗したログイン試行を記録する。
+///
+/// Eメールアドレス又はユーザー名が登録されているユーザーのログイン試行のみを記録する。
+/// ログイン試行を記録できなくても、ログインに失敗したことを応答できるように、エラーはログに出力するのみとする。
+///
+/// # Arguments
+///
+/// * `identifier` - ログインを試行したユーザーの識別子。
+/// * `client` - ログインを試行したクライアントの情報。
+/// * `attempted_at` - 試行日時。
+/// * `pool` - データベースコネクションプール。
+async fn record_failed_login_attempt(
+    identifier: &LoginIdentifier,
+    client: &LoginClient,
+    attempted_at: OffsetDateTime,
+    pool: &PgPool,
+) {
+    let result: anyhow::Result<()> = async {
+        let mut tx = pool.begin().await?;
+        if let Some(user) = identifier.find_user(&mut tx).await? {
+            let attempt = client.to_login_attempt(user.id(), false, attempted_at);
+            record_login_attempt(&attempt, &mut tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!("ログイン試行を記録できませんでした。{:?}", e);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefreshError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("認証されていません。")]
+    Unauthorized,
+}
+
+/// トークンをリフレッシュする。
+///
+/// クッキーに記録されていたリフレッシュトークンが、セッションデータのリフレッシュトークンと一致して、
+/// 有効期限内の場合は、セッションIDを変更せずにトークンを更新したセッションデータを生成して、データベース
+/// とRedisに登録する。リフレッシュ回数が上限に達している場合と、ユーザーが無効になっている場合は、
+/// セッションを破棄して再ログインを要求する。
+///
+/// セッションストアのセッションを継続させるため、ログインと異なりセッションを更新（`renew`）しない。
+pub async fn refresh(
+    refresh_token: &str,
+    settings: &Settings,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<SessionData, AuthError> {
+    // セッションデータを取得
+    let session_data = session
+        .get()
+        .map_err(|e| RefreshError::UnexpectedError(e.into()))?
+        .ok_or(RefreshError::Unauthorized)?;
+    // リフレッシュトークンが一致して、有効期限内か確認
+    if session_data.refresh_token.expose() != refresh_token
+        || session_data.refresh_expiration < current_unix_epoch()
+    {
+        return Err(RefreshError::Unauthorized.into());
+    }
+    // 1回のログインでリフレッシュできる回数の上限に達している場合は、セッションを破棄して再ログインを要求
+    let Settings { tokens, .. } = settings;
+    if session_data.has_exhausted_refresh_chain(tokens.max_refresh_chain) {
+        session.purge();
+        return Err(RefreshError::Unauthorized.into());
+    }
+
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| RefreshError::UnexpectedError(e.into()))?;
+    // ユーザーが存在して、アクティブか確認
+    let user = PgUserRepository
+        .get_by_id(UserId::new(session_data.user_id), &mut tx)
+        .await
+        .map_err(|e| RefreshError::UnexpectedError(e.into()))?
+        .ok_or(RefreshError::Unauthorized)?;
+    // 無効になったユーザーには、トークンを再発行せずにセッションを破棄して再ログインを要求
+    if !user.is_active() {
+        tracing::warn!(
+            user_id = %session_data.user_id,
+            "無効になったユーザーがトークンをリフレッシュしようとしました。"
+        );
+        session.purge();
+        return Err(RefreshError::Unauthorized.into());
+    }
+
+    // セッションIDを変更せずに、トークンを再発行したセッションデータを生成
+    let session_data =
+        reissue_session_data(session_data, tokens).map_err(RefreshError::UnexpectedError)?;
+
+    // データベースに記録されているリフレッシュトークンを更新
+    let refresh_token = RefreshToken::from_session_data(&session_data, &tokens.secret_key)
+        .map_err(RefreshError::UnexpectedError)?;
+    PgRefreshTokenRepository
+        .update(&refresh_token, &mut tx)
+        .await
+        .map_err(|e| match e {
+            RefreshTokenRepositoryError::NotFoundError(_) => RefreshError::Unauthorized,
+            _ => RefreshError::UnexpectedError(e.into()),
+        })?;
 
     // トランザクションをコミット
     tx.commit()
         .await
-        .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+        .map_err(|e| RefreshError::UnexpectedError(e.into()))?;
+
+    // セッションIDを維持するため、セッションを更新せずにセッションデータをセッションストアに登録
+    session
+        .insert(&session_data)
+        .map_err(|e| RefreshError::UnexpectedError(e.into()))?;
 
-    // セッションデータを返却
     Ok(session_data)
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum LogoutError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+}
+
+/// ログアウトする。
+///
+/// データベースから現在のセッションのリフレッシュトークンを削除して、Redisに格納されたセッションデータを
+/// 削除する。
+/// リフレッシュトークンが既に削除されている場合は、ログアウトを継続する。
+///
+/// # Returns
+///
+/// 終了したセッションのセッションID。セッションデータがなかった場合は`None`。
+pub async fn logout(
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<Option<Uuid>, AuthError> {
+    // セッションデータを取得
+    let session_data = session
+        .get()
+        .map_err(|e| LogoutError::UnexpectedError(e.into()))?;
+    let session_id = session_data
+        .as_ref()
+        .map(|session_data| session_data.session_id);
+    if let Some(session_data) = session_data {
+        // トランザクションを開始
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|e| LogoutError::UnexpectedError(e.into()))?;
+        // 現在のセッションのリフレッシュトークンを削除
+        let result = PgRefreshTokenRepository
+            .delete(SessionId::new(session_data.session_id), &mut tx)
+            .await;
+        match result {
+            Ok(_) => {}
+            Err(RefreshTokenRepositoryError::NotFoundError(session_id)) => {
+                tracing::info!(
+                    "セッション({})のリフレッシュトークンは既に削除されています。",
+                    session_id
+                );
+            }
+            Err(e) => return Err(LogoutError::UnexpectedError(e.into()).into()),
+        }
+        // トランザクションをコミット
+        tx.commit()
+            .await
+            .map_err(|e| LogoutError::UnexpectedError(e.into()))?;
+    }
+    // Redisからセッションデータを削除
+    session.purge();
+
+    Ok(session_id)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ChangePasswordError {
     #[error(transparent)]
@@ -223,38 +686,127 @@ pub enum ChangePasswordError {
     IncorrectCurrentPassword,
     #[error("ユーザー({0})が存在しません。")]
     NotFound(Uuid),
+    #[error("パスワードを変更するには、再認証が必要です。")]
+    ReauthRequired,
 }
 
 /// パスワードを変更する。
 ///
+/// ログイン又は現在のパスワードの検証から、システム設定の再認証せずに許可する期間が経過している場合は、
+/// パスワードを変更せずに、再認証が必要であることを示すエラーを返却する。
+///
+/// 同じユーザーのパスワードの変更はトランザクション内でロックして直列化して、ロックを取得した後に
+/// データベースから取得したパスワードで、現在のパスワードを検証する。
+///
 /// パスワードの変更を試行して、パスワードの変更に成功したら、Redisに格納されたセッションデータを削除する。
+/// また、ユーザーのすべてのリフレッシュトークンを削除するため、他のセッションも認証ミドルウェアで認証されなくなる。
+///
+/// システム設定でパスワード変更時にログアウトしないように設定されている場合は、現在のセッションのセッション
+/// データを削除せずに、トークンを更新したセッションデータをデータベースとRedisに登録して、現在のセッションを継続する。
+///
+/// # Returns
+///
+/// 現在のセッションを継続した場合は、トークンを更新したセッションデータ。ログアウトした場合は`None`。
 pub async fn change_password(
     user: &User,
     current_password: RawPassword,
     new_password: RawPassword,
+    settings: &Settings,
+    webhooks: &WebhookDispatcher,
     session: &TypedSession,
     pool: &PgPool,
-) -> anyhow::Result<(), ChangePasswordError> {
-    // ユーザーの現在のパスワードが一致するか確認
-    let expected_hashed = user.hashed_password().value().to_owned();
-    let result = spawn_blocking_with_tracing(move || {
-        verify_password(&expected_hashed, current_password.value())
-    })
-    .await
-    .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
-    if result.is_err() {
-        return Err(ChangePasswordError::IncorrectCurrentPassword);
+) -> anyhow::Result<Option<SessionData>, AuthError> {
+    // 最終認証日時から、再認証せずに許可する期間が経過していないか確認
+    let Settings { tokens, .. } = settings;
+    let current_session_data = session
+        .get()
+        .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+    let recently_authenticated = current_session_data.as_ref().is_some_and(|session_data| {
+        session_data
+            .is_recently_authenticated(current_unix_epoch(), tokens.reauthentication_window())
+    });
+    if !recently_authenticated {
+        return Err(ChangePasswordError::ReauthRequired.into());
     }
-    // トランザクションを開始
-    let mut tx = pool
-        .begin()
+    let session_data = with_transaction(
+        pool,
+        |e| ChangePasswordError::UnexpectedError(e).into(),
+        |tx| {
+            Box::pin(async move {
+                change_password_in_transaction(
+                    user,
+                    current_password,
+                    new_password,
+                    tokens,
+                    current_session_data,
+                    tx,
+                )
+                .await
+            })
+        },
+    )
+    .await?;
+    // パスワードを変更したことを外部のサービスに通知
+    webhooks.dispatch(WebhookEventType::PasswordChanged, user.id().value());
+    match &session_data {
+        // セッションを更新して、トークンを更新したセッションデータをRedisに登録
+        Some(session_data) => {
+            session
+                .insert(session_data)
+                .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+            session.renew();
+        }
+        // Redisからセッションデータを削除
+        None => session.purge(),
+    }
+
+    Ok(session_data)
+}
+
+/// トランザクション内で、パスワードを変更する。
+///
+/// 現在のパスワードを検証してパスワードを変更した後、ユーザーのすべてのリフレッシュトークンを削除する。
+/// 現在のセッションを継続する場合は、トークンを更新したセッションデータを生成して、リフレッシュトークンを
+/// データベースに登録する。
+///
+/// # Returns
+///
+/// 現在のセッションを継続する場合は、トークンを更新したセッションデータ。ログアウトする場合は`None`。
+async fn change_password_in_transaction(
+    user: &User,
+    current_password: RawPassword,
+    new_password: RawPassword,
+    tokens: &TokensSettings,
+    current_session_data: Option<SessionData>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<Option<SessionData>, AuthError> {
+    // 同じユーザーのパスワードを同時に変更できないように、トランザクションが終了するまでロック
+    PgUserRepository
+        .lock_password_change(user.id(), tx)
         .await
         .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+    // 先に完了したパスワードの変更を反映するため、ロックを取得した後にユーザーを取得し直して、
+    // 現在のパスワードが一致するか確認
+    let user = PgUserRepository
+        .get_by_id(user.id(), tx)
+        .await
+        .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ChangePasswordError::NotFound(user.id().value()))?;
+    let expected_hashed = user.hashed_password().value().to_owned();
+    let result =
+        spawn_password_hashing(move || verify_password(&expected_hashed, current_password.value()))
+            .await
+            .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+    if result.is_err() {
+        return Err(ChangePasswordError::IncorrectCurrentPassword.into());
+    }
     // パスワードを変更
-    let hashed_password =
-        HashedPassword::new(&new_password).map_err(ChangePasswordError::UnexpectedError)?;
-    PgUserRepository::default()
-        .change_password(user.id(), hashed_password, &mut tx)
+    let hashed_password = spawn_password_hashing(move || HashedPassword::new(&new_password))
+        .await
+        .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?
+        .map_err(ChangePasswordError::UnexpectedError)?;
+    PgUserRepository
+        .change_password(user.id(), hashed_password, tx)
         .await
         .map_err(|e| match e {
             UserRepositoryError::UnexpectedError(e) => ChangePasswordError::UnexpectedError(e),
@@ -263,12 +815,236 @@ pub async fn change_password(
                 "パスワード変更する機能に、実装上のエラーがあります。"
             )),
         })?;
+    // パスワードが漏洩していた場合に他のセッションを使用できないように、ユーザーのすべてのリフレッシュ
+    // トークンを削除して、ユーザーのすべてのセッションを失効
+    PgRefreshTokenRepository
+        .delete_by_user_id(user.id(), tx)
+        .await
+        .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+    // 現在のセッションを継続する場合は、セッションIDを変更せずに、トークンを更新したセッションデータを生成して、
+    // リフレッシュトークンをデータベースに登録
+    let session_data = match current_session_data {
+        Some(session_data) if !tokens.logout_on_password_change => {
+            // 現在のパスワードで認証したため、最終認証日時を更新
+            let session_data = generate_session_data(
+                session_data.session_id,
+                session_data.user_id,
+                session_data.device_name,
+                current_unix_epoch(),
+                tokens,
+            )
+            .map_err(ChangePasswordError::UnexpectedError)?;
+            let refresh_token = RefreshToken::from_session_data(&session_data, &tokens.secret_key)
+                .map_err(ChangePasswordError::UnexpectedError)?;
+            PgRefreshTokenRepository
+                .insert(&refresh_token, tx)
+                .await
+                .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+            Some(session_data)
+        }
+        _ => None,
+    };
+
+    Ok(session_data)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteAccountError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("パスワードが間違っています。")]
+    IncorrectPassword,
+    #[error("ユーザー({0})が存在しません。")]
+    NotFound(Uuid),
+}
+
+/// アカウントを削除する。
+///
+/// パスワードを検証した後、ユーザーのすべてのリフレッシュトークンを削除して、ユーザーを論理削除する。
+/// これらは1つのトランザクションで処理して、最後にRedisに格納された現在のセッションデータを削除する。
+///
+/// 他のセッションのセッションデータはRedisに残るが、ユーザーを取得できず、リフレッシュトークンも
+/// 存在しないため、認証ミドルウェアはそれらのセッションを認証しない。
+pub async fn delete_account(
+    user: &User,
+    password: Secret<String>,
+    webhooks: &WebhookDispatcher,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // ユーザーのパスワードが一致するか確認
+    let expected_hashed = user.hashed_password().value().to_owned();
+    let result = spawn_password_hashing(move || verify_password(&expected_hashed, &password))
+        .await
+        .map_err(|e| DeleteAccountError::UnexpectedError(e.into()))?;
+    if let Err(e) = result {
+        let e = match e {
+            password::AuthError::InvalidCredentials(_) => DeleteAccountError::IncorrectPassword,
+            password::AuthError::UnexpectedError(e) => DeleteAccountError::UnexpectedError(e),
+        };
+        return Err(e.into());
+    }
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| DeleteAccountError::UnexpectedError(e.into()))?;
+    // ユーザーのすべてのリフレッシュトークンを削除
+    PgRefreshTokenRepository
+        .delete_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(|e| DeleteAccountError::UnexpectedError(e.into()))?;
+    // ユーザーを論理削除
+    PgUserRepository
+        .soft_delete(user.id(), &mut tx)
+        .await
+        .map_err(|e| match e {
+            UserRepositoryError::NotFoundError(id) => DeleteAccountError::NotFound(id),
+            e => DeleteAccountError::UnexpectedError(e.into()),
+        })?;
     // トランザクションをコミット
     tx.commit()
         .await
-        .map_err(|e| ChangePasswordError::UnexpectedError(e.into()))?;
+        .map_err(|e| DeleteAccountError::UnexpectedError(e.into()))?;
+    // アカウントを削除したことを外部のサービスに通知
+    webhooks.dispatch(WebhookEventType::AccountDeleted, user.id().value());
     // Redisからセッションデータを削除
     session.purge();
 
     Ok(())
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyPasswordError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("パスワードが間違っています。")]
+    IncorrectPassword,
+}
+
+/// ログインしているユーザーの現在のパスワードを検証する。
+///
+/// 重要な操作の前にパスワードを再度入力させるステップアップ認証で使用する。パスワードが一致した場合は、
+/// セッションデータの最終認証日時を更新して、ユーザーやトークンは変更しない。
+///
+/// # Arguments
+///
+/// * `user` - ログインしているユーザー。
+/// * `password` - 検証するパスワード。
+/// * `session` - セッション。
+pub async fn verify_current_password(
+    user: &User,
+    password: Secret<String>,
+    session: &TypedSession,
+) -> anyhow::Result<(), AuthError> {
+    let expected_hashed = user.hashed_password().value().to_owned();
+    spawn_password_hashing(move || verify_password(&expected_hashed, &password))
+        .await
+        .map_err(|e| VerifyPasswordError::UnexpectedError(e.into()))?
+        .map_err(|e| match e {
+            password::AuthError::InvalidCredentials(_) => VerifyPasswordError::IncorrectPassword,
+            password::AuthError::UnexpectedError(e) => VerifyPasswordError::UnexpectedError(e),
+        })?;
+    // 最終認証日時を更新
+    let session_data = session
+        .get()
+        .map_err(|e| VerifyPasswordError::UnexpectedError(e.into()))?;
+    if let Some(mut session_data) = session_data {
+        session_data.last_authenticated_at = current_unix_epoch();
+        session
+            .insert(&session_data)
+            .map_err(|e| VerifyPasswordError::UnexpectedError(e.into()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    /// 間違ったパスワードで検証した場合に、予期していないエラーではなく、クレデンシャルが不正であることを
+    /// 示すエラーに変換されることを確認するテスト
+    #[test]
+    fn wrong_password_yields_invalid_credentials() {
+        let hashed =
+            password::compute_hashed_password(&Secret::new("correct-password".to_owned())).unwrap();
+        let e = verify_password(&hashed, &Secret::new("wrong-password".to_owned())).unwrap_err();
+        assert!(matches!(
+            LoginError::from(e),
+            LoginError::InvalidCredentials
+        ));
+        // ハッシュ化したパスワードが不正な場合は、予期していないエラー
+        let e = verify_password(
+            &Secret::new("invalid-phc-string".to_owned()),
+            &Secret::new("wrong-password".to_owned()),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            LoginError::from(e),
+            LoginError::UnexpectedError(_)
+        ));
+    }
+
+    /// ユーザーに記録されているハッシュ化パスワードでパスワードを検証して、検証に成功した場合は受け取った
+    /// ユーザーをそのまま返却することを確認するテスト
+    #[test]
+    fn verify_user_password_returns_user() {
+        let user = user_with_password("correct-password");
+        let id = user.id().value();
+        let (user, _) =
+            verify_user_password(user, Secret::new("correct-password".to_owned()), None).unwrap();
+        assert_eq!(user.id().value(), id);
+        assert!(matches!(
+            verify_user_password(user, Secret::new("wrong-password".to_owned()), None),
+            Err(LoginError::InvalidCredentials)
+        ));
+    }
+
+    /// パスワードのポリシーを強化すると、正しいパスワードで認証できても、パスワードがポリシーを満たしていない
+    /// ことを返却することを確認するテスト
+    #[test]
+    fn verify_user_password_flags_password_not_meeting_tightened_policy() {
+        let password = "01abCD#$xy";
+        let current = RawPasswordSettings {
+            min_len: 8,
+            max_len: 256,
+        };
+        let tightened = RawPasswordSettings {
+            min_len: 12,
+            max_len: 256,
+        };
+        let user = user_with_password(password);
+        let (user, meets_policy) =
+            verify_user_password(user, Secret::new(password.to_owned()), Some(&current)).unwrap();
+        assert!(meets_policy);
+        let (user, meets_policy) =
+            verify_user_password(user, Secret::new(password.to_owned()), Some(&tightened)).unwrap();
+        assert!(!meets_policy);
+        // ポリシーを確認しない場合は、ポリシーを満たしているとみなす
+        let (_, meets_policy) =
+            verify_user_password(user, Secret::new(password.to_owned()), None).unwrap();
+        assert!(meets_policy);
+    }
+
+    /// 指定したパスワードをハッシュ化して記録したユーザーを生成する。
+    fn user_with_password(password: &str) -> User {
+        let hashed = password::compute_hashed_password(&Secret::new(password.to_owned())).unwrap();
+        User::new(
+            UserId::default(),
+            DEFAULT_TENANT_ID,
+            UserName::new("taro").unwrap(),
+            EmailAddress::new("taro@example.com").unwrap(),
+            HashedPassword::new_unchecked(hashed.expose_secret().to_owned()),
+            true,
+            None,
+            ProfileVisibility::default(),
+            None,
+            false,
+            UserRole::User,
+            None,
+            None,
+        )
+    }
+}
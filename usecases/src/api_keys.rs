@@ -0,0 +1,110 @@
+use secrecy::Secret;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use configurations::{
+    tokens::{api_key_hash, generate_api_key},
+    TokensSettings,
+};
+use domains::models::{
+    api_keys::{ApiKey, ApiKeyId, ApiKeyScope},
+    users::User,
+};
+use infrastructures::repositories::api_keys::PgApiKeyRepository;
+
+use crate::errors::AuthError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("APIキー({0})が見つかりません。")]
+    NotFound(Uuid),
+}
+
+/// 発行したAPIキー構造体
+pub struct IssuedApiKey {
+    /// APIキーID。
+    pub id: Uuid,
+    /// APIキー。
+    pub api_key: Secret<String>,
+}
+
+/// ユーザーのAPIキーを発行する。
+///
+/// APIキーは、ハッシュのみをデータベースに記録するため、発行したAPIキーを再度取得することはできない。
+///
+/// # Arguments
+///
+/// * `user` - APIキーを発行するユーザー。
+/// * `scopes` - APIキーのスコープ。
+/// * `expires_in` - APIキーの有効期間。有効期限を設定しない場合は`None`。
+/// * `settings` - トークン設定。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 発行したAPIキー。
+pub async fn issue_api_key(
+    user: &User,
+    scopes: Vec<ApiKeyScope>,
+    expires_in: Option<Duration>,
+    settings: &TokensSettings,
+    pool: &PgPool,
+) -> anyhow::Result<IssuedApiKey, AuthError> {
+    let api_key = generate_api_key();
+    let key_hash =
+        api_key_hash(&api_key, &settings.secret_key).map_err(ApiKeyError::UnexpectedError)?;
+    let id = ApiKeyId::default();
+    let expired_at = expires_in.map(|expires_in| OffsetDateTime::now_utc() + expires_in);
+    let record = ApiKey::new(id.clone(), &key_hash, user.id(), scopes, expired_at);
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+    // APIキーを登録
+    PgApiKeyRepository
+        .insert(&record, &mut tx)
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+
+    Ok(IssuedApiKey {
+        id: id.value(),
+        api_key: Secret::new(api_key),
+    })
+}
+
+/// ユーザーのAPIキーを失効させる。
+///
+/// # Arguments
+///
+/// * `user` - APIキーを所有するユーザー。
+/// * `id` - 失効させるAPIキーのAPIキーID。
+/// * `pool` - データベースコネクションプール。
+pub async fn revoke_api_key(user: &User, id: Uuid, pool: &PgPool) -> anyhow::Result<(), AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+    // APIキーを削除して、ユーザーのAPIキーが見つからない場合は、他のユーザーのAPIキーかどうかを明かさない
+    let deleted = PgApiKeyRepository
+        .delete(ApiKeyId::new(id), user.id(), &mut tx)
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+    if !deleted {
+        return Err(ApiKeyError::NotFound(id).into());
+    }
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| ApiKeyError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
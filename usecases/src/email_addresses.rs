@@ -0,0 +1,183 @@
+use sqlx::PgPool;
+
+use domains::models::{
+    user_email_addresses::{UserEmailAddress, UserEmailAddressId},
+    users::User,
+    EmailAddress,
+};
+use infrastructures::repositories::{
+    user_email_addresses::{PgUserEmailAddressRepository, UserEmailAddressRepositoryError},
+    users::PgUserRepository,
+};
+
+use crate::errors::AuthError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmailAddressError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("Eメールアドレスが既に登録されています。")]
+    AlreadyExists,
+    #[error("Eメールアドレス({0})が登録されていません。")]
+    NotFound(String),
+    #[error("プライマリEメールアドレスは削除できません。")]
+    PrimaryCannotBeRemoved,
+    #[error("Eメールアドレス({0})が確認されていません。")]
+    NotVerified(String),
+}
+
+impl From<UserEmailAddressRepositoryError> for EmailAddressError {
+    fn from(e: UserEmailAddressRepositoryError) -> Self {
+        match e {
+            UserEmailAddressRepositoryError::NotFoundError(email_address) => {
+                Self::NotFound(email_address)
+            }
+            _ => Self::UnexpectedError(e.into()),
+        }
+    }
+}
+
+/// ユーザーにEメールアドレスのエイリアスを登録する。
+///
+/// 登録したエイリアスは、未確認のEメールアドレスとして記録する。未確認のエイリアスは、所有が確認されるまで
+/// ログインに使用できず、プライマリEメールアドレスにも設定できない。
+/// エイリアスは、他のユーザーに登録されたEメールアドレスと重複できない。
+///
+/// # Arguments
+///
+/// * `user` - エイリアスを登録するユーザー。
+/// * `email_address` - 登録するEメールアドレス。
+/// * `pool` - データベースコネクションプール。
+pub async fn add_email_alias(
+    user: &User,
+    email_address: EmailAddress,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+    let repository = PgUserEmailAddressRepository;
+    // Eメールアドレスが登録されていないか確認
+    let exists = repository
+        .exists_by_email_address(&email_address, &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?;
+    if exists {
+        return Err(EmailAddressError::AlreadyExists.into());
+    }
+    // エイリアスを登録
+    let alias = UserEmailAddress::new(
+        UserEmailAddressId::default(),
+        user.id(),
+        email_address,
+        false,
+        false,
+        None,
+    );
+    repository
+        .insert(&alias, &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+/// ユーザーのプライマリEメールアドレスを変更する。
+///
+/// プライマリEメールアドレスは、ユーザーへの通知の送信先として使用する。
+/// 未確認のEメールアドレスは、プライマリEメールアドレスに設定できない。
+///
+/// # Arguments
+///
+/// * `user` - プライマリEメールアドレスを変更するユーザー。
+/// * `email_address` - プライマリEメールアドレスに設定する、ユーザーに登録されたEメールアドレス。
+/// * `pool` - データベースコネクションプール。
+pub async fn set_primary_email(
+    user: &User,
+    email_address: EmailAddress,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+    let repository = PgUserEmailAddressRepository;
+    // 設定するEメールアドレスが確認済みか確認
+    let registered = repository
+        .list_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?
+        .into_iter()
+        .find(|registered| registered.email_address().value() == email_address.value())
+        .ok_or_else(|| EmailAddressError::NotFound(email_address.value().to_owned()))?;
+    if !registered.is_verified() {
+        return Err(EmailAddressError::NotVerified(email_address.value().to_owned()).into());
+    }
+    // プライマリEメールアドレスを変更
+    repository
+        .set_primary(user.id(), &email_address, &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?;
+    // ユーザーのEメールアドレスをプライマリEメールアドレスに変更
+    PgUserRepository
+        .change_email_address(user.id(), &email_address, &mut tx)
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+/// ユーザーからEメールアドレスのエイリアスを削除する。
+///
+/// プライマリEメールアドレスは削除できない。
+///
+/// # Arguments
+///
+/// * `user` - エイリアスを削除するユーザー。
+/// * `email_address` - 削除するEメールアドレス。
+/// * `pool` - データベースコネクションプール。
+pub async fn remove_email_alias(
+    user: &User,
+    email_address: EmailAddress,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+    let repository = PgUserEmailAddressRepository;
+    // 削除するEメールアドレスがプライマリEメールアドレスでないか確認
+    let registered = repository
+        .list_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?
+        .into_iter()
+        .find(|registered| registered.email_address().value() == email_address.value())
+        .ok_or_else(|| EmailAddressError::NotFound(email_address.value().to_owned()))?;
+    if registered.is_primary() {
+        return Err(EmailAddressError::PrimaryCannotBeRemoved.into());
+    }
+    // エイリアスを削除
+    repository
+        .delete(user.id(), &email_address, &mut tx)
+        .await
+        .map_err(EmailAddressError::from)?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| EmailAddressError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
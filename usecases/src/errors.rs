@@ -0,0 +1,291 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+
+use crate::accounts::{
+    ChangePasswordError, DeleteAccountError, LoginError, LogoutError, RefreshError, SignupError,
+    VerifyPasswordError,
+};
+use crate::api_keys::ApiKeyError;
+use crate::email_addresses::EmailAddressError;
+use crate::invite_codes::InviteCodeError;
+use crate::login_attempts::LoginAttemptError;
+use crate::passkeys::PasskeyError;
+use crate::password_resets::PasswordResetError;
+use crate::security_questions::SecurityQuestionError;
+use crate::sessions::SessionError;
+use crate::totp::TotpError;
+use crate::users::UserError;
+
+/// 認証エラー
+///
+/// 認証に関するユースケースが返却するエラーで、それぞれのユースケース固有のエラーを内包する。
+/// HTTPステータスコードへの変換は、`ResponseError`の実装に集約する。
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error(transparent)]
+    Signup(#[from] SignupError),
+    #[error(transparent)]
+    Login(#[from] LoginError),
+    #[error(transparent)]
+    Refresh(#[from] RefreshError),
+    #[error(transparent)]
+    Logout(#[from] LogoutError),
+    #[error(transparent)]
+    ChangePassword(#[from] ChangePasswordError),
+    #[error(transparent)]
+    DeleteAccount(#[from] DeleteAccountError),
+    #[error(transparent)]
+    VerifyPassword(#[from] VerifyPasswordError),
+    #[error(transparent)]
+    PasswordReset(#[from] PasswordResetError),
+    #[error(transparent)]
+    SecurityQuestion(#[from] SecurityQuestionError),
+    #[error(transparent)]
+    LoginAttempt(#[from] LoginAttemptError),
+    #[error(transparent)]
+    User(#[from] UserError),
+    #[error(transparent)]
+    EmailAddress(#[from] EmailAddressError),
+    #[error(transparent)]
+    Passkey(#[from] PasskeyError),
+    #[error(transparent)]
+    Totp(#[from] TotpError),
+    #[error(transparent)]
+    InviteCode(#[from] InviteCodeError),
+    #[error(transparent)]
+    Session(#[from] SessionError),
+    #[error(transparent)]
+    ApiKey(#[from] ApiKeyError),
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Signup(e) => match e {
+                SignupError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                SignupError::EmailAddressAlreadyExists
+                | SignupError::UserNameAlreadyExists
+                | SignupError::InvalidInviteCode
+                | SignupError::DisposableEmail => StatusCode::BAD_REQUEST,
+            },
+            Self::Login(e) => match e {
+                LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                LoginError::InvalidCredentials | LoginError::NotActive(_) => {
+                    StatusCode::UNAUTHORIZED
+                }
+            },
+            Self::Refresh(e) => match e {
+                RefreshError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                RefreshError::Unauthorized => StatusCode::UNAUTHORIZED,
+            },
+            Self::Logout(e) => match e {
+                LogoutError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::ChangePassword(e) => match e {
+                ChangePasswordError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                ChangePasswordError::IncorrectCurrentPassword
+                | ChangePasswordError::NotFound(_) => StatusCode::BAD_REQUEST,
+                ChangePasswordError::ReauthRequired => StatusCode::UNAUTHORIZED,
+            },
+            Self::DeleteAccount(e) => match e {
+                DeleteAccountError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DeleteAccountError::IncorrectPassword | DeleteAccountError::NotFound(_) => {
+                    StatusCode::BAD_REQUEST
+                }
+            },
+            Self::VerifyPassword(e) => match e {
+                VerifyPasswordError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                VerifyPasswordError::IncorrectPassword => StatusCode::UNAUTHORIZED,
+            },
+            Self::PasswordReset(e) => match e {
+                PasswordResetError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                PasswordResetError::InvalidToken => StatusCode::BAD_REQUEST,
+                PasswordResetError::UserNotFound(_) => StatusCode::NOT_FOUND,
+            },
+            Self::SecurityQuestion(e) => match e {
+                SecurityQuestionError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                SecurityQuestionError::InvalidQuestions(_)
+                | SecurityQuestionError::NotConfigured => StatusCode::BAD_REQUEST,
+                SecurityQuestionError::IncorrectAnswers => StatusCode::UNAUTHORIZED,
+            },
+            Self::LoginAttempt(e) => match e {
+                LoginAttemptError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::User(e) => match e {
+                UserError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                UserError::NotFound(_) => StatusCode::NOT_FOUND,
+                UserError::TenantMismatch(_) => StatusCode::FORBIDDEN,
+            },
+            Self::EmailAddress(e) => match e {
+                EmailAddressError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                EmailAddressError::AlreadyExists
+                | EmailAddressError::PrimaryCannotBeRemoved
+                | EmailAddressError::NotVerified(_) => StatusCode::BAD_REQUEST,
+                EmailAddressError::NotFound(_) => StatusCode::NOT_FOUND,
+            },
+            Self::Passkey(e) => match e {
+                PasskeyError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                PasskeyError::InvalidChallenge
+                | PasskeyError::InvalidRegistration(_)
+                | PasskeyError::AlreadyRegistered => StatusCode::BAD_REQUEST,
+                PasskeyError::AuthenticationFailed | PasskeyError::NotActive(_) => {
+                    StatusCode::UNAUTHORIZED
+                }
+            },
+            Self::Totp(e) => match e {
+                TotpError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TotpError::InvalidChallenge
+                | TotpError::NotEnrolled
+                | TotpError::NotPending
+                | TotpError::ReverificationRequired => StatusCode::BAD_REQUEST,
+                TotpError::InvalidCode | TotpError::NotActive(_) => StatusCode::UNAUTHORIZED,
+            },
+            Self::InviteCode(e) => match e {
+                InviteCodeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::Session(e) => match e {
+                SessionError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                SessionError::NotFound(_) => StatusCode::NOT_FOUND,
+                SessionError::TokenNotFound => StatusCode::NOT_FOUND,
+            },
+            Self::ApiKey(e) => match e {
+                ApiKeyError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                ApiKeyError::NotFound(_) => StatusCode::NOT_FOUND,
+            },
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        tracing::error!("{:?}", self);
+
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use uuid::Uuid;
+
+    /// ユースケース固有のエラーが、HTTPステータスコードに変換されることを確認するテスト
+    #[test]
+    fn auth_error_status_code() {
+        let cases: Vec<(AuthError, StatusCode)> = vec![
+            (
+                SignupError::EmailAddressAlreadyExists.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                SignupError::UserNameAlreadyExists.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                SignupError::InvalidInviteCode.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (SignupError::DisposableEmail.into(), StatusCode::BAD_REQUEST),
+            (
+                LoginError::InvalidCredentials.into(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                LoginError::NotActive(Uuid::new_v4()).into(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                LoginError::UnexpectedError(anyhow!("error")).into(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (RefreshError::Unauthorized.into(), StatusCode::UNAUTHORIZED),
+            (
+                LogoutError::UnexpectedError(anyhow!("error")).into(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                ChangePasswordError::IncorrectCurrentPassword.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                ChangePasswordError::ReauthRequired.into(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                DeleteAccountError::IncorrectPassword.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                VerifyPasswordError::IncorrectPassword.into(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                PasswordResetError::InvalidToken.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                PasswordResetError::UserNotFound(Uuid::new_v4()).into(),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                SecurityQuestionError::InvalidQuestions(anyhow!("error")).into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                UserError::NotFound(Uuid::new_v4()).into(),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                UserError::TenantMismatch(Uuid::new_v4()).into(),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                EmailAddressError::AlreadyExists.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                EmailAddressError::PrimaryCannotBeRemoved.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                EmailAddressError::NotFound("foo@example.com".to_owned()).into(),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                EmailAddressError::NotVerified("foo@example.com".to_owned()).into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                PasskeyError::InvalidChallenge.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                PasskeyError::AlreadyRegistered.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                PasskeyError::AuthenticationFailed.into(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (TotpError::InvalidChallenge.into(), StatusCode::BAD_REQUEST),
+            (TotpError::InvalidCode.into(), StatusCode::UNAUTHORIZED),
+            (TotpError::NotEnrolled.into(), StatusCode::BAD_REQUEST),
+            (TotpError::NotPending.into(), StatusCode::BAD_REQUEST),
+            (
+                TotpError::ReverificationRequired.into(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                SessionError::NotFound(Uuid::new_v4()).into(),
+                StatusCode::NOT_FOUND,
+            ),
+            (SessionError::TokenNotFound.into(), StatusCode::NOT_FOUND),
+            (
+                ApiKeyError::NotFound(Uuid::new_v4()).into(),
+                StatusCode::NOT_FOUND,
+            ),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(error.status_code(), expected, "{:?}", error);
+            assert_eq!(error.error_response().status(), expected);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use configurations::{
+    tokens::{generate_invite_code, invite_code_hash},
+    TokensSettings,
+};
+use infrastructures::repositories::invite_codes::PgInviteCodeRepository;
+
+use crate::errors::AuthError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InviteCodeError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+}
+
+/// 招待コードを発行する。
+///
+/// 招待コードは、ハッシュのみをデータベースに記録する。発行した招待コードは、メールなどで招待する利用者に
+/// 通知する。招待コードは、サインアップに1回だけ使用できる。招待コードを使用して登録したユーザーは、
+/// 招待コードを発行したテナントに所属する。
+///
+/// # Arguments
+///
+/// * `tenant_id` - 招待するテナントのテナントID。
+/// * `settings` - トークン設定。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 招待コード。
+pub async fn issue_invite_code(
+    tenant_id: Uuid,
+    settings: &TokensSettings,
+    pool: &PgPool,
+) -> anyhow::Result<Secret<String>, AuthError> {
+    let code = generate_invite_code();
+    let code_hash =
+        invite_code_hash(&code, &settings.secret_key).map_err(InviteCodeError::UnexpectedError)?;
+
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| InviteCodeError::UnexpectedError(e.into()))?;
+    PgInviteCodeRepository
+        .insert(&code_hash, tenant_id, &mut tx)
+        .await
+        .map_err(|e| InviteCodeError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| InviteCodeError::UnexpectedError(e.into()))?;
+
+    Ok(Secret::new(code))
+}
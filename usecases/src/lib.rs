@@ -1 +1,14 @@
 pub mod accounts;
+pub mod api_keys;
+pub mod email_addresses;
+pub mod errors;
+pub mod invite_codes;
+pub mod login_attempts;
+pub mod passkeys;
+pub mod password_resets;
+pub mod security_questions;
+pub mod sessions;
+pub mod totp;
+pub mod transactions;
+pub mod users;
+pub mod webhooks;
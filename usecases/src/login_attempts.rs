@@ -0,0 +1,296 @@
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+
+use domains::models::{
+    login_attempts::{GeoLocation, LoginAttempt, LoginAttemptId},
+    users::UserId,
+};
+use infrastructures::repositories::login_attempts::PgLoginAttemptRepository;
+
+use crate::errors::AuthError;
+
+/// 異常検知で比較する過去のログイン試行の最大数
+pub const ANOMALY_DETECTION_HISTORY_LEN: i64 = 50;
+
+/// 地理的に不可能な移動と判断する移動速度（km/h）
+///
+/// 旅客機の巡航速度（約900km/h）を超える速度で移動した場合は、地理的に不可能な移動と判断する。
+pub const IMPOSSIBLE_TRAVEL_SPEED_KMH: f64 = 1_000.0;
+
+/// 地理的に不可能な移動と判断する最小の移動距離（km）
+///
+/// IPアドレスから推定した位置には誤差があるため、この距離未満の移動は異常と判断しない。
+pub const IMPOSSIBLE_TRAVEL_MIN_DISTANCE_KM: f64 = 500.0;
+
+/// 異常な時間帯を判断するために必要な過去のログイン試行の最小数
+///
+/// 過去のログイン試行が少ない場合は、ログインする時間帯の傾向がわからないため、異常な時間帯を判断しない。
+pub const UNUSUAL_HOUR_MIN_HISTORY_LEN: usize = 10;
+
+/// 異常な時間帯と判断しない、過去にログインした時間帯の前後の時間
+const UNUSUAL_HOUR_TOLERANCE: u8 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoginAttemptError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+}
+
+/// ログインを試行したクライアントの情報
+#[derive(Debug, Clone, Default)]
+pub struct LoginClient {
+    /// IPアドレス。
+    pub ip_address: Option<String>,
+    /// デバイス名。
+    pub device_name: Option<String>,
+    /// 位置。
+    pub location: Option<GeoLocation>,
+}
+
+impl LoginClient {
+    /// クライアントの情報からログイン試行インスタンスを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - ユーザーID。
+    /// * `succeeded` - ログインに成功したか。
+    /// * `attempted_at` - 試行日時。
+    ///
+    /// # Returns
+    ///
+    /// ログイン試行インスタンス。
+    pub fn to_login_attempt(
+        &self,
+        user_id: UserId,
+        succeeded: bool,
+        attempted_at: OffsetDateTime,
+    ) -> LoginAttempt {
+        LoginAttempt::new(
+            LoginAttemptId::default(),
+            user_id,
+            succeeded,
+            self.ip_address.clone(),
+            self.device_name.clone(),
+            self.location,
+            attempted_at,
+        )
+    }
+}
+
+/// ログイン試行の異常の種類
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnomalyType {
+    /// 地理的に不可能な移動。
+    ImpossibleTravel {
+        /// 前回ログインした位置からの距離（km）。
+        distance_km: f64,
+        /// 前回ログインした位置からの移動速度（km/h）。
+        speed_kmh: f64,
+    },
+    /// 過去にログインしたことがない時間帯（UTC）。
+    UnusualHour {
+        /// ログインを試行した時間（UTC）。
+        hour: u8,
+    },
+}
+
+/// ログイン試行を記録する。
+///
+/// # Arguments
+///
+/// * `attempt` - ログイン試行。
+/// * `tx` - トランザクション。
+pub async fn record_login_attempt(
+    attempt: &LoginAttempt,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<(), AuthError> {
+    PgLoginAttemptRepository
+        .insert(attempt, tx)
+        .await
+        .map_err(|e| LoginAttemptError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+/// 過去のログイン試行と比較して、ログイン試行の異常を検知する。
+///
+/// ユーザーが過去にログインに成功したログイン試行と比較して、地理的に不可能な移動と、
+/// 過去にログインしたことがない時間帯のログイン試行を検知する。
+///
+/// # Arguments
+///
+/// * `user_id` - ユーザーID。
+/// * `current_attempt` - 検知するログイン試行。記録する前のログイン試行を指定する。
+/// * `tx` - トランザクション。
+///
+/// # Returns
+///
+/// 検知した異常の種類。異常を検知しなかった場合は`None`。
+pub async fn detect_anomaly(
+    user_id: UserId,
+    current_attempt: &LoginAttempt,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<Option<AnomalyType>, AuthError> {
+    let history = PgLoginAttemptRepository
+        .list_recent_succeeded_by_user_id(user_id, ANOMALY_DETECTION_HISTORY_LEN, tx)
+        .await
+        .map_err(|e| LoginAttemptError::UnexpectedError(e.into()))?;
+
+    Ok(detect_anomaly_from_history(&history, current_attempt))
+}
+
+/// 過去のログイン試行と比較して、ログイン試行の異常を検知する。
+///
+/// # Arguments
+///
+/// * `history` - ユーザーが過去にログインに成功したログイン試行を、試行日時の降順で並べたスライス。
+/// * `current_attempt` - 検知するログイン試行。
+///
+/// # Returns
+///
+/// 検知した異常の種類。異常を検知しなかった場合は`None`。
+pub fn detect_anomaly_from_history(
+    history: &[LoginAttempt],
+    current_attempt: &LoginAttempt,
+) -> Option<AnomalyType> {
+    detect_impossible_travel(history, current_attempt)
+        .or_else(|| detect_unusual_hour(history, current_attempt))
+}
+
+/// 位置を記録した直近のログイン試行からの移動速度で、地理的に不可能な移動を検知する。
+fn detect_impossible_travel(
+    history: &[LoginAttempt],
+    current_attempt: &LoginAttempt,
+) -> Option<AnomalyType> {
+    let current_location = current_attempt.location()?;
+    let (previous_location, previous_attempted_at) = history
+        .iter()
+        .find_map(|attempt| Some((attempt.location()?, attempt.attempted_at())))?;
+    let distance_km = current_location.distance_km(&previous_location);
+    if distance_km < IMPOSSIBLE_TRAVEL_MIN_DISTANCE_KM {
+        return None;
+    }
+    // 同時刻の場合に0で除算しないように、経過時間は1秒以上とする
+    let elapsed_hours = (current_attempt.attempted_at() - previous_attempted_at)
+        .as_seconds_f64()
+        .max(1.0)
+        / 3_600.0;
+    let speed_kmh = distance_km / elapsed_hours;
+    if speed_kmh <= IMPOSSIBLE_TRAVEL_SPEED_KMH {
+        return None;
+    }
+
+    Some(AnomalyType::ImpossibleTravel {
+        distance_km,
+        speed_kmh,
+    })
+}
+
+/// 過去にログインした時間帯と比較して、異常な時間帯のログイン試行を検知する。
+fn detect_unusual_hour(
+    history: &[LoginAttempt],
+    current_attempt: &LoginAttempt,
+) -> Option<AnomalyType> {
+    if history.len() < UNUSUAL_HOUR_MIN_HISTORY_LEN {
+        return None;
+    }
+    let hour = current_attempt.attempted_at().hour();
+    let is_usual = history.iter().any(|attempt| {
+        let diff = attempt.attempted_at().hour().abs_diff(hour);
+        // 23時と0時のように日をまたぐ時間も前後の時間として扱う
+        diff.min(24 - diff) <= UNUSUAL_HOUR_TOLERANCE
+    });
+    if is_usual {
+        return None;
+    }
+
+    Some(AnomalyType::UnusualHour { hour })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{macros::datetime, Duration};
+
+    fn tokyo() -> GeoLocation {
+        GeoLocation::new(35.6812, 139.7671).unwrap()
+    }
+
+    fn attempt(location: Option<GeoLocation>, attempted_at: OffsetDateTime) -> LoginAttempt {
+        let client = LoginClient {
+            ip_address: Some("192.0.2.1".to_owned()),
+            device_name: Some("Chrome (Windows)".to_owned()),
+            location,
+        };
+        client.to_login_attempt(UserId::default(), true, attempted_at)
+    }
+
+    /// 毎日9時（UTC）に東京からログインした履歴を、試行日時の降順で生成する。
+    fn daily_history(last: OffsetDateTime, days: i64) -> Vec<LoginAttempt> {
+        (0..days)
+            .map(|day| attempt(Some(tokyo()), last - Duration::days(day)))
+            .collect()
+    }
+
+    #[test]
+    fn test_no_anomaly_with_usual_pattern() {
+        let history = daily_history(datetime!(2022-10-01 09:00 UTC), 20);
+        // 翌日の同じ時間帯に、東京の近くからログイン
+        let yokohama = GeoLocation::new(35.4437, 139.6380).unwrap();
+        let current = attempt(Some(yokohama), datetime!(2022-10-02 09:30 UTC));
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+        // 前後の時間帯のログインは異常としない
+        let current = attempt(Some(tokyo()), datetime!(2022-10-02 10:00 UTC));
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+        // 履歴がない場合は異常としない
+        assert_eq!(detect_anomaly_from_history(&[], &current), None);
+    }
+
+    #[test]
+    fn test_detect_impossible_travel() {
+        let history = daily_history(datetime!(2022-10-01 09:00 UTC), 20);
+        // 東京からログインした1時間後に、ロンドンからログイン
+        let london = GeoLocation::new(51.5074, -0.1278).unwrap();
+        let current = attempt(Some(london), datetime!(2022-10-01 10:00 UTC));
+        match detect_anomaly_from_history(&history, &current) {
+            Some(AnomalyType::ImpossibleTravel {
+                distance_km,
+                speed_kmh,
+            }) => {
+                assert!(9_500.0 < distance_km);
+                assert!(IMPOSSIBLE_TRAVEL_SPEED_KMH < speed_kmh);
+            }
+            other => panic!("地理的に不可能な移動を検知できませんでした。{:?}", other),
+        }
+        // 旅客機で移動できる時間が経過していれば異常としない
+        let current = attempt(Some(london), datetime!(2022-10-02 09:00 UTC));
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+        // 位置が不明な場合は、地理的に不可能な移動を判断しない
+        let current = attempt(None, datetime!(2022-10-01 10:00 UTC));
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+    }
+
+    #[test]
+    fn test_detect_unusual_hour() {
+        let history = daily_history(datetime!(2022-10-01 09:00 UTC), 20);
+        // 過去にログインしたことがない深夜にログイン
+        let current = attempt(Some(tokyo()), datetime!(2022-10-02 18:00 UTC));
+        assert_eq!(
+            detect_anomaly_from_history(&history, &current),
+            Some(AnomalyType::UnusualHour { hour: 18 })
+        );
+        // 履歴が少ない場合は、異常な時間帯を判断しない
+        let history = daily_history(
+            datetime!(2022-10-01 09:00 UTC),
+            UNUSUAL_HOUR_MIN_HISTORY_LEN as i64 - 1,
+        );
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+    }
+
+    #[test]
+    fn test_unusual_hour_across_midnight() {
+        let history = daily_history(datetime!(2022-10-01 23:00 UTC), 20);
+        let current = attempt(Some(tokyo()), datetime!(2022-10-02 00:30 UTC));
+        assert_eq!(detect_anomaly_from_history(&history, &current), None);
+    }
+}
@@ -0,0 +1,488 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Url, Webauthn,
+    WebauthnBuilder, WebauthnError,
+};
+use webauthn_rs_proto::AllowCredentials;
+
+use configurations::{
+    session::{SessionData, TypedSession},
+    Settings, WebAuthnSettings,
+};
+use domains::models::{user_credentials::UserCredential, users::User, EmailAddress};
+use infrastructures::repositories::{
+    user_credentials::{PgUserCredentialRepository, UserCredentialRepositoryError},
+    users::PgUserRepository,
+};
+use miscellaneous::current_unix_epoch;
+
+use crate::accounts::start_session;
+use crate::errors::AuthError;
+use crate::login_attempts::LoginClient;
+use crate::totp::start_totp_challenge;
+
+/// WebAuthnチャレンジの有効期間（秒）
+pub const WEBAUTHN_CHALLENGE_SECONDS: u64 = 5 * 60;
+
+/// パスキーを登録していないユーザーの認証オプションに含める、クレデンシャルIDのバイト数
+const DUMMY_CREDENTIAL_ID_BYTES: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasskeyError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("WebAuthnチャレンジが存在しないか、有効期限が切れています。")]
+    InvalidChallenge,
+    #[error("パスキーを登録できません。{0}")]
+    InvalidRegistration(WebauthnError),
+    #[error("パスキーは既に登録されています。")]
+    AlreadyRegistered,
+    #[error("パスキーで認証できません。")]
+    AuthenticationFailed,
+    #[error("ユーザー({0})が無効になっています。")]
+    NotActive(Uuid),
+}
+
+/// セッションに保存するWebAuthnの状態列挙型
+///
+/// 登録又は認証を開始したときに、webauthn-rsが生成したチャレンジなどの状態を保存して、登録又は認証を
+/// 完了するときにセッションから取り出して検証する。
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WebAuthnState {
+    /// パスキーの登録
+    Registration {
+        /// パスキーを登録するユーザーのユーザーID。
+        user_id: Uuid,
+        state: PasskeyRegistration,
+        /// 有効期限（UNIXエポック秒）。
+        expiration: u64,
+    },
+    /// パスキーによる認証
+    Authentication {
+        state: PasskeyAuthentication,
+        /// 有効期限（UNIXエポック秒）。
+        expiration: u64,
+    },
+}
+
+impl WebAuthnState {
+    /// 有効期限を返却する。
+    fn expiration(&self) -> u64 {
+        match self {
+            Self::Registration { expiration, .. } | Self::Authentication { expiration, .. } => {
+                *expiration
+            }
+        }
+    }
+}
+
+/// WebAuthn設定から、パスキーの登録と認証に使用するWebAuthnインスタンスを構築する。
+///
+/// # Arguments
+///
+/// * `settings` - WebAuthn設定。
+///
+/// # Returns
+///
+/// WebAuthnインスタンス。オリジンがURLでないか、リライングパーティーIDがオリジンのドメインと一致しない
+/// 場合はエラー。
+pub fn build_webauthn(settings: &WebAuthnSettings) -> anyhow::Result<Webauthn> {
+    let origin = Url::parse(&settings.origin)
+        .with_context(|| format!("WebAuthnのオリジン({})が不正です。", settings.origin))?;
+
+    WebauthnBuilder::new(&settings.rp_id, &origin)
+        .and_then(|builder| {
+            builder
+                .rp_name(&settings.rp_name)
+                .timeout(Duration::from_secs(WEBAUTHN_CHALLENGE_SECONDS))
+                .build()
+        })
+        .with_context(|| {
+            format!(
+                "WebAuthnのリライングパーティーID({})がオリジン({})のドメインと一致しません。",
+                settings.rp_id, settings.origin
+            )
+        })
+}
+
+/// JSONにシリアライズしたパスキーを復元する。
+fn deserialize_passkey(credential: &UserCredential) -> anyhow::Result<Passkey, PasskeyError> {
+    serde_json::from_str(credential.passkey()).map_err(|e| PasskeyError::UnexpectedError(e.into()))
+}
+
+/// パスキーを登録していないユーザーの認証オプションに含める、ダミーのクレデンシャルIDを生成する。
+///
+/// 同じEメールアドレスに対して常に同じクレデンシャルIDを返却するため、認証オプションを繰り返し取得しても、
+/// パスキーを登録したユーザーと区別できない。
+///
+/// # Arguments
+///
+/// * `email_address` - 認証を開始したEメールアドレス。
+/// * `secret_key` - HMACの秘密鍵。
+///
+/// # Returns
+///
+/// ダミーのクレデンシャルID。
+fn dummy_credential_id(email_address: &EmailAddress, secret_key: &Secret<String>) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.expose_secret().as_bytes())
+        .expect("HMACは任意の長さの鍵を受け付けます。");
+    // 同じ秘密鍵で計算する他のHMACと区別するために、用途を示す接頭辞を付与
+    mac.update(b"webauthn_credential_id\0");
+    mac.update(email_address.value().as_bytes());
+
+    mac.finalize().into_bytes()[..DUMMY_CREDENTIAL_ID_BYTES].to_vec()
+}
+
+/// セッションからWebAuthnの状態を取り出して、有効期限を確認する。
+///
+/// # Arguments
+///
+/// * `session` - セッション。
+///
+/// # Returns
+///
+/// WebAuthnの状態。
+fn take_state(session: &TypedSession) -> anyhow::Result<WebAuthnState, PasskeyError> {
+    let state: WebAuthnState = session
+        .take_webauthn_state()
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?
+        .ok_or(PasskeyError::InvalidChallenge)?;
+    if state.expiration() <= current_unix_epoch() {
+        return Err(PasskeyError::InvalidChallenge);
+    }
+
+    Ok(state)
+}
+
+/// パスキーの登録を開始する。
+///
+/// WebAuthnの状態をセッションに保存して、パスキーの登録オプションを返却する。
+///
+/// # Arguments
+///
+/// * `user` - パスキーを登録するユーザー。
+/// * `webauthn` - WebAuthnインスタンス。
+/// * `session` - セッション。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// パスキーの登録オプション。
+pub async fn start_registration(
+    user: &User,
+    webauthn: &Webauthn,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<CreationChallengeResponse, AuthError> {
+    // 既に登録されているパスキーを取得
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    let credentials = PgUserCredentialRepository
+        .list_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    tx.commit()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    let exclude_credentials = credentials
+        .iter()
+        .map(|credential| credential.credential_id().to_vec().into())
+        .collect();
+    let (options, state) = webauthn
+        .start_passkey_registration(
+            user.id().value(),
+            user.email_address().value(),
+            user.user_name().value(),
+            Some(exclude_credentials),
+        )
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    // WebAuthnの状態をセッションに保存
+    session
+        .insert_webauthn_state(&WebAuthnState::Registration {
+            user_id: user.id().value(),
+            state,
+            expiration: current_unix_epoch() + WEBAUTHN_CHALLENGE_SECONDS,
+        })
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+
+    Ok(options)
+}
+
+/// パスキーの登録を完了する。
+///
+/// セッションに保存したWebAuthnの状態でクライアントが送信したアテステーションを検証して、パスキーを
+/// ユーザークレデンシャルとして登録する。
+///
+/// # Arguments
+///
+/// * `user` - パスキーを登録するユーザー。
+/// * `credential` - クライアントが送信したアテステーション。
+/// * `webauthn` - WebAuthnインスタンス。
+/// * `session` - セッション。
+/// * `pool` - データベースコネクションプール。
+pub async fn finish_registration(
+    user: &User,
+    credential: &RegisterPublicKeyCredential,
+    webauthn: &Webauthn,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // 登録を開始したユーザーのWebAuthnの状態か確認
+    let state = match take_state(session)? {
+        WebAuthnState::Registration { user_id, state, .. } if user_id == user.id().value() => state,
+        _ => return Err(PasskeyError::InvalidChallenge.into()),
+    };
+    // アテステーションを検証
+    let passkey = webauthn
+        .finish_passkey_registration(credential, &state)
+        .map_err(PasskeyError::InvalidRegistration)?;
+    let serialized =
+        serde_json::to_string(&passkey).map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    // ユーザークレデンシャルを登録
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    PgUserCredentialRepository
+        .insert(
+            &UserCredential::new(passkey.cred_id().to_vec(), user.id(), serialized),
+            &mut tx,
+        )
+        .await
+        .map_err(|e| match e {
+            UserCredentialRepositoryError::CreateError => PasskeyError::AlreadyRegistered,
+            UserCredentialRepositoryError::UnexpectedError(e) => PasskeyError::UnexpectedError(e),
+        })?;
+    tx.commit()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+/// パスキーによる認証を開始する。
+///
+/// WebAuthnの状態をセッションに保存して、パスキーの認証オプションを返却する。
+///
+/// Eメールアドレスのユーザーが存在しないか、パスキーを登録していない場合も、ユーザーの存在やパスキーの
+/// 登録状況を明かさないように、Eメールアドレスから導出したダミーのクレデンシャルIDを含む認証オプションを
+/// 返却する。ダミーのクレデンシャルIDはWebAuthnの状態に含まれないため、認証は必ず失敗する。
+///
+/// # Arguments
+///
+/// * `email_address` - 認証するユーザーのEメールアドレス。
+/// * `settings` - システム設定。
+/// * `webauthn` - WebAuthnインスタンス。
+/// * `session` - セッション。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// パスキーの認証オプション。
+pub async fn start_authentication(
+    email_address: EmailAddress,
+    settings: &Settings,
+    webauthn: &Webauthn,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<RequestChallengeResponse, AuthError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    let user = PgUserRepository
+        .get_by_email_address(&email_address, &mut tx)
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    let credentials = match &user {
+        Some(user) => PgUserCredentialRepository
+            .list_by_user_id(user.id(), &mut tx)
+            .await
+            .map_err(|e| PasskeyError::UnexpectedError(e.into()))?,
+        None => vec![],
+    };
+    tx.commit()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    let passkeys = credentials
+        .iter()
+        .map(deserialize_passkey)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (mut options, state) = webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    if passkeys.is_empty() {
+        options.public_key.allow_credentials.push(AllowCredentials {
+            type_: "public-key".to_owned(),
+            id: dummy_credential_id(&email_address, &settings.tokens.secret_key).into(),
+            transports: None,
+        });
+    }
+    // WebAuthnの状態をセッションに保存
+    session
+        .insert_webauthn_state(&WebAuthnState::Authentication {
+            state,
+            expiration: current_unix_epoch() + WEBAUTHN_CHALLENGE_SECONDS,
+        })
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+
+    Ok(options)
+}
+
+/// パスキーによるログイン結果列挙型
+#[derive(Debug)]
+pub enum PasskeyLoginOutcome {
+    /// 認証に成功して、セッションを開始した。
+    Authenticated(SessionData),
+    /// 2要素認証を有効にしているため、TOTPのコードの検証が必要。チャレンジトークンを保持する。
+    TotpRequired(String),
+}
+
+/// パスキーによる認証を完了して、ログインする。
+///
+/// セッションに保存したWebAuthnの状態でクライアントが送信したアサーションを、登録したパスキーの公開鍵で
+/// 検証する。認証に成功したら、パスワードでログインした場合と同様にセッションを開始する。
+///
+/// パスキーによる認証では、認証器にユーザーの検証（UV）を要求するため、パスキーの所持と生体認証又はPINに
+/// よる知識の2要素で認証したものとして、2要素認証を有効にしているユーザーでもTOTPのコードの検証を
+/// 省略する。ユーザーを検証していないアサーションを受け付けた場合は、所持の1要素でしかないため、
+/// パスワードでログインした場合と同様にTOTPチャレンジを返却して、セッションを開始しない。
+///
+/// # Arguments
+///
+/// * `credential` - クライアントが送信したアサーション。
+/// * `client` - ログインしたクライアントの情報。
+/// * `settings` - システム設定。
+/// * `webauthn` - WebAuthnインスタンス。
+/// * `session` - セッション。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// ログイン結果。
+pub async fn finish_authentication(
+    credential: &PublicKeyCredential,
+    client: LoginClient,
+    settings: &Settings,
+    webauthn: &Webauthn,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<PasskeyLoginOutcome, AuthError> {
+    let attempted_at = OffsetDateTime::now_utc();
+    let state = match take_state(session)? {
+        WebAuthnState::Authentication { state, .. } => state,
+        _ => return Err(PasskeyError::InvalidChallenge.into()),
+    };
+    // 認証を開始したユーザーのパスキーで、アサーションを検証
+    let result = webauthn
+        .finish_passkey_authentication(credential, &state)
+        .map_err(|e| {
+            tracing::warn!("パスキーを検証できませんでした。{}", e);
+            PasskeyError::AuthenticationFailed
+        })?;
+
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    // 認証を開始した後に削除されたパスキーは拒否
+    let stored = PgUserCredentialRepository
+        .get_by_credential_id(result.cred_id(), &mut tx)
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?
+        .ok_or(PasskeyError::AuthenticationFailed)?;
+    // 署名カウンターなどを更新したパスキーを記録
+    let mut passkey = deserialize_passkey(&stored)?;
+    passkey.update_credential(&result);
+    let serialized =
+        serde_json::to_string(&passkey).map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    PgUserCredentialRepository
+        .update_passkey(stored.credential_id(), &serialized, &mut tx)
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+    // ユーザーが有効か確認
+    let user = PgUserRepository
+        .get_by_id(stored.user_id(), &mut tx)
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?
+        .ok_or(PasskeyError::AuthenticationFailed)?;
+    if !user.is_active() {
+        return Err(PasskeyError::NotActive(user.id().value()).into());
+    }
+
+    // 2要素認証を有効にしていて、認証器がユーザーを検証していない場合は、TOTPのコードを検証するまで
+    // セッションを開始しない
+    if user.totp_secret().is_some() && !result.user_verified() {
+        tx.commit()
+            .await
+            .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+        let challenge_token = start_totp_challenge(&user, session)?;
+        return Ok(PasskeyLoginOutcome::TotpRequired(challenge_token));
+    }
+
+    // セッションを開始
+    let session_data =
+        start_session(&user, client, attempted_at, settings, session, &mut tx).await?;
+
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| PasskeyError::UnexpectedError(e.into()))?;
+
+    Ok(PasskeyLoginOutcome::Authenticated(session_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ダミーのクレデンシャルIDが、Eメールアドレスごとに一定で、秘密鍵ごとに異なることを確認するテスト
+    #[test]
+    fn dummy_credential_id_is_deterministic_per_email_address() {
+        let secret_key = Secret::new("secret".to_owned());
+        let taro = EmailAddress::new("taro@example.com").unwrap();
+        let jiro = EmailAddress::new("jiro@example.com").unwrap();
+        let id = dummy_credential_id(&taro, &secret_key);
+        assert_eq!(id.len(), DUMMY_CREDENTIAL_ID_BYTES);
+        assert_eq!(id, dummy_credential_id(&taro, &secret_key));
+        assert_eq!(
+            id,
+            dummy_credential_id(&EmailAddress::new("Taro@Example.com").unwrap(), &secret_key)
+        );
+        assert_ne!(id, dummy_credential_id(&jiro, &secret_key));
+        assert_ne!(
+            id,
+            dummy_credential_id(&taro, &Secret::new("other".to_owned()))
+        );
+    }
+
+    /// リライングパーティーIDがオリジンのドメインと一致しない場合は、WebAuthnインスタンスを構築できない
+    /// ことを確認するテスト
+    #[test]
+    fn build_webauthn_rejects_mismatched_rp_id() {
+        let mut settings = WebAuthnSettings {
+            rp_id: "localhost".to_owned(),
+            rp_name: "jwt-auth-example".to_owned(),
+            origin: "http://localhost:8000".to_owned(),
+        };
+        assert!(build_webauthn(&settings).is_ok());
+        settings.rp_id = "example.com".to_owned();
+        assert!(build_webauthn(&settings).is_err());
+        settings.origin = "not a url".to_owned();
+        assert!(build_webauthn(&settings).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use configurations::{
+    password::spawn_password_hashing,
+    tokens::{generate_password_reset_token, password_reset_token_hash},
+    TokensSettings,
+};
+use domains::models::{
+    password_reset_tokens::PasswordResetToken,
+    users::{HashedPassword, RawPassword, UserId},
+    EmailAddress,
+};
+use infrastructures::repositories::{
+    password_reset_tokens::PgPasswordResetTokenRepository,
+    refresh_tokens::PgRefreshTokenRepository,
+    users::{PgUserRepository, UserRepositoryError},
+};
+
+use crate::errors::AuthError;
+use crate::webhooks::{WebhookDispatcher, WebhookEvent};
+
+/// パスワードリセットトークンの有効期間（秒）
+pub const PASSWORD_RESET_TOKEN_SECONDS: i64 = 30 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordResetError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("パスワードリセットトークンが無効です。")]
+    InvalidToken,
+    #[error("ユーザー({0})が見つかりません。")]
+    UserNotFound(Uuid),
+}
+
+/// 発行したパスワードリセットトークン
+#[derive(Debug)]
+pub struct IssuedPasswordResetToken {
+    /// パスワードをリセットするユーザーのユーザーID。
+    pub user_id: UserId,
+    /// パスワードリセットトークンをバインドしたEメールアドレス。
+    pub email_address: EmailAddress,
+    /// パスワードリセットトークン。
+    pub token: Secret<String>,
+}
+
+/// パスワードリセットトークンを発行する。
+///
+/// パスワードリセットトークンは、ユーザーのメールアドレスにバインドしたハッシュのみをデータベースに記録する。
+/// エイリアスのメールアドレスを指定した場合も、パスワードリセットトークンはユーザーのメールアドレスにバインド
+/// する。発行したパスワードリセットトークンは、メールなどでユーザーに通知する。
+///
+/// # Arguments
+///
+/// * `email_address` - パスワードをリセットするユーザーのメールアドレス。
+/// * `settings` - トークン設定。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 発行したパスワードリセットトークン。メールアドレスのユーザーが存在しないか、無効な場合は`None`。
+pub async fn issue_password_reset_token(
+    email_address: EmailAddress,
+    settings: &TokensSettings,
+    pool: &PgPool,
+) -> anyhow::Result<Option<IssuedPasswordResetToken>, AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // メールアドレスからユーザーを取得
+    let user = PgUserRepository
+        .get_by_email_address(&email_address, &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    let user = match user {
+        Some(user) if user.is_active() => user,
+        _ => return Ok(None),
+    };
+    // パスワードリセットトークンを生成して、ユーザーのメールアドレスにバインドしたハッシュを登録
+    let email_address = user.email_address().clone();
+    let token = generate_password_reset_token();
+    let token_hash = password_reset_token_hash(&token, email_address.value(), &settings.secret_key)
+        .map_err(PasswordResetError::UnexpectedError)?;
+    let expired_at = OffsetDateTime::now_utc() + Duration::seconds(PASSWORD_RESET_TOKEN_SECONDS);
+    PgPasswordResetTokenRepository
+        .insert(
+            &PasswordResetToken::new(&token_hash, user.id(), expired_at),
+            &mut tx,
+        )
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+
+    Ok(Some(IssuedPasswordResetToken {
+        user_id: user.id(),
+        email_address,
+        token: Secret::new(token),
+    }))
+}
+
+/// ユーザーが要求したパスワードリセットトークンを発行して、Webhookで通知する。
+///
+/// Eメールアドレスが登録されているか推測されないように、ユーザーが存在しない場合も成功する。Webhookを
+/// 受信したサービスは、パスワードリセットトークンをメールなどでユーザーに通知する。
+///
+/// # Arguments
+///
+/// * `email_address` - パスワードをリセットするユーザーのメールアドレス。
+/// * `settings` - トークン設定。
+/// * `webhooks` - Webhookディスパッチャー。
+/// * `pool` - データベースコネクションプール。
+pub async fn request_password_reset(
+    email_address: EmailAddress,
+    settings: &TokensSettings,
+    webhooks: &WebhookDispatcher,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    let issued = match issue_password_reset_token(email_address, settings, pool).await? {
+        Some(issued) => issued,
+        None => return Ok(()),
+    };
+    tracing::info!(
+        user_id = %issued.user_id.value(),
+        "パスワードリセットトークンを発行しました。"
+    );
+    webhooks.dispatch_event(WebhookEvent::password_reset_requested(
+        issued.user_id.value(),
+        issued.email_address.value(),
+        &issued.token,
+    ));
+
+    Ok(())
+}
+
+/// パスワードリセットトークンで、ユーザーのパスワードをリセットする。
+///
+/// パスワードリセットトークンとメールアドレスからハッシュを計算するため、パスワードリセットトークンを
+/// 発行したときのメールアドレスを指定した場合のみ、パスワードをリセットできる。パスワードをリセットした
+/// 後は、ユーザーのすべてのリフレッシュトークンを削除して、すべてのセッションを失効させる。
+///
+/// # Arguments
+///
+/// * `token` - パスワードリセットトークン。
+/// * `email_address` - パスワードをリセットするユーザーのメールアドレス。
+/// * `new_password` - 新しいパスワード。
+/// * `settings` - トークン設定。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// パスワードをリセットしたユーザーのユーザーID。
+pub async fn reset_password(
+    token: Secret<String>,
+    email_address: EmailAddress,
+    new_password: RawPassword,
+    settings: &TokensSettings,
+    pool: &PgPool,
+) -> anyhow::Result<UserId, AuthError> {
+    // パスワードリセットトークンとメールアドレスからハッシュを計算
+    let token_hash = password_reset_token_hash(
+        token.expose_secret(),
+        email_address.value(),
+        &settings.secret_key,
+    )
+    .map_err(PasswordResetError::UnexpectedError)?;
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // パスワードリセットトークンを取り出して、有効期限を確認
+    let reset_token = PgPasswordResetTokenRepository
+        .take(&token_hash, &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?
+        .ok_or(PasswordResetError::InvalidToken)?;
+    if reset_token.is_expired(OffsetDateTime::now_utc()) {
+        return Err(PasswordResetError::InvalidToken.into());
+    }
+    // ユーザーが存在して、メールアドレスが一致するか確認
+    let user = PgUserRepository
+        .get_by_id(reset_token.user_id(), &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?
+        .ok_or(PasswordResetError::InvalidToken)?;
+    if user.email_address().value() != email_address.value() {
+        return Err(PasswordResetError::InvalidToken.into());
+    }
+    // パスワードを変更
+    let hashed_password = spawn_password_hashing(move || HashedPassword::new(&new_password))
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?
+        .map_err(PasswordResetError::UnexpectedError)?;
+    PgUserRepository
+        .change_password(user.id(), hashed_password, &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // ユーザーの残りのパスワードリセットトークンとすべてのリフレッシュトークンを削除
+    PgPasswordResetTokenRepository
+        .delete_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    PgRefreshTokenRepository
+        .delete_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+
+    Ok(user.id())
+}
+
+/// 管理者がユーザーのパスワードをリセットする。
+///
+/// パスワードをリセットした後は、ユーザーのすべてのリフレッシュトークンを削除して、すべてのセッションを
+/// 失効させる。また、ユーザーが次回のログインでパスワードを変更するまで、パスワードの変更以外の
+/// 保護されたリソースにアクセスできないようにする。
+///
+/// # Arguments
+///
+/// * `user_id` - パスワードをリセットするユーザーのユーザーID。
+/// * `tenant_id` - 管理者が管理するテナントのテナントID。
+/// * `new_password` - 新しいパスワード。
+/// * `pool` - データベースコネクションプール。
+pub async fn admin_reset_password(
+    user_id: Uuid,
+    tenant_id: Uuid,
+    new_password: RawPassword,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    let user_id = UserId::new(user_id);
+    // 他のテナントのユーザーは、存在しないユーザーと同様に扱う
+    let map_user_error = |e: UserRepositoryError| match e {
+        UserRepositoryError::NotFoundError(id) | UserRepositoryError::TenantMismatch(id) => {
+            PasswordResetError::UserNotFound(id)
+        }
+        e => PasswordResetError::UnexpectedError(e.into()),
+    };
+    // パスワードをハッシュ化
+    let hashed_password = spawn_password_hashing(move || HashedPassword::new(&new_password))
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?
+        .map_err(PasswordResetError::UnexpectedError)?;
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // 管理するテナントのユーザーか確認
+    PgUserRepository
+        .get_by_id_in_tenant(user_id.clone(), tenant_id, &mut tx)
+        .await
+        .map_err(map_user_error)?
+        .ok_or_else(|| PasswordResetError::UserNotFound(user_id.value()))?;
+    // パスワードを変更して、次回のログインでパスワードの変更を要求
+    PgUserRepository
+        .change_password(user_id.clone(), hashed_password, &mut tx)
+        .await
+        .map_err(map_user_error)?;
+    PgUserRepository
+        .require_password_change(user_id.clone(), &mut tx)
+        .await
+        .map_err(map_user_error)?;
+    // ユーザーのすべてのリフレッシュトークンを削除
+    PgRefreshTokenRepository
+        .delete_by_user_id(user_id, &mut tx)
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| PasswordResetError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use anyhow::anyhow;
+use secrecy::Secret;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use configurations::{
+    password::{spawn_password_hashing, verify_dummy_password},
+    TokensSettings,
+};
+use domains::models::{
+    security_questions::{SecurityQuestion, SECURITY_QUESTIONS_MAX_COUNT},
+    users::User,
+    EmailAddress,
+};
+use infrastructures::repositories::{
+    login_attempts::PgLoginAttemptRepository,
+    security_questions::{PgSecurityQuestionRepository, SecurityQuestionRepositoryError},
+    users::PgUserRepository,
+};
+
+use crate::errors::AuthError;
+use crate::login_attempts::{record_login_attempt, LoginClient};
+use crate::password_resets::issue_password_reset_token;
+use crate::webhooks::{WebhookDispatcher, WebhookEvent};
+
+/// アカウントの回復を制限する、失敗したログイン試行の数
+pub const ACCOUNT_RECOVERY_MAX_FAILED_ATTEMPTS: i64 = 5;
+
+/// アカウントの回復を制限するために数える、失敗したログイン試行の期間（秒）
+pub const ACCOUNT_RECOVERY_LOCKOUT_SECONDS: i64 = 15 * 60;
+
+/// 登録する秘密の質問と回答
+#[derive(Debug, Clone)]
+pub struct NewSecurityQuestion {
+    /// 質問。
+    pub question: String,
+    /// 回答。
+    pub answer: Secret<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecurityQuestionError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error(transparent)]
+    InvalidQuestions(anyhow::Error),
+    #[error("秘密の質問が設定されていません。")]
+    NotConfigured,
+    #[error("秘密の質問の回答が間違っています。")]
+    IncorrectAnswers,
+}
+
+impl From<SecurityQuestionRepositoryError> for SecurityQuestionError {
+    fn from(e: SecurityQuestionRepositoryError) -> Self {
+        match e {
+            SecurityQuestionRepositoryError::UnexpectedError(e) => Self::UnexpectedError(e),
+            SecurityQuestionRepositoryError::CreateError => Self::UnexpectedError(e.into()),
+        }
+    }
+}
+
+/// ユーザーの秘密の質問を設定する。
+///
+/// 回答はパスワードと同様にハッシュ化して保存する。
+/// ユーザーに設定されていた秘密の質問は、すべて置き換えられる。
+pub async fn set_security_questions(
+    user: &User,
+    questions: Vec<NewSecurityQuestion>,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // 秘密の質問の数を確認
+    if questions.is_empty() || SECURITY_QUESTIONS_MAX_COUNT < questions.len() {
+        return Err(SecurityQuestionError::InvalidQuestions(anyhow!(format!(
+            "秘密の質問は1個から{}個設定してください。",
+            SECURITY_QUESTIONS_MAX_COUNT
+        )))
+        .into());
+    }
+    // 回答をハッシュ化
+    let user_id = user.id();
+    let questions = spawn_password_hashing(move || {
+        questions
+            .iter()
+            .enumerate()
+            .map(|(position, q)| {
+                SecurityQuestion::new(user_id.clone(), position as i16, &q.question, &q.answer)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?
+    .map_err(SecurityQuestionError::InvalidQuestions)?;
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    // 秘密の質問を置き換え
+    PgSecurityQuestionRepository
+        .replace(user.id(), &questions, &mut tx)
+        .await
+        .map_err(SecurityQuestionError::from)?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+/// ユーザーの秘密の質問の回答を検証する。
+///
+/// 回答は、質問の順番で指定する。
+/// すべての回答が正しい場合に成功する。どの回答が間違っているか推測されないように、回答が間違っていても
+/// すべての回答を検証する。
+pub async fn verify_security_answers(
+    user: &User,
+    answers: Vec<Secret<String>>,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // ユーザーの秘密の質問を取得
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    let questions = PgSecurityQuestionRepository
+        .list_by_user_id(user.id(), &mut tx)
+        .await
+        .map_err(SecurityQuestionError::from)?;
+    tx.commit()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    if questions.is_empty() {
+        return Err(SecurityQuestionError::NotConfigured.into());
+    }
+    let answer_count_matches = questions.len() == answers.len();
+    // どの回答が間違っているか応答時間から推測されないように、回答が間違っていてもすべての回答を検証
+    let verified = spawn_password_hashing(move || {
+        questions
+            .iter()
+            .zip(answers.iter())
+            .fold(true, |verified, (question, answer)| {
+                question.verify(answer) && verified
+            })
+    })
+    .await
+    .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    if !answer_count_matches || !verified {
+        return Err(SecurityQuestionError::IncorrectAnswers.into());
+    }
+
+    Ok(())
+}
+
+/// 秘密の質問の回答でアカウントを回復する。
+///
+/// Eメールアドレスのユーザーの秘密の質問の回答を検証して、すべての回答が正しい場合は、パスワードリセット
+/// トークンを発行して、パスワードリセットを要求した場合と同様にWebhookで通知する。ユーザーは、通知された
+/// パスワードリセットトークンでパスワードをリセットする。
+///
+/// 回答が間違っている場合は、失敗したログイン試行として記録する。ユーザーが最後にログインに成功した後に、
+/// [`ACCOUNT_RECOVERY_LOCKOUT_SECONDS`]秒以内に失敗したログイン試行が[`ACCOUNT_RECOVERY_MAX_FAILED_ATTEMPTS`]
+/// 回に達した場合は、回答が正しくてもアカウントを回復しない。
+///
+/// Eメールアドレスが登録されているか推測されないように、ユーザーが存在しない場合、ユーザーが無効な場合、
+/// 秘密の質問が設定されていない場合、アカウントの回復を制限している場合、及び回答が間違っている場合も
+/// 成功する。応答時間からも推測されないように、これらの場合もダミーのハッシュで回答を検証する。
+///
+/// # Arguments
+///
+/// * `email_address` - アカウントを回復するユーザーのEメールアドレス。
+/// * `answers` - 質問の順番で指定した秘密の質問の回答。
+/// * `client` - アカウントの回復を試行したクライアントの情報。
+/// * `settings` - トークン設定。
+/// * `webhooks` - Webhookディスパッチャー。
+/// * `pool` - データベースコネクションプール。
+pub async fn recover_account(
+    email_address: EmailAddress,
+    answers: Vec<Secret<String>>,
+    client: LoginClient,
+    settings: &TokensSettings,
+    webhooks: &WebhookDispatcher,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    let attempted_at = OffsetDateTime::now_utc();
+    let answer_count = answers.len();
+    // Eメールアドレスからユーザーを取得して、直近に失敗したログイン試行の数を取得
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    let user = PgUserRepository
+        .get_by_email_address(&email_address, &mut tx)
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    let user = match user {
+        Some(user) if user.is_active() => user,
+        _ => return verify_dummy_answers(answer_count).await,
+    };
+    let failed_attempts = PgLoginAttemptRepository
+        .count_recent_failed_by_user_id(
+            user.id(),
+            attempted_at - Duration::seconds(ACCOUNT_RECOVERY_LOCKOUT_SECONDS),
+            &mut tx,
+        )
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    tx.commit()
+        .await
+        .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+    if ACCOUNT_RECOVERY_MAX_FAILED_ATTEMPTS <= failed_attempts {
+        tracing::warn!(
+            user_id = %user.id().value(),
+            "ログイン試行の失敗が続いているため、アカウントの回復を拒否しました。"
+        );
+        return verify_dummy_answers(answer_count).await;
+    }
+    // 秘密の質問の回答を検証
+    match verify_security_answers(&user, answers, pool).await {
+        Ok(()) => {}
+        Err(AuthError::SecurityQuestion(SecurityQuestionError::NotConfigured)) => {
+            return verify_dummy_answers(answer_count).await;
+        }
+        Err(AuthError::SecurityQuestion(SecurityQuestionError::IncorrectAnswers)) => {
+            // 回答が間違っている場合は、失敗したログイン試行を記録
+            let mut tx = pool
+                .begin()
+                .await
+                .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+            let attempt = client.to_login_attempt(user.id(), false, attempted_at);
+            record_login_attempt(&attempt, &mut tx).await?;
+            tx.commit()
+                .await
+                .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+    // パスワードリセットトークンを発行して、Webhookで通知
+    let issued = issue_password_reset_token(user.email_address().clone(), settings, pool)
+        .await?
+        .ok_or_else(|| {
+            SecurityQuestionError::UnexpectedError(anyhow!(
+                "パスワードリセットトークンを発行できませんでした。"
+            ))
+        })?;
+    tracing::info!(
+        user_id = %issued.user_id.value(),
+        "秘密の質問の回答でアカウントを回復するパスワードリセットトークンを発行しました。"
+    );
+    webhooks.dispatch_event(WebhookEvent::password_reset_requested(
+        issued.user_id.value(),
+        issued.email_address.value(),
+        &issued.token,
+    ));
+
+    Ok(())
+}
+
+/// 応答時間からユーザーの存在などが推測されないように、ダミーのハッシュで回答を検証する。
+///
+/// 秘密の質問を設定できる最大数を上限として、回答の数だけ検証する。
+///
+/// # Arguments
+///
+/// * `answer_count` - 秘密の質問の回答の数。
+async fn verify_dummy_answers(answer_count: usize) -> anyhow::Result<(), AuthError> {
+    spawn_password_hashing(move || {
+        let answer = Secret::new(String::new());
+        for _ in 0..answer_count.clamp(1, SECURITY_QUESTIONS_MAX_COUNT) {
+            verify_dummy_password(&answer);
+        }
+    })
+    .await
+    .map_err(|e| SecurityQuestionError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
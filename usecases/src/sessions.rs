@@ -0,0 +1,200 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use configurations::{session::TypedSession, tokens::refresh_token_hash, TokensSettings};
+use domains::models::{refresh_tokens::SessionId, users::User};
+use infrastructures::repositories::refresh_tokens::{
+    PgRefreshTokenRepository, RefreshTokenRepositoryError,
+};
+
+use crate::errors::AuthError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("セッション({0})が見つかりません。")]
+    NotFound(Uuid),
+    #[error("リフレッシュトークンのセッションが見つかりません。")]
+    TokenNotFound,
+}
+
+/// セッションストアからセッションを削除するトレイト
+///
+/// セッションストアは、セッションキーをキーにセッションの状態を記録するため、セッションIDからセッションキーを
+/// 引く索引でセッションを特定して削除する。
+#[async_trait::async_trait(?Send)]
+pub trait SessionPurger {
+    /// セッションIDで特定したセッションを、セッションストアから削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - 削除するセッションのセッションID。
+    ///
+    /// # Returns
+    ///
+    /// セッションを削除した場合は`true`、セッションストアにセッションが存在しない場合は`false`。
+    async fn purge_session(&self, session_id: Uuid) -> anyhow::Result<bool>;
+}
+
+/// ユーザーが失効させるセッションの指定方法列挙型
+pub enum RevokeTarget {
+    /// リフレッシュトークンの値で指定する。
+    RefreshToken(Secret<String>),
+    /// セッションIDで指定する。
+    SessionId(Uuid),
+}
+
+/// 管理者がセッションを失効させる。
+///
+/// セッションのリフレッシュトークンをデータベースから削除するとともに、セッションストアからセッションの状態を
+/// 削除する。データベースの障害などで、認証ミドルウェアがセッションが失効しているか確認できない場合でも、
+/// 失効させたセッションで認証できないようにするためである。セッションストアから削除できなかった場合は、
+/// 管理者が再度失効させられるように、リフレッシュトークンを削除しない。
+///
+/// 他のテナントのユーザーのセッションは、存在しないセッションと同様に扱う。
+///
+/// # Arguments
+///
+/// * `session_id` - 失効させるセッションのセッションID。
+/// * `tenant_id` - 管理者が管理するテナントのテナントID。
+/// * `purger` - セッションストアからセッションを削除するインスタンス。
+/// * `pool` - データベースコネクションプール。
+pub async fn revoke_session(
+    session_id: Uuid,
+    tenant_id: Uuid,
+    purger: &dyn SessionPurger,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| SessionError::UnexpectedError(e.into()))?;
+    // セッションのリフレッシュトークンを削除
+    PgRefreshTokenRepository
+        .delete_in_tenant(SessionId::new(session_id), tenant_id, &mut tx)
+        .await
+        .map_err(|e| match e {
+            RefreshTokenRepositoryError::NotFoundError(id) => SessionError::NotFound(id),
+            e => SessionError::UnexpectedError(e.into()),
+        })?;
+    // セッションストアからセッションの状態を削除
+    let purged = purger
+        .purge_session(session_id)
+        .await
+        .map_err(SessionError::UnexpectedError)?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| SessionError::UnexpectedError(e.into()))?;
+    tracing::info!(
+        session_id = %session_id,
+        tenant_id = %tenant_id,
+        purged,
+        "管理者がセッションを失効させました。"
+    );
+
+    Ok(())
+}
+
+/// ユーザーが自分のセッションを失効させる。
+///
+/// 他のデバイスからブラウザをログアウトさせるために、リフレッシュトークンの値又はセッションIDで指定された
+/// セッションのリフレッシュトークンをデータベースから削除するとともに、セッションストアからセッションの状態を
+/// 削除する。セッションストアから削除できなかった場合は、ユーザーが再度失効させられるように、リフレッシュ
+/// トークンを削除しない。
+///
+/// セッションIDを推測できないように、指定されたセッションが他のユーザーのセッションの場合は、存在しない
+/// セッションと同様に扱う。
+///
+/// # Arguments
+///
+/// * `user` - 認証されたユーザー。
+/// * `target` - 失効させるセッションの指定。
+/// * `session` - 現在のセッション。
+/// * `settings` - トークン設定。
+/// * `purger` - セッションストアからセッションを削除するインスタンス。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 失効させたセッションのセッションID。
+pub async fn revoke_own_session(
+    user: &User,
+    target: RevokeTarget,
+    session: &TypedSession,
+    settings: &TokensSettings,
+    purger: &dyn SessionPurger,
+    pool: &PgPool,
+) -> anyhow::Result<Uuid, AuthError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| SessionError::UnexpectedError(e.into()))?;
+    // 失効させるセッションのリフレッシュトークンを取得
+    let (refresh_token, not_found) = match target {
+        RevokeTarget::RefreshToken(token) => (
+            PgRefreshTokenRepository
+                .get_by_token(
+                    &refresh_token_hash(token.expose_secret(), &settings.secret_key)
+                        .map_err(SessionError::UnexpectedError)?,
+                    &mut tx,
+                )
+                .await
+                .map_err(|e| SessionError::UnexpectedError(e.into()))?,
+            SessionError::TokenNotFound,
+        ),
+        RevokeTarget::SessionId(session_id) => (
+            PgRefreshTokenRepository
+                .get_by_session_id(SessionId::new(session_id), &mut tx)
+                .await
+                .map_err(|e| SessionError::UnexpectedError(e.into()))?,
+            SessionError::NotFound(session_id),
+        ),
+    };
+    // 他のユーザーのセッションは、存在しないセッションと同様に扱う
+    let refresh_token = match refresh_token {
+        Some(refresh_token) if refresh_token.user_id().value() == user.id().value() => {
+            refresh_token
+        }
+        Some(refresh_token) => {
+            tracing::warn!(
+                session_id = %refresh_token.session_id().value(),
+                user_id = %user.id().value(),
+                "ユーザーが他のユーザーのセッションを失効させようとしました。"
+            );
+            return Err(not_found.into());
+        }
+        None => return Err(not_found.into()),
+    };
+    let session_id = refresh_token.session_id().value();
+    // セッションのリフレッシュトークンを削除
+    PgRefreshTokenRepository
+        .delete(refresh_token.session_id(), &mut tx)
+        .await
+        .map_err(|e| match e {
+            RefreshTokenRepositoryError::NotFoundError(id) => SessionError::NotFound(id),
+            e => SessionError::UnexpectedError(e.into()),
+        })?;
+    // セッションストアからセッションの状態を削除
+    let purged = purger
+        .purge_session(session_id)
+        .await
+        .map_err(SessionError::UnexpectedError)?;
+    tx.commit()
+        .await
+        .map_err(|e| SessionError::UnexpectedError(e.into()))?;
+    // 現在のセッションを失効させた場合は、セッションミドルウェアがセッションの状態を保存し直さないように破棄
+    let current_session_id = session
+        .get()
+        .map_err(|e| SessionError::UnexpectedError(e.into()))?
+        .map(|session_data| session_data.session_id);
+    if current_session_id == Some(session_id) {
+        session.purge();
+    }
+    tracing::info!(session_id = %session_id, purged, "ユーザーがセッションを失効させました。");
+
+    Ok(session_id)
+}
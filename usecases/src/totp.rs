@@ -0,0 +1,387 @@
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use configurations::{
+    session::{SessionData, TypedSession},
+    totp::{
+        generate_recovery_codes, generate_totp_secret, otpauth_uri, recovery_code_hash,
+        verify_totp, TotpChallenge,
+    },
+    Settings,
+};
+use domains::models::users::{User, UserId};
+use infrastructures::repositories::{
+    totp_recovery_codes::PgTotpRecoveryCodeRepository, users::PgUserRepository,
+};
+use miscellaneous::current_unix_epoch;
+
+use crate::accounts::start_session;
+use crate::errors::AuthError;
+use crate::login_attempts::{record_login_attempt, LoginClient};
+
+/// TOTPチャレンジの有効期間（秒）
+pub const TOTP_CHALLENGE_SECONDS: u64 = 5 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TotpError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("TOTPチャレンジが存在しないか、有効期限が切れています。")]
+    InvalidChallenge,
+    #[error("コードが間違っています。")]
+    InvalidCode,
+    #[error("ユーザー({0})が無効になっています。")]
+    NotActive(Uuid),
+    #[error("2要素認証が有効になっていません。")]
+    NotEnrolled,
+    #[error("確認待ちの共有シークレットが存在しません。")]
+    NotPending,
+    #[error("2要素認証を既に有効にしているため、現在のコード又はリカバリーコードが必要です。")]
+    ReverificationRequired,
+}
+
+/// TOTPの登録結果構造体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollment {
+    /// Base32でエンコードした共有シークレット。
+    pub secret: String,
+    /// 認証アプリに共有シークレットを登録する`otpauth`URI。
+    pub otpauth_uri: String,
+}
+
+/// リカバリーコードを生成して、ハッシュをユーザーに登録する。
+///
+/// # Arguments
+///
+/// * `user` - リカバリーコードを発行するユーザー。
+/// * `tx` - トランザクション。
+///
+/// # Returns
+///
+/// リカバリーコード。
+async fn issue_recovery_codes(
+    user: &User,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<Vec<String>, TotpError> {
+    let codes = generate_recovery_codes();
+    let code_hashes: Vec<String> = codes
+        .iter()
+        .map(|code| recovery_code_hash(user.id().value(), code))
+        .collect();
+    PgTotpRecoveryCodeRepository
+        .replace(user.id(), &code_hashes, tx)
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(codes)
+}
+
+/// TOTPによる2要素認証の登録を開始する。
+///
+/// 共有シークレットを生成して、確認待ちの共有シークレットとしてユーザーに登録する。確認待ちの共有シークレットは、
+/// `confirm_totp`で認証アプリが生成したコードを確認するまで、2要素認証に使用しない。
+/// 既に2要素認証を有効にしている場合は、共有シークレットを置き換えるために、現在の共有シークレットで生成した
+/// コード又はリカバリーコードによる再認証を要求する。
+///
+/// # Arguments
+///
+/// * `user` - 2要素認証を有効にするユーザー。
+/// * `current` - 既に2要素認証を有効にしている場合に、再認証に使用する第2要素。
+/// * `settings` - システム設定。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 共有シークレット及び`otpauth`URI。
+pub async fn enroll_totp(
+    user: &User,
+    current: Option<SecondFactor<'_>>,
+    settings: &Settings,
+    pool: &PgPool,
+) -> anyhow::Result<TotpEnrollment, AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    // 既に2要素認証を有効にしている場合は、現在の第2要素で再認証
+    if let Some(current_secret) = user.totp_secret() {
+        let factor = current.ok_or(TotpError::ReverificationRequired)?;
+        let verified = verify_second_factor(user, current_secret, factor, &mut tx).await?;
+        if !verified {
+            return Err(TotpError::InvalidCode.into());
+        }
+    }
+    let secret = generate_totp_secret();
+    PgUserRepository
+        .set_pending_totp_secret(user.id(), Some(&Secret::new(secret.clone())), &mut tx)
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(TotpEnrollment {
+        otpauth_uri: otpauth_uri(&settings.totp.issuer, user.email_address().value(), &secret),
+        secret,
+    })
+}
+
+/// 確認待ちの共有シークレットを、認証アプリが生成したコードで確認して、2要素認証を有効にする。
+///
+/// コードが正しい場合は、確認待ちの共有シークレットで既存の共有シークレットを置き換えて、リカバリーコードを
+/// 発行する。既存のリカバリーコードは無効にする。
+/// コードが間違っていた場合は、コードを総当たりで推測されないように、確認待ちの共有シークレットを破棄する。
+///
+/// # Arguments
+///
+/// * `user` - 2要素認証を有効にするユーザー。
+/// * `code` - 認証アプリが生成したコード。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// リカバリーコード。
+pub async fn confirm_totp(
+    user: &User,
+    code: &str,
+    pool: &PgPool,
+) -> anyhow::Result<Vec<String>, AuthError> {
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    let pending_secret = PgUserRepository
+        .get_pending_totp_secret(user.id(), &mut tx)
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?
+        .ok_or(TotpError::NotPending)?;
+    let step = verify_totp(
+        pending_secret.expose_secret(),
+        code,
+        current_unix_epoch(),
+        None,
+    );
+    let Some(step) = step else {
+        PgUserRepository
+            .set_pending_totp_secret(user.id(), None, &mut tx)
+            .await
+            .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+        tx.commit()
+            .await
+            .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+        return Err(TotpError::InvalidCode.into());
+    };
+    PgUserRepository
+        .activate_pending_totp_secret(user.id(), step, &mut tx)
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    let recovery_codes = issue_recovery_codes(user, &mut tx).await?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(recovery_codes)
+}
+
+/// リカバリーコードを再発行する。
+///
+/// 既存のリカバリーコードは、使用済みかどうかにかかわらずすべて無効にする。
+///
+/// # Arguments
+///
+/// * `user` - リカバリーコードを再発行するユーザー。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// リカバリーコード。
+pub async fn regenerate_recovery_codes(
+    user: &User,
+    pool: &PgPool,
+) -> anyhow::Result<Vec<String>, AuthError> {
+    if user.totp_secret().is_none() {
+        return Err(TotpError::NotEnrolled.into());
+    }
+
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    let recovery_codes = issue_recovery_codes(user, &mut tx).await?;
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(recovery_codes)
+}
+
+/// TOTPチャレンジを生成して、セッションに保存する。
+///
+/// パスワードで認証したユーザーが2要素認証を有効にしている場合に、セッションを開始する代わりに呼び出す。
+///
+/// # Arguments
+///
+/// * `user` - パスワードで認証したユーザー。
+/// * `session` - セッション。
+///
+/// # Returns
+///
+/// チャレンジトークン。
+pub(crate) fn start_totp_challenge(
+    user: &User,
+    session: &TypedSession,
+) -> anyhow::Result<String, AuthError> {
+    let challenge = TotpChallenge::generate(
+        user.id().value(),
+        current_unix_epoch() + TOTP_CHALLENGE_SECONDS,
+    );
+    session
+        .insert_totp_challenge(&challenge)
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(challenge.token)
+}
+
+/// 2要素認証の第2要素
+#[derive(Debug, Clone, Copy)]
+pub enum SecondFactor<'a> {
+    /// 認証アプリが表示したTOTPのコード。
+    Totp(&'a str),
+    /// リカバリーコード。
+    RecoveryCode(&'a str),
+}
+
+/// 第2要素を検証する。
+///
+/// TOTPのコードは、検証に成功すると時間ステップを記録して、同じ時間ステップ以前のコードを再使用できないようにする。
+/// リカバリーコードは、検証に成功すると使用済みにする。
+///
+/// # Arguments
+///
+/// * `user` - 第2要素を検証するユーザー。
+/// * `secret` - Base32でエンコードしたTOTPの共有シークレット。
+/// * `factor` - 第2要素。
+/// * `tx` - トランザクション。
+///
+/// # Returns
+///
+/// 第2要素が正しい場合は`true`。
+async fn verify_second_factor(
+    user: &User,
+    secret: &Secret<String>,
+    factor: SecondFactor<'_>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<bool, TotpError> {
+    match factor {
+        SecondFactor::Totp(code) => {
+            let last_step = PgUserRepository
+                .get_totp_last_step(user.id(), tx)
+                .await
+                .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+            match verify_totp(
+                secret.expose_secret(),
+                code,
+                current_unix_epoch(),
+                last_step,
+            ) {
+                // 同じコードを同時に検証した場合は、先に時間ステップを記録した検証のみ成功
+                Some(step) => PgUserRepository
+                    .record_totp_step(user.id(), step, tx)
+                    .await
+                    .map_err(|e| TotpError::UnexpectedError(e.into())),
+                None => Ok(false),
+            }
+        }
+        SecondFactor::RecoveryCode(code) => PgTotpRecoveryCodeRepository
+            .consume(user.id(), &recovery_code_hash(user.id().value(), code), tx)
+            .await
+            .map_err(|e| TotpError::UnexpectedError(e.into())),
+    }
+}
+
+/// TOTPのコード又はリカバリーコードを検証して、ログインを完了する。
+///
+/// セッションに保存したTOTPチャレンジとクライアントが送信したチャレンジトークンを照合して、第2要素を検証する。
+/// リカバリーコードは、検証に成功すると使用済みにして、再度使用できないようにする。
+/// TOTPチャレンジは1回しか使用できないため、第2要素が間違っていた場合は、パスワードによる認証からやり直す
+/// 必要がある。第2要素が間違っていた場合は、失敗したログイン試行を記録する。
+///
+/// # Arguments
+///
+/// * `challenge_token` - クライアントが送信したチャレンジトークン。
+/// * `factor` - 第2要素。
+/// * `client` - ログインしたクライアントの情報。
+/// * `settings` - システム設定。
+/// * `session` - セッション。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// セッションデータ。
+pub async fn finish_totp_login(
+    challenge_token: &str,
+    factor: SecondFactor<'_>,
+    client: LoginClient,
+    settings: &Settings,
+    session: &TypedSession,
+    pool: &PgPool,
+) -> anyhow::Result<SessionData, AuthError> {
+    let attempted_at = OffsetDateTime::now_utc();
+    let challenge = session
+        .take_totp_challenge()
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?
+        .ok_or(TotpError::InvalidChallenge)?;
+    if challenge.token != challenge_token || challenge.expiration <= current_unix_epoch() {
+        return Err(TotpError::InvalidChallenge.into());
+    }
+
+    // トランザクションを開始
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+    // パスワードで認証したユーザーが、2要素認証を有効にしたままか確認
+    let user = PgUserRepository
+        .get_by_id(UserId::new(challenge.user_id), &mut tx)
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?
+        .ok_or(TotpError::InvalidChallenge)?;
+    let secret = user
+        .totp_secret()
+        .ok_or(TotpError::InvalidChallenge)?
+        .clone();
+    if !user.is_active() {
+        return Err(TotpError::NotActive(user.id().value()).into());
+    }
+    // 第2要素を検証
+    let verified = verify_second_factor(&user, &secret, factor, &mut tx).await?;
+    if !verified {
+        let attempt = client.to_login_attempt(user.id(), false, attempted_at);
+        record_login_attempt(&attempt, &mut tx).await?;
+        tx.commit()
+            .await
+            .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+        return Err(TotpError::InvalidCode.into());
+    }
+
+    // セッションを開始
+    let session_data =
+        start_session(&user, client, attempted_at, settings, session, &mut tx).await?;
+
+    // トランザクションをコミット
+    tx.commit()
+        .await
+        .map_err(|e| TotpError::UnexpectedError(e.into()))?;
+
+    Ok(session_data)
+}
@@ -0,0 +1,56 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// トランザクション内で実行する処理が返却するフューチャー。
+pub type TransactionFuture<'c, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + 'c>>;
+
+/// トランザクション内で処理を実行する。
+///
+/// トランザクションを開始して処理を実行した後、処理が成功した場合はトランザクションをコミットして、
+/// 処理が失敗した場合はトランザクションをロールバックする。
+///
+/// # Arguments
+///
+/// * `pool` - データベースコネクションプール。
+/// * `unexpected_error` - トランザクションの開始又はコミットに失敗したときのエラーを、
+///   処理が返却するエラーに変換する関数。
+/// * `f` - トランザクション内で実行する処理（`|tx| Box::pin(async move { ... })`の形式で指定）。
+///
+/// # Returns
+///
+/// 処理の戻り値。
+pub async fn with_transaction<'a, T, E, U, F>(
+    pool: &'a PgPool,
+    unexpected_error: U,
+    f: F,
+) -> Result<T, E>
+where
+    U: FnOnce(anyhow::Error) -> E,
+    F: for<'c> FnOnce(&'c mut Transaction<'a, Postgres>) -> TransactionFuture<'c, T, E>,
+{
+    // トランザクションを開始
+    let mut tx: Transaction<'a, Postgres> = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Err(unexpected_error(e.into())),
+    };
+
+    match f(&mut tx).await {
+        // トランザクションをコミット
+        Ok(value) => {
+            tx.commit().await.map_err(|e| unexpected_error(e.into()))?;
+            Ok(value)
+        }
+        // トランザクションをロールバックして、ロールバックに失敗した場合も処理が返却したエラーを返却
+        Err(e) => {
+            if let Err(rollback_error) = tx.rollback().await {
+                tracing::error!(
+                    "トランザクションをロールバックできませんでした。{:?}",
+                    rollback_error
+                );
+            }
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use domains::models::users::{ProfileVisibility, User, UserId};
+use infrastructures::repositories::users::{PgUserRepository, UserRepositoryError};
+
+use crate::errors::AuthError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UserError {
+    #[error(transparent)]
+    UnexpectedError(anyhow::Error),
+    #[error("ユーザー({0})が存在しません。")]
+    NotFound(Uuid),
+    #[error("ユーザー({0})は他のテナントに所属しています。")]
+    TenantMismatch(Uuid),
+}
+
+impl From<UserRepositoryError> for UserError {
+    fn from(e: UserRepositoryError) -> Self {
+        match e {
+            UserRepositoryError::NotFoundError(id) => Self::NotFound(id),
+            UserRepositoryError::TenantMismatch(id) => Self::TenantMismatch(id),
+            _ => Self::UnexpectedError(e.into()),
+        }
+    }
+}
+
+/// ユーザープロフィール
+///
+/// 閲覧者に公開しないプロフィール情報は`None`として、応答に含めない。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub user_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_logged_in: Option<OffsetDateTime>,
+}
+
+impl UserProfile {
+    /// 閲覧者に公開するプロフィール情報で、ユーザープロフィールを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - プロフィールを閲覧されるユーザー。
+    /// * `viewer` - 閲覧者。閲覧者が認証されていない場合は`None`。
+    ///
+    /// # Returns
+    ///
+    /// ユーザープロフィール。
+    pub fn new(user: &User, viewer: Option<&User>) -> Self {
+        let is_authenticated = viewer.is_some();
+        let is_owner = viewer.is_some_and(|viewer| viewer.id().value() == user.id().value());
+        let visibility = user.profile_visibility();
+
+        Self {
+            id: user.id().value(),
+            user_name: user.user_name().value().to_owned(),
+            email_address: visibility
+                .email_address
+                .is_visible_to(is_authenticated, is_owner)
+                .then(|| user.email_address().value().to_owned()),
+            last_logged_in: visibility
+                .last_logged_in
+                .is_visible_to(is_authenticated, is_owner)
+                .then(|| *user.last_logged_in())
+                .flatten(),
+        }
+    }
+}
+
+/// ユーザープロフィールを取得する。
+///
+/// 閲覧者が所属するテナントのユーザーのプロフィールのみを取得できる。他のテナントのユーザーを取得できない
+/// ように、テナントを特定できない場合でも、テナントIDで絞り込んで取得する。
+///
+/// # Arguments
+///
+/// * `user_id` - プロフィールを取得するユーザーのユーザーID。
+/// * `viewer` - 閲覧者。閲覧者が認証されていない場合は`None`。
+/// * `tenant_id` - 閲覧者が所属するテナントのテナントID。閲覧者が認証されていない場合は既定のテナントID。
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 閲覧者に公開するプロフィール情報のみを含んだユーザープロフィール。
+pub async fn get_user_profile(
+    user_id: UserId,
+    viewer: Option<&User>,
+    tenant_id: Uuid,
+    pool: &PgPool,
+) -> anyhow::Result<UserProfile, AuthError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| UserError::UnexpectedError(e.into()))?;
+    let user = PgUserRepository
+        .get_by_id_in_tenant(user_id.clone(), tenant_id, &mut tx)
+        .await
+        .map_err(UserError::from)?
+        .ok_or_else(|| UserError::NotFound(user_id.value()))?;
+    tx.commit()
+        .await
+        .map_err(|e| UserError::UnexpectedError(e.into()))?;
+    // 無効なユーザーのプロフィールは、存在しないユーザーと同様に公開しない
+    if !user.is_active() {
+        return Err(UserError::NotFound(user_id.value()).into());
+    }
+
+    Ok(UserProfile::new(&user, viewer))
+}
+
+/// ユーザーのプロフィールの公開設定を変更する。
+///
+/// # Arguments
+///
+/// * `user` - 公開設定を変更するユーザー。
+/// * `profile_visibility` - プロフィールの公開設定。
+/// * `pool` - データベースコネクションプール。
+pub async fn set_profile_visibility(
+    user: &User,
+    profile_visibility: ProfileVisibility,
+    pool: &PgPool,
+) -> anyhow::Result<(), AuthError> {
+    let mut user = user.clone();
+    user.set_profile_visibility(profile_visibility);
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| UserError::UnexpectedError(e.into()))?;
+    PgUserRepository
+        .update(&user, &mut tx)
+        .await
+        .map_err(UserError::from)?;
+    tx.commit()
+        .await
+        .map_err(|e| UserError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! アカウントのライフサイクルのイベントを、外部のサービスにWebhookで通知する。
+//!
+//! イベントは、Webhook設定のURLにJSONでPOSTして、ペイロードをHMAC-SHA256で署名した値を
+//! `X-Webhook-Signature`ヘッダーに`sha256=<16進数>`の形式で指定する。受信したサービスは、同じ秘密鍵で
+//! リクエストボディの署名を計算して、ヘッダーの署名と比較することで、送信元とペイロードを検証できる。
+//!
+//! リクエストの処理をWebhookの応答で遅らせないように、イベントはバックグラウンドタスクで送信する。送信に
+//! 失敗した場合は、エラーをログに記録して、設定した回数まで再送する。
+use std::time::Duration;
+
+use actix_web::rt;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use configurations::{tokens::RedactedToken, WebhookSettings};
+use miscellaneous::current_unix_epoch;
+
+/// ペイロードの署名を指定するヘッダー
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Webhookで通知するイベントの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WebhookEventType {
+    /// ユーザーがサインアップした。
+    #[serde(rename = "user.signed_up")]
+    UserSignedUp,
+    /// ユーザーがパスワードを変更した。
+    #[serde(rename = "user.password_changed")]
+    PasswordChanged,
+    /// ユーザーがアカウントを削除した。
+    #[serde(rename = "user.deleted")]
+    AccountDeleted,
+    /// ユーザーがパスワードのリセットを要求した。
+    #[serde(rename = "user.password_reset_requested")]
+    PasswordResetRequested,
+}
+
+/// Webhookで通知するイベント
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEvent {
+    /// イベントID。再送したイベントを受信したサービスが重複を除外できるように、再送しても変わらない。
+    pub id: Uuid,
+    /// イベントの種類。
+    #[serde(rename = "type")]
+    pub event_type: WebhookEventType,
+    /// イベントが発生した日時（UNIXエポック秒）。
+    pub occurred_at: u64,
+    /// イベントが発生したユーザーのユーザーID。
+    pub user_id: Uuid,
+    /// パスワードリセットトークンを通知するEメールアドレス。パスワードのリセットを要求したイベントのみ。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+    /// ユーザーに通知するパスワードリセットトークン。パスワードのリセットを要求したイベントのみ。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_reset_token: Option<RedactedToken>,
+}
+
+impl WebhookEvent {
+    /// 現在日時に発生したイベントを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - イベントの種類。
+    /// * `user_id` - イベントが発生したユーザーのユーザーID。
+    ///
+    /// # Returns
+    ///
+    /// イベント。
+    pub fn new(event_type: WebhookEventType, user_id: Uuid) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type,
+            occurred_at: current_unix_epoch(),
+            user_id,
+            email_address: None,
+            password_reset_token: None,
+        }
+    }
+
+    /// ユーザーがパスワードのリセットを要求したイベントを構築する。
+    ///
+    /// Webhookを受信したサービスは、パスワードリセットトークンをメールなどでユーザーに通知する。
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - パスワードのリセットを要求したユーザーのユーザーID。
+    /// * `email_address` - パスワードリセットトークンを通知するEメールアドレス。
+    /// * `token` - パスワードリセットトークン。
+    ///
+    /// # Returns
+    ///
+    /// イベント。
+    pub fn password_reset_requested(
+        user_id: Uuid,
+        email_address: &str,
+        token: &Secret<String>,
+    ) -> Self {
+        Self {
+            email_address: Some(email_address.to_owned()),
+            password_reset_token: Some(RedactedToken::new(token.expose_secret().as_str())),
+            ..Self::new(WebhookEventType::PasswordResetRequested, user_id)
+        }
+    }
+}
+
+/// Webhookのペイロードの署名を計算する。
+///
+/// # Arguments
+///
+/// * `secret` - 署名する秘密鍵。
+/// * `payload` - ペイロード。
+///
+/// # Returns
+///
+/// `sha256=`に続けて、ペイロードのHMAC-SHA256を16進数で表現した署名。
+pub fn webhook_signature(secret: &Secret<String>, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMACは任意の長さの鍵を受け付けます。");
+    mac.update(payload);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    format!("sha256={}", signature)
+}
+
+/// Webhookの送信先
+#[derive(Debug, Clone)]
+struct WebhookEndpoint {
+    /// イベントを送信するURL。
+    url: String,
+    /// ペイロードに署名する秘密鍵。
+    secret: Secret<String>,
+    /// 送信を試行する回数。
+    max_attempts: u32,
+    /// 送信に失敗してから再送するまでの間隔。
+    retry_interval: Duration,
+}
+
+/// Webhookディスパッチャー
+///
+/// ワーカー間でHTTPクライアントを共有するため、Webアプリの起動前に構築する。
+pub struct WebhookDispatcher {
+    /// HTTPクライアント。
+    client: reqwest::Client,
+    /// 送信先。Webhookが設定されていない場合は`None`。
+    endpoint: Option<WebhookEndpoint>,
+}
+
+impl WebhookDispatcher {
+    /// Webhookディスパッチャーを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Webhook設定。
+    ///
+    /// # Returns
+    ///
+    /// Webhookディスパッチャー。URLを設定して秘密鍵を設定していない場合や、HTTPクライアントを構築できない
+    /// 場合はエラー。
+    pub fn new(settings: &WebhookSettings) -> anyhow::Result<Self> {
+        settings.verify()?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(settings.timeout()))
+            .build()?;
+        let endpoint = match (&settings.url, &settings.secret) {
+            (Some(url), Some(secret)) => Some(WebhookEndpoint {
+                url: url.clone(),
+                secret: secret.clone(),
+                max_attempts: settings.max_attempts.max(1),
+                retry_interval: Duration::from_secs(settings.retry_interval()),
+            }),
+            _ => None,
+        };
+
+        Ok(Self { client, endpoint })
+    }
+
+    /// イベントを送信するバックグラウンドタスクを起動する。
+    ///
+    /// Webhookが設定されていない場合は、何もしない。
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - イベントの種類。
+    /// * `user_id` - イベントが発生したユーザーのユーザーID。
+    pub fn dispatch(&self, event_type: WebhookEventType, user_id: Uuid) {
+        self.dispatch_event(WebhookEvent::new(event_type, user_id));
+    }
+
+    /// 構築したイベントを送信するバックグラウンドタスクを起動する。
+    ///
+    /// Webhookが設定されていない場合は、何もしない。
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - イベント。
+    pub fn dispatch_event(&self, event: WebhookEvent) {
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => return,
+        };
+        let client = self.client.clone();
+        rt::spawn(async move { deliver(&client, &endpoint, &event).await });
+    }
+}
+
+/// イベントを送信して、失敗した場合は送信を試行する回数まで再送する。
+///
+/// # Arguments
+///
+/// * `client` - HTTPクライアント。
+/// * `endpoint` - 送信先。
+/// * `event` - イベント。
+///
+/// # Returns
+///
+/// 送信に成功した場合は`true`。
+async fn deliver(
+    client: &reqwest::Client,
+    endpoint: &WebhookEndpoint,
+    event: &WebhookEvent,
+) -> bool {
+    let payload = match serde_json::to_vec(event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Webhookのペイロードを生成できませんでした。{}", e);
+            return false;
+        }
+    };
+    let signature = webhook_signature(&endpoint.secret, &payload);
+    for attempt in 1..=endpoint.max_attempts {
+        let result = client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_SIGNATURE_HEADER, &signature)
+            .body(payload.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => return true,
+            Err(e) => tracing::warn!(
+                event_id = %event.id,
+                attempt,
+                "Webhookの送信に失敗しました。{}",
+                e
+            ),
+        }
+        if attempt < endpoint.max_attempts {
+            rt::time::sleep(endpoint.retry_interval).await;
+        }
+    }
+    tracing::error!(
+        event_id = %event.id,
+        "Webhookの送信を{}回試行しましたが、すべて失敗しました。",
+        endpoint.max_attempts
+    );
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 署名が、ペイロードのHMAC-SHA256を16進数で表現した値であることを確認するテスト
+    #[test]
+    fn webhook_signature_is_hex_hmac() {
+        let secret = Secret::new("key".to_owned());
+        // HMAC-SHA256の既知のテストベクター
+        assert_eq!(
+            webhook_signature(&secret, b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        let other = Secret::new("other".to_owned());
+        assert_ne!(
+            webhook_signature(&secret, b"payload"),
+            webhook_signature(&other, b"payload")
+        );
+    }
+
+    /// イベントが、受信したサービスに公開する形式のJSONに変換されることを確認するテスト
+    #[test]
+    fn webhook_event_serializes_to_json() {
+        let user_id = Uuid::new_v4();
+        let event = WebhookEvent::new(WebhookEventType::PasswordChanged, user_id);
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["id"], event.id.to_string());
+        assert_eq!(value["type"], "user.password_changed");
+        assert_eq!(value["occurredAt"], event.occurred_at);
+        assert_eq!(value["userId"], user_id.to_string());
+        assert!(value.get("emailAddress").is_none());
+        assert!(value.get("passwordResetToken").is_none());
+    }
+
+    /// パスワードのリセットを要求したイベントに、Eメールアドレスとパスワードリセットトークンが含まれ、
+    /// デバッグ出力ではトークンが隠されることを確認するテスト
+    #[test]
+    fn password_reset_requested_event_contains_token() {
+        let user_id = Uuid::new_v4();
+        let token = Secret::new("reset-token-value".to_owned());
+        let event = WebhookEvent::password_reset_requested(user_id, "foo@example.com", &token);
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "user.password_reset_requested");
+        assert_eq!(value["userId"], user_id.to_string());
+        assert_eq!(value["emailAddress"], "foo@example.com");
+        assert_eq!(value["passwordResetToken"], "reset-token-value");
+        assert!(!format!("{:?}", event).contains("reset-token-value"));
+    }
+
+    /// Webhookが設定されていない場合は、イベントを送信しないことを確認するテスト
+    #[actix_web::test]
+    async fn dispatcher_without_url_does_not_send() {
+        let dispatcher = WebhookDispatcher::new(&WebhookSettings {
+            url: None,
+            secret: None,
+            max_attempts: 3,
+            retry_interval: actix_web::cookie::time::Duration::seconds(1),
+            timeout: actix_web::cookie::time::Duration::seconds(5),
+        })
+        .unwrap();
+        assert!(dispatcher.endpoint.is_none());
+        dispatcher.dispatch(WebhookEventType::UserSignedUp, Uuid::new_v4());
+    }
+
+    /// 送信先に接続できない場合は、送信を試行する回数まで再送して失敗することを確認するテスト
+    #[actix_web::test]
+    async fn deliver_gives_up_after_max_attempts() {
+        // 接続を拒否するように、リッスンしていないポートを送信先にする
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let endpoint = WebhookEndpoint {
+            url: format!("http://127.0.0.1:{}/hooks", port),
+            secret: Secret::new("secret".to_owned()),
+            max_attempts: 2,
+            retry_interval: Duration::from_millis(10),
+        };
+        let event = WebhookEvent::new(WebhookEventType::AccountDeleted, Uuid::new_v4());
+        assert!(!deliver(&reqwest::Client::new(), &endpoint, &event).await);
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use redis::{aio::MultiplexedConnection, FromRedisValue};
+use secrecy::ExposeSecret;
+
+use configurations::SessionStoreSettings;
+use middlewares::idempotency::IdempotencyStore;
+
+use crate::session_stores::prefixed_key;
+
+/// Redis冪等ストア
+///
+/// 冪等キーとリクエストの処理結果を、有効期限を付けてRedisに記録する。
+#[derive(Clone)]
+pub struct RedisIdempotencyStore {
+    /// Redisとのコネクション。
+    connection: MultiplexedConnection,
+    /// Redisに記録するキーに付与する接頭辞。
+    key_prefix: String,
+    /// Redisのコマンドのタイムアウト。
+    timeout: Duration,
+}
+
+impl RedisIdempotencyStore {
+    /// Redisに接続して、Redis冪等ストアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - セッションストア設定。
+    ///
+    /// # Returns
+    ///
+    /// Redis冪等ストアインスタンス。
+    pub async fn connect(settings: &SessionStoreSettings) -> anyhow::Result<Self> {
+        let client = redis::Client::open(settings.uri.expose_secret().as_str())
+            .context("RedisのURIが不正です。")?;
+        let connection = tokio::time::timeout(
+            settings.connect_timeout(),
+            client.get_multiplexed_tokio_connection(),
+        )
+        .await
+        .map_err(|_| anyhow!("Redisへの接続がタイムアウトしました。"))?
+        .context("Redisに接続できません。")?;
+
+        Ok(Self {
+            connection,
+            key_prefix: settings.key_prefix.clone(),
+            timeout: settings.command_timeout(),
+        })
+    }
+
+    /// Redisに記録するキーを返却する。
+    fn redis_key(&self, key: &str) -> String {
+        prefixed_key(&self.key_prefix, key)
+    }
+
+    /// タイムアウトを設定して、Redisのコマンドを実行する。
+    async fn query<T: FromRedisValue>(&self, cmd: &redis::Cmd) -> anyhow::Result<T> {
+        let mut connection = self.connection.clone();
+        tokio::time::timeout(self.timeout, cmd.query_async(&mut connection))
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "冪等ストアの操作が{}ミリ秒以内に完了しませんでした。",
+                    self.timeout.as_millis()
+                )
+            })?
+            .map_err(|e| e.into())
+    }
+}
+
+/// 有効期限の秒数を返却する。
+///
+/// Redisは0秒の有効期限を受け付けないため、1秒以上とする。
+fn ttl_seconds(ttl: Duration) -> u64 {
+    ttl.as_secs().max(1)
+}
+
+#[async_trait::async_trait(?Send)]
+impl IdempotencyStore for RedisIdempotencyStore {
+    async fn insert_if_absent(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+    ) -> anyhow::Result<bool> {
+        let result: Option<String> = self
+            .query(
+                redis::cmd("SET")
+                    .arg(self.redis_key(key))
+                    .arg(value)
+                    .arg("NX")
+                    .arg("EX")
+                    .arg(ttl_seconds(ttl)),
+            )
+            .await?;
+
+        Ok(result.is_some())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.query(redis::cmd("GET").arg(self.redis_key(key))).await
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> anyhow::Result<()> {
+        self.query(
+            redis::cmd("SET")
+                .arg(self.redis_key(key))
+                .arg(value)
+                .arg("EX")
+                .arg(ttl_seconds(ttl)),
+        )
+        .await
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.query(redis::cmd("DEL").arg(self.redis_key(key))).await
+    }
+}
+
+/// メモリ内冪等ストア
+///
+/// 冪等キーとリクエストの処理結果をプロセスのメモリに記録する冪等ストアで、Redisを用意せずに
+/// Webアプリを構築するテストで使用する。
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryIdempotencyStore {
+    /// キーをキーに、値と有効期限を記録するマップ。
+    values: Arc<RwLock<HashMap<String, (String, Instant)>>>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// 有効期限を過ぎた値を削除する。
+    fn remove_expired_values(values: &mut HashMap<String, (String, Instant)>) {
+        let now = Instant::now();
+        values.retain(|_, (_, expired_at)| now < *expired_at);
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn insert_if_absent(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+    ) -> anyhow::Result<bool> {
+        let mut values = self.values.write().map_err(|e| anyhow!("{}", e))?;
+        Self::remove_expired_values(&mut values);
+        if values.contains_key(key) {
+            return Ok(false);
+        }
+        values.insert(key.to_owned(), (value, Instant::now() + ttl));
+
+        Ok(true)
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let mut values = self.values.write().map_err(|e| anyhow!("{}", e))?;
+        Self::remove_expired_values(&mut values);
+
+        Ok(values.get(key).map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) -> anyhow::Result<()> {
+        self.values
+            .write()
+            .map_err(|e| anyhow!("{}", e))?
+            .insert(key.to_owned(), (value, Instant::now() + ttl));
+
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.values
+            .write()
+            .map_err(|e| anyhow!("{}", e))?
+            .remove(key);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_idempotency_store() {
+        let store = InMemoryIdempotencyStore::default();
+        let ttl = Duration::from_secs(60);
+        // キーが記録されていない場合のみ記録
+        assert!(store
+            .insert_if_absent("foo", "bar".to_owned(), ttl)
+            .await
+            .unwrap());
+        assert!(!store
+            .insert_if_absent("foo", "baz".to_owned(), ttl)
+            .await
+            .unwrap());
+        assert_eq!(store.get("foo").await.unwrap().as_deref(), Some("bar"));
+        // 記録されている値を上書き
+        store.set("foo", "qux".to_owned(), ttl).await.unwrap();
+        assert_eq!(store.get("foo").await.unwrap().as_deref(), Some("qux"));
+        // キーを削除した後は、再び記録できる
+        store.remove("foo").await.unwrap();
+        assert!(store.get("foo").await.unwrap().is_none());
+        assert!(store
+            .insert_if_absent("foo", "bar".to_owned(), ttl)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_idempotency_store_expired() {
+        let store = InMemoryIdempotencyStore::default();
+        assert!(store
+            .insert_if_absent("foo", "bar".to_owned(), Duration::ZERO)
+            .await
+            .unwrap());
+        assert!(store.get("foo").await.unwrap().is_none());
+        assert!(store
+            .insert_if_absent("foo", "baz".to_owned(), Duration::from_secs(60))
+            .await
+            .unwrap());
+    }
+}
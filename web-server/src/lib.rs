@@ -1 +1,4 @@
+pub mod idempotency_stores;
+pub mod refresh_token_cleanup;
+pub mod session_stores;
 pub mod startup;
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+
+use infrastructures::repositories::refresh_tokens::{
+    PgRefreshTokenRepository, RefreshTokenRepositoryError,
+};
+
+/// 1回のクエリで削除するリフレッシュトークンの最大数
+const REFRESH_TOKEN_CLEANUP_BATCH_SIZE: i64 = 1000;
+
+/// 有効期限が切れたリフレッシュトークンを、すべて削除するまでバッチ単位で削除する。
+///
+/// バッチごとにトランザクションをコミットして、テーブルを長時間ロックしないようにする。
+///
+/// # Arguments
+///
+/// * `pool` - データベースコネクションプール。
+///
+/// # Returns
+///
+/// 削除したリフレッシュトークンの数。
+pub async fn delete_expired_refresh_tokens(
+    pool: &PgPool,
+) -> Result<u64, RefreshTokenRepositoryError> {
+    let mut total = 0;
+    loop {
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        let deleted = PgRefreshTokenRepository
+            .delete_expired(REFRESH_TOKEN_CLEANUP_BATCH_SIZE, &mut tx)
+            .await?;
+        tx.commit()
+            .await
+            .map_err(|e| RefreshTokenRepositoryError::UnexpectedError(e.into()))?;
+        total += deleted;
+        // バッチの最大数より少なければ、有効期限が切れたリフレッシュトークンは残っていない
+        if deleted < REFRESH_TOKEN_CLEANUP_BATCH_SIZE as u64 {
+            return Ok(total);
+        }
+    }
+}
+
+/// 有効期限が切れたリフレッシュトークンを、定期的に削除するバックグラウンドタスクを起動する。
+///
+/// Webアプリの起動を遅らせないように、最初の削除は間隔が経過してから実施する。削除に失敗した場合は、
+/// エラーをログに記録して、次の間隔で再度削除する。
+///
+/// # Arguments
+///
+/// * `pool` - データベースコネクションプール。
+/// * `period` - 削除する間隔。
+pub fn spawn_refresh_token_cleanup(pool: PgPool, period: Duration) {
+    tokio::spawn(async move {
+        let mut interval = interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match delete_expired_refresh_tokens(&pool).await {
+                Ok(count) => tracing::info!(
+                    count,
+                    "有効期限が切れたリフレッシュトークンを削除しました。"
+                ),
+                Err(e) => tracing::error!(
+                    "有効期限が切れたリフレッシュトークンを削除できませんでした。{}",
+                    e
+                ),
+            }
+        }
+    });
+}
@@ -0,0 +1,1061 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use anyhow::{anyhow, Context};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use redis::{aio::ConnectionManager, Value};
+use secrecy::{ExposeSecret, Secret};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use configurations::session::{session_key_mac, verify_session_key_mac, SESSION_ID_STATE_KEY};
+use configurations::SessionStoreSettings;
+use usecases::sessions::SessionPurger;
+
+/// セッションの状態
+type SessionState = HashMap<String, String>;
+
+/// セッションキーの長さ
+const SESSION_KEY_LEN: usize = 64;
+
+/// セッションストアに記録するキーを返却する。
+///
+/// 同じセッションストアを複数の環境で共有してもキーが衝突しないように、キーに接頭辞を付与する。
+///
+/// # Arguments
+///
+/// * `key_prefix` - キーに付与する接頭辞。
+/// * `key` - セッションキーや冪等キーなどのキー。
+///
+/// # Returns
+///
+/// 接頭辞を付与したキー。
+pub fn prefixed_key(key_prefix: &str, key: &str) -> String {
+    format!("{}{}", key_prefix, key)
+}
+
+/// 新しいセッションキーを生成する。
+///
+/// # Returns
+///
+/// セッションキー。
+fn generate_session_key() -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(SESSION_KEY_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// セッションIDからセッションキーを引く索引のキーを返却する。
+///
+/// セッションキーは英数字のみで構成されるため、コロンを含む索引のキーとは衝突しない。
+///
+/// # Arguments
+///
+/// * `session_id` - セッションID。
+///
+/// # Returns
+///
+/// 索引のキー。
+fn session_index_key(session_id: Uuid) -> String {
+    format!("session_index:{}", session_id)
+}
+
+/// セッションの状態に記録されたセッションIDを返却する。
+///
+/// # Arguments
+///
+/// * `session_state` - セッションの状態。
+///
+/// # Returns
+///
+/// セッションID。ログインしていないセッションなど、セッションIDが記録されていない場合は`None`。
+fn indexed_session_id(session_state: &SessionState) -> Option<Uuid> {
+    session_state
+        .get(SESSION_ID_STATE_KEY)
+        .and_then(|value| serde_json::from_str(value).ok())
+}
+
+/// 文字列をセッションキーに変換する。
+fn to_session_key(session_key: String) -> anyhow::Result<SessionKey> {
+    session_key.try_into().map_err(|e| anyhow!("{}", e))
+}
+
+/// セッションキーを指定して記録できるセッションストア
+///
+/// セッションキー検証ストアが、生成したセッションキーの検証コードを含めたセッションの状態を、1回の書き込みで
+/// 記録するために使用する。
+///
+/// セッションの状態にセッションIDが記録されている場合は、管理者がセッションを失効させたときにセッションを
+/// 削除できるように、セッションIDからセッションキーを引く索引も記録する。
+#[async_trait::async_trait(?Send)]
+pub trait KeyedSessionStore: SessionStore {
+    /// 指定したセッションキーのセッションが存在しない場合に、セッションの状態を記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_key` - セッションキー。
+    /// * `session_state` - セッションの状態。
+    /// * `ttl` - セッションの有効期間。
+    ///
+    /// # Returns
+    ///
+    /// 記録した場合は`true`、既にセッションが存在する場合は`false`。
+    async fn insert_if_absent(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool>;
+
+    /// 指定したセッションキーのセッションが存在する場合に、セッションの状態を更新する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_key` - セッションキー。
+    /// * `session_state` - セッションの状態。
+    /// * `ttl` - セッションの有効期間。
+    ///
+    /// # Returns
+    ///
+    /// 更新した場合は`true`、セッションが存在しないか有効期限が切れている場合は`false`。
+    async fn update_if_present(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool>;
+
+    /// セッションIDで特定したセッションを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - セッションID。
+    ///
+    /// # Returns
+    ///
+    /// セッションを削除した場合は`true`、セッションが存在しない場合は`false`。
+    async fn delete_by_session_id(&self, session_id: Uuid) -> anyhow::Result<bool>;
+}
+
+/// セッションキーを生成して、セッションの状態を新しいセッションとして記録する。
+///
+/// 既存のセッションキーと重複した場合は、セッションキーを生成し直す。
+async fn save_with_new_key<S, F>(
+    store: &S,
+    session_state: &SessionState,
+    ttl: &Duration,
+    bind: F,
+) -> anyhow::Result<SessionKey>
+where
+    S: KeyedSessionStore + ?Sized,
+    F: Fn(&str, &SessionState) -> SessionState,
+{
+    loop {
+        let session_key = generate_session_key();
+        let state = bind(&session_key, session_state);
+        if store.insert_if_absent(&session_key, &state, ttl).await? {
+            return to_session_key(session_key);
+        }
+    }
+}
+
+/// Redisセッションストア
+///
+/// セッションの状態をJSONにシリアライズして、接頭辞を付与したセッションキーをキーに、有効期限を付けてRedisに
+/// 記録する。actix-sessionのRedisセッションストアと同じ形式で記録するが、セッションキーを指定して記録できる。
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    /// Redisとのコネクション。
+    ///
+    /// Redisがコネクションを切断した場合は、次のコマンドで再接続する。
+    connection: ConnectionManager,
+    /// セッションキーに付与する接頭辞。
+    key_prefix: String,
+}
+
+impl RedisSessionStore {
+    /// Redisに接続して、Redisセッションストアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - セッションストア設定。
+    ///
+    /// # Returns
+    ///
+    /// Redisセッションストアインスタンス。
+    pub async fn connect(settings: &SessionStoreSettings) -> anyhow::Result<Self> {
+        let client = redis::Client::open(settings.uri.expose_secret().as_str())
+            .context("RedisのURIが不正です。")?;
+        let connection =
+            tokio::time::timeout(settings.connect_timeout(), ConnectionManager::new(client))
+                .await
+                .map_err(|_| anyhow!("Redisへの接続がタイムアウトしました。"))?
+                .context("Redisに接続できません。")?;
+
+        Ok(Self {
+            connection,
+            key_prefix: settings.key_prefix.clone(),
+        })
+    }
+
+    /// Redisに記録するキーを返却する。
+    fn redis_key(&self, session_key: &str) -> String {
+        prefixed_key(&self.key_prefix, session_key)
+    }
+
+    /// 条件を指定して、セッションの状態をRedisに記録する。
+    ///
+    /// セッションの状態を記録して、セッションIDが記録されている場合は、セッションと同じ有効期限で索引も記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `session_key` - セッションキー。
+    /// * `session_state` - セッションの状態。
+    /// * `ttl` - セッションの有効期間。
+    /// * `condition` - `SET`コマンドの条件（`NX`又は`XX`）。
+    ///
+    /// # Returns
+    ///
+    /// 条件を満たして記録した場合は`true`。
+    async fn set(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+        condition: &str,
+    ) -> anyhow::Result<bool> {
+        let body = serde_json::to_string(session_state)?;
+        // Redisは0秒以下の有効期限を受け付けないため、1秒以上とする
+        let ttl = ttl.whole_seconds().max(1);
+        let value: Value = redis::cmd("SET")
+            .arg(self.redis_key(session_key))
+            .arg(body)
+            .arg(condition)
+            .arg("EX")
+            .arg(ttl)
+            .query_async(&mut self.connection.clone())
+            .await?;
+        if value == Value::Nil {
+            return Ok(false);
+        }
+        if let Some(session_id) = indexed_session_id(session_state) {
+            redis::cmd("SET")
+                .arg(self.redis_key(&session_index_key(session_id)))
+                .arg(session_key)
+                .arg("EX")
+                .arg(ttl)
+                .query_async::<_, ()>(&mut self.connection.clone())
+                .await?;
+        }
+
+        Ok(true)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl KeyedSessionStore for RedisSessionStore {
+    async fn insert_if_absent(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        self.set(session_key, session_state, ttl, "NX").await
+    }
+
+    async fn update_if_present(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        self.set(session_key, session_state, ttl, "XX").await
+    }
+
+    async fn delete_by_session_id(&self, session_id: Uuid) -> anyhow::Result<bool> {
+        let index_key = self.redis_key(&session_index_key(session_id));
+        let session_key: Option<String> = redis::cmd("GET")
+            .arg(&index_key)
+            .query_async(&mut self.connection.clone())
+            .await?;
+        let session_key = match session_key {
+            Some(session_key) => session_key,
+            None => return Ok(false),
+        };
+        let (deleted,): (u32,) = redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(self.redis_key(&session_key))
+            .cmd("DEL")
+            .arg(&index_key)
+            .ignore()
+            .query_async(&mut self.connection.clone())
+            .await?;
+
+        Ok(0 < deleted)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for RedisSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let value: Option<String> = redis::cmd("GET")
+            .arg(self.redis_key(session_key.as_ref()))
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(|e| LoadError::Other(e.into()))?;
+
+        value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        save_with_new_key(self, &session_state, ttl, |_, state| state.clone())
+            .await
+            .map_err(SaveError::Other)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        if self
+            .update_if_present(session_key.as_ref(), &session_state, ttl)
+            .await
+            .map_err(UpdateError::Other)?
+        {
+            return Ok(session_key);
+        }
+        // セッションの有効期限が切れていた場合は、新しいセッションとして記録
+        save_with_new_key(self, &session_state, ttl, |_, state| state.clone())
+            .await
+            .map_err(UpdateError::Other)
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        redis::cmd("DEL")
+            .arg(self.redis_key(session_key.as_ref()))
+            .query_async::<_, ()>(&mut self.connection.clone())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// メモリ内セッションストア
+///
+/// セッションデータをプロセスのメモリに記録するセッションストアで、Redisを用意せずにセッションを
+/// 扱うテストで使用する。
+/// セッションデータはプロセス間で共有されず、プロセスが終了すると失われるため、本番環境では使用しない。
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySessionStore {
+    /// 接頭辞を付与したセッションキーをキーに、セッションの状態と有効期限を記録するマップ。
+    sessions: Arc<RwLock<HashMap<String, (SessionState, OffsetDateTime)>>>,
+    /// 接頭辞を付与した索引のキーをキーに、セッションキーを記録するマップ。
+    index: Arc<RwLock<HashMap<String, String>>>,
+    /// セッションキーに付与する接頭辞。
+    key_prefix: String,
+}
+
+impl InMemorySessionStore {
+    /// Redisセッションストアと同様に、セッションキーに接頭辞を付与して記録するメモリ内セッションストアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key_prefix` - セッションキーに付与する接頭辞。
+    ///
+    /// # Returns
+    ///
+    /// メモリ内セッションストアインスタンス。
+    pub fn with_key_prefix(key_prefix: impl Into<String>) -> Self {
+        Self {
+            sessions: Default::default(),
+            index: Default::default(),
+            key_prefix: key_prefix.into(),
+        }
+    }
+
+    /// セッションキーに接頭辞を付与して、マップに記録するキーを返却する。
+    fn cache_key(&self, session_key: &str) -> String {
+        prefixed_key(&self.key_prefix, session_key)
+    }
+
+    /// セッションの状態にセッションIDが記録されている場合は、索引を記録する。
+    fn insert_index(&self, session_key: &str, session_state: &SessionState) -> anyhow::Result<()> {
+        if let Some(session_id) = indexed_session_id(session_state) {
+            self.index.write().map_err(|e| anyhow!("{}", e))?.insert(
+                self.cache_key(&session_index_key(session_id)),
+                session_key.to_owned(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// 有効期限を過ぎたセッションを削除する。
+    fn remove_expired_sessions(sessions: &mut HashMap<String, (SessionState, OffsetDateTime)>) {
+        let now = OffsetDateTime::now_utc();
+        sessions.retain(|_, (_, expired_at)| now < *expired_at);
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|e| LoadError::Other(anyhow!("{}", e)))?;
+        Self::remove_expired_sessions(&mut sessions);
+
+        Ok(sessions
+            .get(&self.cache_key(session_key.as_ref()))
+            .map(|(state, _)| state.clone()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        save_with_new_key(self, &session_state, ttl, |_, state| state.clone())
+            .await
+            .map_err(SaveError::Other)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        if self
+            .update_if_present(session_key.as_ref(), &session_state, ttl)
+            .await
+            .map_err(UpdateError::Other)?
+        {
+            return Ok(session_key);
+        }
+        // セッションの有効期限が切れていた場合は、Redisセッションストアと同様に新しいセッションとして記録
+        save_with_new_key(self, &session_state, ttl, |_, state| state.clone())
+            .await
+            .map_err(UpdateError::Other)
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        self.sessions
+            .write()
+            .map_err(|e| anyhow!("{}", e))?
+            .remove(&self.cache_key(session_key.as_ref()));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl KeyedSessionStore for InMemorySessionStore {
+    async fn insert_if_absent(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        let mut sessions = self.sessions.write().map_err(|e| anyhow!("{}", e))?;
+        Self::remove_expired_sessions(&mut sessions);
+        let cache_key = self.cache_key(session_key);
+        if sessions.contains_key(&cache_key) {
+            return Ok(false);
+        }
+        sessions.insert(
+            cache_key,
+            (session_state.clone(), OffsetDateTime::now_utc() + *ttl),
+        );
+        self.insert_index(session_key, session_state)?;
+
+        Ok(true)
+    }
+
+    async fn update_if_present(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        let mut sessions = self.sessions.write().map_err(|e| anyhow!("{}", e))?;
+        Self::remove_expired_sessions(&mut sessions);
+        match sessions.get_mut(&self.cache_key(session_key)) {
+            Some(session) => {
+                *session = (session_state.clone(), OffsetDateTime::now_utc() + *ttl);
+                self.insert_index(session_key, session_state)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_by_session_id(&self, session_id: Uuid) -> anyhow::Result<bool> {
+        let session_key = self
+            .index
+            .write()
+            .map_err(|e| anyhow!("{}", e))?
+            .remove(&self.cache_key(&session_index_key(session_id)));
+        let session_key = match session_key {
+            Some(session_key) => session_key,
+            None => return Ok(false),
+        };
+        let mut sessions = self.sessions.write().map_err(|e| anyhow!("{}", e))?;
+        Self::remove_expired_sessions(&mut sessions);
+
+        Ok(sessions.remove(&self.cache_key(&session_key)).is_some())
+    }
+}
+
+/// タイムアウト付きセッションストア
+///
+/// 内包するセッションストアの操作が指定した時間内に完了しない場合はエラーとして、応答の遅いRedisによって
+/// リクエストの処理が停止し続けることを防ぐ。
+#[derive(Debug, Clone)]
+pub struct TimeoutSessionStore<S> {
+    /// 内包するセッションストア。
+    inner: S,
+    /// セッションストアの操作のタイムアウト。
+    timeout: std::time::Duration,
+}
+
+impl<S> TimeoutSessionStore<S> {
+    /// タイムアウト付きセッションストアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - 内包するセッションストア。
+    /// * `timeout` - セッションストアの操作のタイムアウト。
+    ///
+    /// # Returns
+    ///
+    /// タイムアウト付きセッションストアインスタンス。
+    pub fn new(inner: S, timeout: std::time::Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    /// タイムアウトしたことを示すエラーを生成する。
+    fn timeout_error(&self, operation: &str) -> anyhow::Error {
+        anyhow!(
+            "セッションストアの{}が{}ミリ秒以内に完了しませんでした。",
+            operation,
+            self.timeout.as_millis()
+        )
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S: SessionStore> SessionStore for TimeoutSessionStore<S> {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        tokio::time::timeout(self.timeout, self.inner.load(session_key))
+            .await
+            .map_err(|_| LoadError::Other(self.timeout_error("読み込み")))?
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        tokio::time::timeout(self.timeout, self.inner.save(session_state, ttl))
+            .await
+            .map_err(|_| SaveError::Other(self.timeout_error("保存")))?
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        tokio::time::timeout(
+            self.timeout,
+            self.inner.update(session_key, session_state, ttl),
+        )
+        .await
+        .map_err(|_| UpdateError::Other(self.timeout_error("更新")))?
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        tokio::time::timeout(self.timeout, self.inner.delete(session_key))
+            .await
+            .map_err(|_| self.timeout_error("削除"))?
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S: KeyedSessionStore> KeyedSessionStore for TimeoutSessionStore<S> {
+    async fn insert_if_absent(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        tokio::time::timeout(
+            self.timeout,
+            self.inner.insert_if_absent(session_key, session_state, ttl),
+        )
+        .await
+        .map_err(|_| self.timeout_error("保存"))?
+    }
+
+    async fn update_if_present(
+        &self,
+        session_key: &str,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<bool> {
+        tokio::time::timeout(
+            self.timeout,
+            self.inner
+                .update_if_present(session_key, session_state, ttl),
+        )
+        .await
+        .map_err(|_| self.timeout_error("更新"))?
+    }
+
+    async fn delete_by_session_id(&self, session_id: Uuid) -> anyhow::Result<bool> {
+        tokio::time::timeout(self.timeout, self.inner.delete_by_session_id(session_id))
+            .await
+            .map_err(|_| self.timeout_error("削除"))?
+    }
+}
+
+/// セッションキー検証ストア
+///
+/// セッションキーをトークンの秘密鍵で計算した検証コードをセッションの状態に記録して、読み込むときに
+/// クッキーから取得したセッションキーと一致するか確認する。セッションストアに書き込める攻撃者が、他の
+/// セッションの状態を複製したり入れ替えたりした場合は、検証コードが一致しないため、セッションを破棄して
+/// セッションが存在しないものとして扱う。
+///
+/// セッションキーは、ログインしたときではなく、レスポンスを返却するときにセッションストアがセッションを保存
+/// するときに決まるため、認証ミドルウェアではなくセッションストアで検証コードを記録する。検証コードを記録して
+/// いない状態のセッションが存在しないように、セッションキーを生成して、検証コードを含めたセッションの状態を
+/// 1回の書き込みで記録する。
+#[derive(Debug, Clone)]
+pub struct SessionKeyBindingStore<S> {
+    /// 内包するセッションストア。
+    inner: S,
+    /// 検証コードを計算するトークンの秘密鍵。
+    secret_key: Secret<String>,
+}
+
+impl<S: KeyedSessionStore> SessionKeyBindingStore<S> {
+    /// セッションの状態に検証コードを記録するキー
+    const SESSION_KEY_MAC_KEY: &'static str = "session_key_mac";
+
+    /// セッションキー検証ストアを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - 内包するセッションストア。
+    /// * `secret_key` - 検証コードを計算するトークンの秘密鍵。
+    ///
+    /// # Returns
+    ///
+    /// セッションキー検証ストアインスタンス。
+    pub fn new(inner: S, secret_key: Secret<String>) -> Self {
+        Self { inner, secret_key }
+    }
+
+    /// セッションの状態に、セッションキーの検証コードを追加する。
+    fn bind(&self, session_key: &str, session_state: &SessionState) -> SessionState {
+        let mut session_state = session_state.clone();
+        session_state.insert(
+            Self::SESSION_KEY_MAC_KEY.to_owned(),
+            session_key_mac(session_key, &self.secret_key),
+        );
+
+        session_state
+    }
+
+    /// セッションキーを生成して、検証コードを含めたセッションの状態を記録する。
+    async fn save_bound(
+        &self,
+        session_state: &SessionState,
+        ttl: &Duration,
+    ) -> anyhow::Result<SessionKey> {
+        save_with_new_key(&self.inner, session_state, ttl, |session_key, state| {
+            self.bind(session_key, state)
+        })
+        .await
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S: KeyedSessionStore> SessionStore for SessionKeyBindingStore<S> {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let session_state = match self.inner.load(session_key).await? {
+            Some(session_state) => session_state,
+            None => return Ok(None),
+        };
+        let verified = session_state
+            .get(Self::SESSION_KEY_MAC_KEY)
+            .map(|mac| verify_session_key_mac(session_key.as_ref(), mac, &self.secret_key))
+            .unwrap_or(false);
+        if verified {
+            return Ok(Some(session_state));
+        }
+        // 検証コードが一致しないセッションは、複製されたか、他のセッションと入れ替えられたと判断して破棄
+        tracing::warn!("セッションキーが検証コードと一致しないため、セッションを破棄します。");
+        self.inner
+            .delete(session_key)
+            .await
+            .map_err(LoadError::Other)?;
+
+        Ok(None)
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        self.save_bound(&session_state, ttl)
+            .await
+            .map_err(SaveError::Other)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let bound_state = self.bind(session_key.as_ref(), &session_state);
+        if self
+            .inner
+            .update_if_present(session_key.as_ref(), &bound_state, ttl)
+            .await
+            .map_err(UpdateError::Other)?
+        {
+            return Ok(session_key);
+        }
+        // 有効期限が切れたセッションは、新しいセッションキーの検証コードを含めて新しいセッションとして記録
+        self.save_bound(&session_state, ttl)
+            .await
+            .map_err(UpdateError::Other)
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        self.inner.delete(session_key).await
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S: KeyedSessionStore> SessionPurger for SessionKeyBindingStore<S> {
+    async fn purge_session(&self, session_id: Uuid) -> anyhow::Result<bool> {
+        self.inner.delete_by_session_id(session_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_state() -> SessionState {
+        HashMap::from([("foo".to_owned(), "bar".to_owned())])
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_session_store() {
+        let store = InMemorySessionStore::default();
+        let ttl = Duration::minutes(1);
+        // セッションを記録して取得
+        let session_key = store.save(session_state(), &ttl).await.unwrap();
+        assert_eq!(session_key.as_ref().len(), SESSION_KEY_LEN);
+        let state = store.load(&session_key).await.unwrap().unwrap();
+        assert_eq!(state, session_state());
+        // セッションを更新しても、セッションキーが変わらないことを確認
+        let mut new_state = session_state();
+        new_state.insert("baz".to_owned(), "qux".to_owned());
+        let updated_key = store
+            .update(session_key, new_state.clone(), &ttl)
+            .await
+            .unwrap();
+        let state = store.load(&updated_key).await.unwrap().unwrap();
+        assert_eq!(state, new_state);
+        // セッションを削除
+        store.delete(&updated_key).await.unwrap();
+        assert!(store.load(&updated_key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_session_store_with_key_prefix() {
+        let store = InMemorySessionStore::with_key_prefix("staging:");
+        let ttl = Duration::minutes(1);
+        let session_key = store.save(session_state(), &ttl).await.unwrap();
+        // クライアントに返却するセッションキーには接頭辞を付与せず、記録するキーに接頭辞を付与
+        assert_eq!(session_key.as_ref().len(), SESSION_KEY_LEN);
+        let keys: Vec<String> = store.sessions.read().unwrap().keys().cloned().collect();
+        assert_eq!(keys, vec![format!("staging:{}", session_key.as_ref())]);
+        let session_key = store
+            .update(session_key, session_state(), &ttl)
+            .await
+            .unwrap();
+        assert!(store.load(&session_key).await.unwrap().is_some());
+        assert_eq!(store.sessions.read().unwrap().len(), 1);
+        // 接頭辞が異なるセッションストアとは、同じマップを共有してもセッションが衝突しない
+        let other = InMemorySessionStore {
+            sessions: store.sessions.clone(),
+            index: store.index.clone(),
+            key_prefix: "production:".to_owned(),
+        };
+        assert!(other.load(&session_key).await.unwrap().is_none());
+        store.delete(&session_key).await.unwrap();
+        assert!(store.sessions.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_key_binding_store() {
+        let inner = InMemorySessionStore::default();
+        let store = SessionKeyBindingStore::new(inner.clone(), Secret::new("secret".to_owned()));
+        let ttl = Duration::minutes(1);
+        // 保存したセッションは、検証コードが一致するため読み込める
+        let session_key = store.save(session_state(), &ttl).await.unwrap();
+        let state = store.load(&session_key).await.unwrap().unwrap();
+        assert_eq!(state["foo"], "bar");
+        let session_key = store
+            .update(session_key, session_state(), &ttl)
+            .await
+            .unwrap();
+        assert!(store.load(&session_key).await.unwrap().is_some());
+
+        // 他のセッションキーに複製されたセッションは、検証コードが一致しないため破棄
+        let other_key = inner.save(state, &ttl).await.unwrap();
+        assert!(store.load(&other_key).await.unwrap().is_none());
+        assert!(inner.load(&other_key).await.unwrap().is_none());
+        // 検証コードを記録していないセッションも破棄
+        let unbound_key = inner.save(session_state(), &ttl).await.unwrap();
+        assert!(store.load(&unbound_key).await.unwrap().is_none());
+        // 異なる秘密鍵では検証に失敗
+        let other = SessionKeyBindingStore::new(inner.clone(), Secret::new("other".to_owned()));
+        assert!(other.load(&session_key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_key_binding_store_rebinds_expired_session() {
+        let inner = InMemorySessionStore::default();
+        let store = SessionKeyBindingStore::new(inner, Secret::new("secret".to_owned()));
+        let session_key = store
+            .save(session_state(), &Duration::minutes(1))
+            .await
+            .unwrap();
+        store.inner.sessions.write().unwrap().clear();
+        // 有効期限が切れたセッションを新しいセッションキーで保存した場合も、検証コードが一致する
+        let new_key = store
+            .update(session_key, session_state(), &Duration::minutes(1))
+            .await
+            .unwrap();
+        assert!(store.load(&new_key).await.unwrap().is_some());
+    }
+
+    /// 書き込まれたセッションの状態を記録するセッションストア
+    #[derive(Clone, Default)]
+    struct RecordingSessionStore {
+        inner: InMemorySessionStore,
+        writes: Arc<RwLock<Vec<SessionState>>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl KeyedSessionStore for RecordingSessionStore {
+        async fn insert_if_absent(
+            &self,
+            session_key: &str,
+            session_state: &SessionState,
+            ttl: &Duration,
+        ) -> anyhow::Result<bool> {
+            self.writes.write().unwrap().push(session_state.clone());
+            self.inner
+                .insert_if_absent(session_key, session_state, ttl)
+                .await
+        }
+
+        async fn update_if_present(
+            &self,
+            session_key: &str,
+            session_state: &SessionState,
+            ttl: &Duration,
+        ) -> anyhow::Result<bool> {
+            self.writes.write().unwrap().push(session_state.clone());
+            self.inner
+                .update_if_present(session_key, session_state, ttl)
+                .await
+        }
+
+        async fn delete_by_session_id(&self, session_id: Uuid) -> anyhow::Result<bool> {
+            self.inner.delete_by_session_id(session_id).await
+        }
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl SessionStore for RecordingSessionStore {
+        async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+            self.inner.load(session_key).await
+        }
+
+        async fn save(
+            &self,
+            session_state: SessionState,
+            ttl: &Duration,
+        ) -> Result<SessionKey, SaveError> {
+            self.inner.save(session_state, ttl).await
+        }
+
+        async fn update(
+            &self,
+            session_key: SessionKey,
+            session_state: SessionState,
+            ttl: &Duration,
+        ) -> Result<SessionKey, UpdateError> {
+            self.inner.update(session_key, session_state, ttl).await
+        }
+
+        async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+            self.inner.delete(session_key).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_key_binding_store_writes_once_with_mac() {
+        let inner = RecordingSessionStore::default();
+        let store = SessionKeyBindingStore::new(inner.clone(), Secret::new("secret".to_owned()));
+        let ttl = Duration::minutes(1);
+        // 保存と更新は、検証コードを含めたセッションの状態を1回だけ書き込む
+        let session_key = store.save(session_state(), &ttl).await.unwrap();
+        assert_eq!(inner.writes.read().unwrap().len(), 1);
+        let session_key = store
+            .update(session_key, session_state(), &ttl)
+            .await
+            .unwrap();
+        assert_eq!(inner.writes.read().unwrap().len(), 2);
+        // 有効期限が切れたセッションの更新は、存在しないセッションの更新と新しいセッションの記録のみ
+        inner.inner.sessions.write().unwrap().clear();
+        store
+            .update(session_key, session_state(), &ttl)
+            .await
+            .unwrap();
+        let writes = inner.writes.read().unwrap();
+        assert_eq!(writes.len(), 4);
+        assert!(writes
+            .iter()
+            .all(|state| state.contains_key("session_key_mac") && state["foo"] == "bar"));
+    }
+
+    #[tokio::test]
+    async fn test_session_key_binding_store_purges_session_by_id() {
+        let inner = InMemorySessionStore::default();
+        let store = SessionKeyBindingStore::new(inner.clone(), Secret::new("secret".to_owned()));
+        let ttl = Duration::minutes(1);
+        let session_id = Uuid::new_v4();
+        let mut state = session_state();
+        state.insert(
+            SESSION_ID_STATE_KEY.to_owned(),
+            serde_json::to_string(&session_id).unwrap(),
+        );
+        // セッションキーが変わっても、最後に記録したセッションキーのセッションを削除
+        let session_key = store.save(state.clone(), &ttl).await.unwrap();
+        inner.sessions.write().unwrap().clear();
+        let session_key = store.update(session_key, state, &ttl).await.unwrap();
+        let other_key = store.save(session_state(), &ttl).await.unwrap();
+        assert!(store.purge_session(session_id).await.unwrap());
+        assert!(store.load(&session_key).await.unwrap().is_none());
+        // 他のセッションは削除しない
+        assert!(store.load(&other_key).await.unwrap().is_some());
+        // 削除したセッションや、存在しないセッションは削除しない
+        assert!(!store.purge_session(session_id).await.unwrap());
+        assert!(!store.purge_session(Uuid::new_v4()).await.unwrap());
+    }
+
+    #[test]
+    fn test_prefixed_key() {
+        assert_eq!(prefixed_key("dev:", "foo"), "dev:foo");
+        assert_eq!(prefixed_key("", "foo"), "foo");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_session_store_expired() {
+        let store = InMemorySessionStore::default();
+        let session_key = store
+            .save(session_state(), &Duration::seconds(-1))
+            .await
+            .unwrap();
+        assert!(store.load(&session_key).await.unwrap().is_none());
+        // 有効期限が切れたセッションを更新した場合は、新しいセッションキーで記録
+        let expired_key = session_key.as_ref().to_owned();
+        let new_key = store
+            .update(session_key, session_state(), &Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_ne!(new_key.as_ref(), expired_key);
+        assert!(store.load(&new_key).await.unwrap().is_some());
+    }
+
+    /// 操作が完了しないセッションストア
+    #[derive(Clone)]
+    struct PendingSessionStore;
+
+    #[async_trait::async_trait(?Send)]
+    impl SessionStore for PendingSessionStore {
+        async fn load(&self, _: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+            std::future::pending().await
+        }
+
+        async fn save(&self, _: SessionState, _: &Duration) -> Result<SessionKey, SaveError> {
+            std::future::pending().await
+        }
+
+        async fn update(
+            &self,
+            _: SessionKey,
+            _: SessionState,
+            _: &Duration,
+        ) -> Result<SessionKey, UpdateError> {
+            std::future::pending().await
+        }
+
+        async fn delete(&self, _: &SessionKey) -> Result<(), anyhow::Error> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_session_store() {
+        // 操作が完了するセッションストアは、そのまま結果を返却
+        let store = TimeoutSessionStore::new(
+            InMemorySessionStore::default(),
+            std::time::Duration::from_secs(1),
+        );
+        let session_key = store
+            .save(session_state(), &Duration::minutes(1))
+            .await
+            .unwrap();
+        assert!(store.load(&session_key).await.unwrap().is_some());
+        // 操作が完了しないセッションストアは、タイムアウトしてエラーを返却
+        let store =
+            TimeoutSessionStore::new(PendingSessionStore, std::time::Duration::from_millis(10));
+        assert!(matches!(
+            store.load(&session_key).await,
+            Err(LoadError::Other(_))
+        ));
+        assert!(matches!(
+            store.save(session_state(), &Duration::minutes(1)).await,
+            Err(SaveError::Other(_))
+        ));
+        assert!(store.delete(&session_key).await.is_err());
+    }
+}
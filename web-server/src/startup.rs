@@ -1,14 +1,57 @@
+use std::fs::File;
+use std::io::BufReader;
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
 
-use actix_session::{storage::RedisSessionStore, SessionLength, SessionMiddleware};
-use actix_web::{cookie::Key, dev::Server, web, App, HttpServer};
-use middlewares::JwtAuth;
+use actix_cors::Cors;
+use actix_session::{SessionLength, SessionMiddleware};
+use actix_web::{
+    cookie::{time, Key},
+    dev::Server,
+    http::header,
+    middleware::Condition,
+    web, App, HttpServer,
+};
+use middlewares::{
+    idempotency::{Idempotency, IdempotencyStore, IDEMPOTENCY_KEY_HEADER},
+    require_scope::RequireScope,
+    session_key_rotation::SessionKeyRotation,
+    user_cache::UserCache,
+    JwtAuth,
+};
 use secrecy::ExposeSecret;
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{postgres::PgPoolOptions, Connection, PgConnection, PgPool};
 
-use routes::{accounts::accounts_scope, health_check, protected_resource};
+use routes::{
+    accounts::accounts_scope,
+    admin::admin_scope,
+    api_keys::api_keys_scope,
+    health_check, protected_resource,
+    responses::{json_config, not_found},
+    users::users_scope,
+    websocket,
+};
 
-use configurations::{DatabaseSettings, Settings};
+use anyhow::{anyhow, Context};
+use configurations::{
+    argon2_settings, session::SessionDataCipher, DatabaseSettings, SessionStoreSettings, Settings,
+    TlsSettings, WebAppSettings,
+};
+use usecases::{passkeys::build_webauthn, sessions::SessionPurger, webhooks::WebhookDispatcher};
+
+use crate::idempotency_stores::{InMemoryIdempotencyStore, RedisIdempotencyStore};
+use crate::refresh_token_cleanup::spawn_refresh_token_cleanup;
+use crate::session_stores::{
+    KeyedSessionStore, RedisSessionStore, SessionKeyBindingStore, TimeoutSessionStore,
+};
+
+/// 冪等キーを受け付けるパス
+///
+/// ネットワークの再試行で重複して送信されても、ユーザーの二重登録やセッションの重複した生成が起きないように、
+/// サインアップとログインで冪等キーを受け付ける。
+const IDEMPOTENT_PATHS: &[&str] = &["/accounts/signup", "/accounts/login"];
 
 /// Webアプリ構造体
 pub struct WebApp {
@@ -21,6 +64,8 @@ pub struct WebApp {
 impl WebApp {
     /// Webアプリを構築する。
     ///
+    /// セッションデータは、Redisに記録する。
+    ///
     /// # Arguments
     ///
     /// * `settings` - 設定インスタンス。
@@ -29,48 +74,206 @@ impl WebApp {
     ///
     /// Webアプリインスタンス。
     pub async fn build(settings: Settings) -> Result<Self, anyhow::Error> {
+        // Redisに接続できない場合は、Webアプリの構築を中止
+        let session_store = &settings.session_store;
+        verify_session_store_connection(session_store).await?;
+
+        // セッションデータのキーに接頭辞を付与して、Redisに記録
+        let store = RedisSessionStore::connect(session_store).await?;
+        // 応答の遅いRedisでリクエストの処理が停止しないように、コマンドにタイムアウトを設定
+        let store = TimeoutSessionStore::new(store, session_store.command_timeout());
+        // 冪等キーとリクエストの処理結果もRedisに記録
+        let idempotency_store = RedisIdempotencyStore::connect(session_store).await?;
+
+        Self::build_with_stores(settings, store, idempotency_store).await
+    }
+
+    /// セッションストアを指定して、Webアプリを構築する。
+    ///
+    /// テストでRedisの代わりにメモリ内セッションストアを使用する場合などに使用する。
+    /// 冪等キーとリクエストの処理結果は、メモリ内冪等ストアに記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - 設定インスタンス。
+    /// * `store` - セッションストア。
+    ///
+    /// # Returns
+    ///
+    /// Webアプリインスタンス。
+    pub async fn build_with_store<S>(settings: Settings, store: S) -> Result<Self, anyhow::Error>
+    where
+        S: KeyedSessionStore + Clone + Send + 'static,
+    {
+        Self::build_with_stores(settings, store, InMemoryIdempotencyStore::default()).await
+    }
+
+    /// セッションストアと冪等ストアを指定して、Webアプリを構築する。
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - 設定インスタンス。
+    /// * `store` - セッションストア。
+    /// * `idempotency_store` - 冪等ストア。
+    ///
+    /// # Returns
+    ///
+    /// Webアプリインスタンス。
+    async fn build_with_stores<S, I>(
+        settings: Settings,
+        store: S,
+        idempotency_store: I,
+    ) -> Result<Self, anyhow::Error>
+    where
+        S: KeyedSessionStore + Clone + Send + 'static,
+        I: IdempotencyStore + Clone + Send + 'static,
+    {
+        // 環境変数に設定したArgon2設定が不正な場合は、最初のパスワードのハッシュ化でエラーにならないように、
+        // Webアプリの構築を中止
+        argon2_settings()?;
+        // 本番環境で、認証情報を記録するクッキーの設定が安全ではない場合は、Webアプリの構築を中止
+        settings
+            .session_cookie
+            .verify_security(settings.web_app.environment)?;
+        // データベースに接続できない場合は、Webアプリの構築を中止
+        verify_database_connection(&settings.db).await?;
+        // データベースのスキーマが古い場合は、マイグレーションを適用するか、Webアプリの構築を中止
+        verify_migrations(&settings.db).await?;
+
         let Settings {
             web_app,
             session_cookie,
             tokens,
             session_store,
             db,
+            user_cache,
+            webhook,
+            webauthn,
             ..
         } = settings.clone();
         let settings = web::Data::new(settings);
+        // セッションストアに記録したセッションの状態を、他のセッションキーに複製したり入れ替えたりできない
+        // ように、セッションキーの検証コードを記録して、読み込むときに検証
+        let store = SessionKeyBindingStore::new(store, tokens.secret_key.clone());
+        // ワーカー間で共有するため、ユーザーキャッシュはサーバーの起動前に構築
+        let user_cache = web::Data::new(UserCache::new(&user_cache));
+        // ワーカー間でHTTPクライアントを共有するため、Webhookディスパッチャーはサーバーの起動前に構築
+        let webhooks = web::Data::new(WebhookDispatcher::new(&webhook)?);
+        // リライングパーティーIDとオリジンの設定の誤りを起動時に検出するため、WebAuthnインスタンスは
+        // サーバーの起動前に構築
+        let webauthn = web::Data::new(build_webauthn(&webauthn)?);
+        // リクエストごとに構築しないように、セッションデータ暗号はサーバーの起動前に構築
+        let session_data_cipher =
+            SessionDataCipher::from_settings(&session_store)?.map(web::Data::new);
 
         let pool = web::Data::new(get_connection_pool(&db));
+        // 有効期限が切れたリフレッシュトークンを定期的に削除
+        if 0 < tokens.refresh_token_cleanup_interval() {
+            spawn_refresh_token_cleanup(
+                pool.as_ref().clone(),
+                Duration::from_secs(tokens.refresh_token_cleanup_interval()),
+            );
+        }
 
+        let json_config = json_config(
+            web_app.json_payload_limit,
+            web_app.json_content_type_required,
+        );
+        // TLSが有効な場合は、証明書と秘密鍵を読み込み
+        let tls_config = match &web_app.tls {
+            Some(tls) => Some(load_rustls_config(tls)?),
+            None => None,
+        };
         let listener = TcpListener::bind(web_app.socket_address())?;
         let port = listener.local_addr().unwrap().port();
 
-        let store = RedisSessionStore::new(session_store.uri.expose_secret()).await?;
         let store_key = Key::from(session_store.key.expose_secret().as_bytes());
+        let legacy_store_keys: Vec<Key> = session_store
+            .legacy_keys
+            .iter()
+            .map(|key| Key::from(key.expose_secret().as_bytes()))
+            .collect();
+        let idempotency_key_ttl = session_store.idempotency_key_ttl();
+        // トークンを記録するクッキーに`Max-Age`を付与する場合は、ブラウザを閉じてもログインを継続できるように、
+        // セッションIDのクッキーにも同じ`Max-Age`を付与
+        let session_length = match session_cookie.max_age_seconds {
+            Some(max_age) => SessionLength::Predetermined {
+                max_session_length: Some(time::Duration::seconds(max_age as i64)),
+            },
+            None => SessionLength::BrowserSession {
+                state_ttl: Some(tokens.refresh_token_duration),
+            },
+        };
 
         tracing::info!("Startup web app...");
         let server = HttpServer::new(move || {
+            let session_data_cipher = session_data_cipher.clone();
+            // ユーザー又は管理者がセッションを失効させたときに、セッションストアからセッションを削除
+            let session_purger: web::Data<dyn SessionPurger> =
+                web::Data::from(Arc::new(store.clone()) as Arc<dyn SessionPurger>);
             App::new()
                 .wrap(
                     SessionMiddleware::builder(store.clone(), store_key.clone())
-                        .session_length(SessionLength::BrowserSession {
-                            state_ttl: Some(tokens.refresh_token_duration),
-                        })
+                        .session_length(session_length.clone())
                         .cookie_name(session_cookie.session_id_cookie_name.clone())
                         .cookie_http_only(true)
                         .cookie_same_site(session_cookie.same_site)
                         .cookie_secure(session_cookie.secure)
                         .build(),
                 )
+                // 鍵を更新する前に暗号化されたセッションIDのクッキーを、セッションミドルウェアが読み込む前に
+                // 暗号化し直すため、セッションミドルウェアより外側で処理
+                .wrap(SessionKeyRotation::new(
+                    store_key.clone(),
+                    legacy_store_keys.clone(),
+                    &session_cookie,
+                ))
                 .app_data(settings.clone())
                 .app_data(pool.clone())
+                .app_data(user_cache.clone())
+                .app_data(webhooks.clone())
+                .app_data(webauthn.clone())
+                .app_data(session_purger)
+                .configure(|cfg| {
+                    if let Some(cipher) = session_data_cipher {
+                        cfg.app_data(cipher);
+                    }
+                })
                 .route("/health_check", web::get().to(health_check::health_check))
-                .service(accounts_scope())
-                .service(web::scope("").wrap(JwtAuth).route(
-                    "/protected_resource",
-                    web::get().to(protected_resource::protected_resource),
+                .route("/version", web::get().to(health_check::version))
+                .service(accounts_scope().app_data(json_config.clone()))
+                .service(users_scope())
+                .service(admin_scope().app_data(json_config.clone()))
+                .service(api_keys_scope().app_data(json_config.clone()))
+                .service(
+                    web::resource("/protected_resource")
+                        .wrap(JwtAuth)
+                        // APIキーで認証したリクエストは、スコープを付与したAPIキーに限定
+                        .wrap(RequireScope::new(
+                            protected_resource::PROTECTED_RESOURCE_READ_SCOPE,
+                        ))
+                        .route(web::get().to(protected_resource::protected_resource)),
+                )
+                // WebSocketへのアップグレード時に、ハンドラでセッションを認証
+                .route("/ws", web::get().to(websocket::websocket))
+                .default_service(web::to(not_found))
+                // 記録する応答にセッションIDのクッキーを含めるため、セッションミドルウェアより外側で処理
+                .wrap(Idempotency::new(
+                    idempotency_store.clone(),
+                    idempotency_key_ttl,
+                    IDEMPOTENT_PATHS,
+                    &tokens.secret_key,
                 ))
-        })
-        .listen(listener)?
+                // 認証に失敗した応答にもCORSヘッダーを付与するため、最も外側で処理
+                .wrap(Condition::new(
+                    !web_app.cors_allowed_origins.is_empty(),
+                    build_cors(&web_app),
+                ))
+        });
+        let server = match tls_config {
+            Some(tls_config) => server.listen_rustls(listener, tls_config)?,
+            None => server.listen(listener)?,
+        }
         .run();
 
         Ok(Self { port, server })
@@ -91,6 +294,173 @@ impl WebApp {
     }
 }
 
+/// クロスオリジンリクエストを処理するCORSミドルウェアを構築する。
+///
+/// 許可したオリジンからの、クッキーを含むリクエストを受け付ける。
+///
+/// # Arguments
+///
+/// * `settings` - Webアプリ設定。
+///
+/// # Returns
+///
+/// CORSミドルウェア。
+fn build_cors(settings: &WebAppSettings) -> Cors {
+    settings
+        .cors_allowed_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allowed_methods(["GET", "POST", "PUT", "DELETE"])
+        .allowed_header(header::CONTENT_TYPE)
+        .allowed_header(IDEMPOTENCY_KEY_HEADER)
+        .supports_credentials()
+        .max_age(3600)
+}
+
+/// 起動時にデータベースへの接続を確認するときのタイムアウト
+const CONNECTION_VERIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// データベースに接続できるか確認する。
+async fn verify_database_connection(settings: &DatabaseSettings) -> anyhow::Result<()> {
+    tracing::info!("Verify connection to database...");
+    let connection = tokio::time::timeout(
+        CONNECTION_VERIFICATION_TIMEOUT,
+        PgConnection::connect_with(&settings.with_db()),
+    )
+    .await
+    .map_err(|_| {
+        anyhow!(
+            "データベース({}:{})への接続がタイムアウトしました。",
+            settings.host,
+            settings.port
+        )
+    })?
+    .with_context(|| {
+        format!(
+            "データベース({}:{})に接続できません。",
+            settings.host, settings.port
+        )
+    })?;
+    connection.close().await?;
+
+    Ok(())
+}
+
+/// Webアプリが要求するデータベースのマイグレーション
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// データベースにマイグレーションが適用されているか確認する。
+///
+/// データベース設定で自動的にマイグレーションを適用するように設定されている場合は、適用されていない
+/// マイグレーションを適用する。それ以外の場合は、適用されていないマイグレーションがあればエラーを返却する。
+///
+/// # Arguments
+///
+/// * `settings` - データベース設定。
+///
+/// # Returns
+///
+/// マイグレーションが適用されている場合は`()`。
+async fn verify_migrations(settings: &DatabaseSettings) -> anyhow::Result<()> {
+    tracing::info!("Verify database migrations...");
+    let mut connection = PgConnection::connect_with(&settings.with_db())
+        .await
+        .with_context(|| {
+            format!(
+                "データベース({}:{})に接続できません。",
+                settings.host, settings.port
+            )
+        })?;
+    if settings.auto_migrate {
+        MIGRATOR
+            .run(&mut connection)
+            .await
+            .context("データベースにマイグレーションを適用できませんでした。")?;
+    } else {
+        let pending = pending_migrations(&mut connection).await?;
+        if !pending.is_empty() {
+            return Err(anyhow!(
+                "データベースに適用されていないマイグレーション({})があります。マイグレーションを適用するか、\
+                環境変数AUTO_MIGRATEにtrueを設定してください。",
+                pending.join(", ")
+            ));
+        }
+    }
+    connection.close().await?;
+
+    Ok(())
+}
+
+/// データベースに適用されていないマイグレーションを返却する。
+///
+/// # Arguments
+///
+/// * `connection` - データベースコネクション。
+///
+/// # Returns
+///
+/// 適用されていないマイグレーションのバージョンと説明。マイグレーションの途中で失敗していたり、適用した
+/// マイグレーションの内容が変更されていたりする場合はエラー。
+pub async fn pending_migrations(connection: &mut PgConnection) -> anyhow::Result<Vec<String>> {
+    // マイグレーションを適用していないデータベースには、マイグレーションを記録するテーブルが存在しない
+    let migrated = sqlx::query!(
+        r#"
+        SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS "migrated!"
+        "#
+    )
+    .fetch_one(&mut *connection)
+    .await?
+    .migrated;
+    let applied = if migrated {
+        if let Some(version) = connection.dirty_version().await? {
+            return Err(anyhow!(
+                "データベースのマイグレーション({})が途中で失敗しています。",
+                version
+            ));
+        }
+        connection.list_applied_migrations().await?
+    } else {
+        vec![]
+    };
+
+    let mut pending = vec![];
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        match applied.iter().find(|a| a.version == migration.version) {
+            Some(applied) if applied.checksum != migration.checksum => {
+                return Err(anyhow!(
+                    "データベースに適用したマイグレーション({})の内容が変更されています。",
+                    migration.version
+                ));
+            }
+            Some(_) => {}
+            None => pending.push(format!("{}_{}", migration.version, migration.description)),
+        }
+    }
+
+    Ok(pending)
+}
+
+/// Redisに接続できるか確認する。
+async fn verify_session_store_connection(settings: &SessionStoreSettings) -> anyhow::Result<()> {
+    tracing::info!("Verify connection to session store...");
+    let client = redis::Client::open(settings.uri.expose_secret().as_str())
+        .context("RedisのURIが不正です。")?;
+    tokio::time::timeout(settings.connect_timeout(), async {
+        let mut connection = client.get_async_connection().await?;
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+    })
+    .await
+    .map_err(|_| anyhow!("Redisへの接続がタイムアウトしました。"))?
+    .context("Redisに接続できません。")?;
+
+    Ok(())
+}
+
 /// データベースコネクションプールを構築する。
 ///
 /// # Arguments
@@ -104,3 +474,71 @@ pub fn get_connection_pool(settings: &DatabaseSettings) -> PgPool {
     tracing::info!("Connect to database...");
     PgPoolOptions::new().connect_lazy_with(settings.with_db())
 }
+
+/// TLS設定から、rustlsのサーバー設定を構築する。
+///
+/// # Arguments
+///
+/// * `settings` - TLS設定。
+///
+/// # Returns
+///
+/// rustlsのサーバー設定インスタンス。
+pub fn load_rustls_config(settings: &TlsSettings) -> anyhow::Result<rustls::ServerConfig> {
+    // 証明書（チェーン）を読み込み
+    let cert_file = File::open(&settings.cert_path).with_context(|| {
+        format!(
+            "TLS証明書ファイル({})を開けませんでした。",
+            settings.cert_path
+        )
+    })?;
+    let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .with_context(|| {
+            format!(
+                "TLS証明書ファイル({})を読み込めませんでした。",
+                settings.cert_path
+            )
+        })?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(anyhow!(
+            "TLS証明書ファイル({})にPEM形式の証明書が含まれていません。",
+            settings.cert_path
+        ));
+    }
+    // 秘密鍵を読み込み
+    let key_file = File::open(&settings.key_path).with_context(|| {
+        format!(
+            "TLS秘密鍵ファイル({})を開けませんでした。",
+            settings.key_path
+        )
+    })?;
+    let key = rustls_pemfile::read_all(&mut BufReader::new(key_file))
+        .with_context(|| {
+            format!(
+                "TLS秘密鍵ファイル({})を読み込めませんでした。",
+                settings.key_path
+            )
+        })?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "TLS秘密鍵ファイル({})にPEM形式の秘密鍵が含まれていません。",
+                settings.key_path
+            )
+        })?;
+
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("TLS証明書と秘密鍵からサーバー設定を構築できませんでした。")
+}